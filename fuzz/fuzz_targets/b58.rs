@@ -0,0 +1,15 @@
+#![no_main]
+use helium_crypto::PublicKey;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Ok(public_key) = s.parse::<PublicKey>() {
+            // Anything that parses has to survive a round trip
+            assert_eq!(
+                public_key,
+                public_key.to_string().parse().expect("public key")
+            );
+        }
+    }
+});
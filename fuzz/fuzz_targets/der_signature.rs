@@ -0,0 +1,23 @@
+#![no_main]
+use helium_crypto::{KeyTag, KeyType, Network, Signature};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for key_type in [KeyType::EccCompact, KeyType::Secp256k1] {
+        let tag = KeyTag {
+            network: Network::MainNet,
+            key_type,
+        };
+        if let Ok(signature) = Signature::from_der(tag, data) {
+            // The DER and raw forms have to convert into each other
+            assert_eq!(
+                signature,
+                Signature::from_raw(tag, &signature.to_raw()).expect("raw signature")
+            );
+            assert_eq!(
+                signature,
+                Signature::from_der(tag, &signature.to_der()).expect("der signature")
+            );
+        }
+    }
+});
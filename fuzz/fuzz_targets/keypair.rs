@@ -0,0 +1,12 @@
+#![no_main]
+use helium_crypto::Keypair;
+use libfuzzer_sys::fuzz_target;
+use std::convert::TryFrom;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(keypair) = Keypair::try_from(data) {
+        // Anything that parses has to survive a round trip
+        let bytes = keypair.to_vec();
+        assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+    }
+});
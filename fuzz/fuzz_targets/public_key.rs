@@ -0,0 +1,15 @@
+#![no_main]
+use helium_crypto::PublicKey;
+use libfuzzer_sys::fuzz_target;
+use std::convert::TryFrom;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(public_key) = PublicKey::try_from(data) {
+        // Anything that parses has to survive a round trip
+        let bytes = public_key.to_vec();
+        assert_eq!(
+            public_key,
+            PublicKey::try_from(&bytes[..]).expect("public key")
+        );
+    }
+});
@@ -0,0 +1,533 @@
+//! [age](https://age-encryption.org/v1) encryption to ed25519 keys.
+//!
+//! The ed25519 key is converted to its X25519 form, as for
+//! [sealed boxes](crate::sealed_box), and used as a standard age X25519
+//! recipient. A [`Recipient`] displays as the usual `age1...` string, so a
+//! backup can be encrypted to the public key of a hotspot with the `age`
+//! tool, and files produced by [`encrypt_to`] can be decrypted by the `age`
+//! tool with the converted identity and the other way around.
+//!
+//! Files are in the binary age format, without ASCII armor. Stanzas of other
+//! recipient types are skipped when decrypting.
+use crate::{ed25519, entropy::EntropySource, error, Keypair, PublicKey};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use curve25519_dalek::{constants::X25519_BASEPOINT, montgomery::MontgomeryPoint, scalar::Scalar};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use std::{convert::TryFrom, fmt, str::FromStr};
+use zeroize::Zeroize;
+
+const VERSION_LINE: &str = "age-encryption.org/v1";
+const X25519_LABEL: &[u8] = b"age-encryption.org/v1/X25519";
+const RECIPIENT_HRP: &str = "age";
+const KEY_LENGTH: usize = 32;
+const FILE_KEY_LENGTH: usize = 16;
+const PAYLOAD_NONCE_LENGTH: usize = 16;
+const TAG_LENGTH: usize = 16;
+const CHUNK_LENGTH: usize = 64 * 1024;
+const LINE_LENGTH: usize = 64;
+
+/// An age X25519 recipient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recipient([u8; KEY_LENGTH]);
+
+/// An age X25519 identity, the secret for a [`Recipient`].
+pub struct Identity([u8; KEY_LENGTH]);
+
+impl Drop for Identity {
+    fn drop(&mut self) {
+        self.0[..].zeroize();
+    }
+}
+
+impl Recipient {
+    /// The X25519 public key of the recipient.
+    pub fn as_bytes(&self) -> &[u8; KEY_LENGTH] {
+        &self.0
+    }
+}
+
+impl TryFrom<&PublicKey> for Recipient {
+    type Error = error::Error;
+
+    /// The recipient for an ed25519 public key. Other key types can't be age
+    /// recipients.
+    fn try_from(public_key: &PublicKey) -> error::Result<Self> {
+        Ok(Self(ed25519::PublicKey::try_from(public_key)?.to_x25519()))
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&bech32::encode(RECIPIENT_HRP, &self.0))
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> error::Result<Self> {
+        let data = bech32::decode(RECIPIENT_HRP, s).ok_or_else(error::invalid_age)?;
+        let key = <[u8; KEY_LENGTH]>::try_from(&data[..]).map_err(|_| error::invalid_age())?;
+        Ok(Self(key))
+    }
+}
+
+impl Identity {
+    /// The recipient files for this identity are encrypted to.
+    pub fn recipient(&self) -> Recipient {
+        Recipient((X25519_BASEPOINT * Scalar::from_bits(self.0)).to_bytes())
+    }
+
+    fn unwrap_file_key(&self, share: &[u8], body: &[u8]) -> Option<[u8; FILE_KEY_LENGTH]> {
+        let share = <[u8; KEY_LENGTH]>::try_from(share).ok()?;
+        let key = wrap_key(
+            &Scalar::from_bits(self.0),
+            &share,
+            &share,
+            &self.recipient().0,
+        )?;
+        let mut file_key = key.decrypt(&Nonce::default(), body).ok()?;
+        let result = <[u8; FILE_KEY_LENGTH]>::try_from(&file_key[..]).ok();
+        file_key.zeroize();
+        result
+    }
+}
+
+impl From<&ed25519::Keypair> for Identity {
+    fn from(keypair: &ed25519::Keypair) -> Self {
+        Self(keypair.to_x25519_secret())
+    }
+}
+
+impl TryFrom<&Keypair> for Identity {
+    type Error = error::Error;
+
+    /// The identity for an ed25519 keypair. Other key types can't be age
+    /// identities.
+    fn try_from(keypair: &Keypair) -> error::Result<Self> {
+        match keypair {
+            Keypair::Ed25519(keypair) => Ok(Self::from(keypair)),
+            _ => Err(error::unsupported_keytype(keypair.key_tag().key_type)),
+        }
+    }
+}
+
+/// Encrypt the given message to one or more recipients. The file key and the
+/// ephemeral keys are drawn from the given entropy source.
+pub fn encrypt_to<E>(
+    recipients: &[Recipient],
+    msg: &[u8],
+    entropy: &mut E,
+) -> error::Result<Vec<u8>>
+where
+    E: EntropySource + ?Sized,
+{
+    if recipients.is_empty() {
+        return Err(error::invalid_age());
+    }
+    let mut file_key = [0u8; FILE_KEY_LENGTH];
+    entropy.fill_entropy(&mut file_key)?;
+
+    let mut header = format!("{}\n", VERSION_LINE);
+    for recipient in recipients {
+        let mut secret = [0u8; KEY_LENGTH];
+        entropy.fill_entropy(&mut secret)?;
+        let ephemeral = Scalar::from_bits(clamp(secret));
+        secret.zeroize();
+        let share = (X25519_BASEPOINT * ephemeral).to_bytes();
+        let body = wrap_key(&ephemeral, &recipient.0, &share, &recipient.0)
+            .ok_or_else(error::invalid_age)?
+            .encrypt(&Nonce::default(), &file_key[..])
+            .map_err(|_| error::invalid_age())?;
+        header.push_str(&format!("-> X25519 {}\n", encode(&share)));
+        push_body(&mut header, &encode(&body));
+    }
+    header.push_str("---");
+    let mac = header_mac(&file_key, header.as_bytes())
+        .finalize()
+        .into_bytes();
+    header.push_str(&format!(" {}\n", encode(&mac)));
+
+    let mut nonce = [0u8; PAYLOAD_NONCE_LENGTH];
+    entropy.fill_entropy(&mut nonce)?;
+    let payload = payload_cipher(&file_key, &nonce);
+    file_key.zeroize();
+
+    // An empty message is encrypted as a single empty chunk
+    let chunks: Vec<&[u8]> = if msg.is_empty() {
+        vec![msg]
+    } else {
+        msg.chunks(CHUNK_LENGTH).collect()
+    };
+    let mut result = header.into_bytes();
+    result.reserve(PAYLOAD_NONCE_LENGTH + msg.len() + chunks.len() * TAG_LENGTH);
+    result.extend_from_slice(&nonce);
+    for (counter, chunk) in chunks.iter().enumerate() {
+        let ciphertext = payload
+            .encrypt(&chunk_nonce(counter, counter + 1 == chunks.len()), *chunk)
+            .map_err(|_| error::invalid_age())?;
+        result.extend_from_slice(&ciphertext);
+    }
+    Ok(result)
+}
+
+/// Decrypt an age file with the given identity. Fails with `DecodeError::Age`
+/// if the file is malformed, not encrypted to the identity or has been
+/// tampered with.
+pub fn decrypt(identity: &Identity, file: &[u8]) -> error::Result<Vec<u8>> {
+    let header = Header::parse(file).ok_or_else(error::invalid_age)?;
+    let mut file_key = header
+        .stanzas
+        .iter()
+        .find_map(|stanza| match &stanza.args[..] {
+            [kind, share] if kind == "X25519" => {
+                identity.unwrap_file_key(&decode(share)?, &stanza.body)
+            }
+            _ => None,
+        })
+        .ok_or_else(error::invalid_age)?;
+    let verified = header_mac(&file_key, &file[..header.mac_offset])
+        .verify(&header.mac)
+        .is_ok();
+    let payload = &file[header.len..];
+    if !verified || payload.len() < PAYLOAD_NONCE_LENGTH {
+        file_key.zeroize();
+        return Err(error::invalid_age());
+    }
+    let (nonce, mut payload) = payload.split_at(PAYLOAD_NONCE_LENGTH);
+    let cipher = payload_cipher(&file_key, nonce);
+    file_key.zeroize();
+
+    let mut result = Vec::with_capacity(payload.len());
+    let mut counter = 0;
+    loop {
+        let last = payload.len() <= CHUNK_LENGTH + TAG_LENGTH;
+        let (chunk, rest) = payload.split_at(payload.len().min(CHUNK_LENGTH + TAG_LENGTH));
+        // Only the payload of an empty message may end in an empty chunk
+        if last && counter > 0 && chunk.len() == TAG_LENGTH {
+            return Err(error::invalid_age());
+        }
+        let plaintext = cipher
+            .decrypt(&chunk_nonce(counter, last), chunk)
+            .map_err(|_| error::invalid_age())?;
+        result.extend_from_slice(&plaintext);
+        if last {
+            return Ok(result);
+        }
+        payload = rest;
+        counter += 1;
+    }
+}
+
+struct Stanza {
+    args: Vec<String>,
+    body: Vec<u8>,
+}
+
+struct Header {
+    stanzas: Vec<Stanza>,
+    mac: Vec<u8>,
+    /// The offset of the end of the `---` marker the MAC covers the header
+    /// up to.
+    mac_offset: usize,
+    /// The length of the header including its final newline.
+    len: usize,
+}
+
+impl Header {
+    fn parse(file: &[u8]) -> Option<Self> {
+        let mut offset = 0;
+        let mut next_line = || {
+            let end = file[offset..].iter().position(|b| *b == b'\n')?;
+            let line = std::str::from_utf8(&file[offset..offset + end]).ok()?;
+            offset += end + 1;
+            Some((line, offset))
+        };
+        if next_line()?.0 != VERSION_LINE {
+            return None;
+        }
+        let mut stanzas = vec![];
+        loop {
+            let (line, end) = next_line()?;
+            if let Some(mac) = line.strip_prefix("--- ") {
+                return Some(Self {
+                    stanzas,
+                    mac: decode(mac)?,
+                    mac_offset: end - 1 - mac.len() - 1,
+                    len: end,
+                });
+            }
+            let args: Vec<String> = line
+                .strip_prefix("-> ")?
+                .split(' ')
+                .map(String::from)
+                .collect();
+            let mut body = String::new();
+            loop {
+                let (line, _) = next_line()?;
+                body.push_str(line);
+                if line.len() < LINE_LENGTH {
+                    break;
+                }
+            }
+            stanzas.push(Stanza {
+                args,
+                body: decode(&body)?,
+            });
+        }
+    }
+}
+
+fn clamp(mut secret: [u8; KEY_LENGTH]) -> [u8; KEY_LENGTH] {
+    secret[0] &= 248;
+    secret[31] &= 127;
+    secret[31] |= 64;
+    secret
+}
+
+/// The key wrapping the file key for a recipient. The secret is either the
+/// ephemeral secret with the recipient as the peer, when encrypting, or the
+/// identity with the share as the peer, when decrypting. Returns `None` if
+/// the peer is a low order point.
+fn wrap_key(
+    secret: &Scalar,
+    peer: &[u8; KEY_LENGTH],
+    share: &[u8; KEY_LENGTH],
+    recipient: &[u8; KEY_LENGTH],
+) -> Option<ChaCha20Poly1305> {
+    let mut shared = (MontgomeryPoint(*peer) * secret).to_bytes();
+    if shared == [0u8; KEY_LENGTH] {
+        return None;
+    }
+    let mut salt = [0u8; 2 * KEY_LENGTH];
+    salt[..KEY_LENGTH].copy_from_slice(share);
+    salt[KEY_LENGTH..].copy_from_slice(recipient);
+    let mut key = [0u8; KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(&salt), &shared)
+        .expand(X25519_LABEL, &mut key)
+        .expect("hkdf output length");
+    shared.zeroize();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    key.zeroize();
+    Some(cipher)
+}
+
+fn header_mac(file_key: &[u8; FILE_KEY_LENGTH], header: &[u8]) -> Hmac<Sha256> {
+    let mut key = [0u8; KEY_LENGTH];
+    Hkdf::<Sha256>::new(None, file_key)
+        .expand(b"header", &mut key)
+        .expect("hkdf output length");
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).expect("hmac key length");
+    key.zeroize();
+    mac.update(header);
+    mac
+}
+
+fn payload_cipher(file_key: &[u8; FILE_KEY_LENGTH], nonce: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = [0u8; KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(nonce), file_key)
+        .expand(b"payload", &mut key)
+        .expect("hkdf output length");
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    key.zeroize();
+    cipher
+}
+
+/// The STREAM nonce of a chunk, an 11 byte big endian counter followed by
+/// the last chunk flag.
+fn chunk_nonce(counter: usize, last: bool) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[3..11].copy_from_slice(&(counter as u64).to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+fn encode(data: &[u8]) -> String {
+    base64::encode_config(data, base64::STANDARD_NO_PAD)
+}
+
+fn decode(data: &str) -> Option<Vec<u8>> {
+    let decoded = base64::decode_config(data, base64::STANDARD_NO_PAD).ok()?;
+    // Only the canonical encoding is accepted
+    if encode(&decoded) == data {
+        Some(decoded)
+    } else {
+        None
+    }
+}
+
+/// Appends a stanza body wrapped at 64 columns. The last line is always
+/// shorter than 64 columns, and empty if the body fills its lines exactly.
+fn push_body(header: &mut String, body: &str) {
+    for line in body.as_bytes().chunks(LINE_LENGTH) {
+        header.push_str(std::str::from_utf8(line).expect("base64"));
+        header.push('\n');
+    }
+    if body.len() % LINE_LENGTH == 0 {
+        header.push('\n');
+    }
+}
+
+/// Bech32 (BIP 173) encoding of lowercase age recipients.
+mod bech32 {
+    const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    const CHECKSUM_LENGTH: usize = 6;
+
+    fn polymod(values: &[u8]) -> u32 {
+        values.iter().fold(1, |check, value| {
+            let top = check >> 25;
+            let check = ((check & 0x01ff_ffff) << 5) ^ u32::from(*value);
+            GENERATOR
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| (top >> i) & 1 == 1)
+                .fold(check, |check, (_, generator)| check ^ generator)
+        })
+    }
+
+    fn hrp_expand(hrp: &str) -> Vec<u8> {
+        let mut result: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+        result.push(0);
+        result.extend(hrp.bytes().map(|b| b & 31));
+        result
+    }
+
+    pub(super) fn encode(hrp: &str, data: &[u8]) -> String {
+        let mut values = Vec::with_capacity((data.len() * 8 + 4) / 5 + CHECKSUM_LENGTH);
+        let (mut acc, mut bits) = (0u32, 0);
+        for byte in data {
+            acc = (acc << 8) | u32::from(*byte);
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                values.push(((acc >> bits) & 31) as u8);
+            }
+        }
+        if bits > 0 {
+            values.push(((acc << (5 - bits)) & 31) as u8);
+        }
+        let mut checked = hrp_expand(hrp);
+        checked.extend_from_slice(&values);
+        checked.extend_from_slice(&[0; CHECKSUM_LENGTH]);
+        let checksum = polymod(&checked) ^ 1;
+        values.extend((0..CHECKSUM_LENGTH).map(|i| ((checksum >> (5 * (5 - i))) & 31) as u8));
+
+        let mut result = String::with_capacity(hrp.len() + 1 + values.len());
+        result.push_str(hrp);
+        result.push('1');
+        result.extend(values.iter().map(|v| CHARSET[*v as usize] as char));
+        result
+    }
+
+    /// Decodes a string with the given human readable part. Mixed case and
+    /// non zero padding are rejected.
+    pub(super) fn decode(hrp: &str, s: &str) -> Option<Vec<u8>> {
+        if s.bytes().any(|b| b.is_ascii_uppercase()) && s.bytes().any(|b| b.is_ascii_lowercase()) {
+            return None;
+        }
+        let s = s.to_ascii_lowercase();
+        let data = s.strip_prefix(hrp)?.strip_prefix('1')?;
+        if data.len() < CHECKSUM_LENGTH {
+            return None;
+        }
+        let values = data
+            .bytes()
+            .map(|b| CHARSET.iter().position(|c| *c == b).map(|v| v as u8))
+            .collect::<Option<Vec<u8>>>()?;
+        let mut checked = hrp_expand(hrp);
+        checked.extend_from_slice(&values);
+        if polymod(&checked) != 1 {
+            return None;
+        }
+
+        let values = &values[..values.len() - CHECKSUM_LENGTH];
+        let mut result = Vec::with_capacity(values.len() * 5 / 8);
+        let (mut acc, mut bits) = (0u32, 0);
+        for value in values {
+            acc = ((acc << 5) | u32::from(*value)) & 0xfff;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                result.push((acc >> bits) as u8);
+            }
+        }
+        if bits >= 5 || (acc << (8 - bits)) & 0xff != 0 {
+            return None;
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, encrypt_to, Identity, Recipient, CHUNK_LENGTH};
+    use crate::{ecc_compact, ed25519, Keypair, Network};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn recipient_string() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let recipient = Recipient::try_from(&keypair.public_key).expect("recipient");
+        let encoded = recipient.to_string();
+        assert!(encoded.starts_with("age1"));
+        assert_eq!(62, encoded.len());
+        assert_eq!(recipient, encoded.parse().expect("recipient"));
+        assert_eq!(
+            recipient,
+            encoded.to_uppercase().parse().expect("recipient")
+        );
+        assert_eq!(recipient, Identity::from(&keypair).recipient());
+
+        let mut corrupted = encoded.into_bytes();
+        corrupted[10] = if corrupted[10] == b'q' { b'p' } else { b'q' };
+        assert!(String::from_utf8(corrupted)
+            .expect("string")
+            .parse::<Recipient>()
+            .is_err());
+
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        assert!(Recipient::try_from(&ecc.public_key).is_err());
+        assert!(Identity::try_from(&Keypair::EccCompact(ecc)).is_err());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let alice = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let bob = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let recipients = [
+            Recipient::try_from(&alice.public_key).expect("alice"),
+            Recipient::try_from(&bob.public_key).expect("bob"),
+        ];
+        for msg in [vec![], b"hello world".to_vec(), vec![7u8; 2 * CHUNK_LENGTH]] {
+            let file = encrypt_to(&recipients, &msg, &mut OsRng).expect("encrypt");
+            for keypair in [&alice, &bob] {
+                assert_eq!(
+                    msg,
+                    decrypt(&Identity::from(keypair), &file).expect("decrypt")
+                );
+            }
+            let mut tampered = file.clone();
+            let last = tampered.len() - 1;
+            tampered[last] ^= 1;
+            assert!(decrypt(&Identity::from(&alice), &tampered).is_err());
+            assert!(decrypt(&Identity::from(&alice), &file[..file.len() - 1]).is_err());
+        }
+
+        let file = encrypt_to(&recipients[..1], b"hello world", &mut OsRng).expect("encrypt");
+        assert!(decrypt(&Identity::from(&bob), &file).is_err());
+        assert!(encrypt_to(&[], b"hello world", &mut OsRng).is_err());
+    }
+}
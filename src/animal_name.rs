@@ -0,0 +1,46 @@
+//! Three word animal names for public keys, the hotspot names shown by
+//! explorers and wallets, like `angry-purple-tiger`.
+//!
+//! Names are derived from the b58 address with the canonical [angry purple
+//! tiger](https://github.com/helium/angry-purple-tiger-rs) algorithm. Many keys
+//! share a name, so a name only identifies a key among a known set of keys.
+use crate::PublicKey;
+use angry_purple_tiger::AnimalName;
+
+impl PublicKey {
+    /// The animal name of this key, in its lowercase dash separated form.
+    pub fn animal_name(&self) -> String {
+        self.to_string()
+            .parse::<AnimalName>()
+            .expect("animal name")
+            .to_string()
+    }
+}
+
+/// Returns the keys with the given animal name. Names are matched ignoring
+/// case and with spaces treated as dashes, so `Angry Purple Tiger` matches.
+pub fn find<'a, I>(keys: I, name: &str) -> Vec<&'a PublicKey>
+where
+    I: IntoIterator<Item = &'a PublicKey>,
+{
+    let name = name.trim().to_lowercase().replace(' ', "-");
+    keys.into_iter()
+        .filter(|key| key.animal_name() == name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find;
+    use crate::PublicKey;
+
+    #[test]
+    fn animal_name() {
+        let key: PublicKey = "112CuoXo7WCcp6GGwDNBo6H5nKXGH45UNJ39iEefdv2mwmnwdFt8"
+            .parse()
+            .expect("public key");
+        assert_eq!("feisty-glass-dalmatian", key.animal_name());
+        assert_eq!(vec![&key], find(&[key.clone()], "Feisty Glass Dalmatian"));
+        assert!(find(&[key.clone()], "angry-purple-tiger").is_empty());
+    }
+}
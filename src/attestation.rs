@@ -0,0 +1,495 @@
+//! Attestation statements proving that a public key was generated inside a
+//! hardware backend, for onboarding servers that need to tell genuine hardware
+//! keys from software ones.
+//!
+//! A statement is signed by an attestation key of the device, which the
+//! verifier has to trust already, for example from enrollment at
+//! manufacturing. Every statement covers a challenge from the verifier so it
+//! can't be replayed. How the attestation key itself is vouched for, by
+//! Microchip certificates or a TPM endorsement key, is out of scope here.
+//!
+//! ATECC608 statements come from generating a public key digest of the key
+//! slot into TempKey and signing it, together with the slot configuration and
+//! the serial number of the chip, with an internal sign by the attestation
+//! slot. The chip only sets the flags an internal sign covers when the key
+//! was generated on the chip.
+//!
+//! TPM statements are `TPM2_Certify` results for the key, by an attestation
+//! key in the same TPM. The certified public area has to be that of a fixed,
+//! TPM generated signing key.
+use crate::{ecc_compact, error, public_key, Network, PublicKey};
+use p256::ecdsa;
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+
+/// Length of the raw `r || s` signature of a statement.
+pub const SIGNATURE_LENGTH: usize = 64;
+/// Length of the ATECC608 configuration zone.
+pub const ECC608_CONFIG_LENGTH: usize = 128;
+/// Length of the raw x and y coordinates of an ATECC608 public key.
+pub const ECC608_POINT_LENGTH: usize = 64;
+
+const FORMAT_ECC608: u8 = 0x01;
+const FORMAT_TPM: u8 = 0x02;
+
+const ECC608_OP_GENKEY: u8 = 0x40;
+const ECC608_OP_SIGN: u8 = 0x41;
+/// GenKey mode that digests the public key of a slot into TempKey.
+pub(crate) const ECC608_GENKEY_DIGEST: u8 = 0x08;
+/// Sign mode that signs an internally generated message including the full
+/// serial number.
+pub(crate) const ECC608_SIGN_INTERNAL: u8 = 0x40;
+// TempKey flags after a GenKey digest over a passthrough nonce
+const ECC608_TEMPKEY_INPUT: u8 = 0x10;
+const ECC608_TEMPKEY_GENKEY: u8 = 0x40;
+// Configuration zone offsets
+const ECC608_SLOT_CONFIG: usize = 20;
+const ECC608_SLOT_LOCKED: usize = 88;
+const ECC608_KEY_CONFIG: usize = 96;
+// KeyConfig bits
+const ECC608_KEY_PRIVATE: u16 = 0x0001;
+const ECC608_KEY_PUB_INFO: u16 = 0x0002;
+
+const TPM_GENERATED_VALUE: u32 = 0xff54_4347;
+const TPM_ST_ATTEST_CERTIFY: u16 = 0x8017;
+const TPM_ALG_ECC: u16 = 0x0023;
+const TPM_ALG_SHA256: u16 = 0x000b;
+const TPM_ALG_NULL: u16 = 0x0010;
+const TPM_ECC_NIST_P256: u16 = 0x0003;
+// Object attributes a TPM generated, non-exportable signing key has
+const TPMA_FIXED_TPM: u32 = 1 << 1;
+const TPMA_FIXED_PARENT: u32 = 1 << 4;
+const TPMA_SENSITIVE_DATA_ORIGIN: u32 = 1 << 5;
+const TPMA_SIGN: u32 = 1 << 18;
+const TPM_KEY_ATTRIBUTES: u32 =
+    TPMA_FIXED_TPM | TPMA_FIXED_PARENT | TPMA_SENSITIVE_DATA_ORIGIN | TPMA_SIGN;
+/// Length of the clock info and firmware version in a `TPMS_ATTEST`.
+const TPM_CLOCK_FIRMWARE_LENGTH: usize = 17 + 8;
+
+/// A statement that a public key was generated in a hardware backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attestation {
+    Ecc608 {
+        /// The configuration zone of the chip.
+        config: [u8; ECC608_CONFIG_LENGTH],
+        /// The slot holding the attested key.
+        key_slot: u8,
+        /// The slot holding the attestation key.
+        attestation_slot: u8,
+        /// The raw x and y coordinates of the attested key.
+        point: [u8; ECC608_POINT_LENGTH],
+        signature: [u8; SIGNATURE_LENGTH],
+    },
+    Tpm {
+        /// The marshalled `TPMT_PUBLIC` of the attested key.
+        public: Vec<u8>,
+        /// The marshalled `TPMS_ATTEST` the attestation key signed.
+        attest: Vec<u8>,
+        signature: [u8; SIGNATURE_LENGTH],
+    },
+}
+
+impl Attestation {
+    /// Verify the statement against the given attestation key and the
+    /// challenge it was made for, and return the attested public key for the
+    /// given network.
+    pub fn verify(
+        &self,
+        network: Network,
+        attestation_key: &p256::PublicKey,
+        challenge: &[u8],
+    ) -> error::Result<PublicKey> {
+        use signature::Verifier;
+        let (message, signature, public_key) = match self {
+            Self::Ecc608 {
+                config,
+                key_slot,
+                attestation_slot,
+                point,
+                signature,
+            } => {
+                let key_config = ecc608_slot_word(config, ECC608_KEY_CONFIG, *key_slot)?;
+                if key_config & ECC608_KEY_PRIVATE == 0 {
+                    return Err(error::invalid_attestation());
+                }
+                let message = ecc608_sign_message(
+                    config,
+                    *key_slot,
+                    *attestation_slot,
+                    &ecc608_key_digest(config, *key_slot, point, challenge),
+                )?;
+                let mut sec1 = [0u8; ecc_compact::SEC1_UNCOMPRESSED_LENGTH];
+                sec1[0] = 0x04;
+                sec1[1..].copy_from_slice(point);
+                let public_key = ecc_compact::PublicKey::from_sec1_point(&sec1)?;
+                (message, signature, public_key)
+            }
+            Self::Tpm {
+                public,
+                attest,
+                signature,
+            } => {
+                let public_key = tpm_public_key(public)?;
+                let mut name = TPM_ALG_SHA256.to_be_bytes().to_vec();
+                name.extend_from_slice(&Sha256::digest(public));
+                tpm_check_attest(attest, &name, &Sha256::digest(challenge))?;
+                (attest.clone(), signature, public_key)
+            }
+        };
+        let signature = ecdsa::Signature::try_from(&signature[..])?;
+        ecdsa::VerifyingKey::from(*attestation_key).verify(&message, &signature)?;
+        Ok(public_key::PublicKey::for_network(network, public_key))
+    }
+
+    /// The binary form of the statement.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![];
+        match self {
+            Self::Ecc608 {
+                config,
+                key_slot,
+                attestation_slot,
+                point,
+                signature,
+            } => {
+                result.push(FORMAT_ECC608);
+                result.extend_from_slice(config);
+                result.push(*key_slot);
+                result.push(*attestation_slot);
+                result.extend_from_slice(point);
+                result.extend_from_slice(signature);
+            }
+            Self::Tpm {
+                public,
+                attest,
+                signature,
+            } => {
+                result.push(FORMAT_TPM);
+                for part in &[public, attest] {
+                    result.extend_from_slice(&(part.len() as u16).to_be_bytes());
+                    result.extend_from_slice(part);
+                }
+                result.extend_from_slice(signature);
+            }
+        }
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for Attestation {
+    type Error = error::Error;
+
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        let mut reader = Reader(input);
+        let result = match reader.u8()? {
+            FORMAT_ECC608 => Self::Ecc608 {
+                config: reader.array()?,
+                key_slot: reader.u8()?,
+                attestation_slot: reader.u8()?,
+                point: reader.array()?,
+                signature: reader.array()?,
+            },
+            FORMAT_TPM => Self::Tpm {
+                public: reader.sized()?.to_vec(),
+                attest: reader.sized()?.to_vec(),
+                signature: reader.array()?,
+            },
+            _ => return Err(error::invalid_attestation()),
+        };
+        if !reader.0.is_empty() {
+            return Err(error::invalid_attestation());
+        }
+        Ok(result)
+    }
+}
+
+/// The 9 byte serial number of an ATECC608, spread over the configuration
+/// zone.
+fn ecc608_serial(config: &[u8; ECC608_CONFIG_LENGTH]) -> [u8; 9] {
+    let mut result = [0u8; 9];
+    result[..4].copy_from_slice(&config[..4]);
+    result[4..].copy_from_slice(&config[8..13]);
+    result
+}
+
+/// The little endian configuration word for a slot in the table at the given
+/// offset.
+fn ecc608_slot_word(
+    config: &[u8; ECC608_CONFIG_LENGTH],
+    offset: usize,
+    slot: u8,
+) -> error::Result<u16> {
+    if slot > 15 {
+        return Err(error::invalid_attestation());
+    }
+    let offset = offset + 2 * slot as usize;
+    Ok(u16::from_le_bytes([config[offset], config[offset + 1]]))
+}
+
+/// The TempKey value after loading the digest of the challenge as a
+/// passthrough nonce and generating the public key digest of the key slot.
+pub(crate) fn ecc608_key_digest(
+    config: &[u8; ECC608_CONFIG_LENGTH],
+    key_slot: u8,
+    point: &[u8; ECC608_POINT_LENGTH],
+    challenge: &[u8],
+) -> [u8; 32] {
+    let serial = ecc608_serial(config);
+    let mut hasher = Sha256::new();
+    hasher.update(Sha256::digest(challenge));
+    hasher.update([ECC608_OP_GENKEY, ECC608_GENKEY_DIGEST]);
+    hasher.update((key_slot as u16).to_le_bytes());
+    hasher.update([serial[8]]);
+    hasher.update(&serial[..2]);
+    hasher.update([0u8; 25]);
+    hasher.update(point);
+    hasher.finalize().into()
+}
+
+/// The message an internal sign by the attestation slot signs, with TempKey
+/// holding the public key digest of the key slot.
+fn ecc608_sign_message(
+    config: &[u8; ECC608_CONFIG_LENGTH],
+    key_slot: u8,
+    attestation_slot: u8,
+    tempkey: &[u8; 32],
+) -> error::Result<Vec<u8>> {
+    let serial = ecc608_serial(config);
+    let slot_config = ecc608_slot_word(config, ECC608_SLOT_CONFIG, key_slot)?;
+    let key_config = ecc608_slot_word(config, ECC608_KEY_CONFIG, key_slot)?;
+    let slot_locked =
+        u16::from_le_bytes([config[ECC608_SLOT_LOCKED], config[ECC608_SLOT_LOCKED + 1]]);
+    let mut result = tempkey.to_vec();
+    result.extend_from_slice(&[ECC608_OP_SIGN, ECC608_SIGN_INTERNAL]);
+    result.extend_from_slice(&(attestation_slot as u16).to_le_bytes());
+    result.extend_from_slice(&slot_config.to_le_bytes());
+    result.extend_from_slice(&key_config.to_le_bytes());
+    result.push(key_slot | ECC608_TEMPKEY_INPUT | ECC608_TEMPKEY_GENKEY);
+    result.extend_from_slice(&[0, 0, serial[8]]);
+    result.extend_from_slice(&serial[4..8]);
+    result.extend_from_slice(&serial[..4]);
+    // Locked slots have their bit cleared
+    result.push((((slot_locked >> key_slot) & 1) ^ 1) as u8);
+    result.push(u8::from(key_config & ECC608_KEY_PUB_INFO != 0));
+    result.extend_from_slice(&[0, 0]);
+    Ok(result)
+}
+
+/// The public key in a marshalled `TPMT_PUBLIC`, which has to be a fixed, TPM
+/// generated P-256 signing key.
+fn tpm_public_key(public: &[u8]) -> error::Result<ecc_compact::PublicKey> {
+    let mut reader = Reader(public);
+    if reader.u16()? != TPM_ALG_ECC
+        || reader.u16()? != TPM_ALG_SHA256
+        || reader.u32()? & TPM_KEY_ATTRIBUTES != TPM_KEY_ATTRIBUTES
+    {
+        return Err(error::invalid_attestation());
+    }
+    // Auth policy
+    reader.sized()?;
+    // Symmetric definition, only null for signing keys
+    if reader.u16()? != TPM_ALG_NULL {
+        return Err(error::invalid_attestation());
+    }
+    // Signing scheme with its hash algorithm unless null
+    if reader.u16()? != TPM_ALG_NULL {
+        reader.u16()?;
+    }
+    if reader.u16()? != TPM_ECC_NIST_P256 {
+        return Err(error::invalid_attestation());
+    }
+    // Key derivation scheme
+    if reader.u16()? != TPM_ALG_NULL {
+        reader.u16()?;
+    }
+    let mut sec1 = [0u8; ecc_compact::SEC1_UNCOMPRESSED_LENGTH];
+    sec1[0] = 0x04;
+    for coordinate in sec1[1..].chunks_mut(32) {
+        // The TPM strips leading zeros
+        let value = reader.sized()?;
+        if value.len() > coordinate.len() {
+            return Err(error::invalid_attestation());
+        }
+        let start = coordinate.len() - value.len();
+        coordinate[start..].copy_from_slice(value);
+    }
+    if !reader.0.is_empty() {
+        return Err(error::invalid_attestation());
+    }
+    ecc_compact::PublicKey::from_sec1_point(&sec1)
+}
+
+/// Check that a marshalled `TPMS_ATTEST` certifies the object with the given
+/// name for the given qualifying data.
+fn tpm_check_attest(attest: &[u8], name: &[u8], qualifying_data: &[u8]) -> error::Result {
+    let mut reader = Reader(attest);
+    if reader.u32()? != TPM_GENERATED_VALUE || reader.u16()? != TPM_ST_ATTEST_CERTIFY {
+        return Err(error::invalid_attestation());
+    }
+    // Qualified name of the attestation key
+    reader.sized()?;
+    if reader.sized()? != qualifying_data {
+        return Err(error::invalid_attestation());
+    }
+    reader.take(TPM_CLOCK_FIRMWARE_LENGTH)?;
+    if reader.sized()? != name {
+        return Err(error::invalid_attestation());
+    }
+    // Qualified name of the certified object
+    reader.sized()?;
+    if !reader.0.is_empty() {
+        return Err(error::invalid_attestation());
+    }
+    Ok(())
+}
+
+/// Reads the big endian, length prefixed fields of statements.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> error::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(error::invalid_attestation());
+        }
+        let (result, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(result)
+    }
+
+    fn array<const N: usize>(&mut self) -> error::Result<[u8; N]> {
+        let mut result = [0u8; N];
+        result.copy_from_slice(self.take(N)?);
+        Ok(result)
+    }
+
+    fn u8(&mut self) -> error::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> error::Result<u16> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> error::Result<u32> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    /// A field with a two byte length prefix, like a TPM2B.
+    fn sized(&mut self) -> error::Result<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Network;
+    use rand::rngs::OsRng;
+
+    fn sign(key: &p256::SecretKey, message: &[u8]) -> [u8; SIGNATURE_LENGTH] {
+        use signature::Signer;
+        let signature: ecdsa::Signature = ecdsa::SigningKey::from(key.clone()).sign(message);
+        let mut result = [0u8; SIGNATURE_LENGTH];
+        result.copy_from_slice(signature.as_ref());
+        result
+    }
+
+    fn sized(output: &mut Vec<u8>, value: &[u8]) {
+        output.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        output.extend_from_slice(value);
+    }
+
+    #[test]
+    fn ecc608() {
+        let attestation_key = p256::SecretKey::random(&mut OsRng);
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let mut point = [0u8; ECC608_POINT_LENGTH];
+        point.copy_from_slice(
+            &ecc_compact::PublicKey::try_from(&keypair.public_key)
+                .expect("ecc_compact")
+                .to_sec1_uncompressed()[1..],
+        );
+        let mut config = [0u8; ECC608_CONFIG_LENGTH];
+        config[..4].copy_from_slice(&[0x01, 0x23, 0x45, 0x67]);
+        config[ECC608_KEY_CONFIG] = ECC608_KEY_PRIVATE as u8;
+        let digest = ecc608_key_digest(&config, 0, &point, b"challenge");
+        let message = ecc608_sign_message(&config, 0, 1, &digest).expect("message");
+        let attestation = Attestation::Ecc608 {
+            config,
+            key_slot: 0,
+            attestation_slot: 1,
+            point,
+            signature: sign(&attestation_key, &message),
+        };
+        let attestation =
+            Attestation::try_from(&attestation.to_vec()[..]).expect("decoded attestation");
+        let verifying_key = attestation_key.public_key();
+        assert_eq!(
+            keypair.public_key,
+            attestation
+                .verify(Network::MainNet, &verifying_key, b"challenge")
+                .expect("attested key")
+        );
+        assert!(attestation
+            .verify(Network::MainNet, &verifying_key, b"other")
+            .is_err());
+        let other = p256::SecretKey::random(&mut OsRng).public_key();
+        assert!(attestation
+            .verify(Network::MainNet, &other, b"challenge")
+            .is_err());
+    }
+
+    #[test]
+    fn tpm() {
+        let attestation_key = p256::SecretKey::random(&mut OsRng);
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let point = ecc_compact::PublicKey::try_from(&keypair.public_key)
+            .expect("ecc_compact")
+            .to_sec1_uncompressed();
+
+        let mut public = vec![];
+        public.extend_from_slice(&TPM_ALG_ECC.to_be_bytes());
+        public.extend_from_slice(&TPM_ALG_SHA256.to_be_bytes());
+        public.extend_from_slice(&TPM_KEY_ATTRIBUTES.to_be_bytes());
+        sized(&mut public, &[]);
+        for alg in &[TPM_ALG_NULL, TPM_ALG_NULL, TPM_ECC_NIST_P256, TPM_ALG_NULL] {
+            public.extend_from_slice(&alg.to_be_bytes());
+        }
+        sized(&mut public, &point[1..33]);
+        sized(&mut public, &point[33..]);
+
+        let mut name = TPM_ALG_SHA256.to_be_bytes().to_vec();
+        name.extend_from_slice(&Sha256::digest(&public));
+        let mut attest = vec![];
+        attest.extend_from_slice(&TPM_GENERATED_VALUE.to_be_bytes());
+        attest.extend_from_slice(&TPM_ST_ATTEST_CERTIFY.to_be_bytes());
+        sized(&mut attest, &[0u8; 34]);
+        sized(&mut attest, &Sha256::digest(b"challenge"));
+        attest.extend_from_slice(&[0u8; TPM_CLOCK_FIRMWARE_LENGTH]);
+        sized(&mut attest, &name);
+        sized(&mut attest, &name);
+
+        let attestation = Attestation::Tpm {
+            signature: sign(&attestation_key, &attest),
+            public: public.clone(),
+            attest,
+        };
+        let attestation =
+            Attestation::try_from(&attestation.to_vec()[..]).expect("decoded attestation");
+        let verifying_key = attestation_key.public_key();
+        assert_eq!(
+            keypair.public_key,
+            attestation
+                .verify(Network::MainNet, &verifying_key, b"challenge")
+                .expect("attested key")
+        );
+        assert!(attestation
+            .verify(Network::MainNet, &verifying_key, b"other")
+            .is_err());
+
+        // Keys that can leave the TPM are not attested
+        public[7] &= !(TPMA_FIXED_TPM as u8);
+        assert!(tpm_public_key(&public).is_err());
+    }
+}
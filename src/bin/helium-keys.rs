@@ -0,0 +1,157 @@
+//! Command line tool for generating and inspecting Helium keys, signing files
+//! and verifying signatures.
+//!
+//! Keypair files are read and written in one of three formats: `raw`, the
+//! binary form of the keypair, `b58`, the base58 encoded binary form, or
+//! `pem`, an unencrypted PKCS#8 PEM block. Signatures are printed and read as
+//! base58.
+use clap::{Parser, Subcommand, ValueEnum};
+use helium_crypto::{KeyTag, Keypair, Network, PublicKey, Sign, Verify};
+use std::{convert::TryFrom, error::Error, fs, path::PathBuf};
+
+type Result<T = ()> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(name = "helium-keys", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a new keypair
+    Generate {
+        /// Key tag of the keypair, like mainnet/ed25519
+        #[arg(long, default_value = "mainnet/ed25519", value_parser = generatable_tag)]
+        tag: KeyTag,
+        /// Format of the keypair file
+        #[arg(long, value_enum, default_value_t = Format::Raw)]
+        format: Format,
+        /// File to write the keypair to
+        output: PathBuf,
+    },
+    /// Print the address of a keypair
+    Address {
+        #[command(flatten)]
+        keypair: KeypairFile,
+    },
+    /// Sign the contents of a file
+    Sign {
+        #[command(flatten)]
+        keypair: KeypairFile,
+        /// File to sign
+        file: PathBuf,
+    },
+    /// Verify a signature over the contents of a file
+    Verify {
+        /// Address of the signing key
+        address: PublicKey,
+        /// File that was signed
+        file: PathBuf,
+        /// Base58 encoded signature
+        signature: String,
+    },
+    /// Convert a keypair file to a different format
+    Convert {
+        #[command(flatten)]
+        keypair: KeypairFile,
+        /// Format to convert to
+        #[arg(long, value_enum)]
+        to: Format,
+        /// File to write the converted keypair to
+        output: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Raw,
+    B58,
+    Pem,
+}
+
+#[derive(clap::Args)]
+struct KeypairFile {
+    /// Keypair file
+    keypair: PathBuf,
+    /// Format of the keypair file
+    #[arg(long, value_enum, default_value_t = Format::Raw)]
+    format: Format,
+    /// Network for pem keypair files, which do not record it
+    #[arg(long, default_value = "mainnet")]
+    network: Network,
+}
+
+impl KeypairFile {
+    fn read(&self) -> Result<Keypair> {
+        let data = fs::read(&self.keypair)?;
+        let keypair = match self.format {
+            Format::Raw => Keypair::try_from(&data[..])?,
+            Format::B58 => {
+                let bytes = bs58::decode(std::str::from_utf8(&data)?.trim()).into_vec()?;
+                Keypair::try_from(&bytes[..])?
+            }
+            Format::Pem => Keypair::from_pkcs8_pem(self.network, std::str::from_utf8(&data)?)?,
+        };
+        Ok(keypair)
+    }
+}
+
+/// Parse a key tag, accepting only those a keypair can be generated for.
+fn generatable_tag(s: &str) -> std::result::Result<KeyTag, String> {
+    let tag = s.parse::<KeyTag>().map_err(|err| err.to_string())?;
+    if KeyTag::generatable().any(|generatable| generatable == tag) {
+        return Ok(tag);
+    }
+    let supported: Vec<String> = KeyTag::generatable().map(|tag| tag.to_string()).collect();
+    Err(format!(
+        "no keypairs for {}, expected one of: {}",
+        tag,
+        supported.join(", ")
+    ))
+}
+
+fn write_keypair(keypair: &Keypair, format: Format, output: &PathBuf) -> Result {
+    let data = match format {
+        Format::Raw => keypair.to_vec(),
+        Format::B58 => format!("{}\n", bs58::encode(keypair.to_vec()).into_string()).into_bytes(),
+        Format::Pem => keypair.to_pkcs8_pem().into_bytes(),
+    };
+    fs::write(output, data)?;
+    Ok(())
+}
+
+fn main() -> Result {
+    match Cli::parse().command {
+        Command::Generate {
+            tag,
+            format,
+            output,
+        } => {
+            let keypair = Keypair::generate_default(tag)?;
+            write_keypair(&keypair, format, &output)?;
+            println!("{}", keypair.public_key());
+        }
+        Command::Address { keypair } => println!("{}", keypair.read()?.public_key()),
+        Command::Sign { keypair, file } => {
+            let signature = keypair.read()?.sign(&fs::read(file)?)?;
+            println!("{}", bs58::encode(signature).into_string());
+        }
+        Command::Verify {
+            address,
+            file,
+            signature,
+        } => {
+            let signature = bs58::decode(signature.trim()).into_vec()?;
+            address.verify(&fs::read(file)?, &signature)?;
+            println!("ok");
+        }
+        Command::Convert {
+            keypair,
+            to,
+            output,
+        } => write_keypair(&keypair.read()?, to, &output)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,309 @@
+//! Deterministic entropy for other applications following [BIP85][BIP85].
+//!
+//! BIP85 derives independent secrets, like the entropy for another wallet's
+//! BIP39 phrase or a password, from a single BIP32 secp256k1 master key, so
+//! backing up the master seed backs up every secret derived from it while no
+//! derived secret reveals the seed or any of its siblings. The child key at
+//! `m/83696968'/<application>'/...` is derived with hardened BIP32 steps and
+//! the entropy for the path is `HMAC-SHA512(key = "bip-entropy-from-k", data =
+//! child secret key)`.
+//!
+//! The master key is derived from a seed as the BIP32 master key, which is
+//! also the secp256k1 key [`mnemonic`](crate::mnemonic) recovers from a
+//! phrase, or decoded from an `xprv`.
+//!
+//! [BIP85]: https://github.com/bitcoin/bips/blob/master/bip-0085.mediawiki
+use crate::{
+    error,
+    hd::{DerivationPath, HARDENED},
+};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha512;
+use zeroize::Zeroize;
+
+/// The BIP85 purpose, the first index of every BIP85 path.
+pub const PURPOSE: u32 = 83_696_968;
+/// The length of the entropy derived for a path.
+pub const ENTROPY_LENGTH: usize = 64;
+/// The shortest output of the HEX application.
+pub const MIN_HEX_LENGTH: usize = 16;
+
+const SECP256K1_SEED_KEY: &[u8] = b"Bitcoin seed";
+const ENTROPY_KEY: &[u8] = b"bip-entropy-from-k";
+#[cfg(feature = "mnemonic")]
+const APPLICATION_BIP39: u32 = 39;
+#[cfg(feature = "mnemonic")]
+const LANGUAGE_ENGLISH: u32 = 0;
+const APPLICATION_HEX: u32 = 128_169;
+const XPRV_VERSION: &[u8] = &[0x04, 0x88, 0xad, 0xe4];
+const XPRV_LENGTH: usize = 78;
+
+/// A BIP32 secp256k1 extended secret key: a secret key together with its
+/// chain code. Both are zeroized on drop.
+pub struct MasterKey {
+    secret: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl std::fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("MasterKey")
+            .field("secret", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl MasterKey {
+    /// The BIP32 master key for the given seed. Fails with
+    /// `DecodeError::ExtendedKey` for the astronomically unlikely seeds whose
+    /// master secret is not a valid scalar, which BIP32 declares invalid.
+    pub fn from_seed(seed: &[u8]) -> error::Result<Self> {
+        let result = Self::from_hmac(SECP256K1_SEED_KEY, &[seed]);
+        k256::SecretKey::from_bytes(&result.secret).map_err(|_| error::invalid_extended_key())?;
+        Ok(result)
+    }
+
+    /// The BIP32 master key for the seed of the given BIP39 phrase, with an
+    /// empty passphrase.
+    #[cfg(feature = "mnemonic")]
+    pub fn from_phrase(phrase: &str) -> error::Result<Self> {
+        let mut seed = crate::mnemonic::seed(phrase)?;
+        let result = Self::from_seed(&seed);
+        seed.zeroize();
+        result
+    }
+
+    /// Decode a BIP32 extended secret key in its base58check `xprv` form. The
+    /// key is used as the root for derivation whatever its depth.
+    pub fn from_xprv(s: &str) -> error::Result<Self> {
+        let mut data = bs58::decode(s)
+            .with_check(None)
+            .into_vec()
+            .map_err(|_| error::invalid_extended_key())?;
+        // version (4) | depth (1) | parent fingerprint (4) | child number (4) |
+        // chain code (32) | 0x00 | secret key (32)
+        if data.len() != XPRV_LENGTH || !data.starts_with(XPRV_VERSION) || data[45] != 0 {
+            data.zeroize();
+            return Err(error::invalid_extended_key());
+        }
+        let mut result = Self {
+            secret: [0u8; 32],
+            chain_code: [0u8; 32],
+        };
+        result.chain_code.copy_from_slice(&data[13..45]);
+        result.secret.copy_from_slice(&data[46..]);
+        data.zeroize();
+        k256::SecretKey::from_bytes(&result.secret).map_err(|_| error::invalid_extended_key())?;
+        Ok(result)
+    }
+
+    export_secret! {
+        /// The entropy for the given path, which has to start with [`PURPOSE`].
+        /// Every index in the path is hardened.
+        fn derive_entropy(&self, path: &DerivationPath) -> error::Result<[u8; ENTROPY_LENGTH]> {
+            if path.indexes().first() != Some(&PURPOSE) {
+                return Err(error::invalid_derivation_path(&path.to_string()));
+            }
+            let child = self.derive(path)?;
+            let mut mac = Hmac::<Sha512>::new_from_slice(ENTROPY_KEY).expect("hmac key");
+            mac.update(&child.secret);
+            let mut output = mac.finalize().into_bytes();
+            let mut entropy = [0u8; ENTROPY_LENGTH];
+            entropy.copy_from_slice(&output);
+            output[..].zeroize();
+            Ok(entropy)
+        }
+    }
+
+    export_secret! {
+        /// The first `length` bytes of entropy of the HEX application for the
+        /// given index, at `m/83696968'/128169'/<length>'/<index>'`. The length
+        /// is between [`MIN_HEX_LENGTH`] and [`ENTROPY_LENGTH`] bytes.
+        fn derive_hex(&self, length: usize, index: u32) -> error::Result<Vec<u8>> {
+            if !(MIN_HEX_LENGTH..=ENTROPY_LENGTH).contains(&length) {
+                return Err(error::invalid_length(ENTROPY_LENGTH, length));
+            }
+            let path = DerivationPath::new(&[PURPOSE, APPLICATION_HEX, length as u32, index])?;
+            let mut entropy = self.derive_entropy(&path)?;
+            let result = entropy[..length].to_vec();
+            entropy.zeroize();
+            Ok(result)
+        }
+    }
+
+    export_secret! {
+        /// The English phrase of the BIP39 application with the given number
+        /// of words for the given index, at
+        /// `m/83696968'/39'/0'/<words>'/<index>'`. The phrase can be
+        /// recovered into a keypair with
+        /// [`mnemonic::recover`](crate::mnemonic::recover).
+        #[cfg(feature = "mnemonic")]
+        fn derive_phrase(
+            &self,
+            word_count: crate::mnemonic::WordCount,
+            index: u32,
+        ) -> error::Result<String> {
+            let path = DerivationPath::new(&[
+                PURPOSE,
+                APPLICATION_BIP39,
+                LANGUAGE_ENGLISH,
+                word_count.words(),
+                index,
+            ])?;
+            let mut entropy = self.derive_entropy(&path)?;
+            let mnemonic = bip39::Mnemonic::from_entropy(&entropy[..word_count.entropy_length()])
+                .map_err(|_| error::invalid_mnemonic());
+            entropy.zeroize();
+            Ok(mnemonic?.to_string())
+        }
+    }
+
+    fn derive(&self, path: &DerivationPath) -> error::Result<Self> {
+        let mut result = Self {
+            secret: self.secret,
+            chain_code: self.chain_code,
+        };
+        for index in path.indexes() {
+            result = result.derive_child(*index)?;
+        }
+        Ok(result)
+    }
+
+    /// The hardened child at the given index. The HMAC output is a tweak that
+    /// is added to the parent secret, and BIP32 declares the child invalid
+    /// when the tweak is not below the group order or the sum is zero.
+    fn derive_child(&self, index: u32) -> error::Result<Self> {
+        let mut result = Self::from_hmac(
+            &self.chain_code,
+            &[&[0u8], &self.secret, &(index | HARDENED).to_be_bytes()],
+        );
+        let tweak = k256::SecretKey::from_bytes(&result.secret)
+            .map_err(|_| error::invalid_extended_key())?;
+        let parent = k256::SecretKey::from_bytes(&self.secret).expect("valid secret");
+        let mut sum = *tweak.secret_scalar().as_ref() + *parent.secret_scalar().as_ref();
+        result.secret.copy_from_slice(&sum.to_bytes());
+        sum.zeroize();
+        k256::SecretKey::from_bytes(&result.secret).map_err(|_| error::invalid_extended_key())?;
+        Ok(result)
+    }
+
+    fn from_hmac(key: &[u8], data: &[&[u8]]) -> Self {
+        let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("hmac key");
+        for part in data {
+            mac.update(part);
+        }
+        let mut output = mac.finalize().into_bytes();
+        let mut secret = [0u8; 32];
+        let mut chain_code = [0u8; 32];
+        secret.copy_from_slice(&output[..32]);
+        chain_code.copy_from_slice(&output[32..]);
+        output[..].zeroize();
+        Self { secret, chain_code }
+    }
+}
+
+impl Zeroize for MasterKey {
+    fn zeroize(&mut self) {
+        self.secret.zeroize();
+        self.chain_code.zeroize();
+    }
+}
+
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MasterKey, PURPOSE};
+    use crate::{error, hd::DerivationPath};
+    use hex_literal::hex;
+
+    // The master key of the BIP85 test vectors
+    const XPRV: &str = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8\
+                        kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+    fn path(indexes: &[u32]) -> DerivationPath {
+        DerivationPath::new(indexes).expect("path")
+    }
+
+    #[test]
+    fn bip85_vectors() {
+        let master = MasterKey::from_xprv(XPRV).expect("xprv");
+        // Test cases 1 and 2 from BIP85
+        assert_eq!(
+            hex!(
+                "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f0"
+                "0b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7"
+            ),
+            master
+                .derive_entropy(&path(&[PURPOSE, 0, 0]))
+                .expect("entropy")
+        );
+        assert_eq!(
+            hex!(
+                "70c6e3e8ebee8dc4c0dbba66076819bb8c09672527c4277ca8729532ad711872"
+                "218f826919f6b67218adde99018a6df9095ab2b58d803b5b93ec9802085a690e"
+            ),
+            master
+                .derive_entropy(&path(&[PURPOSE, 0, 1]))
+                .expect("entropy")
+        );
+        // The HEX application vector
+        assert_eq!(
+            hex!(
+                "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f8785"
+                "55d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"
+            )
+            .to_vec(),
+            master.derive_hex(64, 0).expect("hex")
+        );
+    }
+
+    #[cfg(feature = "mnemonic")]
+    #[test]
+    fn bip39_vector() {
+        use crate::mnemonic::WordCount;
+        let master = MasterKey::from_xprv(XPRV).expect("xprv");
+        assert_eq!(
+            "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose",
+            master.derive_phrase(WordCount::Twelve, 0).expect("phrase")
+        );
+    }
+
+    #[test]
+    fn seed_master() {
+        // The master key of test vector 1 from BIP32
+        let master = MasterKey::from_seed(&hex!("000102030405060708090a0b0c0d0e0f")).expect("seed");
+        assert_eq!(
+            hex!("e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"),
+            master.secret
+        );
+        assert_eq!(
+            hex!("873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508"),
+            master.chain_code
+        );
+    }
+
+    #[test]
+    fn rejects() {
+        let master = MasterKey::from_xprv(XPRV).expect("xprv");
+        assert!(master.derive_entropy(&path(&[44, 0, 0])).is_err());
+        assert!(matches!(
+            master.derive_hex(15, 0),
+            Err(error::Error::Decode(error::DecodeError::Length {
+                expected: 64,
+                found: 15
+            }))
+        ));
+        assert!(master.derive_hex(65, 0).is_err());
+        // A changed character fails the checksum
+        let tampered = XPRV.replacen("xprv9s21", "xprv9s22", 1);
+        assert!(matches!(
+            MasterKey::from_xprv(&tampered),
+            Err(error::Error::Decode(error::DecodeError::ExtendedKey))
+        ));
+    }
+}
@@ -0,0 +1,303 @@
+//! BLS12-381 keys with aggregatable signatures, for oracle and consensus
+//! designs that need compact attestations from many signers.
+//!
+//! Keys and signatures follow the minimal public key size variant of the IETF
+//! BLS signature draft with the proof of possession ciphersuite: public keys
+//! are 48 byte compressed G1 points and signatures 96 byte compressed G2
+//! points. Signatures by any number of keys aggregate into a single
+//! signature of the same size.
+//!
+//! Aggregating signatures over the same message is only safe for keys whose
+//! proof of possession, made with [`Keypair::prove_possession`], has been
+//! checked with [`PublicKey::verify_possession`], for example when a signer
+//! joins a consensus group. Without it a rogue key can forge an aggregate
+//! signature.
+//!
+//! These keys are not Helium chain keys and have no key tag or address.
+use crate::{
+    entropy::EntropySource,
+    error::{self, PointError},
+};
+use blst::{min_pk, BLST_ERROR};
+use zeroize::Zeroize;
+
+/// Domain separation tag for signatures over messages.
+const SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// Domain separation tag for proofs of possession.
+const POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+pub const SECRET_KEY_LENGTH: usize = 32;
+pub const PUBLIC_KEY_LENGTH: usize = 48;
+pub const SIGNATURE_LENGTH: usize = 96;
+
+/// A BLS12-381 keypair.
+#[derive(Clone)]
+pub struct Keypair {
+    pub public_key: PublicKey,
+    secret: min_pk::SecretKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey(min_pk::PublicKey);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature(min_pk::Signature);
+
+fn point_error(err: BLST_ERROR) -> error::Error {
+    match err {
+        BLST_ERROR::BLST_POINT_NOT_IN_GROUP => error::invalid_point(PointError::Torsion),
+        BLST_ERROR::BLST_PK_IS_INFINITY => error::invalid_point(PointError::SmallOrder),
+        BLST_ERROR::BLST_BAD_ENCODING => error::invalid_point(PointError::NonCanonical),
+        _ => error::invalid_point(PointError::NotOnCurve),
+    }
+}
+
+fn verify_result(err: BLST_ERROR) -> error::Result {
+    match err {
+        BLST_ERROR::BLST_SUCCESS => Ok(()),
+        _ => Err(signature::Error::new().into()),
+    }
+}
+
+impl Keypair {
+    pub fn generate<E>(entropy: &mut E) -> error::Result<Keypair>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let mut ikm = [0u8; SECRET_KEY_LENGTH];
+        entropy.fill_entropy(&mut ikm)?;
+        let secret = min_pk::SecretKey::key_gen(&ikm, &[]).map_err(|_| error::entropy_failure());
+        ikm.zeroize();
+        Ok(Self::from_secret(secret?))
+    }
+
+    /// Generate a keypair using the operating system random number generator.
+    pub fn generate_default() -> error::Result<Keypair> {
+        Self::generate(&mut rand_core::OsRng)
+    }
+
+    /// Construct a keypair from a 32 byte big endian secret scalar.
+    pub fn from_bytes(input: &[u8]) -> error::Result<Keypair> {
+        if input.len() != SECRET_KEY_LENGTH {
+            return Err(error::invalid_length(SECRET_KEY_LENGTH, input.len()));
+        }
+        let secret = min_pk::SecretKey::from_bytes(input)
+            .map_err(|_| error::Error::from(p256::elliptic_curve::Error))?;
+        Ok(Self::from_secret(secret))
+    }
+
+    fn from_secret(secret: min_pk::SecretKey) -> Keypair {
+        Keypair {
+            public_key: PublicKey(secret.sk_to_pk()),
+            secret,
+        }
+    }
+
+    export_secret! {
+        /// The secret scalar of the keypair, big endian.
+        fn to_bytes(&self) -> [u8; SECRET_KEY_LENGTH] {
+            self.secret.to_bytes()
+        }
+    }
+
+    pub fn sign(&self, msg: &[u8]) -> Signature {
+        Signature(self.secret.sign(msg, SIGNATURE_DST, &[]))
+    }
+
+    /// Prove possession of the secret key, by signing the public key under
+    /// a separate domain.
+    pub fn prove_possession(&self) -> Signature {
+        Signature(self.secret.sign(&self.public_key.to_bytes(), POP_DST, &[]))
+    }
+}
+
+/// Keypairs never include their secret in `Debug` output.
+impl core::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("public", &self.public_key)
+            .field("secret", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.public_key == other.public_key
+    }
+}
+
+impl PublicKey {
+    /// Parse a compressed public key. The point has to be in the prime order
+    /// subgroup and not the identity.
+    pub fn from_bytes(input: &[u8]) -> error::Result<Self> {
+        if input.len() != PUBLIC_KEY_LENGTH {
+            return Err(error::invalid_length(PUBLIC_KEY_LENGTH, input.len()));
+        }
+        Ok(PublicKey(
+            min_pk::PublicKey::key_validate(input).map_err(point_error)?,
+        ))
+    }
+
+    /// The compressed form of the public key.
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        self.0.compress()
+    }
+
+    pub fn verify(&self, msg: &[u8], signature: &Signature) -> error::Result {
+        verify_result(
+            signature
+                .0
+                .verify(true, msg, SIGNATURE_DST, &[], &self.0, false),
+        )
+    }
+
+    /// Verify a proof of possession made with [`Keypair::prove_possession`].
+    pub fn verify_possession(&self, proof: &Signature) -> error::Result {
+        verify_result(
+            proof
+                .0
+                .verify(true, &self.to_bytes(), POP_DST, &[], &self.0, false),
+        )
+    }
+}
+
+impl Signature {
+    /// Parse a compressed signature. The point has to be in the prime order
+    /// subgroup.
+    pub fn from_bytes(input: &[u8]) -> error::Result<Self> {
+        if input.len() != SIGNATURE_LENGTH {
+            return Err(error::invalid_length(SIGNATURE_LENGTH, input.len()));
+        }
+        Ok(Signature(
+            min_pk::Signature::sig_validate(input, false).map_err(point_error)?,
+        ))
+    }
+
+    /// The compressed form of the signature.
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_LENGTH] {
+        self.0.compress()
+    }
+
+    /// Aggregate the given signatures into one. Fails if no signatures are
+    /// given.
+    pub fn aggregate(signatures: &[Signature]) -> error::Result<Self> {
+        if signatures.is_empty() {
+            return Err(signature::Error::new().into());
+        }
+        let signatures: Vec<&min_pk::Signature> = signatures.iter().map(|s| &s.0).collect();
+        let aggregate =
+            min_pk::AggregateSignature::aggregate(&signatures, false).map_err(point_error)?;
+        Ok(Signature(aggregate.to_signature()))
+    }
+}
+
+/// Verify an aggregate signature where each key signed its own message. The
+/// keys and messages are matched up by position.
+pub fn aggregate_verify(
+    public_keys: &[PublicKey],
+    msgs: &[&[u8]],
+    signature: &Signature,
+) -> error::Result {
+    if public_keys.is_empty() || public_keys.len() != msgs.len() {
+        return Err(signature::Error::new().into());
+    }
+    let public_keys: Vec<&min_pk::PublicKey> = public_keys.iter().map(|key| &key.0).collect();
+    verify_result(
+        signature
+            .0
+            .aggregate_verify(true, msgs, SIGNATURE_DST, &public_keys, false),
+    )
+}
+
+/// Verify an aggregate signature where every key signed the same message.
+/// The proof of possession of every key has to have been checked before.
+pub fn fast_aggregate_verify(
+    public_keys: &[PublicKey],
+    msg: &[u8],
+    signature: &Signature,
+) -> error::Result {
+    if public_keys.is_empty() {
+        return Err(signature::Error::new().into());
+    }
+    let public_keys: Vec<&min_pk::PublicKey> = public_keys.iter().map(|key| &key.0).collect();
+    verify_result(
+        signature
+            .0
+            .fast_aggregate_verify(true, msg, SIGNATURE_DST, &public_keys),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aggregate_verify, fast_aggregate_verify, Keypair, PublicKey, Signature};
+    use rand::rngs::OsRng;
+
+    fn keypairs(n: usize) -> Vec<Keypair> {
+        (0..n)
+            .map(|_| Keypair::generate(&mut OsRng).expect("keypair"))
+            .collect()
+    }
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(&mut OsRng).expect("keypair");
+        let signature = keypair.sign(b"hello world");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world!", &signature)
+            .is_err());
+
+        let proof = keypair.prove_possession();
+        assert!(keypair.public_key.verify_possession(&proof).is_ok());
+        // A proof of possession is not a signature over the key bytes
+        let forged = keypair.sign(&keypair.public_key.to_bytes());
+        assert!(keypair.public_key.verify_possession(&forged).is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(&mut OsRng).expect("keypair");
+        assert_eq!(
+            keypair,
+            Keypair::from_bytes(&keypair.to_bytes()).expect("keypair")
+        );
+        let public_key = PublicKey::from_bytes(&keypair.public_key.to_bytes()).expect("public key");
+        assert_eq!(keypair.public_key, public_key);
+        let signature = keypair.sign(b"hello world");
+        assert_eq!(
+            signature,
+            Signature::from_bytes(&signature.to_bytes()).expect("signature")
+        );
+        assert!(PublicKey::from_bytes(&[0u8; 48]).is_err());
+        assert!(Signature::from_bytes(&signature.to_bytes()[1..]).is_err());
+    }
+
+    #[test]
+    fn aggregate() {
+        let keypairs = keypairs(4);
+        let public_keys: Vec<PublicKey> = keypairs.iter().map(|k| k.public_key.clone()).collect();
+
+        let msgs: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i; 8]).collect();
+        let msg_refs: Vec<&[u8]> = msgs.iter().map(Vec::as_slice).collect();
+        let signatures: Vec<Signature> = keypairs
+            .iter()
+            .zip(&msgs)
+            .map(|(keypair, msg)| keypair.sign(msg))
+            .collect();
+        let signature = Signature::aggregate(&signatures).expect("aggregate");
+        assert!(aggregate_verify(&public_keys, &msg_refs, &signature).is_ok());
+        assert!(aggregate_verify(&public_keys[1..], &msg_refs[1..], &signature).is_err());
+
+        let signatures: Vec<Signature> = keypairs.iter().map(|k| k.sign(b"attest")).collect();
+        let signature = Signature::aggregate(&signatures).expect("aggregate");
+        assert!(fast_aggregate_verify(&public_keys, b"attest", &signature).is_ok());
+        assert!(fast_aggregate_verify(&public_keys[..3], b"attest", &signature).is_err());
+        assert!(fast_aggregate_verify(&public_keys, b"attest!", &signature).is_err());
+        assert!(Signature::aggregate(&[]).is_err());
+    }
+}
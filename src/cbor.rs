@@ -0,0 +1,288 @@
+//! Minimal deterministic CBOR (RFC 8949 section 4.2) encoding and strict
+//! decoding, covering the integers, byte strings, text strings, arrays, maps
+//! and tags used by the signed formats in this crate.
+//!
+//! The decoder only accepts the deterministic encoding: shortest form
+//! argument encodings, no indefinite lengths, and map keys in ascending
+//! bytewise order of their encodings without duplicates. Any value therefore
+//! has exactly one accepted encoding, which is what the signatures are
+//! computed over.
+use crate::error;
+use std::convert::TryFrom;
+
+pub(crate) const MAJOR_UINT: u8 = 0;
+pub(crate) const MAJOR_NEGATIVE: u8 = 1;
+pub(crate) const MAJOR_BYTES: u8 = 2;
+pub(crate) const MAJOR_TEXT: u8 = 3;
+pub(crate) const MAJOR_ARRAY: u8 = 4;
+pub(crate) const MAJOR_MAP: u8 = 5;
+pub(crate) const MAJOR_TAG: u8 = 6;
+
+/// How deeply nested items skipped over may be.
+const MAX_NESTING: usize = 16;
+
+pub(crate) fn write_head(output: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        output.push(major | value as u8);
+    } else if value <= u8::MAX as u64 {
+        output.extend_from_slice(&[major | 24, value as u8]);
+    } else if value <= u16::MAX as u64 {
+        output.push(major | 25);
+        output.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        output.push(major | 26);
+        output.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        output.push(major | 27);
+        output.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+pub(crate) fn write_uint(output: &mut Vec<u8>, value: u64) {
+    write_head(output, MAJOR_UINT, value);
+}
+
+pub(crate) fn write_int(output: &mut Vec<u8>, value: i64) {
+    if value < 0 {
+        write_head(output, MAJOR_NEGATIVE, !value as u64);
+    } else {
+        write_uint(output, value as u64);
+    }
+}
+
+pub(crate) fn write_bytes(output: &mut Vec<u8>, value: &[u8]) {
+    write_head(output, MAJOR_BYTES, value.len() as u64);
+    output.extend_from_slice(value);
+}
+
+pub(crate) fn write_text(output: &mut Vec<u8>, value: &str) {
+    write_head(output, MAJOR_TEXT, value.len() as u64);
+    output.extend_from_slice(value.as_bytes());
+}
+
+/// Writes a map with text keys and values, ordering the entries by their
+/// encoded keys.
+pub(crate) fn write_text_map<'a, I>(output: &mut Vec<u8>, entries: I)
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let mut entries: Vec<(Vec<u8>, &str)> = entries
+        .into_iter()
+        .map(|(key, value)| {
+            let mut encoded = vec![];
+            write_text(&mut encoded, key);
+            (encoded, value)
+        })
+        .collect();
+    entries.sort();
+    write_head(output, MAJOR_MAP, entries.len() as u64);
+    for (key, value) in entries {
+        output.extend_from_slice(&key);
+        write_text(output, value);
+    }
+}
+
+/// Strict reader over deterministically encoded CBOR.
+pub(crate) struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(input: &'a [u8]) -> Self {
+        Self { input }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> error::Result<&'a [u8]> {
+        if self.input.len() < len {
+            return Err(error::invalid_cbor());
+        }
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(bytes)
+    }
+
+    /// Reads the head of the next item, returning its major type and argument.
+    pub(crate) fn head(&mut self) -> error::Result<(u8, u64)> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let (value, min) = match info {
+            0..=23 => return Ok((major, info as u64)),
+            24 => (self.take(1)?[0] as u64, 24),
+            25 => {
+                let bytes = self.take(2)?;
+                (
+                    u16::from_be_bytes([bytes[0], bytes[1]]) as u64,
+                    u8::MAX as u64 + 1,
+                )
+            }
+            26 => {
+                let bytes = self.take(4)?;
+                (
+                    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64,
+                    u16::MAX as u64 + 1,
+                )
+            }
+            27 => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(self.take(8)?);
+                (u64::from_be_bytes(bytes), u32::MAX as u64 + 1)
+            }
+            // Reserved values and indefinite lengths
+            _ => return Err(error::invalid_cbor()),
+        };
+        // Only the shortest encoding of the argument is deterministic
+        if value < min {
+            return Err(error::invalid_cbor());
+        }
+        Ok((major, value))
+    }
+
+    fn expect(&mut self, major: u8) -> error::Result<u64> {
+        match self.head()? {
+            (found, value) if found == major => Ok(value),
+            _ => Err(error::invalid_cbor()),
+        }
+    }
+
+    pub(crate) fn uint(&mut self) -> error::Result<u64> {
+        self.expect(MAJOR_UINT)
+    }
+
+    pub(crate) fn int(&mut self) -> error::Result<i64> {
+        let (major, value) = self.head()?;
+        let value = i64::try_from(value).map_err(|_| error::invalid_cbor())?;
+        match major {
+            MAJOR_UINT => Ok(value),
+            MAJOR_NEGATIVE => Ok(!value),
+            _ => Err(error::invalid_cbor()),
+        }
+    }
+
+    pub(crate) fn bytes(&mut self) -> error::Result<&'a [u8]> {
+        let len = self.expect(MAJOR_BYTES)?;
+        self.take(usize::try_from(len).map_err(|_| error::invalid_cbor())?)
+    }
+
+    pub(crate) fn text(&mut self) -> error::Result<&'a str> {
+        let len = self.expect(MAJOR_TEXT)?;
+        let bytes = self.take(usize::try_from(len).map_err(|_| error::invalid_cbor())?)?;
+        std::str::from_utf8(bytes).map_err(|_| error::invalid_cbor())
+    }
+
+    pub(crate) fn map_len(&mut self) -> error::Result<u64> {
+        self.expect(MAJOR_MAP)
+    }
+
+    pub(crate) fn array_len(&mut self) -> error::Result<u64> {
+        self.expect(MAJOR_ARRAY)
+    }
+
+    /// Reads the tag of the next item if it is tagged with the given tag.
+    pub(crate) fn optional_tag(&mut self, tag: u64) -> error::Result<bool> {
+        let mut peek = Reader::new(self.input);
+        if peek.head()? != (MAJOR_TAG, tag) {
+            return Ok(false);
+        }
+        self.input = peek.input;
+        Ok(true)
+    }
+
+    /// Skips over the next item, whatever its type.
+    pub(crate) fn skip(&mut self) -> error::Result {
+        self.skip_nested(MAX_NESTING)
+    }
+
+    fn skip_nested(&mut self, depth: usize) -> error::Result {
+        let depth = depth.checked_sub(1).ok_or_else(error::invalid_cbor)?;
+        let (major, value) = self.head()?;
+        let items = match major {
+            MAJOR_BYTES | MAJOR_TEXT => {
+                self.take(usize::try_from(value).map_err(|_| error::invalid_cbor())?)?;
+                0
+            }
+            MAJOR_ARRAY => value,
+            MAJOR_MAP => value.checked_mul(2).ok_or_else(error::invalid_cbor)?,
+            MAJOR_TAG => 1,
+            // Integers and simple values are just their head
+            _ => 0,
+        };
+        for _ in 0..items {
+            self.skip_nested(depth)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a map with text keys and values, checking the key order.
+    pub(crate) fn text_map(&mut self) -> error::Result<Vec<(String, String)>> {
+        let len = self.map_len()?;
+        let mut result = vec![];
+        let mut previous: Option<&[u8]> = None;
+        for _ in 0..len {
+            let start = self.input;
+            let key = self.text()?;
+            let encoded = &start[..start.len() - self.input.len()];
+            if matches!(previous, Some(previous) if previous >= encoded) {
+                return Err(error::invalid_cbor());
+            }
+            previous = Some(encoded);
+            result.push((key.to_string(), self.text()?.to_string()));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_int, write_text_map, write_uint, Reader};
+
+    #[test]
+    fn uint_roundtrip() {
+        for value in &[0, 23, 24, 255, 256, 65535, 65536, u32::MAX as u64, u64::MAX] {
+            let mut encoded = vec![];
+            write_uint(&mut encoded, *value);
+            let mut reader = Reader::new(&encoded);
+            assert_eq!(*value, reader.uint().expect("uint"));
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[test]
+    fn int_roundtrip() {
+        for value in &[0, 23, -1, -24, -25, -256, -257, i64::MIN, i64::MAX] {
+            let mut encoded = vec![];
+            write_int(&mut encoded, *value);
+            let mut reader = Reader::new(&encoded);
+            assert_eq!(*value, reader.int().expect("int"));
+            assert!(reader.is_empty());
+        }
+        // -8 is major type 1 with argument 7
+        assert_eq!(Reader::new(&[0x27]).int().expect("int"), -8);
+    }
+
+    #[test]
+    fn non_deterministic() {
+        // 10 with a one byte argument
+        assert!(Reader::new(&[0x18, 0x0a]).uint().is_err());
+        // Indefinite length byte string
+        assert!(Reader::new(&[0x5f, 0x41, 0x00, 0xff]).bytes().is_err());
+        // Map keys out of order
+        assert!(Reader::new(&[0xa2, 0x61, 0x62, 0x60, 0x61, 0x61, 0x60])
+            .text_map()
+            .is_err());
+    }
+
+    #[test]
+    fn text_map_order() {
+        let mut encoded = vec![];
+        write_text_map(&mut encoded, vec![("bb", "2"), ("c", "3"), ("a", "1")]);
+        // Shorter keys sort first since their encoded length is smaller
+        let entries = Reader::new(&encoded).text_map().expect("map");
+        let keys: Vec<&str> = entries.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(vec!["a", "c", "bb"], keys);
+    }
+}
@@ -0,0 +1,75 @@
+//! Domain separation for signatures.
+//!
+//! A signature made for one protocol must not be accepted by another that
+//! happens to sign messages of the same shape. [`Sign::sign_with_context`]
+//! and [`Verify::verify_with_context`] sign and verify
+//! `len(domain) || domain || msg` instead of the message itself, with the
+//! length as a single byte, so a signature only verifies for the domain it
+//! was made for. Domains are at most [`MAX_DOMAIN_LENGTH`] bytes.
+//!
+//! Messages signed with a context can still collide with messages signed
+//! without one, so a protocol should either always or never use a context.
+//!
+//! [`Sign::sign_with_context`]: crate::Sign::sign_with_context
+//! [`Verify::verify_with_context`]: crate::Verify::verify_with_context
+use crate::error;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// The maximum length of a signing domain.
+pub const MAX_DOMAIN_LENGTH: usize = u8::MAX as usize;
+
+/// The domain for onboarding payloads.
+pub const ONBOARDING: &[u8] = b"helium/onboarding/v1";
+/// The domain for transaction payloads.
+pub const TRANSACTION: &[u8] = b"helium/transaction/v1";
+/// The domain for provisioning bundles and device registration.
+pub const PROVISIONING: &[u8] = b"helium/provisioning/v1";
+/// The domain for login and other authentication challenges.
+pub const AUTHENTICATION: &[u8] = b"helium/authentication/v1";
+/// The domain for [key rotation statements](crate::rotation).
+pub const ROTATION: &[u8] = b"helium/rotation/v1";
+/// The domain for [revocation statements](crate::revocation).
+pub const REVOCATION: &[u8] = b"helium/revocation/v1";
+
+/// The message actually signed for the given domain and message.
+#[cfg(feature = "alloc")]
+pub(crate) fn message(domain: &[u8], msg: &[u8]) -> error::Result<Vec<u8>> {
+    if domain.len() > MAX_DOMAIN_LENGTH {
+        return Err(error::invalid_length(MAX_DOMAIN_LENGTH, domain.len()));
+    }
+    let mut result = Vec::with_capacity(1 + domain.len() + msg.len());
+    result.push(domain.len() as u8);
+    result.extend_from_slice(domain);
+    result.extend_from_slice(msg);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ONBOARDING, TRANSACTION};
+    use crate::{ecc_compact, ed25519, Network, Sign, Verify};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn domains() {
+        let ed = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        for (keypair, public_key) in &[
+            (&ed as &dyn Sign, &ed.public_key),
+            (&ecc as &dyn Sign, &ecc.public_key),
+        ] {
+            let signature = keypair
+                .sign_with_context(ONBOARDING, b"hello world")
+                .expect("signature");
+            assert!(public_key
+                .verify_with_context(ONBOARDING, b"hello world", &signature)
+                .is_ok());
+            assert!(public_key
+                .verify_with_context(TRANSACTION, b"hello world", &signature)
+                .is_err());
+            assert!(public_key.verify(b"hello world", &signature).is_err());
+        }
+        assert!(ed.sign_with_context(&[0u8; 256], b"hello").is_err());
+    }
+}
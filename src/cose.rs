@@ -0,0 +1,203 @@
+//! Single signer COSE messages, `COSE_Sign1` from RFC 9052, signed and
+//! verified with crate keypairs, for constrained device flows that use CBOR
+//! rather than DER or JSON.
+//!
+//! ecc_compact keys sign with `ES256` (-7), with the fixed size `r || s`
+//! signature form, and ed25519 keys with `EdDSA` (-8). The protected header
+//! holds the algorithm and, as the key id, the binary form of the signing
+//! public key, so both are covered by the signature. Messages are written
+//! with the `COSE_Sign1` tag and an empty unprotected header. Untagged
+//! messages and any unprotected header are accepted when verifying, but the
+//! payload has to be attached.
+use crate::{
+    cbor::{self, Reader},
+    error, KeyType, Keypair, PublicKey, Sign, Verify, VerifyOptions,
+};
+use std::convert::TryFrom;
+
+/// The algorithm identifier for ECDSA P-256 SHA-256 signatures.
+pub const ALG_ES256: i64 = -7;
+/// The algorithm identifier for ed25519 signatures.
+pub const ALG_EDDSA: i64 = -8;
+
+/// The CBOR tag of a `COSE_Sign1` message.
+const TAG_SIGN1: u64 = 18;
+const HEADER_ALG: i64 = 1;
+const HEADER_KID: i64 = 4;
+/// The context string of the signed structure for `COSE_Sign1`.
+const CONTEXT_SIGN1: &str = "Signature1";
+
+/// Length of signatures for both supported algorithms.
+const SIGNATURE_LENGTH: usize = 64;
+
+fn alg(key_type: KeyType) -> error::Result<i64> {
+    match key_type {
+        KeyType::EccCompact => Ok(ALG_ES256),
+        KeyType::Ed25519 => Ok(ALG_EDDSA),
+        _ => Err(error::unsupported_keytype(key_type)),
+    }
+}
+
+/// The `Sig_structure` the signature is computed over.
+fn signed_data(protected: &[u8], external_aad: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(16 + protected.len() + external_aad.len() + payload.len());
+    cbor::write_head(&mut result, cbor::MAJOR_ARRAY, 4);
+    cbor::write_text(&mut result, CONTEXT_SIGN1);
+    cbor::write_bytes(&mut result, protected);
+    cbor::write_bytes(&mut result, external_aad);
+    cbor::write_bytes(&mut result, payload);
+    result
+}
+
+/// Sign the given payload with the given keypair into a tagged `COSE_Sign1`
+/// message. The external additional data is covered by the signature but not
+/// included in the message, and has to be given again to verify it.
+pub fn sign(keypair: &Keypair, payload: &[u8], external_aad: &[u8]) -> error::Result<Vec<u8>> {
+    let mut protected = vec![];
+    cbor::write_head(&mut protected, cbor::MAJOR_MAP, 2);
+    cbor::write_int(&mut protected, HEADER_ALG);
+    cbor::write_int(&mut protected, alg(keypair.key_tag().key_type)?);
+    cbor::write_int(&mut protected, HEADER_KID);
+    cbor::write_bytes(&mut protected, &keypair.public_key().to_bytes());
+
+    let signature = keypair.sign(&signed_data(&protected, external_aad, payload))?;
+    let signature = match keypair {
+        Keypair::EccCompact(_) => p256::ecdsa::Signature::from_der(&signature)?
+            .as_ref()
+            .to_vec(),
+        _ => signature,
+    };
+    let mut result = vec![];
+    cbor::write_head(&mut result, cbor::MAJOR_TAG, TAG_SIGN1);
+    cbor::write_head(&mut result, cbor::MAJOR_ARRAY, 4);
+    cbor::write_bytes(&mut result, &protected);
+    cbor::write_head(&mut result, cbor::MAJOR_MAP, 0);
+    cbor::write_bytes(&mut result, payload);
+    cbor::write_bytes(&mut result, &signature);
+    Ok(result)
+}
+
+/// The parts of a `COSE_Sign1` message.
+struct Sign1<'a> {
+    protected: &'a [u8],
+    alg: i64,
+    kid: Option<&'a [u8]>,
+    payload: &'a [u8],
+    signature: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for Sign1<'a> {
+    type Error = error::Error;
+    fn try_from(input: &'a [u8]) -> error::Result<Self> {
+        let invalid = |_| error::invalid_cose();
+        let mut reader = Reader::new(input);
+        reader.optional_tag(TAG_SIGN1).map_err(invalid)?;
+        if reader.array_len().map_err(invalid)? != 4 {
+            return Err(error::invalid_cose());
+        }
+        let protected = reader.bytes().map_err(invalid)?;
+        reader.skip().map_err(invalid)?;
+        let payload = reader.bytes().map_err(invalid)?;
+        let signature = reader.bytes().map_err(invalid)?;
+        if !reader.is_empty() {
+            return Err(error::invalid_cose());
+        }
+
+        let mut header = Reader::new(protected);
+        let (mut alg, mut kid) = (None, None);
+        for _ in 0..header.map_len().map_err(invalid)? {
+            match header.int().map_err(invalid)? {
+                HEADER_ALG if alg.is_none() => alg = Some(header.int().map_err(invalid)?),
+                HEADER_KID if kid.is_none() => kid = Some(header.bytes().map_err(invalid)?),
+                HEADER_ALG | HEADER_KID => return Err(error::invalid_cose()),
+                _ => header.skip().map_err(invalid)?,
+            }
+        }
+        if !header.is_empty() {
+            return Err(error::invalid_cose());
+        }
+        Ok(Self {
+            protected,
+            alg: alg.ok_or_else(error::invalid_cose)?,
+            kid,
+            payload,
+            signature,
+        })
+    }
+}
+
+/// The public key named by the key id of the given message, without
+/// verifying the message.
+pub fn key_id(message: &[u8]) -> error::Result<PublicKey> {
+    let kid = Sign1::try_from(message)?
+        .kid
+        .ok_or_else(error::invalid_cose)?;
+    PublicKey::try_from(kid).map_err(|_| error::invalid_cose())
+}
+
+/// Verify the given message and external additional data against the given
+/// key and return the payload.
+pub fn verify<'a>(
+    public_key: &PublicKey,
+    message: &'a [u8],
+    external_aad: &[u8],
+) -> error::Result<&'a [u8]> {
+    let sign1 = Sign1::try_from(message)?;
+    // The algorithm is fixed by the key, never by the message
+    if sign1.alg != alg(public_key.tag().key_type)?
+        || matches!(sign1.kid, Some(kid) if kid != &public_key.to_bytes()[..])
+        || sign1.signature.len() != SIGNATURE_LENGTH
+    {
+        return Err(error::invalid_cose());
+    }
+    let options = VerifyOptions {
+        allow_raw_ecdsa: true,
+        ..VerifyOptions::DEFAULT
+    };
+    public_key.verify_with_options(
+        &signed_data(sign1.protected, external_aad, sign1.payload),
+        sign1.signature,
+        &options,
+    )?;
+    Ok(sign1.payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{key_id, sign, verify};
+    use crate::{ecc_compact, ed25519, secp256k1, Keypair, Network};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn roundtrip() {
+        let keypairs = vec![
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng)),
+        ];
+        for keypair in keypairs {
+            let message = sign(&keypair, b"manifest", b"device").expect("message");
+            // Tag 18 and a four element array
+            assert_eq!(&[0xd2, 0x84], &message[..2]);
+            let public_key = key_id(&message).expect("key id");
+            assert_eq!(keypair.public_key(), &public_key);
+            assert_eq!(
+                b"manifest",
+                verify(&public_key, &message, b"device").expect("payload")
+            );
+            assert!(verify(&public_key, &message, b"other").is_err());
+            let other = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+            assert!(verify(&other.public_key, &message, b"device").is_err());
+
+            // Untagged messages verify too
+            assert!(verify(&public_key, &message[1..], b"device").is_ok());
+            let mut tampered = message.clone();
+            let last = tampered.len() - 1;
+            tampered[last] ^= 1;
+            assert!(verify(&public_key, &tampered, b"device").is_err());
+            assert!(verify(&public_key, &message[..last], b"device").is_err());
+        }
+        let keypair =
+            Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng));
+        assert!(sign(&keypair, b"manifest", b"").is_err());
+    }
+}
@@ -0,0 +1,114 @@
+//! PKCS#10 (RFC 2986) certificate signing requests signed by a keypair, for
+//! enrolling keys into a PKI without extracting them. Any [`Backend`] can
+//! sign a request, so keys in secure elements and HSMs can be enrolled too.
+//!
+//! The subject of a request is just a common name, the b58 address of the key
+//! unless another name is given, and the request has no attributes. ECDSA
+//! keys sign with `ecdsa-with-SHA256` and ed25519 keys with pure Ed25519.
+//! BIP340 keys have no certificate signature algorithm and are rejected.
+use crate::{
+    error,
+    pkcs8::{self, tlv, BIT_STRING, INTEGER, OID, SEQUENCE},
+    Backend, KeyType, Sign,
+};
+
+const SET: u8 = 0x31;
+const UTF8_STRING: u8 = 0x0c;
+// The implicitly tagged, here empty, attributes of a request
+const ATTRIBUTES_TAG: u8 = 0xa0;
+
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE REQUEST-----";
+const PEM_END: &str = "-----END CERTIFICATE REQUEST-----";
+
+/// Build a DER encoded certificate signing request for the key of the given
+/// signer, with the given subject common name or the key's address.
+pub fn to_der<B>(signer: &B, common_name: Option<&str>) -> error::Result<Vec<u8>>
+where
+    B: Backend + ?Sized,
+{
+    let public_key = signer.public_key();
+    let algorithm = match public_key.tag().key_type {
+        KeyType::EccCompact | KeyType::Secp256k1 => tlv(OID, OID_ECDSA_WITH_SHA256),
+        KeyType::Ed25519 => tlv(OID, pkcs8::OID_ED25519),
+        key_type => return Err(error::unsupported_keytype(key_type)),
+    };
+    let common_name = common_name
+        .map(String::from)
+        .unwrap_or_else(|| public_key.to_string());
+    let mut attribute = tlv(OID, OID_COMMON_NAME);
+    attribute.extend_from_slice(&tlv(UTF8_STRING, common_name.as_bytes()));
+    let subject = tlv(SEQUENCE, &tlv(SET, &tlv(SEQUENCE, &attribute)));
+
+    let mut info = vec![INTEGER, 0x01, 0x00];
+    info.extend_from_slice(&subject);
+    info.extend_from_slice(&pkcs8::encode_spki(public_key)?);
+    info.extend_from_slice(&[ATTRIBUTES_TAG, 0x00]);
+    let info = tlv(SEQUENCE, &info);
+
+    let mut signature = vec![0x00];
+    signature.extend_from_slice(&signer.sign(&info)?);
+    let mut request = info;
+    request.extend_from_slice(&tlv(SEQUENCE, &algorithm));
+    request.extend_from_slice(&tlv(BIT_STRING, &signature));
+    Ok(tlv(SEQUENCE, &request))
+}
+
+/// Build a certificate signing request as a `CERTIFICATE REQUEST` PEM block,
+/// see [`to_der`].
+pub fn to_pem<B>(signer: &B, common_name: Option<&str>) -> error::Result<String>
+where
+    B: Backend + ?Sized,
+{
+    Ok(pkcs8::to_pem_block(
+        PEM_BEGIN,
+        PEM_END,
+        &to_der(signer, common_name)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_der, to_pem};
+    use crate::{
+        ecc_compact, ed25519,
+        pkcs8::{single, Der, BIT_STRING, SEQUENCE},
+        schnorr, secp256k1, Keypair, Network, Verify,
+    };
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn signed_request() {
+        let keypairs = vec![
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng)),
+        ];
+        for keypair in keypairs {
+            let request = to_der(&keypair, None).expect("request");
+            let mut der = Der(single(&request, SEQUENCE).expect("request"));
+            let start = der.0;
+            der.read(SEQUENCE).expect("request info");
+            let info = &start[..start.len() - der.0.len()];
+            der.read(SEQUENCE).expect("signature algorithm");
+            let signature = single(der.0, BIT_STRING).expect("signature");
+            assert!(keypair.public_key().verify(info, &signature[1..]).is_ok());
+            let address = keypair.public_key().to_string();
+            assert!(info
+                .windows(address.len())
+                .any(|window| window == address.as_bytes()));
+
+            let long_name = "x".repeat(300);
+            let request = to_der(&keypair, Some(&long_name)).expect("request");
+            assert!(single(&request, SEQUENCE).is_ok());
+        }
+
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let pem = to_pem(&keypair, Some("hotspot")).expect("pem");
+        assert!(pem.starts_with("-----BEGIN CERTIFICATE REQUEST-----\n"));
+        let keypair = Keypair::Schnorr(schnorr::Keypair::generate(Network::MainNet, &mut OsRng));
+        assert!(to_der(&keypair, None).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! [`did:key`](https://w3c-ccg.github.io/did-method-key/) identifiers for
+//! public keys.
+//!
+//! A `did:key` identifier is the multibase base58btc encoding, prefix `z`, of
+//! the multicodec tagged public key:
+//!
+//! | key type    | multicodec               | key bytes             |
+//! |-------------|--------------------------|-----------------------|
+//! | ed25519     | `ed25519-pub` (0xed)     | 32 byte public key    |
+//! | ecc_compact | `p256-pub` (0x1200)      | SEC1 compressed point |
+//! | secp256k1   | `secp256k1-pub` (0xe7)   | SEC1 compressed point |
+//!
+//! Identifiers do not carry a network, so one has to be given when parsing.
+use crate::{ecc_compact, ed25519, error, secp256k1, KeyType, Network, PublicKey};
+use std::convert::TryFrom;
+
+const DID_KEY_PREFIX: &str = "did:key:";
+const MULTIBASE_BASE58BTC: char = 'z';
+
+// Unsigned varint encodings of the multicodec codes
+const ED25519_PUB: &[u8] = &[0xed, 0x01];
+const P256_PUB: &[u8] = &[0x80, 0x24];
+const SECP256K1_PUB: &[u8] = &[0xe7, 0x01];
+
+impl PublicKey {
+    /// The multibase encoded multicodec form of this key, as used in the
+    /// method specific identifier of a `did:key`.
+    pub fn to_multibase(&self) -> error::Result<String> {
+        let mut data = match self.tag().key_type {
+            KeyType::Ed25519 => ED25519_PUB.to_vec(),
+            KeyType::EccCompact => P256_PUB.to_vec(),
+            KeyType::Secp256k1 => SECP256K1_PUB.to_vec(),
+            KeyType::Schnorr
+            | KeyType::Multisig
+            | KeyType::P384
+            | KeyType::Secp256r1
+            | KeyType::Sr25519
+            | KeyType::Rsa
+            | KeyType::Other(_) => return Err(error::unsupported_keytype(self.tag().key_type)),
+        };
+        match self.tag().key_type {
+            KeyType::Ed25519 => data.extend_from_slice(&self.to_bytes()[1..]),
+            _ => data.extend_from_slice(&self.to_sec1_bytes(true)?),
+        }
+        Ok(format!(
+            "{}{}",
+            MULTIBASE_BASE58BTC,
+            bs58::encode(data).into_string()
+        ))
+    }
+
+    /// Parse a key for the given network from its multibase encoded
+    /// multicodec form.
+    pub fn from_multibase(network: Network, input: &str) -> error::Result<Self> {
+        let encoded = input
+            .strip_prefix(MULTIBASE_BASE58BTC)
+            .ok_or_else(error::invalid_did_key)?;
+        let data = bs58::decode(encoded).into_vec()?;
+        if let Some(key) = data.strip_prefix(ED25519_PUB) {
+            let key =
+                ed25519_dalek::PublicKey::from_bytes(key).map_err(|_| error::invalid_did_key())?;
+            Ok(Self::for_network(
+                network,
+                ed25519::PublicKey::try_from(key)?,
+            ))
+        } else if let Some(key) = data.strip_prefix(P256_PUB) {
+            Ok(Self::for_network(
+                network,
+                ecc_compact::PublicKey::from_sec1_point(key)?,
+            ))
+        } else if let Some(key) = data.strip_prefix(SECP256K1_PUB) {
+            Ok(Self::for_network(
+                network,
+                secp256k1::PublicKey::from_sec1_point(key)?,
+            ))
+        } else {
+            Err(error::invalid_did_key())
+        }
+    }
+
+    /// The `did:key` identifier of this key.
+    pub fn to_did_key(&self) -> error::Result<String> {
+        Ok(format!("{}{}", DID_KEY_PREFIX, self.to_multibase()?))
+    }
+
+    /// Parse a key for the given network from a `did:key` identifier. A DID
+    /// URL fragment, as in `did:key:z6Mk…#z6Mk…`, is ignored.
+    pub fn from_did_key(network: Network, input: &str) -> error::Result<Self> {
+        let identifier = input
+            .strip_prefix(DID_KEY_PREFIX)
+            .ok_or_else(error::invalid_did_key)?;
+        let identifier = identifier.split('#').next().unwrap_or(identifier);
+        Self::from_multibase(network, identifier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ecc_compact, secp256k1, Network, PublicKey};
+    use rand::rngs::OsRng;
+
+    // The ed25519 example from the did:key method specification
+    const ED25519_DID: &str = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+
+    #[test]
+    fn ed25519_vector() {
+        let public_key = PublicKey::from_did_key(Network::MainNet, ED25519_DID).expect("did key");
+        assert_eq!(ED25519_DID, public_key.to_did_key().expect("did key"));
+        let url = format!("{}#{}", ED25519_DID, &ED25519_DID[8..]);
+        assert_eq!(
+            public_key,
+            PublicKey::from_did_key(Network::MainNet, &url).expect("did url")
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        for public_key in vec![
+            ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng).public_key,
+            secp256k1::Keypair::generate(Network::TestNet, &mut OsRng).public_key,
+        ] {
+            let did = public_key.to_did_key().expect("did key");
+            assert_eq!(
+                public_key,
+                PublicKey::from_did_key(public_key.network, &did).expect("public key")
+            );
+        }
+        assert!(PublicKey::from_did_key(Network::MainNet, "did:web:helium.com").is_err());
+    }
+}
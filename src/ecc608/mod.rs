@@ -0,0 +1,752 @@
+//! ATECC608 backed keypairs. The secret key lives in a key slot of a Microchip
+//! ATECC608 secure element reached over I2C and all signing happens on the
+//! chip.
+//!
+//! The bus and a delay provider are taken as `embedded-hal` implementations so
+//! the same code works with a Linux I2C device as well as on a microcontroller.
+//! The chip has to be configured and its configuration zone locked with the
+//! slot set up for P-256 signing before it can be used here. [`Chip`] does
+//! that provisioning: it writes and locks the configuration and data zones
+//! and hands over to a keypair once a key is generated in a slot.
+//!
+//! Since the public key needs to be representable as an ecc_compact public
+//! key, on-chip key generation is retried until the chip produces a compactable
+//! key.
+//!
+//! Existing ecc_compact keypairs can be moved onto a chip with
+//! [`Chip::import_keypair`], for slots configured to accept private key
+//! writes. The secret key then exists outside the chip as well, so generating
+//! keys on the chip is preferable where the identity does not need to be
+//! kept.
+#[cfg(feature = "attestation")]
+use crate::attestation::{self, Attestation};
+use crate::{ecc_compact, ecdh, entropy::EntropySource, error, keypair, public_key, Network};
+use embedded_hal::blocking::{
+    delay::DelayMs,
+    i2c::{Read, Write},
+};
+use sha2::{Digest, Sha256};
+use std::{convert::TryFrom, sync::Mutex};
+use zeroize::Zeroize;
+
+/// The default 7-bit I2C address of the chip.
+pub const DEFAULT_ADDRESS: u8 = 0x60;
+/// The highest key slot number on the chip.
+pub const MAX_SLOT: u8 = 15;
+/// Length of the configuration zone.
+pub const CONFIG_LENGTH: usize = 128;
+/// Length of the serial number of a chip.
+pub const SERIAL_LENGTH: usize = 9;
+
+/// Number of on-chip key generation attempts before giving up on finding a
+/// compactable key.
+const MAX_GENERATE_ATTEMPTS: usize = 64;
+
+const WORD_RESET: u8 = 0x00;
+const WORD_IDLE: u8 = 0x02;
+const WORD_COMMAND: u8 = 0x03;
+// Response to a wake token, including its checksum
+const WAKE_RESPONSE: [u8; 4] = [0x04, 0x11, 0x33, 0x43];
+
+const OP_READ: u8 = 0x02;
+const OP_WRITE: u8 = 0x12;
+const OP_NONCE: u8 = 0x16;
+const OP_LOCK: u8 = 0x17;
+const OP_GENKEY: u8 = 0x40;
+const OP_SIGN: u8 = 0x41;
+const OP_ECDH: u8 = 0x43;
+const OP_GENDIG: u8 = 0x15;
+const OP_PRIVWRITE: u8 = 0x46;
+const GENKEY_PUBLIC: u8 = 0x00;
+const GENKEY_PRIVATE: u8 = 0x04;
+const NONCE_PASSTHROUGH: u8 = 0x03;
+const NONCE_RANDOM: u8 = 0x00;
+const NONCE_INPUT_LENGTH: usize = 20;
+const PRIVWRITE_ENCRYPTED: u8 = 0x40;
+// The private key is written with four bytes of zero padding in front
+const PRIVWRITE_VALUE_LENGTH: usize = 36;
+const SIGN_EXTERNAL: u8 = 0x80;
+const ZONE_CONFIG: u8 = 0x00;
+const ZONE_DATA: u8 = 0x02;
+// Zone flag for 32 byte rather than 4 byte reads and writes
+const ZONE_BLOCK: u8 = 0x80;
+const LOCK_CONFIG: u8 = 0x00;
+const LOCK_DATA: u8 = 0x01;
+const LOCK_SLOT: u8 = 0x02;
+const LOCK_NO_CRC: u8 = 0x80;
+// ECDH with the key in the slot, returning the shared secret in the clear
+const ECDH_CLEAR: u8 = 0x0c;
+
+const CONFIG_LOCK_WORD: usize = 21;
+const CONFIG_LOCK_VALUE: usize = 86;
+const CONFIG_LOCK_CONFIG: usize = 87;
+const UNLOCKED: u8 = 0x55;
+
+// Maximum execution times in milliseconds
+const WAKE_DELAY: u32 = 2;
+const READ_DELAY: u32 = 1;
+const WRITE_DELAY: u32 = 26;
+const LOCK_DELAY: u32 = 32;
+const NONCE_DELAY: u32 = 7;
+const GENKEY_DELAY: u32 = 115;
+const SIGN_DELAY: u32 = 115;
+const ECDH_DELAY: u32 = 58;
+const GENDIG_DELAY: u32 = 11;
+const PRIVWRITE_DELAY: u32 = 48;
+
+/// The I2C bus and delay provider used to talk to the chip.
+struct Bus<I, D> {
+    i2c: I,
+    delay: D,
+    address: u8,
+}
+
+/// A connection to a chip and the slot holding the key to sign with.
+pub struct Ecc608<I, D> {
+    bus: Mutex<Bus<I, D>>,
+    slot: u8,
+}
+
+pub type Keypair<I, D> = keypair::Keypair<Ecc608<I, D>>;
+
+impl<I, D, E> keypair::Sign for Keypair<I, D>
+where
+    I: Read<Error = E> + Write<Error = E>,
+    D: DelayMs<u32>,
+{
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        let digest = Sha256::digest(msg);
+        let mut bus = self.inner.bus.lock().expect("ecc608 lock");
+        let signature = bus.session(|bus| {
+            // The digest is loaded into TempKey and signed from there
+            bus.command(OP_NONCE, NONCE_PASSTHROUGH, 0, &digest, NONCE_DELAY, 0)?;
+            bus.command(
+                OP_SIGN,
+                SIGN_EXTERNAL,
+                self.inner.slot as u16,
+                &[],
+                SIGN_DELAY,
+                64,
+            )
+        })?;
+        // The chip returns the raw r and s values, with s either high or low
+        let signature = p256::ecdsa::Signature::try_from(&signature[..])?;
+        let signature = ecc_compact::normalize_s(signature)?;
+        Ok(signature.to_der().as_bytes().to_vec())
+    }
+}
+
+impl<I, D, E> Keypair<I, D>
+where
+    I: Read<Error = E> + Write<Error = E>,
+    D: DelayMs<u32>,
+{
+    /// Open the key in the given slot of the chip at the given address and
+    /// read its public key.
+    pub fn open(network: Network, i2c: I, delay: D, address: u8, slot: u8) -> error::Result<Self> {
+        check_slot(slot)?;
+        Self::open_on(network, Bus::new(i2c, delay, address), slot)
+    }
+
+    fn open_on(network: Network, mut bus: Bus<I, D>, slot: u8) -> error::Result<Self> {
+        let point = bus.session(|bus| {
+            bus.command(OP_GENKEY, GENKEY_PUBLIC, slot as u16, &[], GENKEY_DELAY, 64)
+        })?;
+        let public_key = public_key_from_point(&point)?;
+        Ok(Self::from_parts(network, public_key, bus, slot))
+    }
+
+    /// Generate a new key in the given slot of the chip at the given address.
+    /// Any existing key in the slot is overwritten, and the slot has to allow
+    /// key generation in its configuration.
+    pub fn generate(
+        network: Network,
+        i2c: I,
+        delay: D,
+        address: u8,
+        slot: u8,
+    ) -> error::Result<Self> {
+        check_slot(slot)?;
+        Self::generate_on(network, Bus::new(i2c, delay, address), slot)
+    }
+
+    fn generate_on(network: Network, mut bus: Bus<I, D>, slot: u8) -> error::Result<Self> {
+        for _ in 0..MAX_GENERATE_ATTEMPTS {
+            let point = bus.session(|bus| {
+                bus.command(
+                    OP_GENKEY,
+                    GENKEY_PRIVATE,
+                    slot as u16,
+                    &[],
+                    GENKEY_DELAY,
+                    64,
+                )
+            })?;
+            match public_key_from_point(&point) {
+                Ok(public_key) => return Ok(Self::from_parts(network, public_key, bus, slot)),
+                Err(error::Error::Decode(error::DecodeError::NotCompact)) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(error::not_compact())
+    }
+
+    /// Agree on a shared secret with the given P-256 peer point on the chip.
+    /// The peer point does not need a compact form, so ephemeral keys work,
+    /// and the slot has to allow ECDH in its configuration.
+    pub fn diffie_hellman(&self, peer: &p256::PublicKey) -> error::Result<ecdh::SharedSecret> {
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        let point = peer.to_encoded_point(false);
+        let mut bus = self.inner.bus.lock().expect("ecc608 lock");
+        let shared = bus.session(|bus| {
+            bus.command(
+                OP_ECDH,
+                ECDH_CLEAR,
+                self.inner.slot as u16,
+                &point.as_bytes()[1..],
+                ECDH_DELAY,
+                ecdh::SHARED_SECRET_LENGTH,
+            )
+        })?;
+        Ok(ecdh::SharedSecret::from_slice(&shared))
+    }
+
+    /// Attest that the key was generated on the chip, with an internal sign
+    /// by the attestation key in the given slot over the public key digest of
+    /// the key and the given challenge. The attestation slot has to allow
+    /// internal signs in its configuration.
+    #[cfg(feature = "attestation")]
+    pub fn attest(&self, attestation_slot: u8, challenge: &[u8]) -> error::Result<Attestation> {
+        check_slot(attestation_slot)?;
+        let nonce = Sha256::digest(challenge);
+        let mut bus = self.inner.bus.lock().expect("ecc608 lock");
+        let (config, point, signature) = bus.session(|bus| {
+            let config = bus.read_config()?;
+            bus.command(OP_NONCE, NONCE_PASSTHROUGH, 0, &nonce, NONCE_DELAY, 0)?;
+            let point = bus.command(
+                OP_GENKEY,
+                attestation::ECC608_GENKEY_DIGEST,
+                self.inner.slot as u16,
+                &[],
+                GENKEY_DELAY,
+                64,
+            )?;
+            let signature = bus.command(
+                OP_SIGN,
+                attestation::ECC608_SIGN_INTERNAL,
+                attestation_slot as u16,
+                &[],
+                SIGN_DELAY,
+                64,
+            )?;
+            Ok((config, point, signature))
+        })?;
+        let (mut attested_point, mut attestation_signature) = (
+            [0u8; attestation::ECC608_POINT_LENGTH],
+            [0u8; attestation::SIGNATURE_LENGTH],
+        );
+        attested_point.copy_from_slice(&point);
+        attestation_signature.copy_from_slice(&signature);
+        Ok(Attestation::Ecc608 {
+            config,
+            key_slot: self.inner.slot,
+            attestation_slot,
+            point: attested_point,
+            signature: attestation_signature,
+        })
+    }
+
+    /// The public key of the attestation key in the given slot, for
+    /// verifiers to enroll.
+    #[cfg(feature = "attestation")]
+    pub fn attestation_key(&self, attestation_slot: u8) -> error::Result<p256::PublicKey> {
+        check_slot(attestation_slot)?;
+        let mut bus = self.inner.bus.lock().expect("ecc608 lock");
+        let point = bus.session(|bus| {
+            bus.command(
+                OP_GENKEY,
+                GENKEY_PUBLIC,
+                attestation_slot as u16,
+                &[],
+                GENKEY_DELAY,
+                64,
+            )
+        })?;
+        let mut sec1 = [0u8; ecc_compact::SEC1_UNCOMPRESSED_LENGTH];
+        sec1[0] = 0x04;
+        sec1[1..].copy_from_slice(&point);
+        p256::PublicKey::from_sec1_bytes(&sec1).map_err(error::Error::from)
+    }
+
+    fn from_parts(
+        network: Network,
+        public_key: ecc_compact::PublicKey,
+        bus: Bus<I, D>,
+        slot: u8,
+    ) -> Self {
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            inner: Ecc608 {
+                bus: Mutex::new(bus),
+                slot,
+            },
+        }
+    }
+}
+
+/// A connection to a chip for provisioning it, before its key slots are used.
+pub struct Chip<I, D> {
+    bus: Bus<I, D>,
+}
+
+impl<I, D, E> Chip<I, D>
+where
+    I: Read<Error = E> + Write<Error = E>,
+    D: DelayMs<u32>,
+{
+    /// Connect to the chip at the given address.
+    pub fn new(i2c: I, delay: D, address: u8) -> Self {
+        Self {
+            bus: Bus::new(i2c, delay, address),
+        }
+    }
+
+    /// The serial number of the chip.
+    pub fn serial(&mut self) -> error::Result<[u8; SERIAL_LENGTH]> {
+        let config = self.read_config()?;
+        let mut result = [0u8; SERIAL_LENGTH];
+        result[..4].copy_from_slice(&config[..4]);
+        result[4..].copy_from_slice(&config[8..13]);
+        Ok(result)
+    }
+
+    /// Read the configuration zone.
+    pub fn read_config(&mut self) -> error::Result<[u8; CONFIG_LENGTH]> {
+        self.bus.session(|bus| bus.read_config())
+    }
+
+    /// Write the given configuration zone. The read only bytes at the start
+    /// and the lock bytes are not written, so a configuration read from
+    /// another chip can be written as is. Fails once the configuration zone is
+    /// locked.
+    pub fn write_config(&mut self, config: &[u8; CONFIG_LENGTH]) -> error::Result {
+        for word in writable_config_words() {
+            let data = &config[word * 4..word * 4 + 4];
+            self.bus.session(|bus| {
+                bus.command(OP_WRITE, ZONE_CONFIG, word as u16, data, WRITE_DELAY, 0)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Whether the configuration and data zones are locked.
+    pub fn is_locked(&mut self) -> error::Result<(bool, bool)> {
+        let config = self.read_config()?;
+        Ok((
+            config[CONFIG_LOCK_CONFIG] != UNLOCKED,
+            config[CONFIG_LOCK_VALUE] != UNLOCKED,
+        ))
+    }
+
+    /// Lock the configuration zone. The chip only locks it when the checksum
+    /// of its configuration matches the given one, which has to be the
+    /// configuration that was written.
+    pub fn lock_config(&mut self, config: &[u8; CONFIG_LENGTH]) -> error::Result {
+        let mut expected = self.read_config()?;
+        // Only the writable bytes come from the written configuration
+        for word in writable_config_words() {
+            expected[word * 4..word * 4 + 4].copy_from_slice(&config[word * 4..word * 4 + 4]);
+        }
+        let crc = crc16(&expected);
+        self.bus
+            .session(|bus| bus.command(OP_LOCK, LOCK_CONFIG, crc, &[], LOCK_DELAY, 0))?;
+        Ok(())
+    }
+
+    /// Lock the data and OTP zones. Slots set up for it in the configuration
+    /// can still be written or have keys generated in them afterwards.
+    pub fn lock_data(&mut self) -> error::Result {
+        self.bus
+            .session(|bus| bus.command(OP_LOCK, LOCK_DATA | LOCK_NO_CRC, 0, &[], LOCK_DELAY, 0))?;
+        Ok(())
+    }
+
+    /// Lock a single slot, permanently, when its configuration allows it.
+    pub fn lock_slot(&mut self, slot: u8) -> error::Result {
+        check_slot(slot)?;
+        self.bus.session(|bus| {
+            bus.command(
+                OP_LOCK,
+                LOCK_SLOT | LOCK_NO_CRC | (slot << 2),
+                0,
+                &[],
+                LOCK_DELAY,
+                0,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Write 32 bytes of data to the block at the given offset of a slot,
+    /// like a certificate, while the data zone is unlocked or when the slot
+    /// configuration allows clear writes.
+    pub fn write_slot(&mut self, slot: u8, block: u8, data: &[u8; 32]) -> error::Result {
+        check_slot(slot)?;
+        let address = ((block as u16) << 8) | ((slot as u16) << 3);
+        self.bus.session(|bus| {
+            bus.command(
+                OP_WRITE,
+                ZONE_DATA | ZONE_BLOCK,
+                address,
+                data,
+                WRITE_DELAY,
+                0,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Generate a key in the given slot and hand the chip over to a keypair
+    /// for it, as [`Keypair::generate`] does.
+    pub fn generate_keypair(self, network: Network, slot: u8) -> error::Result<Keypair<I, D>> {
+        check_slot(slot)?;
+        Keypair::generate_on(network, self.bus, slot)
+    }
+
+    /// Write the secret key of the given keypair into the given slot and hand
+    /// the chip over to a keypair for it, after checking that the chip derives
+    /// the same public key from it. The keypair keeps its network.
+    ///
+    /// While the data zone is unlocked the key is written in the clear and
+    /// `write_key` should be `None`. Once the data zone is locked the chip only
+    /// accepts encrypted writes to slots configured for them, and `write_key`
+    /// has to be the slot number and value of the slot's write key. The key is
+    /// then encrypted under a session key derived from the write key and a
+    /// random nonce, with the nonce input drawn from the given entropy source.
+    pub fn import_keypair<E>(
+        mut self,
+        keypair: &ecc_compact::Keypair,
+        slot: u8,
+        write_key: Option<(u8, &[u8; 32])>,
+        entropy: &mut E,
+    ) -> error::Result<Keypair<I, D>>
+    where
+        E: EntropySource + ?Sized,
+    {
+        check_slot(slot)?;
+        let encryption = match write_key {
+            Some((write_slot, write_key)) => {
+                check_slot(write_slot)?;
+                let mut nonce_input = [0u8; NONCE_INPUT_LENGTH];
+                entropy.fill_entropy(&mut nonce_input)?;
+                Some((write_slot, write_key, nonce_input, self.read_config()?))
+            }
+            None => None,
+        };
+        let mut value = [0u8; PRIVWRITE_VALUE_LENGTH];
+        value[4..].copy_from_slice(&keypair.inner.to_bytes());
+        let written = self.bus.session(|bus| match &encryption {
+            None => bus.command(OP_PRIVWRITE, 0, slot as u16, &value, PRIVWRITE_DELAY, 0),
+            Some((write_slot, write_key, nonce_input, config)) => {
+                let random =
+                    bus.command(OP_NONCE, NONCE_RANDOM, 0, nonce_input, NONCE_DELAY, 32)?;
+                bus.command(
+                    OP_GENDIG,
+                    ZONE_DATA,
+                    *write_slot as u16,
+                    &[],
+                    GENDIG_DELAY,
+                    0,
+                )?;
+                let mut temp_key = gendig_temp_key(
+                    config,
+                    *write_slot,
+                    write_key,
+                    &nonce_temp_key(&random, nonce_input),
+                );
+                let mut data = privwrite_data(config, slot, &temp_key, &value);
+                temp_key.zeroize();
+                let written = bus.command(
+                    OP_PRIVWRITE,
+                    PRIVWRITE_ENCRYPTED,
+                    slot as u16,
+                    &data,
+                    PRIVWRITE_DELAY,
+                    0,
+                );
+                data.zeroize();
+                written
+            }
+        });
+        value.zeroize();
+        written?;
+        let imported = Keypair::open_on(keypair.network, self.bus, slot)?;
+        if imported.public_key != keypair.public_key {
+            return Err(error::device_key_mismatch(&format!("ecc608 slot {}", slot)));
+        }
+        Ok(imported)
+    }
+
+    /// Hand the chip over to a keypair for the key in the given slot, as
+    /// [`Keypair::open`] does.
+    pub fn open_keypair(self, network: Network, slot: u8) -> error::Result<Keypair<I, D>> {
+        check_slot(slot)?;
+        Keypair::open_on(network, self.bus, slot)
+    }
+}
+
+/// The words of the configuration zone that can be written: all but the read
+/// only first 16 bytes and the word with the lock bytes, which only change by
+/// locking.
+fn writable_config_words() -> impl Iterator<Item = usize> {
+    (4..CONFIG_LENGTH / 4).filter(|word| *word != CONFIG_LOCK_WORD)
+}
+
+fn check_slot(slot: u8) -> error::Result {
+    if slot > MAX_SLOT {
+        return Err(error::invalid_ecc608_slot(slot));
+    }
+    Ok(())
+}
+
+/// The serial number bytes that key the digests of writes to the chip.
+fn serial_digest_bytes(config: &[u8; CONFIG_LENGTH]) -> [u8; 3] {
+    [config[12], config[0], config[1]]
+}
+
+/// The TempKey the chip computes for a random nonce, over its random output,
+/// the nonce input and the command.
+fn nonce_temp_key(random: &[u8], nonce_input: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(random);
+    hasher.update(nonce_input);
+    hasher.update([OP_NONCE, NONCE_RANDOM, 0x00]);
+    hasher.finalize().into()
+}
+
+/// The TempKey the chip computes when generating a digest with the key in a
+/// data slot from the previous TempKey.
+fn gendig_temp_key(
+    config: &[u8; CONFIG_LENGTH],
+    slot: u8,
+    key: &[u8; 32],
+    temp_key: &[u8; 32],
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(key);
+    hasher.update([OP_GENDIG, ZONE_DATA]);
+    hasher.update((slot as u16).to_le_bytes());
+    hasher.update(serial_digest_bytes(config));
+    hasher.update([0u8; 25]);
+    hasher.update(temp_key);
+    hasher.finalize().into()
+}
+
+/// The data of an encrypted private key write: the value encrypted with
+/// TempKey, extended by the start of its digest, followed by the MAC over the
+/// plaintext value.
+fn privwrite_data(
+    config: &[u8; CONFIG_LENGTH],
+    slot: u8,
+    temp_key: &[u8; 32],
+    value: &[u8; PRIVWRITE_VALUE_LENGTH],
+) -> Vec<u8> {
+    let pad = Sha256::digest(temp_key);
+    let mut result: Vec<u8> = value
+        .iter()
+        .zip(temp_key.iter().chain(&pad[..4]))
+        .map(|(value, key)| value ^ key)
+        .collect();
+    let mut mac = Sha256::new();
+    mac.update(temp_key);
+    mac.update([OP_PRIVWRITE, PRIVWRITE_ENCRYPTED]);
+    mac.update((slot as u16).to_le_bytes());
+    mac.update(serial_digest_bytes(config));
+    mac.update([0u8; 21]);
+    mac.update(value);
+    result.extend_from_slice(&mac.finalize());
+    result
+}
+
+/// The chip returns public keys as the raw x and y coordinates.
+fn public_key_from_point(point: &[u8]) -> error::Result<ecc_compact::PublicKey> {
+    let mut sec1 = [0u8; ecc_compact::SEC1_UNCOMPRESSED_LENGTH];
+    sec1[0] = 0x04;
+    sec1[1..].copy_from_slice(point);
+    ecc_compact::PublicKey::from_sec1_point(&sec1)
+}
+
+impl<I, D, E> Bus<I, D>
+where
+    I: Read<Error = E> + Write<Error = E>,
+    D: DelayMs<u32>,
+{
+    fn new(i2c: I, delay: D, address: u8) -> Self {
+        Self {
+            i2c,
+            delay,
+            address,
+        }
+    }
+
+    /// Reads the whole configuration zone, which is readable whether or not
+    /// it is locked.
+    fn read_config(&mut self) -> error::Result<[u8; CONFIG_LENGTH]> {
+        let mut config = [0u8; CONFIG_LENGTH];
+        for (block, chunk) in config.chunks_mut(32).enumerate() {
+            let data = self.command(
+                OP_READ,
+                ZONE_CONFIG | ZONE_BLOCK,
+                (block as u16) << 3,
+                &[],
+                READ_DELAY,
+                32,
+            )?;
+            chunk.copy_from_slice(&data);
+        }
+        Ok(config)
+    }
+
+    /// Runs the given commands between waking up the chip and sending it back
+    /// to idle. The chip's watchdog puts it to sleep about a second after
+    /// waking up, so sessions need to be short.
+    fn session<T, F>(&mut self, f: F) -> error::Result<T>
+    where
+        F: FnOnce(&mut Self) -> error::Result<T>,
+    {
+        self.wake()?;
+        let result = f(self);
+        // Idle keeps TempKey valid only until the next wake, which is fine
+        // since every session loads what it needs
+        let idle = self
+            .i2c
+            .write(self.address, &[WORD_IDLE])
+            .map_err(|_| error::ecc608_bus());
+        let result = result?;
+        idle.map(|_| result)
+    }
+
+    fn wake(&mut self) -> error::Result {
+        // Holding SDA low long enough wakes the chip. Writing a zero byte to
+        // the general call address does that at standard bus speeds, and the
+        // write itself is not acknowledged.
+        let _ = self.i2c.write(WORD_RESET, &[0x00]);
+        self.delay.delay_ms(WAKE_DELAY);
+        let mut response = [0u8; 4];
+        self.i2c
+            .read(self.address, &mut response)
+            .map_err(|_| error::ecc608_bus())?;
+        if response != WAKE_RESPONSE {
+            return Err(error::invalid_ecc608_response());
+        }
+        Ok(())
+    }
+
+    /// Sends a command and waits for its response. A `response_len` of zero
+    /// expects a bare status byte, which has to indicate success.
+    fn command(
+        &mut self,
+        opcode: u8,
+        param1: u8,
+        param2: u16,
+        data: &[u8],
+        exec_delay: u32,
+        response_len: usize,
+    ) -> error::Result<Vec<u8>> {
+        self.i2c
+            .write(self.address, &packet(opcode, param1, param2, data))
+            .map_err(|_| error::ecc608_bus())?;
+        self.delay.delay_ms(exec_delay);
+        let mut response = vec![0u8; response_len.max(1) + 3];
+        self.i2c
+            .read(self.address, &mut response)
+            .map_err(|_| error::ecc608_bus())?;
+        let response = check_response(&response)?;
+        match (response, response_len) {
+            ([0x00], 0) => Ok(Vec::new()),
+            ([status], _) => Err(error::ecc608_status(*status)),
+            (data, len) if data.len() == len => Ok(data.to_vec()),
+            _ => Err(error::invalid_ecc608_response()),
+        }
+    }
+}
+
+/// Builds a command packet: the command word address followed by the count,
+/// opcode, parameters, data and a checksum over all but the word address.
+fn packet(opcode: u8, param1: u8, param2: u16, data: &[u8]) -> Vec<u8> {
+    let mut result = vec![WORD_COMMAND, (data.len() + 7) as u8, opcode, param1];
+    result.extend_from_slice(&param2.to_le_bytes());
+    result.extend_from_slice(data);
+    let crc = crc16(&result[1..]);
+    result.extend_from_slice(&crc.to_le_bytes());
+    result
+}
+
+/// Checks the count and checksum of a response, returning the data in it.
+fn check_response(response: &[u8]) -> error::Result<&[u8]> {
+    let count = *response
+        .first()
+        .ok_or_else(error::invalid_ecc608_response)? as usize;
+    if count < 4 || count > response.len() {
+        return Err(error::invalid_ecc608_response());
+    }
+    let (body, crc) = response[..count].split_at(count - 2);
+    if crc16(body).to_le_bytes() != crc {
+        return Err(error::invalid_ecc608_response());
+    }
+    Ok(&body[1..])
+}
+
+/// The CRC-16 used by the chip, with polynomial 0x8005 over bit reversed input
+/// and no final reflection.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        for bit in 0..8 {
+            let data_bit = (byte >> bit) & 1;
+            let crc_bit = (crc >> 15) as u8;
+            crc <<= 1;
+            if data_bit != crc_bit {
+                crc ^= 0x8005;
+            }
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_response, crc16, packet, privwrite_data, CONFIG_LENGTH, PRIVWRITE_VALUE_LENGTH,
+        WAKE_RESPONSE,
+    };
+
+    #[test]
+    fn checksum() {
+        assert_eq!(
+            u16::from_le_bytes([WAKE_RESPONSE[2], WAKE_RESPONSE[3]]),
+            crc16(&WAKE_RESPONSE[..2])
+        );
+        assert_eq!(&[0x11], check_response(&WAKE_RESPONSE).expect("response"));
+        // A packet carries its own checksum past the word address
+        let packet = packet(0x41, 0x80, 0x0002, &[]);
+        assert_eq!(7, packet[1]);
+        assert_eq!(&[0x41, 0x80, 0x02, 0x00], &packet[2..6]);
+        assert!(check_response(&packet[1..]).is_ok());
+    }
+
+    #[test]
+    fn encrypted_write() {
+        let config = [0x5au8; CONFIG_LENGTH];
+        let temp_key = [0x11u8; 32];
+        let mut value = [0u8; PRIVWRITE_VALUE_LENGTH];
+        value[4..].copy_from_slice(&[0x22; 32]);
+        let data = privwrite_data(&config, 2, &temp_key, &value);
+        assert_eq!(PRIVWRITE_VALUE_LENGTH + 32, data.len());
+        // The first 32 bytes are encrypted with TempKey itself
+        assert_eq!([0x11u8; 4], data[..4]);
+        assert!(data[4..32].iter().all(|byte| *byte == 0x11 ^ 0x22));
+        assert_ne!(data, privwrite_data(&config, 3, &temp_key, &value));
+    }
+}
@@ -1,93 +1,412 @@
-use crate::{error, keypair, public_key, IntoBytes, KeyTag, KeyType, Network};
+#[cfg(feature = "pkcs8")]
+use crate::pkcs8;
+use crate::{
+    ecdh,
+    entropy::{check_secret, EntropyRng, EntropySource},
+    error::{self, EntropyError},
+    keypair,
+    keypair::{FixedSha256, Prehash},
+    output_prefix,
+    public_key::{self, VerifyOptions},
+    secret::Secret,
+    IntoBytes, KeyTag, KeyType, Network,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
 use p256::{
     ecdsa,
     elliptic_curve::{sec1::ToCompactEncodedPoint, weierstrass::DecompactPoint},
     FieldBytes,
 };
-use std::convert::TryFrom;
+use sha2::Sha256;
+#[cfg(feature = "std")]
+use std::{sync::mpsc, thread};
+use zeroize::Zeroize;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct PublicKey(p256::PublicKey);
 
+/// Domain separation for the candidates of `Keypair::generate_from_seed`.
+const SEED_DOMAIN: &[u8] = b"helium ecc_compact seed";
+/// Half of all candidates are accepted, so running out of candidates has
+/// probability 2^-128.
+const MAX_SEED_CANDIDATES: u32 = 128;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Signature(ecdsa::Signature);
 
-pub type Keypair = keypair::Keypair<p256::SecretKey>;
+/// The signing key is kept rather than the secret key it is built from, so it
+/// does not need to be rebuilt for every signature.
+pub type Keypair = keypair::Keypair<Secret<ecdsa::SigningKey>>;
+
+#[cfg(any(test, feature = "test-utils"))]
+impl keypair::DebugSecret for Keypair {
+    fn secret_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes().to_vec()
+    }
+}
 
 pub const KEYPAIR_LENGTH: usize = 33;
 
+/// The length of a SEC1 compressed point.
+pub const SEC1_COMPRESSED_LENGTH: usize = 33;
+/// The length of a SEC1 uncompressed point.
+pub const SEC1_UNCOMPRESSED_LENGTH: usize = 65;
+
+/// The maximum length of a DER encoded P-256 ECDSA signature.
+pub const MAX_SIGNATURE_LENGTH: usize = 72;
+
+/// DER prefix of a P-256 SubjectPublicKeyInfo up to the uncompressed point.
+pub(crate) const P256_SPKI_PREFIX: &[u8] = &[
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
 impl keypair::Sign for Keypair {
+    #[cfg(feature = "alloc")]
     fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
         use signature::Signer;
         let signature = self.try_sign(msg)?;
         Ok(signature.0.to_der().as_bytes().to_vec())
     }
+
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        use signature::Signer;
+        let signature = self.try_sign(msg)?;
+        keypair::copy_signature(signature.0.to_der().as_bytes(), output)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn sign_digest(&self, digest: Prehash) -> error::Result<Vec<u8>> {
+        match digest {
+            Prehash::Sha256(digest) => self.sign_sha256(digest),
+            Prehash::Sha256Digest(_) | Prehash::Sha512(_) | Prehash::Sha512Digest(_) => {
+                Err(error::unsupported_prehash())
+            }
+        }
+    }
 }
 
 impl TryFrom<&[u8]> for Keypair {
     type Error = error::Error;
     fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != KEYPAIR_LENGTH {
+            return Err(error::invalid_length(KEYPAIR_LENGTH, input.len()));
+        }
         let network = Network::try_from(input[0])?;
-        let inner = p256::SecretKey::from_bytes(&input[1..])?;
-        let public_key = public_key::PublicKey::for_network(network, PublicKey(inner.public_key()));
+        let secret = p256::SecretKey::from_bytes(&input[1..])?;
+        let public_key =
+            public_key::PublicKey::for_network(network, PublicKey(secret.public_key()));
         Ok(Keypair {
             network,
             public_key,
-            inner,
+            inner: ecdsa::SigningKey::from(secret).into(),
         })
     }
 }
 
 impl IntoBytes for Keypair {
-    fn bytes_into(&self, output: &mut [u8]) {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let output = output_prefix(output, KEYPAIR_LENGTH)?;
         output[0] = u8::from(KeyTag {
             network: self.network,
             key_type: KeyType::EccCompact,
         });
-        output[1..].copy_from_slice(&self.inner.to_bytes());
+        let mut secret = self.inner.to_bytes();
+        output[1..].copy_from_slice(&secret);
+        secret[..].zeroize();
+        Ok(KEYPAIR_LENGTH)
     }
 }
 
 impl Keypair {
-    pub fn generate<R>(network: Network, csprng: &mut R) -> Keypair
+    /// Length of the binary form of keypairs of this type.
+    pub const LEN: usize = KEYPAIR_LENGTH;
+
+    pub fn generate<E>(network: Network, entropy: &mut E) -> Keypair
     where
-        R: rand_core::CryptoRng + rand_core::RngCore,
+        E: EntropySource + ?Sized,
     {
-        let mut inner = p256::SecretKey::random(&mut *csprng);
-        let mut public_key = inner.public_key();
-        while !bool::from(public_key.as_affine().is_compactable()) {
-            inner = p256::SecretKey::random(&mut *csprng);
-            public_key = inner.public_key();
-        }
+        let (secret, public_key) =
+            compactable_secret(p256::SecretKey::random(&mut EntropyRng(entropy)));
         Keypair {
             network,
             public_key: public_key::PublicKey::for_network(network, PublicKey(public_key)),
-            inner,
+            inner: ecdsa::SigningKey::from(secret).into(),
+        }
+    }
+
+    /// Generate a keypair by rejecting candidates that are not compactable
+    /// rather than negating them, drawing at most `max_retries` further
+    /// candidates after the first. Fails with `DecodeError::NotCompact` when
+    /// none of them is compactable.
+    pub fn generate_with_retries<E>(
+        network: Network,
+        entropy: &mut E,
+        max_retries: u32,
+    ) -> error::Result<Keypair>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let mut candidate = [0u8; 32];
+        for _ in 0..=max_retries {
+            entropy.fill_entropy(&mut candidate)?;
+            let result = Self::generate_from_entropy_unchecked(network, &candidate);
+            candidate.zeroize();
+            if let Ok(keypair) = result {
+                return Ok(keypair);
+            }
+        }
+        Err(error::not_compact())
+    }
+
+    /// Generate a keypair using the operating system random number generator.
+    #[cfg(feature = "std")]
+    pub fn generate_default(network: Network) -> Keypair {
+        Self::generate(network, &mut rand_core::OsRng)
+    }
+
+    /// Replace the secret of this keypair with a freshly generated one for the
+    /// same network, zeroizing the old secret. The returned statement that the
+    /// new key replaces the old one as of `timestamp` is signed by both keys,
+    /// see [`rotation`](crate::rotation).
+    #[cfg(feature = "alloc")]
+    pub fn rekey<E>(
+        &mut self,
+        entropy: &mut E,
+        timestamp: u64,
+    ) -> error::Result<crate::rotation::Rotation>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let new = Self::generate(self.network, entropy);
+        self.rotate(new, timestamp)
+    }
+
+    /// Generate `count` keypairs spread over all available cores, using the
+    /// operating system random number generator. Keypairs are delivered through
+    /// the returned channel as they are generated; dropping the receiver stops
+    /// any remaining generation.
+    #[cfg(feature = "std")]
+    pub fn generate_batch(network: Network, count: usize) -> mpsc::Receiver<Keypair> {
+        let (tx, rx) = mpsc::channel();
+        let threads = thread::available_parallelism()
+            .map(usize::from)
+            .unwrap_or(1)
+            .min(count)
+            .max(1);
+        for i in 0..threads {
+            let tx = tx.clone();
+            let share = count / threads + usize::from(i < count % threads);
+            thread::spawn(move || {
+                for _ in 0..share {
+                    let keypair = Keypair::generate_default(network);
+                    if tx.send(keypair).is_err() {
+                        break;
+                    }
+                }
+            });
         }
+        rx
     }
 
+    /// Construct a keypair from a 32 byte secret key candidate, negating the
+    /// secret if needed to make its public key compactable.
+    pub(crate) fn from_candidate(network: Network, candidate: &[u8]) -> error::Result<Keypair> {
+        let (secret, public_key) = compactable_secret(p256::SecretKey::from_bytes(candidate)?);
+        Ok(Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, PublicKey(public_key)),
+            inner: ecdsa::SigningKey::from(secret).into(),
+        })
+    }
+
+    /// Construct a keypair for the given network from a p256 secret key. Fails
+    /// with `DecodeError::NotCompact` if its public key is not compactable.
+    pub fn from_secret_key(network: Network, secret: p256::SecretKey) -> error::Result<Keypair> {
+        let public_key = PublicKey::try_from(secret.public_key())?;
+        Ok(Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            inner: ecdsa::SigningKey::from(secret).into(),
+        })
+    }
+
+    /// Construct a keypair from 32 bytes of entropy used as the secret key.
+    /// The entropy is rejected with a distinct [`EntropyError`] when it has
+    /// the wrong length, is all zero, is not below the curve order or has too
+    /// few distinct bytes to be random. Fails with `DecodeError::NotCompact`
+    /// if the public key of the secret is not compactable.
+    ///
+    /// [`EntropyError`]: crate::error::EntropyError
     pub fn generate_from_entropy(network: Network, entropy: &[u8]) -> error::Result<Keypair> {
-        let inner = p256::SecretKey::from_bytes(entropy)?;
-        let public_key = inner.public_key();
+        check_secret(entropy, 32)?;
+        p256::SecretKey::from_bytes(entropy)
+            .map_err(|_| error::weak_entropy(EntropyError::OutOfRange))?;
+        Self::generate_from_entropy_unchecked(network, entropy)
+    }
+
+    /// Construct a keypair from 32 bytes of entropy without the checks of
+    /// `generate_from_entropy`, for secrets that come from a derivation
+    /// rather than a random source.
+    pub fn generate_from_entropy_unchecked(
+        network: Network,
+        entropy: &[u8],
+    ) -> error::Result<Keypair> {
+        let secret = p256::SecretKey::from_bytes(entropy)?;
+        let public_key = secret.public_key();
         if !bool::from(public_key.as_affine().is_compactable()) {
             return Err(error::not_compact());
         }
         Ok(Keypair {
             network,
             public_key: public_key::PublicKey::for_network(network, PublicKey(public_key)),
-            inner,
+            inner: ecdsa::SigningKey::from(secret).into(),
         })
     }
 
-    pub fn to_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
-        let mut result = [0u8; KEYPAIR_LENGTH];
-        self.bytes_into(&mut result);
+    /// Deterministically derive a keypair from a seed of any length. Unlike
+    /// `generate_from_entropy` this always succeeds, since candidates that are
+    /// not a valid secret or not compactable are rejected and the next one is
+    /// tried.
+    ///
+    /// Candidate `i`, counting from zero, is
+    /// `HMAC-SHA256(key = seed, data = "helium ecc_compact seed" || be32(i))`.
+    /// The first candidate that is a scalar in `[1, n)` with a compactable
+    /// public key is the secret key. Candidates are never negated, so other
+    /// implementations only need an HMAC and a compactability check to derive
+    /// the same key.
+    pub fn generate_from_seed(network: Network, seed: &[u8]) -> error::Result<Keypair> {
+        use hmac::{Hmac, Mac, NewMac};
+        for counter in 0..MAX_SEED_CANDIDATES {
+            let mut mac = Hmac::<Sha256>::new_from_slice(seed).expect("hmac key");
+            mac.update(SEED_DOMAIN);
+            mac.update(&counter.to_be_bytes());
+            let mut candidate = mac.finalize().into_bytes();
+            let result = Self::generate_from_entropy_unchecked(network, &candidate);
+            candidate[..].zeroize();
+            if let Ok(keypair) = result {
+                return Ok(keypair);
+            }
+        }
+        Err(error::not_compact())
+    }
+
+    /// Sign a SHA-256 digest of a message that has been fed in
+    /// incrementally.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn sign_sha256(&self, digest: Sha256) -> error::Result<Vec<u8>> {
+        use signature::DigestSigner;
+        let signature: ecdsa::Signature = self.inner.try_sign_digest(digest)?;
+        Ok(normalize_s(signature)?.to_der().as_bytes().to_vec())
+    }
+
+    /// Sign the given message, returning the fixed size `r || s` form of the
+    /// signature for protocols that need fixed size signatures.
+    pub fn sign_raw(&self, msg: &[u8]) -> error::Result<[u8; RAW_SIGNATURE_LENGTH]> {
+        use signature::Signer;
+        Ok(self.try_sign(msg)?.to_raw())
+    }
+
+    /// Agree on a shared secret with the given ecc_compact peer key.
+    pub fn ecdh(&self, peer: &public_key::PublicKey) -> error::Result<ecdh::SharedSecret> {
+        let peer = PublicKey::try_from(peer)?;
+        let secret = self.secret_key();
+        let shared = p256::ecdh::diffie_hellman(secret.secret_scalar(), peer.0.as_affine());
+        Ok(ecdh::SharedSecret::from_slice(shared.as_bytes()))
+    }
+
+    export_secret! {
+        fn to_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
+            let mut result = [0u8; KEYPAIR_LENGTH];
+            self.try_bytes_into(&mut result).expect("keypair length");
+            result
+        }
+    }
+
+    /// The RustCrypto secret key of this keypair.
+    pub(crate) fn secret_key(&self) -> p256::SecretKey {
+        p256::SecretKey::from_bytes(&self.inner.to_bytes()).expect("signing key")
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+impl Keypair {
+    export_secret! {
+        /// Encode the keypair as an unencrypted PKCS#8 document in DER form.
+        fn to_pkcs8_der(&self) -> Vec<u8> {
+            let public_key = PublicKey::try_from(&self.public_key).expect("ecc_compact public key");
+            pkcs8::encode_ec(
+                KeyType::EccCompact,
+                &self.inner.to_bytes(),
+                &public_key.to_sec1_uncompressed(),
+            )
+        }
+    }
+
+    /// Decode a DER encoded PKCS#8 P-256 key for the given network. Fails with
+    /// `DecodeError::NotCompact` if its public key is not compactable.
+    pub fn from_pkcs8_der(network: Network, input: &[u8]) -> error::Result<Keypair> {
+        let (key_type, mut secret) = pkcs8::decode(input)?;
+        let result = match key_type {
+            KeyType::EccCompact => p256::SecretKey::from_bytes(&secret)
+                .map_err(error::Error::from)
+                .and_then(|secret| Self::from_secret_key(network, secret)),
+            _ => Err(error::invalid_keytype(u8::from(KeyTag {
+                network,
+                key_type,
+            }))),
+        };
+        secret[..].zeroize();
         result
     }
+
+    export_secret! {
+        /// Encode the keypair as an unencrypted PKCS#8 PEM block.
+        fn to_pkcs8_pem(&self) -> String {
+            pkcs8::to_pem(&self.to_pkcs8_der())
+        }
+    }
+
+    /// Decode a PKCS#8 PEM block holding a P-256 key for the given network.
+    pub fn from_pkcs8_pem(network: Network, input: &str) -> error::Result<Keypair> {
+        Self::from_pkcs8_der(network, &pkcs8::from_pem(input)?)
+    }
+}
+
+#[cfg(not(feature = "no-export"))]
+impl From<&Keypair> for p256::SecretKey {
+    fn from(v: &Keypair) -> Self {
+        v.secret_key()
+    }
+}
+
+#[cfg(not(feature = "no-export"))]
+impl From<&Keypair> for ecdsa::SigningKey {
+    fn from(v: &Keypair) -> Self {
+        (*v.inner).clone()
+    }
+}
+
+/// Turns the given secret key into one with a compactable public key. A point
+/// (x, y) that is not compactable has a compactable negation (x, p - y), which
+/// belongs to the negated secret scalar n - d. Negating instead of resampling
+/// means every sampled secret yields a usable key.
+fn compactable_secret(secret: p256::SecretKey) -> (p256::SecretKey, p256::PublicKey) {
+    let public_key = secret.public_key();
+    if bool::from(public_key.as_affine().is_compactable()) {
+        return (secret, public_key);
+    }
+    let scalar = p256::Scalar::from_bytes_reduced(&secret.to_bytes());
+    let secret = p256::SecretKey::from_bytes(&(-scalar).to_bytes()).expect("non zero scalar");
+    let public_key =
+        p256::PublicKey::from_affine(-*public_key.as_affine()).expect("negated public key");
+    (secret, public_key)
 }
 
 impl signature::Signature for Signature {
-    fn from_bytes(input: &[u8]) -> std::result::Result<Self, signature::Error> {
+    fn from_bytes(input: &[u8]) -> core::result::Result<Self, signature::Error> {
         Ok(Signature(signature::Signature::from_bytes(input)?))
     }
 
@@ -102,27 +421,160 @@ impl AsRef<[u8]> for Signature {
     }
 }
 
+impl Signature {
+    /// Parse a DER encoded signature.
+    pub fn from_der(input: &[u8]) -> error::Result<Self> {
+        Ok(Signature(ecdsa::Signature::from_der(input)?))
+    }
+
+    /// The DER encoding of the signature, as produced by `Sign::sign`.
+    #[cfg(feature = "alloc")]
+    pub fn to_der(&self) -> Vec<u8> {
+        self.0.to_der().as_bytes().to_vec()
+    }
+
+    /// Write the DER encoding of the signature into the start of the given
+    /// output slice, returning its length.
+    pub(crate) fn der_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        keypair::copy_signature(self.0.to_der().as_bytes(), output)
+    }
+
+    /// Parse a fixed size `r || s` signature with both values big endian.
+    pub fn from_raw(input: &[u8]) -> error::Result<Self> {
+        Ok(Signature(signature::Signature::from_bytes(input)?))
+    }
+
+    /// The fixed size `r || s` form of the signature.
+    pub fn to_raw(&self) -> [u8; RAW_SIGNATURE_LENGTH] {
+        let mut result = [0u8; RAW_SIGNATURE_LENGTH];
+        result.copy_from_slice(self.0.as_ref());
+        result
+    }
+}
+
 impl signature::Signer<Signature> for Keypair {
-    fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
-        // TODO: Thre has to be a way to avoid cloning for every signature?
-        Ok(Signature(
-            p256::ecdsa::SigningKey::from(self.inner.clone()).sign(msg),
-        ))
+    fn try_sign(&self, msg: &[u8]) -> core::result::Result<Signature, signature::Error> {
+        let signature: ecdsa::Signature = signature::Signer::try_sign(&*self.inner, msg)?;
+        Ok(Signature(normalize_s(signature)?))
+    }
+}
+
+/// Always hand out the low s form of a signature, so signatures pass
+/// verification that requires a low s.
+pub(crate) fn normalize_s(
+    signature: ecdsa::Signature,
+) -> core::result::Result<ecdsa::Signature, signature::Error> {
+    if signature.as_ref()[32..] <= HALF_ORDER[..] {
+        return Ok(signature);
     }
+    let mut raw = [0u8; RAW_SIGNATURE_LENGTH];
+    raw.copy_from_slice(signature.as_ref());
+    let mut s = [0u8; 32];
+    s.copy_from_slice(&raw[32..]);
+    raw[32..].copy_from_slice(&negate(&s));
+    signature::Signature::from_bytes(&raw)
+}
+
+/// Computes n - v for a big endian value v in (0, n).
+fn negate(v: &[u8; 32]) -> [u8; 32] {
+    let mut result = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut diff = ORDER[i] as i16 - v[i] as i16 - borrow;
+        borrow = if diff < 0 {
+            diff += 256;
+            1
+        } else {
+            0
+        };
+        result[i] = diff as u8;
+    }
+    result
 }
 
 impl public_key::Verify for PublicKey {
-    fn verify(&self, msg: &[u8], signature: &[u8]) -> error::Result {
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
         use signature::Verifier;
-        let signature = p256::ecdsa::Signature::from_der(signature).map_err(error::Error::from)?;
+        let signature = parse_signature(signature, options)?;
         Ok(p256::ecdsa::VerifyingKey::from(self.0).verify(msg, &signature)?)
     }
+
+    fn verify_digest_with_options(
+        &self,
+        digest: Prehash,
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        match digest {
+            Prehash::Sha256(digest) => self.verify_sha256(digest, signature, options),
+            Prehash::Sha256Digest(digest) => {
+                use signature::DigestVerifier;
+                let signature = parse_signature(signature, options)?;
+                Ok(p256::ecdsa::VerifyingKey::from(self.0)
+                    .verify_digest(FixedSha256::new(&digest), &signature)?)
+            }
+            Prehash::Sha512(_) | Prehash::Sha512Digest(_) => Err(error::unsupported_prehash()),
+        }
+    }
+}
+
+/// The order of the P-256 curve, big endian.
+const ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63, 0x25, 0x51,
+];
+
+/// Half the order of the P-256 curve, the largest allowed low s value.
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0x80, 0x00, 0x00, 0x00, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xde, 0x73, 0x7d, 0x56, 0xd3, 0x8b, 0xcf, 0x42, 0x79, 0xdc, 0xe5, 0x61, 0x7e, 0x31, 0x92, 0xa8,
+];
+
+/// Length of a fixed size `r || s` signature.
+pub const RAW_SIGNATURE_LENGTH: usize = 64;
+
+/// Parse a DER signature, or a raw one if allowed, applying the encoding rules
+/// in the given options. A 64 byte signature can be valid DER, so DER parsing
+/// is tried first.
+pub(crate) fn parse_signature(
+    input: &[u8],
+    options: &VerifyOptions,
+) -> error::Result<ecdsa::Signature> {
+    let signature = match ecdsa::Signature::from_der(input) {
+        Ok(signature) => {
+            if options.strict_der && signature.to_der().as_bytes() != input {
+                return Err(signature::Error::new().into());
+            }
+            signature
+        }
+        Err(_) if options.allow_raw_ecdsa && input.len() == RAW_SIGNATURE_LENGTH => {
+            <ecdsa::Signature as signature::Signature>::from_bytes(input)?
+        }
+        Err(err) => return Err(err.into()),
+    };
+    // The fixed size form is r || s with both big endian, so s compares
+    // bytewise against half the order
+    if options.require_low_s && signature.as_ref()[32..] > HALF_ORDER[..] {
+        return Err(signature::Error::new().into());
+    }
+    Ok(signature)
 }
 
 impl TryFrom<&[u8]> for PublicKey {
     type Error = error::Error;
 
     fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != public_key::PUBLIC_KEY_LENGTH {
+            return Err(error::invalid_length(
+                public_key::PUBLIC_KEY_LENGTH,
+                input.len(),
+            ));
+        }
         match p256::AffinePoint::decompact(&FieldBytes::from_slice(&input[1..])).into() {
             Some(point) => Ok(PublicKey(
                 p256::PublicKey::from_affine(point).map_err(error::Error::from)?,
@@ -132,20 +584,160 @@ impl TryFrom<&[u8]> for PublicKey {
     }
 }
 
+/// The P-256 field modulus, big endian.
+const FIELD_MODULUS: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+];
+
+impl PublicKey {
+    /// Checks that the given untagged key bytes are a field element, without
+    /// decompacting the point.
+    pub(crate) fn quick_check(input: &[u8]) -> bool {
+        input.len() == FIELD_MODULUS.len() && input < &FIELD_MODULUS[..]
+    }
+
+    /// Verify a fixed size `r || s` signature for the given message.
+    pub fn verify_raw(&self, msg: &[u8], signature: &[u8]) -> error::Result {
+        self.verify_parsed(msg, &Signature::from_raw(signature)?.0)
+    }
+
+    /// Verify an already parsed signature for the given message.
+    pub(crate) fn verify_parsed(&self, msg: &[u8], signature: &ecdsa::Signature) -> error::Result {
+        use signature::Verifier;
+        Ok(p256::ecdsa::VerifyingKey::from(self.0).verify(msg, signature)?)
+    }
+
+    /// Verify the given DER signature against a SHA-256 digest of the signed
+    /// message that has been fed in incrementally.
+    pub(crate) fn verify_sha256(
+        &self,
+        digest: Sha256,
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        use signature::DigestVerifier;
+        let signature = parse_signature(signature, options)?;
+        Ok(p256::ecdsa::VerifyingKey::from(self.0).verify_digest(digest, &signature)?)
+    }
+
+    /// P-256 has a cofactor of one, and both decompaction and SEC1 parsing
+    /// only produce points on the curve other than the identity, so every
+    /// constructed key is valid. The checks are repeated here to not depend on
+    /// that.
+    pub(crate) fn check_point(&self) -> error::Result {
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        let encoded = self.0.to_encoded_point(false);
+        let decoded = p256::PublicKey::from_sec1_bytes(encoded.as_bytes())
+            .map_err(|_| error::invalid_point(error::PointError::NotOnCurve))?;
+        if decoded != self.0 {
+            return Err(error::invalid_point(error::PointError::NonCanonical));
+        }
+        Ok(())
+    }
+
+    /// Construct a public key from a SEC1 compressed or uncompressed point.
+    /// Only points with a compact form can be represented, others are
+    /// rejected with `DecodeError::NotCompact`.
+    pub fn from_sec1_point(input: &[u8]) -> error::Result<Self> {
+        let public_key = p256::PublicKey::from_sec1_bytes(input).map_err(error::Error::from)?;
+        if !bool::from(public_key.as_affine().is_compactable()) {
+            return Err(error::not_compact());
+        }
+        Ok(PublicKey(public_key))
+    }
+
+    /// The SEC1 compressed encoding of this key, a `0x02` or `0x03` prefix
+    /// followed by the x coordinate.
+    pub fn to_sec1_compressed(&self) -> [u8; SEC1_COMPRESSED_LENGTH] {
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        let mut result = [0u8; SEC1_COMPRESSED_LENGTH];
+        result.copy_from_slice(self.0.to_encoded_point(true).as_bytes());
+        result
+    }
+
+    /// The SEC1 uncompressed encoding of this key, a `0x04` prefix followed by
+    /// the x and y coordinates.
+    pub fn to_sec1_uncompressed(&self) -> [u8; SEC1_UNCOMPRESSED_LENGTH] {
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        let mut result = [0u8; SEC1_UNCOMPRESSED_LENGTH];
+        result.copy_from_slice(self.0.to_encoded_point(false).as_bytes());
+        result
+    }
+
+    /// The SEC1 compressed or uncompressed encoding of this key, as taken by
+    /// OpenSSL and key import APIs.
+    #[cfg(feature = "alloc")]
+    pub fn to_sec1_bytes(&self, compressed: bool) -> Vec<u8> {
+        if compressed {
+            self.to_sec1_compressed().to_vec()
+        } else {
+            self.to_sec1_uncompressed().to_vec()
+        }
+    }
+}
+
+impl TryFrom<p256::PublicKey> for PublicKey {
+    type Error = error::Error;
+
+    /// Only keys with a compactable point can be represented.
+    fn try_from(v: p256::PublicKey) -> error::Result<Self> {
+        if !bool::from(v.as_affine().is_compactable()) {
+            return Err(error::not_compact());
+        }
+        Ok(PublicKey(v))
+    }
+}
+
+impl TryFrom<&ecdsa::VerifyingKey> for PublicKey {
+    type Error = error::Error;
+
+    /// Only keys with a compactable point can be represented.
+    fn try_from(v: &ecdsa::VerifyingKey) -> error::Result<Self> {
+        Self::from_sec1_point(v.to_encoded_point(false).as_bytes())
+    }
+}
+
+impl From<PublicKey> for p256::PublicKey {
+    fn from(v: PublicKey) -> Self {
+        v.0
+    }
+}
+
+impl From<&PublicKey> for ecdsa::VerifyingKey {
+    fn from(v: &PublicKey) -> Self {
+        ecdsa::VerifyingKey::from(v.0)
+    }
+}
+
+impl TryFrom<&public_key::PublicKey> for PublicKey {
+    type Error = error::Error;
+
+    /// Fails for keys of any other key type.
+    fn try_from(v: &public_key::PublicKey) -> error::Result<Self> {
+        match &v.inner {
+            public_key::PublicKeyRepr::EccCompact(key) => Ok(key.clone()),
+            _ => Err(error::unsupported_keytype(v.tag().key_type)),
+        }
+    }
+}
+
 impl IntoBytes for PublicKey {
-    fn bytes_into(&self, output: &mut [u8]) {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
         let encoded = self
             .0
             .as_affine()
             .to_compact_encoded_point()
             .expect("compact point");
-        output.copy_from_slice(&encoded.as_bytes()[1..])
+        let encoded = &encoded.as_bytes()[1..];
+        output_prefix(output, encoded.len())?.copy_from_slice(encoded);
+        Ok(encoded.len())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Keypair, PublicKey, TryFrom};
+    use super::{Keypair, PublicKey, Signature, TryFrom};
     use crate::{Network, Sign, Verify};
     use hex_literal::hex;
     use rand::rngs::OsRng;
@@ -160,6 +752,20 @@ mod tests {
             .is_ok())
     }
 
+    #[test]
+    fn sign_into_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let mut signature = [0u8; super::MAX_SIGNATURE_LENGTH];
+        let len = keypair
+            .sign_into(b"hello world", &mut signature)
+            .expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature[..len])
+            .is_ok());
+        assert!(keypair.sign_into(b"hello world", &mut [0u8; 8]).is_err());
+    }
+
     #[test]
     fn bytes_roundtrip() {
         use rand::rngs::OsRng;
@@ -171,6 +777,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn generate_negated() {
+        // Every generated key must be compactable and its public key must
+        // match the one derived from the (possibly negated) secret
+        for _ in 0..32 {
+            let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+            assert_eq!(
+                keypair.public_key,
+                crate::PublicKey::from_bytes(keypair.public_key.to_bytes()).expect("compact key")
+            );
+            assert_eq!(
+                keypair,
+                Keypair::try_from(&keypair.to_bytes()[..]).expect("keypair")
+            );
+        }
+    }
+
+    #[test]
+    fn generate_from_entropy_checks() {
+        use crate::error::{EntropyError, Error};
+        let check = |entropy: &[u8]| match Keypair::generate_from_entropy(Network::MainNet, entropy)
+        {
+            Err(Error::WeakEntropy(err)) => Some(err),
+            _ => None,
+        };
+        assert_eq!(
+            Some(EntropyError::Length {
+                expected: 32,
+                found: 16
+            }),
+            check(&[1u8; 16])
+        );
+        assert_eq!(Some(EntropyError::Zero), check(&[0u8; 32]));
+        assert_eq!(Some(EntropyError::LowQuality), check(&[0xabu8; 32]));
+        // The curve order itself
+        assert_eq!(
+            Some(EntropyError::OutOfRange),
+            check(&hex!(
+                "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"
+            ))
+        );
+        assert_eq!(
+            None,
+            check(&hex!(
+                "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721"
+            ))
+        );
+        assert!(!matches!(
+            Keypair::generate_from_entropy_unchecked(Network::MainNet, &[0xabu8; 32]),
+            Err(Error::WeakEntropy(_))
+        ));
+    }
+
+    #[test]
+    fn generate_from_seed() {
+        // Roughly half of these seeds have an uncompactable first candidate
+        for seed in 0u8..32 {
+            let keypair = Keypair::generate_from_seed(Network::MainNet, &[seed]).expect("keypair");
+            assert_eq!(
+                keypair,
+                Keypair::generate_from_seed(Network::MainNet, &[seed]).expect("keypair")
+            );
+            assert_eq!(
+                keypair.public_key,
+                crate::PublicKey::from_bytes(keypair.public_key.to_bytes()).expect("compact key")
+            );
+        }
+        assert_ne!(
+            Keypair::generate_from_seed(Network::MainNet, b"one").expect("keypair"),
+            Keypair::generate_from_seed(Network::MainNet, b"two").expect("keypair")
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn generate_batch() {
+        let keypairs: Vec<Keypair> = Keypair::generate_batch(Network::TestNet, 10)
+            .into_iter()
+            .collect();
+        assert_eq!(10, keypairs.len());
+        assert!(keypairs
+            .iter()
+            .all(|keypair| keypair.network == Network::TestNet));
+    }
+
     #[test]
     fn verify() {
         // Test a msg signed and verified with a keypair generated with erlang libp2p_crypto
@@ -183,6 +874,127 @@ mod tests {
         assert!(public_key.verify(MSG, SIG).is_ok());
     }
 
+    #[test]
+    fn verify_options() {
+        use crate::public_key::VerifyOptions;
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let low_s = VerifyOptions {
+            require_low_s: true,
+            ..VerifyOptions::DEFAULT
+        };
+        let msg = b"hello world";
+        let raw = keypair.sign_raw(msg).expect("signature");
+        // Signing always produces a low s. Negating it gives the equally valid
+        // high s form
+        assert!(raw[32..] <= super::HALF_ORDER[..]);
+        let mut high_s = raw;
+        let mut s = [0u8; 32];
+        s.copy_from_slice(&raw[32..]);
+        high_s[32..].copy_from_slice(&super::negate(&s));
+        let signature = Signature::from_raw(&high_s).expect("raw").to_der();
+        assert!(keypair.public_key.verify(msg, &signature).is_ok());
+        assert!(keypair
+            .public_key
+            .verify_with_options(msg, &signature, &VerifyOptions::CONSENSUS)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify_with_options(msg, &signature, &low_s)
+            .is_err());
+        assert!(keypair.public_key.verify_strict(msg, &signature).is_err());
+        let signature = Signature::from_raw(&raw).expect("raw").to_der();
+        assert!(keypair.public_key.verify_strict(msg, &signature).is_ok());
+    }
+
+    #[test]
+    fn verify_raw() {
+        use crate::public_key::VerifyOptions;
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        let signature = p256::ecdsa::Signature::from_der(&signature).expect("der");
+        let raw = signature.as_ref();
+        let allow_raw = VerifyOptions {
+            allow_raw_ecdsa: true,
+            ..VerifyOptions::DEFAULT
+        };
+        assert!(keypair.public_key.verify(b"hello world", raw).is_err());
+        assert!(keypair
+            .public_key
+            .verify_with_options(b"hello world", raw, &allow_raw)
+            .is_ok());
+    }
+
+    #[test]
+    fn raw_signature_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let raw = keypair.sign_raw(b"hello world").expect("signature");
+        let public_key = PublicKey::try_from(&keypair.public_key).expect("ecc_compact key");
+        assert!(public_key.verify_raw(b"hello world", &raw).is_ok());
+        assert!(public_key.verify_raw(b"hello world!", &raw).is_err());
+
+        let der = Signature::from_raw(&raw).expect("raw").to_der();
+        assert!(keypair.public_key.verify(b"hello world", &der).is_ok());
+        assert_eq!(raw, Signature::from_der(&der).expect("der").to_raw());
+        assert!(Signature::from_raw(&der).is_err());
+    }
+
+    #[test]
+    fn sec1_roundtrip() {
+        use crate::IntoBytes;
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key = PublicKey::try_from(&keypair.public_key).expect("ecc_compact key");
+        let mut compact = [0u8; 32];
+        assert_eq!(
+            32,
+            public_key.try_bytes_into(&mut compact).expect("compact")
+        );
+        let compressed = public_key.to_sec1_compressed();
+        assert!(compressed[0] == 0x02 || compressed[0] == 0x03);
+        assert_eq!(compact, compressed[1..]);
+        let uncompressed = public_key.to_sec1_uncompressed();
+        assert_eq!(0x04, uncompressed[0]);
+        assert_eq!(compact, uncompressed[1..33]);
+        assert_eq!(compressed.to_vec(), public_key.to_sec1_bytes(true));
+        assert_eq!(uncompressed.to_vec(), public_key.to_sec1_bytes(false));
+        for encoded in &[&compressed[..], &uncompressed[..]] {
+            assert_eq!(
+                public_key,
+                PublicKey::from_sec1_point(encoded).expect("sec1 point")
+            );
+        }
+        assert_eq!(
+            keypair.public_key,
+            crate::PublicKey::from_sec1_bytes(Network::MainNet, &compressed).expect("sec1 key")
+        );
+        assert_eq!(
+            compressed.to_vec(),
+            keypair.public_key.to_sec1_bytes(true).expect("sec1 bytes")
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-export"))]
+    fn rustcrypto_conversions() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let secret = p256::SecretKey::from(&keypair);
+        assert_eq!(
+            keypair,
+            Keypair::from_secret_key(Network::TestNet, secret).expect("keypair")
+        );
+        let public_key = PublicKey::try_from(&keypair.public_key).expect("ecc_compact key");
+        let verifying_key = p256::ecdsa::VerifyingKey::from(&public_key);
+        assert_eq!(
+            public_key,
+            PublicKey::try_from(&verifying_key).expect("verifying key")
+        );
+        let point = p256::PublicKey::from(public_key.clone());
+        assert_eq!(public_key, PublicKey::try_from(point).expect("point"));
+        assert_eq!(
+            keypair.public_key,
+            crate::PublicKey::from_ecc_compact(Network::TestNet, public_key)
+        );
+    }
+
     #[test]
     fn b58_roundtrip() {
         const B58: &str = "112jXiCTi9DpLC5nLdSZ2zccRVEtZizRJMizziCebaNbRDi8k6wR";
@@ -0,0 +1,97 @@
+//! Elliptic curve Diffie-Hellman key agreement between Helium identities.
+//!
+//! Both ecc_compact and secp256k1 keypairs agree on the x coordinate of the
+//! shared point, the same raw shared secret as SEC1 ECDH. The raw secret is
+//! not uniformly random and should be passed through a key derivation
+//! function, like HKDF, before use as a symmetric key.
+use core::fmt;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
+
+/// The length of a shared secret.
+pub const SHARED_SECRET_LENGTH: usize = 32;
+
+/// A shared secret from key agreement. The secret is zeroized on drop.
+pub struct SharedSecret([u8; SHARED_SECRET_LENGTH]);
+
+impl SharedSecret {
+    pub(crate) fn from_slice(bytes: &[u8]) -> Self {
+        let mut secret = [0u8; SHARED_SECRET_LENGTH];
+        secret.copy_from_slice(bytes);
+        Self(secret)
+    }
+
+    /// The raw shared secret.
+    pub fn as_bytes(&self) -> &[u8; SHARED_SECRET_LENGTH] {
+        &self.0
+    }
+}
+
+impl ConstantTimeEq for SharedSecret {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+/// Shared secrets compare in constant time.
+impl PartialEq for SharedSecret {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SharedSecret {}
+
+impl Zeroize for SharedSecret {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for SharedSecret {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// The secret is never included in `Debug` output.
+impl fmt::Debug for SharedSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedSecret(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ecc_compact, ed25519, secp256k1, Keypair, Network};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn agreement() {
+        let pairs = vec![
+            (
+                Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+                Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+            ),
+            (
+                Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng)),
+                Keypair::Secp256k1(secp256k1::Keypair::generate(Network::TestNet, &mut OsRng)),
+            ),
+        ];
+        for (alice, bob) in pairs {
+            let a = alice.ecdh(bob.public_key()).expect("alice secret");
+            let b = bob.ecdh(alice.public_key()).expect("bob secret");
+            assert_eq!(a.as_bytes(), b.as_bytes());
+            assert_eq!("SharedSecret(..)", format!("{:?}", a));
+        }
+    }
+
+    #[test]
+    fn mismatched_key_type() {
+        let ecc = Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng));
+        let secp = Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng));
+        let ed = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        assert!(ecc.ecdh(secp.public_key()).is_err());
+        assert!(ed.ecdh(ecc.public_key()).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! ECIES encryption of small messages to a public key.
+//!
+//! The sender generates an ephemeral keypair of the recipient's key type and
+//! agrees on a shared secret with the recipient key, see [`crate::ecdh`]. The
+//! shared secret is expanded with HKDF-SHA256, using the binary forms of the
+//! ephemeral and the recipient public key as the salt, into a ChaCha20-Poly1305
+//! key and nonce. A ciphertext is laid out as:
+//!
+//! ```text
+//! version (1) | ephemeral public key (33 or 34) | encrypted message | tag (16)
+//! ```
+//!
+//! The ephemeral public key has the binary form of the recipient's key type,
+//! 34 bytes for secp256k1 and 33 for ecc_compact. The version and ephemeral
+//! public key are authenticated as associated data.
+//! Since every message uses a fresh ephemeral key, the derived nonce is never
+//! reused. Only ecc_compact and secp256k1 keys are supported.
+use crate::{ecc_compact, entropy::EntropySource, error, secp256k1, KeyType, Keypair, PublicKey};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+const VERSION: u8 = 1;
+const INFO: &[u8] = b"helium-crypto ecies v1";
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+/// The most bytes a ciphertext is longer than its message. Ciphertexts to
+/// ecc_compact keys are one byte shorter, see [`overhead`].
+pub const OVERHEAD: usize = 1 + PublicKey::MAX_LEN + TAG_LENGTH;
+
+/// The number of bytes a ciphertext to the given key is longer than its
+/// message.
+pub fn overhead(public_key: &PublicKey) -> usize {
+    1 + public_key.encoded_len() + TAG_LENGTH
+}
+
+impl PublicKey {
+    /// Encrypt the given message to this key. The ephemeral key is drawn from
+    /// the given entropy source.
+    pub fn encrypt<E>(&self, msg: &[u8], entropy: &mut E) -> error::Result<Vec<u8>>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let ephemeral = match self.tag().key_type {
+            KeyType::EccCompact => {
+                Keypair::EccCompact(ecc_compact::Keypair::generate(self.network, entropy))
+            }
+            KeyType::Secp256k1 => {
+                Keypair::Secp256k1(secp256k1::Keypair::generate(self.network, entropy))
+            }
+            _ => return Err(error::unsupported_keytype(self.tag().key_type)),
+        };
+        let mut result = Vec::with_capacity(overhead(self) + msg.len());
+        result.push(VERSION);
+        result.extend_from_slice(&ephemeral.public_key().to_bytes());
+        let (cipher, nonce) = derive_cipher(&ephemeral, ephemeral.public_key(), self)?;
+        let ciphertext = cipher
+            .encrypt(&nonce, Payload { msg, aad: &result })
+            .map_err(|_| error::invalid_ecies())?;
+        result.extend_from_slice(&ciphertext);
+        Ok(result)
+    }
+}
+
+impl Keypair {
+    /// Decrypt a ciphertext produced by [`PublicKey::encrypt`] for the public
+    /// key of this keypair. Fails with `DecodeError::Ecies` if the ciphertext
+    /// is malformed, was not encrypted to this key or has been tampered with.
+    pub fn decrypt(&self, ciphertext: &[u8]) -> error::Result<Vec<u8>> {
+        if ciphertext.len() < overhead(self.public_key()) || ciphertext[0] != VERSION {
+            return Err(error::invalid_ecies());
+        }
+        let (header, ciphertext) = ciphertext.split_at(1 + self.public_key().encoded_len());
+        let ephemeral = PublicKey::from_bytes(&header[1..]).map_err(|_| error::invalid_ecies())?;
+        if ephemeral.tag() != self.key_tag() {
+            return Err(error::invalid_ecies());
+        }
+        let (cipher, nonce) = derive_cipher(self, &ephemeral, self.public_key())?;
+        cipher
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| error::invalid_ecies())
+    }
+}
+
+/// Derives the cipher and nonce for a message from the ephemeral to the
+/// recipient key. The given keypair is either the ephemeral keypair, when
+/// encrypting, or the recipient keypair, when decrypting.
+fn derive_cipher(
+    keypair: &Keypair,
+    ephemeral: &PublicKey,
+    recipient: &PublicKey,
+) -> error::Result<(ChaCha20Poly1305, Nonce)> {
+    let peer = if keypair.public_key() == ephemeral {
+        recipient
+    } else {
+        ephemeral
+    };
+    let shared = keypair.ecdh(peer)?;
+    let mut salt = ephemeral.to_vec();
+    salt.extend_from_slice(&recipient.to_bytes());
+    let mut okm = [0u8; KEY_LENGTH + NONCE_LENGTH];
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(INFO, &mut okm)
+        .expect("hkdf output length");
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&okm[..KEY_LENGTH]));
+    let nonce = *Nonce::from_slice(&okm[KEY_LENGTH..]);
+    okm[..].zeroize();
+    Ok((cipher, nonce))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{overhead, OVERHEAD};
+    use crate::{ecc_compact, ed25519, secp256k1, Keypair, Network};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn roundtrip() {
+        for keypair in vec![
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Secp256k1(secp256k1::Keypair::generate(Network::TestNet, &mut OsRng)),
+        ] {
+            let ciphertext = keypair
+                .public_key()
+                .encrypt(b"hello world", &mut OsRng)
+                .expect("encrypt");
+            let overhead = overhead(keypair.public_key());
+            assert!(overhead <= OVERHEAD);
+            assert_eq!(overhead + 11, ciphertext.len());
+            assert_eq!(
+                b"hello world".to_vec(),
+                keypair.decrypt(&ciphertext).expect("decrypt")
+            );
+
+            let mut tampered = ciphertext.clone();
+            tampered[overhead] ^= 1;
+            assert!(keypair.decrypt(&tampered).is_err());
+        }
+    }
+
+    #[test]
+    fn wrong_recipient() {
+        let alice =
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng));
+        let bob = Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng));
+        let ciphertext = alice
+            .public_key()
+            .encrypt(b"hello world", &mut OsRng)
+            .expect("encrypt");
+        assert!(bob.decrypt(&ciphertext).is_err());
+
+        let ed = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        assert!(ed.public_key.encrypt(b"hello world", &mut OsRng).is_err());
+    }
+}
@@ -1,5 +1,29 @@
-use crate::{error, keypair, public_key, IntoBytes, KeyTag, KeyType, Network};
-use std::convert::TryFrom;
+#[cfg(feature = "pkcs8")]
+use crate::pkcs8;
+use crate::{
+    entropy::{check_secret, EntropyRng, EntropySource},
+    error::{self, EntropyError, PointError},
+    keypair::{self, Prehash},
+    output_prefix,
+    public_key::{self, VerifyOptions},
+    secret::Secret,
+    IntoBytes, KeyTag, KeyType, Network,
+};
+#[cfg(feature = "alloc")]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::convert::{TryFrom, TryInto};
+use curve25519_dalek::{
+    constants::ED25519_BASEPOINT_POINT,
+    edwards::{CompressedEdwardsY, EdwardsPoint, VartimeEdwardsPrecomputation},
+    scalar::Scalar,
+    traits::{IsIdentity, VartimePrecomputedMultiscalarMul},
+};
+use sha2::{Digest, Sha512};
+use zeroize::Zeroize;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct PublicKey(ed25519_dalek::PublicKey);
@@ -7,27 +31,63 @@ pub struct PublicKey(ed25519_dalek::PublicKey);
 #[derive(Debug, PartialEq, Clone)]
 pub struct Signature(ed25519_dalek::Signature);
 
-pub type Keypair = keypair::Keypair<ed25519_dalek::Keypair>;
+pub type Keypair = keypair::Keypair<Secret<ed25519_dalek::Keypair>>;
+
+#[cfg(any(test, feature = "test-utils"))]
+impl keypair::DebugSecret for Keypair {
+    fn secret_bytes(&self) -> Vec<u8> {
+        self.inner.secret.to_bytes().to_vec()
+    }
+}
 
 pub const KEYPAIR_LENGTH: usize = ed25519_dalek::KEYPAIR_LENGTH + 1;
 
+/// The length of an ed25519 signature.
+pub const MAX_SIGNATURE_LENGTH: usize = ed25519_dalek::SIGNATURE_LENGTH;
+
+/// The RFC 8032 dom2 prefix for Ed25519ph with an empty context.
+const ED25519PH_DOM: &[u8] = b"SigEd25519 no Ed25519 collisions\x01\x00";
+
 impl keypair::Sign for Keypair {
+    #[cfg(feature = "alloc")]
     fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
         use signature::Signer;
         let signature = self.try_sign(msg)?;
         Ok(signature.as_ref().to_vec())
     }
+
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        use signature::Signer;
+        let signature = self.try_sign(msg)?;
+        keypair::copy_signature(signature.as_ref(), output)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn sign_digest(&self, digest: Prehash) -> error::Result<Vec<u8>> {
+        match digest {
+            Prehash::Sha512(digest) => {
+                let signature = self.inner.sign_prehashed(digest, None)?;
+                Ok(signature.to_bytes().to_vec())
+            }
+            Prehash::Sha256(_) | Prehash::Sha256Digest(_) | Prehash::Sha512Digest(_) => {
+                Err(error::unsupported_prehash())
+            }
+        }
+    }
 }
 
 impl TryFrom<&[u8]> for Keypair {
     type Error = error::Error;
 
     fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != KEYPAIR_LENGTH {
+            return Err(error::invalid_length(KEYPAIR_LENGTH, input.len()));
+        }
         let network = Network::try_from(input[0])?;
         let inner = ed25519_dalek::Keypair::from_bytes(&input[1..])?;
         let public_key = public_key::PublicKey::for_network(network, PublicKey(inner.public));
         Ok(Keypair {
-            inner,
+            inner: inner.into(),
             network,
             public_key,
         })
@@ -35,48 +95,227 @@ impl TryFrom<&[u8]> for Keypair {
 }
 
 impl IntoBytes for Keypair {
-    fn bytes_into(&self, output: &mut [u8]) {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let output = output_prefix(output, KEYPAIR_LENGTH)?;
         output[0] = u8::from(KeyTag {
             network: self.network,
             key_type: KeyType::Ed25519,
         });
-        output[1..].copy_from_slice(&self.inner.to_bytes());
+        let mut secret = self.inner.to_bytes();
+        output[1..].copy_from_slice(&secret);
+        secret[..].zeroize();
+        Ok(KEYPAIR_LENGTH)
     }
 }
 
 impl Keypair {
-    pub fn generate<R>(network: Network, csprng: &mut R) -> Keypair
+    /// Length of the binary form of keypairs of this type.
+    pub const LEN: usize = KEYPAIR_LENGTH;
+
+    pub fn generate<E>(network: Network, entropy: &mut E) -> Keypair
     where
-        R: rand_core::CryptoRng + rand_core::RngCore,
+        E: EntropySource + ?Sized,
     {
-        let inner = ed25519_dalek::Keypair::generate(csprng);
+        let inner = ed25519_dalek::Keypair::generate(&mut EntropyRng(entropy));
         let public_key = public_key::PublicKey::for_network(network, PublicKey(inner.public));
         Keypair {
             network,
             public_key,
-            inner,
+            inner: inner.into(),
         }
     }
 
+    /// Generate a keypair using the operating system random number generator.
+    #[cfg(feature = "std")]
+    pub fn generate_default(network: Network) -> Keypair {
+        Self::generate(network, &mut rand_core::OsRng)
+    }
+
+    /// Replace the secret of this keypair with a freshly generated one for the
+    /// same network, zeroizing the old secret. The returned statement that the
+    /// new key replaces the old one as of `timestamp` is signed by both keys,
+    /// see [`rotation`](crate::rotation).
+    #[cfg(feature = "alloc")]
+    pub fn rekey<E>(
+        &mut self,
+        entropy: &mut E,
+        timestamp: u64,
+    ) -> error::Result<crate::rotation::Rotation>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let new = Self::generate(self.network, entropy);
+        self.rotate(new, timestamp)
+    }
+
+    /// Construct a keypair for the given network from an ed25519-dalek
+    /// keypair. Fails if its public key is a weak point.
+    pub fn from_dalek(network: Network, inner: ed25519_dalek::Keypair) -> error::Result<Keypair> {
+        let public_key = PublicKey::try_from(inner.public)?;
+        Ok(Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            inner: inner.into(),
+        })
+    }
+
+    /// Construct a keypair from its 64 byte secret and public key form. The
+    /// secret half is rejected with a distinct [`EntropyError`] when it is
+    /// all zero or has too few distinct bytes to be random.
+    ///
+    /// [`EntropyError`]: crate::error::EntropyError
     pub fn generate_from_entropy(network: Network, entropy: &[u8]) -> error::Result<Keypair> {
+        if entropy.len() != ed25519_dalek::KEYPAIR_LENGTH {
+            return Err(error::weak_entropy(EntropyError::Length {
+                expected: ed25519_dalek::KEYPAIR_LENGTH,
+                found: entropy.len(),
+            }));
+        }
+        let secret = &entropy[..ed25519_dalek::SECRET_KEY_LENGTH];
+        check_secret(secret, ed25519_dalek::SECRET_KEY_LENGTH)?;
+        Self::generate_from_entropy_unchecked(network, entropy)
+    }
+
+    /// Construct a keypair from its 64 byte form without the checks of
+    /// `generate_from_entropy`.
+    pub fn generate_from_entropy_unchecked(
+        network: Network,
+        entropy: &[u8],
+    ) -> error::Result<Keypair> {
         let inner = ed25519_dalek::Keypair::from_bytes(entropy)?;
         let public_key = public_key::PublicKey::for_network(network, PublicKey(inner.public));
         Ok(Keypair {
             network,
             public_key,
-            inner,
+            inner: inner.into(),
+        })
+    }
+
+    /// Construct a keypair from a 32 byte ed25519 secret key.
+    pub(crate) fn from_secret(network: Network, secret: &[u8]) -> error::Result<Keypair> {
+        let secret = ed25519_dalek::SecretKey::from_bytes(secret)?;
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let inner = ed25519_dalek::Keypair { secret, public };
+        let public_key = public_key::PublicKey::for_network(network, PublicKey(inner.public));
+        Ok(Keypair {
+            network,
+            public_key,
+            inner: inner.into(),
         })
     }
 
-    pub fn to_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
-        let mut result = [0u8; KEYPAIR_LENGTH];
-        self.bytes_into(&mut result);
+    export_secret! {
+        fn to_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
+            let mut result = [0u8; KEYPAIR_LENGTH];
+            self.try_bytes_into(&mut result).expect("keypair length");
+            result
+        }
+    }
+
+    /// The clamped X25519 secret key for this keypair, as computed by
+    /// libsodium's `crypto_sign_ed25519_sk_to_curve25519`.
+    pub(crate) fn to_x25519_secret(&self) -> [u8; 32] {
+        let mut hash = Sha512::digest(self.inner.secret.as_bytes());
+        let mut secret = [0u8; 32];
+        secret.copy_from_slice(&hash[..32]);
+        hash[..].zeroize();
+        secret[0] &= 248;
+        secret[31] &= 127;
+        secret[31] |= 64;
+        secret
+    }
+
+    export_secret! {
+        /// The 64 byte Solana form of the keypair, the secret key followed by
+        /// the public key.
+        fn to_solana_bytes(&self) -> [u8; ed25519_dalek::KEYPAIR_LENGTH] {
+            self.inner.to_bytes()
+        }
+    }
+
+    /// Construct a keypair for the given network from its 64 byte Solana
+    /// form. The public key half must match the secret key.
+    pub fn from_solana_bytes(network: Network, input: &[u8]) -> error::Result<Keypair> {
+        if input.len() != ed25519_dalek::KEYPAIR_LENGTH {
+            return Err(error::invalid_solana());
+        }
+        let (secret, public) = input.split_at(ed25519_dalek::SECRET_KEY_LENGTH);
+        let keypair = Self::from_secret(network, secret)?;
+        if keypair.inner.public.as_bytes() != public {
+            return Err(error::invalid_solana());
+        }
+        Ok(keypair)
+    }
+
+    export_secret! {
+        /// Encode the keypair as a Solana CLI keypair file, a JSON array of
+        /// the 64 bytes of its Solana form.
+        #[cfg(feature = "alloc")]
+        fn to_solana_json(&self) -> String {
+            let mut bytes = self.to_solana_bytes();
+            let encoded: Vec<String> = bytes.iter().map(u8::to_string).collect();
+            bytes[..].zeroize();
+            format!("[{}]", encoded.join(","))
+        }
+    }
+
+    /// Decode a Solana CLI keypair file for the given network.
+    #[cfg(feature = "alloc")]
+    pub fn from_solana_json(network: Network, input: &str) -> error::Result<Keypair> {
+        let inner = input
+            .trim()
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(error::invalid_solana)?;
+        let mut bytes = inner
+            .split(',')
+            .map(|byte| byte.trim().parse::<u8>())
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| error::invalid_solana())?;
+        let result = Self::from_solana_bytes(network, &bytes);
+        bytes[..].zeroize();
+        result
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+impl Keypair {
+    export_secret! {
+        /// Encode the keypair as an unencrypted PKCS#8 document in DER form.
+        fn to_pkcs8_der(&self) -> Vec<u8> {
+            pkcs8::encode_ed25519(self.inner.secret.as_bytes())
+        }
+    }
+
+    /// Decode a DER encoded PKCS#8 ed25519 key for the given network.
+    pub fn from_pkcs8_der(network: Network, input: &[u8]) -> error::Result<Keypair> {
+        let (key_type, mut secret) = pkcs8::decode(input)?;
+        let result = match key_type {
+            KeyType::Ed25519 => Self::from_secret(network, &secret),
+            _ => Err(error::invalid_keytype(u8::from(KeyTag {
+                network,
+                key_type,
+            }))),
+        };
+        secret[..].zeroize();
         result
     }
+
+    export_secret! {
+        /// Encode the keypair as an unencrypted PKCS#8 PEM block.
+        fn to_pkcs8_pem(&self) -> String {
+            pkcs8::to_pem(&self.to_pkcs8_der())
+        }
+    }
+
+    /// Decode a PKCS#8 PEM block holding an ed25519 key for the given network.
+    pub fn from_pkcs8_pem(network: Network, input: &str) -> error::Result<Keypair> {
+        Self::from_pkcs8_der(network, &pkcs8::from_pem(input)?)
+    }
 }
 
 impl signature::Signature for Signature {
-    fn from_bytes(input: &[u8]) -> std::result::Result<Self, signature::Error> {
+    fn from_bytes(input: &[u8]) -> core::result::Result<Self, signature::Error> {
         Ok(Signature(signature::Signature::from_bytes(input)?))
     }
 
@@ -92,7 +331,7 @@ impl AsRef<[u8]> for Signature {
 }
 
 impl signature::Signer<Signature> for Keypair {
-    fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
+    fn try_sign(&self, msg: &[u8]) -> core::result::Result<Signature, signature::Error> {
         Ok(Signature(self.inner.sign(msg)))
     }
 }
@@ -108,17 +347,225 @@ impl TryFrom<&[u8]> for Signature {
 }
 
 impl public_key::Verify for PublicKey {
-    fn verify(&self, msg: &[u8], signature: &[u8]) -> std::result::Result<(), error::Error> {
-        use ed25519_dalek::Verifier;
-        let signature = Signature::try_from(signature)?;
-        Verifier::<ed25519_dalek::Signature>::verify(&self.0, msg, &signature.0)
-            .map_err(error::Error::from)
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> core::result::Result<(), error::Error> {
+        if !options.strict_ed25519 && !options.cofactored {
+            use ed25519_dalek::Verifier;
+            let signature = Signature::try_from(signature)?;
+            return Verifier::<ed25519_dalek::Signature>::verify(&self.0, msg, &signature.0)
+                .map_err(error::Error::from);
+        }
+        let mut hasher = self.verify_hasher(signature)?;
+        hasher.update(msg);
+        self.verify_hashed(signature, hasher, options)
+    }
+
+    /// Verifies an Ed25519ph signature with an empty context. The challenge
+    /// hash is built here so the same verification rules as for regular
+    /// signatures apply.
+    fn verify_digest_with_options(
+        &self,
+        digest: Prehash,
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        let mut prehash = [0u8; 64];
+        match digest {
+            Prehash::Sha512(digest) => prehash.copy_from_slice(&digest.finalize()),
+            Prehash::Sha512Digest(digest) => prehash = digest,
+            Prehash::Sha256(_) | Prehash::Sha256Digest(_) => {
+                return Err(error::unsupported_prehash())
+            }
+        }
+        if signature.len() != ed25519_dalek::SIGNATURE_LENGTH {
+            return Err(signature::Error::new().into());
+        }
+        let mut hasher = Sha512::new();
+        hasher.update(ED25519PH_DOM);
+        hasher.update(&signature[..32]);
+        hasher.update(self.0.as_bytes());
+        hasher.update(&prehash);
+        self.verify_hashed(signature, hasher, options)
+    }
+}
+
+impl PublicKey {
+    /// Checks that the given untagged key bytes are a canonically encoded
+    /// field element, without decompressing the point.
+    pub(crate) fn quick_check(input: &[u8]) -> bool {
+        // The y coordinate is little endian with the top bit holding the sign
+        // of x. It is only out of range for 2^255 - 19 <= y < 2^255.
+        input.len() == 32
+            && !(input[31] & 0x7f == 0x7f
+                && input[1..31].iter().all(|byte| *byte == 0xff)
+                && input[0] >= 0xed)
+    }
+
+    /// Check that the key is a canonically encoded point of large order. When
+    /// `torsion` is set the point also has to be in the prime order subgroup,
+    /// which parsing does not require since libsodium does not either.
+    pub(crate) fn check_point(&self, torsion: bool) -> error::Result {
+        let encoded = CompressedEdwardsY(self.0.to_bytes());
+        let point = encoded
+            .decompress()
+            .ok_or_else(|| error::invalid_point(PointError::NotOnCurve))?;
+        if point.compress() != encoded {
+            return Err(error::invalid_point(PointError::NonCanonical));
+        }
+        if point.is_small_order() {
+            return Err(error::invalid_point(PointError::SmallOrder));
+        }
+        if torsion && !point.is_torsion_free() {
+            return Err(error::invalid_point(PointError::Torsion));
+        }
+        Ok(())
+    }
+
+    /// The X25519 public key for this key, the Montgomery form of the point,
+    /// as computed by libsodium's `crypto_sign_ed25519_pk_to_curve25519`.
+    pub fn to_x25519(&self) -> [u8; 32] {
+        CompressedEdwardsY(self.0.to_bytes())
+            .decompress()
+            .expect("valid ed25519 point")
+            .to_montgomery()
+            .to_bytes()
+    }
+
+    /// Start verifying the given signature over a message that will be fed in
+    /// incrementally. The returned hasher has the signature's R and the public
+    /// key absorbed and needs the message added before calling
+    /// `verify_hashed`.
+    pub(crate) fn verify_hasher(&self, signature: &[u8]) -> error::Result<Sha512> {
+        if signature.len() != ed25519_dalek::SIGNATURE_LENGTH {
+            return Err(signature::Error::new().into());
+        }
+        let mut hasher = Sha512::new();
+        hasher.update(&signature[..32]);
+        hasher.update(self.0.as_bytes());
+        Ok(hasher)
+    }
+
+    /// Finish verifying the given signature by checking that `[s]B = R +
+    /// [k]A`, or its cofactored form if requested, where k is the digest of the
+    /// hasher returned by `verify_hasher` after the message was added.
+    pub(crate) fn verify_hashed(
+        &self,
+        signature: &[u8],
+        hasher: Sha512,
+        options: &VerifyOptions,
+    ) -> error::Result {
+        let a = CompressedEdwardsY(self.0.to_bytes())
+            .decompress()
+            .ok_or_else(signature::Error::new)?;
+        check_hashed(signature, hasher, options, &a, |k, s| {
+            EdwardsPoint::vartime_double_scalar_mul_basepoint(k, &-a, s)
+        })
+    }
+}
+
+/// The checks of `PublicKey::verify_hashed` for the key point `a`, where
+/// `mul(k, s)` computes `[s]B - [k]A`.
+fn check_hashed(
+    signature: &[u8],
+    hasher: Sha512,
+    options: &VerifyOptions,
+    a: &EdwardsPoint,
+    mul: impl FnOnce(&Scalar, &Scalar) -> EdwardsPoint,
+) -> error::Result {
+    let s: [u8; 32] = signature[32..]
+        .try_into()
+        .map_err(|_| signature::Error::new())?;
+    let s = Scalar::from_canonical_bytes(s).ok_or_else(signature::Error::new)?;
+    let k = Scalar::from_hash(hasher);
+    let r = mul(&k, &s);
+    let r_bytes = &signature[..32];
+    if options.strict_ed25519 || options.cofactored {
+        let mut encoded = [0u8; 32];
+        encoded.copy_from_slice(r_bytes);
+        let expected = CompressedEdwardsY(encoded)
+            .decompress()
+            .ok_or_else(signature::Error::new)?;
+        if options.strict_ed25519
+            && (a.is_small_order()
+                || expected.is_small_order()
+                || expected.compress().as_bytes() != &encoded)
+        {
+            return Err(signature::Error::new().into());
+        }
+        if options.cofactored {
+            if !(r - expected).mul_by_cofactor().is_identity() {
+                return Err(signature::Error::new().into());
+            }
+            return Ok(());
+        }
+    }
+    if r.compress().as_bytes()[..] != *r_bytes {
+        return Err(signature::Error::new().into());
+    }
+    Ok(())
+}
+
+/// An ed25519 public key prepared for repeated verification, with its point
+/// decompressed and multiplication tables for it and the basepoint
+/// precomputed.
+pub(crate) struct PreparedPublicKey {
+    key: PublicKey,
+    a: EdwardsPoint,
+    // Tables for the basepoint and -A, in that order
+    tables: VartimeEdwardsPrecomputation,
+}
+
+impl PreparedPublicKey {
+    pub(crate) fn new(key: &PublicKey) -> Self {
+        let a = CompressedEdwardsY(key.0.to_bytes())
+            .decompress()
+            .expect("ed25519 point");
+        Self {
+            key: key.clone(),
+            a,
+            tables: VartimeEdwardsPrecomputation::new(&[ED25519_BASEPOINT_POINT, -a]),
+        }
+    }
+
+    pub(crate) fn verify(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        let mut hasher = self.key.verify_hasher(signature)?;
+        hasher.update(msg);
+        let mul = |k: &Scalar, s: &Scalar| self.tables.vartime_multiscalar_mul(&[*s, *k]);
+        if options.strict_ed25519 || options.cofactored {
+            return check_hashed(signature, hasher, options, &self.a, mul);
+        }
+        // Accept the same s values as ed25519-dalek, which verifies these
+        // signatures for unprepared keys: any with the high nibble clear, and
+        // canonical ones otherwise
+        let mut s = [0u8; 32];
+        s.copy_from_slice(&signature[32..]);
+        let s = if s[31] & 0xf0 == 0 {
+            Scalar::from_bits(s)
+        } else {
+            Scalar::from_canonical_bytes(s).ok_or_else(signature::Error::new)?
+        };
+        let r = mul(&Scalar::from_hash(hasher), &s);
+        if r.compress().as_bytes()[..] != signature[..32] {
+            return Err(signature::Error::new().into());
+        }
+        Ok(())
     }
 }
 
 impl IntoBytes for PublicKey {
-    fn bytes_into(&self, output: &mut [u8]) {
-        output.copy_from_slice(self.as_ref())
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let bytes = self.as_ref();
+        output_prefix(output, bytes.len())?.copy_from_slice(bytes);
+        Ok(bytes.len())
     }
 }
 
@@ -126,9 +573,52 @@ impl TryFrom<&[u8]> for PublicKey {
     type Error = error::Error;
 
     fn try_from(input: &[u8]) -> error::Result<Self> {
-        Ok(PublicKey(ed25519_dalek::PublicKey::from_bytes(
-            &input[1..],
-        )?))
+        if input.len() != public_key::PUBLIC_KEY_LENGTH {
+            return Err(error::invalid_length(
+                public_key::PUBLIC_KEY_LENGTH,
+                input.len(),
+            ));
+        }
+        let public_key = PublicKey(ed25519_dalek::PublicKey::from_bytes(&input[1..])?);
+        public_key.check_point(false)?;
+        Ok(public_key)
+    }
+}
+
+impl TryFrom<ed25519_dalek::PublicKey> for PublicKey {
+    type Error = error::Error;
+
+    /// Weak points are rejected like when parsing the binary form.
+    fn try_from(v: ed25519_dalek::PublicKey) -> error::Result<Self> {
+        let public_key = PublicKey(v);
+        public_key.check_point(false)?;
+        Ok(public_key)
+    }
+}
+
+impl From<PublicKey> for ed25519_dalek::PublicKey {
+    fn from(v: PublicKey) -> Self {
+        v.0
+    }
+}
+
+impl TryFrom<&public_key::PublicKey> for PublicKey {
+    type Error = error::Error;
+
+    /// Fails for keys of any other key type.
+    fn try_from(v: &public_key::PublicKey) -> error::Result<Self> {
+        match &v.inner {
+            public_key::PublicKeyRepr::Ed25519(key) => Ok(key.clone()),
+            _ => Err(error::unsupported_keytype(v.tag().key_type)),
+        }
+    }
+}
+
+#[cfg(not(feature = "no-export"))]
+impl From<&Keypair> for ed25519_dalek::Keypair {
+    fn from(v: &Keypair) -> Self {
+        // The dalek keypair is not Clone
+        ed25519_dalek::Keypair::from_bytes(&v.inner.to_bytes()).expect("keypair bytes")
     }
 }
 
@@ -138,6 +628,53 @@ impl AsRef<[u8]> for PublicKey {
     }
 }
 
+/// Verify the given signatures together by checking `[8](sum [z_i]R_i + sum
+/// [z_i k_i]A_i - [sum z_i s_i]B) = 0` for random 128 bit z_i, using a single
+/// multiscalar multiplication.
+#[cfg(feature = "std")]
+pub(crate) fn verify_batch<R>(items: &[(&PublicKey, &[u8], &[u8])], rng: &mut R) -> error::Result
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    use curve25519_dalek::{constants::ED25519_BASEPOINT_POINT, traits::VartimeMultiscalarMul};
+    let mut scalars = Vec::with_capacity(2 * items.len() + 1);
+    let mut points = Vec::with_capacity(2 * items.len() + 1);
+    let mut b = Scalar::zero();
+    for (key, msg, signature) in items {
+        let mut hasher = key.verify_hasher(signature)?;
+        hasher.update(msg);
+        let k = Scalar::from_hash(hasher);
+        let s: [u8; 32] = signature[32..]
+            .try_into()
+            .map_err(|_| signature::Error::new())?;
+        let s = Scalar::from_canonical_bytes(s).ok_or_else(signature::Error::new)?;
+        let r: [u8; 32] = signature[..32]
+            .try_into()
+            .map_err(|_| signature::Error::new())?;
+        let r = CompressedEdwardsY(r)
+            .decompress()
+            .ok_or_else(signature::Error::new)?;
+        let a = CompressedEdwardsY(key.0.to_bytes())
+            .decompress()
+            .ok_or_else(signature::Error::new)?;
+        let mut z = [0u8; 32];
+        rng.fill_bytes(&mut z[..16]);
+        let z = Scalar::from_bits(z);
+        b -= z * s;
+        scalars.push(z);
+        points.push(r);
+        scalars.push(z * k);
+        points.push(a);
+    }
+    scalars.push(b);
+    points.push(ED25519_BASEPOINT_POINT);
+    let check = EdwardsPoint::vartime_multiscalar_mul(scalars, points);
+    if !check.mul_by_cofactor().is_identity() {
+        return Err(signature::Error::new().into());
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::Keypair;
@@ -156,6 +693,72 @@ mod tests {
             .is_ok())
     }
 
+    #[test]
+    fn sign_prehashed() {
+        use crate::Prehash;
+        use sha2::{Digest, Sha512};
+        // Ed25519ph test vector from RFC 8032
+        let keypair = Keypair::from_secret(
+            Network::MainNet,
+            &hex!("833fe62409237b9d62ec77587520911e9a759cec1d19755b7da901b96dca3d42"),
+        )
+        .expect("keypair");
+        assert_eq!(
+            hex!("ec172b93ad5e563bf4932c70e1245034c35467ef2efd4d64ebf819683467e2bf"),
+            keypair.public_key.to_bytes()[1..]
+        );
+        let expected = hex!("98a70222f0b8121aa9d30f813d683f809e462b469c7ff87639499bb94e6dae4131f85042463c2a355a2003d062adf5aaa10b8c61e636062aaad11c2a26083406");
+        let digest = Prehash::from(Sha512::new().chain(b"abc"));
+        let signature = keypair.sign_digest(digest.clone()).expect("signature");
+        assert_eq!(&expected[..], &signature[..]);
+        assert!(keypair.public_key.verify_digest(digest, &signature).is_ok());
+        assert!(keypair.public_key.verify(b"abc", &signature).is_err());
+    }
+
+    #[test]
+    fn sign_buf_roundtrip() {
+        use rand::rngs::OsRng;
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign_buf(b"hello world").expect("signature");
+        assert_eq!(super::MAX_SIGNATURE_LENGTH, signature.len());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok())
+    }
+
+    #[test]
+    fn solana_roundtrip() {
+        // RFC 8032 test vector 1
+        let mut bytes =
+            hex!("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60").to_vec();
+        bytes.extend_from_slice(&hex!(
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"
+        ));
+        let keypair = Keypair::from_solana_bytes(Network::MainNet, &bytes).expect("keypair");
+        assert_eq!(
+            "FVen3X669xLzsi6N2V91DoiyzHzg1uAgqiT8jZ9nS96Z",
+            keypair.public_key.to_solana_address().expect("address")
+        );
+        assert_eq!(
+            keypair.public_key,
+            crate::PublicKey::from_solana_address(
+                Network::MainNet,
+                "FVen3X669xLzsi6N2V91DoiyzHzg1uAgqiT8jZ9nS96Z"
+            )
+            .expect("public key")
+        );
+        let json = keypair.to_solana_json();
+        assert!(json.starts_with("[157,97,177,"));
+        assert_eq!(
+            keypair,
+            Keypair::from_solana_json(Network::MainNet, &json).expect("json keypair")
+        );
+        // The public key half has to match the secret
+        bytes[63] ^= 1;
+        assert!(Keypair::from_solana_bytes(Network::MainNet, &bytes).is_err());
+    }
+
     #[test]
     fn bytes_roundtrip() {
         use rand::rngs::OsRng;
@@ -188,6 +791,84 @@ mod tests {
         assert!(public_key.verify(MSG, SIG).is_ok());
     }
 
+    #[test]
+    fn verify_options() {
+        use crate::public_key::VerifyOptions;
+        // The libp2p_crypto vector has to pass every set of rules
+        const MSG: &[u8] = b"hello world";
+        const PUBKEY: &str = "13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw";
+        const SIG: &[u8] =
+            &hex!("ef3e85dc7ea338c6b67399873131ea7b2265c516222e105fc39a59dda71f668a3b95fe27457d941a3cf5c422c9efbf0da112171d2997d74bc68f7b8118c6930e");
+        let public_key: crate::PublicKey = PUBKEY.parse().expect("b58 public key");
+        let cofactored = VerifyOptions {
+            cofactored: true,
+            ..VerifyOptions::CONSENSUS
+        };
+        for options in &[VerifyOptions::DEFAULT, VerifyOptions::CONSENSUS, cofactored] {
+            assert!(public_key.verify_with_options(MSG, SIG, options).is_ok());
+            assert!(public_key
+                .verify_with_options(b"hello there", SIG, options)
+                .is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-export"))]
+    fn dalek_conversions() {
+        use rand::rngs::OsRng;
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let dalek = ed25519_dalek::Keypair::from(&keypair);
+        assert_eq!(
+            keypair,
+            Keypair::from_dalek(Network::TestNet, dalek).expect("keypair")
+        );
+        let public_key = super::PublicKey::try_from(&keypair.public_key).expect("ed25519 key");
+        assert_eq!(
+            keypair.public_key,
+            crate::PublicKey::from_ed25519(Network::TestNet, public_key)
+        );
+    }
+
+    #[test]
+    fn debug_redacted() {
+        use crate::DebugSecret;
+        use rand::rngs::OsRng;
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let secret: String = keypair
+            .secret_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let debug = format!("{:?}", keypair);
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains(&secret));
+        assert!(format!("{:?}", keypair.debug_secret()).contains(&secret));
+        assert_eq!(keypair.public_key.to_string(), keypair.to_string());
+    }
+
+    #[test]
+    fn reject_weak_points() {
+        use crate::error::{DecodeError, Error, PointError};
+        // The identity point (y = 1) has small order
+        let mut identity = [0u8; 33];
+        identity[0] = 0x01;
+        identity[1] = 0x01;
+        // y = p is a non-canonical encoding of y = 0
+        let mut non_canonical = [0xffu8; 33];
+        non_canonical[0] = 0x01;
+        non_canonical[1] = 0xed;
+        non_canonical[32] = 0x7f;
+        for (bytes, expected) in &[
+            (identity, PointError::SmallOrder),
+            (non_canonical, PointError::NonCanonical),
+        ] {
+            match crate::PublicKey::from_bytes(&bytes[..]) {
+                Err(Error::Decode(DecodeError::Point(err))) => assert_eq!(*expected, err),
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+
     #[test]
     fn b58_roundtrip_ecc() {
         const B58: &str = "14HZVR4bdF9QMowYxWrumcFBNfWnhDdD5XXA5za1fWwUhHxxFS1";
@@ -0,0 +1,161 @@
+//! Entropy sources feed key generation. Any `rand_core` CSPRNG, like `OsRng`,
+//! is an entropy source. On bare metal targets without an operating system
+//! random number generator, a hardware TRNG peripheral can be used by
+//! implementing [`EntropySource`] for it:
+//!
+//! ```ignore
+//! use helium_crypto::{ecc_compact, entropy::EntropySource, error, Network};
+//!
+//! struct Trng(hal::rng::Rng);
+//!
+//! impl EntropySource for Trng {
+//!     fn fill_entropy(&mut self, dest: &mut [u8]) -> error::Result {
+//!         for chunk in dest.chunks_mut(4) {
+//!             // Block until the peripheral has a fresh word available
+//!             let word = nb::block!(self.0.read()).map_err(|_| error::entropy_failure())?;
+//!             chunk.copy_from_slice(&word.to_le_bytes()[..chunk.len()]);
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut trng);
+//! ```
+use crate::error::{self, EntropyError};
+use core::num::NonZeroU32;
+
+/// Uniformly random secrets of 32 bytes have close to 32 distinct bytes, and
+/// fewer than this many with a probability of about 2^-122.
+const MIN_DISTINCT_BYTES: usize = 8;
+
+/// A source of cryptographically secure random bytes used to generate keys.
+pub trait EntropySource {
+    /// Fill the given buffer entirely with random bytes.
+    fn fill_entropy(&mut self, dest: &mut [u8]) -> error::Result;
+}
+
+impl<R> EntropySource for R
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    fn fill_entropy(&mut self, dest: &mut [u8]) -> error::Result {
+        self.try_fill_bytes(dest).map_err(error::Error::from)
+    }
+}
+
+/// Check that entropy given for a secret key has the expected length, is not
+/// all zero and has enough distinct bytes to plausibly be random. This catches
+/// uninitialized buffers, repeated patterns and similar mistakes, but can not
+/// tell a weak source from a good one. Checking the secret against the curve
+/// order is left to the key type.
+pub(crate) fn check_secret(secret: &[u8], expected: usize) -> error::Result {
+    if secret.len() != expected {
+        return Err(error::weak_entropy(EntropyError::Length {
+            expected,
+            found: secret.len(),
+        }));
+    }
+    if secret.iter().all(|byte| *byte == 0) {
+        return Err(error::weak_entropy(EntropyError::Zero));
+    }
+    let mut seen = [false; 256];
+    for byte in secret {
+        seen[*byte as usize] = true;
+    }
+    if seen.iter().filter(|seen| **seen).count() < MIN_DISTINCT_BYTES {
+        return Err(error::weak_entropy(EntropyError::LowQuality));
+    }
+    Ok(())
+}
+
+/// Adapts an entropy source into a `rand_core` CSPRNG for the underlying
+/// curve libraries. Infallible generation methods panic if the entropy source
+/// fails, matching the behavior of `rand_core::RngCore::fill_bytes`.
+pub(crate) struct EntropyRng<'a, E: ?Sized>(pub(crate) &'a mut E);
+
+impl<'a, E: EntropySource + ?Sized> rand_core::RngCore for EntropyRng<'a, E> {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_entropy(dest).expect("entropy source")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        match self.0.fill_entropy(dest) {
+            Ok(()) => Ok(()),
+            Err(error::Error::Entropy(err)) => Err(err),
+            Err(_) => Err(rand_core::Error::from(
+                NonZeroU32::new(rand_core::Error::CUSTOM_START).expect("non zero error code"),
+            )),
+        }
+    }
+}
+
+impl<'a, E: EntropySource + ?Sized> rand_core::CryptoRng for EntropyRng<'a, E> {}
+
+/// A deterministic ChaCha20 based random number generator for tests and for
+/// generating reproducible fixtures. It is only available with the
+/// `test-utils` feature in builds with debug assertions, so it can not end up
+/// generating keys in a release build.
+#[cfg(all(feature = "test-utils", debug_assertions))]
+pub struct TestRng(rand_chacha::ChaCha20Rng);
+
+#[cfg(all(feature = "test-utils", debug_assertions))]
+impl TestRng {
+    /// Create a generator from a 32 byte seed, which is used as the ChaCha20
+    /// key with a zero nonce and stream position so other implementations can
+    /// reproduce the same output.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        use rand_core::SeedableRng;
+        Self(rand_chacha::ChaCha20Rng::from_seed(seed))
+    }
+
+    /// Create a generator from a small seed, convenient for property tests.
+    pub fn from_u64(seed: u64) -> Self {
+        use rand_core::SeedableRng;
+        Self(rand_chacha::ChaCha20Rng::seed_from_u64(seed))
+    }
+}
+
+#[cfg(all(feature = "test-utils", debug_assertions))]
+impl rand_core::RngCore for TestRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::RngCore::next_u32(&mut self.0)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::RngCore::next_u64(&mut self.0)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand_core::RngCore::fill_bytes(&mut self.0, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        rand_core::RngCore::try_fill_bytes(&mut self.0, dest)
+    }
+}
+
+#[cfg(all(feature = "test-utils", debug_assertions))]
+impl rand_core::CryptoRng for TestRng {}
+
+#[cfg(all(test, feature = "test-utils"))]
+mod tests {
+    use super::TestRng;
+    use crate::{ed25519, Network};
+
+    #[test]
+    fn seeded_generate() {
+        let a = ed25519::Keypair::generate(Network::MainNet, &mut TestRng::from_u64(42));
+        let b = ed25519::Keypair::generate(Network::MainNet, &mut TestRng::from_u64(42));
+        let c = ed25519::Keypair::generate(Network::MainNet, &mut TestRng::from_u64(43));
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+}
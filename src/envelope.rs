@@ -0,0 +1,410 @@
+//! Multi-recipient envelopes: a payload encrypted once, with its key wrapped
+//! to each of a number of public keys, possibly of different key types. This
+//! is how a configuration blob is distributed to a fleet of gateways.
+//!
+//! The random file key is wrapped for ecc_compact and secp256k1 recipients
+//! with [ECIES](crate::ecies). For ed25519 recipients it is wrapped with an
+//! ephemeral X25519 key against the X25519 form of the recipient key, the
+//! shared secret expanded into a ChaCha20-Poly1305 key and nonce with
+//! HKDF-SHA256 as for ECIES. An envelope is laid out as:
+//!
+//! ```text
+//! magic "HENV" (4) | version (1) | recipient count (1) | stanzas | payload nonce (16) | chunks
+//! stanza: key hint (4) | length (1) | wrapped file key
+//! ```
+//!
+//! The key hint is the start of the SHA-256 digest of the binary recipient
+//! key, so a recipient only tries the stanzas likely meant for it. The
+//! payload is encrypted in chunks of 64 KiB with ChaCha20-Poly1305, under a
+//! key derived from the file key with HKDF-SHA256 using the header up to and
+//! including the payload nonce as the salt, so the header can't be altered
+//! either. A chunk nonce is an 11 byte big endian counter followed by a byte
+//! that is 1 for the last chunk, which detects truncated envelopes.
+//!
+//! When opening a stream, each chunk is written out as soon as it has been
+//! authenticated. Output written before an error has to be discarded.
+use crate::{entropy::EntropySource, error, KeyType, Keypair, PublicKey};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use curve25519_dalek::{constants::X25519_BASEPOINT, montgomery::MontgomeryPoint, scalar::Scalar};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use std::{
+    convert::{TryFrom, TryInto},
+    io::{self, Read, Write},
+};
+use zeroize::Zeroize;
+
+const MAGIC: &[u8; 4] = b"HENV";
+const VERSION: u8 = 1;
+const PAYLOAD_INFO: &[u8] = b"helium-crypto envelope payload v1";
+const X25519_INFO: &[u8] = b"helium-crypto envelope x25519 v1";
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+const HINT_LENGTH: usize = 4;
+const PAYLOAD_NONCE_LENGTH: usize = 16;
+/// The size of the plaintext chunks of the payload.
+pub const CHUNK_LENGTH: usize = 64 * 1024;
+
+/// Encrypt the given message to the given recipients. The file key and the
+/// ephemeral keys are drawn from the given entropy source.
+pub fn seal<E>(recipients: &[PublicKey], msg: &[u8], entropy: &mut E) -> error::Result<Vec<u8>>
+where
+    E: EntropySource + ?Sized,
+{
+    let mut result = Vec::new();
+    seal_stream(recipients, msg, &mut result, entropy)?;
+    Ok(result)
+}
+
+/// Open an envelope with the given keypair, which has to be one of its
+/// recipients. Fails with `DecodeError::Envelope` if the envelope is
+/// malformed, not for this keypair or has been tampered with.
+pub fn open(keypair: &Keypair, envelope: &[u8]) -> error::Result<Vec<u8>> {
+    let mut result = Vec::new();
+    open_stream(keypair, envelope, &mut result)?;
+    Ok(result)
+}
+
+/// Encrypt everything read from the given reader to the given recipients
+/// and write the envelope to the given writer. Only one chunk is held in
+/// memory at a time.
+pub fn seal_stream<R, W, E>(
+    recipients: &[PublicKey],
+    mut reader: R,
+    mut writer: W,
+    entropy: &mut E,
+) -> error::Result
+where
+    R: Read,
+    W: Write,
+    E: EntropySource + ?Sized,
+{
+    let count = u8::try_from(recipients.len()).map_err(|_| error::invalid_envelope())?;
+    if count == 0 {
+        return Err(error::invalid_envelope());
+    }
+    let mut file_key = [0u8; KEY_LENGTH];
+    entropy.fill_entropy(&mut file_key)?;
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&[VERSION, count]);
+    for recipient in recipients {
+        let wrapped = wrap(recipient, &file_key, entropy)?;
+        header.extend_from_slice(&hint(recipient));
+        header.push(wrapped.len() as u8);
+        header.extend_from_slice(&wrapped);
+    }
+    let mut nonce = [0u8; PAYLOAD_NONCE_LENGTH];
+    entropy.fill_entropy(&mut nonce)?;
+    header.extend_from_slice(&nonce);
+    let cipher = payload_cipher(&file_key, &header);
+    file_key.zeroize();
+    writer.write_all(&header)?;
+
+    // Read ahead by one chunk to know which chunk is the last one
+    let mut chunk = vec![0u8; CHUNK_LENGTH];
+    let mut next = vec![0u8; CHUNK_LENGTH];
+    let mut len = read_full(&mut reader, &mut chunk)?;
+    let mut counter = 0;
+    loop {
+        let next_len = if len == CHUNK_LENGTH {
+            read_full(&mut reader, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        let ciphertext = cipher
+            .encrypt(&chunk_nonce(counter, last), &chunk[..len])
+            .map_err(|_| error::invalid_envelope())?;
+        writer.write_all(&ciphertext)?;
+        if last {
+            chunk.zeroize();
+            next.zeroize();
+            return Ok(());
+        }
+        std::mem::swap(&mut chunk, &mut next);
+        len = next_len;
+        counter += 1;
+    }
+}
+
+/// Open an envelope read from the given reader with the given keypair and
+/// write the payload to the given writer.
+pub fn open_stream<R, W>(keypair: &Keypair, mut reader: R, mut writer: W) -> error::Result
+where
+    R: Read,
+    W: Write,
+{
+    let mut header = vec![0u8; MAGIC.len() + 2];
+    read_exact(&mut reader, &mut header)?;
+    if header[..MAGIC.len()] != MAGIC[..] || header[MAGIC.len()] != VERSION {
+        return Err(error::invalid_envelope());
+    }
+    let hint = hint(keypair.public_key());
+    let mut file_key = None;
+    for _ in 0..header[MAGIC.len() + 1] {
+        let start = header.len();
+        header.resize(start + HINT_LENGTH + 1, 0);
+        read_exact(&mut reader, &mut header[start..])?;
+        let len = header[start + HINT_LENGTH] as usize;
+        header.resize(start + HINT_LENGTH + 1 + len, 0);
+        read_exact(&mut reader, &mut header[start + HINT_LENGTH + 1..])?;
+        if file_key.is_none() && header[start..start + HINT_LENGTH] == hint {
+            file_key = unwrap(keypair, &header[start + HINT_LENGTH + 1..]);
+        }
+    }
+    let start = header.len();
+    header.resize(start + PAYLOAD_NONCE_LENGTH, 0);
+    read_exact(&mut reader, &mut header[start..])?;
+    let mut file_key = file_key.ok_or_else(error::invalid_envelope)?;
+    let cipher = payload_cipher(&file_key, &header);
+    file_key.zeroize();
+
+    let mut chunk = vec![0u8; CHUNK_LENGTH + TAG_LENGTH];
+    let mut next = vec![0u8; CHUNK_LENGTH + TAG_LENGTH];
+    let mut len = read_full(&mut reader, &mut chunk)?;
+    let mut counter = 0;
+    loop {
+        let next_len = if len == chunk.len() {
+            read_full(&mut reader, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        // Only the payload of an empty message ends in an empty chunk
+        if last && counter > 0 && len == TAG_LENGTH {
+            return Err(error::invalid_envelope());
+        }
+        let mut plaintext = cipher
+            .decrypt(&chunk_nonce(counter, last), &chunk[..len])
+            .map_err(|_| error::invalid_envelope())?;
+        writer.write_all(&plaintext)?;
+        plaintext.zeroize();
+        if last {
+            return Ok(());
+        }
+        std::mem::swap(&mut chunk, &mut next);
+        len = next_len;
+        counter += 1;
+    }
+}
+
+fn hint(public_key: &PublicKey) -> [u8; HINT_LENGTH] {
+    Sha256::digest(&public_key.to_vec())[..HINT_LENGTH]
+        .try_into()
+        .expect("hint length")
+}
+
+fn wrap<E>(
+    recipient: &PublicKey,
+    file_key: &[u8; KEY_LENGTH],
+    entropy: &mut E,
+) -> error::Result<Vec<u8>>
+where
+    E: EntropySource + ?Sized,
+{
+    match recipient.tag().key_type {
+        KeyType::EccCompact | KeyType::Secp256k1 => recipient.encrypt(file_key, entropy),
+        KeyType::Ed25519 => {
+            let recipient = crate::ed25519::PublicKey::try_from(recipient)?.to_x25519();
+            let mut secret = [0u8; KEY_LENGTH];
+            entropy.fill_entropy(&mut secret)?;
+            secret[0] &= 248;
+            secret[31] &= 127;
+            secret[31] |= 64;
+            let ephemeral = Scalar::from_bits(secret);
+            secret.zeroize();
+            let share = (X25519_BASEPOINT * ephemeral).to_bytes();
+            let (cipher, nonce) = x25519_cipher(&ephemeral, &recipient, &share, &recipient)
+                .ok_or_else(error::invalid_envelope)?;
+            let mut result = share.to_vec();
+            result.extend_from_slice(
+                &cipher
+                    .encrypt(
+                        &nonce,
+                        Payload {
+                            msg: file_key,
+                            aad: &share,
+                        },
+                    )
+                    .map_err(|_| error::invalid_envelope())?,
+            );
+            Ok(result)
+        }
+        key_type => Err(error::unsupported_keytype(key_type)),
+    }
+}
+
+/// The file key in the given stanza if it was wrapped for the given keypair.
+fn unwrap(keypair: &Keypair, wrapped: &[u8]) -> Option<[u8; KEY_LENGTH]> {
+    let mut file_key = match keypair {
+        Keypair::EccCompact(_) | Keypair::Secp256k1(_) => keypair.decrypt(wrapped).ok()?,
+        Keypair::Ed25519(keypair) => {
+            if wrapped.len() < KEY_LENGTH {
+                return None;
+            }
+            let (share, ciphertext) = wrapped.split_at(KEY_LENGTH);
+            let share: [u8; KEY_LENGTH] = share.try_into().expect("share length");
+            let recipient = crate::ed25519::PublicKey::try_from(&keypair.public_key)
+                .ok()?
+                .to_x25519();
+            let mut secret = keypair.to_x25519_secret();
+            let (cipher, nonce) =
+                x25519_cipher(&Scalar::from_bits(secret), &share, &share, &recipient)?;
+            secret.zeroize();
+            cipher
+                .decrypt(
+                    &nonce,
+                    Payload {
+                        msg: ciphertext,
+                        aad: &share,
+                    },
+                )
+                .ok()?
+        }
+        Keypair::Schnorr(_) => return None,
+    };
+    let result = <[u8; KEY_LENGTH]>::try_from(&file_key[..]).ok();
+    file_key.zeroize();
+    result
+}
+
+/// The cipher and nonce wrapping the file key for an ed25519 recipient. The
+/// secret is either the ephemeral secret with the recipient as the peer, when
+/// sealing, or the recipient secret with the share as the peer, when opening.
+/// Returns `None` if the peer is a low order point.
+fn x25519_cipher(
+    secret: &Scalar,
+    peer: &[u8; KEY_LENGTH],
+    share: &[u8; KEY_LENGTH],
+    recipient: &[u8; KEY_LENGTH],
+) -> Option<(ChaCha20Poly1305, Nonce)> {
+    let mut shared = (MontgomeryPoint(*peer) * secret).to_bytes();
+    if shared == [0u8; KEY_LENGTH] {
+        return None;
+    }
+    let mut salt = share.to_vec();
+    salt.extend_from_slice(recipient);
+    let mut okm = [0u8; KEY_LENGTH + NONCE_LENGTH];
+    Hkdf::<Sha256>::new(Some(&salt), &shared)
+        .expand(X25519_INFO, &mut okm)
+        .expect("hkdf output length");
+    shared.zeroize();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&okm[..KEY_LENGTH]));
+    let nonce = *Nonce::from_slice(&okm[KEY_LENGTH..]);
+    okm.zeroize();
+    Some((cipher, nonce))
+}
+
+fn payload_cipher(file_key: &[u8; KEY_LENGTH], header: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = [0u8; KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(header), file_key)
+        .expand(PAYLOAD_INFO, &mut key)
+        .expect("hkdf output length");
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    key.zeroize();
+    cipher
+}
+
+fn chunk_nonce(counter: u64, last: bool) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Reads until the buffer is full or the reader is exhausted, and returns
+/// the number of bytes read.
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buffer.len() {
+        match reader.read(&mut buffer[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
+/// Reads the header field filling the buffer, failing on a truncated header.
+fn read_exact<R: Read>(reader: &mut R, buffer: &mut [u8]) -> error::Result {
+    if read_full(reader, buffer)? != buffer.len() {
+        return Err(error::invalid_envelope());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open, open_stream, seal, CHUNK_LENGTH};
+    use crate::{ecc_compact, ed25519, secp256k1, Keypair, Network};
+    use rand::rngs::OsRng;
+
+    fn recipients() -> Vec<Keypair> {
+        vec![
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng)),
+        ]
+    }
+
+    #[test]
+    fn roundtrip() {
+        let keypairs = recipients();
+        let public_keys: Vec<_> = keypairs.iter().map(|k| k.public_key().clone()).collect();
+        for msg in [
+            vec![],
+            b"hello world".to_vec(),
+            vec![7u8; CHUNK_LENGTH],
+            vec![9u8; 2 * CHUNK_LENGTH + 1],
+        ] {
+            let envelope = seal(&public_keys, &msg, &mut OsRng).expect("seal");
+            for keypair in &keypairs {
+                assert_eq!(msg, open(keypair, &envelope).expect("open"));
+                let mut streamed = Vec::new();
+                open_stream(keypair, &envelope[..], &mut streamed).expect("open stream");
+                assert_eq!(msg, streamed);
+            }
+
+            let mut tampered = envelope.clone();
+            let last = tampered.len() - 1;
+            tampered[last] ^= 1;
+            assert!(open(&keypairs[0], &tampered).is_err());
+            let mut tampered = envelope.clone();
+            tampered[7] ^= 1;
+            assert!(open(&keypairs[2], &tampered).is_err());
+            assert!(open(&keypairs[1], &envelope[..envelope.len() - 1]).is_err());
+        }
+    }
+
+    #[test]
+    fn truncated_chunks() {
+        let keypairs = recipients();
+        let public_keys: Vec<_> = keypairs.iter().map(|k| k.public_key().clone()).collect();
+        let envelope = seal(&public_keys, &[1u8; 2 * CHUNK_LENGTH], &mut OsRng).expect("seal");
+        // Dropping the whole last chunk leaves a complete, but non-final, chunk
+        assert!(open(
+            &keypairs[1],
+            &envelope[..envelope.len() - CHUNK_LENGTH - 16]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn wrong_recipient() {
+        let keypairs = recipients();
+        let envelope = seal(
+            &[keypairs[0].public_key().clone()],
+            b"hello world",
+            &mut OsRng,
+        )
+        .expect("seal");
+        assert!(open(&keypairs[1], &envelope).is_err());
+        assert!(seal(&[], b"hello world", &mut OsRng).is_err());
+    }
+}
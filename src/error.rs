@@ -1,6 +1,10 @@
+use crate::{KeyType, Network};
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+use core::num::NonZeroU32;
 use p256::elliptic_curve;
 use thiserror::Error;
-pub type Result<T = ()> = std::result::Result<T, Error>;
+pub type Result<T = ()> = core::result::Result<T, Error>;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -9,19 +13,330 @@ pub enum Error {
     #[error("elliptic_curve error")]
     EccCompact(elliptic_curve::Error),
     #[error("signature error")]
-    Signature(#[from] signature::Error),
+    Signature(#[cfg_attr(feature = "std", source)] signature::Error),
+    #[cfg(feature = "std")]
+    #[error("io error")]
+    Io(#[from] std::io::Error),
+    #[error("entropy error")]
+    Entropy(#[cfg_attr(feature = "std", source)] rand_core::Error),
+    #[error("rejected key entropy: {0}")]
+    WeakEntropy(#[from] EntropyError),
+    #[cfg(feature = "smartcard")]
+    #[error("smartcard error")]
+    Smartcard(#[from] pcsc::Error),
+    #[cfg(feature = "smartcard")]
+    #[error("smartcard status {0:#06x}")]
+    SmartcardStatus(u16),
+    #[cfg(feature = "smartcard")]
+    #[error("invalid smartcard response")]
+    InvalidSmartcardResponse,
+    #[cfg(feature = "yubikey")]
+    #[error("unsupported smartcard slot {0:?}")]
+    SmartcardSlot(crate::smartcard::Slot),
+    #[cfg(feature = "ecc608")]
+    #[error("ecc608 bus error")]
+    Ecc608Bus,
+    #[cfg(feature = "ecc608")]
+    #[error("ecc608 status {0:#04x}")]
+    Ecc608Status(u8),
+    #[cfg(feature = "ecc608")]
+    #[error("invalid ecc608 slot {0}")]
+    Ecc608Slot(u8),
+    #[cfg(feature = "ecc608")]
+    #[error("invalid ecc608 response")]
+    InvalidEcc608Response,
+    #[cfg(feature = "tpm")]
+    #[error("tpm error")]
+    Tpm(#[from] tss_esapi::Error),
+    #[cfg(feature = "tpm")]
+    #[error("invalid tpm response")]
+    InvalidTpmResponse,
+    #[cfg(feature = "pkcs11")]
+    #[error("pkcs11 error")]
+    Pkcs11(#[from] cryptoki::error::Error),
+    #[cfg(feature = "pkcs11")]
+    #[error("pkcs11 {0} not found")]
+    Pkcs11NotFound(String),
+    #[cfg(feature = "pkcs11")]
+    #[error("invalid pkcs11 response")]
+    InvalidPkcs11Response,
+    #[cfg(feature = "ledger")]
+    #[error("ledger transport error")]
+    Ledger(#[from] hidapi::HidError),
+    #[cfg(feature = "ledger")]
+    #[error("no ledger device found")]
+    LedgerNotFound,
+    #[cfg(feature = "ledger")]
+    #[error("ledger status {0:#06x}")]
+    LedgerStatus(u16),
+    #[cfg(feature = "ledger")]
+    #[error("invalid ledger response")]
+    InvalidLedgerResponse,
+    #[cfg(feature = "ssh-agent")]
+    #[error("ssh agent {0} not found")]
+    SshAgentNotFound(String),
+    #[cfg(feature = "ssh-agent")]
+    #[error("ssh agent refused the request")]
+    SshAgentFailure,
+    #[cfg(feature = "ssh-agent")]
+    #[error("invalid ssh agent response")]
+    InvalidSshAgentResponse,
+    #[cfg(feature = "kms")]
+    #[error("kms error")]
+    Kms(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[cfg(feature = "kms")]
+    #[error("invalid kms response")]
+    InvalidKmsResponse,
+    #[cfg(feature = "secure-enclave")]
+    #[error("secure enclave error: {0}")]
+    SecureEnclave(String),
+    #[cfg(feature = "keyfile")]
+    #[error("incorrect password or corrupted keypair file")]
+    KeyfilePassword,
+    #[cfg(feature = "keystore")]
+    #[error("incorrect password or corrupted keystore")]
+    KeystorePassword,
+    #[cfg(feature = "hpke")]
+    #[error("hpke error")]
+    Hpke(#[from] hpke::HpkeError),
+    #[error("signer pool queue full")]
+    PoolFull,
+    #[error("signer pool closed")]
+    PoolClosed,
+    #[error("invalid seed length {0}")]
+    SeedLength(usize),
+    #[error("wallet account {0} does not match seed")]
+    WalletMismatch(u32),
+    #[cfg(feature = "alloc")]
+    #[error("device key does not match local key for {0}")]
+    DeviceKeyMismatch(String),
+    #[cfg(feature = "http-signature")]
+    #[error("http message signature not yet valid or expired")]
+    HttpSignatureExpired,
+    #[cfg(feature = "jose")]
+    #[error("json web token not yet valid or expired")]
+    JwtExpired,
+    #[cfg(feature = "txn")]
+    #[error("transaction has no such signer")]
+    TxnSigner,
+    #[error("invalid multisig threshold {0} of {1}")]
+    MultisigThreshold(usize, usize),
+    #[cfg(feature = "alloc")]
+    #[error("duplicate multisig member {0}")]
+    MultisigDuplicateMember(String),
+    #[error("invalid secret sharing threshold {0} of {1}")]
+    ShareThreshold(u8, u8),
+    #[cfg(feature = "std")]
+    #[error("frost participant {0} sent an invalid proof or share")]
+    FrostParticipant(u8),
+    #[error("output buffer too small, {0} bytes required")]
+    BufferTooSmall(usize),
+    #[error("too many keypairs requested at once: {0}")]
+    BatchTooLarge(usize),
+    #[error("{found} signature given for {expected} key")]
+    SignatureTypeMismatch { expected: KeyType, found: KeyType },
+    #[error("unsupported prehash for signer or key")]
+    UnsupportedPrehash,
+    #[error("unsupported key type {0}")]
+    UnsupportedKeyType(KeyType),
+    #[cfg(feature = "std")]
+    #[error("key type {0:#04x} is already registered")]
+    KeyTypeRegistered(u8),
+    #[cfg(feature = "alloc")]
+    #[error("firmware rejected")]
+    Firmware(#[from] FirmwareError),
+    #[error("key rotation rejected")]
+    Rotation(#[from] RotationError),
 }
 
 #[derive(Error, Debug)]
 pub enum DecodeError {
     #[error("b58 decode error")]
-    B58(#[from] bs58::decode::Error),
+    B58(#[cfg_attr(feature = "std", source)] bs58::decode::Error),
     #[error("unrecognized type value {0}")]
     Type(u8),
+    #[error("unrecognized network value {0}")]
+    Network(u8),
+    #[error("invalid length {found}, expected {expected}")]
+    Length { expected: usize, found: usize },
+    #[cfg(feature = "alloc")]
     #[error("unrecognized type string {0}")]
     TypeString(String),
+    // Without alloc the unrecognized string can not be kept
+    #[cfg(not(feature = "alloc"))]
+    #[error("unrecognized type string")]
+    TypeString,
     #[error("not a compact key")]
     NotCompact,
+    #[cfg(feature = "pkcs8")]
+    #[error("invalid pkcs8 document")]
+    Pkcs8,
+    #[cfg(feature = "jwk")]
+    #[error("invalid json web key")]
+    Jwk,
+    #[cfg(feature = "openssh")]
+    #[error("invalid openssh key")]
+    OpenSsh,
+    #[cfg(feature = "mnemonic")]
+    #[error("invalid mnemonic phrase")]
+    Mnemonic,
+    #[cfg(feature = "keyfile")]
+    #[error("invalid keypair file")]
+    Keyfile,
+    #[cfg(feature = "keystore")]
+    #[error("invalid keystore")]
+    Keystore,
+    #[cfg(feature = "rsa")]
+    #[error("invalid or unsupported rsa key")]
+    Rsa,
+    #[error("invalid point")]
+    Point(#[from] PointError),
+    #[error("invalid address")]
+    Address(#[from] AddressError),
+    #[error("invalid paper backup line {0}")]
+    PaperLine(usize),
+    #[error("paper backup checksum mismatch")]
+    PaperChecksum,
+    #[cfg(feature = "alloc")]
+    #[error("invalid derivation path {0}")]
+    DerivationPath(String),
+    #[error("invalid extended private key")]
+    ExtendedKey,
+    #[error("invalid wallet state")]
+    WalletState,
+    #[error("invalid key set")]
+    KeySet,
+    #[error("invalid multisig key or signature")]
+    Multisig,
+    #[error("invalid short address")]
+    ShortAddress,
+    #[error("unrecognized public key encoding")]
+    Encoding,
+    #[error("invalid or mismatched secret share")]
+    Share,
+    #[cfg(feature = "std")]
+    #[error("invalid frost message")]
+    Frost,
+    #[error("invalid musig key, nonce or partial signature")]
+    Musig,
+    #[error("invalid vrf proof")]
+    VrfProof,
+    #[error("invalid solana address or keypair")]
+    Solana,
+    #[error("invalid swarm key")]
+    SwarmKey,
+    #[error("invalid peer id")]
+    PeerId,
+    #[cfg(feature = "did-key")]
+    #[error("invalid did:key identifier")]
+    DidKey,
+    #[cfg(feature = "ecies")]
+    #[error("invalid or tampered ecies ciphertext")]
+    Ecies,
+    #[cfg(feature = "ecies")]
+    #[error("invalid or tampered envelope")]
+    Envelope,
+    #[cfg(feature = "sealed-box")]
+    #[error("invalid or tampered sealed box")]
+    SealedBox,
+    #[cfg(feature = "age")]
+    #[error("invalid or tampered age file")]
+    Age,
+    #[error("invalid firmware manifest")]
+    Manifest,
+    #[error("invalid key rotation statement")]
+    Rotation,
+    #[cfg(feature = "revocation")]
+    #[error("invalid revocation statement")]
+    Revocation,
+    #[error("invalid or non-deterministic cbor")]
+    Cbor,
+    #[error("invalid provisioning bundle")]
+    ProvisioningBundle,
+    #[cfg(feature = "http-signature")]
+    #[error("invalid http message signature")]
+    HttpSignature,
+    #[cfg(feature = "jose")]
+    #[error("invalid json web token")]
+    Jwt,
+    #[cfg(feature = "cose")]
+    #[error("invalid cose message")]
+    Cose,
+    #[cfg(feature = "attestation")]
+    #[error("invalid attestation statement")]
+    Attestation,
+}
+
+/// Reasons a public key point is rejected.
+#[derive(Error, Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PointError {
+    #[error("point is not on the curve")]
+    NotOnCurve,
+    #[error("point has small order")]
+    SmallOrder,
+    #[error("point is not in the prime order subgroup")]
+    Torsion,
+    #[error("non-canonical point encoding")]
+    NonCanonical,
+}
+
+/// Reasons entropy given for a secret key is rejected.
+#[derive(Error, Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EntropyError {
+    #[error("invalid length {found}, expected {expected}")]
+    Length { expected: usize, found: usize },
+    #[error("entropy is all zero")]
+    Zero,
+    #[error("entropy is not below the curve order")]
+    OutOfRange,
+    #[error("entropy has too few distinct bytes to be random")]
+    LowQuality,
+}
+
+/// Reasons an address string is rejected, detailed enough to show to users.
+#[derive(Error, Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AddressError {
+    #[error("invalid character '{character}' at position {index}")]
+    InvalidCharacter { character: char, index: usize },
+    #[error("invalid checksum, the address is mistyped or incomplete")]
+    Checksum,
+    #[error("unsupported address version {0}")]
+    Version(u8),
+    #[error("invalid address length, {0} bytes")]
+    Length(usize),
+    #[error("unknown network {0:#04x}")]
+    UnknownNetwork(u8),
+    #[error("address is for {found}, expected {expected}")]
+    WrongNetwork { expected: Network, found: Network },
+    #[error("unsupported key type {0:#04x}")]
+    UnsupportedKeyType(u8),
+    #[error("address does not hold a valid key")]
+    InvalidKey,
+}
+
+/// Reasons a firmware manifest or file is rejected.
+#[cfg(feature = "alloc")]
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum FirmwareError {
+    #[error("manifest is for {found} hardware, expected {expected}")]
+    Hardware { expected: String, found: String },
+    #[error("rollback counter {found} is below {minimum}")]
+    Rollback { minimum: u64, found: u64 },
+    #[error("file {0} does not match the manifest")]
+    File(String),
+}
+
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RotationError {
+    #[error("old and new key are the same")]
+    SameKey,
+    #[error("rotation {0} does not replace the current key")]
+    Chain(usize),
+    #[error("rotation {0} is not later than the previous rotation")]
+    Timestamp(usize),
 }
 
 impl From<elliptic_curve::Error> for Error {
@@ -36,14 +351,413 @@ impl From<bs58::decode::Error> for Error {
     }
 }
 
+// The signature, entropy and base58 errors only implement the error trait
+// with their std features, so they are not `#[from]` sources in no_std builds
+impl From<signature::Error> for Error {
+    fn from(v: signature::Error) -> Self {
+        Self::Signature(v)
+    }
+}
+
+impl From<rand_core::Error> for Error {
+    fn from(v: rand_core::Error) -> Self {
+        Self::Entropy(v)
+    }
+}
+
+impl From<bs58::decode::Error> for DecodeError {
+    fn from(v: bs58::decode::Error) -> Self {
+        Self::B58(v)
+    }
+}
+
 pub fn invalid_keytype(v: u8) -> Error {
     Error::Decode(DecodeError::Type(v))
 }
 
+pub fn invalid_network(v: u8) -> Error {
+    Error::Decode(DecodeError::Network(v))
+}
+
+pub fn invalid_length(expected: usize, found: usize) -> Error {
+    Error::Decode(DecodeError::Length { expected, found })
+}
+
+#[cfg(feature = "alloc")]
 pub fn invalid_keytype_str(v: &str) -> Error {
     Error::Decode(DecodeError::TypeString(v.to_string()))
 }
 
+#[cfg(not(feature = "alloc"))]
+pub fn invalid_keytype_str(_v: &str) -> Error {
+    Error::Decode(DecodeError::TypeString)
+}
+
+#[cfg(feature = "pkcs8")]
+pub fn invalid_pkcs8() -> Error {
+    Error::Decode(DecodeError::Pkcs8)
+}
+
+#[cfg(feature = "jwk")]
+pub fn invalid_jwk() -> Error {
+    Error::Decode(DecodeError::Jwk)
+}
+
+#[cfg(feature = "openssh")]
+pub fn invalid_openssh() -> Error {
+    Error::Decode(DecodeError::OpenSsh)
+}
+
+#[cfg(feature = "mnemonic")]
+pub fn invalid_mnemonic() -> Error {
+    Error::Decode(DecodeError::Mnemonic)
+}
+
+#[cfg(feature = "keyfile")]
+pub fn invalid_keyfile() -> Error {
+    Error::Decode(DecodeError::Keyfile)
+}
+
+#[cfg(feature = "keystore")]
+pub fn invalid_keystore() -> Error {
+    Error::Decode(DecodeError::Keystore)
+}
+
+#[cfg(feature = "rsa")]
+pub fn invalid_rsa_key() -> Error {
+    Error::Decode(DecodeError::Rsa)
+}
+
 pub fn not_compact() -> Error {
     Error::Decode(DecodeError::NotCompact)
 }
+
+pub fn buffer_too_small(v: usize) -> Error {
+    Error::BufferTooSmall(v)
+}
+
+pub fn batch_too_large(v: usize) -> Error {
+    Error::BatchTooLarge(v)
+}
+
+pub fn entropy_failure() -> Error {
+    Error::Entropy(rand_core::Error::from(
+        NonZeroU32::new(rand_core::Error::CUSTOM_START).expect("non zero error code"),
+    ))
+}
+
+pub fn weak_entropy(v: EntropyError) -> Error {
+    Error::WeakEntropy(v)
+}
+
+#[cfg(feature = "smartcard")]
+pub fn smartcard_status(v: u16) -> Error {
+    Error::SmartcardStatus(v)
+}
+
+#[cfg(feature = "smartcard")]
+pub fn invalid_smartcard_response() -> Error {
+    Error::InvalidSmartcardResponse
+}
+
+#[cfg(feature = "yubikey")]
+pub fn invalid_smartcard_slot(v: crate::smartcard::Slot) -> Error {
+    Error::SmartcardSlot(v)
+}
+
+#[cfg(feature = "ecc608")]
+pub fn ecc608_bus() -> Error {
+    Error::Ecc608Bus
+}
+
+#[cfg(feature = "ecc608")]
+pub fn ecc608_status(v: u8) -> Error {
+    Error::Ecc608Status(v)
+}
+
+#[cfg(feature = "ecc608")]
+pub fn invalid_ecc608_slot(v: u8) -> Error {
+    Error::Ecc608Slot(v)
+}
+
+#[cfg(feature = "ecc608")]
+pub fn invalid_ecc608_response() -> Error {
+    Error::InvalidEcc608Response
+}
+
+#[cfg(feature = "tpm")]
+pub fn invalid_tpm_response() -> Error {
+    Error::InvalidTpmResponse
+}
+
+#[cfg(feature = "pkcs11")]
+pub fn pkcs11_not_found(v: String) -> Error {
+    Error::Pkcs11NotFound(v)
+}
+
+#[cfg(feature = "pkcs11")]
+pub fn invalid_pkcs11_response() -> Error {
+    Error::InvalidPkcs11Response
+}
+
+#[cfg(feature = "ledger")]
+pub fn ledger_not_found() -> Error {
+    Error::LedgerNotFound
+}
+
+#[cfg(feature = "ledger")]
+pub fn ledger_status(v: u16) -> Error {
+    Error::LedgerStatus(v)
+}
+
+#[cfg(feature = "ledger")]
+pub fn invalid_ledger_response() -> Error {
+    Error::InvalidLedgerResponse
+}
+
+#[cfg(feature = "ssh-agent")]
+pub fn ssh_agent_not_found(v: String) -> Error {
+    Error::SshAgentNotFound(v)
+}
+
+#[cfg(feature = "ssh-agent")]
+pub fn ssh_agent_failure() -> Error {
+    Error::SshAgentFailure
+}
+
+#[cfg(feature = "ssh-agent")]
+pub fn invalid_ssh_agent_response() -> Error {
+    Error::InvalidSshAgentResponse
+}
+
+#[cfg(feature = "kms")]
+pub fn kms<E: std::error::Error + Send + Sync + 'static>(err: E) -> Error {
+    Error::Kms(Box::new(err))
+}
+
+#[cfg(feature = "kms")]
+pub fn invalid_kms_response() -> Error {
+    Error::InvalidKmsResponse
+}
+
+#[cfg(feature = "secure-enclave")]
+pub fn secure_enclave(v: String) -> Error {
+    Error::SecureEnclave(v)
+}
+
+#[cfg(feature = "keyfile")]
+pub fn keyfile_password() -> Error {
+    Error::KeyfilePassword
+}
+
+#[cfg(feature = "keystore")]
+pub fn keystore_password() -> Error {
+    Error::KeystorePassword
+}
+
+pub fn pool_full() -> Error {
+    Error::PoolFull
+}
+
+pub fn pool_closed() -> Error {
+    Error::PoolClosed
+}
+
+pub fn paper_line(v: usize) -> Error {
+    Error::Decode(DecodeError::PaperLine(v))
+}
+
+pub fn paper_checksum() -> Error {
+    Error::Decode(DecodeError::PaperChecksum)
+}
+
+#[cfg(feature = "alloc")]
+pub fn invalid_derivation_path(v: &str) -> Error {
+    Error::Decode(DecodeError::DerivationPath(v.to_string()))
+}
+
+pub fn invalid_extended_key() -> Error {
+    Error::Decode(DecodeError::ExtendedKey)
+}
+
+pub fn invalid_wallet_state() -> Error {
+    Error::Decode(DecodeError::WalletState)
+}
+
+pub fn invalid_key_set() -> Error {
+    Error::Decode(DecodeError::KeySet)
+}
+
+pub fn invalid_multisig() -> Error {
+    Error::Decode(DecodeError::Multisig)
+}
+
+pub fn invalid_short_address() -> Error {
+    Error::Decode(DecodeError::ShortAddress)
+}
+
+pub fn invalid_encoding() -> Error {
+    Error::Decode(DecodeError::Encoding)
+}
+
+pub fn invalid_share() -> Error {
+    Error::Decode(DecodeError::Share)
+}
+
+#[cfg(feature = "std")]
+pub fn invalid_frost() -> Error {
+    Error::Decode(DecodeError::Frost)
+}
+
+pub fn invalid_musig() -> Error {
+    Error::Decode(DecodeError::Musig)
+}
+
+pub fn invalid_vrf_proof() -> Error {
+    Error::Decode(DecodeError::VrfProof)
+}
+
+pub fn invalid_solana() -> Error {
+    Error::Decode(DecodeError::Solana)
+}
+
+pub fn invalid_swarm_key() -> Error {
+    Error::Decode(DecodeError::SwarmKey)
+}
+
+pub fn invalid_peer_id() -> Error {
+    Error::Decode(DecodeError::PeerId)
+}
+
+#[cfg(feature = "did-key")]
+pub fn invalid_did_key() -> Error {
+    Error::Decode(DecodeError::DidKey)
+}
+
+#[cfg(feature = "ecies")]
+pub fn invalid_ecies() -> Error {
+    Error::Decode(DecodeError::Ecies)
+}
+
+#[cfg(feature = "ecies")]
+pub fn invalid_envelope() -> Error {
+    Error::Decode(DecodeError::Envelope)
+}
+
+#[cfg(feature = "sealed-box")]
+pub fn invalid_sealed_box() -> Error {
+    Error::Decode(DecodeError::SealedBox)
+}
+
+#[cfg(feature = "age")]
+pub fn invalid_age() -> Error {
+    Error::Decode(DecodeError::Age)
+}
+
+pub fn invalid_manifest() -> Error {
+    Error::Decode(DecodeError::Manifest)
+}
+
+pub fn invalid_rotation() -> Error {
+    Error::Decode(DecodeError::Rotation)
+}
+
+#[cfg(feature = "revocation")]
+pub fn invalid_revocation() -> Error {
+    Error::Decode(DecodeError::Revocation)
+}
+
+pub fn invalid_cbor() -> Error {
+    Error::Decode(DecodeError::Cbor)
+}
+
+pub fn invalid_provisioning_bundle() -> Error {
+    Error::Decode(DecodeError::ProvisioningBundle)
+}
+
+pub fn multisig_threshold(m: usize, n: usize) -> Error {
+    Error::MultisigThreshold(m, n)
+}
+
+#[cfg(feature = "alloc")]
+pub fn multisig_duplicate_member(v: &str) -> Error {
+    Error::MultisigDuplicateMember(v.to_string())
+}
+
+pub fn share_threshold(m: u8, n: u8) -> Error {
+    Error::ShareThreshold(m, n)
+}
+
+#[cfg(feature = "std")]
+pub fn frost_participant(v: u8) -> Error {
+    Error::FrostParticipant(v)
+}
+
+#[cfg(feature = "http-signature")]
+pub fn invalid_http_signature() -> Error {
+    Error::Decode(DecodeError::HttpSignature)
+}
+
+#[cfg(feature = "http-signature")]
+pub fn http_signature_expired() -> Error {
+    Error::HttpSignatureExpired
+}
+
+#[cfg(feature = "jose")]
+pub fn invalid_jwt() -> Error {
+    Error::Decode(DecodeError::Jwt)
+}
+
+#[cfg(feature = "jose")]
+pub fn jwt_expired() -> Error {
+    Error::JwtExpired
+}
+
+#[cfg(feature = "txn")]
+pub fn txn_signer() -> Error {
+    Error::TxnSigner
+}
+
+#[cfg(feature = "cose")]
+pub fn invalid_cose() -> Error {
+    Error::Decode(DecodeError::Cose)
+}
+
+#[cfg(feature = "attestation")]
+pub fn invalid_attestation() -> Error {
+    Error::Decode(DecodeError::Attestation)
+}
+
+pub fn invalid_seed_length(v: usize) -> Error {
+    Error::SeedLength(v)
+}
+
+pub fn wallet_mismatch(v: u32) -> Error {
+    Error::WalletMismatch(v)
+}
+
+#[cfg(feature = "alloc")]
+pub fn device_key_mismatch(v: &str) -> Error {
+    Error::DeviceKeyMismatch(v.to_string())
+}
+
+pub fn invalid_point(v: PointError) -> Error {
+    Error::Decode(DecodeError::Point(v))
+}
+
+pub fn signature_type_mismatch(expected: KeyType, found: KeyType) -> Error {
+    Error::SignatureTypeMismatch { expected, found }
+}
+
+pub fn unsupported_prehash() -> Error {
+    Error::UnsupportedPrehash
+}
+
+pub fn unsupported_keytype(v: KeyType) -> Error {
+    Error::UnsupportedKeyType(v)
+}
+
+#[cfg(feature = "std")]
+pub fn keytype_registered(v: u8) -> Error {
+    Error::KeyTypeRegistered(v)
+}
@@ -0,0 +1,97 @@
+//! [UniFFI](https://mozilla.github.io/uniffi-rs/) bindings for the mobile
+//! wallets, covering key generation, parsing, signing and verification.
+//!
+//! Bindings are generated in library mode from a `cdylib` or `staticlib` crate
+//! that depends on this one with the `uniffi` feature:
+//!
+//! ```text
+//! uniffi-bindgen generate --library libwallet.so --language kotlin --out-dir out
+//! uniffi-bindgen generate --library libwallet.a --language swift --out-dir out
+//! ```
+//!
+//! Keypairs and public keys cross the boundary as objects and are created from
+//! and converted to their binary and b58 forms. All errors surface as
+//! [`FfiError`] with the message of the underlying error.
+use crate::{error, KeyTag, Keypair, PublicKey, Sign, Verify};
+use std::{convert::TryFrom, sync::Arc};
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum FfiError {
+    #[error("{0}")]
+    Crypto(#[from] error::Error),
+}
+
+/// A keypair of any of the supported key types.
+#[derive(uniffi::Object)]
+pub struct FfiKeypair(Keypair);
+
+#[uniffi::export]
+impl FfiKeypair {
+    /// Generate a keypair for the given key tag, like `mainnet/ecc_compact`.
+    #[uniffi::constructor]
+    pub fn generate(tag: String) -> Result<Arc<Self>, FfiError> {
+        let tag: KeyTag = tag.parse()?;
+        Ok(Arc::new(Self(Keypair::generate_default(tag)?)))
+    }
+
+    /// Parse a keypair from its binary form.
+    #[uniffi::constructor]
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Arc<Self>, FfiError> {
+        Ok(Arc::new(Self(Keypair::try_from(&bytes[..])?)))
+    }
+
+    /// The binary form of the keypair.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    /// The public key of the keypair.
+    pub fn public_key(&self) -> Arc<FfiPublicKey> {
+        Arc::new(FfiPublicKey(self.0.public_key().clone()))
+    }
+
+    /// Sign the given message.
+    pub fn sign(&self, msg: Vec<u8>) -> Result<Vec<u8>, FfiError> {
+        Ok(self.0.sign(&msg)?)
+    }
+}
+
+/// A public key of any of the supported key types.
+#[derive(uniffi::Object)]
+pub struct FfiPublicKey(PublicKey);
+
+#[uniffi::export]
+impl FfiPublicKey {
+    /// Parse a public key from its b58 address.
+    #[uniffi::constructor]
+    pub fn from_b58(address: String) -> Result<Arc<Self>, FfiError> {
+        Ok(Arc::new(Self(address.parse()?)))
+    }
+
+    /// Parse a public key from its binary form.
+    #[uniffi::constructor]
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Arc<Self>, FfiError> {
+        Ok(Arc::new(Self(PublicKey::try_from(&bytes[..])?)))
+    }
+
+    /// The b58 address of the public key.
+    pub fn to_b58(&self) -> String {
+        self.0.to_string()
+    }
+
+    /// The binary form of the public key.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    /// The key tag of the public key, like `mainnet/ed25519`.
+    pub fn key_tag(&self) -> String {
+        self.0.tag().to_string()
+    }
+
+    /// Verify a signature over the given message.
+    pub fn verify(&self, msg: Vec<u8>, signature: Vec<u8>) -> Result<(), FfiError> {
+        Ok(self.0.verify(&msg, &signature)?)
+    }
+}
@@ -0,0 +1,293 @@
+//! Detached signing of firmware manifests with maker keys.
+//!
+//! A manifest lists the files of a firmware release with their sizes and
+//! SHA-256 digests, together with the release version, the target hardware
+//! and a rollback counter. The maker signs the binary form of the manifest and
+//! ships the signature next to it. A device verifies the signature against
+//! the maker keys it trusts, checks that the manifest is for its hardware and
+//! not older than its current rollback counter, and then checks each file
+//! while streaming it in with a [`FileVerifier`].
+//!
+//! The binary form starts with the four byte magic `HFWM` and a format
+//! version byte of 1, followed by:
+//!
+//! * the version and the target hardware, each as a big endian `u16` length
+//!   and UTF-8 bytes,
+//! * the rollback counter as a big endian `u64`,
+//! * the number of files as a big endian `u16`, and for each file its path
+//!   as a big endian `u16` length and UTF-8 bytes, its size as a big endian
+//!   `u64` and its 32 byte SHA-256 digest.
+use crate::{error, PublicKey, Sign, Verify};
+use sha2::{Digest, Sha256};
+use std::convert::{TryFrom, TryInto};
+
+const MAGIC: &[u8; 4] = b"HFWM";
+const FORMAT_VERSION: u8 = 1;
+
+/// A file in a firmware release.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FileEntry {
+    /// The path of the file within the release
+    pub path: String,
+    /// The size of the file in bytes
+    pub size: u64,
+    /// The SHA-256 digest of the file contents
+    pub digest: [u8; 32],
+}
+
+impl FileEntry {
+    /// Describe the given file contents.
+    pub fn new(path: &str, contents: &[u8]) -> Self {
+        Self {
+            path: path.to_string(),
+            size: contents.len() as u64,
+            digest: Sha256::digest(contents)[..]
+                .try_into()
+                .expect("digest length"),
+        }
+    }
+
+    /// Start checking file contents that are fed in incrementally against this
+    /// entry.
+    pub fn verifier(&self) -> FileVerifier<'_> {
+        FileVerifier {
+            entry: self,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+}
+
+/// A firmware release manifest.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Manifest {
+    /// The release version
+    pub version: String,
+    /// The hardware the release is built for
+    pub hardware: String,
+    /// Devices refuse releases with a counter below the one they last
+    /// installed
+    pub rollback: u64,
+    /// The files in the release
+    pub files: Vec<FileEntry>,
+}
+
+impl Manifest {
+    /// Look up a file by path.
+    pub fn file(&self, path: &str) -> Option<&FileEntry> {
+        self.files.iter().find(|entry| entry.path == path)
+    }
+
+    /// Check that this manifest is for the given hardware and that its
+    /// rollback counter is at least the given minimum.
+    pub fn check(&self, hardware: &str, min_rollback: u64) -> error::Result {
+        if self.hardware != hardware {
+            return Err(error::FirmwareError::Hardware {
+                expected: hardware.to_string(),
+                found: self.hardware.clone(),
+            }
+            .into());
+        }
+        if self.rollback < min_rollback {
+            return Err(error::FirmwareError::Rollback {
+                minimum: min_rollback,
+                found: self.rollback,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Convert the manifest to its binary form.
+    pub fn to_vec(&self) -> error::Result<Vec<u8>> {
+        let mut result = MAGIC.to_vec();
+        result.push(FORMAT_VERSION);
+        write_str(&mut result, &self.version)?;
+        write_str(&mut result, &self.hardware)?;
+        result.extend_from_slice(&self.rollback.to_be_bytes());
+        let count = u16::try_from(self.files.len()).map_err(|_| error::invalid_manifest())?;
+        result.extend_from_slice(&count.to_be_bytes());
+        for entry in &self.files {
+            write_str(&mut result, &entry.path)?;
+            result.extend_from_slice(&entry.size.to_be_bytes());
+            result.extend_from_slice(&entry.digest);
+        }
+        Ok(result)
+    }
+
+    /// Sign the binary form of the manifest, returning it together with the
+    /// detached signature.
+    pub fn sign<S: Sign + ?Sized>(&self, maker: &S) -> error::Result<(Vec<u8>, Vec<u8>)> {
+        let bytes = self.to_vec()?;
+        let signature = maker.sign(&bytes)?;
+        Ok((bytes, signature))
+    }
+}
+
+impl TryFrom<&[u8]> for Manifest {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        let mut input = match input.strip_prefix(&MAGIC[..]) {
+            Some([FORMAT_VERSION, rest @ ..]) => rest,
+            _ => return Err(error::invalid_manifest()),
+        };
+        let version = read_str(&mut input)?;
+        let hardware = read_str(&mut input)?;
+        let rollback = u64::from_be_bytes(read_array(&mut input)?);
+        let count = u16::from_be_bytes(read_array(&mut input)?);
+        let files = (0..count)
+            .map(|_| {
+                Ok(FileEntry {
+                    path: read_str(&mut input)?,
+                    size: u64::from_be_bytes(read_array(&mut input)?),
+                    digest: read_array(&mut input)?,
+                })
+            })
+            .collect::<error::Result<Vec<FileEntry>>>()?;
+        if !input.is_empty() {
+            return Err(error::invalid_manifest());
+        }
+        Ok(Self {
+            version,
+            hardware,
+            rollback,
+            files,
+        })
+    }
+}
+
+/// Verify the detached signature over the binary form of a manifest against
+/// the given maker keys, and parse the manifest if any of them signed it. The
+/// signature is checked before the manifest is parsed.
+pub fn verify(makers: &[PublicKey], manifest: &[u8], signature: &[u8]) -> error::Result<Manifest> {
+    if !makers
+        .iter()
+        .any(|maker| maker.verify(manifest, signature).is_ok())
+    {
+        return Err(signature::Error::new().into());
+    }
+    Manifest::try_from(manifest)
+}
+
+/// Checks file contents that are fed in incrementally through `update`
+/// against a manifest entry.
+pub struct FileVerifier<'a> {
+    entry: &'a FileEntry,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl FileVerifier<'_> {
+    /// Add the next part of the file. Fails as soon as more data is added
+    /// than the manifest lists for the file.
+    pub fn update(&mut self, data: &[u8]) -> error::Result {
+        self.size += data.len() as u64;
+        if self.size > self.entry.size {
+            return Err(error::FirmwareError::File(self.entry.path.clone()).into());
+        }
+        self.hasher.update(data);
+        Ok(())
+    }
+
+    /// Check the size and digest of all file data added so far.
+    pub fn finalize(self) -> error::Result {
+        if self.size != self.entry.size || self.hasher.finalize()[..] != self.entry.digest {
+            return Err(error::FirmwareError::File(self.entry.path.clone()).into());
+        }
+        Ok(())
+    }
+}
+
+fn write_str(output: &mut Vec<u8>, value: &str) -> error::Result {
+    let len = u16::try_from(value.len()).map_err(|_| error::invalid_manifest())?;
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> error::Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(error::invalid_manifest());
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn read_array<const N: usize>(input: &mut &[u8]) -> error::Result<[u8; N]> {
+    Ok(read_bytes(input, N)?.try_into().expect("array length"))
+}
+
+fn read_str(input: &mut &[u8]) -> error::Result<String> {
+    let len = u16::from_be_bytes(read_array(input)?) as usize;
+    let bytes = read_bytes(input, len)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| error::invalid_manifest())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify, FileEntry, Manifest};
+    use crate::{ed25519, Network};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    const IMAGE: &[u8] = b"firmware image contents";
+
+    fn manifest() -> Manifest {
+        Manifest {
+            version: "1.2.3".to_string(),
+            hardware: "hotspot-v2".to_string(),
+            rollback: 7,
+            files: vec![
+                FileEntry::new("image.bin", IMAGE),
+                FileEntry::new("config.json", b"{}"),
+            ],
+        }
+    }
+
+    #[test]
+    fn sign_verify() {
+        let maker = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let other = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let (bytes, signature) = manifest().sign(&maker).expect("signed manifest");
+        assert_eq!(
+            manifest(),
+            Manifest::try_from(&bytes[..]).expect("manifest")
+        );
+        let makers = [other.public_key.clone(), maker.public_key.clone()];
+        let verified = verify(&makers, &bytes, &signature).expect("verified manifest");
+        assert_eq!(manifest(), verified);
+        assert!(verify(&makers[..1], &bytes, &signature).is_err());
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().expect("byte") ^= 1;
+        assert!(verify(&makers, &tampered, &signature).is_err());
+        assert!(Manifest::try_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn check() {
+        let manifest = manifest();
+        assert!(manifest.check("hotspot-v2", 7).is_ok());
+        assert!(manifest.check("hotspot-v2", 8).is_err());
+        assert!(manifest.check("hotspot-v1", 0).is_err());
+    }
+
+    #[test]
+    fn file_verifier() {
+        let manifest = manifest();
+        let entry = manifest.file("image.bin").expect("entry");
+        let mut verifier = entry.verifier();
+        for chunk in IMAGE.chunks(5) {
+            verifier.update(chunk).expect("update");
+        }
+        assert!(verifier.finalize().is_ok());
+
+        let mut verifier = entry.verifier();
+        verifier.update(&IMAGE[1..]).expect("update");
+        assert!(verifier.finalize().is_err());
+
+        let mut verifier = entry.verifier();
+        verifier.update(IMAGE).expect("update");
+        assert!(verifier.update(b"x").is_err());
+    }
+}
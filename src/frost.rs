@@ -0,0 +1,939 @@
+//! FROST threshold signatures over ed25519, so that any `threshold` of a group
+//! of signers can jointly control a single ordinary ed25519 key.
+//!
+//! Signing follows RFC 9591 with the FROST(Ed25519, SHA-512) ciphersuite. An
+//! aggregated signature is a plain ed25519 signature that verifies against
+//! the group [`PublicKey`] like any other, so nothing on chain shows that the
+//! key is shared, unlike a [`multisig`](crate::multisig) key.
+//!
+//! Keys are generated without a trusted dealer, with the distributed key
+//! generation from the FROST paper. Participants are numbered from 1 to the
+//! number of signers:
+//!
+//! 1. Every participant calls [`KeyGen::new`] and broadcasts the returned
+//!    [`Commitment`] to all other participants.
+//! 2. With the commitments of all participants, every participant calls
+//!    [`KeyGen::shares`] and sends each returned [`SecretShare`] to the
+//!    participant it is for, over an encrypted and authenticated channel.
+//! 3. With the shares sent to it, every participant calls [`KeyGen::finish`]
+//!    to get its [`KeyPackage`].
+//!
+//! Signing takes two rounds between a coordinator and at least `threshold`
+//! signers:
+//!
+//! 1. Every signer calls [`KeyPackage::commit`], keeps the [`SigningNonces`]
+//!    and sends the [`SigningCommitments`] to the coordinator.
+//! 2. The coordinator sends the message and the commitments of all signers to
+//!    every signer, who replies with the [`SignatureShare`] from
+//!    [`KeyPackage::sign`].
+//! 3. The coordinator combines the shares with [`GroupKey::aggregate`].
+//!
+//! Signing consumes the nonces, which must never be used for a second
+//! signature. Invalid proofs and shares are reported with the participant
+//! they came from as [`Error::FrostParticipant`](crate::Error::FrostParticipant).
+use crate::{ed25519, entropy::EntropySource, error, Network, PublicKey};
+use curve25519_dalek::{
+    constants::ED25519_BASEPOINT_TABLE,
+    edwards::{CompressedEdwardsY, EdwardsPoint},
+    scalar::Scalar,
+    traits::{Identity, IsIdentity},
+};
+use sha2::{Digest, Sha512};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::{TryFrom, TryInto},
+};
+use zeroize::Zeroize;
+
+/// The RFC 9591 context string of the ciphersuite.
+const CONTEXT: &[u8] = b"FROST-ED25519-SHA512-v1";
+const POINT_LENGTH: usize = 32;
+const SCALAR_LENGTH: usize = 32;
+
+/// The length of an aggregated signature.
+pub const SIGNATURE_LENGTH: usize = 64;
+/// The maximum number of participants.
+pub const MAX_SIGNERS: u8 = 255;
+
+fn hasher(parts: &[&[u8]]) -> Sha512 {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher
+}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    Scalar::from_hash(hasher(parts))
+}
+
+fn identifier_scalar(identifier: u8) -> Scalar {
+    Scalar::from(u64::from(identifier))
+}
+
+fn random_scalar<E>(entropy: &mut E) -> error::Result<Scalar>
+where
+    E: EntropySource + ?Sized,
+{
+    let mut bytes = [0u8; 64];
+    entropy.fill_entropy(&mut bytes)?;
+    let scalar = Scalar::from_bytes_mod_order_wide(&bytes);
+    bytes.zeroize();
+    Ok(scalar)
+}
+
+/// Decode a canonically encoded point in the prime order subgroup, other
+/// than the identity.
+fn decode_point(input: &[u8]) -> error::Result<EdwardsPoint> {
+    let point = CompressedEdwardsY::from_slice(input)
+        .decompress()
+        .ok_or_else(error::invalid_frost)?;
+    if point.compress().as_bytes()[..] != *input || point.is_identity() || !point.is_torsion_free()
+    {
+        return Err(error::invalid_frost());
+    }
+    Ok(point)
+}
+
+fn decode_scalar(input: &[u8]) -> error::Result<Scalar> {
+    Scalar::from_canonical_bytes(input.try_into().expect("scalar")).ok_or_else(error::invalid_frost)
+}
+
+/// Evaluate the polynomial with the given coefficients, lowest degree first.
+fn evaluate(coefficients: &[Scalar], x: u8) -> Scalar {
+    let x = identifier_scalar(x);
+    coefficients
+        .iter()
+        .rev()
+        .fold(Scalar::zero(), |acc, coefficient| acc * x + coefficient)
+}
+
+fn dkg_challenge(identifier: u8, secret_commitment: &EdwardsPoint, r: &EdwardsPoint) -> Scalar {
+    hash_to_scalar(&[
+        CONTEXT,
+        b"dkg",
+        identifier_scalar(identifier).as_bytes(),
+        secret_commitment.compress().as_bytes(),
+        r.compress().as_bytes(),
+    ])
+}
+
+/// The key generation message every participant broadcasts: commitments to
+/// the coefficients of its secret polynomial and a proof of knowledge of its
+/// secret.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commitment {
+    /// The participant the commitment is from
+    pub identifier: u8,
+    coefficients: Vec<EdwardsPoint>,
+    proof: (EdwardsPoint, Scalar),
+}
+
+impl Commitment {
+    fn verify_proof(&self) -> bool {
+        let (r, mu) = &self.proof;
+        let c = dkg_challenge(self.identifier, &self.coefficients[0], r);
+        mu * &ED25519_BASEPOINT_TABLE == r + self.coefficients[0] * c
+    }
+
+    /// The commitment to the secret share of the given participant.
+    fn evaluate(&self, x: u8) -> EdwardsPoint {
+        let x = identifier_scalar(x);
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(EdwardsPoint::identity(), |acc, coefficient| {
+                acc * x + coefficient
+            })
+    }
+
+    /// Encode the commitment in its binary form, the identifier and
+    /// threshold followed by the coefficient commitments and the proof.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result =
+            Vec::with_capacity(2 + POINT_LENGTH * (self.coefficients.len() + 1) + SCALAR_LENGTH);
+        result.extend_from_slice(&[self.identifier, self.coefficients.len() as u8]);
+        for coefficient in &self.coefficients {
+            result.extend_from_slice(coefficient.compress().as_bytes());
+        }
+        result.extend_from_slice(self.proof.0.compress().as_bytes());
+        result.extend_from_slice(self.proof.1.as_bytes());
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for Commitment {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() < 2 || input[0] == 0 || input[1] < 2 {
+            return Err(error::invalid_frost());
+        }
+        let threshold = usize::from(input[1]);
+        if input.len() != 2 + POINT_LENGTH * (threshold + 1) + SCALAR_LENGTH {
+            return Err(error::invalid_frost());
+        }
+        let mut chunks = input[2..].chunks(POINT_LENGTH);
+        let coefficients = chunks
+            .by_ref()
+            .take(threshold)
+            .map(decode_point)
+            .collect::<error::Result<Vec<_>>>()?;
+        let r = decode_point(chunks.next().expect("proof commitment"))?;
+        let mu = decode_scalar(chunks.next().expect("proof response"))?;
+        Ok(Commitment {
+            identifier: input[0],
+            coefficients,
+            proof: (r, mu),
+        })
+    }
+}
+
+/// A share of the secret of one participant for another, sent privately
+/// during key generation.
+#[derive(Clone)]
+pub struct SecretShare {
+    /// The participant the share is from
+    pub sender: u8,
+    /// The participant the share is for
+    pub receiver: u8,
+    value: Scalar,
+}
+
+impl SecretShare {
+    /// Encode the share in its binary form, the sender and receiver followed
+    /// by the share.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(2 + SCALAR_LENGTH);
+        result.extend_from_slice(&[self.sender, self.receiver]);
+        result.extend_from_slice(self.value.as_bytes());
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for SecretShare {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != 2 + SCALAR_LENGTH || input[0] == 0 || input[1] == 0 {
+            return Err(error::invalid_frost());
+        }
+        Ok(SecretShare {
+            sender: input[0],
+            receiver: input[1],
+            value: decode_scalar(&input[2..])?,
+        })
+    }
+}
+
+/// Secret shares never include their value in `Debug` output.
+impl std::fmt::Debug for SecretShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretShare")
+            .field("sender", &self.sender)
+            .field("receiver", &self.receiver)
+            .field("value", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl Drop for SecretShare {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+/// The state of one participant during key generation.
+pub struct KeyGen {
+    identifier: u8,
+    max_signers: u8,
+    coefficients: Vec<Scalar>,
+    commitment: Commitment,
+}
+
+impl KeyGen {
+    /// Start key generation for the given participant of a group of
+    /// `max_signers` with the given signing threshold, which has to be at
+    /// least 2. Returns the state to keep and the commitment to broadcast.
+    pub fn new<E>(
+        identifier: u8,
+        threshold: u8,
+        max_signers: u8,
+        entropy: &mut E,
+    ) -> error::Result<(KeyGen, Commitment)>
+    where
+        E: EntropySource + ?Sized,
+    {
+        if threshold < 2 || threshold > max_signers {
+            return Err(error::share_threshold(threshold, max_signers));
+        }
+        if identifier == 0 || identifier > max_signers {
+            return Err(error::invalid_frost());
+        }
+        let mut coefficients = Vec::with_capacity(usize::from(threshold));
+        for _ in 0..threshold {
+            coefficients.push(random_scalar(entropy)?);
+        }
+        let mut k = random_scalar(entropy)?;
+        let r = &k * &ED25519_BASEPOINT_TABLE;
+        let points: Vec<EdwardsPoint> = coefficients
+            .iter()
+            .map(|coefficient| coefficient * &ED25519_BASEPOINT_TABLE)
+            .collect();
+        let mu = k + coefficients[0] * dkg_challenge(identifier, &points[0], &r);
+        k.zeroize();
+        let commitment = Commitment {
+            identifier,
+            coefficients: points,
+            proof: (r, mu),
+        };
+        Ok((
+            KeyGen {
+                identifier,
+                max_signers,
+                coefficients,
+                commitment: commitment.clone(),
+            },
+            commitment,
+        ))
+    }
+
+    /// Checks that there is one commitment from every participant, including
+    /// this one, and that every proof of knowledge verifies.
+    fn check_commitments<'a>(
+        &self,
+        commitments: &'a [Commitment],
+    ) -> error::Result<BTreeMap<u8, &'a Commitment>> {
+        let mut result = BTreeMap::new();
+        for commitment in commitments {
+            let identifier = commitment.identifier;
+            if identifier == 0
+                || identifier > self.max_signers
+                || result.insert(identifier, commitment).is_some()
+            {
+                return Err(error::invalid_frost());
+            }
+            if identifier == self.identifier {
+                if *commitment != self.commitment {
+                    return Err(error::invalid_frost());
+                }
+            } else if commitment.coefficients.len() != self.coefficients.len()
+                || !commitment.verify_proof()
+            {
+                return Err(error::frost_participant(identifier));
+            }
+        }
+        if result.len() != usize::from(self.max_signers) {
+            return Err(error::invalid_frost());
+        }
+        Ok(result)
+    }
+
+    /// Returns the shares to send to every other participant, after checking
+    /// the commitments of all participants.
+    pub fn shares(&self, commitments: &[Commitment]) -> error::Result<Vec<SecretShare>> {
+        self.check_commitments(commitments)?;
+        Ok((1..=self.max_signers)
+            .filter(|receiver| *receiver != self.identifier)
+            .map(|receiver| SecretShare {
+                sender: self.identifier,
+                receiver,
+                value: evaluate(&self.coefficients, receiver),
+            })
+            .collect())
+    }
+
+    /// Finish key generation with the commitments of all participants and
+    /// the shares every other participant sent to this one.
+    pub fn finish(
+        self,
+        commitments: &[Commitment],
+        shares: &[SecretShare],
+    ) -> error::Result<KeyPackage> {
+        let commitments = self.check_commitments(commitments)?;
+        let mut senders = BTreeSet::new();
+        for share in shares {
+            if share.receiver != self.identifier
+                || share.sender == self.identifier
+                || !senders.insert(share.sender)
+            {
+                return Err(error::invalid_frost());
+            }
+            let commitment = commitments
+                .get(&share.sender)
+                .ok_or_else(error::invalid_frost)?;
+            if &share.value * &ED25519_BASEPOINT_TABLE != commitment.evaluate(self.identifier) {
+                return Err(error::frost_participant(share.sender));
+            }
+        }
+        if senders.len() + 1 != usize::from(self.max_signers) {
+            return Err(error::invalid_frost());
+        }
+        let secret = shares.iter().fold(
+            evaluate(&self.coefficients, self.identifier),
+            |acc, share| acc + share.value,
+        );
+        let public_key = commitments
+            .values()
+            .fold(EdwardsPoint::identity(), |acc, commitment| {
+                acc + commitment.coefficients[0]
+            });
+        let verifying_shares = (1..=self.max_signers)
+            .map(|x| {
+                let share = commitments
+                    .values()
+                    .fold(EdwardsPoint::identity(), |acc, commitment| {
+                        acc + commitment.evaluate(x)
+                    });
+                (x, share)
+            })
+            .collect();
+        Ok(KeyPackage {
+            identifier: self.identifier,
+            secret,
+            group: GroupKey {
+                threshold: self.coefficients.len() as u8,
+                public_key,
+                verifying_shares,
+            },
+        })
+    }
+}
+
+impl Drop for KeyGen {
+    fn drop(&mut self) {
+        for coefficient in self.coefficients.iter_mut() {
+            coefficient.zeroize();
+        }
+    }
+}
+
+/// The public side of a group: the group key and the key of every
+/// participant's secret share. Coordinators only need this to aggregate
+/// signatures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupKey {
+    /// The number of signers needed for a signature
+    pub threshold: u8,
+    public_key: EdwardsPoint,
+    verifying_shares: BTreeMap<u8, EdwardsPoint>,
+}
+
+impl GroupKey {
+    /// The ed25519 public key of the group for the given network.
+    pub fn public_key(&self, network: Network) -> PublicKey {
+        let key = ed25519_dalek::PublicKey::from_bytes(self.public_key.compress().as_bytes())
+            .expect("group key");
+        PublicKey::from_ed25519(
+            network,
+            ed25519::PublicKey::try_from(key).expect("prime order group key"),
+        )
+    }
+
+    /// Aggregate the signature shares of the signers who sent the given
+    /// commitments into an ed25519 signature over the message. Every share is
+    /// checked, and an invalid one fails with the participant that sent it.
+    pub fn aggregate(
+        &self,
+        msg: &[u8],
+        commitments: &[SigningCommitments],
+        shares: &[SignatureShare],
+    ) -> error::Result<Vec<u8>> {
+        let context = SigningContext::new(self, msg, commitments)?;
+        if shares.len() != context.commitments.len() {
+            return Err(error::invalid_frost());
+        }
+        let mut signers = BTreeSet::new();
+        let mut z = Scalar::zero();
+        for share in shares {
+            let identifier = share.identifier;
+            let commitment = context
+                .commitments
+                .get(&identifier)
+                .ok_or_else(error::invalid_frost)?;
+            if !signers.insert(identifier) {
+                return Err(error::invalid_frost());
+            }
+            let expected = commitment.hiding
+                + commitment.binding * context.binding_factors[&identifier]
+                + self.verifying_shares[&identifier]
+                    * (context.challenge * context.lagrange(identifier));
+            if &share.share * &ED25519_BASEPOINT_TABLE != expected {
+                return Err(error::frost_participant(identifier));
+            }
+            z += share.share;
+        }
+        let mut result = Vec::with_capacity(SIGNATURE_LENGTH);
+        result.extend_from_slice(context.group_commitment.compress().as_bytes());
+        result.extend_from_slice(z.as_bytes());
+        Ok(result)
+    }
+
+    /// Encode the group key in its binary form, the threshold and number of
+    /// participants followed by the group key and the identifier and key of
+    /// every participant's share.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result =
+            Vec::with_capacity(2 + POINT_LENGTH + (1 + POINT_LENGTH) * self.verifying_shares.len());
+        result.extend_from_slice(&[self.threshold, self.verifying_shares.len() as u8]);
+        result.extend_from_slice(self.public_key.compress().as_bytes());
+        for (identifier, share) in &self.verifying_shares {
+            result.push(*identifier);
+            result.extend_from_slice(share.compress().as_bytes());
+        }
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for GroupKey {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() < 2 || input[0] < 2 || input[0] > input[1] {
+            return Err(error::invalid_frost());
+        }
+        let count = usize::from(input[1]);
+        if input.len() != 2 + POINT_LENGTH + (1 + POINT_LENGTH) * count {
+            return Err(error::invalid_frost());
+        }
+        let public_key = decode_point(&input[2..2 + POINT_LENGTH])?;
+        let mut verifying_shares = BTreeMap::new();
+        for entry in input[2 + POINT_LENGTH..].chunks(1 + POINT_LENGTH) {
+            if entry[0] == 0
+                || verifying_shares
+                    .insert(entry[0], decode_point(&entry[1..])?)
+                    .is_some()
+            {
+                return Err(error::invalid_frost());
+            }
+        }
+        Ok(GroupKey {
+            threshold: input[0],
+            public_key,
+            verifying_shares,
+        })
+    }
+}
+
+/// The key material of one participant: its secret share and the group key.
+#[derive(Clone)]
+pub struct KeyPackage {
+    /// The identifier of the participant
+    pub identifier: u8,
+    secret: Scalar,
+    pub group: GroupKey,
+}
+
+impl KeyPackage {
+    /// Generate the nonces for one signature. The nonces are kept by the
+    /// signer for [`KeyPackage::sign`] and the commitments sent to the
+    /// coordinator.
+    pub fn commit<E>(&self, entropy: &mut E) -> error::Result<(SigningNonces, SigningCommitments)>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let hiding = self.nonce(entropy)?;
+        let binding = self.nonce(entropy)?;
+        let commitments = SigningCommitments {
+            identifier: self.identifier,
+            hiding: &hiding * &ED25519_BASEPOINT_TABLE,
+            binding: &binding * &ED25519_BASEPOINT_TABLE,
+        };
+        Ok((
+            SigningNonces {
+                hiding,
+                binding,
+                commitments: commitments.clone(),
+            },
+            commitments,
+        ))
+    }
+
+    /// A nonce derived from fresh randomness and the secret share, so a weak
+    /// random number generator alone does not leak the secret.
+    fn nonce<E>(&self, entropy: &mut E) -> error::Result<Scalar>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let mut random = [0u8; 32];
+        entropy.fill_entropy(&mut random)?;
+        let nonce = hash_to_scalar(&[CONTEXT, b"nonce", &random, self.secret.as_bytes()]);
+        random.zeroize();
+        Ok(nonce)
+    }
+
+    /// Sign the message with the nonces this signer committed to, given the
+    /// commitments of all signers, which have to include its own.
+    pub fn sign(
+        &self,
+        nonces: SigningNonces,
+        msg: &[u8],
+        commitments: &[SigningCommitments],
+    ) -> error::Result<SignatureShare> {
+        let context = SigningContext::new(&self.group, msg, commitments)?;
+        let binding_factor = match context.commitments.get(&self.identifier) {
+            Some(commitment) if **commitment == nonces.commitments => {
+                context.binding_factors[&self.identifier]
+            }
+            _ => return Err(error::invalid_frost()),
+        };
+        let share = nonces.hiding
+            + nonces.binding * binding_factor
+            + context.lagrange(self.identifier) * self.secret * context.challenge;
+        Ok(SignatureShare {
+            identifier: self.identifier,
+            share,
+        })
+    }
+
+    export_secret! {
+        /// Encode the key package in its binary form, the identifier and
+        /// secret share followed by the binary form of the group key.
+        fn to_vec(&self) -> Vec<u8> {
+            let mut result = vec![self.identifier];
+            result.extend_from_slice(self.secret.as_bytes());
+            result.extend_from_slice(&self.group.to_vec());
+            result
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for KeyPackage {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() < 1 + SCALAR_LENGTH || input[0] == 0 {
+            return Err(error::invalid_frost());
+        }
+        let package = KeyPackage {
+            identifier: input[0],
+            secret: decode_scalar(&input[1..1 + SCALAR_LENGTH])?,
+            group: GroupKey::try_from(&input[1 + SCALAR_LENGTH..])?,
+        };
+        let verifying_share = package.group.verifying_shares.get(&package.identifier);
+        if verifying_share != Some(&(&package.secret * &ED25519_BASEPOINT_TABLE)) {
+            return Err(error::invalid_frost());
+        }
+        Ok(package)
+    }
+}
+
+/// Key packages never include their secret share in `Debug` output.
+impl std::fmt::Debug for KeyPackage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyPackage")
+            .field("identifier", &self.identifier)
+            .field("secret", &format_args!("<redacted>"))
+            .field("group", &self.group)
+            .finish()
+    }
+}
+
+impl Drop for KeyPackage {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+/// The secret nonces of a signer for one signature. They are consumed by
+/// signing and can not be copied, so they are used at most once.
+pub struct SigningNonces {
+    hiding: Scalar,
+    binding: Scalar,
+    commitments: SigningCommitments,
+}
+
+impl Drop for SigningNonces {
+    fn drop(&mut self) {
+        self.hiding.zeroize();
+        self.binding.zeroize();
+    }
+}
+
+/// The public commitments to the nonces of a signer for one signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SigningCommitments {
+    /// The participant the commitments are from
+    pub identifier: u8,
+    hiding: EdwardsPoint,
+    binding: EdwardsPoint,
+}
+
+impl SigningCommitments {
+    /// Encode the commitments in their binary form, the identifier followed
+    /// by the hiding and binding nonce commitments.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(1 + 2 * POINT_LENGTH);
+        result.push(self.identifier);
+        result.extend_from_slice(self.hiding.compress().as_bytes());
+        result.extend_from_slice(self.binding.compress().as_bytes());
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for SigningCommitments {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != 1 + 2 * POINT_LENGTH || input[0] == 0 {
+            return Err(error::invalid_frost());
+        }
+        Ok(SigningCommitments {
+            identifier: input[0],
+            hiding: decode_point(&input[1..1 + POINT_LENGTH])?,
+            binding: decode_point(&input[1 + POINT_LENGTH..])?,
+        })
+    }
+}
+
+/// The share of a signature from one signer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureShare {
+    /// The participant the share is from
+    pub identifier: u8,
+    share: Scalar,
+}
+
+impl SignatureShare {
+    /// Encode the share in its binary form, the identifier followed by the
+    /// share.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(1 + SCALAR_LENGTH);
+        result.push(self.identifier);
+        result.extend_from_slice(self.share.as_bytes());
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for SignatureShare {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != 1 + SCALAR_LENGTH || input[0] == 0 {
+            return Err(error::invalid_frost());
+        }
+        Ok(SignatureShare {
+            identifier: input[0],
+            share: decode_scalar(&input[1..])?,
+        })
+    }
+}
+
+/// The values signers and the coordinator derive from the message and the
+/// commitments of all signers.
+struct SigningContext<'a> {
+    commitments: BTreeMap<u8, &'a SigningCommitments>,
+    binding_factors: BTreeMap<u8, Scalar>,
+    group_commitment: EdwardsPoint,
+    challenge: Scalar,
+}
+
+impl<'a> SigningContext<'a> {
+    fn new(
+        group: &GroupKey,
+        msg: &[u8],
+        commitments: &'a [SigningCommitments],
+    ) -> error::Result<Self> {
+        if commitments.len() < usize::from(group.threshold) {
+            return Err(error::invalid_frost());
+        }
+        let mut sorted = BTreeMap::new();
+        for commitment in commitments {
+            if !group.verifying_shares.contains_key(&commitment.identifier)
+                || sorted.insert(commitment.identifier, commitment).is_some()
+            {
+                return Err(error::invalid_frost());
+            }
+        }
+        let mut encoded = Vec::with_capacity(sorted.len() * (SCALAR_LENGTH + 2 * POINT_LENGTH));
+        for (identifier, commitment) in &sorted {
+            encoded.extend_from_slice(identifier_scalar(*identifier).as_bytes());
+            encoded.extend_from_slice(commitment.hiding.compress().as_bytes());
+            encoded.extend_from_slice(commitment.binding.compress().as_bytes());
+        }
+        let public_key = group.public_key.compress();
+        let mut prefix = public_key.as_bytes().to_vec();
+        prefix.extend_from_slice(&hasher(&[CONTEXT, b"msg", msg]).finalize());
+        prefix.extend_from_slice(&hasher(&[CONTEXT, b"com", &encoded]).finalize());
+        let binding_factors: BTreeMap<u8, Scalar> = sorted
+            .keys()
+            .map(|identifier| {
+                let binding_factor = hash_to_scalar(&[
+                    CONTEXT,
+                    b"rho",
+                    &prefix,
+                    identifier_scalar(*identifier).as_bytes(),
+                ]);
+                (*identifier, binding_factor)
+            })
+            .collect();
+        let mut group_commitment = EdwardsPoint::identity();
+        for (identifier, commitment) in &sorted {
+            group_commitment +=
+                commitment.hiding + commitment.binding * binding_factors[identifier];
+        }
+        let challenge = hash_to_scalar(&[
+            group_commitment.compress().as_bytes(),
+            public_key.as_bytes(),
+            msg,
+        ]);
+        Ok(SigningContext {
+            commitments: sorted,
+            binding_factors,
+            group_commitment,
+            challenge,
+        })
+    }
+
+    /// The Lagrange coefficient of the given signer at zero over the set of
+    /// signers.
+    fn lagrange(&self, identifier: u8) -> Scalar {
+        let x = identifier_scalar(identifier);
+        let (numerator, denominator) = self
+            .commitments
+            .keys()
+            .filter(|other| **other != identifier)
+            .fold((Scalar::one(), Scalar::one()), |(num, den), other| {
+                let other = identifier_scalar(*other);
+                (num * other, den * (other - x))
+            });
+        numerator * denominator.invert()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Commitment, GroupKey, KeyGen, KeyPackage, SecretShare, SignatureShare, SigningCommitments,
+    };
+    use crate::{Error, Network, Verify};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    fn keygen(threshold: u8, max_signers: u8) -> Vec<KeyPackage> {
+        let (states, commitments): (Vec<KeyGen>, Vec<Commitment>) = (1..=max_signers)
+            .map(|identifier| {
+                KeyGen::new(identifier, threshold, max_signers, &mut OsRng).expect("keygen")
+            })
+            .unzip();
+        let shares: Vec<SecretShare> = states
+            .iter()
+            .flat_map(|state| state.shares(&commitments).expect("shares"))
+            .collect();
+        states
+            .into_iter()
+            .enumerate()
+            .map(|(index, state)| {
+                let received: Vec<SecretShare> = shares
+                    .iter()
+                    .filter(|share| usize::from(share.receiver) == index + 1)
+                    .cloned()
+                    .collect();
+                state.finish(&commitments, &received).expect("key package")
+            })
+            .collect()
+    }
+
+    fn sign(
+        packages: &[&KeyPackage],
+        msg: &[u8],
+    ) -> (Vec<SigningCommitments>, Vec<SignatureShare>) {
+        let (nonces, commitments): (Vec<_>, Vec<_>) = packages
+            .iter()
+            .map(|package| package.commit(&mut OsRng).expect("commit"))
+            .unzip();
+        let shares = packages
+            .iter()
+            .zip(nonces)
+            .map(|(package, nonces)| package.sign(nonces, msg, &commitments).expect("share"))
+            .collect();
+        (commitments, shares)
+    }
+
+    #[test]
+    fn sign_roundtrip() {
+        let packages = keygen(2, 3);
+        let group = packages[0].group.clone();
+        assert!(packages.iter().all(|package| package.group == group));
+        let public_key = group.public_key(Network::MainNet);
+
+        let (commitments, shares) = sign(&[&packages[0], &packages[2]], b"hello world");
+        let signature = group
+            .aggregate(b"hello world", &commitments, &shares)
+            .expect("signature");
+        assert!(public_key.verify(b"hello world", &signature).is_ok());
+        assert!(public_key.verify(b"hello world!", &signature).is_err());
+
+        // Every subset of threshold size can sign
+        let (commitments, shares) = sign(&[&packages[2], &packages[1]], b"hello world");
+        let signature = group
+            .aggregate(b"hello world", &commitments, &shares)
+            .expect("signature");
+        assert!(public_key.verify(b"hello world", &signature).is_ok());
+
+        // A share over another message is caught and attributed
+        let (commitments, mut shares) = sign(&[&packages[0], &packages[1]], b"hello world");
+        let (_, other) = sign(&[&packages[0], &packages[1]], b"hello world!");
+        shares[1] = other[1].clone();
+        assert!(matches!(
+            group.aggregate(b"hello world", &commitments, &shares),
+            Err(Error::FrostParticipant(2))
+        ));
+
+        // Too few signers
+        let (nonces, commitments) = packages[0].commit(&mut OsRng).expect("commit");
+        assert!(packages[0]
+            .sign(nonces, b"hello world", &[commitments])
+            .is_err());
+    }
+
+    #[test]
+    fn keygen_rejects_bad_shares() {
+        let (states, commitments): (Vec<KeyGen>, Vec<Commitment>) = (1..=3)
+            .map(|identifier| KeyGen::new(identifier, 2, 3, &mut OsRng).expect("keygen"))
+            .unzip();
+        let mut received: Vec<SecretShare> = states[1..]
+            .iter()
+            .flat_map(|state| state.shares(&commitments).expect("shares"))
+            .filter(|share| share.receiver == 1)
+            .collect();
+        received[1].value += curve25519_dalek::scalar::Scalar::one();
+        let mut states = states.into_iter();
+        let first = states.next().expect("state");
+        assert!(matches!(
+            first.finish(&commitments, &received),
+            Err(Error::FrostParticipant(3))
+        ));
+        let second = states.next().expect("state");
+        assert!(second.shares(&commitments[..2]).is_err());
+        assert!(KeyGen::new(1, 1, 3, &mut OsRng).is_err());
+        assert!(KeyGen::new(4, 2, 3, &mut OsRng).is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let packages = keygen(2, 3);
+        let package = &packages[1];
+        let decoded = KeyPackage::try_from(&package.to_vec()[..]).expect("key package");
+        assert_eq!(package.identifier, decoded.identifier);
+        assert_eq!(package.group, decoded.group);
+        assert_eq!(
+            package.group,
+            GroupKey::try_from(&package.group.to_vec()[..]).expect("group key")
+        );
+        let mut tampered = package.to_vec();
+        tampered[0] = 3;
+        assert!(KeyPackage::try_from(&tampered[..]).is_err());
+
+        let (commitments, shares) = sign(&[&packages[0], &packages[1]], b"hello world");
+        assert_eq!(
+            commitments[0],
+            SigningCommitments::try_from(&commitments[0].to_vec()[..]).expect("commitments")
+        );
+        assert_eq!(
+            shares[0],
+            SignatureShare::try_from(&shares[0].to_vec()[..]).expect("share")
+        );
+
+        let (_, commitment) = KeyGen::new(1, 2, 3, &mut OsRng).expect("keygen");
+        assert_eq!(
+            commitment,
+            Commitment::try_from(&commitment.to_vec()[..]).expect("commitment")
+        );
+        assert!(Commitment::try_from(&commitment.to_vec()[1..]).is_err());
+    }
+}
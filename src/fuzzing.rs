@@ -0,0 +1,119 @@
+//! `arbitrary` and `proptest` support for property testing code that handles
+//! Helium keys, like serialization and consensus logic.
+//!
+//! Generated keys and signatures are always valid: keypairs are generated from
+//! a seed taken from the fuzz input or the proptest runner, so a failing case
+//! reproduces and shrinks like any other input. Key tags cover every network
+//! and assigned key type, including multisig, while keypairs, public keys and
+//! signatures use the key types that can sign.
+//!
+//! ```ignore
+//! use helium_crypto::{fuzzing, Verify};
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     #[test]
+//!     fn signatures_verify((public_key, msg, signature) in fuzzing::signed_message()) {
+//!         prop_assert!(public_key.verify(&msg, &signature).is_ok());
+//!     }
+//! }
+//! ```
+use crate::{KeyTag, KeyType, Keypair, Network, PublicKey, Sign, SignatureBuf};
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+use rand_core::SeedableRng;
+
+const NETWORKS: [Network; 3] = [Network::MainNet, Network::TestNet, Network::DevNet];
+const SIGNING_KEY_TYPES: [KeyType; 4] = [
+    KeyType::Ed25519,
+    KeyType::EccCompact,
+    KeyType::Secp256k1,
+    KeyType::Schnorr,
+];
+
+fn keypair_from_seed(network: Network, key_type: KeyType, seed: [u8; 32]) -> Keypair {
+    let tag = KeyTag { network, key_type };
+    Keypair::generate(tag, &mut rand_chacha::ChaCha20Rng::from_seed(seed))
+        .expect("signing key type")
+}
+
+impl<'a> Arbitrary<'a> for Network {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.choose(&NETWORKS).copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for KeyTag {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&KeyTag::all().collect::<Vec<_>>())?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Keypair {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let network = Network::arbitrary(u)?;
+        let key_type = *u.choose(&SIGNING_KEY_TYPES)?;
+        Ok(keypair_from_seed(network, key_type, u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for PublicKey {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Keypair::arbitrary(u)?.public_key().clone())
+    }
+}
+
+/// A signature by an arbitrary keypair over an arbitrary message.
+impl<'a> Arbitrary<'a> for SignatureBuf {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let keypair = Keypair::arbitrary(u)?;
+        let msg = <&[u8]>::arbitrary(u)?;
+        Ok(keypair.sign_buf(msg).expect("signature"))
+    }
+}
+
+/// A strategy for key tags over every network and assigned key type.
+pub fn key_tag() -> impl Strategy<Value = KeyTag> {
+    prop::sample::select(KeyTag::all().collect::<Vec<_>>())
+}
+
+/// A strategy for keypairs of the key types that can sign.
+pub fn keypair() -> impl Strategy<Value = Keypair> {
+    (
+        prop::sample::select(NETWORKS.to_vec()),
+        prop::sample::select(SIGNING_KEY_TYPES.to_vec()),
+        any::<[u8; 32]>(),
+    )
+        .prop_map(|(network, key_type, seed)| keypair_from_seed(network, key_type, seed))
+}
+
+/// A strategy for public keys of the key types that can sign.
+pub fn public_key() -> impl Strategy<Value = PublicKey> {
+    keypair().prop_map(|keypair| keypair.public_key().clone())
+}
+
+/// A strategy for a public key, a message and a valid signature over it.
+pub fn signed_message() -> impl Strategy<Value = (PublicKey, Vec<u8>, SignatureBuf)> {
+    (keypair(), any::<Vec<u8>>()).prop_map(|(keypair, msg)| {
+        let signature = keypair.sign_buf(&msg).expect("signature");
+        (keypair.public_key().clone(), msg, signature)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::signed_message;
+    use crate::{PublicKey, Verify};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn signed_message_verifies((public_key, msg, signature) in signed_message()) {
+            prop_assert!(public_key.verify(&msg, &signature).is_ok());
+            prop_assert_eq!(
+                &public_key,
+                &public_key.to_string().parse::<PublicKey>().expect("public key")
+            );
+        }
+    }
+}
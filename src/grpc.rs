@@ -0,0 +1,201 @@
+//! Application layer authentication of gRPC calls with [tonic].
+//!
+//! A signed call carries the signer's public key, a deadline and a signature
+//! in its metadata. The signature covers the full method path, the deadline
+//! and a SHA-256 digest of the encoded request message, so it can not be
+//! replayed against another method, after the deadline or with a different
+//! payload.
+//!
+//! tonic interceptors only see call metadata, not the method or the message,
+//! so signing and full verification happen with [`sign_request`] and
+//! [`verify_request`] on the typed request. [`ServerInterceptor`] rejects
+//! calls from unknown keys or past their deadline before the message is
+//! decoded.
+//!
+//! [tonic]: https://docs.rs/tonic
+use crate::{Keypair, PublicKey, Sign, Verify};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonic::{metadata::MetadataValue, Request, Status};
+
+/// Metadata key for the base58 encoded signer public key.
+pub const KEY_METADATA: &str = "x-helium-key";
+/// Metadata key for the deadline in milliseconds since the unix epoch.
+pub const DEADLINE_METADATA: &str = "x-helium-deadline";
+/// Binary metadata key for the signature.
+pub const SIGNATURE_METADATA: &str = "x-helium-signature-bin";
+
+/// The bytes that are signed for a call: the method path, the deadline as a
+/// big endian u64 and the SHA-256 digest of the encoded message.
+fn signed_data(method: &str, deadline: u64, payload: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(method.len() + 1 + 8 + 32);
+    result.extend_from_slice(method.as_bytes());
+    result.push(0);
+    result.extend_from_slice(&deadline.to_be_bytes());
+    result.extend_from_slice(&Sha256::digest(payload));
+    result
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Sign the given request for the given full method path, like
+/// `/helium.router/route`, valid until the given deadline.
+pub fn sign_request<T: prost::Message>(
+    keypair: &Keypair,
+    method: &str,
+    deadline: SystemTime,
+    request: &mut Request<T>,
+) -> Result<(), Status> {
+    let deadline = unix_millis(deadline);
+    let data = signed_data(method, deadline, &request.get_ref().encode_to_vec());
+    let signature = keypair
+        .sign(&data)
+        .map_err(|_| Status::internal("signing failed"))?;
+    let metadata = request.metadata_mut();
+    metadata.insert(
+        KEY_METADATA,
+        keypair
+            .public_key()
+            .to_string()
+            .parse()
+            .map_err(|_| Status::internal("invalid key metadata"))?,
+    );
+    metadata.insert(
+        DEADLINE_METADATA,
+        deadline
+            .to_string()
+            .parse()
+            .map_err(|_| Status::internal("invalid deadline metadata"))?,
+    );
+    metadata.insert_bin(SIGNATURE_METADATA, MetadataValue::from_bytes(&signature));
+    Ok(())
+}
+
+/// Signer and deadline read from call metadata.
+fn call_key<T>(request: &Request<T>, now: SystemTime) -> Result<(PublicKey, u64), Status> {
+    let metadata = request.metadata();
+    let public_key: PublicKey = metadata
+        .get(KEY_METADATA)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| Status::unauthenticated("missing or invalid signer key"))?;
+    let deadline: u64 = metadata
+        .get(DEADLINE_METADATA)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| Status::unauthenticated("missing or invalid deadline"))?;
+    if deadline <= unix_millis(now) {
+        return Err(Status::unauthenticated("call deadline passed"));
+    }
+    Ok((public_key, deadline))
+}
+
+/// Verify the signature on the given request for the given full method path.
+/// `authorize` decides whether the signer key is allowed to call. Returns the
+/// signer key.
+pub fn verify_request<T, F>(
+    method: &str,
+    request: &Request<T>,
+    now: SystemTime,
+    authorize: F,
+) -> Result<PublicKey, Status>
+where
+    T: prost::Message,
+    F: FnOnce(&PublicKey) -> bool,
+{
+    let (public_key, deadline) = call_key(request, now)?;
+    if !authorize(&public_key) {
+        return Err(Status::permission_denied("signer not authorized"));
+    }
+    let signature = request
+        .metadata()
+        .get_bin(SIGNATURE_METADATA)
+        .and_then(|value| value.to_bytes().ok())
+        .ok_or_else(|| Status::unauthenticated("missing or invalid signature"))?;
+    let data = signed_data(method, deadline, &request.get_ref().encode_to_vec());
+    public_key
+        .verify(&data, &signature)
+        .map_err(|_| Status::unauthenticated("invalid signature"))?;
+    Ok(public_key)
+}
+
+/// Rejects calls from keys that are not authorized or whose deadline has
+/// passed, before their message is decoded. Signatures still need to be
+/// checked with [`verify_request`] in the handler.
+#[derive(Clone)]
+pub struct ServerInterceptor<F> {
+    authorize: F,
+    max_deadline: Duration,
+}
+
+impl<F> ServerInterceptor<F>
+where
+    F: Fn(&PublicKey) -> bool,
+{
+    /// Create an interceptor that authorizes signers with the given function
+    /// and rejects deadlines further out than `max_deadline`, limiting how
+    /// long a captured call can be replayed.
+    pub fn new(authorize: F, max_deadline: Duration) -> Self {
+        Self {
+            authorize,
+            max_deadline,
+        }
+    }
+}
+
+impl<F> tonic::service::Interceptor for ServerInterceptor<F>
+where
+    F: Fn(&PublicKey) -> bool,
+{
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let now = SystemTime::now();
+        let (public_key, deadline) = call_key(&request, now)?;
+        if deadline > unix_millis(now + self.max_deadline) {
+            return Err(Status::unauthenticated("call deadline too far out"));
+        }
+        if !(self.authorize)(&public_key) {
+            return Err(Status::permission_denied("signer not authorized"));
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign_request, verify_request, ServerInterceptor};
+    use crate::{ed25519, Keypair, Network};
+    use rand::rngs::OsRng;
+    use std::time::{Duration, SystemTime};
+    use tonic::{service::Interceptor, Request};
+
+    const METHOD: &str = "/helium.router/route";
+
+    #[test]
+    fn sign_verify() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let now = SystemTime::now();
+        let mut request = Request::new("hello world".to_string());
+        sign_request(
+            &keypair,
+            METHOD,
+            now + Duration::from_secs(10),
+            &mut request,
+        )
+        .expect("signed request");
+        let public_key = verify_request(METHOD, &request, now, |_| true).expect("verified");
+        assert_eq!(keypair.public_key(), &public_key);
+        assert!(verify_request("/helium.router/other", &request, now, |_| true).is_err());
+        assert!(verify_request(METHOD, &request, now, |_| false).is_err());
+        assert!(verify_request(METHOD, &request, now + Duration::from_secs(10), |_| true).is_err());
+
+        let mut interceptor = ServerInterceptor::new(|_: &_| true, Duration::from_secs(60));
+        let mut metadata_only = Request::new(());
+        *metadata_only.metadata_mut() = request.metadata().clone();
+        assert!(interceptor.call(metadata_only).is_ok());
+        assert!(interceptor.call(Request::new(())).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! Hierarchical deterministic derivation of ed25519 keys following
+//! [SLIP-0010][SLIP10].
+//!
+//! Edwards curve keys only support hardened derivation, so every index in a
+//! derivation path is hardened. Helium accounts use the BIP44 style path
+//! `m/44'/904'/<account>'/0'/0'`, which is also what hardware wallets derive.
+//!
+//! [SLIP10]: https://github.com/satoshilabs/slips/blob/master/slip-0010.md
+use crate::{ed25519, error, public_key, Network};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha512;
+use std::{fmt, str::FromStr};
+use zeroize::Zeroize;
+
+/// The registered BIP44 coin type for Helium.
+pub const HELIUM_COIN_TYPE: u32 = 904;
+
+pub(crate) const HARDENED: u32 = 0x8000_0000;
+const ED25519_SEED_KEY: &[u8] = b"ed25519 seed";
+
+/// A derivation path of hardened indexes.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// Construct a path from the given indexes. Indexes must be below 2^31 and
+    /// are all treated as hardened.
+    pub fn new(indexes: &[u32]) -> error::Result<Self> {
+        if let Some(index) = indexes.iter().find(|index| **index >= HARDENED) {
+            return Err(error::invalid_derivation_path(&index.to_string()));
+        }
+        Ok(Self(indexes.to_vec()))
+    }
+
+    /// The path for the given Helium account, `m/44'/904'/<account>'/0'/0'`.
+    pub fn account(account: u32) -> error::Result<Self> {
+        Self::new(&[44, HELIUM_COIN_TYPE, account, 0, 0])
+    }
+
+    /// The (unhardened) indexes in this path
+    pub fn indexes(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = error::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(error::invalid_derivation_path(s));
+        }
+        let indexes = parts
+            .map(|part| {
+                part.strip_suffix('\'')
+                    .or_else(|| part.strip_suffix('h'))
+                    .and_then(|index| index.parse::<u32>().ok())
+                    .ok_or_else(|| error::invalid_derivation_path(s))
+            })
+            .collect::<error::Result<Vec<u32>>>()?;
+        Self::new(&indexes)
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
+        f.write_str("m")?;
+        for index in &self.0 {
+            write!(f, "/{}'", index)?;
+        }
+        Ok(())
+    }
+}
+
+/// An extended ed25519 secret: a secret key together with its chain code. Both
+/// are zeroized on drop.
+pub struct ExtendedSecret {
+    secret: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl std::fmt::Debug for ExtendedSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("ExtendedSecret")
+            .field("secret", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl ExtendedSecret {
+    /// Derive the master extended secret for the given seed.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        Self::from_hmac(ED25519_SEED_KEY, &[seed])
+    }
+
+    /// Derive the extended secret at the given path below this one.
+    pub fn derive(&self, path: &DerivationPath) -> Self {
+        let mut result = Self {
+            secret: self.secret,
+            chain_code: self.chain_code,
+        };
+        for index in path.indexes() {
+            result = result.derive_child(*index);
+        }
+        result
+    }
+
+    /// The ed25519 keypair for this extended secret on the given network.
+    pub fn to_keypair(&self, network: Network) -> ed25519::Keypair {
+        ed25519::Keypair::from_secret(network, &self.secret).expect("ed25519 secret")
+    }
+
+    fn derive_child(&self, index: u32) -> Self {
+        Self::from_hmac(
+            &self.chain_code,
+            &[&[0u8], &self.secret, &(index | HARDENED).to_be_bytes()],
+        )
+    }
+
+    fn from_hmac(key: &[u8], data: &[&[u8]]) -> Self {
+        let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("hmac key");
+        for part in data {
+            mac.update(part);
+        }
+        let mut output = mac.finalize().into_bytes();
+        let mut secret = [0u8; 32];
+        let mut chain_code = [0u8; 32];
+        secret.copy_from_slice(&output[..32]);
+        chain_code.copy_from_slice(&output[32..]);
+        output[..].zeroize();
+        Self { secret, chain_code }
+    }
+}
+
+impl Zeroize for ExtendedSecret {
+    fn zeroize(&mut self) {
+        self.secret.zeroize();
+        self.chain_code.zeroize();
+    }
+}
+
+impl Drop for ExtendedSecret {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// Path layouts seen in Helium wallets. Helium accounts normally use the
+/// first, other layouts show up when software and hardware wallets disagree on
+/// which path level holds the account index.
+const PATH_LAYOUTS: &[fn(u32) -> [u32; 5]] = &[
+    |account| [44, HELIUM_COIN_TYPE, account, 0, 0],
+    |account| [44, HELIUM_COIN_TYPE, 0, 0, account],
+    |account| [44, HELIUM_COIN_TYPE, 0, account, 0],
+];
+
+/// The public key, and so the address, that a hardware wallet initialized
+/// with the given seed will display for the given path.
+pub fn preview_address(
+    seed: &[u8],
+    network: Network,
+    path: &DerivationPath,
+) -> public_key::PublicKey {
+    ExtendedSecret::from_seed(seed)
+        .derive(path)
+        .to_keypair(network)
+        .public_key
+}
+
+/// Check that a public key reported by a device for the given path is the one
+/// derived locally from the seed. Devices report either the raw 32 byte
+/// ed25519 key or the binary Helium public key including its tag byte.
+pub fn verify_device_key(
+    seed: &[u8],
+    network: Network,
+    path: &DerivationPath,
+    reported: &[u8],
+) -> error::Result {
+    let expected = preview_address(seed, network, path).to_bytes();
+    let matches = match reported.len() {
+        len if len == expected.len() => reported == &expected[..],
+        len if len == expected.len() - 1 => reported == &expected[1..],
+        _ => false,
+    };
+    if !matches {
+        return Err(error::device_key_mismatch(&path.to_string()));
+    }
+    Ok(())
+}
+
+/// Search the known path layouts for the first `accounts` accounts for the
+/// path deriving the given public key. Useful to diagnose which derivation a
+/// device or other wallet actually used.
+pub fn find_path(
+    seed: &[u8],
+    public_key: &public_key::PublicKey,
+    accounts: u32,
+) -> Option<DerivationPath> {
+    let master = ExtendedSecret::from_seed(seed);
+    (0..accounts.min(HARDENED))
+        .flat_map(|account| PATH_LAYOUTS.iter().map(move |layout| layout(account)))
+        .map(|indexes| DerivationPath(indexes.to_vec()))
+        .find(|path| {
+            &master
+                .derive(path)
+                .to_keypair(public_key.network)
+                .public_key
+                == public_key
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DerivationPath, ExtendedSecret};
+    use hex_literal::hex;
+
+    #[test]
+    fn slip10_vector() {
+        // Test vector 1 for ed25519 from SLIP-0010
+        const SEED: &[u8] = &hex!("000102030405060708090a0b0c0d0e0f");
+        let master = ExtendedSecret::from_seed(SEED);
+        assert_eq!(
+            hex!("2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"),
+            master.secret
+        );
+        assert_eq!(
+            hex!("90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"),
+            master.chain_code
+        );
+        let child = master.derive(&"m/0'".parse().expect("path"));
+        assert_eq!(
+            hex!("68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3"),
+            child.secret
+        );
+        assert_eq!(
+            hex!("8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69"),
+            child.chain_code
+        );
+    }
+
+    #[test]
+    fn device_key() {
+        use super::{find_path, preview_address, verify_device_key};
+        use crate::Network;
+        const SEED: &[u8] = &hex!("000102030405060708090a0b0c0d0e0f");
+        let path = DerivationPath::account(2).expect("path");
+        let public_key = preview_address(SEED, Network::MainNet, &path);
+        let bytes = public_key.to_bytes();
+        assert!(verify_device_key(SEED, Network::MainNet, &path, &bytes).is_ok());
+        assert!(verify_device_key(SEED, Network::MainNet, &path, &bytes[1..]).is_ok());
+        let other = DerivationPath::account(3).expect("path");
+        assert!(verify_device_key(SEED, Network::MainNet, &other, &bytes).is_err());
+        // A key derived with the account in the last position is found
+        let swapped = DerivationPath::new(&[44, 904, 0, 0, 2]).expect("path");
+        let public_key = preview_address(SEED, Network::MainNet, &swapped);
+        assert_eq!(Some(swapped), find_path(SEED, &public_key, 5));
+    }
+
+    #[test]
+    fn path_roundtrip() {
+        let path = DerivationPath::account(3).expect("path");
+        assert_eq!("m/44'/904'/3'/0'/0'", path.to_string());
+        assert_eq!(path, "m/44h/904h/3h/0h/0h".parse().expect("path"));
+        assert!("m/44'/904".parse::<DerivationPath>().is_err());
+        assert!("44'/904'".parse::<DerivationPath>().is_err());
+    }
+}
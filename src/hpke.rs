@@ -0,0 +1,248 @@
+//! Hybrid Public Key Encryption (RFC 9180) to crate keys.
+//!
+//! The KEM follows from the recipient key type:
+//!
+//! * ecc_compact keys use DHKEM(P-256, HKDF-SHA256)
+//! * ed25519 keys use DHKEM(X25519, HKDF-SHA256), with the key converted to its
+//!   X25519 form as for [sealed boxes](crate::sealed_box)
+//!
+//! The KDF is always HKDF-SHA256 and the AEAD ChaCha20-Poly1305, and only the
+//! base mode is supported. The encapsulated key returned when sealing is the
+//! serialized ephemeral public key from the RFC, 65 bytes for P-256 and 32 bytes
+//! for X25519, and has to be sent along with the ciphertexts. secp256k1 keys
+//! have no standard KEM and are rejected.
+use crate::{
+    ecc_compact, ed25519,
+    entropy::{EntropyRng, EntropySource},
+    error, KeyType, Keypair, PublicKey,
+};
+use hpke::{
+    aead::{AeadCtxR, AeadCtxS, ChaCha20Poly1305},
+    kdf::HkdfSha256,
+    kem::{DhP256HkdfSha256, X25519HkdfSha256},
+    Deserializable, Kem, OpModeR, OpModeS, Serializable,
+};
+use std::convert::TryFrom;
+use zeroize::Zeroize;
+
+/// The sending side of a multi-message HPKE context.
+pub struct SenderContext(SenderInner);
+
+enum SenderInner {
+    P256(AeadCtxS<ChaCha20Poly1305, HkdfSha256, DhP256HkdfSha256>),
+    X25519(AeadCtxS<ChaCha20Poly1305, HkdfSha256, X25519HkdfSha256>),
+}
+
+/// The receiving side of a multi-message HPKE context.
+pub struct ReceiverContext(ReceiverInner);
+
+enum ReceiverInner {
+    P256(AeadCtxR<ChaCha20Poly1305, HkdfSha256, DhP256HkdfSha256>),
+    X25519(AeadCtxR<ChaCha20Poly1305, HkdfSha256, X25519HkdfSha256>),
+}
+
+impl SenderContext {
+    /// Encrypt the next message in the context. Messages have to be opened
+    /// in the order they are sealed.
+    pub fn seal(&mut self, msg: &[u8], aad: &[u8]) -> error::Result<Vec<u8>> {
+        match &mut self.0 {
+            SenderInner::P256(ctx) => Ok(ctx.seal(msg, aad)?),
+            SenderInner::X25519(ctx) => Ok(ctx.seal(msg, aad)?),
+        }
+    }
+}
+
+impl ReceiverContext {
+    /// Decrypt the next message in the context.
+    pub fn open(&mut self, ciphertext: &[u8], aad: &[u8]) -> error::Result<Vec<u8>> {
+        match &mut self.0 {
+            ReceiverInner::P256(ctx) => Ok(ctx.open(ciphertext, aad)?),
+            ReceiverInner::X25519(ctx) => Ok(ctx.open(ciphertext, aad)?),
+        }
+    }
+}
+
+/// Encrypt a single message to the given recipient key. Returns the
+/// encapsulated key and the ciphertext.
+pub fn seal<E>(
+    recipient: &PublicKey,
+    info: &[u8],
+    aad: &[u8],
+    msg: &[u8],
+    entropy: &mut E,
+) -> error::Result<(Vec<u8>, Vec<u8>)>
+where
+    E: EntropySource + ?Sized,
+{
+    let (encapped_key, mut ctx) = setup_sender(recipient, info, entropy)?;
+    Ok((encapped_key, ctx.seal(msg, aad)?))
+}
+
+/// Decrypt a single message sealed to the public key of the given keypair.
+pub fn open(
+    recipient: &Keypair,
+    encapped_key: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> error::Result<Vec<u8>> {
+    setup_receiver(recipient, encapped_key, info)?.open(ciphertext, aad)
+}
+
+/// Set up a context for sending any number of messages to the given recipient
+/// key. Returns the encapsulated key and the context.
+pub fn setup_sender<E>(
+    recipient: &PublicKey,
+    info: &[u8],
+    entropy: &mut E,
+) -> error::Result<(Vec<u8>, SenderContext)>
+where
+    E: EntropySource + ?Sized,
+{
+    let mut rng = EntropyRng(entropy);
+    match recipient.tag().key_type {
+        KeyType::EccCompact => {
+            let public_key = recipient.to_sec1_bytes(false)?;
+            let (encapped_key, ctx) = hpke::setup_sender::<_, _, DhP256HkdfSha256, _>(
+                &OpModeS::Base,
+                &kem_public_key::<DhP256HkdfSha256>(&public_key)?,
+                info,
+                &mut rng,
+            )?;
+            Ok((
+                encapped_key.to_bytes().to_vec(),
+                SenderContext(SenderInner::P256(ctx)),
+            ))
+        }
+        KeyType::Ed25519 => {
+            let public_key = ed25519::PublicKey::try_from(recipient)?.to_x25519();
+            let (encapped_key, ctx) = hpke::setup_sender::<_, _, X25519HkdfSha256, _>(
+                &OpModeS::Base,
+                &kem_public_key::<X25519HkdfSha256>(&public_key)?,
+                info,
+                &mut rng,
+            )?;
+            Ok((
+                encapped_key.to_bytes().to_vec(),
+                SenderContext(SenderInner::X25519(ctx)),
+            ))
+        }
+        _ => Err(error::unsupported_keytype(recipient.tag().key_type)),
+    }
+}
+
+/// Set up a context for receiving messages sealed to the public key of the
+/// given keypair with the given encapsulated key.
+pub fn setup_receiver(
+    recipient: &Keypair,
+    encapped_key: &[u8],
+    info: &[u8],
+) -> error::Result<ReceiverContext> {
+    match recipient {
+        Keypair::EccCompact(keypair) => {
+            let secret = ecc_compact_secret(keypair);
+            let ctx = hpke::setup_receiver::<_, _, DhP256HkdfSha256>(
+                &OpModeR::Base,
+                &<DhP256HkdfSha256 as Kem>::PrivateKey::from_bytes(&secret)?,
+                &<DhP256HkdfSha256 as Kem>::EncappedKey::from_bytes(encapped_key)?,
+                info,
+            )?;
+            Ok(ReceiverContext(ReceiverInner::P256(ctx)))
+        }
+        Keypair::Ed25519(keypair) => {
+            let secret = Secret(keypair.to_x25519_secret());
+            let ctx = hpke::setup_receiver::<_, _, X25519HkdfSha256>(
+                &OpModeR::Base,
+                &<X25519HkdfSha256 as Kem>::PrivateKey::from_bytes(&secret)?,
+                &<X25519HkdfSha256 as Kem>::EncappedKey::from_bytes(encapped_key)?,
+                info,
+            )?;
+            Ok(ReceiverContext(ReceiverInner::X25519(ctx)))
+        }
+        Keypair::Secp256k1(_) | Keypair::Schnorr(_) => {
+            Err(error::unsupported_keytype(recipient.key_tag().key_type))
+        }
+    }
+}
+
+fn kem_public_key<K: Kem>(bytes: &[u8]) -> error::Result<K::PublicKey> {
+    Ok(K::PublicKey::from_bytes(bytes)?)
+}
+
+/// Zeroizes the secret bytes handed to the HPKE library on drop.
+struct Secret([u8; 32]);
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0[..].zeroize();
+    }
+}
+
+impl std::ops::Deref for Secret {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+fn ecc_compact_secret(keypair: &ecc_compact::Keypair) -> Secret {
+    let mut bytes = keypair.to_bytes();
+    let mut secret = [0u8; 32];
+    secret.copy_from_slice(&bytes[1..]);
+    bytes[..].zeroize();
+    Secret(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open, seal, setup_receiver, setup_sender};
+    use crate::{ecc_compact, ed25519, secp256k1, Keypair, Network};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn single_shot() {
+        for (keypair, encapped_length) in vec![
+            (
+                Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+                65,
+            ),
+            (
+                Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng)),
+                32,
+            ),
+        ] {
+            let (encapped_key, ciphertext) = seal(
+                keypair.public_key(),
+                b"info",
+                b"aad",
+                b"hello world",
+                &mut OsRng,
+            )
+            .expect("seal");
+            assert_eq!(encapped_length, encapped_key.len());
+            assert_eq!(
+                b"hello world".to_vec(),
+                open(&keypair, &encapped_key, b"info", b"aad", &ciphertext).expect("open")
+            );
+            assert!(open(&keypair, &encapped_key, b"info", b"other", &ciphertext).is_err());
+        }
+    }
+
+    #[test]
+    fn multi_message() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let (encapped_key, mut sender) =
+            setup_sender(keypair.public_key(), b"info", &mut OsRng).expect("sender");
+        let mut receiver = setup_receiver(&keypair, &encapped_key, b"info").expect("receiver");
+        for msg in &[&b"first"[..], b"second", b"third"] {
+            let ciphertext = sender.seal(msg, b"").expect("seal");
+            assert_eq!(msg.to_vec(), receiver.open(&ciphertext, b"").expect("open"));
+        }
+    }
+
+    #[test]
+    fn unsupported_key_type() {
+        let keypair = secp256k1::Keypair::generate(Network::MainNet, &mut OsRng);
+        assert!(seal(&keypair.public_key, b"", b"", b"hello", &mut OsRng).is_err());
+    }
+}
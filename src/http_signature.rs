@@ -0,0 +1,372 @@
+//! HTTP message signatures as specified in [RFC 9421][RFC9421].
+//!
+//! Signatures cover a selection of message components, derived components
+//! like `@method` and `@path` as well as header fields, together with the
+//! signature parameters. ed25519 keys sign with the `ed25519` algorithm and
+//! ecc_compact keys with `ecdsa-p256-sha256`, which uses the fixed size `r ||
+//! s` signature form rather than DER.
+//!
+//! This module does not depend on a HTTP library. Messages are read through
+//! the [`Message`] trait, and signing produces the values of the
+//! `Signature-Input` and `Signature` header fields for the caller to attach.
+//!
+//! [RFC9421]: https://www.rfc-editor.org/rfc/rfc9421
+use crate::{error, Keypair, PublicKey, Sign, Verify, VerifyOptions};
+use std::fmt::Write;
+
+/// The algorithm name for ed25519 signatures.
+pub const ALG_ED25519: &str = "ed25519";
+/// The algorithm name for ECDSA P-256 SHA-256 signatures.
+pub const ALG_ECDSA_P256_SHA256: &str = "ecdsa-p256-sha256";
+
+/// Length of signatures for both supported algorithms.
+const SIGNATURE_LENGTH: usize = 64;
+
+/// A HTTP request or response whose components can be covered by a
+/// signature.
+pub trait Message {
+    /// The value of the given component. Derived components are named with
+    /// their leading `@`, like `@method`, and header fields by their lower
+    /// case name. Values of fields that occur more than once should be joined
+    /// with `", "`. Returns `None` if the message has no such component.
+    fn component(&self, name: &str) -> Option<String>;
+}
+
+/// The signature parameters.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SignatureParams {
+    /// The covered components, in order
+    pub components: Vec<String>,
+    /// Creation time as a unix timestamp
+    pub created: Option<u64>,
+    /// Expiration time as a unix timestamp
+    pub expires: Option<u64>,
+    pub nonce: Option<String>,
+    pub alg: Option<String>,
+    pub keyid: Option<String>,
+}
+
+impl SignatureParams {
+    /// Serialize the parameters as the inner list used in the
+    /// `Signature-Input` field and the `@signature-params` line.
+    pub fn serialize(&self) -> String {
+        let mut result = String::from("(");
+        for (i, component) in self.components.iter().enumerate() {
+            if i > 0 {
+                result.push(' ');
+            }
+            let _ = write!(result, "\"{}\"", component);
+        }
+        result.push(')');
+        if let Some(created) = self.created {
+            let _ = write!(result, ";created={}", created);
+        }
+        if let Some(expires) = self.expires {
+            let _ = write!(result, ";expires={}", expires);
+        }
+        if let Some(nonce) = &self.nonce {
+            let _ = write!(result, ";nonce=\"{}\"", nonce);
+        }
+        if let Some(alg) = &self.alg {
+            let _ = write!(result, ";alg=\"{}\"", alg);
+        }
+        if let Some(keyid) = &self.keyid {
+            let _ = write!(result, ";keyid=\"{}\"", keyid);
+        }
+        result
+    }
+
+    /// Parse a serialized inner list with parameters.
+    fn parse(input: &str) -> error::Result<Self> {
+        let input = input
+            .strip_prefix('(')
+            .ok_or_else(error::invalid_http_signature)?;
+        let (components, params) = input
+            .split_once(')')
+            .ok_or_else(error::invalid_http_signature)?;
+        let components = components
+            .split(' ')
+            .filter(|item| !item.is_empty())
+            .map(|item| unquote(item).map(str::to_string))
+            .collect::<error::Result<Vec<String>>>()?;
+        let mut result = Self {
+            components,
+            ..Default::default()
+        };
+        for param in params.split(';').skip(1) {
+            let (name, value) = param
+                .split_once('=')
+                .ok_or_else(error::invalid_http_signature)?;
+            let integer = || {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| error::invalid_http_signature())
+            };
+            match name {
+                "created" => result.created = Some(integer()?),
+                "expires" => result.expires = Some(integer()?),
+                "nonce" => result.nonce = Some(unquote(value)?.to_string()),
+                "alg" => result.alg = Some(unquote(value)?.to_string()),
+                "keyid" => result.keyid = Some(unquote(value)?.to_string()),
+                // Unknown parameters are covered by the signature base as is
+                _ => (),
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// The header field values produced by [`sign`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct SignatureHeaders {
+    /// The value of the `Signature-Input` field
+    pub signature_input: String,
+    /// The value of the `Signature` field
+    pub signature: String,
+}
+
+/// Sign the given message with the given keypair under the given label. The
+/// `alg` parameter is not added, but is checked against the keypair if set.
+pub fn sign<M: Message + ?Sized>(
+    keypair: &Keypair,
+    label: &str,
+    message: &M,
+    params: &SignatureParams,
+) -> error::Result<SignatureHeaders> {
+    let alg = match keypair {
+        Keypair::EccCompact(_) => ALG_ECDSA_P256_SHA256,
+        Keypair::Ed25519(_) => ALG_ED25519,
+        // There is no registered algorithm for secp256k1
+        Keypair::Secp256k1(_) | Keypair::Schnorr(_) => return Err(error::invalid_http_signature()),
+    };
+    if matches!(params.alg.as_deref(), Some(v) if v != alg) {
+        return Err(error::invalid_http_signature());
+    }
+    let serialized = params.serialize();
+    let base = signature_base(message, &params.components, &serialized)?;
+    let signature = keypair.sign(base.as_bytes())?;
+    let signature = match keypair {
+        Keypair::EccCompact(_) => p256::ecdsa::Signature::from_der(&signature)?
+            .as_ref()
+            .to_vec(),
+        Keypair::Ed25519(_) | Keypair::Secp256k1(_) | Keypair::Schnorr(_) => signature,
+    };
+    Ok(SignatureHeaders {
+        signature_input: format!("{}={}", label, serialized),
+        signature: format!("{}=:{}:", label, base64::encode(signature)),
+    })
+}
+
+/// Parse the parameters of the signature with the given label in the given
+/// `Signature-Input` field value without verifying the signature, for example
+/// to look up the key named by `keyid` before verifying.
+pub fn signature_params(signature_input: &str, label: &str) -> error::Result<SignatureParams> {
+    SignatureParams::parse(dictionary_member(signature_input, label)?)
+}
+
+/// Verify the signature with the given label in the given `Signature-Input`
+/// and `Signature` field values over the given message. Signatures created
+/// after `now` or expired at `now`, a unix timestamp, are rejected. Returns
+/// the verified signature parameters.
+pub fn verify<M: Message + ?Sized>(
+    public_key: &PublicKey,
+    label: &str,
+    message: &M,
+    signature_input: &str,
+    signature: &str,
+    now: u64,
+) -> error::Result<SignatureParams> {
+    let serialized = dictionary_member(signature_input, label)?;
+    let params = SignatureParams::parse(serialized)?;
+    if matches!(params.created, Some(created) if created > now)
+        || matches!(params.expires, Some(expires) if expires <= now)
+    {
+        return Err(error::http_signature_expired());
+    }
+    let signature = dictionary_member(signature, label)?
+        .strip_prefix(':')
+        .and_then(|v| v.strip_suffix(':'))
+        .ok_or_else(error::invalid_http_signature)?;
+    let signature = base64::decode(signature).map_err(|_| error::invalid_http_signature())?;
+    if signature.len() != SIGNATURE_LENGTH {
+        return Err(error::invalid_http_signature());
+    }
+    let base = signature_base(message, &params.components, serialized)?;
+    let options = VerifyOptions {
+        allow_raw_ecdsa: true,
+        ..VerifyOptions::DEFAULT
+    };
+    public_key.verify_with_options(base.as_bytes(), &signature, &options)?;
+    Ok(params)
+}
+
+/// Build the signature base for the given components and serialized
+/// signature parameters.
+fn signature_base<M: Message + ?Sized>(
+    message: &M,
+    components: &[String],
+    serialized_params: &str,
+) -> error::Result<String> {
+    let mut base = String::new();
+    for name in components {
+        if name == "@signature-params" {
+            return Err(error::invalid_http_signature());
+        }
+        let value = message
+            .component(name)
+            .ok_or_else(error::invalid_http_signature)?;
+        let _ = writeln!(base, "\"{}\": {}", name, value.trim());
+    }
+    let _ = write!(base, "\"@signature-params\": {}", serialized_params);
+    Ok(base)
+}
+
+/// Returns the raw value of the member with the given key in a structured
+/// field dictionary.
+fn dictionary_member<'a>(input: &'a str, key: &str) -> error::Result<&'a str> {
+    let mut start = 0;
+    let mut quoted = false;
+    let mut members = vec![];
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                members.push(&input[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    members.push(&input[start..]);
+    members
+        .into_iter()
+        .filter_map(|member| member.trim().split_once('='))
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| value)
+        .ok_or_else(error::invalid_http_signature)
+}
+
+fn unquote(input: &str) -> error::Result<&str> {
+    input
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(error::invalid_http_signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, verify, Message, SignatureParams};
+    use crate::{ecc_compact, ed25519, Keypair, Network};
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+    use std::collections::HashMap;
+
+    struct Request(HashMap<&'static str, &'static str>);
+
+    impl Message for Request {
+        fn component(&self, name: &str) -> Option<String> {
+            self.0.get(name).map(|v| v.to_string())
+        }
+    }
+
+    fn request() -> Request {
+        Request(
+            vec![
+                ("@method", "POST"),
+                ("@path", "/foo"),
+                ("@authority", "example.com"),
+                ("date", "Tue, 20 Apr 2021 02:07:55 GMT"),
+                ("content-type", "application/json"),
+                ("content-length", "18"),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+
+    // Test case B.2.6 from RFC 9421
+    const SIGNATURE_INPUT: &str = "sig-b26=(\"date\" \"@method\" \"@path\" \"@authority\" \
+        \"content-type\" \"content-length\");created=1618884473;keyid=\"test-key-ed25519\"";
+    const SIGNATURE: &str = "sig-b26=:wqcAqbmYJ2ji2glfAMaRy4gruYYnx2nEFN2HN6jrnDnQCK1u02Gb04v9\
+        EDgwUPiu4A0w6vuQv5lIp5WPpBKRCw==:";
+
+    fn rfc_keypair() -> ed25519::Keypair {
+        ed25519::Keypair::generate_from_entropy(
+            Network::MainNet,
+            &hex!(
+                "9f8362f87a484a954e6e740c5b4c0e84229139a20aa8ab56ff66586f6a7d29c5"
+                "26b40b8f93fff3d897112f7ebc582b232dbd72517d082fe83cfb30ddce43d1bb"
+            ),
+        )
+        .expect("keypair")
+    }
+
+    #[test]
+    fn rfc_vector() {
+        let keypair = Keypair::Ed25519(rfc_keypair());
+        let params = SignatureParams {
+            components: [
+                "date",
+                "@method",
+                "@path",
+                "@authority",
+                "content-type",
+                "content-length",
+            ]
+            .iter()
+            .map(|v| v.to_string())
+            .collect(),
+            created: Some(1618884473),
+            keyid: Some("test-key-ed25519".to_string()),
+            ..Default::default()
+        };
+        let headers = sign(&keypair, "sig-b26", &request(), &params).expect("signature");
+        assert_eq!(SIGNATURE_INPUT, headers.signature_input);
+        assert_eq!(SIGNATURE, headers.signature);
+        let verified = verify(
+            keypair.public_key(),
+            "sig-b26",
+            &request(),
+            SIGNATURE_INPUT,
+            SIGNATURE,
+            1618884473,
+        )
+        .expect("verified");
+        assert_eq!(params, verified);
+        // Signatures from the future are rejected
+        assert!(verify(
+            keypair.public_key(),
+            "sig-b26",
+            &request(),
+            SIGNATURE_INPUT,
+            SIGNATURE,
+            1618884472,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn ecdsa_roundtrip() {
+        let keypair =
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng));
+        let params = SignatureParams {
+            components: vec!["@method".to_string(), "@path".to_string()],
+            created: Some(100),
+            expires: Some(200),
+            ..Default::default()
+        };
+        let headers = sign(&keypair, "sig", &request(), &params).expect("signature");
+        let verify_at = |now| {
+            verify(
+                keypair.public_key(),
+                "sig",
+                &request(),
+                &headers.signature_input,
+                &headers.signature,
+                now,
+            )
+        };
+        assert!(verify_at(150).is_ok());
+        assert!(verify_at(200).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Interoperability tests against vectors produced by other implementations.
+//!
+//! Every `*.json` file in `fixtures/interop` holds an array of vectors in
+//! the format of [`test_vectors::to_json`], with an additional `source` field
+//! naming the implementation that produced them. Vectors from the erlang
+//! `libp2p_crypto` library and the Go implementation belong there as
+//! `erlang.json` and `go.json`. The vectors checked in so far are the first
+//! ed25519 vector of RFC 8032 and ECDSA vectors signed by OpenSSL, whose
+//! signatures have a high s and test DER parsing and s normalization.
+//!
+//! In the other direction, setting `HELIUM_INTEROP_EXPORT` to a file path
+//! writes the vectors of this crate there, for the test suites of the other
+//! implementations to verify.
+use crate::{test_vectors, KeyTag, Keypair, PublicKey, Sign, Verify};
+use std::{convert::TryFrom, env, fs, path::Path};
+
+fn hex(field: &serde_json::Value) -> Vec<u8> {
+    let hex = field.as_str().expect("hex field");
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("hex digits"))
+        .collect()
+}
+
+fn check(source: &str, vector: &serde_json::Value) {
+    let key_tag: KeyTag = vector["key_tag"]
+        .as_str()
+        .expect("key tag")
+        .parse()
+        .expect("key tag");
+    let keypair_bytes = hex(&vector["keypair"]);
+    let keypair = Keypair::try_from(&keypair_bytes[..]).expect(source);
+    assert_eq!(key_tag, keypair.key_tag(), "{}", source);
+    assert_eq!(keypair_bytes, keypair.to_vec(), "{}", source);
+    assert_eq!(hex(&vector["seed"]), keypair_bytes[1..33], "{}", source);
+
+    let address = vector["address"].as_str().expect("address");
+    assert_eq!(address, keypair.public_key().to_string(), "{}", source);
+    let public_key: PublicKey = address.parse().expect(source);
+    let message = hex(&vector["message"]);
+    let signature = hex(&vector["signature"]);
+    assert!(
+        public_key.verify(&message, &signature).is_ok(),
+        "{} {} signature",
+        source,
+        key_tag
+    );
+    let ours = keypair.sign(&message).expect("signature");
+    assert!(public_key.verify(&message, &ours).is_ok(), "{}", source);
+    if let Keypair::Ed25519(_) | Keypair::Schnorr(_) = keypair {
+        assert_eq!(signature, ours, "{} {} signature", source, key_tag);
+    }
+}
+
+#[test]
+fn fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/interop");
+    let mut count = 0;
+    for entry in fs::read_dir(dir).expect("fixtures") {
+        let path = entry.expect("fixture").path();
+        if path
+            .extension()
+            .map_or(true, |extension| extension != "json")
+        {
+            continue;
+        }
+        let vectors: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).expect("fixture")).expect("json");
+        for vector in vectors.as_array().expect("array of vectors") {
+            let source = vector["source"].as_str().expect("source");
+            check(source, vector);
+            count += 1;
+        }
+    }
+    assert!(count > 0);
+}
+
+#[test]
+fn export() {
+    // Our own vectors have to pass the checks applied to everyone else's
+    let vectors: serde_json::Value = serde_json::from_str(&test_vectors::to_json()).expect("json");
+    for vector in vectors.as_array().expect("array of vectors") {
+        check("helium-crypto", vector);
+    }
+    if let Some(path) = env::var_os("HELIUM_INTEROP_EXPORT") {
+        fs::write(path, test_vectors::to_json()).expect("export");
+    }
+}
@@ -0,0 +1,178 @@
+//! JSON Web Tokens (RFC 7519) in the JWS compact serialization, signed and
+//! verified with crate keypairs, for authenticating to API gateways with
+//! standard tokens.
+//!
+//! ecc_compact keys sign with `ES256`, which uses the fixed size `r || s`
+//! signature form rather than DER, and ed25519 keys with `EdDSA` (RFC 8037).
+//! secp256k1 and BIP340 keys have no registered algorithm. The `kid` header
+//! is the b58 address of the signing key, as for [`crate::jwk`], so a
+//! verifier can find the key from the token and then decide whether to trust
+//! it.
+//!
+//! Claims are any serde type. The registered `exp` and `nbf` claims are
+//! checked on verification when present.
+use crate::{error, KeyType, Keypair, PublicKey, Sign, Verify, VerifyOptions};
+use serde_crate::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// The algorithm name for ECDSA P-256 SHA-256 signatures.
+pub const ALG_ES256: &str = "ES256";
+/// The algorithm name for ed25519 signatures.
+pub const ALG_EDDSA: &str = "EdDSA";
+
+/// Length of signatures for both supported algorithms.
+const SIGNATURE_LENGTH: usize = 64;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct Header {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+}
+
+/// The timing claims checked on verification.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct Validity {
+    exp: Option<u64>,
+    nbf: Option<u64>,
+}
+
+fn alg(key_type: KeyType) -> error::Result<&'static str> {
+    match key_type {
+        KeyType::EccCompact => Ok(ALG_ES256),
+        KeyType::Ed25519 => Ok(ALG_EDDSA),
+        _ => Err(error::unsupported_keytype(key_type)),
+    }
+}
+
+fn encode(input: &[u8]) -> String {
+    base64::encode_config(input, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(input: &str) -> error::Result<Vec<u8>> {
+    base64::decode_config(input, base64::URL_SAFE_NO_PAD).map_err(|_| error::invalid_jwt())
+}
+
+/// Sign the given claims with the given keypair into a compact JWT.
+pub fn sign<C: Serialize>(keypair: &Keypair, claims: &C) -> error::Result<String> {
+    let header = Header {
+        alg: alg(keypair.key_tag().key_type)?.to_string(),
+        typ: Some("JWT".to_string()),
+        kid: Some(keypair.public_key().to_string()),
+    };
+    let header = serde_json::to_vec(&header).expect("jwt header");
+    let claims = serde_json::to_vec(claims).map_err(|_| error::invalid_jwt())?;
+    let mut token = format!("{}.{}", encode(&header), encode(&claims));
+    let signature = keypair.sign(token.as_bytes())?;
+    let signature = match keypair {
+        Keypair::EccCompact(_) => p256::ecdsa::Signature::from_der(&signature)?
+            .as_ref()
+            .to_vec(),
+        _ => signature,
+    };
+    token.push('.');
+    token.push_str(&encode(&signature));
+    Ok(token)
+}
+
+/// The public key named by the `kid` header of the given token, without
+/// verifying the token.
+pub fn key_id(token: &str) -> error::Result<PublicKey> {
+    let header = token.split('.').next().ok_or_else(error::invalid_jwt)?;
+    let header: Header =
+        serde_json::from_slice(&decode(header)?).map_err(|_| error::invalid_jwt())?;
+    header
+        .kid
+        .ok_or_else(error::invalid_jwt)?
+        .parse()
+        .map_err(|_| error::invalid_jwt())
+}
+
+/// Verify the given token against the key named by its `kid` header and
+/// return that key with the claims. Only use the claims after checking that
+/// the key is trusted. Tokens that are not yet valid or expired at `now`, a
+/// unix timestamp, are rejected.
+pub fn verify<C: DeserializeOwned>(token: &str, now: u64) -> error::Result<(PublicKey, C)> {
+    let public_key = key_id(token)?;
+    let claims = verify_with_key(&public_key, token, now)?;
+    Ok((public_key, claims))
+}
+
+/// Verify the given token against the given key and return its claims.
+/// Tokens that are not yet valid or expired at `now`, a unix timestamp, are
+/// rejected.
+pub fn verify_with_key<C: DeserializeOwned>(
+    public_key: &PublicKey,
+    token: &str,
+    now: u64,
+) -> error::Result<C> {
+    let (signed, signature) = token.rsplit_once('.').ok_or_else(error::invalid_jwt)?;
+    let signature = decode(signature)?;
+    let (header, claims) = signed.split_once('.').ok_or_else(error::invalid_jwt)?;
+    let header: Header =
+        serde_json::from_slice(&decode(header)?).map_err(|_| error::invalid_jwt())?;
+    // The algorithm is fixed by the key, never by the token
+    if header.alg != alg(public_key.tag().key_type)?
+        || matches!(&header.kid, Some(kid) if kid != &public_key.to_string())
+        || signature.len() != SIGNATURE_LENGTH
+    {
+        return Err(error::invalid_jwt());
+    }
+    let options = VerifyOptions {
+        allow_raw_ecdsa: true,
+        ..VerifyOptions::DEFAULT
+    };
+    public_key.verify_with_options(signed.as_bytes(), &signature, &options)?;
+
+    let claims = decode(claims)?;
+    let validity: Validity = serde_json::from_slice(&claims).map_err(|_| error::invalid_jwt())?;
+    if matches!(validity.nbf, Some(nbf) if nbf > now)
+        || matches!(validity.exp, Some(exp) if exp <= now)
+    {
+        return Err(error::jwt_expired());
+    }
+    serde_json::from_slice(&claims).map_err(|_| error::invalid_jwt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, verify, verify_with_key};
+    use crate::{ecc_compact, ed25519, error::Error, secp256k1, Keypair, Network};
+    use rand::rngs::OsRng;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn roundtrip() {
+        let keypairs = vec![
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng)),
+        ];
+        let claims = json!({"sub": "hotspot", "nbf": 100, "exp": 200});
+        for keypair in keypairs {
+            let token = sign(&keypair, &claims).expect("token");
+            let (public_key, verified) = verify::<Value>(&token, 150).expect("claims");
+            assert_eq!(keypair.public_key(), &public_key);
+            assert_eq!(claims, verified);
+            assert!(matches!(
+                verify::<Value>(&token, 200),
+                Err(Error::JwtExpired)
+            ));
+            assert!(matches!(
+                verify::<Value>(&token, 99),
+                Err(Error::JwtExpired)
+            ));
+
+            let other = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+            assert!(verify_with_key::<Value>(&other.public_key, &token, 150).is_err());
+            let mut tampered = token.clone();
+            tampered.insert_str(token.find('.').expect("header") + 1, "e30");
+            assert!(verify::<Value>(&tampered, 150).is_err());
+        }
+        let keypair =
+            Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng));
+        assert!(sign(&keypair, &claims).is_err());
+    }
+}
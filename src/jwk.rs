@@ -0,0 +1,227 @@
+//! JSON Web Keys (RFC 7517) for public keys and keypairs, for use with JOSE and
+//! OpenID Connect tooling. ecc_compact and secp256r1 keys map to `EC` keys on
+//! the `P-256` curve, secp256k1 keys to `EC` keys on the `secp256k1` curve
+//! (RFC 8812) and ed25519 keys to `OKP` keys on the `Ed25519` curve (RFC 8037).
+//! `P-256` keys import as ecc_compact keys when they are compactable.
+//!
+//! The key id (`kid`) is the b58 address of the public key. Since a JWK does
+//! not carry the network of a key, it is given when importing, and a key id in
+//! an imported JWK has to match the address of the key on that network.
+use crate::{
+    ecc_compact, ed25519, error, public_key::PublicKeyRepr, secp256k1, KeyType, Keypair, Network,
+    PublicKey,
+};
+use serde_crate::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use zeroize::Zeroize;
+
+const KTY_EC: &str = "EC";
+const KTY_OKP: &str = "OKP";
+const CRV_P256: &str = "P-256";
+const CRV_SECP256K1: &str = "secp256k1";
+const CRV_ED25519: &str = "Ed25519";
+const COORDINATE_LENGTH: usize = 32;
+
+/// A JSON Web Key. Coordinates and the private key are base64url encoded
+/// without padding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+    /// The private key, only present for keypairs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub d: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+}
+
+impl Jwk {
+    /// The JSON form of this key.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("jwk json")
+    }
+
+    /// Parse a key from its JSON form.
+    pub fn from_json(input: &str) -> error::Result<Self> {
+        serde_json::from_str(input).map_err(|_| error::invalid_jwk())
+    }
+
+    /// The public key of this JWK on the given network.
+    pub fn to_public_key(&self, network: Network) -> error::Result<PublicKey> {
+        let public_key = match (self.kty.as_str(), self.crv.as_str()) {
+            (KTY_EC, CRV_P256) => PublicKey::from_p256_sec1_bytes(network, &self.ec_point()?)?,
+            (KTY_EC, CRV_SECP256K1) => PublicKey::for_network(
+                network,
+                secp256k1::PublicKey::from_sec1_point(&self.ec_point()?)?,
+            ),
+            (KTY_OKP, CRV_ED25519) if self.y.is_none() => {
+                let x = ed25519_dalek::PublicKey::from_bytes(&decode(&self.x)?)
+                    .map_err(|_| error::invalid_jwk())?;
+                PublicKey::from_ed25519(network, ed25519::PublicKey::try_from(x)?)
+            }
+            _ => return Err(error::invalid_jwk()),
+        };
+        match &self.kid {
+            Some(kid) if kid != &public_key.to_string() => Err(error::invalid_jwk()),
+            _ => Ok(public_key),
+        }
+    }
+
+    /// The keypair of this JWK on the given network. The private key has to
+    /// match the public key in the JWK.
+    pub fn to_keypair(&self, network: Network) -> error::Result<Keypair> {
+        let public_key = self.to_public_key(network)?;
+        let mut secret = decode(self.d.as_ref().ok_or_else(error::invalid_jwk)?)?;
+        let keypair = match public_key.tag().key_type {
+            KeyType::EccCompact => p256::SecretKey::from_bytes(&secret)
+                .map_err(error::Error::from)
+                .and_then(|secret| ecc_compact::Keypair::from_secret_key(network, secret))
+                .map(Keypair::EccCompact),
+            KeyType::Ed25519 => {
+                ed25519::Keypair::from_secret(network, &secret).map(Keypair::Ed25519)
+            }
+            KeyType::Secp256k1 => {
+                secp256k1::Keypair::from_secret(network, &secret).map(Keypair::Secp256k1)
+            }
+            _ => Err(error::invalid_jwk()),
+        };
+        secret[..].zeroize();
+        match keypair {
+            Ok(keypair) if keypair.public_key() == &public_key => Ok(keypair),
+            Ok(_) => Err(error::invalid_jwk()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The uncompressed SEC1 point for the coordinates of an `EC` key.
+    fn ec_point(&self) -> error::Result<Vec<u8>> {
+        let x = decode(&self.x)?;
+        let y = decode(self.y.as_ref().ok_or_else(error::invalid_jwk)?)?;
+        if x.len() != COORDINATE_LENGTH || y.len() != COORDINATE_LENGTH {
+            return Err(error::invalid_jwk());
+        }
+        let mut point = vec![0x04];
+        point.extend_from_slice(&x);
+        point.extend_from_slice(&y);
+        Ok(point)
+    }
+}
+
+/// Fails for keys of key types that have no JWK form.
+impl TryFrom<&PublicKey> for Jwk {
+    type Error = error::Error;
+    fn try_from(v: &PublicKey) -> error::Result<Self> {
+        let (kty, crv, x, y) = match &v.inner {
+            PublicKeyRepr::EccCompact(key) => {
+                let point = key.to_sec1_uncompressed();
+                (
+                    KTY_EC,
+                    CRV_P256,
+                    encode(&point[1..33]),
+                    Some(encode(&point[33..])),
+                )
+            }
+            PublicKeyRepr::Secp256r1(key) => {
+                let point = key.to_sec1_bytes(false);
+                let x = encode(&point[1..33]);
+                (KTY_EC, CRV_P256, x, Some(encode(&point[33..])))
+            }
+            PublicKeyRepr::Secp256k1(key) => {
+                let point = key.to_sec1_uncompressed();
+                let x = encode(&point[1..33]);
+                (KTY_EC, CRV_SECP256K1, x, Some(encode(&point[33..])))
+            }
+            // There is no JWK form for BIP340 keys, they export as their point
+            PublicKeyRepr::Schnorr(key) => {
+                let point = key.to_sec1_uncompressed();
+                let x = encode(&point[1..33]);
+                (KTY_EC, CRV_SECP256K1, x, Some(encode(&point[33..])))
+            }
+            PublicKeyRepr::Ed25519(key) => (KTY_OKP, CRV_ED25519, encode(key.as_ref()), None),
+            PublicKeyRepr::Other(..) => return Err(error::unsupported_keytype(v.tag().key_type)),
+            #[cfg(feature = "sr25519")]
+            PublicKeyRepr::Sr25519(_) => return Err(error::unsupported_keytype(v.tag().key_type)),
+        };
+        Ok(Self {
+            kty: kty.to_string(),
+            crv: crv.to_string(),
+            x,
+            y,
+            d: None,
+            kid: Some(v.to_string()),
+        })
+    }
+}
+
+#[cfg(not(feature = "no-export"))]
+impl From<&Keypair> for Jwk {
+    fn from(v: &Keypair) -> Self {
+        let secret = match v {
+            Keypair::EccCompact(keypair) => encode(&keypair.inner.to_bytes()),
+            Keypair::Ed25519(keypair) => encode(keypair.inner.secret.as_bytes()),
+            Keypair::Secp256k1(keypair) => encode(&keypair.inner.to_bytes()),
+            Keypair::Schnorr(keypair) => encode(&keypair.inner.to_bytes()),
+        };
+        Self {
+            d: Some(secret),
+            // Keypairs are never of a key type without a JWK form
+            ..Self::try_from(v.public_key()).expect("jwk public key")
+        }
+    }
+}
+
+fn encode(input: &[u8]) -> String {
+    base64::encode_config(input, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(input: &str) -> error::Result<Vec<u8>> {
+    base64::decode_config(input, base64::URL_SAFE_NO_PAD).map_err(|_| error::invalid_jwk())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Jwk;
+    use crate::{ecc_compact, ed25519, secp256k1, Keypair, Network};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    #[cfg(not(feature = "no-export"))]
+    fn roundtrip() {
+        let keypairs = vec![
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng)),
+        ];
+        for keypair in keypairs {
+            let jwk = Jwk::from_json(&Jwk::from(&keypair).to_json()).expect("jwk");
+            assert_eq!(Some(keypair.public_key().to_string()), jwk.kid);
+            assert_eq!(keypair, jwk.to_keypair(Network::MainNet).expect("keypair"));
+            let public = Jwk::try_from(keypair.public_key()).expect("jwk");
+            assert_eq!(None, public.d);
+            assert_eq!(
+                keypair.public_key(),
+                &public.to_public_key(Network::MainNet).expect("public key")
+            );
+            // The key id pins the network
+            assert!(public.to_public_key(Network::TestNet).is_err());
+        }
+    }
+
+    #[test]
+    fn rfc8037_ed25519() {
+        // The example key from RFC 8037 appendix A
+        let jwk = Jwk::from_json(
+            r#"{"kty":"OKP","crv":"Ed25519",
+                "d":"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A",
+                "x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#,
+        )
+        .expect("jwk");
+        let keypair = jwk.to_keypair(Network::MainNet).expect("keypair");
+        assert!(matches!(keypair, Keypair::Ed25519(_)));
+    }
+}
@@ -0,0 +1,137 @@
+//! A pool of pre-generated ecc_compact keypairs.
+//!
+//! A background thread keeps the pool filled up to a target size so callers
+//! with a tight latency budget, like provisioning endpoints, can take a key
+//! without waiting for key generation.
+use crate::{ecc_compact, Network};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+struct State {
+    keys: VecDeque<ecc_compact::Keypair>,
+    closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    // Signalled when keys are taken or the pool is closed
+    refill: Condvar,
+    target: usize,
+}
+
+pub struct KeyPool {
+    network: Network,
+    shared: Arc<Shared>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl KeyPool {
+    /// Create a pool for the given network and start filling it up to
+    /// `target` keypairs.
+    pub fn new(network: Network, target: usize) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                keys: VecDeque::with_capacity(target),
+                closed: false,
+            }),
+            refill: Condvar::new(),
+            target,
+        });
+        let worker = {
+            let shared = shared.clone();
+            thread::spawn(move || loop {
+                {
+                    let mut state = shared.state.lock().expect("pool lock");
+                    while !state.closed && state.keys.len() >= shared.target {
+                        state = shared.refill.wait(state).expect("pool lock");
+                    }
+                    if state.closed {
+                        break;
+                    }
+                }
+                // Generate without holding the lock so takers are not blocked
+                let keypair = ecc_compact::Keypair::generate_default(network);
+                shared
+                    .state
+                    .lock()
+                    .expect("pool lock")
+                    .keys
+                    .push_back(keypair);
+            })
+        };
+        Self {
+            network,
+            shared,
+            worker: Some(worker),
+        }
+    }
+
+    /// Take a keypair from the pool, generating one on the spot if the pool
+    /// has run dry.
+    pub fn take(&self) -> ecc_compact::Keypair {
+        self.try_take()
+            .unwrap_or_else(|| ecc_compact::Keypair::generate_default(self.network))
+    }
+
+    /// Take a keypair from the pool if one is available.
+    pub fn try_take(&self) -> Option<ecc_compact::Keypair> {
+        let keypair = self
+            .shared
+            .state
+            .lock()
+            .expect("pool lock")
+            .keys
+            .pop_front();
+        self.shared.refill.notify_one();
+        keypair
+    }
+
+    /// The number of keypairs currently in the pool.
+    pub fn len(&self) -> usize {
+        self.shared.state.lock().expect("pool lock").keys.len()
+    }
+
+    /// Whether the pool currently holds no keypairs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for KeyPool {
+    fn drop(&mut self) {
+        self.shared.state.lock().expect("pool lock").closed = true;
+        self.shared.refill.notify_one();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeyPool;
+    use crate::{Network, Sign, Verify};
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn fill_and_take() {
+        let pool = KeyPool::new(Network::MainNet, 4);
+        for _ in 0..100 {
+            if pool.len() == 4 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(4, pool.len());
+        let keypair = pool.try_take().expect("pooled keypair");
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert_ne!(keypair, pool.take());
+    }
+}
@@ -0,0 +1,211 @@
+//! A process wide registry of handlers for key types outside of the ones this
+//! crate implements.
+//!
+//! Keys with a key type in [`KEYTYPE_EXPERIMENTAL_RANGE`] or
+//! [`KEYTYPE_VENDOR_RANGE`] parse as [`KeyType::Other`] and are kept as their
+//! 32 key bytes. Without a handler nothing checks those bytes and verifying
+//! with such a key fails. Registering a [`KeyTypeHandler`] for the key type
+//! makes [`PublicKey::try_from`](crate::PublicKey) reject keys the handler
+//! does not accept and routes verification to the handler, so private
+//! networks can use their own key types without forking this crate.
+//!
+//! ```
+//! use helium_crypto::{error, key_registry::{self, KeyTypeHandler}, VerifyOptions};
+//!
+//! struct AlwaysValid;
+//!
+//! impl KeyTypeHandler for AlwaysValid {
+//!     fn check(&self, _key: &[u8; 32]) -> error::Result {
+//!         Ok(())
+//!     }
+//!
+//!     fn verify(
+//!         &self,
+//!         _key: &[u8; 32],
+//!         _msg: &[u8],
+//!         _signature: &[u8],
+//!         _options: &VerifyOptions,
+//!     ) -> error::Result {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! key_registry::register(0x0b, AlwaysValid).expect("registered");
+//! ```
+use crate::{
+    error, keypair::Prehash, public_key::PUBLIC_KEY_LENGTH, KeyType, VerifyOptions,
+    KEYTYPE_EXPERIMENTAL_RANGE, KEYTYPE_VENDOR_RANGE,
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+/// The key bytes of a key of an external key type, without the tag.
+pub type KeyBytes = [u8; PUBLIC_KEY_LENGTH - 1];
+
+/// Parsing and verification for an external key type.
+pub trait KeyTypeHandler: Send + Sync {
+    /// Check that the given bytes are a valid key of this type. Called
+    /// whenever a key of this type is parsed.
+    fn check(&self, key: &KeyBytes) -> error::Result;
+
+    /// Verify the given message against the given signature.
+    fn verify(
+        &self,
+        key: &KeyBytes,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result;
+
+    /// Verify a signature over a hashed message. Handlers that can only
+    /// verify full messages return `Error::UnsupportedPrehash`.
+    fn verify_digest(
+        &self,
+        _key: &KeyBytes,
+        _digest: Prehash,
+        _signature: &[u8],
+        _options: &VerifyOptions,
+    ) -> error::Result {
+        Err(error::unsupported_prehash())
+    }
+}
+
+static HANDLERS: RwLock<BTreeMap<u8, Arc<dyn KeyTypeHandler>>> = RwLock::new(BTreeMap::new());
+
+/// Register the handler for the given key type value, which has to be in the
+/// experimental or vendor range. A key type can only be registered once.
+pub fn register<H>(key_type: u8, handler: H) -> error::Result
+where
+    H: KeyTypeHandler + 'static,
+{
+    if !KEYTYPE_EXPERIMENTAL_RANGE.contains(&key_type) && !KEYTYPE_VENDOR_RANGE.contains(&key_type)
+    {
+        return Err(error::invalid_keytype(key_type));
+    }
+    let mut handlers = HANDLERS.write().expect("key type registry");
+    if handlers.contains_key(&key_type) {
+        return Err(error::keytype_registered(key_type));
+    }
+    handlers.insert(key_type, Arc::new(handler));
+    Ok(())
+}
+
+/// Remove the handler for the given key type value, returning whether one
+/// was registered.
+pub fn unregister(key_type: u8) -> bool {
+    HANDLERS
+        .write()
+        .expect("key type registry")
+        .remove(&key_type)
+        .is_some()
+}
+
+/// Whether a handler is registered for the given key type value.
+pub fn is_registered(key_type: u8) -> bool {
+    HANDLERS
+        .read()
+        .expect("key type registry")
+        .contains_key(&key_type)
+}
+
+fn handler(key_type: u8) -> Option<Arc<dyn KeyTypeHandler>> {
+    HANDLERS
+        .read()
+        .expect("key type registry")
+        .get(&key_type)
+        .cloned()
+}
+
+/// Checks a parsed key of an external key type. Keys of unregistered key
+/// types pass unchecked.
+pub(crate) fn check(key_type: u8, key: &KeyBytes) -> error::Result {
+    match handler(key_type) {
+        Some(handler) => handler.check(key),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn verify(
+    key_type: u8,
+    key: &KeyBytes,
+    msg: &[u8],
+    signature: &[u8],
+    options: &VerifyOptions,
+) -> error::Result {
+    handler(key_type)
+        .ok_or_else(|| error::unsupported_keytype(KeyType::Other(key_type)))?
+        .verify(key, msg, signature, options)
+}
+
+pub(crate) fn verify_digest(
+    key_type: u8,
+    key: &KeyBytes,
+    digest: Prehash,
+    signature: &[u8],
+    options: &VerifyOptions,
+) -> error::Result {
+    handler(key_type)
+        .ok_or_else(|| error::unsupported_keytype(KeyType::Other(key_type)))?
+        .verify_digest(key, digest, signature, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{register, unregister, KeyBytes, KeyTypeHandler};
+    use crate::{ed25519, error, PublicKey, Sign, Verify, VerifyOptions};
+    use ed25519_dalek::Verifier;
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    /// ed25519 keys under a vendor key type.
+    struct VendorEd25519;
+
+    impl KeyTypeHandler for VendorEd25519 {
+        fn check(&self, key: &KeyBytes) -> error::Result {
+            ed25519_dalek::PublicKey::from_bytes(key)?;
+            Ok(())
+        }
+
+        fn verify(
+            &self,
+            key: &KeyBytes,
+            msg: &[u8],
+            signature: &[u8],
+            _options: &VerifyOptions,
+        ) -> error::Result {
+            let key = ed25519_dalek::PublicKey::from_bytes(key)?;
+            let signature = ed25519_dalek::Signature::try_from(signature)?;
+            Ok(key.verify(msg, &signature)?)
+        }
+    }
+
+    #[test]
+    fn registered_key_type() {
+        let keypair = ed25519::Keypair::generate(crate::Network::MainNet, &mut OsRng);
+        let mut bytes = keypair.public_key.to_vec();
+        bytes[0] = 0x0d;
+        // An unregistered key type parses but can not verify
+        let unregistered = PublicKey::try_from(&bytes[..]).expect("public key");
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(unregistered.verify(b"hello world", &signature).is_err());
+
+        register(0x0d, VendorEd25519).expect("registered");
+        assert!(register(0x0d, VendorEd25519).is_err());
+        assert!(register(0x03, VendorEd25519).is_err());
+        let public_key = PublicKey::try_from(&bytes[..]).expect("public key");
+        assert!(public_key.verify(b"hello world", &signature).is_ok());
+        assert!(public_key.verify(b"hello", &signature).is_err());
+
+        // y = 2 is not on the curve, and is rejected at parse time now
+        let mut invalid = bytes.clone();
+        invalid[1..].copy_from_slice(&[0; 32]);
+        invalid[1] = 2;
+        assert!(PublicKey::try_from(&invalid[..]).is_err());
+
+        assert!(unregister(0x0d));
+        assert!(!unregister(0x0d));
+        assert!(PublicKey::try_from(&invalid[..]).is_ok());
+    }
+}
@@ -0,0 +1,293 @@
+//! Membership sets for large public key allow and deny lists.
+//!
+//! A [`PublicKeySet`] answers membership exactly and stores each key as its
+//! binary form in a sorted list, avoiding per entry allocations and
+//! hashing of base58 strings. A [`BloomFilter`] answers membership
+//! approximately in a fraction of the memory: it never misses a member but
+//! reports a non-member as a member at the configured false positive rate.
+//! Where a false positive matters, use the filter to skip the exact check for
+//! the common case of a key that is not in the set.
+//!
+//! Both serialize to a compact binary form with a leading version byte.
+use crate::{
+    error,
+    public_key::{split_tagged, PublicKeyBytes},
+    PublicKey,
+};
+use sha2::{Digest, Sha256};
+use std::{convert::TryFrom, iter::FromIterator};
+
+const SET_VERSION: u8 = 1;
+const BLOOM_VERSION: u8 = 1;
+/// Length of the bloom filter header: version, hash count and bit count.
+const BLOOM_HEADER_LENGTH: usize = 1 + 1 + 8;
+
+/// An exact set of public keys.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PublicKeySet {
+    keys: Vec<PublicKeyBytes>,
+}
+
+impl PublicKeySet {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a key into the set, returning whether it was not yet in the set.
+    /// Insertion is linear in the size of the set, prefer collecting large
+    /// sets from an iterator.
+    pub fn insert(&mut self, public_key: &PublicKey) -> bool {
+        let bytes = public_key.to_bytes();
+        match self.keys.binary_search(&bytes) {
+            Ok(_) => false,
+            Err(pos) => {
+                self.keys.insert(pos, bytes);
+                true
+            }
+        }
+    }
+
+    /// Remove a key from the set, returning whether it was in the set.
+    pub fn remove(&mut self, public_key: &PublicKey) -> bool {
+        match self.keys.binary_search(&public_key.to_bytes()) {
+            Ok(pos) => {
+                self.keys.remove(pos);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Whether the given key is in the set.
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.keys.binary_search(&public_key.to_bytes()).is_ok()
+    }
+
+    /// Whether the given binary form of a key is in the set. This avoids
+    /// parsing keys from incoming data just to look them up.
+    pub fn contains_bytes(&self, bytes: &[u8]) -> bool {
+        self.keys.binary_search_by(|key| key[..].cmp(bytes)).is_ok()
+    }
+
+    /// The number of keys in the set.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the set holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Build a bloom filter holding all keys in this set with the given false
+    /// positive rate.
+    pub fn to_bloom_filter(&self, false_positive_rate: f64) -> BloomFilter {
+        let mut filter = BloomFilter::new(self.len(), false_positive_rate);
+        for key in &self.keys {
+            filter.insert_bytes(key);
+        }
+        filter
+    }
+
+    /// Convert the set to its binary form: the version byte followed by the
+    /// sorted binary keys.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result =
+            Vec::with_capacity(1 + self.keys.iter().map(|key| key.len()).sum::<usize>());
+        result.push(SET_VERSION);
+        for key in &self.keys {
+            result.extend_from_slice(key);
+        }
+        result
+    }
+}
+
+impl<'a> FromIterator<&'a PublicKey> for PublicKeySet {
+    fn from_iter<I: IntoIterator<Item = &'a PublicKey>>(iter: I) -> Self {
+        let mut keys: Vec<_> = iter.into_iter().map(PublicKey::to_bytes).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        Self { keys }
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKeySet {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        match input.split_first() {
+            Some((&SET_VERSION, mut rest)) => {
+                let mut keys = Vec::new();
+                while !rest.is_empty() {
+                    let (key, tail) = split_tagged(rest).map_err(|_| error::invalid_key_set())?;
+                    keys.push(PublicKeyBytes::from_slice(key));
+                    rest = tail;
+                }
+                // Lookups rely on the keys being sorted and unique
+                if keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+                    return Err(error::invalid_key_set());
+                }
+                Ok(Self { keys })
+            }
+            _ => Err(error::invalid_key_set()),
+        }
+    }
+}
+
+/// An approximate set of public keys.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u8>,
+    num_bits: u64,
+    num_hashes: u8,
+}
+
+impl BloomFilter {
+    /// Create an empty filter sized for the given number of keys at the given
+    /// false positive rate.
+    pub fn new(expected_keys: usize, false_positive_rate: f64) -> Self {
+        let n = expected_keys.max(1) as f64;
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 1.0);
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = ((-n * rate.ln()) / (ln2 * ln2)).ceil().max(8.0) as u64;
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().clamp(1.0, 32.0) as u8;
+        Self {
+            bits: vec![0u8; byte_len(num_bits).expect("bloom filter size")],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    /// Add a key to the filter.
+    pub fn insert(&mut self, public_key: &PublicKey) {
+        self.insert_bytes(&public_key.to_bytes())
+    }
+
+    /// Whether the given key may be in the filter. A `false` result is exact.
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.contains_bytes(&public_key.to_bytes())
+    }
+
+    /// Whether the given binary form of a key may be in the filter.
+    pub fn contains_bytes(&self, bytes: &[u8]) -> bool {
+        self.bit_indexes(bytes)
+            .all(|bit| self.bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0)
+    }
+
+    /// Convert the filter to its binary form: the version byte, the number of
+    /// hashes, the number of bits as a big endian u64 and the bits.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(BLOOM_HEADER_LENGTH + self.bits.len());
+        result.push(BLOOM_VERSION);
+        result.push(self.num_hashes);
+        result.extend_from_slice(&self.num_bits.to_be_bytes());
+        result.extend_from_slice(&self.bits);
+        result
+    }
+
+    fn insert_bytes(&mut self, bytes: &[u8]) {
+        for bit in self.bit_indexes(bytes) {
+            self.bits[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+    }
+
+    /// Bit indexes for a key using double hashing over a SHA-256 digest of the
+    /// key. Hashing the key, rather than using its bytes directly, keeps
+    /// crafted keys from clustering on a few bits.
+    fn bit_indexes(&self, bytes: &[u8]) -> impl Iterator<Item = u64> {
+        let digest = Sha256::digest(bytes);
+        let mut h1 = [0u8; 8];
+        let mut h2 = [0u8; 8];
+        h1.copy_from_slice(&digest[..8]);
+        h2.copy_from_slice(&digest[8..16]);
+        let h1 = u64::from_le_bytes(h1);
+        let h2 = u64::from_le_bytes(h2);
+        let num_bits = self.num_bits;
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+}
+
+/// The number of bytes holding the given number of bits, or `None` if it
+/// does not fit a `usize`. The bit count of a binary filter is read from the
+/// input, so it can be anything.
+fn byte_len(num_bits: u64) -> Option<usize> {
+    let bytes = num_bits / 8 + u64::from(num_bits % 8 != 0);
+    usize::try_from(bytes).ok()
+}
+
+impl TryFrom<&[u8]> for BloomFilter {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() < BLOOM_HEADER_LENGTH || input[0] != BLOOM_VERSION || input[1] == 0 {
+            return Err(error::invalid_key_set());
+        }
+        let mut num_bits = [0u8; 8];
+        num_bits.copy_from_slice(&input[2..BLOOM_HEADER_LENGTH]);
+        let num_bits = u64::from_be_bytes(num_bits);
+        let bits = &input[BLOOM_HEADER_LENGTH..];
+        if num_bits == 0 || byte_len(num_bits) != Some(bits.len()) {
+            return Err(error::invalid_key_set());
+        }
+        Ok(Self {
+            bits: bits.to_vec(),
+            num_bits,
+            num_hashes: input[1],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BloomFilter, PublicKeySet};
+    use crate::{ed25519, Network, PublicKey};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    fn public_keys(count: usize) -> Vec<PublicKey> {
+        (0..count)
+            .map(|_| ed25519::Keypair::generate(Network::MainNet, &mut OsRng).public_key)
+            .collect()
+    }
+
+    #[test]
+    fn exact_set() {
+        let members = public_keys(64);
+        let others = public_keys(16);
+        let mut set: PublicKeySet = members.iter().collect();
+        assert_eq!(64, set.len());
+        assert!(members.iter().all(|key| set.contains(key)));
+        assert!(members
+            .iter()
+            .all(|key| set.contains_bytes(&key.to_bytes())));
+        assert!(!others.iter().any(|key| set.contains(key)));
+        assert!(!set.insert(&members[0]));
+        assert!(set.insert(&others[0]));
+        assert!(set.remove(&others[0]));
+        let decoded = PublicKeySet::try_from(&set.to_vec()[..]).expect("key set");
+        assert_eq!(set, decoded);
+    }
+
+    #[test]
+    fn bloom_filter() {
+        let members = public_keys(256);
+        let set: PublicKeySet = members.iter().collect();
+        let filter = set.to_bloom_filter(0.01);
+        assert!(members.iter().all(|key| filter.contains(key)));
+        let false_positives = public_keys(1000)
+            .iter()
+            .filter(|key| filter.contains(key))
+            .count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+        let decoded = BloomFilter::try_from(&filter.to_vec()[..]).expect("bloom filter");
+        assert_eq!(filter, decoded);
+    }
+
+    #[test]
+    fn bloom_filter_bit_counts() {
+        let mut bytes = BloomFilter::new(1, 0.5).to_vec();
+        bytes[2..10].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(BloomFilter::try_from(&bytes[..]).is_err());
+        bytes[2..10].copy_from_slice(&0u64.to_be_bytes());
+        assert!(BloomFilter::try_from(&bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+//! A password protected file format for keypairs at rest.
+//!
+//! The keypair is encrypted with XChaCha20-Poly1305 under a key derived from
+//! the password with Argon2id. A file is laid out as:
+//!
+//! ```text
+//! magic "HKEY" | version (1) | kdf (1 = argon2id)
+//! | memory cost (u32, KiB) | time cost (u32) | parallelism (u32)
+//! | salt (16) | nonce (24) | encrypted keypair bytes | tag (16)
+//! ```
+//!
+//! Integers are big endian. Everything before the ciphertext is authenticated
+//! as associated data, so the KDF parameters can not be weakened without the
+//! file failing to decrypt. The plaintext is the binary form of the keypair,
+//! starting with its key tag.
+use crate::{entropy::EntropySource, error, Keypair};
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use std::convert::{TryFrom, TryInto};
+use zeroize::Zeroize;
+
+const MAGIC: &[u8; 4] = b"HKEY";
+const VERSION: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+const HEADER_LENGTH: usize = MAGIC.len() + 2 + 12 + SALT_LENGTH + NONCE_LENGTH;
+
+/// Upper bound on the memory cost, 1 GiB, accepted when reading a file, so a
+/// crafted file can not make decryption allocate unbounded memory.
+pub const MAX_MEMORY_COST: u32 = 1 << 20;
+/// Upper bound on the time cost accepted when reading a file.
+pub const MAX_TIME_COST: u32 = 16;
+/// Upper bound on the parallelism accepted when reading a file. Each lane
+/// runs on the calling thread, so it bounds decryption time like the time
+/// cost does.
+pub const MAX_PARALLELISM: u32 = 16;
+
+/// Argon2id cost parameters.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Params {
+    /// Memory cost in KiB
+    pub memory_cost: u32,
+    /// Number of passes over the memory
+    pub time_cost: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for Params {
+    /// The OWASP recommended minimum of 19 MiB of memory and two passes.
+    fn default() -> Self {
+        Self {
+            memory_cost: 19 * 1024,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl Params {
+    fn derive_key(&self, password: &[u8], salt: &[u8]) -> error::Result<[u8; KEY_LENGTH]> {
+        if self.memory_cost > MAX_MEMORY_COST
+            || self.time_cost > MAX_TIME_COST
+            || self.parallelism > MAX_PARALLELISM
+        {
+            return Err(error::invalid_keyfile());
+        }
+        let params = argon2::Params::new(
+            self.memory_cost,
+            self.time_cost,
+            self.parallelism,
+            Some(KEY_LENGTH),
+        )
+        .map_err(|_| error::invalid_keyfile())?;
+        let mut key = [0u8; KEY_LENGTH];
+        Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+            .hash_password_into(password, salt, &mut key)
+            .map_err(|_| error::invalid_keyfile())?;
+        Ok(key)
+    }
+}
+
+/// Encrypt the given keypair with the given password. A fresh salt and nonce
+/// are drawn from the entropy source. Fails with `DecodeError::Keyfile` if
+/// the parameters are out of range for Argon2id or above the bounds for
+/// reading a file.
+pub fn encrypt<E>(
+    keypair: &Keypair,
+    password: &[u8],
+    params: &Params,
+    entropy: &mut E,
+) -> error::Result<Vec<u8>>
+where
+    E: EntropySource + ?Sized,
+{
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    entropy.fill_entropy(&mut salt)?;
+    entropy.fill_entropy(&mut nonce)?;
+
+    let mut result = Vec::with_capacity(HEADER_LENGTH + 128);
+    result.extend_from_slice(MAGIC);
+    result.extend_from_slice(&[VERSION, KDF_ARGON2ID]);
+    result.extend_from_slice(&params.memory_cost.to_be_bytes());
+    result.extend_from_slice(&params.time_cost.to_be_bytes());
+    result.extend_from_slice(&params.parallelism.to_be_bytes());
+    result.extend_from_slice(&salt);
+    result.extend_from_slice(&nonce);
+
+    let mut key = params.derive_key(password, &salt)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    key[..].zeroize();
+    let mut plaintext = keypair.to_vec();
+    let ciphertext = cipher.encrypt(
+        XNonce::from_slice(&nonce),
+        Payload {
+            msg: &plaintext,
+            aad: &result,
+        },
+    );
+    plaintext[..].zeroize();
+    result.extend_from_slice(&ciphertext.map_err(|_| error::invalid_keyfile())?);
+    Ok(result)
+}
+
+/// Decrypt a keypair file with the given password. Fails with
+/// `Error::KeyfilePassword` if the password is wrong or the file has been
+/// tampered with, which can not be told apart.
+pub fn decrypt(input: &[u8], password: &[u8]) -> error::Result<Keypair> {
+    if input.len() < HEADER_LENGTH || &input[..MAGIC.len()] != MAGIC {
+        return Err(error::invalid_keyfile());
+    }
+    let (header, ciphertext) = input.split_at(HEADER_LENGTH);
+    if header[4] != VERSION || header[5] != KDF_ARGON2ID {
+        return Err(error::invalid_keyfile());
+    }
+    let u32_at = |pos: usize| u32::from_be_bytes(header[pos..pos + 4].try_into().expect("u32"));
+    let params = Params {
+        memory_cost: u32_at(6),
+        time_cost: u32_at(10),
+        parallelism: u32_at(14),
+    };
+    let salt = &header[18..18 + SALT_LENGTH];
+    let nonce = &header[18 + SALT_LENGTH..];
+
+    let mut key = params.derive_key(password, salt)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    key[..].zeroize();
+    let mut plaintext = cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| error::keyfile_password())?;
+    let keypair = if plaintext.is_empty() {
+        Err(error::invalid_keyfile())
+    } else {
+        Keypair::try_from(&plaintext[..])
+    };
+    plaintext[..].zeroize();
+    keypair
+}
+
+/// A keypair in the password protected format, for structures that are
+/// serialized with serde. [`Keypair`] deliberately does not implement
+/// `Serialize`, so a secret key can not end up in plaintext in a serialized
+/// structure by accident; such structures hold an `EncryptedKeypair` instead.
+/// Human readable formats get the base64 encoded file, binary formats the
+/// file bytes.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EncryptedKeypair(Vec<u8>);
+
+#[cfg(feature = "serde")]
+impl EncryptedKeypair {
+    /// Encrypt the given keypair with the given password, see [`encrypt`].
+    pub fn encrypt<E>(
+        keypair: &Keypair,
+        password: &[u8],
+        params: &Params,
+        entropy: &mut E,
+    ) -> error::Result<Self>
+    where
+        E: EntropySource + ?Sized,
+    {
+        encrypt(keypair, password, params, entropy).map(Self)
+    }
+
+    /// Decrypt the keypair with the given password, see [`decrypt`].
+    pub fn decrypt(&self, password: &[u8]) -> error::Result<Keypair> {
+        decrypt(&self.0, password)
+    }
+
+    /// The encrypted file bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<Vec<u8>> for EncryptedKeypair {
+    type Error = error::Error;
+    /// Wraps encrypted file bytes, checking only the header since the
+    /// contents can't be checked without the password.
+    fn try_from(input: Vec<u8>) -> error::Result<Self> {
+        if input.len() <= HEADER_LENGTH
+            || &input[..MAGIC.len()] != MAGIC
+            || input[4] != VERSION
+            || input[5] != KDF_ARGON2ID
+        {
+            return Err(error::invalid_keyfile());
+        }
+        Ok(Self(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, encrypt, Params};
+    use crate::{ecc_compact, ed25519, error, secp256k1, Keypair, Network};
+    use rand::rngs::OsRng;
+
+    // Cheap parameters to keep the tests fast
+    const PARAMS: Params = Params {
+        memory_cost: 64,
+        time_cost: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn roundtrip() {
+        for keypair in vec![
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Ed25519(ed25519::Keypair::generate(Network::TestNet, &mut OsRng)),
+            Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng)),
+        ] {
+            let encrypted = encrypt(&keypair, b"password", &PARAMS, &mut OsRng).expect("encrypt");
+            assert_eq!(keypair, decrypt(&encrypted, b"password").expect("decrypt"));
+        }
+    }
+
+    #[test]
+    fn wrong_password() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let encrypted = encrypt(&keypair, b"password", &PARAMS, &mut OsRng).expect("encrypt");
+        assert!(matches!(
+            decrypt(&encrypted, b"passw0rd"),
+            Err(error::Error::KeyfilePassword)
+        ));
+    }
+
+    #[test]
+    fn tampered_params() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let mut encrypted = encrypt(&keypair, b"password", &PARAMS, &mut OsRng).expect("encrypt");
+        // Changing the time cost is caught by the authenticated header
+        encrypted[13] = 2;
+        assert!(decrypt(&encrypted, b"password").is_err());
+        encrypted[4] = 2;
+        assert!(matches!(
+            decrypt(&encrypted, b"password"),
+            Err(error::Error::Decode(error::DecodeError::Keyfile))
+        ));
+    }
+
+    #[test]
+    fn unbounded_params() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let encrypted = encrypt(&keypair, b"password", &PARAMS, &mut OsRng).expect("encrypt");
+        // Memory cost, time cost and parallelism are rejected before any work
+        // is done for them
+        for offset in &[6, 10, 14] {
+            let mut tampered = encrypted.clone();
+            tampered[*offset..*offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+            assert!(matches!(
+                decrypt(&tampered, b"password"),
+                Err(error::Error::Decode(error::DecodeError::Keyfile))
+            ));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use super::EncryptedKeypair;
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let encrypted =
+            EncryptedKeypair::encrypt(&keypair, b"password", &PARAMS, &mut OsRng).expect("encrypt");
+        let json = serde_json::to_string(&encrypted).expect("json");
+        let decoded: EncryptedKeypair = serde_json::from_str(&json).expect("encrypted keypair");
+        assert_eq!(keypair, decoded.decrypt(b"password").expect("decrypt"));
+        assert!(serde_json::from_str::<EncryptedKeypair>("\"SEtFWQ==\"").is_err());
+    }
+}
@@ -1,33 +1,494 @@
-use crate::{error, public_key, Network};
+use crate::{error, public_key, KeyType, Network};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+use sha2::{digest::generic_array::GenericArray, Digest, Sha256, Sha512};
 
 /// Defines a trait for signing messages. Rather than the signature::Signer
 /// trait which deals with exact signature sizes, this trait allows for variable
 /// sized signatures, since the ECDSA signature is DER encoded.
 pub trait Sign {
     /// Sign the given message
+    #[cfg(feature = "alloc")]
     fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>>;
+
+    /// Sign the given message into the given output slice and return the
+    /// length of the signature. The default implementation falls back to
+    /// `sign`; the software keypairs in this crate implement it without
+    /// allocating.
+    #[cfg(feature = "alloc")]
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        let signature = self.sign(msg)?;
+        copy_signature(&signature, output)
+    }
+
+    /// Sign the given message into the given output slice and return the
+    /// length of the signature. Without the `alloc` feature this is the one
+    /// method signers implement.
+    #[cfg(not(feature = "alloc"))]
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize>;
+
+    /// Sign the given message into a fixed size signature buffer.
+    fn sign_buf(&self, msg: &[u8]) -> error::Result<SignatureBuf> {
+        let mut bytes = [0u8; MAX_SIGNATURE_LENGTH];
+        let len = self.sign_into(msg, &mut bytes)?;
+        Ok(SignatureBuf { bytes, len })
+    }
+
+    /// Sign the given message into a fixed capacity vector that lives on the
+    /// stack.
+    #[cfg(feature = "heapless")]
+    fn sign_heapless(&self, msg: &[u8]) -> error::Result<heapless::Vec<u8, MAX_SIGNATURE_LENGTH>> {
+        let mut output = [0u8; MAX_SIGNATURE_LENGTH];
+        let len = self.sign_into(msg, &mut output)?;
+        Ok(heapless::Vec::from_slice(&output[..len]).expect("signature length"))
+    }
+
+    /// Sign the given message for the given domain, see [`context`]. The
+    /// signature only verifies with
+    /// [`Verify::verify_with_context`](crate::Verify::verify_with_context) for
+    /// the same domain.
+    ///
+    /// [`context`]: crate::context
+    #[cfg(feature = "alloc")]
+    fn sign_with_context(&self, domain: &[u8], msg: &[u8]) -> error::Result<Vec<u8>> {
+        self.sign(&crate::context::message(domain, msg)?)
+    }
+
+    /// Sign a message that has been hashed into the given digest. See
+    /// [`Prehash`] for the digest each key type expects. Signers that can only
+    /// sign full messages return `Error::UnsupportedPrehash`.
+    #[cfg(feature = "alloc")]
+    fn sign_digest(&self, _digest: Prehash) -> error::Result<Vec<u8>> {
+        Err(error::unsupported_prehash())
+    }
+
+    /// Box this signer, so signers of different types, like software keys
+    /// and hardware backed keys, can be held together.
+    #[cfg(feature = "alloc")]
+    fn into_boxed_signer(self) -> BoxedSigner
+    where
+        Self: Sized + Send + Sync + 'static,
+    {
+        Box::new(self)
+    }
+}
+
+/// A signer of any type. [`Sign`] is object safe, so signers can also be used
+/// as `&dyn Sign` or shared as `Arc<dyn Sign + Send + Sync>`.
+#[cfg(feature = "alloc")]
+pub type BoxedSigner = Box<dyn Sign + Send + Sync>;
+
+macro_rules! forward_sign {
+    ($($pointer:ty),+) => {
+        $(impl<S: Sign + ?Sized> Sign for $pointer {
+            #[cfg(feature = "alloc")]
+            fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+                (**self).sign(msg)
+            }
+
+            fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+                (**self).sign_into(msg, output)
+            }
+
+            #[cfg(feature = "alloc")]
+            fn sign_digest(&self, digest: Prehash) -> error::Result<Vec<u8>> {
+                (**self).sign_digest(digest)
+            }
+        })+
+    };
+}
+
+forward_sign!(&S);
+#[cfg(feature = "alloc")]
+forward_sign!(alloc::sync::Arc<S>, Box<S>);
+
+/// A message hashed incrementally for signing or verifying without buffering
+/// it. ECDSA keys sign the SHA-256 digest of a message anyway, so their
+/// prehashed signatures are regular signatures. ed25519 keys sign the SHA-512
+/// digest using Ed25519ph from RFC 8032 with an empty context, and those
+/// signatures only verify as prehashed. BIP340 keys hash the message together
+/// with the nonce and have no prehashed form.
+///
+/// A digest that has been computed elsewhere can only be verified against,
+/// see [`Prehash::from_digest`].
+#[derive(Clone)]
+pub enum Prehash {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    /// A finished SHA-256 digest
+    Sha256Digest([u8; 32]),
+    /// A finished SHA-512 digest
+    Sha512Digest([u8; 64]),
+}
+
+impl Prehash {
+    /// Start hashing a message for signing with the given key type.
+    pub fn new(key_type: KeyType) -> error::Result<Self> {
+        match key_type {
+            KeyType::EccCompact | KeyType::Secp256k1 | KeyType::Secp256r1 => {
+                Ok(Self::Sha256(Sha256::new()))
+            }
+            KeyType::Ed25519 => Ok(Self::Sha512(Sha512::new())),
+            KeyType::Schnorr
+            | KeyType::Multisig
+            | KeyType::P384
+            | KeyType::Sr25519
+            | KeyType::Rsa
+            | KeyType::Other(_) => Err(error::unsupported_keytype(key_type)),
+        }
+    }
+
+    /// Wrap the finished digest of a message for verifying with
+    /// [`Verify::verify_digest`](crate::Verify::verify_digest): the 32 byte
+    /// SHA-256 digest for ECDSA keys or the 64 byte SHA-512 digest for
+    /// ed25519 keys. ECDSA signing derives its nonce with the hasher itself,
+    /// so finished digests can not be signed.
+    pub fn from_digest(digest: &[u8]) -> error::Result<Self> {
+        let mut sha256 = [0u8; 32];
+        let mut sha512 = [0u8; 64];
+        match digest.len() {
+            32 => {
+                sha256.copy_from_slice(digest);
+                Ok(Self::Sha256Digest(sha256))
+            }
+            64 => {
+                sha512.copy_from_slice(digest);
+                Ok(Self::Sha512Digest(sha512))
+            }
+            len => Err(error::invalid_length(32, len)),
+        }
+    }
+
+    /// Add the next part of the message. A finished digest is left as is.
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Sha512(hasher) => hasher.update(data),
+            Self::Sha256Digest(_) | Self::Sha512Digest(_) => (),
+        }
+    }
 }
 
-/// Abstract keypair definition
-pub struct Keypair<C> {
+impl From<Sha256> for Prehash {
+    fn from(v: Sha256) -> Self {
+        Self::Sha256(v)
+    }
+}
+
+impl From<Sha512> for Prehash {
+    fn from(v: Sha512) -> Self {
+        Self::Sha512(v)
+    }
+}
+
+/// A SHA-256 digest computed elsewhere, posing as the hasher that produced
+/// it so ECDSA verifiers that take a hasher can verify against it. It is only
+/// ever finalized, data fed to it is ignored.
+#[derive(Clone, Default)]
+pub(crate) struct FixedSha256([u8; 32]);
+
+impl FixedSha256 {
+    pub(crate) fn new(digest: &[u8; 32]) -> Self {
+        Self(*digest)
+    }
+}
+
+impl sha2::digest::Update for FixedSha256 {
+    fn update(&mut self, _data: impl AsRef<[u8]>) {}
+}
+
+impl sha2::digest::FixedOutput for FixedSha256 {
+    type OutputSize = sha2::digest::consts::U32;
+
+    fn finalize_into(self, out: &mut GenericArray<u8, Self::OutputSize>) {
+        out.copy_from_slice(&self.0);
+    }
+
+    fn finalize_into_reset(&mut self, out: &mut GenericArray<u8, Self::OutputSize>) {
+        out.copy_from_slice(&self.0);
+    }
+}
+
+impl sha2::digest::Reset for FixedSha256 {
+    fn reset(&mut self) {}
+}
+
+/// The future returned by [`AsyncSign::sign_async`].
+#[cfg(feature = "async")]
+pub type SignFuture<'a> =
+    core::pin::Pin<Box<dyn core::future::Future<Output = error::Result<Vec<u8>>> + Send + 'a>>;
+
+/// Defines a trait for signing messages asynchronously, for signers that wait
+/// on a network or a device and can not reasonably block in [`Sign`]. The
+/// software keypairs in this crate implement it by signing in place.
+#[cfg(feature = "async")]
+pub trait AsyncSign {
+    /// Sign the given message
+    fn sign_async<'a>(&'a self, msg: &'a [u8]) -> SignFuture<'a>;
+}
+
+/// Implements `AsyncSign` for keypairs that sign in memory, where signing is
+/// cheap enough to do while polling the future.
+#[cfg(feature = "async")]
+macro_rules! impl_local_async_sign {
+    ($($keypair:ty),*) => {
+        $(impl AsyncSign for $keypair {
+            fn sign_async<'a>(&'a self, msg: &'a [u8]) -> SignFuture<'a> {
+                Box::pin(core::future::ready(Sign::sign(self, msg)))
+            }
+        })*
+    };
+}
+
+#[cfg(feature = "async")]
+impl_local_async_sign!(
+    crate::ecc_compact::Keypair,
+    crate::ed25519::Keypair,
+    crate::schnorr::Keypair,
+    crate::secp256k1::Keypair,
+    crate::Keypair
+);
+
+/// The maximum length of a signature for any of the supported key types other
+/// than RSA. DER encoded P-384 ECDSA signatures are the longest at 104 bytes.
+/// RSA signatures are at least 256 bytes and only come as a `Vec`.
+pub const MAX_SIGNATURE_LENGTH: usize = 104;
+
+/// A signature held in a fixed size buffer large enough for any supported key
+/// type other than RSA, so signing does not need to allocate.
+#[derive(Clone, Copy)]
+pub struct SignatureBuf {
+    bytes: [u8; MAX_SIGNATURE_LENGTH],
+    len: usize,
+}
+
+impl SignatureBuf {
+    /// The length of the signature
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The signature bytes
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl AsRef<[u8]> for SignatureBuf {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl core::ops::Deref for SignatureBuf {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl PartialEq for SignatureBuf {
+    fn eq(&self, other: &Self) -> bool {
+        use subtle::ConstantTimeEq;
+        self.as_slice().ct_eq(other.as_slice()).into()
+    }
+}
+
+impl core::fmt::Debug for SignatureBuf {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
+        f.debug_tuple("SignatureBuf")
+            .field(&self.as_slice())
+            .finish()
+    }
+}
+
+/// Copies a signature into the given output slice, returning an error if the
+/// output is too small to hold it.
+pub(crate) fn copy_signature(signature: &[u8], output: &mut [u8]) -> error::Result<usize> {
+    let len = signature.len();
+    if output.len() < len {
+        return Err(error::buffer_too_small(len));
+    }
+    output[..len].copy_from_slice(signature);
+    Ok(len)
+}
+
+/// Abstract keypair definition. The secret key is held by the curve library
+/// types, which zeroize it on drop, in a [`Secret`](crate::secret::Secret)
+/// for software keys. Key types whose public keys do not fit a
+/// [`public_key::PublicKey`], like P-384, bring their own public key type.
+pub struct Keypair<C, P = public_key::PublicKey> {
     /// The network this keypair is valid for
     pub network: Network,
     /// The public key for this keypair
-    pub public_key: public_key::PublicKey,
+    pub public_key: P,
     pub(crate) inner: C,
 }
 
-impl<C> PartialEq for Keypair<C> {
+impl<C, P: PartialEq> PartialEq for Keypair<C, P> {
     fn eq(&self, other: &Self) -> bool {
         self.network == other.network && self.public_key == other.public_key
     }
 }
 
-impl<C> std::fmt::Debug for Keypair<C> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+impl<C> Keypair<C> {
+    /// The network this keypair is valid for.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// The same keypair tagged for the given network.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self.public_key.network = network;
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<C> Keypair<C>
+where
+    Self: Backend,
+{
+    /// Replaces this keypair with the given one, returning a rotation
+    /// statement signed by the current and the new key. The replaced secret
+    /// is zeroized when it is dropped.
+    pub(crate) fn rotate(
+        &mut self,
+        new: Self,
+        timestamp: u64,
+    ) -> error::Result<crate::rotation::Rotation> {
+        let rotation = crate::rotation::Rotation::sign(self, &new, timestamp)?;
+        *self = new;
+        Ok(rotation)
+    }
+}
+
+/// A place a secret key is kept and used to sign, like memory, a secure
+/// element or a remote signing service. Every keypair in this crate is a
+/// backend, so applications can pick the storage at runtime and hold any of
+/// them as a [`BackendKeypair`].
+pub trait Backend: Sign + Send + Sync {
+    /// The public key for the secret key held by this backend
+    fn public_key(&self) -> &public_key::PublicKey;
+}
+
+impl<C> Backend for Keypair<C>
+where
+    Self: Sign + Send + Sync,
+{
+    fn public_key(&self) -> &public_key::PublicKey {
+        &self.public_key
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<B: Backend + ?Sized> Backend for Box<B> {
+    fn public_key(&self) -> &public_key::PublicKey {
+        (**self).public_key()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<B: Backend + ?Sized> Backend for alloc::sync::Arc<B> {
+    fn public_key(&self) -> &public_key::PublicKey {
+        (**self).public_key()
+    }
+}
+
+/// A keypair backed by any [`Backend`].
+#[cfg(feature = "alloc")]
+pub type BackendKeypair = Keypair<Box<dyn Backend>>;
+
+#[cfg(feature = "alloc")]
+impl Keypair<Box<dyn Backend>> {
+    /// Wrap the given backend, taking the network and public key from it.
+    pub fn from_backend<B: Backend + 'static>(backend: B) -> Self {
+        let public_key = backend.public_key().clone();
+        Self {
+            network: public_key.network,
+            public_key,
+            inner: Box::new(backend),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Sign for Keypair<Box<dyn Backend>> {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        self.inner.sign(msg)
+    }
+
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        self.inner.sign_into(msg, output)
+    }
+
+    fn sign_digest(&self, digest: Prehash) -> error::Result<Vec<u8>> {
+        self.inner.sign_digest(digest)
+    }
+}
+
+/// Keypairs never include their secret in `Debug` output. Use
+/// [`DebugSecret`] in tests to include it.
+impl<C> core::fmt::Debug for Keypair<C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
         f.debug_struct("Keypair")
-            .field("network", &self.network)
+            .field("tag", &self.public_key.tag())
             .field("public", &self.public_key)
+            .field("secret", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+/// Keypairs display as the b58 address of their public key.
+impl<C, P: core::fmt::Display> core::fmt::Display for Keypair<C, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
+        core::fmt::Display::fmt(&self.public_key, f)
+    }
+}
+
+/// Exposes the secret of a keypair in `Debug` output. This is only available
+/// in tests of this crate and with the `test-utils` feature, keeping it out
+/// of release builds.
+#[cfg(any(test, feature = "test-utils"))]
+pub trait DebugSecret {
+    /// The raw secret key bytes
+    fn secret_bytes(&self) -> Vec<u8>;
+
+    /// Returns a value whose `Debug` output includes the hex encoded secret.
+    fn debug_secret(&self) -> WithSecret<'_, Self>
+    where
+        Self: Sized,
+    {
+        WithSecret(self)
+    }
+}
+
+/// `Debug` output of a keypair including its secret, see [`DebugSecret`].
+#[cfg(any(test, feature = "test-utils"))]
+pub struct WithSecret<'a, K>(&'a K);
+
+#[cfg(any(test, feature = "test-utils"))]
+impl<C> core::fmt::Debug for WithSecret<'_, Keypair<C>>
+where
+    Keypair<C>: DebugSecret,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
+        use alloc::{format, string::String};
+        let secret: String = self
+            .0
+            .secret_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        f.debug_struct("Keypair")
+            .field("tag", &self.0.public_key.tag())
+            .field("public", &self.0.public_key)
+            .field("secret", &secret)
             .finish()
     }
 }
@@ -0,0 +1,418 @@
+//! Ethereum [Web3 Secret Storage](https://ethereum.org/en/developers/docs/data-structures-and-encoding/web3-secret-storage/)
+//! (keystore v3) files for secp256k1 keypairs.
+//!
+//! The secret key is encrypted with AES-128-CTR under the first half of a key
+//! derived from the password with scrypt or PBKDF2-HMAC-SHA256. The second
+//! half authenticates the ciphertext with a Keccak-256 MAC.
+use crate::{entropy::EntropySource, error, secp256k1, Network};
+use aes::Aes128;
+use ctr::cipher::{generic_array::GenericArray, NewCipher, StreamCipher};
+use hmac::Hmac;
+use serde_crate::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use std::convert::TryFrom;
+use zeroize::Zeroize;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+const VERSION: u32 = 3;
+const CIPHER: &str = "aes-128-ctr";
+const KDF_SCRYPT: &str = "scrypt";
+const KDF_PBKDF2: &str = "pbkdf2";
+const PRF_HMAC_SHA256: &str = "hmac-sha256";
+const DKLEN: usize = 32;
+const SECRET_LENGTH: usize = 32;
+
+/// Upper bound on the scrypt memory requirement, `128 * r * n` bytes, that is
+/// accepted when reading a keystore.
+pub const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+/// Upper bound on the scrypt parallelism `p` accepted when reading a
+/// keystore. scrypt runs its memory hard function `p` times, so `p` bounds the
+/// time a keystore can make reading it take.
+pub const MAX_SCRYPT_PARALLELISM: u32 = 16;
+/// Upper bound on the PBKDF2 iteration count accepted when reading a keystore.
+pub const MAX_PBKDF2_ROUNDS: u32 = 10_000_000;
+
+/// The key derivation function and its parameters.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Kdf {
+    /// scrypt with a cost of `2^log_n`
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    /// PBKDF2-HMAC-SHA256 with the given number of rounds
+    Pbkdf2 { c: u32 },
+}
+
+impl Default for Kdf {
+    /// The scrypt parameters used by geth.
+    fn default() -> Self {
+        Self::Scrypt {
+            log_n: 18,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+impl Kdf {
+    fn derive_key(&self, password: &[u8], salt: &[u8]) -> error::Result<[u8; DKLEN]> {
+        let mut key = [0u8; DKLEN];
+        match *self {
+            Self::Scrypt { log_n, r, p } => {
+                if log_n >= 64
+                    || p == 0
+                    || p > MAX_SCRYPT_PARALLELISM
+                    || (128 * r as u64).saturating_mul(1 << log_n) > MAX_SCRYPT_MEMORY
+                {
+                    return Err(error::invalid_keystore());
+                }
+                let params =
+                    scrypt::Params::new(log_n, r, p).map_err(|_| error::invalid_keystore())?;
+                scrypt::scrypt(password, salt, &params, &mut key)
+                    .map_err(|_| error::invalid_keystore())?;
+            }
+            Self::Pbkdf2 { c } => {
+                if c == 0 || c > MAX_PBKDF2_ROUNDS {
+                    return Err(error::invalid_keystore());
+                }
+                pbkdf2::pbkdf2::<Hmac<Sha256>>(password, salt, c, &mut key);
+            }
+        }
+        Ok(key)
+    }
+
+    fn params(&self, salt: &[u8]) -> (&'static str, Value) {
+        match *self {
+            Self::Scrypt { log_n, r, p } => (
+                KDF_SCRYPT,
+                json!({
+                    "dklen": DKLEN,
+                    "n": 1u64 << log_n,
+                    "p": p,
+                    "r": r,
+                    "salt": hex_encode(salt),
+                }),
+            ),
+            Self::Pbkdf2 { c } => (
+                KDF_PBKDF2,
+                json!({
+                    "c": c,
+                    "dklen": DKLEN,
+                    "prf": PRF_HMAC_SHA256,
+                    "salt": hex_encode(salt),
+                }),
+            ),
+        }
+    }
+
+    fn from_params(kdf: &str, params: &Value) -> error::Result<(Self, Vec<u8>)> {
+        let field = |name: &str| params.get(name).ok_or_else(error::invalid_keystore);
+        let number = |name: &str| {
+            field(name)?
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(error::invalid_keystore)
+        };
+        if number("dklen")? as usize != DKLEN {
+            return Err(error::invalid_keystore());
+        }
+        let salt = hex_decode(
+            field("salt")?
+                .as_str()
+                .ok_or_else(error::invalid_keystore)?,
+        )?;
+        let kdf = match kdf {
+            KDF_SCRYPT => {
+                let n = field("n")?.as_u64().ok_or_else(error::invalid_keystore)?;
+                if !n.is_power_of_two() || n < 2 {
+                    return Err(error::invalid_keystore());
+                }
+                Self::Scrypt {
+                    log_n: n.trailing_zeros() as u8,
+                    r: number("r")?,
+                    p: number("p")?,
+                }
+            }
+            KDF_PBKDF2 if field("prf")?.as_str() == Some(PRF_HMAC_SHA256) => {
+                Self::Pbkdf2 { c: number("c")? }
+            }
+            _ => return Err(error::invalid_keystore()),
+        };
+        Ok((kdf, salt))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct Keystore {
+    version: u32,
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    // Some older wallets capitalize the crypto section
+    #[serde(alias = "Crypto")]
+    crypto: Crypto,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct Crypto {
+    cipher: String,
+    cipherparams: CipherParams,
+    ciphertext: String,
+    kdf: String,
+    kdfparams: Value,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct CipherParams {
+    iv: String,
+}
+
+/// Encrypt the given keypair as a keystore v3 JSON document with the given
+/// password. The salt, IV and key id are drawn from the entropy source.
+pub fn encrypt<E>(
+    keypair: &secp256k1::Keypair,
+    password: &[u8],
+    kdf: &Kdf,
+    entropy: &mut E,
+) -> error::Result<String>
+where
+    E: EntropySource + ?Sized,
+{
+    let mut salt = [0u8; 32];
+    let mut iv = [0u8; 16];
+    let mut id = [0u8; 16];
+    entropy.fill_entropy(&mut salt)?;
+    entropy.fill_entropy(&mut iv)?;
+    entropy.fill_entropy(&mut id)?;
+
+    let mut key = kdf.derive_key(password, &salt)?;
+    let mut ciphertext = keypair.to_bytes()[1..].to_vec();
+    apply_cipher(&key, &iv, &mut ciphertext);
+    let mac = mac(&key, &ciphertext);
+    key[..].zeroize();
+
+    let (kdf, kdfparams) = kdf.params(&salt);
+    let keystore = Keystore {
+        version: VERSION,
+        id: uuid(id),
+        address: Some(address(keypair)),
+        crypto: Crypto {
+            cipher: CIPHER.to_string(),
+            cipherparams: CipherParams {
+                iv: hex_encode(&iv),
+            },
+            ciphertext: hex_encode(&ciphertext),
+            kdf: kdf.to_string(),
+            kdfparams,
+            mac: hex_encode(&mac),
+        },
+    };
+    Ok(serde_json::to_string(&keystore).expect("keystore json"))
+}
+
+/// Decrypt a keystore v3 JSON document with the given password into a
+/// keypair for the given network. Fails with `Error::KeystorePassword` if the
+/// MAC does not match, which usually means the password is wrong.
+pub fn decrypt(
+    network: Network,
+    input: &str,
+    password: &[u8],
+) -> error::Result<secp256k1::Keypair> {
+    let (mut secret, address_hex) = decrypt_secret(input, password)?;
+    let keypair = secp256k1::Keypair::from_secret(network, &secret);
+    secret[..].zeroize();
+    let keypair = keypair?;
+    match address_hex {
+        Some(expected) if !expected.eq_ignore_ascii_case(&address(&keypair)) => {
+            Err(error::invalid_keystore())
+        }
+        _ => Ok(keypair),
+    }
+}
+
+/// The Ethereum address of the given keypair in lowercase hex, without a
+/// `0x` prefix, as it appears in keystore files.
+pub fn address(keypair: &secp256k1::Keypair) -> String {
+    let point = keypair
+        .public_key
+        .to_sec1_bytes(false)
+        .expect("secp256k1 point");
+    hex_encode(&Keccak256::digest(&point[1..])[12..])
+}
+
+fn decrypt_secret(input: &str, password: &[u8]) -> error::Result<(Vec<u8>, Option<String>)> {
+    let keystore: Keystore = serde_json::from_str(input).map_err(|_| error::invalid_keystore())?;
+    let crypto = &keystore.crypto;
+    if keystore.version != VERSION || crypto.cipher != CIPHER {
+        return Err(error::invalid_keystore());
+    }
+    let (kdf, salt) = Kdf::from_params(&crypto.kdf, &crypto.kdfparams)?;
+    let iv = hex_decode(&crypto.cipherparams.iv)?;
+    let mut secret = hex_decode(&crypto.ciphertext)?;
+    if iv.len() != 16 || secret.len() != SECRET_LENGTH {
+        return Err(error::invalid_keystore());
+    }
+
+    let mut key = kdf.derive_key(password, &salt)?;
+    let valid = mac(&key, &secret)[..] == hex_decode(&crypto.mac)?[..];
+    if valid {
+        apply_cipher(&key, &iv, &mut secret);
+    }
+    key[..].zeroize();
+    if !valid {
+        return Err(error::keystore_password());
+    }
+    let address = keystore.address.map(|address| {
+        address
+            .strip_prefix("0x")
+            .map(str::to_string)
+            .unwrap_or(address)
+    });
+    Ok((secret, address))
+}
+
+fn apply_cipher(key: &[u8; DKLEN], iv: &[u8], data: &mut [u8]) {
+    let mut cipher = Aes128Ctr::new(
+        GenericArray::from_slice(&key[..16]),
+        GenericArray::from_slice(iv),
+    );
+    cipher.apply_keystream(data);
+}
+
+fn mac(key: &[u8; DKLEN], ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(&key[16..]);
+    hasher.update(ciphertext);
+    hasher.finalize().into()
+}
+
+/// Formats the given random bytes as a version 4 UUID.
+fn uuid(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex_encode(&bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn hex_encode(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn hex_decode(input: &str) -> error::Result<Vec<u8>> {
+    if input.len() % 2 != 0 || !input.is_ascii() {
+        return Err(error::invalid_keystore());
+    }
+    (0..input.len())
+        .step_by(2)
+        .map(|pos| u8::from_str_radix(&input[pos..pos + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| error::invalid_keystore())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, decrypt_secret, encrypt, Kdf};
+    use crate::{error, secp256k1, Network};
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    // The PBKDF2 test vector from the Web3 Secret Storage definition
+    const PBKDF2_VECTOR: &str = r#"{
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": {"iv": "6087dab2f9fdbbfaddc31a909735c1e6"},
+            "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf": "pbkdf2",
+            "kdfparams": {
+                "c": 262144,
+                "dklen": 32,
+                "prf": "hmac-sha256",
+                "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+            },
+            "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+
+    #[test]
+    fn pbkdf2_vector() {
+        let (secret, _) = decrypt_secret(PBKDF2_VECTOR, b"testpassword").expect("secret");
+        assert_eq!(
+            hex!("7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"),
+            secret[..]
+        );
+        // The public key of this key has an odd y coordinate
+        let keypair = decrypt(Network::MainNet, PBKDF2_VECTOR, b"testpassword").expect("keypair");
+        assert_eq!(
+            hex!("0332d87c5cd4b31d81c5b010af42a2e413af253dc3a91bd3d53c6b2c45291c3de7"),
+            keypair.public_key.to_bytes()[1..]
+        );
+        assert!(matches!(
+            decrypt_secret(PBKDF2_VECTOR, b"wrongpassword"),
+            Err(error::Error::KeystorePassword)
+        ));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let keypair = secp256k1::Keypair::generate(Network::MainNet, &mut OsRng);
+        // Cheap parameters to keep the test fast
+        for kdf in &[
+            Kdf::Scrypt {
+                log_n: 4,
+                r: 8,
+                p: 1,
+            },
+            Kdf::Pbkdf2 { c: 16 },
+        ] {
+            let encrypted = encrypt(&keypair, b"password", kdf, &mut OsRng).expect("encrypt");
+            assert_eq!(
+                keypair,
+                decrypt(Network::MainNet, &encrypted, b"password").expect("decrypt")
+            );
+        }
+    }
+
+    #[test]
+    fn kdf_bounds() {
+        for kdf in &[
+            Kdf::Scrypt {
+                log_n: 4,
+                r: 8,
+                p: 0,
+            },
+            Kdf::Scrypt {
+                log_n: 4,
+                r: 8,
+                p: super::MAX_SCRYPT_PARALLELISM + 1,
+            },
+            Kdf::Scrypt {
+                log_n: 30,
+                r: 8,
+                p: 1,
+            },
+            Kdf::Pbkdf2 { c: 0 },
+            Kdf::Pbkdf2 {
+                c: super::MAX_PBKDF2_ROUNDS + 1,
+            },
+        ] {
+            assert!(matches!(
+                kdf.derive_key(b"password", b"salt"),
+                Err(error::Error::Decode(error::DecodeError::Keystore))
+            ));
+        }
+    }
+}
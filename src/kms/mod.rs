@@ -0,0 +1,171 @@
+//! AWS KMS backed keypairs. The secret key is an asymmetric `ECC_NIST_P256`
+//! KMS key and all signing happens through the KMS API, so the private key is
+//! never held locally.
+//!
+//! The KMS client is asynchronous while `Sign` is not, so each keypair runs
+//! its requests on a runtime of its own. Signing blocks the calling thread for
+//! a network round trip and must not be called from within an async context;
+//! use `AsyncSign` with the `async` feature, or a `SignerPool`, to sign from
+//! async code.
+//!
+//! Keys are ecc_compact keys when the public key of the KMS key is
+//! compactable. Since KMS does not allow choosing the key, keys that are not
+//! compactable are secp256r1 keys.
+use crate::{ecc_compact, error, keypair, public_key, Network};
+use aws_config::BehaviorVersion;
+use aws_sdk_kms::{
+    primitives::Blob,
+    types::{KeySpec, MessageType, SigningAlgorithmSpec},
+    Client,
+};
+use sha2::{Digest, Sha256};
+use tokio::runtime::{Builder, Runtime};
+
+/// A KMS client and the id of the key to sign with.
+pub struct Kms {
+    client: Client,
+    key_id: String,
+    runtime: Runtime,
+}
+
+pub type Keypair = keypair::Keypair<Kms>;
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        self.inner.runtime.block_on(self.sign_remote(msg))
+    }
+}
+
+#[cfg(feature = "async")]
+impl keypair::AsyncSign for Keypair {
+    fn sign_async<'a>(&'a self, msg: &'a [u8]) -> keypair::SignFuture<'a> {
+        Box::pin(self.sign_remote(msg))
+    }
+}
+
+impl Keypair {
+    async fn sign_remote(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        // Only the digest is sent to KMS, which returns a DER encoded signature
+        // that may have a high s
+        let digest = Sha256::digest(msg);
+        let response = self
+            .inner
+            .client
+            .sign()
+            .key_id(&self.inner.key_id)
+            .message(Blob::new(digest.to_vec()))
+            .message_type(MessageType::Digest)
+            .signing_algorithm(SigningAlgorithmSpec::EcdsaSha256)
+            .send()
+            .await
+            .map_err(error::kms)?;
+        let signature = response
+            .signature()
+            .ok_or_else(error::invalid_kms_response)?;
+        let signature = p256::ecdsa::Signature::from_der(signature.as_ref())
+            .map_err(|_| error::invalid_kms_response())?;
+        let signature = ecc_compact::normalize_s(signature)?;
+        Ok(signature.to_der().as_bytes().to_vec())
+    }
+
+    /// Open the KMS key with the given id, alias or ARN using the AWS
+    /// configuration from the environment and read its public key.
+    pub fn open(network: Network, key_id: &str) -> error::Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let config = runtime.block_on(aws_config::defaults(BehaviorVersion::latest()).load());
+        Self::from_client(network, Client::new(&config), key_id, runtime)
+    }
+
+    /// Open the KMS key with the given id, alias or ARN using the given client
+    /// and read its public key. Requests are run on the given runtime.
+    pub fn from_client(
+        network: Network,
+        client: Client,
+        key_id: &str,
+        runtime: Runtime,
+    ) -> error::Result<Self> {
+        let response = runtime
+            .block_on(client.get_public_key().key_id(key_id).send())
+            .map_err(error::kms)?;
+        if response.key_spec() != Some(&KeySpec::EccNistP256) {
+            return Err(error::invalid_kms_response());
+        }
+        let spki = response
+            .public_key()
+            .ok_or_else(error::invalid_kms_response)?;
+        let public_key = public_key_from_spki(network, spki.as_ref())?;
+        Ok(Keypair {
+            network,
+            public_key,
+            inner: Kms {
+                client,
+                key_id: key_id.to_string(),
+                runtime,
+            },
+        })
+    }
+}
+
+/// The public key in a DER encoded P-256 SubjectPublicKeyInfo as returned by
+/// KMS.
+fn public_key_from_spki(network: Network, spki: &[u8]) -> error::Result<public_key::PublicKey> {
+    let point = spki
+        .strip_prefix(ecc_compact::P256_SPKI_PREFIX)
+        .ok_or_else(error::invalid_kms_response)?;
+    public_key::PublicKey::from_p256_sec1_bytes(network, point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, KeyType};
+    use hex_literal::hex;
+
+    // The SubjectPublicKeyInfo of the P-256 generator, whose y is the smaller
+    // of y and p - y, and of its negation
+    const GENERATOR_SPKI: [u8; 91] = hex!(
+        "3059301306072a8648ce3d020106082a8648ce3d03010703420004"
+        "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"
+        "4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5"
+    );
+    const NEGATED_GENERATOR_SPKI: [u8; 91] = hex!(
+        "3059301306072a8648ce3d020106082a8648ce3d03010703420004"
+        "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"
+        "b01cbd1c01e58065711814b583f061e9d431cca994cea1313449bf97c840ae0a"
+    );
+
+    #[test]
+    fn spki_public_keys() {
+        for (spki, key_type) in [
+            (GENERATOR_SPKI, KeyType::EccCompact),
+            (NEGATED_GENERATOR_SPKI, KeyType::Secp256r1),
+        ] {
+            let public_key =
+                public_key_from_spki(Network::MainNet, &spki).expect("spki public key");
+            assert_eq!(key_type, public_key.key_type());
+            assert_eq!(Network::MainNet, public_key.network);
+            assert_eq!(
+                &spki[ecc_compact::P256_SPKI_PREFIX.len()..],
+                &public_key.to_sec1_bytes(false).expect("sec1 bytes")[..]
+            );
+        }
+    }
+
+    #[test]
+    fn spki_rejects() {
+        let mut wrong_prefix = GENERATOR_SPKI;
+        wrong_prefix[10] ^= 0xff;
+        for spki in [
+            &[][..],
+            ecc_compact::P256_SPKI_PREFIX,
+            &GENERATOR_SPKI[..GENERATOR_SPKI.len() - 1],
+            &wrong_prefix[..],
+        ] {
+            assert!(public_key_from_spki(Network::MainNet, spki).is_err());
+        }
+        assert!(matches!(
+            public_key_from_spki(Network::MainNet, &wrong_prefix),
+            Err(Error::InvalidKmsResponse)
+        ));
+    }
+}
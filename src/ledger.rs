@@ -0,0 +1,271 @@
+//! Ledger hardware wallet backed keypairs. The secret key is an ed25519 key
+//! derived by the Solana app on a Ledger device from a derivation path, and
+//! all signing happens on the device after the user confirms it there.
+//!
+//! The device is reached over USB HID. Keys are derived with SLIP-0010 like
+//! the keys of [`crate::hd`], so a path gives the same key on the device as
+//! in software for the same seed. The Solana app only derives keys below
+//! `m/44'/501'` and only signs Solana transaction messages, which is what
+//! Helium transactions are since the move to Solana.
+use crate::{ed25519, error, hd::DerivationPath, keypair, Network, PublicKey};
+use hidapi::{HidApi, HidDevice};
+use std::{
+    convert::TryFrom,
+    sync::{Arc, Mutex},
+};
+
+/// The BIP44 coin type of the paths the Solana app derives keys for.
+pub const SOLANA_COIN_TYPE: u32 = 501;
+
+const LEDGER_VENDOR_ID: u16 = 0x2c97;
+const LEDGER_USAGE_PAGE: u16 = 0xffa0;
+const CHANNEL: u16 = 0x0101;
+const TAG_APDU: u8 = 0x05;
+const PACKET_LENGTH: usize = 64;
+const PACKET_HEADER_LENGTH: usize = 5;
+
+const CLA: u8 = 0xe0;
+const INS_GET_PUBKEY: u8 = 0x05;
+const INS_SIGN_MESSAGE: u8 = 0x06;
+const P1_NON_CONFIRM: u8 = 0x00;
+const P1_CONFIRM: u8 = 0x01;
+const P2_EXTEND: u8 = 0x01;
+const P2_MORE: u8 = 0x02;
+const MAX_CHUNK_LENGTH: usize = 255;
+const STATUS_OK: u16 = 0x9000;
+
+/// A connected Ledger device. Exchanges from different threads are
+/// serialized on the device.
+pub struct Device {
+    device: Mutex<HidDevice>,
+}
+
+/// A key on a Ledger device.
+pub struct Ledger {
+    device: Arc<Device>,
+    path: DerivationPath,
+}
+
+pub type Keypair = keypair::Keypair<Ledger>;
+
+impl keypair::Sign for Keypair {
+    /// Sign a Solana transaction message. The user has to confirm the
+    /// transaction on the device.
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        // The first chunk starts with the number of signers and the path
+        let mut first = vec![1];
+        first.extend_from_slice(&path_bytes(&self.inner.path));
+        let (head, rest) = msg.split_at(msg.len().min(MAX_CHUNK_LENGTH - first.len()));
+        first.extend_from_slice(head);
+        let more = if rest.is_empty() { 0 } else { P2_MORE };
+        let mut response =
+            self.inner
+                .device
+                .exchange(INS_SIGN_MESSAGE, P1_CONFIRM, more, &first)?;
+        let mut chunks = rest.chunks(MAX_CHUNK_LENGTH).peekable();
+        while let Some(chunk) = chunks.next() {
+            let more = if chunks.peek().is_some() { P2_MORE } else { 0 };
+            response = self.inner.device.exchange(
+                INS_SIGN_MESSAGE,
+                P1_CONFIRM,
+                P2_EXTEND | more,
+                chunk,
+            )?;
+        }
+        if response.len() != ed25519_dalek::SIGNATURE_LENGTH {
+            return Err(error::invalid_ledger_response());
+        }
+        Ok(response)
+    }
+}
+
+impl Keypair {
+    /// The keypair for the key at the given derivation path on the given
+    /// device.
+    pub fn open(
+        device: Arc<Device>,
+        network: Network,
+        path: DerivationPath,
+    ) -> error::Result<Self> {
+        let public_key = device.public_key(network, &path, false)?;
+        Ok(Keypair {
+            network,
+            public_key,
+            inner: Ledger { device, path },
+        })
+    }
+
+    /// The derivation path of this key.
+    pub fn path(&self) -> &DerivationPath {
+        &self.inner.path
+    }
+}
+
+impl Device {
+    /// Open the first connected Ledger device.
+    pub fn open() -> error::Result<Self> {
+        let api = HidApi::new()?;
+        let info = api
+            .device_list()
+            .find(|info| {
+                info.vendor_id() == LEDGER_VENDOR_ID
+                    && (info.usage_page() == LEDGER_USAGE_PAGE || info.interface_number() == 0)
+            })
+            .ok_or_else(error::ledger_not_found)?;
+        Ok(Self {
+            device: Mutex::new(info.open_device(&api)?),
+        })
+    }
+
+    /// The public key at the given derivation path, which has to be at or
+    /// below `m/44'/501'`. With `confirm` the device displays the address and
+    /// the user has to approve it, so it can be compared with the address
+    /// shown by the host.
+    pub fn public_key(
+        &self,
+        network: Network,
+        path: &DerivationPath,
+        confirm: bool,
+    ) -> error::Result<PublicKey> {
+        let p1 = if confirm { P1_CONFIRM } else { P1_NON_CONFIRM };
+        let response = self.exchange(INS_GET_PUBKEY, p1, 0, &path_bytes(path))?;
+        let key = ed25519_dalek::PublicKey::from_bytes(&response)
+            .map_err(|_| error::invalid_ledger_response())?;
+        Ok(PublicKey::from_ed25519(
+            network,
+            ed25519::PublicKey::try_from(key)?,
+        ))
+    }
+
+    /// Sends an APDU to the app and returns the response data. Status words
+    /// other than success become `Error::LedgerStatus`.
+    fn exchange(&self, ins: u8, p1: u8, p2: u8, data: &[u8]) -> error::Result<Vec<u8>> {
+        let mut apdu = vec![CLA, ins, p1, p2, data.len() as u8];
+        apdu.extend_from_slice(data);
+        let device = self.device.lock().expect("ledger lock");
+        for packet in frame(&apdu) {
+            // hidapi expects the report id in front of the packet
+            let mut report = [0u8; PACKET_LENGTH + 1];
+            report[1..].copy_from_slice(&packet);
+            device.write(&report)?;
+        }
+        let mut response = Response::default();
+        loop {
+            let mut packet = [0u8; PACKET_LENGTH];
+            let len = device.read(&mut packet)?;
+            if let Some(mut response) = response.push(&packet[..len])? {
+                return match response.len().checked_sub(2) {
+                    Some(end) => {
+                        let status = u16::from_be_bytes([response[end], response[end + 1]]);
+                        response.truncate(end);
+                        if status == STATUS_OK {
+                            Ok(response)
+                        } else {
+                            Err(error::ledger_status(status))
+                        }
+                    }
+                    None => Err(error::invalid_ledger_response()),
+                };
+            }
+        }
+    }
+}
+
+/// The path as its length followed by the big endian hardened indexes.
+fn path_bytes(path: &DerivationPath) -> Vec<u8> {
+    let mut result = vec![path.indexes().len() as u8];
+    for index in path.indexes() {
+        result.extend_from_slice(&(index | 0x8000_0000).to_be_bytes());
+    }
+    result
+}
+
+/// Splits an APDU into HID packets: each has the channel, the APDU tag and a
+/// sequence number, and the first also the length of the APDU.
+fn frame(apdu: &[u8]) -> Vec<[u8; PACKET_LENGTH]> {
+    let mut data = (apdu.len() as u16).to_be_bytes().to_vec();
+    data.extend_from_slice(apdu);
+    data.chunks(PACKET_LENGTH - PACKET_HEADER_LENGTH)
+        .enumerate()
+        .map(|(sequence, chunk)| {
+            let mut packet = [0u8; PACKET_LENGTH];
+            packet[..2].copy_from_slice(&CHANNEL.to_be_bytes());
+            packet[2] = TAG_APDU;
+            packet[3..5].copy_from_slice(&(sequence as u16).to_be_bytes());
+            packet[PACKET_HEADER_LENGTH..PACKET_HEADER_LENGTH + chunk.len()].copy_from_slice(chunk);
+            packet
+        })
+        .collect()
+}
+
+/// Reassembles a response from HID packets.
+#[derive(Default)]
+struct Response {
+    data: Vec<u8>,
+    len: usize,
+    sequence: u16,
+}
+
+impl Response {
+    /// Adds a packet and returns the response once it is complete.
+    fn push(&mut self, packet: &[u8]) -> error::Result<Option<Vec<u8>>> {
+        if packet.len() < PACKET_HEADER_LENGTH
+            || packet[..2] != CHANNEL.to_be_bytes()
+            || packet[2] != TAG_APDU
+            || packet[3..5] != self.sequence.to_be_bytes()
+        {
+            return Err(error::invalid_ledger_response());
+        }
+        let mut data = &packet[PACKET_HEADER_LENGTH..];
+        if self.sequence == 0 {
+            if data.len() < 2 {
+                return Err(error::invalid_ledger_response());
+            }
+            self.len = u16::from_be_bytes([data[0], data[1]]) as usize;
+            data = &data[2..];
+        }
+        self.sequence = self.sequence.wrapping_add(1);
+        self.data.extend_from_slice(data);
+        if self.data.len() < self.len {
+            return Ok(None);
+        }
+        self.data.truncate(self.len);
+        Ok(Some(std::mem::take(&mut self.data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame, path_bytes, Response, PACKET_LENGTH, SOLANA_COIN_TYPE};
+    use crate::hd::DerivationPath;
+
+    #[test]
+    fn path() {
+        let path = DerivationPath::new(&[44, SOLANA_COIN_TYPE, 0, 0]).expect("path");
+        assert_eq!(
+            vec![4, 0x80, 0, 0, 44, 0x80, 0, 0x01, 0xf5, 0x80, 0, 0, 0, 0x80, 0, 0, 0],
+            path_bytes(&path)
+        );
+    }
+
+    #[test]
+    fn framing() {
+        let apdu: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let packets = frame(&apdu);
+        assert_eq!(4, packets.len());
+        assert_eq!([0x01, 0x01, 0x05, 0x00, 0x00, 0x00, 200], packets[0][..7]);
+        assert_eq!([0x01, 0x01, 0x05, 0x00, 0x03], packets[3][..5]);
+
+        let mut response = Response::default();
+        for packet in &packets[..3] {
+            assert_eq!(None, response.push(packet).expect("packet"));
+        }
+        assert_eq!(
+            Some(apdu),
+            response.push(&packets[3][..PACKET_LENGTH]).expect("packet")
+        );
+
+        let mut response = Response::default();
+        assert!(response.push(&packets[1]).is_err());
+    }
+}
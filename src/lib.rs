@@ -1,8 +1,9 @@
 //! Crypto primitives used by various [Helium][HELIUM] blockchain and wallet services.
 //!
-//! The library exposes both [Elliptic Curve (ECC)][ECC] NIST P-256 (secp256r1),
-//! and ED25519 keypairs based on the excellent work done by the
-//! [RustCrypto][RUSTCRYPTO] and [Dalek cryptography][DALEK] projects.
+//! The library exposes [Elliptic Curve (ECC)][ECC] NIST P-256 (secp256r1),
+//! secp256k1, with ECDSA or BIP340 Schnorr signatures, and ED25519 keypairs
+//! based on the excellent work done by the [RustCrypto][RUSTCRYPTO] and [Dalek
+//! cryptography][DALEK] projects.
 //!
 //! ECC keypairs keys implement the strategy described in a [Victor Miller
 //! paper][JIVSOV] which compresses keys to just their X-coordinate.
@@ -20,41 +21,341 @@
 //! [JIVSOV]: https://tools.ietf.org/html/draft-jivsov-ecc-compact-05
 //!
 //! [HELIUM]: https://helium.com
+//!
+//! ## no_std
+//!
+//! The keypair and public key types, key agreement and the errors build
+//! without the standard library when the default `std` feature is disabled.
+//! Parsing keys from slices and [`Sign::sign_into`] do not allocate either,
+//! so they also build without the `alloc` feature, which adds the functions
+//! that return a `Vec` or a `String`, like [`Sign::sign`], and boxed signers
+//! and verifiers. The remaining modules and the optional features need
+//! `std`, which implies `alloc`.
+//!
+//! ## WebAssembly
+//!
+//! On `wasm32-unknown-unknown` there is no operating system random number
+//! generator, so builds with `std` need the `wasm` feature, which draws
+//! randomness from the browser's `crypto.getRandomValues` and adds the
+//! JavaScript bindings in [`wasm`]. no_std builds do not depend on an operating
+//! system random number generator at all.
+//!
+//! ## Secret export
+//!
+//! With the `no-export` feature the functions that return or write out secret
+//! keys in plaintext, like [`Keypair::to_vec`], the per key type `to_bytes`
+//! and unencrypted PKCS#8 or OpenSSH private keys, are only visible within
+//! the crate, and the conversions into RustCrypto and dalek secret keys are
+//! not available. Deployments that enable it can rely on the compiler to show
+//! that no secret leaves the process other than encrypted, for example in a
+//! `keyfile`. Features that can't work without plaintext export, such as the
+//! language bindings and the command line tool, fail to build with it.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(all(
+    feature = "no-export",
+    any(
+        feature = "cli",
+        feature = "python",
+        feature = "test-utils",
+        feature = "uniffi",
+        feature = "wasm"
+    )
+))]
+compile_error!("the no-export feature can't be combined with features that export secret keys");
+
+/// Defines an accessor for plaintext secret key material, which is public
+/// unless the `no-export` feature is enabled. The crate itself can always use
+/// it, for example to encrypt the secret.
+macro_rules! export_secret {
+    ($(#[$attr:meta])* fn $($item:tt)*) => {
+        #[cfg(not(feature = "no-export"))]
+        $(#[$attr])*
+        pub fn $($item)*
+
+        #[cfg(feature = "no-export")]
+        $(#[$attr])*
+        #[allow(dead_code)]
+        pub(crate) fn $($item)*
+    };
+}
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
+#[cfg(feature = "age")]
+pub mod age;
+#[cfg(feature = "animal-name")]
+pub mod animal_name;
+#[cfg(feature = "attestation")]
+pub mod attestation;
+#[cfg(feature = "std")]
+pub mod bip85;
+#[cfg(feature = "bls12-381")]
+pub mod bls12_381;
+#[cfg(feature = "std")]
+mod cbor;
+pub mod context;
+#[cfg(feature = "cose")]
+pub mod cose;
+#[cfg(feature = "csr")]
+pub mod csr;
+#[cfg(feature = "did-key")]
+pub mod did_key;
+#[cfg(feature = "ecc608")]
+pub mod ecc608;
 pub mod ecc_compact;
+pub mod ecdh;
+#[cfg(feature = "ecies")]
+pub mod ecies;
 pub mod ed25519;
+pub mod entropy;
+#[cfg(feature = "ecies")]
+pub mod envelope;
 pub mod error;
+#[cfg(feature = "uniffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod firmware;
+#[cfg(feature = "std")]
+pub mod frost;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "std")]
+pub mod hd;
+#[cfg(feature = "hpke")]
+pub mod hpke;
+#[cfg(feature = "http-signature")]
+pub mod http_signature;
+#[cfg(all(test, feature = "test-vectors"))]
+mod interop;
+#[cfg(feature = "jose")]
+pub mod jose;
+#[cfg(feature = "jwk")]
+pub mod jwk;
+#[cfg(feature = "std")]
+pub mod key_pool;
+#[cfg(feature = "std")]
+pub mod key_registry;
+#[cfg(feature = "std")]
+pub mod key_set;
+#[cfg(feature = "keyfile")]
+pub mod keyfile;
+#[cfg(feature = "keystore")]
+pub mod keystore;
+#[cfg(feature = "kms")]
+pub mod kms;
+#[cfg(feature = "ledger")]
+pub mod ledger;
+#[cfg(feature = "std")]
+pub mod libp2p;
+#[cfg(feature = "defmt")]
+mod log_format;
+#[cfg(feature = "tower")]
+pub mod middleware;
+#[cfg(feature = "mnemonic")]
+pub mod mnemonic;
+#[cfg(feature = "std")]
+pub mod multisig;
+#[cfg(feature = "noise")]
+pub mod noise;
+#[cfg(feature = "openssh")]
+pub mod openssh;
+#[cfg(feature = "p384")]
+pub mod p384;
+#[cfg(feature = "std")]
+pub mod paper;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+#[cfg(feature = "pkcs8")]
+mod pkcs8;
+pub mod prepared;
+#[cfg(feature = "std")]
+pub mod provisioning;
 pub mod public_key;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "revocation")]
+pub mod revocation;
+#[cfg(feature = "alloc")]
+pub mod rotation;
+#[cfg(feature = "rsa")]
+pub mod rsa;
+#[cfg(feature = "schemars")]
+mod schema;
+pub mod schnorr;
+#[cfg(feature = "sealed-box")]
+pub mod sealed_box;
+pub mod secp256k1;
+#[cfg(feature = "std")]
+pub mod secp256r1;
+pub mod secret;
+#[cfg(all(
+    feature = "secure-enclave",
+    any(target_os = "macos", target_os = "ios")
+))]
+pub mod secure_enclave;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "std")]
+pub mod short_address;
+#[cfg(feature = "std")]
+pub mod signature_bytes;
+#[cfg(feature = "std")]
+pub mod signer_pool;
+#[cfg(feature = "smartcard")]
+pub mod smartcard;
+#[cfg(feature = "sr25519")]
+pub mod sr25519;
+#[cfg(all(feature = "ssh-agent", unix))]
+pub mod ssh_agent;
+#[cfg(feature = "std")]
+pub mod sss;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
+#[cfg(feature = "rustls")]
+pub mod tls;
+#[cfg(feature = "tpm")]
+pub mod tpm;
+#[cfg(feature = "txn")]
+pub mod txn;
+pub mod typed_signature;
+pub mod vrf;
+#[cfg(feature = "std")]
+pub mod wallet;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 mod keypair;
 pub use error::Error;
-pub use keypair::Sign;
-pub use public_key::{PublicKey, Verify, PUBLIC_KEY_LENGTH};
-use std::{
+#[cfg(feature = "async")]
+pub use keypair::{AsyncSign, SignFuture};
+pub use keypair::{Backend, Prehash, Sign, SignatureBuf, MAX_SIGNATURE_LENGTH};
+#[cfg(feature = "alloc")]
+pub use keypair::{BackendKeypair, BoxedSigner};
+#[cfg(any(test, feature = "test-utils"))]
+pub use keypair::{DebugSecret, WithSecret};
+#[cfg(feature = "alloc")]
+pub use public_key::BoxedVerifier;
+pub use public_key::{PublicKey, Verify, VerifyOptions, MAX_PUBLIC_KEY_LENGTH, PUBLIC_KEY_LENGTH};
+#[cfg(feature = "std")]
+pub use signature_bytes::{SignatureBytes, SignatureEncoding};
+pub use typed_signature::Signature;
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+use core::{
     convert::{From, TryFrom, TryInto},
     fmt,
+    ops::RangeInclusive,
     str::FromStr,
 };
+use entropy::EntropySource;
+use zeroize::Zeroize;
 
-/// Keys are generated for a given network. Supported networks are mainnet and
-/// testnet. The default network is mainnet.
+/// Keys are generated for a given network. Supported networks are mainnet,
+/// testnet and devnet. The default network is mainnet.
+///
+/// The network is the high nibble of a key tag. Values in
+/// [`NETTYPE_CORE_RANGE`] are assigned by the chain and an unassigned core
+/// value is rejected. Values in [`NETTYPE_CUSTOM_RANGE`] are free for private
+/// networks and parse as `Custom`, which holds the network bits of the tag, so
+/// keys of private networks never share an address with keys of the public
+/// chains.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Network {
     MainNet,
     TestNet,
+    DevNet,
+    /// A private network, see [`Network::custom`].
+    Custom(CustomNetwork),
 }
 
 impl Copy for Network {}
 
+/// The network bits of a private network, a value in [`NETTYPE_CUSTOM_RANGE`]
+/// with a zero low nibble.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CustomNetwork(u8);
+
+impl CustomNetwork {
+    /// The network bits of key tags on this network.
+    pub fn value(self) -> u8 {
+        self.0
+    }
+}
+
+impl Network {
+    /// The private network with the given network bits. Fails with
+    /// `DecodeError::Network` for values outside [`NETTYPE_CUSTOM_RANGE`] or
+    /// with key type bits set, which would otherwise end up as keys of one of
+    /// the public chains.
+    pub fn custom(v: u8) -> error::Result<Self> {
+        if v & 0x0F != 0 || !NETTYPE_CUSTOM_RANGE.contains(&v) {
+            return Err(error::invalid_network(v));
+        }
+        Ok(Self::Custom(CustomNetwork(v)))
+    }
+}
+
 /// Key types are the supported types of keys for either public or private keys.
 /// The default key type is ed25519.
+///
+/// The key type is the low nibble of a key tag. Values in
+/// [`KEYTYPE_CORE_RANGE`] are assigned by the chain and an unassigned core
+/// value is rejected. Values in [`KEYTYPE_EXPERIMENTAL_RANGE`] and
+/// [`KEYTYPE_VENDOR_RANGE`] are free for experiments and private deployments
+/// and parse as `Other`, so keys of such types pass through unchanged even
+/// though they can not be used for signing or verification here, unless a
+/// handler for them is registered with [`key_registry`].
 #[derive(Debug, PartialEq, Clone)]
 pub enum KeyType {
     Ed25519,
     EccCompact,
+    Secp256k1,
+    /// BIP340 Schnorr keys over secp256k1, see [`schnorr`].
+    Schnorr,
+    /// M-of-N multisig keys, see [`multisig::MultisigPublicKey`]. They have
+    /// their own binary form and are not a [`PublicKey`].
+    Multisig,
+    /// NIST P-384 ECDSA keys, see `p384::PublicKey` with the `p384` feature.
+    /// Their keys do not fit a [`PublicKey`] either.
+    P384,
+    /// NIST P-256 ECDSA keys that need not be compactable, see
+    /// [`secp256r1::PublicKey`], which converts into a [`PublicKey`].
+    Secp256r1,
+    /// sr25519 keys as used by Substrate, see `sr25519::PublicKey` with the
+    /// `sr25519` feature, which converts into a [`PublicKey`].
+    Sr25519,
+    /// RSA keys for HSMs that can only host RSA keys, see `rsa::PublicKey`
+    /// with the `rsa` feature. They have their own public key type too, and
+    /// their binary forms have a two byte extended tag rather than a key tag,
+    /// so no key tag byte parses as RSA.
+    Rsa,
+    Other(u8),
 }
 
 impl Copy for KeyType {}
 
+impl KeyType {
+    /// Whether this key type is in the experimental range.
+    pub fn is_experimental(&self) -> bool {
+        KEYTYPE_EXPERIMENTAL_RANGE.contains(&u8::from(*self))
+    }
+
+    /// Whether this key type is in the vendor range.
+    pub fn is_vendor(&self) -> bool {
+        KEYTYPE_VENDOR_RANGE.contains(&u8::from(*self))
+    }
+}
+
 /// A keytag is the byte prefix tag for both public and private keys in their
 /// binary form. A tag encodes both the network and the type of key.
 #[derive(Debug, PartialEq, Clone)]
@@ -65,6 +366,45 @@ pub struct KeyTag {
 
 impl Copy for KeyTag {}
 
+impl KeyTag {
+    /// All key tags with an assigned core network and key type that have a
+    /// single byte form, which excludes the extended RSA key type. Not all of
+    /// them have a software [`Keypair`], see [`KeyTag::generatable`].
+    pub fn all() -> impl Iterator<Item = KeyTag> {
+        [Network::MainNet, Network::TestNet, Network::DevNet]
+            .iter()
+            .flat_map(|network| {
+                [
+                    KeyType::Ed25519,
+                    KeyType::EccCompact,
+                    KeyType::Secp256k1,
+                    KeyType::Schnorr,
+                    KeyType::Multisig,
+                    KeyType::P384,
+                    KeyType::Secp256r1,
+                    KeyType::Sr25519,
+                ]
+                .iter()
+                .map(move |key_type| KeyTag {
+                    network: *network,
+                    key_type: *key_type,
+                })
+            })
+    }
+
+    /// The key tags from [`KeyTag::all`] that [`Keypair::generate`] supports.
+    /// Multisig, p384, secp256r1 and sr25519 keys are parsed and verified, but
+    /// have no software keypair.
+    pub fn generatable() -> impl Iterator<Item = KeyTag> {
+        Self::all().filter(|tag| {
+            matches!(
+                tag.key_type,
+                KeyType::Ed25519 | KeyType::EccCompact | KeyType::Secp256k1 | KeyType::Schnorr
+            )
+        })
+    }
+}
+
 impl Default for KeyTag {
     fn default() -> Self {
         Self {
@@ -86,7 +426,32 @@ impl TryFrom<u8> for KeyTag {
 
 impl From<KeyTag> for u8 {
     fn from(v: KeyTag) -> Self {
-        u8::from(v.network) | u8::from(v.key_type)
+        match v.key_type {
+            // Extended key types start with the extended network for every
+            // network
+            KeyType::Rsa => u8::from(v.key_type),
+            _ => u8::from(v.network) | u8::from(v.key_type),
+        }
+    }
+}
+
+/// Key tags render as `<network>/<key type>`, as in `mainnet/ecc_compact`.
+impl fmt::Display for KeyTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> core::result::Result<(), fmt::Error> {
+        write!(f, "{}/{}", self.network, self.key_type)
+    }
+}
+
+impl FromStr for KeyTag {
+    type Err = error::Error;
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        let (network, key_type) = s
+            .split_once('/')
+            .ok_or_else(|| error::invalid_keytype_str(s))?;
+        Ok(KeyTag {
+            network: network.parse()?,
+            key_type: key_type.parse()?,
+        })
     }
 }
 
@@ -96,28 +461,39 @@ impl TryFrom<u8> for Network {
         match v & 0xF0 {
             NETTYPE_MAIN => Ok(Self::MainNet),
             NETTYPE_TEST => Ok(Self::TestNet),
-            _ => Err(error::invalid_keytype(v)),
+            NETTYPE_DEV => Ok(Self::DevNet),
+            other if NETTYPE_CUSTOM_RANGE.contains(&other) => {
+                Ok(Self::Custom(CustomNetwork(other)))
+            }
+            _ => Err(error::invalid_network(v)),
         }
     }
 }
 
 impl FromStr for Network {
     type Err = error::Error;
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
         match s {
             NETTYPE_MAIN_STR => Ok(Self::MainNet),
             NETTYPE_TEST_STR => Ok(Self::TestNet),
-            _ => Err(error::invalid_keytype_str(s)),
+            NETTYPE_DEV_STR => Ok(Self::DevNet),
+            // Custom networks render as the hex value of their tag bits
+            _ => match s.strip_prefix("0x").map(|v| u8::from_str_radix(v, 16)) {
+                Some(Ok(v)) => Self::custom(v).map_err(|_| error::invalid_keytype_str(s)),
+                _ => Err(error::invalid_keytype_str(s)),
+            },
         }
     }
 }
 
 impl fmt::Display for Network {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
-        f.write_str(match self {
-            Self::MainNet => NETTYPE_MAIN_STR,
-            Self::TestNet => NETTYPE_TEST_STR,
-        })
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> core::result::Result<(), fmt::Error> {
+        match self {
+            Self::MainNet => f.write_str(NETTYPE_MAIN_STR),
+            Self::TestNet => f.write_str(NETTYPE_TEST_STR),
+            Self::DevNet => f.write_str(NETTYPE_DEV_STR),
+            Self::Custom(v) => write!(f, "{:#04x}", v.value()),
+        }
     }
 }
 
@@ -126,27 +502,48 @@ impl From<Network> for u8 {
         match v {
             Network::MainNet => NETTYPE_MAIN,
             Network::TestNet => NETTYPE_TEST,
+            Network::DevNet => NETTYPE_DEV,
+            Network::Custom(v) => v.value(),
         }
     }
 }
 
 impl FromStr for KeyType {
     type Err = error::Error;
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
         match s {
             KEYTYPE_ED25519_STR => Ok(Self::Ed25519),
             KEYTYPE_ECC_COMPACT_STR => Ok(Self::EccCompact),
-            _ => Err(error::invalid_keytype_str(s)),
+            KEYTYPE_SECP256K1_STR => Ok(Self::Secp256k1),
+            KEYTYPE_SCHNORR_STR => Ok(Self::Schnorr),
+            KEYTYPE_MULTISIG_STR => Ok(Self::Multisig),
+            KEYTYPE_P384_STR => Ok(Self::P384),
+            KEYTYPE_SECP256R1_STR => Ok(Self::Secp256r1),
+            KEYTYPE_SR25519_STR => Ok(Self::Sr25519),
+            KEYTYPE_RSA_STR => Ok(Self::Rsa),
+            // Other key types render as their hex value
+            _ => match s.strip_prefix("0x").map(|v| u8::from_str_radix(v, 16)) {
+                Some(Ok(v)) if v <= 0xF => KeyType::try_from(v),
+                _ => Err(error::invalid_keytype_str(s)),
+            },
         }
     }
 }
 
 impl fmt::Display for KeyType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
-        f.write_str(match self {
-            Self::Ed25519 => KEYTYPE_ED25519_STR,
-            Self::EccCompact => KEYTYPE_ECC_COMPACT_STR,
-        })
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> core::result::Result<(), fmt::Error> {
+        match self {
+            Self::Ed25519 => f.write_str(KEYTYPE_ED25519_STR),
+            Self::EccCompact => f.write_str(KEYTYPE_ECC_COMPACT_STR),
+            Self::Secp256k1 => f.write_str(KEYTYPE_SECP256K1_STR),
+            Self::Schnorr => f.write_str(KEYTYPE_SCHNORR_STR),
+            Self::Multisig => f.write_str(KEYTYPE_MULTISIG_STR),
+            Self::P384 => f.write_str(KEYTYPE_P384_STR),
+            Self::Secp256r1 => f.write_str(KEYTYPE_SECP256R1_STR),
+            Self::Sr25519 => f.write_str(KEYTYPE_SR25519_STR),
+            Self::Rsa => f.write_str(KEYTYPE_RSA_STR),
+            Self::Other(v) => write!(f, "{:#04x}", v),
+        }
     }
 }
 
@@ -156,7 +553,14 @@ impl TryFrom<u8> for KeyType {
         match v & 0xF {
             KEYTYPE_ED25519 => Ok(Self::Ed25519),
             KEYTYPE_ECC_COMPACT => Ok(Self::EccCompact),
-            _ => Err(error::invalid_keytype(v)),
+            KEYTYPE_SECP256K1 => Ok(Self::Secp256k1),
+            KEYTYPE_SCHNORR => Ok(Self::Schnorr),
+            KEYTYPE_MULTISIG => Ok(Self::Multisig),
+            KEYTYPE_P384 => Ok(Self::P384),
+            KEYTYPE_SECP256R1 => Ok(Self::Secp256r1),
+            KEYTYPE_SR25519 => Ok(Self::Sr25519),
+            other if KEYTYPE_CORE_RANGE.contains(&other) => Err(error::invalid_keytype(v)),
+            other => Ok(Self::Other(other)),
         }
     }
 }
@@ -166,31 +570,1160 @@ impl From<KeyType> for u8 {
         match v {
             KeyType::EccCompact => KEYTYPE_ECC_COMPACT,
             KeyType::Ed25519 => KEYTYPE_ED25519,
+            KeyType::Secp256k1 => KEYTYPE_SECP256K1,
+            KeyType::Schnorr => KEYTYPE_SCHNORR,
+            KeyType::Multisig => KEYTYPE_MULTISIG,
+            KeyType::P384 => KEYTYPE_P384,
+            KeyType::Secp256r1 => KEYTYPE_SECP256R1,
+            KeyType::Sr25519 => KEYTYPE_SR25519,
+            // The first byte of the extended tag
+            KeyType::Rsa => NETTYPE_EXTENDED | KEYTYPE_EXTENDED_RSA,
+            KeyType::Other(v) => v,
+        }
+    }
+}
+
+/// A keypair of any of the supported key types.
+#[derive(Debug, PartialEq)]
+pub enum Keypair {
+    EccCompact(ecc_compact::Keypair),
+    Ed25519(ed25519::Keypair),
+    Secp256k1(secp256k1::Keypair),
+    Schnorr(schnorr::Keypair),
+}
+
+/// The HKDF salt for `Keypair::derive_subkey`.
+#[cfg(feature = "subkey")]
+const SUBKEY_SALT: &[u8] = b"helium-crypto subkey v1";
+
+impl Keypair {
+    /// Length of the binary form of the longest keypairs, those of ed25519.
+    pub const MAX_LEN: usize = ed25519::KEYPAIR_LENGTH;
+
+    /// Generate a keypair for the given key tag. Fails for key types that are
+    /// not supported by this crate.
+    pub fn generate<E>(tag: KeyTag, entropy: &mut E) -> error::Result<Self>
+    where
+        E: EntropySource + ?Sized,
+    {
+        match tag.key_type {
+            KeyType::EccCompact => Ok(Self::EccCompact(ecc_compact::Keypair::generate(
+                tag.network,
+                entropy,
+            ))),
+            KeyType::Ed25519 => Ok(Self::Ed25519(ed25519::Keypair::generate(
+                tag.network,
+                entropy,
+            ))),
+            KeyType::Secp256k1 => Ok(Self::Secp256k1(secp256k1::Keypair::generate(
+                tag.network,
+                entropy,
+            ))),
+            KeyType::Schnorr => Ok(Self::Schnorr(schnorr::Keypair::generate(
+                tag.network,
+                entropy,
+            ))),
+            KeyType::Multisig
+            | KeyType::P384
+            | KeyType::Secp256r1
+            | KeyType::Sr25519
+            | KeyType::Rsa
+            | KeyType::Other(_) => Err(error::unsupported_keytype(tag.key_type)),
+        }
+    }
+
+    /// Generate a keypair for the given key tag using the operating system
+    /// random number generator.
+    #[cfg(feature = "std")]
+    pub fn generate_default(tag: KeyTag) -> error::Result<Self> {
+        Self::generate(tag, &mut rand_core::OsRng)
+    }
+
+    /// Generate the keypair for the given key tag that the given seed
+    /// determines, drawing entropy from a [`entropy::TestRng`] seeded with it.
+    /// The same seed gives the same keypair on every run and platform, which
+    /// makes for stable fixtures. Like the generator this is only available in
+    /// builds with debug assertions.
+    #[cfg(all(feature = "test-rng", debug_assertions))]
+    pub fn generate_seeded(tag: KeyTag, seed: [u8; 32]) -> error::Result<Self> {
+        Self::generate(tag, &mut entropy::TestRng::from_seed(seed))
+    }
+
+    #[cfg(feature = "alloc")]
+    export_secret! {
+        /// Generate `count` keypairs for the given key tag, each together with its
+        /// binary form. Entropy for all keys is drawn in a single call and
+        /// ecc_compact candidates are negated rather than rejected when they are
+        /// not compactable, so the cost per key is just the key derivation. Fails
+        /// with `Error::BatchTooLarge` when the entropy for `count` keys can't be
+        /// held in memory.
+        fn generate_many<E>(
+            tag: KeyTag,
+            count: usize,
+            entropy: &mut E,
+        ) -> error::Result<Vec<(Self, Vec<u8>)>>
+        where
+            E: EntropySource + ?Sized,
+        {
+            Self::generate_many_with_progress(tag, count, entropy, |_| ())
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    export_secret! {
+        /// Generate keypairs like [`Keypair::generate_many`], calling `progress`
+        /// with the number of keypairs derived so far after each one. With the
+        /// `rayon` feature the keys are derived on all cores, and `progress` may
+        /// be called from any of them.
+        fn generate_many_with_progress<E, F>(
+            tag: KeyTag,
+            count: usize,
+            entropy: &mut E,
+            progress: F,
+        ) -> error::Result<Vec<(Self, Vec<u8>)>>
+        where
+            E: EntropySource + ?Sized,
+            F: Fn(usize) + Sync,
+        {
+            use core::sync::atomic::{AtomicUsize, Ordering};
+            const SECRET_LENGTH: usize = 32;
+            if let KeyType::Multisig
+            | KeyType::P384
+            | KeyType::Secp256r1
+            | KeyType::Sr25519
+            | KeyType::Rsa
+            | KeyType::Other(_) = tag.key_type
+            {
+                return Err(error::unsupported_keytype(tag.key_type));
+            }
+            let length = count
+                .checked_mul(SECRET_LENGTH)
+                .ok_or_else(|| error::batch_too_large(count))?;
+            let mut secrets = vec![0u8; length];
+            entropy.fill_entropy(&mut secrets)?;
+            let done = AtomicUsize::new(0);
+            let derive = |secret: &[u8]| {
+                let keypair = Self::from_candidate(tag, secret);
+                progress(done.fetch_add(1, Ordering::Relaxed) + 1);
+                keypair
+            };
+            #[cfg(feature = "rayon")]
+            let candidates: Vec<Option<Self>> = {
+                use rayon::prelude::*;
+                secrets.par_chunks(SECRET_LENGTH).map(derive).collect()
+            };
+            #[cfg(not(feature = "rayon"))]
+            let candidates: Vec<Option<Self>> = secrets.chunks(SECRET_LENGTH).map(derive).collect();
+            secrets[..].zeroize();
+            candidates
+                .into_iter()
+                .map(|keypair| {
+                    // A candidate outside the curve order is astronomically
+                    // unlikely, fall back to regular generation for it
+                    let keypair = match keypair {
+                        Some(keypair) => keypair,
+                        None => Self::generate(tag, entropy)?,
+                    };
+                    let bytes = keypair.to_vec();
+                    Ok((keypair, bytes))
+                })
+                .collect()
+        }
+    }
+
+    /// The keypair for the given key tag with the given 32 byte candidate
+    /// secret, or `None` if the candidate is not a valid secret.
+    #[cfg(feature = "alloc")]
+    fn from_candidate(tag: KeyTag, secret: &[u8]) -> Option<Self> {
+        match tag.key_type {
+            KeyType::EccCompact => ecc_compact::Keypair::from_candidate(tag.network, secret)
+                .ok()
+                .map(Self::EccCompact),
+            KeyType::Ed25519 => ed25519::Keypair::from_secret(tag.network, secret)
+                .ok()
+                .map(Self::Ed25519),
+            KeyType::Secp256k1 => secp256k1::Keypair::from_secret(tag.network, secret)
+                .ok()
+                .map(Self::Secp256k1),
+            KeyType::Schnorr => schnorr::Keypair::from_secret(tag.network, secret)
+                .ok()
+                .map(Self::Schnorr),
+            KeyType::Multisig
+            | KeyType::P384
+            | KeyType::Secp256r1
+            | KeyType::Sr25519
+            | KeyType::Rsa
+            | KeyType::Other(_) => None,
+        }
+    }
+
+    /// Deterministically derive a child keypair of the same key tag for the
+    /// given purpose label, like `b"mux"` or `b"telemetry"`, so one root
+    /// identity can hand out separate keys for separate uses. The same root
+    /// and label always give the same child, while the child reveals nothing
+    /// about the root or the children for other labels.
+    ///
+    /// Candidate `i`, counting from zero, is the first 32 bytes of
+    /// `HKDF-SHA256(salt = "helium-crypto subkey v1", ikm = binary keypair,
+    /// info = label || be32(i))`. The first candidate that is a valid secret
+    /// is the child secret. ecc_compact candidates are negated when they are
+    /// not compactable, as in `generate_many`.
+    ///
+    /// Needs the `subkey` feature.
+    #[cfg(feature = "subkey")]
+    pub fn derive_subkey(&self, label: &[u8]) -> Keypair {
+        use hkdf::Hkdf;
+        let mut root = self.to_vec();
+        let hkdf = Hkdf::<sha2::Sha256>::new(Some(SUBKEY_SALT), &root);
+        root.zeroize();
+        let mut info = label.to_vec();
+        let mut candidate = [0u8; 32];
+        for counter in 0u32.. {
+            info.truncate(label.len());
+            info.extend_from_slice(&counter.to_be_bytes());
+            hkdf.expand(&info, &mut candidate)
+                .expect("hkdf output length");
+            let result = Self::from_candidate(self.key_tag(), &candidate);
+            candidate.zeroize();
+            if let Some(keypair) = result {
+                return keypair;
+            }
+        }
+        unreachable!("no valid subkey candidate")
+    }
+
+    /// Replace the secret of this keypair with a freshly generated one of the
+    /// same key type and network, zeroizing the old secret. The returned
+    /// statement that the new key replaces the old one as of `timestamp` is
+    /// signed by both keys, see [`rotation`].
+    #[cfg(feature = "alloc")]
+    pub fn rekey<E>(&mut self, entropy: &mut E, timestamp: u64) -> error::Result<rotation::Rotation>
+    where
+        E: EntropySource + ?Sized,
+    {
+        match self {
+            Self::EccCompact(keypair) => keypair.rekey(entropy, timestamp),
+            Self::Ed25519(keypair) => keypair.rekey(entropy, timestamp),
+            Self::Secp256k1(keypair) => keypair.rekey(entropy, timestamp),
+            Self::Schnorr(keypair) => keypair.rekey(entropy, timestamp),
+        }
+    }
+
+    /// The key tag for this keypair
+    pub fn key_tag(&self) -> KeyTag {
+        self.public_key().tag()
+    }
+
+    /// The network this keypair is valid for.
+    pub fn network(&self) -> Network {
+        self.public_key().network
+    }
+
+    /// The same keypair tagged for the given network.
+    pub fn with_network(self, network: Network) -> Self {
+        match self {
+            Self::EccCompact(keypair) => Self::EccCompact(keypair.with_network(network)),
+            Self::Ed25519(keypair) => Self::Ed25519(keypair.with_network(network)),
+            Self::Secp256k1(keypair) => Self::Secp256k1(keypair.with_network(network)),
+            Self::Schnorr(keypair) => Self::Schnorr(keypair.with_network(network)),
+        }
+    }
+
+    /// The public key for this keypair
+    pub fn public_key(&self) -> &public_key::PublicKey {
+        match self {
+            Self::EccCompact(keypair) => &keypair.public_key,
+            Self::Ed25519(keypair) => &keypair.public_key,
+            Self::Secp256k1(keypair) => &keypair.public_key,
+            Self::Schnorr(keypair) => &keypair.public_key,
         }
     }
+
+    /// Agree on a shared secret with the given peer key, which has to be of
+    /// the same key type. ed25519 and BIP340 keypairs do not support key
+    /// agreement.
+    pub fn ecdh(&self, peer: &public_key::PublicKey) -> error::Result<ecdh::SharedSecret> {
+        match self {
+            Self::EccCompact(keypair) => keypair.ecdh(peer),
+            Self::Secp256k1(keypair) => keypair.ecdh(peer),
+            Self::Ed25519(_) | Self::Schnorr(_) => {
+                Err(error::unsupported_keytype(self.key_tag().key_type))
+            }
+        }
+    }
+
+    /// Length of the binary form of this keypair, at most
+    /// [`Keypair::MAX_LEN`].
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::EccCompact(_) => ecc_compact::Keypair::LEN,
+            Self::Ed25519(_) => ed25519::Keypair::LEN,
+            Self::Secp256k1(_) => secp256k1::Keypair::LEN,
+            Self::Schnorr(_) => schnorr::Keypair::LEN,
+        }
+    }
+
+    export_secret! {
+        /// Write the binary form of the keypair into the start of the given
+        /// buffer, returning its length. Keypairs of different key types
+        /// differ in length, so the buffer has room for the longest.
+        fn write_into(&self, output: &mut [u8; Keypair::MAX_LEN]) -> usize {
+            match self {
+                Self::EccCompact(keypair) => keypair.try_bytes_into(output),
+                Self::Ed25519(keypair) => keypair.try_bytes_into(output),
+                Self::Secp256k1(keypair) => keypair.try_bytes_into(output),
+                Self::Schnorr(keypair) => keypair.try_bytes_into(output),
+            }
+            .expect("keypair buffer")
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    export_secret! {
+        /// Convert the keypair to its binary form
+        fn to_vec(&self) -> Vec<u8> {
+            match self {
+                Self::EccCompact(keypair) => keypair.to_bytes().to_vec(),
+                Self::Ed25519(keypair) => keypair.to_bytes().to_vec(),
+                Self::Secp256k1(keypair) => keypair.to_bytes().to_vec(),
+                Self::Schnorr(keypair) => keypair.to_bytes().to_vec(),
+            }
+        }
+    }
+
+    /// Start building a keypair, by default a mainnet ed25519 keypair from
+    /// the operating system random number generator.
+    pub fn builder<'a>() -> KeypairBuilder<'a> {
+        KeypairBuilder {
+            tag: KeyTag::default(),
+            entropy: None,
+            max_compaction_retries: None,
+            seed: None,
+        }
+    }
+}
+
+/// Builds a [`Keypair`] from options that can come from configuration, see
+/// [`Keypair::builder`].
+pub struct KeypairBuilder<'a> {
+    tag: KeyTag,
+    entropy: Option<&'a mut dyn EntropySource>,
+    max_compaction_retries: Option<u32>,
+    seed: Option<&'a [u8]>,
+}
+
+impl<'a> KeypairBuilder<'a> {
+    /// The network of the keypair.
+    pub fn network(mut self, network: Network) -> Self {
+        self.tag.network = network;
+        self
+    }
+
+    /// The key type of the keypair.
+    pub fn key_type(mut self, key_type: KeyType) -> Self {
+        self.tag.key_type = key_type;
+        self
+    }
+
+    /// The entropy source to generate the keypair from. Without one the
+    /// operating system random number generator is used, which needs the
+    /// `std` feature.
+    pub fn entropy(mut self, entropy: &'a mut dyn EntropySource) -> Self {
+        self.entropy = Some(entropy);
+        self
+    }
+
+    /// Generate ecc_compact keypairs by rejecting candidates that are not
+    /// compactable, with at most the given number of retries, rather than by
+    /// negating them. See [`ecc_compact::Keypair::generate_with_retries`].
+    pub fn max_compaction_retries(mut self, max_retries: u32) -> Self {
+        self.max_compaction_retries = Some(max_retries);
+        self
+    }
+
+    /// Derive the keypair from the given seed instead of generating it. ecc_compact
+    /// keypairs are derived from seeds of any length as described for
+    /// [`ecc_compact::Keypair::generate_from_seed`]. For other key types the
+    /// seed has to be a 32 byte secret key.
+    pub fn seed(mut self, seed: &'a [u8]) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Build the keypair. Fails for key types that are not supported by this
+    /// crate.
+    pub fn build(self) -> error::Result<Keypair> {
+        let network = self.tag.network;
+        if let Some(seed) = self.seed {
+            if self.tag.key_type != KeyType::EccCompact && seed.len() != 32 {
+                return Err(error::invalid_seed_length(seed.len()));
+            }
+            return match self.tag.key_type {
+                KeyType::EccCompact => Ok(Keypair::EccCompact(
+                    ecc_compact::Keypair::generate_from_seed(network, seed)?,
+                )),
+                KeyType::Ed25519 => Ok(Keypair::Ed25519(ed25519::Keypair::from_secret(
+                    network, seed,
+                )?)),
+                KeyType::Secp256k1 => Ok(Keypair::Secp256k1(secp256k1::Keypair::from_secret(
+                    network, seed,
+                )?)),
+                KeyType::Schnorr => Ok(Keypair::Schnorr(schnorr::Keypair::from_secret(
+                    network, seed,
+                )?)),
+                KeyType::Multisig
+                | KeyType::P384
+                | KeyType::Secp256r1
+                | KeyType::Sr25519
+                | KeyType::Rsa
+                | KeyType::Other(_) => Err(error::unsupported_keytype(self.tag.key_type)),
+            };
+        }
+
+        #[cfg(feature = "std")]
+        let mut os_rng = rand_core::OsRng;
+        let entropy: &mut dyn EntropySource = match self.entropy {
+            Some(entropy) => entropy,
+            #[cfg(feature = "std")]
+            None => &mut os_rng,
+            #[cfg(not(feature = "std"))]
+            None => return Err(error::entropy_failure()),
+        };
+        match self.max_compaction_retries {
+            Some(max_retries) if self.tag.key_type == KeyType::EccCompact => {
+                Ok(Keypair::EccCompact(
+                    ecc_compact::Keypair::generate_with_retries(network, entropy, max_retries)?,
+                ))
+            }
+            _ => Keypair::generate(self.tag, entropy),
+        }
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+impl Keypair {
+    export_secret! {
+        /// Encode the keypair as an unencrypted PKCS#8 document in DER form.
+        fn to_pkcs8_der(&self) -> Vec<u8> {
+            match self {
+                Self::EccCompact(keypair) => keypair.to_pkcs8_der(),
+                Self::Ed25519(keypair) => keypair.to_pkcs8_der(),
+                Self::Secp256k1(keypair) => keypair.to_pkcs8_der(),
+                Self::Schnorr(keypair) => keypair.to_pkcs8_der(),
+            }
+        }
+    }
+
+    /// Decode a DER encoded PKCS#8 key of any supported key type for the
+    /// given network. The key type follows from the algorithm in the document,
+    /// so secp256k1 documents always decode as ECDSA keys.
+    pub fn from_pkcs8_der(network: Network, input: &[u8]) -> error::Result<Self> {
+        let (key_type, mut secret) = pkcs8::decode(input)?;
+        secret[..].zeroize();
+        match key_type {
+            KeyType::EccCompact => Ok(Self::EccCompact(ecc_compact::Keypair::from_pkcs8_der(
+                network, input,
+            )?)),
+            KeyType::Ed25519 => Ok(Self::Ed25519(ed25519::Keypair::from_pkcs8_der(
+                network, input,
+            )?)),
+            KeyType::Secp256k1 => Ok(Self::Secp256k1(secp256k1::Keypair::from_pkcs8_der(
+                network, input,
+            )?)),
+            KeyType::Schnorr
+            | KeyType::Multisig
+            | KeyType::P384
+            | KeyType::Secp256r1
+            | KeyType::Sr25519
+            | KeyType::Rsa
+            | KeyType::Other(_) => Err(error::invalid_keytype(u8::from(KeyTag {
+                network,
+                key_type,
+            }))),
+        }
+    }
+
+    export_secret! {
+        /// Encode the keypair as an unencrypted PKCS#8 PEM block.
+        fn to_pkcs8_pem(&self) -> String {
+            pkcs8::to_pem(&self.to_pkcs8_der())
+        }
+    }
+
+    /// Decode a PKCS#8 PEM block holding a key of any supported key type for
+    /// the given network.
+    pub fn from_pkcs8_pem(network: Network, input: &str) -> error::Result<Self> {
+        Self::from_pkcs8_der(network, &pkcs8::from_pem(input)?)
+    }
+}
+
+#[cfg(feature = "keyfile")]
+impl Keypair {
+    /// Write the keypair to the given writer in the password protected
+    /// [`keyfile`] format, using the default key derivation parameters.
+    pub fn write_encrypted<W, E>(
+        &self,
+        writer: &mut W,
+        password: &[u8],
+        entropy: &mut E,
+    ) -> error::Result
+    where
+        W: std::io::Write,
+        E: EntropySource + ?Sized,
+    {
+        let encrypted = keyfile::encrypt(self, password, &keyfile::Params::default(), entropy)?;
+        writer.write_all(&encrypted)?;
+        Ok(())
+    }
+
+    /// Read a keypair in the password protected [`keyfile`] format from the
+    /// given reader.
+    pub fn read_encrypted<R>(reader: &mut R, password: &[u8]) -> error::Result<Self>
+    where
+        R: std::io::Read,
+    {
+        let mut encrypted = Vec::new();
+        reader.read_to_end(&mut encrypted)?;
+        keyfile::decrypt(&encrypted, password)
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        let tag = *input
+            .first()
+            .ok_or_else(|| error::invalid_length(Self::MAX_LEN, 0))?;
+        match KeyType::try_from(tag)? {
+            KeyType::EccCompact => Ok(Self::EccCompact(ecc_compact::Keypair::try_from(input)?)),
+            KeyType::Ed25519 => Ok(Self::Ed25519(ed25519::Keypair::try_from(input)?)),
+            KeyType::Secp256k1 => Ok(Self::Secp256k1(secp256k1::Keypair::try_from(input)?)),
+            KeyType::Schnorr => Ok(Self::Schnorr(schnorr::Keypair::try_from(input)?)),
+            KeyType::Multisig
+            | KeyType::P384
+            | KeyType::Secp256r1
+            | KeyType::Sr25519
+            | KeyType::Rsa
+            | KeyType::Other(_) => Err(error::invalid_keytype(tag)),
+        }
+    }
+}
+
+impl Sign for Keypair {
+    #[cfg(feature = "alloc")]
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        match self {
+            Self::EccCompact(keypair) => keypair.sign(msg),
+            Self::Ed25519(keypair) => keypair.sign(msg),
+            Self::Secp256k1(keypair) => keypair.sign(msg),
+            Self::Schnorr(keypair) => keypair.sign(msg),
+        }
+    }
+
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        match self {
+            Self::EccCompact(keypair) => keypair.sign_into(msg, output),
+            Self::Ed25519(keypair) => keypair.sign_into(msg, output),
+            Self::Secp256k1(keypair) => keypair.sign_into(msg, output),
+            Self::Schnorr(keypair) => keypair.sign_into(msg, output),
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn sign_digest(&self, digest: Prehash) -> error::Result<Vec<u8>> {
+        match self {
+            Self::EccCompact(keypair) => keypair.sign_digest(digest),
+            Self::Ed25519(keypair) => keypair.sign_digest(digest),
+            Self::Secp256k1(keypair) => keypair.sign_digest(digest),
+            Self::Schnorr(keypair) => keypair.sign_digest(digest),
+        }
+    }
+}
+
+/// Keypairs display as the b58 address of their public key.
+impl fmt::Display for Keypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> core::result::Result<(), fmt::Error> {
+        fmt::Display::fmt(self.public_key(), f)
+    }
+}
+
+impl Backend for Keypair {
+    fn public_key(&self) -> &public_key::PublicKey {
+        self.public_key()
+    }
 }
 
 /// The type tag for encoded ed25519 keys.
 pub const KEYTYPE_ED25519: u8 = 0x01;
 // The type tag for encoded ecc_compact keys
 pub const KEYTYPE_ECC_COMPACT: u8 = 0x00;
+/// The type tag for encoded multisig keys.
+pub const KEYTYPE_MULTISIG: u8 = 0x02;
+/// The type tag for encoded secp256k1 keys.
+pub const KEYTYPE_SECP256K1: u8 = 0x04;
+/// The type tag for encoded BIP340 Schnorr keys.
+pub const KEYTYPE_SCHNORR: u8 = 0x05;
+/// The type tag for encoded P-384 keys.
+pub const KEYTYPE_P384: u8 = 0x06;
+/// The type tag for encoded P-256 keys that need not be compactable.
+pub const KEYTYPE_SECP256R1: u8 = 0x07;
+/// The type tag for encoded sr25519 keys.
+pub const KEYTYPE_SR25519: u8 = 0x03;
+/// The extended key type of encoded RSA keys, see [`NETTYPE_EXTENDED`].
+pub const KEYTYPE_EXTENDED_RSA: u8 = 0x00;
 /// The string representation of the ed25519 key type
 pub const KEYTYPE_ED25519_STR: &str = "ed25519";
 /// The string representation of the ecc_compact key type
 pub const KEYTYPE_ECC_COMPACT_STR: &str = "ecc_compact";
+/// The string representation of the secp256k1 key type
+pub const KEYTYPE_SECP256K1_STR: &str = "secp256k1";
+/// The string representation of the multisig key type
+pub const KEYTYPE_MULTISIG_STR: &str = "multisig";
+/// The string representation of the BIP340 Schnorr key type
+pub const KEYTYPE_SCHNORR_STR: &str = "schnorr";
+/// The string representation of the P-384 key type
+pub const KEYTYPE_P384_STR: &str = "p384";
+/// The string representation of the non-compact P-256 key type
+pub const KEYTYPE_SECP256R1_STR: &str = "secp256r1";
+/// The string representation of the sr25519 key type
+pub const KEYTYPE_SR25519_STR: &str = "sr25519";
+/// The string representation of the RSA key type
+pub const KEYTYPE_RSA_STR: &str = "rsa";
+
+/// Key type values assigned by the chain.
+pub const KEYTYPE_CORE_RANGE: RangeInclusive<u8> = 0x0..=0x7;
+/// Key type values free for experimental key types.
+pub const KEYTYPE_EXPERIMENTAL_RANGE: RangeInclusive<u8> = 0x8..=0xB;
+/// Key type values free for vendor and private deployment key types.
+pub const KEYTYPE_VENDOR_RANGE: RangeInclusive<u8> = 0xC..=0xF;
 
 // The type tag for mainnet keys.
 pub const NETTYPE_MAIN: u8 = 0x00;
 // The type tag for testnet keys.
 pub const NETTYPE_TEST: u8 = 0x10;
+/// The type tag for devnet keys.
+pub const NETTYPE_DEV: u8 = 0x20;
+/// The first byte of extended tags, for key types that do not fit the key
+/// type nibble. The low nibble is the extended key type and the next byte is
+/// the network. It is a core value that is never assigned to a network, so
+/// parsers that do not know extended tags reject these keys.
+pub const NETTYPE_EXTENDED: u8 = 0x70;
 /// The string representation of the mainnet network type
 pub const NETTYPE_MAIN_STR: &str = "mainnet";
 /// The string representation of the testnet network type
 pub const NETTYPE_TEST_STR: &str = "testnet";
+/// The string representation of the devnet network type
+pub const NETTYPE_DEV_STR: &str = "devnet";
 
-/// Convert the implementor into its binary form by writing to the given output
-/// slice. The output slice is assumed to be of the right minimum size and
-/// implementors are expected to panic otherwise.
+/// Network tag values assigned by the chain.
+pub const NETTYPE_CORE_RANGE: RangeInclusive<u8> = 0x00..=0x70;
+/// Network tag values free for private networks.
+pub const NETTYPE_CUSTOM_RANGE: RangeInclusive<u8> = 0x80..=0xF0;
+
+/// Convert the implementor into its binary form by writing to the start of the
+/// given output slice, returning the number of bytes written. Fails with
+/// `Error::BufferTooSmall` if the output slice is too short for it.
 pub(crate) trait IntoBytes {
-    fn bytes_into(&self, output: &mut [u8]);
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize>;
+}
+
+/// The first `len` bytes of an [`IntoBytes`] output slice.
+pub(crate) fn output_prefix(output: &mut [u8], len: usize) -> error::Result<&mut [u8]> {
+    output
+        .get_mut(..len)
+        .ok_or_else(|| error::buffer_too_small(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ecc_compact, ed25519, BackendKeypair, KeyTag, KeyType, Keypair, Network, PublicKey, Sign,
+        Verify,
+    };
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn generate_many() {
+        for tag in KeyTag::generatable().filter(|tag| tag.network == Network::TestNet) {
+            let keypairs = Keypair::generate_many(tag, 8, &mut OsRng).expect("keypairs");
+            assert_eq!(8, keypairs.len());
+            for (keypair, bytes) in keypairs {
+                assert_eq!(tag, keypair.key_tag());
+                assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+                let signature = keypair.sign(b"hello world").expect("signature");
+                assert!(keypair
+                    .public_key()
+                    .verify(b"hello world", &signature)
+                    .is_ok());
+            }
+        }
+        assert!(matches!(
+            Keypair::generate_many(KeyTag::default(), usize::MAX, &mut OsRng),
+            Err(super::error::Error::BatchTooLarge(usize::MAX))
+        ));
+    }
+
+    #[cfg(feature = "test-rng")]
+    #[test]
+    fn generate_seeded() {
+        let tag = KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccCompact,
+        };
+        let keypair = Keypair::generate_seeded(tag, [7u8; 32]).expect("keypair");
+        assert_eq!(
+            keypair,
+            Keypair::generate_seeded(tag, [7u8; 32]).expect("keypair")
+        );
+        assert_ne!(
+            keypair,
+            Keypair::generate_seeded(tag, [8u8; 32]).expect("keypair")
+        );
+
+        // Known answers, so a change to the generator or to how a key type
+        // draws its secret is caught. Every key type takes the first 32 bytes
+        // of the ChaCha20 stream as its secret, and the ecc_compact candidate
+        // for this seed is negated to make it compactable.
+        for (key_type, binary, address) in [
+            (
+                KeyType::Ed25519,
+                &hex_literal::hex!(
+                    "01"
+                    "385553aa23a24b14d8bbc2dff606277f444e049797ae7e0404e3a9ba0ecef2fb"
+                    "5b046ad634c08ca3c327b59f7087fdf23e2f56e1a4e7a4cfa90eb957e6b11dfa"
+                )[..],
+                "13dq6DtRiTrzDGGQdDD8kz7N5iPMRSewtaa8j7C4w4uzN7DyGFN",
+            ),
+            (
+                KeyType::EccCompact,
+                &hex_literal::hex!(
+                    "00"
+                    "c7aaac54dc5db4ec27443d2009f9d8807898f6160f692080eed62108ed943256"
+                )[..],
+                "11YkrLY4d3c93m8LwnSAazW8yHpJv5yDomJm3NW3aY4ftzRprhS",
+            ),
+            (
+                KeyType::Secp256k1,
+                &hex_literal::hex!(
+                    "04"
+                    "385553aa23a24b14d8bbc2dff606277f444e049797ae7e0404e3a9ba0ecef2fb"
+                )[..],
+                "1bSmEPjTDmEprzhHEL74qnnQHnFdgU9NzFxa4Hxasf3DVmX2i5rs",
+            ),
+            (
+                KeyType::Schnorr,
+                &hex_literal::hex!(
+                    "05"
+                    "385553aa23a24b14d8bbc2dff606277f444e049797ae7e0404e3a9ba0ecef2fb"
+                )[..],
+                "1C8S6kkarRdRCHNRqgjf13eqsgFn6UA64kC2xsahjLP9Ry7CoHa",
+            ),
+        ] {
+            let tag = KeyTag {
+                network: Network::MainNet,
+                key_type,
+            };
+            let keypair = Keypair::generate_seeded(tag, [3u8; 32]).expect("keypair");
+            assert_eq!(binary, &keypair.to_vec()[..]);
+            assert_eq!(address, keypair.public_key().to_string());
+        }
+    }
+
+    #[test]
+    fn generate_many_progress() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        let tag = KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccCompact,
+        };
+        let calls = AtomicUsize::new(0);
+        let last = AtomicUsize::new(0);
+        let keypairs = Keypair::generate_many_with_progress(tag, 16, &mut OsRng, |done| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            last.fetch_max(done, Ordering::Relaxed);
+        })
+        .expect("keypairs");
+        assert_eq!(16, keypairs.len());
+        assert_eq!(16, calls.load(Ordering::Relaxed));
+        assert_eq!(16, last.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn sign_digest() {
+        use super::Prehash;
+        for key_type in &[KeyType::EccCompact, KeyType::Ed25519, KeyType::Secp256k1] {
+            let tag = KeyTag {
+                network: Network::MainNet,
+                key_type: *key_type,
+            };
+            let keypair = Keypair::generate(tag, &mut OsRng).expect("keypair");
+            let mut digest = Prehash::new(*key_type).expect("prehash");
+            digest.update(b"hello ");
+            digest.update(b"world");
+            let signature = keypair.sign_digest(digest.clone()).expect("signature");
+            let public_key = keypair.public_key();
+            assert!(public_key.verify_digest(digest, &signature).is_ok());
+            // Only ECDSA signs the digest it would compute itself
+            assert_eq!(
+                *key_type != KeyType::Ed25519,
+                public_key.verify(b"hello world", &signature).is_ok()
+            );
+        }
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let digest = Prehash::new(KeyType::Ed25519).expect("prehash");
+        assert!(keypair.sign_digest(digest).is_err());
+    }
+
+    #[test]
+    fn verify_prehashed() {
+        use super::Prehash;
+        use sha2::{Digest, Sha256, Sha512};
+        let digest: [u8; 32] = Sha256::digest(b"hello world").into();
+        for key_type in &[KeyType::EccCompact, KeyType::Secp256k1] {
+            let tag = KeyTag {
+                network: Network::MainNet,
+                key_type: *key_type,
+            };
+            let keypair = Keypair::generate(tag, &mut OsRng).expect("keypair");
+            let signature = keypair.sign(b"hello world").expect("signature");
+            let public_key = keypair.public_key();
+            assert!(public_key.verify_prehashed(&digest, &signature).is_ok());
+            assert!(public_key.verify_prehashed(&[0u8; 32], &signature).is_err());
+        }
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(matches!(
+            keypair.public_key.verify_prehashed(&digest, &signature),
+            Err(super::error::Error::UnsupportedPrehash)
+        ));
+        // ed25519 keys verify Ed25519ph signatures against the SHA-512 digest
+        let sha512 = Sha512::digest(b"hello world");
+        let signature = keypair
+            .sign_digest(Prehash::from(Sha512::new().chain(b"hello world")))
+            .expect("signature");
+        assert!(keypair
+            .public_key
+            .verify_prehashed(&sha512, &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify_prehashed(&[0u8; 64], &signature)
+            .is_err());
+        assert!(keypair
+            .public_key
+            .verify_prehashed(&sha512[..48], &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn backend() {
+        let tag = KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::Secp256k1,
+        };
+        let keypairs = vec![
+            BackendKeypair::from_backend(ed25519::Keypair::generate(Network::MainNet, &mut OsRng)),
+            BackendKeypair::from_backend(ecc_compact::Keypair::generate(
+                Network::TestNet,
+                &mut OsRng,
+            )),
+            BackendKeypair::from_backend(Keypair::generate(tag, &mut OsRng).expect("keypair")),
+        ];
+        for keypair in keypairs {
+            assert_eq!(keypair.network, keypair.public_key.network);
+            let signature = keypair.sign(b"hello world").expect("signature");
+            assert!(keypair
+                .public_key
+                .verify(b"hello world", &signature)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn boxed() {
+        use super::{BoxedSigner, BoxedVerifier};
+        use std::sync::Arc;
+        let ed = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let verifiers: Vec<BoxedVerifier> = vec![
+            ed.public_key.clone().into_boxed_verifier(),
+            Box::new(Arc::new(ecc.public_key.clone())),
+        ];
+        let signers: Vec<BoxedSigner> =
+            vec![ed.into_boxed_signer(), Arc::new(ecc).into_boxed_signer()];
+        for (signer, verifier) in signers.iter().zip(&verifiers) {
+            let signature = signer.sign(b"hello world").expect("signature");
+            assert!(verifier.verify(b"hello world", &signature).is_ok());
+            assert!(verifier.verify(b"hello", &signature).is_err());
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn sign_async() {
+        use super::AsyncSign;
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        )
+        .expect("keypair");
+        let signature = keypair.sign_async(b"hello world").await.expect("signature");
+        assert!(keypair
+            .public_key()
+            .verify(b"hello world", &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn rekey() {
+        for key_type in &[KeyType::EccCompact, KeyType::Ed25519] {
+            let tag = KeyTag {
+                network: Network::TestNet,
+                key_type: *key_type,
+            };
+            let mut keypair = Keypair::generate(tag, &mut OsRng).expect("keypair");
+            let old = keypair.public_key().clone();
+            let rotation = keypair.rekey(&mut OsRng, 1_700_000_000).expect("rotation");
+            assert_eq!(tag, keypair.key_tag());
+            assert_eq!(old, rotation.old_key);
+            assert_eq!(keypair.public_key(), &rotation.new_key);
+            assert_eq!(1_700_000_000, rotation.timestamp);
+            assert!(rotation.verify().is_ok());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "subkey")]
+    fn derive_subkey() {
+        for tag in KeyTag::generatable().filter(|tag| tag.network == Network::MainNet) {
+            let root = Keypair::generate(tag, &mut OsRng).expect("root keypair");
+            let mux = root.derive_subkey(b"mux");
+            assert_eq!(tag, mux.key_tag());
+            assert_eq!(mux.to_vec(), root.derive_subkey(b"mux").to_vec());
+            assert_ne!(root.public_key(), mux.public_key());
+            assert_ne!(
+                mux.public_key(),
+                root.derive_subkey(b"telemetry").public_key()
+            );
+            let signature = mux.sign(b"hello world").expect("signature");
+            assert!(mux.public_key().verify(b"hello world", &signature).is_ok());
+        }
+    }
+
+    #[test]
+    fn key_type_ranges() {
+        // Every core value is assigned
+        assert_eq!(KeyType::Sr25519, KeyType::try_from(0x03).expect("sr25519"));
+        assert_eq!(
+            KeyType::Secp256r1,
+            KeyType::try_from(0x07).expect("secp256r1")
+        );
+        // RSA keys have an extended tag and take no key type value
+        assert!(KeyType::try_from(0x08)
+            .expect("experimental key type")
+            .is_experimental());
+        assert_eq!(
+            KeyType::Multisig,
+            KeyType::try_from(0x12).expect("multisig")
+        );
+        let experimental = KeyType::try_from(0x19).expect("experimental key type");
+        assert_eq!(KeyType::Other(0x09), experimental);
+        assert!(experimental.is_experimental());
+        assert!(!experimental.is_vendor());
+        assert!(KeyType::try_from(0x0c)
+            .expect("vendor key type")
+            .is_vendor());
+        assert!(!KeyType::Ed25519.is_experimental());
+    }
+
+    #[test]
+    fn decode_errors() {
+        use super::error::{DecodeError, Error};
+        assert!(matches!(
+            KeyTag::try_from(0x31),
+            Err(Error::Decode(DecodeError::Network(0x31)))
+        ));
+        assert!(matches!(
+            PublicKey::from_bytes(&[0x03; 33]),
+            Err(Error::Decode(DecodeError::Type(0x03)))
+        ));
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        assert!(matches!(
+            keypair.ecdh(keypair.public_key()),
+            Err(Error::UnsupportedKeyType(KeyType::Ed25519))
+        ));
+    }
+
+    #[test]
+    fn short_input() {
+        use super::{error::Error, IntoBytes};
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng).expect("keypair");
+        let bytes = keypair.to_vec();
+        let public_key = keypair.public_key().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(Keypair::try_from(&bytes[..len]).is_err());
+        }
+        for len in 0..public_key.len() {
+            assert!(crate::PublicKey::try_from(&public_key[..len]).is_err());
+        }
+        assert!(matches!(
+            keypair.public_key().try_bytes_into(&mut [0u8; 8]),
+            Err(Error::BufferTooSmall(33))
+        ));
+    }
+
+    #[test]
+    fn write_into() {
+        for tag in KeyTag::generatable().filter(|tag| tag.network == Network::MainNet) {
+            let keypair = Keypair::generate(tag, &mut OsRng).expect("keypair");
+            let mut output = [0u8; Keypair::MAX_LEN];
+            let len = keypair.write_into(&mut output);
+            assert_eq!(keypair.encoded_len(), len);
+            assert_eq!(keypair.to_vec(), output[..len].to_vec());
+
+            let public_key = keypair.public_key();
+            let mut output = [0u8; crate::PublicKey::MAX_LEN];
+            let len = public_key.write_into(&mut output);
+            assert_eq!(public_key.encoded_len(), len);
+            assert_eq!(public_key.to_bytes()[..], output[..len]);
+        }
+    }
+
+    #[test]
+    fn key_tag_str_roundtrip() {
+        let tag = KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccCompact,
+        };
+        assert_eq!("mainnet/ecc_compact", tag.to_string());
+        assert_eq!(tag, "mainnet/ecc_compact".parse().expect("key tag"));
+        assert!("mainnet".parse::<KeyTag>().is_err());
+        assert!("mainnet/dsa".parse::<KeyTag>().is_err());
+        let other: KeyTag = "testnet/0x0c".parse().expect("other key tag");
+        assert_eq!(KeyType::Other(0x0c), other.key_type);
+        assert_eq!("testnet/0x0c", other.to_string());
+        for tag in KeyTag::all() {
+            assert_eq!(tag, tag.to_string().parse().expect("key tag"));
+            assert_eq!(tag, KeyTag::try_from(u8::from(tag)).expect("key tag"));
+        }
+        assert_eq!(24, KeyTag::all().count());
+        for tag in KeyTag::all() {
+            assert_eq!(
+                KeyTag::generatable().any(|generatable| generatable == tag),
+                Keypair::generate(tag, &mut OsRng).is_ok()
+            );
+        }
+        assert_eq!(12, KeyTag::generatable().count());
+    }
+
+    #[test]
+    fn network_roundtrip() {
+        use super::error::{DecodeError, Error};
+        let tag = KeyTag::try_from(0x21).expect("devnet key tag");
+        assert_eq!(Network::DevNet, tag.network);
+        assert_eq!("devnet/ed25519", tag.to_string());
+        let network = Network::custom(0xa0).expect("custom network");
+        let custom = KeyTag::try_from(0xa1).expect("custom key tag");
+        assert_eq!(network, custom.network);
+        assert_eq!("0xa0/ed25519", custom.to_string());
+        assert_eq!(custom, "0xa0/ed25519".parse().expect("custom key tag"));
+        assert_eq!(0xa1, u8::from(custom));
+        assert!("0xa1".parse::<Network>().is_err());
+        assert!("0x30".parse::<Network>().is_err());
+        // Custom networks can't alias a public network or set key type bits
+        for v in [0x00, 0x05, 0x10, 0x20, 0x70, 0x81, 0xa5] {
+            assert!(matches!(
+                Network::custom(v),
+                Err(Error::Decode(DecodeError::Network(n))) if n == v
+            ));
+        }
+
+        let keypair = ed25519::Keypair::generate(network, &mut OsRng);
+        let public_key = keypair.public_key.clone();
+        assert_eq!(network, public_key.network);
+        assert_ne!(
+            public_key.to_string(),
+            public_key.to_network(Network::MainNet).to_string()
+        );
+        assert_eq!(
+            public_key,
+            public_key.to_string().parse().expect("custom network key")
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn generate_default() {
+        let tag = KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccCompact,
+        };
+        let keypair = Keypair::generate_default(tag).expect("keypair");
+        assert_eq!(tag, keypair.key_tag());
+    }
+
+    #[test]
+    fn builder() {
+        let keypair = Keypair::builder()
+            .network(Network::TestNet)
+            .key_type(KeyType::EccCompact)
+            .entropy(&mut OsRng)
+            .max_compaction_retries(64)
+            .build()
+            .expect("keypair");
+        assert_eq!(
+            KeyTag {
+                network: Network::TestNet,
+                key_type: KeyType::EccCompact,
+            },
+            keypair.key_tag()
+        );
+
+        // Seeds derive the same keypair every time
+        for key_type in [
+            KeyType::EccCompact,
+            KeyType::Ed25519,
+            KeyType::Secp256k1,
+            KeyType::Schnorr,
+        ] {
+            let build = || {
+                Keypair::builder()
+                    .key_type(key_type)
+                    .seed(&[7u8; 32])
+                    .build()
+                    .expect("keypair")
+            };
+            assert_eq!(key_type, build().key_tag().key_type);
+            assert_eq!(build(), build());
+        }
+        assert!(Keypair::builder().seed(&[7u8; 16]).build().is_err());
+        assert!(Keypair::builder()
+            .key_type(KeyType::Multisig)
+            .entropy(&mut OsRng)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn with_network() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let public_key = keypair.public_key().clone();
+        let keypair = keypair.with_network(Network::TestNet);
+        assert_eq!(Network::TestNet, keypair.network());
+        assert_eq!(
+            public_key.to_network(Network::TestNet),
+            *keypair.public_key()
+        );
+        assert_ne!(public_key, *keypair.public_key());
+        assert_eq!(
+            keypair,
+            Keypair::try_from(&keypair.to_vec()[..]).expect("keypair")
+        );
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(public_key.verify(b"hello world", &signature).is_ok());
+    }
 }
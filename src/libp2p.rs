@@ -0,0 +1,216 @@
+//! Compatibility with the keys of the erlang `libp2p_crypto` library used by
+//! Helium hotspots.
+//!
+//! A `swarm_key` file holds `libp2p_crypto:keys_to_bin/1` of a keypair: the
+//! key tag, followed by the secret and the public key in the form the erlang
+//! crypto libraries use them:
+//!
+//! * ecc_compact: the 32 byte secret scalar and the 65 byte uncompressed point
+//! * ed25519: the 64 byte libsodium secret key, which is the seed followed by
+//!   the public key, and the 32 byte public key
+//!
+//! Peer ids are the libp2p `PeerId` of a key: the multihash of the protobuf
+//! encoded libp2p public key, rendered in base58. Keys with a protobuf
+//! encoding of at most 42 bytes, ed25519 and secp256k1 keys, use the identity
+//! multihash so their public key can be recovered from the peer id.
+use crate::{
+    ecc_compact, ed25519, error, public_key::PublicKeyRepr, secp256k1, KeyTag, KeyType, Keypair,
+    Network, PublicKey,
+};
+use sha2::{Digest, Sha256};
+use std::{convert::TryFrom, path::Path};
+use zeroize::Zeroize;
+
+const ECC_COMPACT_SWARM_KEY_LENGTH: usize = 1 + 32 + ecc_compact::SEC1_UNCOMPRESSED_LENGTH;
+const ED25519_SWARM_KEY_LENGTH: usize = 1 + 64 + 32;
+
+// libp2p crypto.proto key types
+const PROTO_ED25519: u8 = 1;
+const PROTO_SECP256K1: u8 = 2;
+const PROTO_ECDSA: u8 = 3;
+
+const MULTIHASH_IDENTITY: u8 = 0x00;
+const MULTIHASH_SHA256: u8 = 0x12;
+const MAX_INLINE_KEY_LENGTH: usize = 42;
+
+export_secret! {
+    /// Encode a keypair in the erlang `swarm_key` form. Only ecc_compact and
+    /// ed25519 keys are supported by the erlang library.
+    fn to_swarm_key(keypair: &Keypair) -> error::Result<Vec<u8>> {
+        let mut result = keypair.to_vec();
+        match keypair {
+            Keypair::EccCompact(keypair) => {
+                result.extend_from_slice(&keypair.public_key.to_sec1_bytes(false)?)
+            }
+            Keypair::Ed25519(keypair) => result.extend_from_slice(&keypair.public_key.to_bytes()[1..]),
+            Keypair::Secp256k1(_) | Keypair::Schnorr(_) => {
+                result[..].zeroize();
+                return Err(error::unsupported_keytype(keypair.key_tag().key_type));
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Decode a keypair from the erlang `swarm_key` form. The public key stored
+/// with the secret must match it.
+pub fn from_swarm_key(input: &[u8]) -> error::Result<Keypair> {
+    let tag = KeyTag::try_from(*input.first().ok_or_else(error::invalid_swarm_key)?)?;
+    match tag.key_type {
+        KeyType::EccCompact if input.len() == ECC_COMPACT_SWARM_KEY_LENGTH => {
+            let keypair = ecc_compact::Keypair::try_from(&input[..33])?;
+            if keypair.public_key.to_sec1_bytes(false)? != input[33..] {
+                return Err(error::invalid_swarm_key());
+            }
+            Ok(Keypair::EccCompact(keypair))
+        }
+        KeyType::Ed25519 if input.len() == ED25519_SWARM_KEY_LENGTH => {
+            let keypair = ed25519::Keypair::try_from(&input[..65])?;
+            // The libsodium secret key embeds the public key as well
+            let public_key = keypair.public_key.to_bytes();
+            let public_key = &public_key[1..];
+            if public_key != &input[33..65] || public_key != &input[65..] {
+                return Err(error::invalid_swarm_key());
+            }
+            Ok(Keypair::Ed25519(keypair))
+        }
+        KeyType::EccCompact | KeyType::Ed25519 => Err(error::invalid_swarm_key()),
+        _ => Err(error::invalid_keytype(input[0])),
+    }
+}
+
+/// Read a keypair from an erlang `swarm_key` file.
+pub fn read_swarm_key<P: AsRef<Path>>(path: P) -> error::Result<Keypair> {
+    let mut data = std::fs::read(path)?;
+    let result = from_swarm_key(&data);
+    data[..].zeroize();
+    result
+}
+
+export_secret! {
+    /// Write a keypair to an erlang `swarm_key` file.
+    fn write_swarm_key<P: AsRef<Path>>(path: P, keypair: &Keypair) -> error::Result {
+        let mut data = to_swarm_key(keypair)?;
+        let result = std::fs::write(path, &data);
+        data[..].zeroize();
+        Ok(result?)
+    }
+}
+
+/// The libp2p peer id of the given public key.
+pub fn to_peer_id(public_key: &PublicKey) -> error::Result<String> {
+    let (key_type, data) = match &public_key.inner {
+        PublicKeyRepr::EccCompact(_) | PublicKeyRepr::Secp256r1(_) => {
+            let mut spki = ecc_compact::P256_SPKI_PREFIX.to_vec();
+            spki.extend_from_slice(&public_key.to_sec1_bytes(false)?);
+            (PROTO_ECDSA, spki)
+        }
+        PublicKeyRepr::Ed25519(_) => (PROTO_ED25519, public_key.to_bytes()[1..].to_vec()),
+        PublicKeyRepr::Secp256k1(_) => (PROTO_SECP256K1, public_key.to_sec1_bytes(true)?),
+        PublicKeyRepr::Schnorr(_) | PublicKeyRepr::Other(..) => {
+            return Err(error::unsupported_keytype(public_key.tag().key_type))
+        }
+        #[cfg(feature = "sr25519")]
+        PublicKeyRepr::Sr25519(_) => {
+            return Err(error::unsupported_keytype(public_key.tag().key_type))
+        }
+    };
+    // message PublicKey { required KeyType Type = 1; required bytes Data = 2; }
+    let mut proto = vec![0x08, key_type, 0x12];
+    push_varint(&mut proto, data.len());
+    proto.extend_from_slice(&data);
+
+    let mut multihash = Vec::with_capacity(2 + proto.len());
+    if proto.len() <= MAX_INLINE_KEY_LENGTH {
+        multihash.extend_from_slice(&[MULTIHASH_IDENTITY, proto.len() as u8]);
+        multihash.extend_from_slice(&proto);
+    } else {
+        multihash.extend_from_slice(&[MULTIHASH_SHA256, 32]);
+        multihash.extend_from_slice(&Sha256::digest(&proto));
+    }
+    Ok(bs58::encode(multihash).into_string())
+}
+
+/// Recover the public key for the given network from a peer id. Only peer
+/// ids using the identity multihash, of ed25519 and secp256k1 keys, embed
+/// the public key.
+pub fn from_peer_id(network: Network, peer_id: &str) -> error::Result<PublicKey> {
+    let multihash = bs58::decode(peer_id).into_vec()?;
+    let proto = match &multihash[..] {
+        [MULTIHASH_IDENTITY, len, proto @ ..] if *len as usize == proto.len() => proto,
+        _ => return Err(error::invalid_peer_id()),
+    };
+    let (key_type, data) = match proto {
+        [0x08, key_type, 0x12, len, data @ ..] if *len as usize == data.len() => (*key_type, data),
+        _ => return Err(error::invalid_peer_id()),
+    };
+    match key_type {
+        PROTO_ED25519 => {
+            let public_key =
+                ed25519_dalek::PublicKey::from_bytes(data).map_err(|_| error::invalid_peer_id())?;
+            Ok(PublicKey::for_network(
+                network,
+                ed25519::PublicKey::try_from(public_key)?,
+            ))
+        }
+        PROTO_SECP256K1 => Ok(PublicKey::for_network(
+            network,
+            secp256k1::PublicKey::from_sec1_point(data)?,
+        )),
+        _ => Err(error::invalid_peer_id()),
+    }
+}
+
+fn push_varint(output: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_peer_id, from_swarm_key, to_peer_id, to_swarm_key};
+    use crate::{ecc_compact, ed25519, secp256k1, Keypair, Network, PublicKey};
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn swarm_key_roundtrip() {
+        for keypair in vec![
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng)),
+            Keypair::Ed25519(ed25519::Keypair::generate(Network::TestNet, &mut OsRng)),
+        ] {
+            let mut encoded = to_swarm_key(&keypair).expect("swarm key");
+            assert_eq!(keypair, from_swarm_key(&encoded).expect("keypair"));
+            // A mismatched public key is rejected
+            let last = encoded.len() - 1;
+            encoded[last] ^= 1;
+            assert!(from_swarm_key(&encoded).is_err());
+        }
+        let keypair =
+            Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng));
+        assert!(to_swarm_key(&keypair).is_err());
+    }
+
+    #[test]
+    fn peer_id() {
+        // RFC 8032 test vector 1 public key
+        let public_key = PublicKey::from_bytes(&hex!(
+            "01d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"
+        ))
+        .expect("public key");
+        let peer_id = to_peer_id(&public_key).expect("peer id");
+        assert!(peer_id.starts_with("12D3KooW"));
+        assert_eq!(
+            public_key,
+            from_peer_id(Network::MainNet, &peer_id).expect("public key")
+        );
+
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let peer_id = to_peer_id(&keypair.public_key).expect("peer id");
+        assert!(peer_id.starts_with("Qm"));
+        assert!(from_peer_id(Network::MainNet, &peer_id).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! [defmt](https://defmt.ferrous-systems.com) formatting for logging key
+//! identities and errors on embedded targets without `core::fmt`.
+//!
+//! Public keys are logged in their short form, like `13Wv…esKw`, and key tags
+//! as `<network>/<key type>`.
+use crate::{
+    error::{DecodeError, Error},
+    KeyTag, KeyType, Network, PublicKey, KEYTYPE_ECC_COMPACT_STR, KEYTYPE_ED25519_STR,
+    KEYTYPE_MULTISIG_STR, KEYTYPE_P384_STR, KEYTYPE_RSA_STR, KEYTYPE_SCHNORR_STR,
+    KEYTYPE_SECP256K1_STR, KEYTYPE_SECP256R1_STR, KEYTYPE_SR25519_STR, NETTYPE_DEV_STR,
+    NETTYPE_MAIN_STR, NETTYPE_TEST_STR,
+};
+use defmt::{write, Format, Formatter};
+
+/// Number of leading and trailing characters kept in the short form.
+const SHORT_CHARS: usize = 4;
+
+/// The leading and trailing characters of the given base58 encoding that make
+/// up its short form, or `None` if the encoding is too short to abbreviate.
+fn short_parts(encoded: &str) -> Option<(&str, &str)> {
+    let len = encoded.len();
+    // base58 is ascii so slicing at any byte is fine
+    (len > 2 * SHORT_CHARS && encoded.is_ascii())
+        .then(|| (&encoded[..SHORT_CHARS], &encoded[len - SHORT_CHARS..]))
+}
+
+impl Format for PublicKey {
+    fn format(&self, f: Formatter) {
+        self.with_b58(|encoded| match short_parts(encoded) {
+            Some((head, tail)) => write!(f, "{=str}…{=str}", head, tail),
+            None => write!(f, "<invalid key>"),
+        })
+    }
+}
+
+impl Format for Network {
+    fn format(&self, f: Formatter) {
+        match self {
+            Self::MainNet => write!(f, "{=str}", NETTYPE_MAIN_STR),
+            Self::TestNet => write!(f, "{=str}", NETTYPE_TEST_STR),
+            Self::DevNet => write!(f, "{=str}", NETTYPE_DEV_STR),
+            Self::Custom(v) => write!(f, "{=u8:#04x}", v.value()),
+        }
+    }
+}
+
+impl Format for KeyType {
+    fn format(&self, f: Formatter) {
+        match self {
+            Self::Ed25519 => write!(f, "{=str}", KEYTYPE_ED25519_STR),
+            Self::EccCompact => write!(f, "{=str}", KEYTYPE_ECC_COMPACT_STR),
+            Self::Secp256k1 => write!(f, "{=str}", KEYTYPE_SECP256K1_STR),
+            Self::Schnorr => write!(f, "{=str}", KEYTYPE_SCHNORR_STR),
+            Self::Multisig => write!(f, "{=str}", KEYTYPE_MULTISIG_STR),
+            Self::P384 => write!(f, "{=str}", KEYTYPE_P384_STR),
+            Self::Secp256r1 => write!(f, "{=str}", KEYTYPE_SECP256R1_STR),
+            Self::Sr25519 => write!(f, "{=str}", KEYTYPE_SR25519_STR),
+            Self::Rsa => write!(f, "{=str}", KEYTYPE_RSA_STR),
+            Self::Other(v) => write!(f, "{=u8:#04x}", v),
+        }
+    }
+}
+
+impl Format for KeyTag {
+    fn format(&self, f: Formatter) {
+        write!(f, "{}/{}", self.network, self.key_type)
+    }
+}
+
+impl Format for Error {
+    fn format(&self, f: Formatter) {
+        match self {
+            Self::Decode(err) => write!(f, "decode error: {}", err),
+            Self::EccCompact(_) => write!(f, "elliptic_curve error"),
+            Self::Signature(_) => write!(f, "signature error"),
+            #[cfg(feature = "std")]
+            Self::Io(_) => write!(f, "io error"),
+            Self::Entropy(_) => write!(f, "entropy error"),
+            Self::WeakEntropy(err) => write!(f, "rejected key entropy: {}", err),
+            #[cfg(feature = "smartcard")]
+            Self::Smartcard(_) => write!(f, "smartcard error"),
+            #[cfg(feature = "smartcard")]
+            Self::SmartcardStatus(v) => write!(f, "smartcard status {=u16:#06x}", v),
+            #[cfg(feature = "smartcard")]
+            Self::InvalidSmartcardResponse => write!(f, "invalid smartcard response"),
+            #[cfg(feature = "yubikey")]
+            Self::SmartcardSlot(v) => {
+                write!(f, "unsupported smartcard slot {=u8:#04x}", u8::from(*v))
+            }
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608Bus => write!(f, "ecc608 bus error"),
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608Status(v) => write!(f, "ecc608 status {=u8:#04x}", v),
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608Slot(v) => write!(f, "invalid ecc608 slot {=u8}", v),
+            #[cfg(feature = "ecc608")]
+            Self::InvalidEcc608Response => write!(f, "invalid ecc608 response"),
+            #[cfg(feature = "tpm")]
+            Self::Tpm(_) => write!(f, "tpm error"),
+            #[cfg(feature = "tpm")]
+            Self::InvalidTpmResponse => write!(f, "invalid tpm response"),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(_) => write!(f, "pkcs11 error"),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11NotFound(v) => write!(f, "pkcs11 {=str} not found", v.as_str()),
+            #[cfg(feature = "pkcs11")]
+            Self::InvalidPkcs11Response => write!(f, "invalid pkcs11 response"),
+            #[cfg(feature = "ledger")]
+            Self::Ledger(_) => write!(f, "ledger transport error"),
+            #[cfg(feature = "ledger")]
+            Self::LedgerNotFound => write!(f, "no ledger device found"),
+            #[cfg(feature = "ledger")]
+            Self::LedgerStatus(v) => write!(f, "ledger status {=u16:#06x}", v),
+            #[cfg(feature = "ledger")]
+            Self::InvalidLedgerResponse => write!(f, "invalid ledger response"),
+            #[cfg(feature = "ssh-agent")]
+            Self::SshAgentNotFound(v) => write!(f, "ssh agent {=str} not found", v.as_str()),
+            #[cfg(feature = "ssh-agent")]
+            Self::SshAgentFailure => write!(f, "ssh agent refused the request"),
+            #[cfg(feature = "ssh-agent")]
+            Self::InvalidSshAgentResponse => write!(f, "invalid ssh agent response"),
+            #[cfg(feature = "kms")]
+            Self::Kms(_) => write!(f, "kms error"),
+            #[cfg(feature = "kms")]
+            Self::InvalidKmsResponse => write!(f, "invalid kms response"),
+            #[cfg(feature = "secure-enclave")]
+            Self::SecureEnclave(v) => write!(f, "secure enclave error: {=str}", v.as_str()),
+            #[cfg(feature = "keyfile")]
+            Self::KeyfilePassword => {
+                write!(f, "incorrect password or corrupted keypair file")
+            }
+            #[cfg(feature = "keystore")]
+            Self::KeystorePassword => write!(f, "incorrect password or corrupted keystore"),
+            #[cfg(feature = "hpke")]
+            Self::Hpke(_) => write!(f, "hpke error"),
+            Self::PoolFull => write!(f, "signer pool queue full"),
+            Self::PoolClosed => write!(f, "signer pool closed"),
+            Self::SeedLength(v) => write!(f, "invalid seed length {=usize}", v),
+            Self::WalletMismatch(v) => write!(f, "wallet account {=u32} does not match seed", v),
+            #[cfg(feature = "alloc")]
+            Self::DeviceKeyMismatch(v) => {
+                write!(
+                    f,
+                    "device key does not match local key for {=str}",
+                    v.as_str()
+                )
+            }
+            #[cfg(feature = "http-signature")]
+            Self::HttpSignatureExpired => {
+                write!(f, "http message signature not yet valid or expired")
+            }
+            #[cfg(feature = "jose")]
+            Self::JwtExpired => write!(f, "json web token not yet valid or expired"),
+            #[cfg(feature = "txn")]
+            Self::TxnSigner => write!(f, "transaction has no such signer"),
+            Self::MultisigThreshold(m, n) => {
+                write!(f, "invalid multisig threshold {=usize} of {=usize}", m, n)
+            }
+            #[cfg(feature = "alloc")]
+            Self::MultisigDuplicateMember(v) => {
+                write!(f, "duplicate multisig member {=str}", v.as_str())
+            }
+            Self::ShareThreshold(m, n) => {
+                write!(f, "invalid secret sharing threshold {=u8} of {=u8}", m, n)
+            }
+            #[cfg(feature = "std")]
+            Self::FrostParticipant(v) => {
+                write!(
+                    f,
+                    "frost participant {=u8} sent an invalid proof or share",
+                    v
+                )
+            }
+            Self::BufferTooSmall(v) => {
+                write!(f, "output buffer too small, {=usize} bytes required", v)
+            }
+            Self::BatchTooLarge(v) => write!(f, "too many keypairs requested at once: {=usize}", v),
+            Self::SignatureTypeMismatch { expected, found } => {
+                write!(f, "{} signature given for {} key", found, expected)
+            }
+            Self::UnsupportedPrehash => write!(f, "unsupported prehash for signer or key"),
+            Self::UnsupportedKeyType(v) => write!(f, "unsupported key type {}", v),
+            #[cfg(feature = "std")]
+            Self::KeyTypeRegistered(v) => {
+                write!(f, "key type {=u8:#04x} is already registered", v)
+            }
+            #[cfg(feature = "alloc")]
+            Self::Firmware(_) => write!(f, "firmware rejected"),
+            Self::Rotation(_) => write!(f, "key rotation rejected"),
+        }
+    }
+}
+
+impl Format for DecodeError {
+    fn format(&self, f: Formatter) {
+        match self {
+            Self::B58(_) => write!(f, "b58 decode error"),
+            Self::Type(v) => write!(f, "unrecognized type value {=u8}", v),
+            Self::Network(v) => write!(f, "unrecognized network value {=u8}", v),
+            Self::Length { expected, found } => write!(
+                f,
+                "invalid length {=usize}, expected {=usize}",
+                found, expected
+            ),
+            #[cfg(feature = "alloc")]
+            Self::TypeString(v) => write!(f, "unrecognized type string {=str}", v.as_str()),
+            #[cfg(not(feature = "alloc"))]
+            Self::TypeString => write!(f, "unrecognized type string"),
+            Self::NotCompact => write!(f, "not a compact key"),
+            #[cfg(feature = "pkcs8")]
+            Self::Pkcs8 => write!(f, "invalid pkcs8 document"),
+            #[cfg(feature = "jwk")]
+            Self::Jwk => write!(f, "invalid json web key"),
+            #[cfg(feature = "openssh")]
+            Self::OpenSsh => write!(f, "invalid openssh key"),
+            #[cfg(feature = "mnemonic")]
+            Self::Mnemonic => write!(f, "invalid mnemonic phrase"),
+            #[cfg(feature = "keyfile")]
+            Self::Keyfile => write!(f, "invalid keypair file"),
+            #[cfg(feature = "keystore")]
+            Self::Keystore => write!(f, "invalid keystore"),
+            #[cfg(feature = "rsa")]
+            Self::Rsa => write!(f, "invalid or unsupported rsa key"),
+            Self::Point(err) => write!(f, "invalid point: {}", err),
+            Self::Address(err) => write!(f, "invalid address: {}", err),
+            Self::PaperLine(v) => write!(f, "invalid paper backup line {=usize}", v),
+            Self::PaperChecksum => write!(f, "paper backup checksum mismatch"),
+            #[cfg(feature = "alloc")]
+            Self::DerivationPath(v) => write!(f, "invalid derivation path {=str}", v.as_str()),
+            Self::ExtendedKey => write!(f, "invalid extended private key"),
+            Self::WalletState => write!(f, "invalid wallet state"),
+            Self::KeySet => write!(f, "invalid key set"),
+            Self::Multisig => write!(f, "invalid multisig key or signature"),
+            Self::ShortAddress => write!(f, "invalid short address"),
+            Self::Encoding => write!(f, "unrecognized public key encoding"),
+            Self::Share => write!(f, "invalid or mismatched secret share"),
+            #[cfg(feature = "std")]
+            Self::Frost => write!(f, "invalid frost message"),
+            Self::Musig => write!(f, "invalid musig key, nonce or partial signature"),
+            Self::VrfProof => write!(f, "invalid vrf proof"),
+            Self::Solana => write!(f, "invalid solana address or keypair"),
+            Self::SwarmKey => write!(f, "invalid swarm key"),
+            Self::PeerId => write!(f, "invalid peer id"),
+            #[cfg(feature = "did-key")]
+            Self::DidKey => write!(f, "invalid did:key identifier"),
+            #[cfg(feature = "ecies")]
+            Self::Ecies => write!(f, "invalid or tampered ecies ciphertext"),
+            #[cfg(feature = "ecies")]
+            Self::Envelope => write!(f, "invalid or tampered envelope"),
+            #[cfg(feature = "sealed-box")]
+            Self::SealedBox => write!(f, "invalid or tampered sealed box"),
+            #[cfg(feature = "age")]
+            Self::Age => write!(f, "invalid or tampered age file"),
+            Self::Manifest => write!(f, "invalid firmware manifest"),
+            Self::Rotation => write!(f, "invalid key rotation statement"),
+            #[cfg(feature = "revocation")]
+            Self::Revocation => write!(f, "invalid revocation statement"),
+            Self::Cbor => write!(f, "invalid or non-deterministic cbor"),
+            Self::ProvisioningBundle => write!(f, "invalid provisioning bundle"),
+            #[cfg(feature = "http-signature")]
+            Self::HttpSignature => write!(f, "invalid http message signature"),
+            #[cfg(feature = "jose")]
+            Self::Jwt => write!(f, "invalid json web token"),
+            #[cfg(feature = "cose")]
+            Self::Cose => write!(f, "invalid cose message"),
+            #[cfg(feature = "attestation")]
+            Self::Attestation => write!(f, "invalid attestation statement"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::short_parts;
+    use crate::{ecc_compact, ed25519, secp256k1, Network, PublicKey};
+    use rand::rngs::OsRng;
+    use std::str::FromStr;
+
+    /// The short form the `Format` impl logs for the given key.
+    fn short_form(public_key: &PublicKey) -> Option<String> {
+        public_key.with_b58(|encoded| {
+            short_parts(encoded).map(|(head, tail)| format!("{}…{}", head, tail))
+        })
+    }
+
+    #[test]
+    fn b58() {
+        const B58: &str = "13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw";
+        let public_key = PublicKey::from_str(B58).expect("public key");
+        assert_eq!(B58, public_key.with_b58(str::to_string));
+        assert_eq!(Some("13Wv…esKw".to_string()), short_form(&public_key));
+    }
+
+    #[test]
+    fn short_forms() {
+        // secp256k1 keys have the longest binary form
+        let public_keys = [
+            ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng).public_key,
+            ed25519::Keypair::generate(Network::TestNet, &mut OsRng).public_key,
+            secp256k1::Keypair::generate(Network::MainNet, &mut OsRng).public_key,
+        ];
+        for public_key in &public_keys {
+            assert_eq!(Some(format!("{:#}", public_key)), short_form(public_key));
+        }
+    }
+
+    #[test]
+    fn short_parts_too_short() {
+        assert_eq!(None, short_parts(""));
+        assert_eq!(None, short_parts("12345678"));
+        assert_eq!(Some(("1234", "6789")), short_parts("123456789"));
+    }
+}
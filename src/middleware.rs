@@ -0,0 +1,323 @@
+//! A [tower] middleware that verifies [HTTP message
+//! signatures](crate::http_signature) on incoming requests.
+//!
+//! The signing key is looked up by the `keyid` signature parameter through a
+//! [`KeyResolver`]. Requests without a valid signature by a known key are
+//! answered with `401 Unauthorized` and never reach the inner service.
+//! Verified requests carry the signer's key as a [`VerifiedKey`] request
+//! extension.
+//!
+//! Note that a signature only covers the request body if the signer covers a
+//! digest header, like `content-digest`, and the service checks that digest
+//! against the body.
+//!
+//! ```ignore
+//! let service = ServiceBuilder::new()
+//!     .layer(VerifySignatureLayer::new("sig", |keyid: &str| keys.get(keyid).cloned()))
+//!     .service(handler);
+//! ```
+//!
+//! [tower]: https://docs.rs/tower
+use crate::{
+    error,
+    http_signature::{self, Message},
+    PublicKey,
+};
+use pin_project_lite::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Looks up the public key for a `keyid` signature parameter.
+pub trait KeyResolver: Send + Sync {
+    fn resolve(&self, keyid: &str) -> Option<PublicKey>;
+}
+
+impl<F> KeyResolver for F
+where
+    F: Fn(&str) -> Option<PublicKey> + Send + Sync,
+{
+    fn resolve(&self, keyid: &str) -> Option<PublicKey> {
+        self(keyid)
+    }
+}
+
+/// The key a request was verified with, added to the request extensions.
+#[derive(Debug, PartialEq, Clone)]
+pub struct VerifiedKey(pub PublicKey);
+
+impl<B> Message for http::Request<B> {
+    fn component(&self, name: &str) -> Option<String> {
+        let uri = self.uri();
+        match name {
+            "@method" => Some(self.method().as_str().to_string()),
+            "@target-uri" => Some(uri.to_string()),
+            "@authority" => uri
+                .authority()
+                .map(|authority| authority.as_str().to_lowercase())
+                .or_else(|| {
+                    self.headers()
+                        .get(http::header::HOST)
+                        .and_then(|host| host.to_str().ok())
+                        .map(str::to_lowercase)
+                }),
+            "@scheme" => uri.scheme_str().map(str::to_lowercase),
+            "@request-target" => uri.path_and_query().map(|v| v.as_str().to_string()),
+            "@path" => Some(uri.path().to_string()),
+            "@query" => Some(format!("?{}", uri.query().unwrap_or_default())),
+            name if name.starts_with('@') => None,
+            name => {
+                let values = self
+                    .headers()
+                    .get_all(name)
+                    .iter()
+                    .map(|value| value.to_str().map(str::trim))
+                    .collect::<Result<Vec<&str>, _>>()
+                    .ok()?;
+                if values.is_empty() {
+                    None
+                } else {
+                    Some(values.join(", "))
+                }
+            }
+        }
+    }
+}
+
+/// Applies [`VerifySignature`] to services.
+pub struct VerifySignatureLayer<R> {
+    label: Arc<str>,
+    resolver: Arc<R>,
+}
+
+impl<R> VerifySignatureLayer<R> {
+    /// Verify the signature with the given label, resolving keys with the
+    /// given resolver.
+    pub fn new(label: &str, resolver: R) -> Self {
+        Self {
+            label: label.into(),
+            resolver: Arc::new(resolver),
+        }
+    }
+}
+
+impl<R> Clone for VerifySignatureLayer<R> {
+    fn clone(&self) -> Self {
+        Self {
+            label: self.label.clone(),
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+impl<S, R> Layer<S> for VerifySignatureLayer<R> {
+    type Service = VerifySignature<S, R>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        VerifySignature {
+            inner,
+            label: self.label.clone(),
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+/// A service that verifies request signatures before passing requests on to
+/// the inner service.
+pub struct VerifySignature<S, R> {
+    inner: S,
+    label: Arc<str>,
+    resolver: Arc<R>,
+}
+
+impl<S: Clone, R> Clone for VerifySignature<S, R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            label: self.label.clone(),
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+impl<S, R> VerifySignature<S, R>
+where
+    R: KeyResolver,
+{
+    fn verify<B>(&self, request: &http::Request<B>) -> error::Result<PublicKey> {
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(error::invalid_http_signature)
+        };
+        let signature_input = header("signature-input")?;
+        let signature = header("signature")?;
+        let params = http_signature::signature_params(signature_input, &self.label)?;
+        let public_key = params
+            .keyid
+            .as_deref()
+            .and_then(|keyid| self.resolver.resolve(keyid))
+            .ok_or_else(error::invalid_http_signature)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        http_signature::verify(
+            &public_key,
+            &self.label,
+            request,
+            signature_input,
+            signature,
+            now,
+        )?;
+        Ok(public_key)
+    }
+}
+
+impl<S, R, B, ResB> Service<http::Request<B>> for VerifySignature<S, R>
+where
+    S: Service<http::Request<B>, Response = http::Response<ResB>>,
+    R: KeyResolver,
+    ResB: Default,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future, ResB>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        match self.verify(&request) {
+            Ok(public_key) => {
+                request.extensions_mut().insert(VerifiedKey(public_key));
+                ResponseFuture::Inner {
+                    future: self.inner.call(request),
+                }
+            }
+            Err(_) => {
+                let mut response = http::Response::new(ResB::default());
+                *response.status_mut() = http::StatusCode::UNAUTHORIZED;
+                ResponseFuture::Rejected {
+                    response: Some(response),
+                }
+            }
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of [`VerifySignature`].
+    #[project = ResponseFutureProj]
+    pub enum ResponseFuture<F, ResB> {
+        Inner { #[pin] future: F },
+        Rejected { response: Option<http::Response<ResB>> },
+    }
+}
+
+impl<F, ResB, E> Future for ResponseFuture<F, ResB>
+where
+    F: Future<Output = Result<http::Response<ResB>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            ResponseFutureProj::Inner { future } => future.poll(cx),
+            ResponseFutureProj::Rejected { response } => {
+                Poll::Ready(Ok(response.take().expect("polled after completion")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VerifiedKey, VerifySignatureLayer};
+    use crate::{
+        ed25519,
+        http_signature::{self, SignatureParams},
+        Keypair, Network, PublicKey,
+    };
+    use rand::rngs::OsRng;
+    use std::{
+        convert::Infallible,
+        task::{Context, Poll},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    struct Echo;
+
+    impl Service<http::Request<()>> for Echo {
+        type Response = http::Response<Option<PublicKey>>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<()>) -> Self::Future {
+            let key = request
+                .extensions()
+                .get::<VerifiedKey>()
+                .map(|key| key.0.clone());
+            std::future::ready(Ok(http::Response::new(key)))
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_requests() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let public_key = keypair.public_key().clone();
+        let resolver_key = public_key.clone();
+        let mut service = VerifySignatureLayer::new("sig", move |keyid: &str| {
+            if keyid == "device" {
+                Some(resolver_key.clone())
+            } else {
+                None
+            }
+        })
+        .layer(Echo);
+        let request = || {
+            http::Request::post("https://example.com/foo?bar=1")
+                .body(())
+                .expect("request")
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_secs();
+        let params = SignatureParams {
+            components: vec!["@method".to_string(), "@path".to_string()],
+            created: Some(now),
+            keyid: Some("device".to_string()),
+            ..Default::default()
+        };
+        let headers = http_signature::sign(&keypair, "sig", &request(), &params).expect("sign");
+        let mut signed = request();
+        signed.headers_mut().insert(
+            "signature-input",
+            headers.signature_input.parse().expect("header"),
+        );
+        signed
+            .headers_mut()
+            .insert("signature", headers.signature.parse().expect("header"));
+        let response = service.call(signed).await.expect("response");
+        assert_eq!(http::StatusCode::OK, response.status());
+        assert_eq!(Some(public_key), *response.body());
+        let response = service.call(request()).await.expect("response");
+        assert_eq!(http::StatusCode::UNAUTHORIZED, response.status());
+    }
+}
@@ -0,0 +1,206 @@
+//! BIP39 mnemonic phrases for keypairs.
+//!
+//! A phrase of 12 or 24 words from the BIP39 English word list encodes 128 or
+//! 256 bits of entropy. The keypair for a phrase is derived from its BIP39
+//! seed, with an empty passphrase, so the phrase can also be used with hardware
+//! wallets and wallet software:
+//!
+//! * ed25519 keys are derived at the Helium account path `m/44'/904'/0'/0'/0'`
+//!   following SLIP-0010, the same key as account 0 of a
+//!   [`Wallet`](crate::wallet::Wallet) for the seed.
+//! * ecc_compact and secp256k1 keys are the SLIP-0010 (BIP32 for secp256k1)
+//!   master secret keys for the seed. When the public key of the ecc_compact
+//!   master secret is not compactable the secret is negated, so recovering a
+//!   phrase always gives the same keypair.
+//! * BIP340 Schnorr keys use the BIP32 master secret key as is.
+use crate::{
+    ecc_compact,
+    entropy::EntropySource,
+    error,
+    hd::{DerivationPath, ExtendedSecret},
+    schnorr, secp256k1, KeyTag, KeyType, Keypair,
+};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha512;
+use zeroize::Zeroize;
+
+const NIST256P1_SEED_KEY: &[u8] = b"Nist256p1 seed";
+const SECP256K1_SEED_KEY: &[u8] = b"Bitcoin seed";
+
+/// The number of words in a phrase.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WordCount {
+    /// 12 words, encoding 128 bits of entropy
+    Twelve,
+    /// 24 words, encoding 256 bits of entropy
+    TwentyFour,
+}
+
+impl WordCount {
+    pub(crate) fn entropy_length(self) -> usize {
+        match self {
+            Self::Twelve => 16,
+            Self::TwentyFour => 32,
+        }
+    }
+
+    pub(crate) fn words(self) -> u32 {
+        match self {
+            Self::Twelve => 12,
+            Self::TwentyFour => 24,
+        }
+    }
+}
+
+export_secret! {
+    /// Generate a keypair for the given key tag together with the phrase it can
+    /// be recovered from.
+    fn generate<E>(
+        tag: KeyTag,
+        word_count: WordCount,
+        entropy: &mut E,
+    ) -> error::Result<(Keypair, String)>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let mut bytes = [0u8; 32];
+        let bytes = &mut bytes[..word_count.entropy_length()];
+        entropy.fill_entropy(bytes)?;
+        let mnemonic = bip39::Mnemonic::from_entropy(bytes).map_err(|_| error::invalid_mnemonic());
+        bytes[..].zeroize();
+        let phrase = mnemonic?.to_string();
+        let keypair = recover(tag, &phrase)?;
+        Ok((keypair, phrase))
+    }
+}
+
+/// Recover the keypair for the given key tag from a phrase. Words are
+/// separated by whitespace and the checksum in the phrase is verified.
+pub fn recover(tag: KeyTag, phrase: &str) -> error::Result<Keypair> {
+    let mut seed = seed(phrase)?;
+    let result = from_seed(tag, &seed);
+    seed[..].zeroize();
+    result
+}
+
+/// The BIP39 seed for a phrase, with an empty passphrase.
+pub(crate) fn seed(phrase: &str) -> error::Result<[u8; 64]> {
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    if words.len() != 12 && words.len() != 24 {
+        return Err(error::invalid_mnemonic());
+    }
+    let mnemonic = bip39::Mnemonic::parse_normalized(&words.join(" "))
+        .map_err(|_| error::invalid_mnemonic())?;
+    Ok(mnemonic.to_seed_normalized(""))
+}
+
+fn from_seed(tag: KeyTag, seed: &[u8]) -> error::Result<Keypair> {
+    let network = tag.network;
+    match tag.key_type {
+        KeyType::Ed25519 => Ok(Keypair::Ed25519(
+            ExtendedSecret::from_seed(seed)
+                .derive(&DerivationPath::account(0)?)
+                .to_keypair(network),
+        )),
+        KeyType::EccCompact => master_secret(NIST256P1_SEED_KEY, seed, |secret| {
+            ecc_compact::Keypair::from_candidate(network, secret)
+        })
+        .map(Keypair::EccCompact),
+        KeyType::Secp256k1 => master_secret(SECP256K1_SEED_KEY, seed, |secret| {
+            secp256k1::Keypair::from_secret(network, secret)
+        })
+        .map(Keypair::Secp256k1),
+        KeyType::Schnorr => master_secret(SECP256K1_SEED_KEY, seed, |secret| {
+            schnorr::Keypair::from_secret(network, secret)
+        })
+        .map(Keypair::Schnorr),
+        KeyType::Multisig
+        | KeyType::P384
+        | KeyType::Secp256r1
+        | KeyType::Sr25519
+        | KeyType::Rsa
+        | KeyType::Other(_) => Err(error::unsupported_keytype(tag.key_type)),
+    }
+}
+
+/// Derives the SLIP-0010 master secret for a weierstrass curve with the given
+/// curve seed key. A secret that is not a valid scalar for the curve is
+/// rejected by `keypair`, in which case, as specified, the HMAC output is
+/// hashed again.
+fn master_secret<K, F>(curve_key: &[u8], seed: &[u8], keypair: F) -> error::Result<K>
+where
+    F: Fn(&[u8]) -> error::Result<K>,
+{
+    let mut data = seed.to_vec();
+    loop {
+        let mut mac = Hmac::<Sha512>::new_from_slice(curve_key).expect("hmac key");
+        mac.update(&data);
+        let mut output = mac.finalize().into_bytes();
+        data[..].zeroize();
+        if let Ok(keypair) = keypair(&output[..32]) {
+            output[..].zeroize();
+            return Ok(keypair);
+        }
+        data = output.to_vec();
+        output[..].zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, recover, WordCount};
+    use crate::{entropy::EntropySource, error, KeyTag, KeyType, Network};
+    use rand::rngs::OsRng;
+
+    struct Zeros;
+
+    impl EntropySource for Zeros {
+        fn fill_entropy(&mut self, dest: &mut [u8]) -> error::Result {
+            dest.iter_mut().for_each(|byte| *byte = 0);
+            Ok(())
+        }
+    }
+
+    fn tag(key_type: KeyType) -> KeyTag {
+        KeyTag {
+            network: Network::MainNet,
+            key_type,
+        }
+    }
+
+    #[test]
+    fn phrase_vector() {
+        let (_, phrase) =
+            generate(tag(KeyType::Ed25519), WordCount::Twelve, &mut Zeros).expect("generate");
+        assert_eq!(
+            "abandon abandon abandon abandon abandon abandon \
+             abandon abandon abandon abandon abandon about",
+            phrase
+        );
+        let (_, phrase) =
+            generate(tag(KeyType::Ed25519), WordCount::TwentyFour, &mut Zeros).expect("generate");
+        assert_eq!(24, phrase.split_whitespace().count());
+        assert!(phrase.ends_with(" art"));
+    }
+
+    #[test]
+    fn roundtrip() {
+        for key_type in &[KeyType::Ed25519, KeyType::EccCompact, KeyType::Secp256k1] {
+            let (keypair, phrase) =
+                generate(tag(*key_type), WordCount::Twelve, &mut OsRng).expect("generate");
+            assert_eq!(*key_type, keypair.key_tag().key_type);
+            // Recovery is deterministic and tolerant of extra whitespace
+            let sloppy = format!("  {}\n", phrase.replace(' ', "   "));
+            assert_eq!(keypair, recover(tag(*key_type), &sloppy).expect("recover"));
+        }
+    }
+
+    #[test]
+    fn invalid_phrase() {
+        let bad_checksum = "abandon abandon abandon abandon abandon abandon \
+                            abandon abandon abandon abandon abandon abandon";
+        assert!(recover(tag(KeyType::Ed25519), bad_checksum).is_err());
+        assert!(recover(tag(KeyType::Ed25519), "abandon about").is_err());
+        assert!(recover(tag(KeyType::Multisig), "").is_err());
+    }
+}
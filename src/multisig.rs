@@ -0,0 +1,1061 @@
+//! Helium M-of-N multisig support.
+//!
+//! A multisig public key commits to its members through a digest over the
+//! member keys. Other implementations, most importantly the chain's Erlang
+//! implementation, have to arrive at the exact same digest for the same
+//! members or the resulting key, and so its address, differs. The rules are:
+//!
+//! * Every member key is encoded in its tagged binary form (see
+//!   [`PublicKey::to_bytes`]).
+//! * Member keys are sorted in ascending byte order of that binary form, which
+//!   is the order Erlang's `lists:sort/1` puts binaries in.
+//! * The sorted binaries are concatenated and hashed with SHA-256. The digest
+//!   is stored as a multihash: the `0x12` hash code and the `0x20` digest
+//!   length followed by the 32 byte digest.
+//!
+//! The binary layouts match the ones used on chain. A multisig public key is
+//! its tag byte (network and the multisig key type), the threshold `M` and
+//! member count `N` as single bytes, and the member digest. A multisig
+//! signature is the binary form of all `N` member keys in canonical order,
+//! followed by each individual signature as its member index and length as
+//! single bytes and the signature bytes, ordered by member index.
+//!
+//! A multisig signature verifies against a multisig key if its members match
+//! the key's digest and at least `M` distinct members signed the message.
+//! Members sign with [`sign_partial`], and [`combine`] assembles their shares
+//! into a signature once there are enough of them.
+//!
+//! Members can be multisig keys themselves, see [`Member`], for example to
+//! compose departmental keys into an organization key. Signatures for such
+//! keys are [`NestedSignature`]s and are not accepted on chain.
+use crate::{
+    error,
+    public_key::{split_tagged, tagged_len, VerifyOptions},
+    Backend, KeyTag, KeyType, Network, PublicKey, Verify,
+};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+
+/// Multihash prefix for a SHA-256 digest: hash code 0x12, length 32.
+pub const MULTIHASH_SHA256: [u8; 2] = [0x12, 0x20];
+
+/// Length of the multihash encoded member digest.
+pub const MEMBERS_DIGEST_LENGTH: usize = MULTIHASH_SHA256.len() + 32;
+
+/// Length of the binary form of a multisig public key.
+pub const MULTISIG_PUBLIC_KEY_LENGTH: usize = 3 + MEMBERS_DIGEST_LENGTH;
+
+/// An M-of-N multisig public key.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MultisigPublicKey {
+    /// The network this key is valid for
+    pub network: Network,
+    /// The number of member signatures required
+    pub m: u8,
+    /// The number of members
+    pub n: u8,
+    /// The multihash encoded member digest, see [`members_digest`]
+    pub digest: [u8; MEMBERS_DIGEST_LENGTH],
+}
+
+impl MultisigPublicKey {
+    /// Construct a multisig key requiring `m` signatures from the given
+    /// members, which may be given in any order. Fails with
+    /// `Error::MultisigDuplicateMember` if a member is given more than once,
+    /// since signatures for such a key never verify, and with
+    /// `Error::MultisigThreshold` if the threshold is zero or larger than the
+    /// number of members.
+    pub fn new(network: Network, m: u8, members: &[PublicKey]) -> error::Result<Self> {
+        let n = members.len();
+        if m == 0 || m as usize > n || n > u8::MAX as usize {
+            return Err(error::multisig_threshold(m as usize, n));
+        }
+        let mut sorted = members.to_vec();
+        sort_members(&mut sorted);
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(error::multisig_duplicate_member(&pair[0].to_string()));
+        }
+        Ok(Self {
+            network,
+            m,
+            n: n as u8,
+            digest: members_digest(members),
+        })
+    }
+
+    /// Construct a multisig key requiring `m` signatures from the given
+    /// members, some of which may be multisig keys themselves. Fails like
+    /// [`MultisigPublicKey::new`].
+    pub fn new_nested(network: Network, m: u8, members: &[Member]) -> error::Result<Self> {
+        let n = members.len();
+        if m == 0 || m as usize > n || n > u8::MAX as usize {
+            return Err(error::multisig_threshold(m as usize, n));
+        }
+        let mut sorted = members.to_vec();
+        sort_nested_members(&mut sorted);
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+            let member = match &pair[0] {
+                Member::Key(key) => key.to_string(),
+                Member::Multisig(key) => key.to_string(),
+            };
+            return Err(error::multisig_duplicate_member(&member));
+        }
+        Ok(Self {
+            network,
+            m,
+            n: n as u8,
+            digest: nested_members_digest(members),
+        })
+    }
+
+    /// Start building a multisig key requiring `m` signatures, see
+    /// [`MultisigKeyBuilder`].
+    pub fn builder(network: Network, m: u8) -> MultisigKeyBuilder {
+        MultisigKeyBuilder {
+            network,
+            m,
+            members: vec![],
+        }
+    }
+
+    /// Convert the key to its binary form.
+    pub fn to_bytes(&self) -> [u8; MULTISIG_PUBLIC_KEY_LENGTH] {
+        let mut result = [0u8; MULTISIG_PUBLIC_KEY_LENGTH];
+        result[0] = u8::from(KeyTag {
+            network: self.network,
+            key_type: KeyType::Multisig,
+        });
+        result[1] = self.m;
+        result[2] = self.n;
+        result[3..].copy_from_slice(&self.digest);
+        result
+    }
+}
+
+/// Builds a [`MultisigPublicKey`] from members added in any order.
+#[derive(Debug, Clone)]
+pub struct MultisigKeyBuilder {
+    network: Network,
+    m: u8,
+    members: Vec<PublicKey>,
+}
+
+impl MultisigKeyBuilder {
+    /// Add a member key.
+    pub fn member(mut self, member: PublicKey) -> Self {
+        self.members.push(member);
+        self
+    }
+
+    /// Add the given member keys.
+    pub fn members<I: IntoIterator<Item = PublicKey>>(mut self, members: I) -> Self {
+        self.members.extend(members);
+        self
+    }
+
+    /// The members added so far in canonical order, which is the order of
+    /// the members of a [`MultisigSignature`] for the key.
+    pub fn sorted_members(&self) -> Vec<PublicKey> {
+        let mut members = self.members.clone();
+        sort_members(&mut members);
+        members
+    }
+
+    /// Build the key. Fails with `Error::MultisigDuplicateMember` if a member
+    /// was added more than once and with `Error::MultisigThreshold` if the
+    /// threshold is zero or larger than the number of members.
+    pub fn build(&self) -> error::Result<MultisigPublicKey> {
+        MultisigPublicKey::new(self.network, self.m, &self.members)
+    }
+}
+
+impl TryFrom<&[u8]> for MultisigPublicKey {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != MULTISIG_PUBLIC_KEY_LENGTH
+            || !matches!(KeyType::try_from(input[0]), Ok(KeyType::Multisig))
+            || input[3..5] != MULTIHASH_SHA256
+        {
+            return Err(error::invalid_multisig());
+        }
+        let (m, n) = (input[1], input[2]);
+        if m == 0 || m > n {
+            return Err(error::invalid_multisig());
+        }
+        let mut digest = [0u8; MEMBERS_DIGEST_LENGTH];
+        digest.copy_from_slice(&input[3..]);
+        Ok(Self {
+            network: Network::try_from(input[0])?,
+            m,
+            n,
+            digest,
+        })
+    }
+}
+
+impl std::str::FromStr for MultisigPublicKey {
+    type Err = error::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let data = bs58::decode(s).with_check(Some(0)).into_vec()?;
+        Self::try_from(&data[1..])
+    }
+}
+
+impl std::fmt::Display for MultisigPublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let mut data = [0u8; MULTISIG_PUBLIC_KEY_LENGTH + 1];
+        data[1..].copy_from_slice(&self.to_bytes());
+        f.write_str(&bs58::encode(data.as_ref()).with_check().into_string())
+    }
+}
+
+impl Verify for MultisigPublicKey {
+    /// The signature is the binary form of a [`MultisigSignature`], or of a
+    /// [`NestedSignature`] if some members are multisig keys themselves, see
+    /// [`MultisigSignature::verify_for`].
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        verify_at(self, msg, signature, options, &mut Vec::new())
+    }
+}
+
+/// The signature of a single member in a multisig signature.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MemberSignature {
+    /// The index of the member in the canonical member order
+    pub index: u8,
+    /// The member's signature
+    pub signature: Vec<u8>,
+}
+
+impl MemberSignature {
+    /// Verify this signature against the given payload, looking up the member
+    /// key by index in the given members, which must be in canonical order.
+    pub fn verify(&self, members: &[PublicKey], payload: &[u8]) -> error::Result {
+        let member = members
+            .get(self.index as usize)
+            .ok_or_else(error::invalid_multisig)?;
+        verify_share(member, payload, &self.signature)
+    }
+}
+
+/// A multisig signature, holding all member keys and the signatures of some
+/// of them.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MultisigSignature {
+    /// All member keys, in canonical order
+    pub members: Vec<PublicKey>,
+    /// Member signatures, ordered by member index
+    pub signatures: Vec<MemberSignature>,
+}
+
+impl MultisigSignature {
+    /// Assemble a multisig signature, putting the members in canonical order
+    /// and the signatures in index order. Signature indexes refer to the
+    /// canonical member order.
+    pub fn new(mut members: Vec<PublicKey>, mut signatures: Vec<MemberSignature>) -> Self {
+        sort_members(&mut members);
+        signatures.sort_by_key(|signature| signature.index);
+        Self {
+            members,
+            signatures,
+        }
+    }
+
+    /// Parse the binary form of a signature for a key with `n` members.
+    pub fn from_bytes(n: u8, input: &[u8]) -> error::Result<Self> {
+        let mut rest = input;
+        let mut members = Vec::with_capacity(n as usize);
+        for _ in 0..n {
+            let (key, tail) = split_tagged(rest).map_err(|_| error::invalid_multisig())?;
+            members.push(PublicKey::try_from(key)?);
+            rest = tail;
+        }
+        let mut signatures = vec![];
+        while let [index, len, tail @ ..] = rest {
+            let len = *len as usize;
+            if tail.len() < len || *index >= n {
+                return Err(error::invalid_multisig());
+            }
+            signatures.push(MemberSignature {
+                index: *index,
+                signature: tail[..len].to_vec(),
+            });
+            rest = &tail[len..];
+        }
+        if !rest.is_empty() {
+            return Err(error::invalid_multisig());
+        }
+        Ok(Self {
+            members,
+            signatures,
+        })
+    }
+
+    /// Verify this signature for the given multisig key. The members must be
+    /// in canonical order and match the key's digest, and at least `M`
+    /// member signatures, each from a different member, must be valid.
+    /// Invalid member signatures do not count towards the threshold but are
+    /// not an error by themselves.
+    pub fn verify_for(
+        &self,
+        key: &MultisigPublicKey,
+        msg: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        let sorted = self
+            .members
+            .windows(2)
+            .all(|pair| pair[0].to_bytes() < pair[1].to_bytes());
+        let unique_indexes = self
+            .signatures
+            .windows(2)
+            .all(|pair| pair[0].index < pair[1].index);
+        if self.members.len() != key.n as usize
+            || !sorted
+            || !unique_indexes
+            || members_digest(&self.members) != key.digest
+        {
+            return Err(error::invalid_multisig());
+        }
+        let valid = self
+            .signatures
+            .iter()
+            .filter(|signature| {
+                matches!(self.members.get(signature.index as usize),
+                    Some(member) if member
+                        .verify_with_options(msg, &signature.signature, options)
+                        .is_ok())
+            })
+            .count();
+        if valid < key.m as usize {
+            return Err(signature::Error::new().into());
+        }
+        Ok(())
+    }
+
+    /// Convert the signature to its binary form. Member signatures longer
+    /// than 255 bytes can't be encoded and fail with
+    /// [`DecodeError::Multisig`](crate::error::DecodeError::Multisig).
+    pub fn to_vec(&self) -> error::Result<Vec<u8>> {
+        let mut result = Vec::with_capacity(
+            self.members
+                .iter()
+                .map(PublicKey::encoded_len)
+                .sum::<usize>()
+                + self
+                    .signatures
+                    .iter()
+                    .map(|signature| 2 + signature.signature.len())
+                    .sum::<usize>(),
+        );
+        for member in &self.members {
+            result.extend_from_slice(&member.to_bytes());
+        }
+        for signature in &self.signatures {
+            let len =
+                u8::try_from(signature.signature.len()).map_err(|_| error::invalid_multisig())?;
+            result.push(signature.index);
+            result.push(len);
+            result.extend_from_slice(&signature.signature);
+        }
+        Ok(result)
+    }
+}
+
+/// The deepest multisig keys can be nested, counting the outermost key.
+pub const MAX_NESTING_DEPTH: usize = 4;
+
+/// A member of a multisig key, which may be a multisig key itself.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Member {
+    Key(PublicKey),
+    Multisig(MultisigPublicKey),
+}
+
+impl Member {
+    /// Convert the member key to its binary form.
+    pub fn to_vec(&self) -> Vec<u8> {
+        match self {
+            Self::Key(key) => key.to_vec(),
+            Self::Multisig(key) => key.to_bytes().to_vec(),
+        }
+    }
+
+    /// Parses the member key at the start of the given input, which is as long
+    /// as its key type needs, and returns the rest of the input.
+    fn parse(input: &[u8]) -> error::Result<(Self, &[u8])> {
+        let tag = *input.first().ok_or_else(error::invalid_multisig)?;
+        let nested = matches!(KeyType::try_from(tag), Ok(KeyType::Multisig));
+        let len = if nested {
+            MULTISIG_PUBLIC_KEY_LENGTH
+        } else {
+            tagged_len(tag)
+        };
+        if input.len() < len {
+            return Err(error::invalid_multisig());
+        }
+        let (key, rest) = input.split_at(len);
+        let member = if nested {
+            Self::Multisig(MultisigPublicKey::try_from(key)?)
+        } else {
+            Self::Key(PublicKey::try_from(key)?)
+        };
+        Ok((member, rest))
+    }
+
+    fn verify_at(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+        path: &mut Vec<[u8; MEMBERS_DIGEST_LENGTH]>,
+    ) -> error::Result {
+        match self {
+            Self::Key(key) => key.verify_with_options(msg, signature, options),
+            Self::Multisig(key) => verify_at(key, msg, signature, options, path),
+        }
+    }
+}
+
+impl From<PublicKey> for Member {
+    fn from(v: PublicKey) -> Self {
+        Self::Key(v)
+    }
+}
+
+impl From<MultisigPublicKey> for Member {
+    fn from(v: MultisigPublicKey) -> Self {
+        Self::Multisig(v)
+    }
+}
+
+/// A signature for a multisig key with multisig members. The layout is the
+/// one of [`MultisigSignature`], except that members are in their own binary
+/// form, so multisig members take 37 bytes, and that signature lengths are
+/// two big endian bytes, since the signature of a multisig member is a
+/// multisig signature itself.
+///
+/// Keys without multisig members always use the [`MultisigSignature`] form.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NestedSignature {
+    /// All member keys, in canonical order
+    pub members: Vec<Member>,
+    /// Member signatures, ordered by member index
+    pub signatures: Vec<MemberSignature>,
+}
+
+impl NestedSignature {
+    /// Assemble a nested signature, putting the members in canonical order
+    /// and the signatures in index order. Signature indexes refer to the
+    /// canonical member order.
+    pub fn new(mut members: Vec<Member>, mut signatures: Vec<MemberSignature>) -> Self {
+        sort_nested_members(&mut members);
+        signatures.sort_by_key(|signature| signature.index);
+        Self {
+            members,
+            signatures,
+        }
+    }
+
+    /// Parse the binary form of a signature for a key with `n` members.
+    pub fn from_bytes(n: u8, input: &[u8]) -> error::Result<Self> {
+        let mut rest = input;
+        let mut members = Vec::with_capacity(n as usize);
+        for _ in 0..n {
+            let (member, tail) = Member::parse(rest)?;
+            members.push(member);
+            rest = tail;
+        }
+        let mut signatures = vec![];
+        while let [index, len_hi, len_lo, tail @ ..] = rest {
+            let len = u16::from_be_bytes([*len_hi, *len_lo]) as usize;
+            if tail.len() < len || *index >= n {
+                return Err(error::invalid_multisig());
+            }
+            signatures.push(MemberSignature {
+                index: *index,
+                signature: tail[..len].to_vec(),
+            });
+            rest = &tail[len..];
+        }
+        if !rest.is_empty() {
+            return Err(error::invalid_multisig());
+        }
+        Ok(Self {
+            members,
+            signatures,
+        })
+    }
+
+    /// Verify this signature for the given multisig key, with the same rules
+    /// as [`MultisigSignature::verify_for`] at every level. Keys nested deeper
+    /// than [`MAX_NESTING_DEPTH`] or that contain themselves are rejected.
+    pub fn verify_for(
+        &self,
+        key: &MultisigPublicKey,
+        msg: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        self.verify_members(key, msg, options, &mut vec![key.digest])
+    }
+
+    fn verify_members(
+        &self,
+        key: &MultisigPublicKey,
+        msg: &[u8],
+        options: &VerifyOptions,
+        path: &mut Vec<[u8; MEMBERS_DIGEST_LENGTH]>,
+    ) -> error::Result {
+        let sorted = self
+            .members
+            .windows(2)
+            .all(|pair| pair[0].to_vec() < pair[1].to_vec());
+        let unique_indexes = self
+            .signatures
+            .windows(2)
+            .all(|pair| pair[0].index < pair[1].index);
+        if self.members.len() != key.n as usize
+            || !sorted
+            || !unique_indexes
+            || nested_members_digest(&self.members) != key.digest
+        {
+            return Err(error::invalid_multisig());
+        }
+        let valid = self
+            .signatures
+            .iter()
+            .filter(|signature| {
+                matches!(self.members.get(signature.index as usize),
+                    Some(member) if member
+                        .verify_at(msg, &signature.signature, options, path)
+                        .is_ok())
+            })
+            .count();
+        if valid < key.m as usize {
+            return Err(signature::Error::new().into());
+        }
+        Ok(())
+    }
+
+    /// Convert the signature to its binary form.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![];
+        for member in &self.members {
+            result.extend_from_slice(&member.to_vec());
+        }
+        for signature in &self.signatures {
+            result.push(signature.index);
+            result.extend_from_slice(&(signature.signature.len() as u16).to_be_bytes());
+            result.extend_from_slice(&signature.signature);
+        }
+        result
+    }
+}
+
+/// Whether the given signature for a key with `n` members is in the nested
+/// form, which is when any of its members is a multisig key.
+fn is_nested(n: u8, signature: &[u8]) -> bool {
+    let mut offset = 0;
+    for _ in 0..n {
+        match signature.get(offset).map(|tag| KeyType::try_from(*tag)) {
+            Some(Ok(KeyType::Multisig)) => return true,
+            Some(_) => offset += tagged_len(signature[offset]),
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Verifies a signature for the given key, which is nested in the keys with
+/// the digests in the given path.
+fn verify_at(
+    key: &MultisigPublicKey,
+    msg: &[u8],
+    signature: &[u8],
+    options: &VerifyOptions,
+    path: &mut Vec<[u8; MEMBERS_DIGEST_LENGTH]>,
+) -> error::Result {
+    if path.len() >= MAX_NESTING_DEPTH || path.contains(&key.digest) {
+        return Err(error::invalid_multisig());
+    }
+    if !is_nested(key.n, signature) {
+        return MultisigSignature::from_bytes(key.n, signature)?.verify_for(key, msg, options);
+    }
+    let signature = NestedSignature::from_bytes(key.n, signature)?;
+    path.push(key.digest);
+    let result = signature.verify_members(key, msg, options, path);
+    path.pop();
+    result
+}
+
+/// Verify a single member's signature share over the payload. Members sign
+/// the payload itself, so a coordinator can check each share as it is
+/// submitted instead of finding a bad one when assembling the multisig
+/// signature.
+pub fn verify_share(member: &PublicKey, payload: &[u8], share: &[u8]) -> error::Result {
+    member.verify(payload, share)
+}
+
+/// Sign the given message as one member of the given members, which may be
+/// given in any order. The returned share refers to the member by its index
+/// in the canonical member order.
+pub fn sign_partial<B>(
+    signer: &B,
+    members: &[PublicKey],
+    msg: &[u8],
+) -> error::Result<MemberSignature>
+where
+    B: Backend + ?Sized,
+{
+    let mut members = members.to_vec();
+    sort_members(&mut members);
+    let index = members
+        .iter()
+        .position(|member| member == signer.public_key())
+        .ok_or_else(error::invalid_multisig)?;
+    Ok(MemberSignature {
+        index: index as u8,
+        signature: signer.sign(msg)?,
+    })
+}
+
+/// Combine member signature shares into a multisig signature for the given
+/// key. The members, in any order, must match the key, and there have to be
+/// at least `M` shares, each from a different member. The shares themselves
+/// are not verified here, see [`verify_share`].
+pub fn combine(
+    key: &MultisigPublicKey,
+    mut members: Vec<PublicKey>,
+    mut shares: Vec<MemberSignature>,
+) -> error::Result<MultisigSignature> {
+    if members.len() != key.n as usize || members_digest(&members) != key.digest {
+        return Err(error::invalid_multisig());
+    }
+    sort_members(&mut members);
+    shares.sort_by_key(|share| share.index);
+    let duplicates = shares.windows(2).any(|pair| pair[0].index == pair[1].index);
+    let invalid = shares
+        .iter()
+        .any(|share| share.index >= key.n || share.signature.len() > u8::MAX as usize);
+    if duplicates || invalid || shares.len() < key.m as usize {
+        return Err(error::invalid_multisig());
+    }
+    Ok(MultisigSignature {
+        members,
+        signatures: shares,
+    })
+}
+
+/// Sort the given member keys into the canonical member order.
+pub fn sort_members(members: &mut [PublicKey]) {
+    members.sort_by_cached_key(PublicKey::to_bytes)
+}
+
+/// Sort the given members, which may include multisig keys, into the
+/// canonical member order.
+pub fn sort_nested_members(members: &mut [Member]) {
+    members.sort_by_cached_key(Member::to_vec)
+}
+
+/// Calculate the multihash encoded digest over the given member keys. The
+/// digest does not depend on the order the members are given in.
+pub fn members_digest(members: &[PublicKey]) -> [u8; MEMBERS_DIGEST_LENGTH] {
+    digest_sorted(members.iter().map(PublicKey::to_bytes).collect())
+}
+
+/// Calculate the member digest for members that may include multisig keys.
+/// Members are hashed in their binary form by the same rules, so the digest
+/// of members that are all single keys is the one of [`members_digest`].
+pub fn nested_members_digest(members: &[Member]) -> [u8; MEMBERS_DIGEST_LENGTH] {
+    digest_sorted(members.iter().map(Member::to_vec).collect())
+}
+
+fn digest_sorted<T: AsRef<[u8]> + Ord>(mut member_bytes: Vec<T>) -> [u8; MEMBERS_DIGEST_LENGTH] {
+    member_bytes.sort();
+    let mut hasher = Sha256::new();
+    for bytes in &member_bytes {
+        hasher.update(bytes);
+    }
+    let mut result = [0u8; MEMBERS_DIGEST_LENGTH];
+    result[..MULTIHASH_SHA256.len()].copy_from_slice(&MULTIHASH_SHA256);
+    result[MULTIHASH_SHA256.len()..].copy_from_slice(&hasher.finalize());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        combine, members_digest, nested_members_digest, sign_partial, sort_members,
+        sort_nested_members, verify_share, Member, MemberSignature, MultisigPublicKey,
+        MultisigSignature, NestedSignature, MAX_NESTING_DEPTH,
+    };
+    use crate::{ed25519, Network, PublicKey, Sign};
+    use hex_literal::hex;
+    use std::convert::TryFrom;
+
+    const MEMBERS: [&str; 3] = [
+        "13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw",
+        "14HZVR4bdF9QMowYxWrumcFBNfWnhDdD5XXA5za1fWwUhHxxFS1",
+        "11nYr7TBMbpGiQadiCxGCPZFZ8ENo1JNtbS7aB5U7UXn4a8Dvb3",
+    ];
+
+    fn members() -> Vec<PublicKey> {
+        MEMBERS
+            .iter()
+            .map(|s| s.parse().expect("public key"))
+            .collect()
+    }
+
+    #[test]
+    fn sort() {
+        let mut members = members();
+        sort_members(&mut members);
+        let sorted: Vec<Vec<u8>> = members.iter().map(PublicKey::to_vec).collect();
+        assert_eq!(
+            vec![
+                hex!("00676cbd3ae59d2bd53c159914063c522b6b510b478b4a6bc6c2f013a8f8438f10").to_vec(),
+                hex!("014b5587796832132f039f4ca97f48c0592eda0e381a4d42aad290ad728d26ec66").to_vec(),
+                hex!("01b0b0af9fa148aa744be8c22f080b3bc2ba1489ff9bb3436d1ad35b37af744e27").to_vec(),
+            ],
+            sorted
+        );
+    }
+
+    #[test]
+    fn digest() {
+        let mut members = members();
+        let expected = hex!("1220f52609c6a2662bc9d3483241c464cdf38091b5cc1d7a19f669ea216c1c46271d");
+        assert_eq!(expected, members_digest(&members));
+        members.reverse();
+        assert_eq!(expected, members_digest(&members));
+        let members: Vec<Member> = members.into_iter().map(Member::from).collect();
+        assert_eq!(expected, nested_members_digest(&members));
+    }
+
+    #[test]
+    fn public_key_layout() {
+        const B58: &str = "1SYKS6ExNrbYnPx8aMCouvy6CqDKdypm9xkVPteFdGdzeqgRhbDmfRCc";
+        let key = MultisigPublicKey::new(Network::MainNet, 2, &members()).expect("multisig key");
+        assert_eq!(
+            hex!("0202031220f52609c6a2662bc9d3483241c464cdf38091b5cc1d7a19f669ea216c1c46271d"),
+            key.to_bytes()
+        );
+        assert_eq!(
+            key,
+            MultisigPublicKey::try_from(&key.to_bytes()[..]).expect("key")
+        );
+        assert_eq!(B58, key.to_string());
+        assert_eq!(key, B58.parse().expect("b58 key"));
+        assert!(MultisigPublicKey::new(Network::MainNet, 4, &members()).is_err());
+        let mut duplicate = members();
+        duplicate.push(duplicate[0].clone());
+        assert!(matches!(
+            MultisigPublicKey::new(Network::MainNet, 2, &duplicate),
+            Err(crate::error::Error::MultisigDuplicateMember(_))
+        ));
+    }
+
+    #[test]
+    fn builder() {
+        let key = MultisigPublicKey::new(Network::MainNet, 2, &members()).expect("multisig key");
+        let mut reversed = members();
+        reversed.reverse();
+        let builder = MultisigPublicKey::builder(Network::MainNet, 2).members(reversed);
+        assert_eq!(key, builder.build().expect("multisig key"));
+        let mut sorted = members();
+        sort_members(&mut sorted);
+        assert_eq!(sorted, builder.sorted_members());
+
+        let duplicate = builder.clone().member(members()[1].clone());
+        assert!(matches!(
+            duplicate.build(),
+            Err(crate::error::Error::MultisigDuplicateMember(_))
+        ));
+        assert!(MultisigPublicKey::builder(Network::MainNet, 0)
+            .members(members())
+            .build()
+            .is_err());
+        assert!(MultisigPublicKey::builder(Network::MainNet, 4)
+            .members(members())
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn verify() {
+        use crate::Verify;
+        use rand::rngs::OsRng;
+        let keypairs: Vec<ed25519::Keypair> = (0..3)
+            .map(|_| ed25519::Keypair::generate(Network::MainNet, &mut OsRng))
+            .collect();
+        let mut members: Vec<PublicKey> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key.clone())
+            .collect();
+        sort_members(&mut members);
+        let key = MultisigPublicKey::new(Network::MainNet, 2, &members).expect("multisig key");
+        let share = |keypair: &ed25519::Keypair, msg: &[u8]| MemberSignature {
+            index: members
+                .iter()
+                .position(|member| member == &keypair.public_key)
+                .expect("member") as u8,
+            signature: keypair.sign(msg).expect("signature"),
+        };
+        let signature = MultisigSignature::new(
+            members.clone(),
+            vec![share(&keypairs[0], b"msg"), share(&keypairs[2], b"msg")],
+        );
+        let bytes = signature.to_vec().expect("binary signature");
+        assert!(key.verify(b"msg", &bytes).is_ok());
+        assert!(key.verify(b"other", &bytes).is_err());
+
+        // A bad share does not count towards the threshold
+        let signature = MultisigSignature::new(
+            members.clone(),
+            vec![share(&keypairs[0], b"msg"), share(&keypairs[1], b"other")],
+        );
+        let bytes = signature.to_vec().expect("binary signature");
+        assert!(key.verify(b"msg", &bytes).is_err());
+
+        // Nor does signing twice
+        let signature = MultisigSignature::new(
+            members.clone(),
+            vec![share(&keypairs[0], b"msg"), share(&keypairs[0], b"msg")],
+        );
+        let bytes = signature.to_vec().expect("binary signature");
+        assert!(key.verify(b"msg", &bytes).is_err());
+
+        // Members must match the key
+        let mut others = members.clone();
+        others[0] = ed25519::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        let signature = MultisigSignature::new(
+            others,
+            vec![share(&keypairs[1], b"msg"), share(&keypairs[2], b"msg")],
+        );
+        let bytes = signature.to_vec().expect("binary signature");
+        assert!(key.verify(b"msg", &bytes).is_err());
+    }
+
+    #[test]
+    fn share() {
+        use rand::rngs::OsRng;
+        let keypairs: Vec<ed25519::Keypair> = (0..3)
+            .map(|_| ed25519::Keypair::generate(Network::MainNet, &mut OsRng))
+            .collect();
+        let mut members: Vec<PublicKey> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key.clone())
+            .collect();
+        sort_members(&mut members);
+        let signature = keypairs[0].sign(b"payload").expect("signature");
+        assert!(verify_share(&keypairs[0].public_key, b"payload", &signature).is_ok());
+        assert!(verify_share(&keypairs[1].public_key, b"payload", &signature).is_err());
+        assert!(verify_share(&keypairs[0].public_key, b"other", &signature).is_err());
+        let index = members
+            .iter()
+            .position(|member| member == &keypairs[0].public_key)
+            .expect("member") as u8;
+        let share = MemberSignature { index, signature };
+        assert!(share.verify(&members, b"payload").is_ok());
+        let wrong_index = MemberSignature {
+            index: (index + 1) % 3,
+            ..share.clone()
+        };
+        assert!(wrong_index.verify(&members, b"payload").is_err());
+        let out_of_range = MemberSignature { index: 3, ..share };
+        assert!(out_of_range.verify(&members, b"payload").is_err());
+    }
+
+    #[test]
+    fn combine_shares() {
+        use crate::Verify;
+        use rand::rngs::OsRng;
+        let keypairs: Vec<ed25519::Keypair> = (0..3)
+            .map(|_| ed25519::Keypair::generate(Network::MainNet, &mut OsRng))
+            .collect();
+        // Members in signing order rather than canonical order
+        let members: Vec<PublicKey> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key.clone())
+            .collect();
+        let key = MultisigPublicKey::new(Network::MainNet, 2, &members).expect("multisig key");
+        let shares: Vec<MemberSignature> = keypairs
+            .iter()
+            .map(|keypair| sign_partial(keypair, &members, b"msg").expect("share"))
+            .collect();
+        let mut sorted = members.clone();
+        sort_members(&mut sorted);
+        for share in &shares {
+            assert!(share.verify(&sorted, b"msg").is_ok());
+        }
+
+        let signature = combine(
+            &key,
+            members.clone(),
+            vec![shares[2].clone(), shares[0].clone()],
+        )
+        .expect("signature");
+        let bytes = signature.to_vec().expect("binary signature");
+        assert!(key.verify(b"msg", &bytes).is_ok());
+        // Below the threshold, with a duplicate signer and for other members
+        assert!(combine(&key, members.clone(), vec![shares[1].clone()]).is_err());
+        assert!(combine(
+            &key,
+            members.clone(),
+            vec![shares[1].clone(), shares[1].clone()]
+        )
+        .is_err());
+        assert!(combine(&key, members[..2].to_vec(), shares.clone()).is_err());
+
+        let outsider = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        assert!(sign_partial(&outsider, &members, b"msg").is_err());
+    }
+
+    #[test]
+    fn nested() {
+        use crate::Verify;
+        use rand::rngs::OsRng;
+        let keypairs: Vec<ed25519::Keypair> = (0..3)
+            .map(|_| ed25519::Keypair::generate(Network::MainNet, &mut OsRng))
+            .collect();
+        let department_members: Vec<PublicKey> = keypairs[..2]
+            .iter()
+            .map(|keypair| keypair.public_key.clone())
+            .collect();
+        let department = MultisigPublicKey::new(Network::MainNet, 2, &department_members)
+            .expect("department key");
+        let members = vec![
+            Member::from(department.clone()),
+            Member::from(keypairs[2].public_key.clone()),
+        ];
+        let key = MultisigPublicKey::new_nested(Network::MainNet, 2, &members).expect("key");
+        let duplicate = vec![members[0].clone(), members[0].clone()];
+        assert!(matches!(
+            MultisigPublicKey::new_nested(Network::MainNet, 1, &duplicate),
+            Err(crate::error::Error::MultisigDuplicateMember(_))
+        ));
+
+        let department_signature = combine(
+            &department,
+            department_members.clone(),
+            keypairs[..2]
+                .iter()
+                .map(|keypair| sign_partial(keypair, &department_members, b"msg").expect("share"))
+                .collect(),
+        )
+        .expect("department signature");
+        let mut sorted = members.clone();
+        sort_nested_members(&mut sorted);
+        let index =
+            |member: &Member| sorted.iter().position(|m| m == member).expect("member") as u8;
+        let signature = NestedSignature::new(
+            members.clone(),
+            vec![
+                MemberSignature {
+                    index: index(&members[0]),
+                    signature: department_signature.to_vec().expect("department bytes"),
+                },
+                MemberSignature {
+                    index: index(&members[1]),
+                    signature: keypairs[2].sign(b"msg").expect("signature"),
+                },
+            ],
+        );
+        let bytes = signature.to_vec();
+        assert_eq!(
+            signature,
+            NestedSignature::from_bytes(2, &bytes).expect("signature")
+        );
+        assert!(key.verify(b"msg", &bytes).is_ok());
+        assert!(key.verify(b"other", &bytes).is_err());
+
+        // The department signature alone is not enough
+        let department_index = index(&members[0]);
+        let shares = signature
+            .signatures
+            .into_iter()
+            .filter(|share| share.index == department_index)
+            .collect();
+        let signature = NestedSignature::new(members, shares);
+        assert!(key.verify(b"msg", &signature.to_vec()).is_err());
+    }
+
+    #[test]
+    fn nesting_depth() {
+        use crate::Verify;
+        use rand::rngs::OsRng;
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let mut key = MultisigPublicKey::new(Network::MainNet, 1, &[keypair.public_key.clone()])
+            .expect("key");
+        let mut signature = MultisigSignature::new(
+            vec![keypair.public_key.clone()],
+            vec![sign_partial(&keypair, &[keypair.public_key.clone()], b"msg").expect("share")],
+        )
+        .to_vec()
+        .expect("binary signature");
+        for depth in 1..=MAX_NESTING_DEPTH + 1 {
+            assert_eq!(
+                depth <= MAX_NESTING_DEPTH,
+                key.verify(b"msg", &signature).is_ok()
+            );
+            let members = vec![Member::from(key)];
+            key = MultisigPublicKey::new_nested(Network::MainNet, 1, &members).expect("key");
+            signature = NestedSignature::new(
+                members,
+                vec![MemberSignature {
+                    index: 0,
+                    signature,
+                }],
+            )
+            .to_vec();
+        }
+    }
+
+    #[test]
+    fn signature_layout() {
+        let signature = MultisigSignature::new(
+            members(),
+            vec![
+                MemberSignature {
+                    index: 2,
+                    signature: vec![0xbb; 64],
+                },
+                MemberSignature {
+                    index: 0,
+                    signature: vec![0xaa; 70],
+                },
+            ],
+        );
+        let bytes = signature.to_vec().expect("binary signature");
+        assert_eq!(3 * 33 + 2 + 70 + 2 + 64, bytes.len());
+        // The first member is the ecc_compact key, which sorts first
+        assert_eq!(0x00, bytes[0]);
+        assert_eq!([0x00, 70, 0xaa], bytes[99..102]);
+        assert_eq!([0x02, 64, 0xbb], bytes[99 + 72..99 + 75]);
+        assert_eq!(
+            signature,
+            MultisigSignature::from_bytes(3, &bytes).expect("signature")
+        );
+        assert!(MultisigSignature::from_bytes(3, &bytes[..bytes.len() - 1]).is_err());
+
+        // Member signatures must fit the one byte length prefix
+        let too_long = MultisigSignature::new(
+            members(),
+            vec![MemberSignature {
+                index: 0,
+                signature: vec![0xaa; 256],
+            }],
+        );
+        assert!(matches!(
+            too_long.to_vec(),
+            Err(crate::error::Error::Decode(
+                crate::error::DecodeError::Multisig
+            ))
+        ));
+    }
+}
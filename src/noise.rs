@@ -0,0 +1,216 @@
+//! Noise protocol static keys backed by crate keypairs, so Noise transports
+//! like libp2p's can authenticate with a Helium identity key directly rather
+//! than with a separate transport key.
+//!
+//! ed25519 identities use the `25519` DH function with the X25519 form of the
+//! key, converted as for [sealed boxes](crate::sealed_box). ecc_compact
+//! identities use a `P256` DH function with 65 byte SEC1 uncompressed public
+//! keys and the x coordinate of the shared point as the DH output. P-256
+//! keys only need raw Diffie-Hellman from their backend, the [`RawDh`] hook,
+//! so keys held in a secure element can be static keys too.
+//!
+//! A handshake only proves that the peer holds the secret for its remote
+//! static key. Compare that key with [`static_public_key`] of the identity
+//! the peer is expected to have to authenticate it.
+use crate::{ecc_compact, ecdh, ed25519, error, Backend, KeyType, Keypair, PublicKey};
+use curve25519_dalek::{montgomery::MontgomeryPoint, scalar::Scalar};
+use std::{convert::TryFrom, sync::Arc};
+use zeroize::Zeroize;
+
+/// The Noise name of the X25519 DH function.
+pub const DH_25519: &str = "25519";
+/// The Noise name of the P-256 DH function.
+pub const DH_P256: &str = "P256";
+
+/// Length of `25519` public keys.
+pub const X25519_KEY_LENGTH: usize = 32;
+
+/// Raw P-256 Diffie-Hellman with the secret key of a backend.
+pub trait RawDh: Backend {
+    /// The x coordinate of the product of the secret key and the given peer
+    /// point. The peer point does not need a compact form.
+    fn raw_dh(&self, peer: &p256::PublicKey) -> error::Result<ecdh::SharedSecret>;
+}
+
+impl RawDh for ecc_compact::Keypair {
+    fn raw_dh(&self, peer: &p256::PublicKey) -> error::Result<ecdh::SharedSecret> {
+        let secret = self.secret_key();
+        let shared = p256::ecdh::diffie_hellman(secret.secret_scalar(), peer.as_affine());
+        Ok(ecdh::SharedSecret::from_slice(shared.as_bytes()))
+    }
+}
+
+#[cfg(feature = "ecc608")]
+impl<I, D, E> RawDh for crate::ecc608::Keypair<I, D>
+where
+    I: embedded_hal::blocking::i2c::Read<Error = E>
+        + embedded_hal::blocking::i2c::Write<Error = E>
+        + Send,
+    D: embedded_hal::blocking::delay::DelayMs<u32> + Send,
+{
+    fn raw_dh(&self, peer: &p256::PublicKey) -> error::Result<ecdh::SharedSecret> {
+        self.diffie_hellman(peer)
+    }
+}
+
+/// A Noise static key.
+pub struct StaticKey {
+    public_key: Vec<u8>,
+    inner: Inner,
+}
+
+enum Inner {
+    X25519(X25519Secret),
+    P256(Arc<dyn RawDh>),
+}
+
+/// Zeroizes the clamped X25519 secret on drop.
+struct X25519Secret([u8; 32]);
+
+impl Drop for X25519Secret {
+    fn drop(&mut self) {
+        self.0[..].zeroize();
+    }
+}
+
+impl StaticKey {
+    /// The static key for the given keypair. Only ed25519 and ecc_compact
+    /// keypairs can be static keys.
+    pub fn from_keypair(keypair: Keypair) -> error::Result<Self> {
+        match keypair {
+            Keypair::Ed25519(keypair) => Ok(Self::from_ed25519(&keypair)),
+            Keypair::EccCompact(keypair) => Self::from_backend(keypair),
+            Keypair::Secp256k1(_) | Keypair::Schnorr(_) => {
+                Err(error::unsupported_keytype(keypair.key_tag().key_type))
+            }
+        }
+    }
+
+    /// The `25519` static key for the given ed25519 keypair.
+    pub fn from_ed25519(keypair: &ed25519::Keypair) -> Self {
+        let public_key = ed25519::PublicKey::try_from(&keypair.public_key)
+            .expect("ed25519 public key")
+            .to_x25519();
+        Self {
+            public_key: public_key.to_vec(),
+            inner: Inner::X25519(X25519Secret(keypair.to_x25519_secret())),
+        }
+    }
+
+    /// The `P256` static key for the given backend.
+    pub fn from_backend<B: RawDh + 'static>(backend: B) -> error::Result<Self> {
+        Self::from_arc(Arc::new(backend))
+    }
+
+    /// The `P256` static key for a shared backend.
+    pub fn from_arc(backend: Arc<dyn RawDh>) -> error::Result<Self> {
+        Ok(Self {
+            public_key: static_public_key(backend.public_key())?,
+            inner: Inner::P256(backend),
+        })
+    }
+
+    /// The Noise name of the DH function of this key.
+    pub fn dh_name(&self) -> &'static str {
+        match self.inner {
+            Inner::X25519(_) => DH_25519,
+            Inner::P256(_) => DH_P256,
+        }
+    }
+
+    /// The public key sent in handshakes.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Diffie-Hellman with the given peer public key, in the form of the DH
+    /// function of this key. Peer keys that would give an all zero X25519
+    /// output are rejected, as the Noise specification allows.
+    pub fn dh(&self, peer: &[u8]) -> error::Result<ecdh::SharedSecret> {
+        match &self.inner {
+            Inner::X25519(secret) => {
+                if peer.len() != X25519_KEY_LENGTH {
+                    return Err(error::invalid_length(X25519_KEY_LENGTH, peer.len()));
+                }
+                let mut point = [0u8; X25519_KEY_LENGTH];
+                point.copy_from_slice(peer);
+                let shared = MontgomeryPoint(point) * Scalar::from_bits(secret.0);
+                if shared.as_bytes() == &[0u8; 32] {
+                    return Err(error::invalid_point(error::PointError::SmallOrder));
+                }
+                Ok(ecdh::SharedSecret::from_slice(shared.as_bytes()))
+            }
+            Inner::P256(backend) => {
+                let peer = p256::PublicKey::from_sec1_bytes(peer).map_err(error::Error::from)?;
+                backend.raw_dh(&peer)
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for StaticKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticKey")
+            .field("dh", &self.dh_name())
+            .field("public_key", &self.public_key)
+            .finish()
+    }
+}
+
+/// The Noise static public key of the given identity key, to compare with the
+/// remote static key of a handshake.
+pub fn static_public_key(public_key: &PublicKey) -> error::Result<Vec<u8>> {
+    match public_key.tag().key_type {
+        KeyType::Ed25519 => Ok(ed25519::PublicKey::try_from(public_key)?
+            .to_x25519()
+            .to_vec()),
+        KeyType::EccCompact => Ok(ecc_compact::PublicKey::try_from(public_key)?
+            .to_sec1_uncompressed()
+            .to_vec()),
+        key_type => Err(error::unsupported_keytype(key_type)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{static_public_key, StaticKey, DH_25519, DH_P256};
+    use crate::{KeyTag, KeyType, Keypair, Network};
+    use rand::rngs::OsRng;
+
+    fn keypair(key_type: KeyType) -> Keypair {
+        Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type,
+            },
+            &mut OsRng,
+        )
+        .expect("keypair")
+    }
+
+    #[test]
+    fn dh() {
+        for (key_type, name) in [(KeyType::Ed25519, DH_25519), (KeyType::EccCompact, DH_P256)] {
+            let (alice, bob) = (keypair(key_type), keypair(key_type));
+            let (alice_public, bob_public) = (
+                static_public_key(alice.public_key()).expect("alice public key"),
+                static_public_key(bob.public_key()).expect("bob public key"),
+            );
+            let alice = StaticKey::from_keypair(alice).expect("alice static key");
+            let bob = StaticKey::from_keypair(bob).expect("bob static key");
+            assert_eq!(name, alice.dh_name());
+            assert_eq!(alice_public, alice.public_key());
+            assert_eq!(bob_public, bob.public_key());
+            assert_eq!(
+                alice.dh(bob.public_key()).expect("alice secret"),
+                bob.dh(alice.public_key()).expect("bob secret")
+            );
+            assert!(alice.dh(&bob.public_key()[1..]).is_err());
+        }
+
+        // The identity of the X25519 group has small order
+        let key = StaticKey::from_keypair(keypair(KeyType::Ed25519)).expect("static key");
+        assert!(key.dh(&[0u8; 32]).is_err());
+        assert!(StaticKey::from_keypair(keypair(KeyType::Secp256k1)).is_err());
+    }
+}
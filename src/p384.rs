@@ -0,0 +1,346 @@
+//! NIST P-384 keypairs with ECDSA over SHA-384 signatures, for deployments
+//! whose compliance requirements mandate a curve stronger than P-256.
+//!
+//! A P-384 point does not fit the 33 byte binary form of a
+//! [`crate::PublicKey`], so like multisig keys these have their own
+//! [`PublicKey`] type. Its binary form is the key tag followed by the 49 byte
+//! SEC1 compressed point, and its address is the b58check encoding of that
+//! form. Keypairs are the key tag followed by the 48 byte secret scalar.
+//! Signatures are DER encoded and always produced with a low s.
+use crate::{
+    entropy::{EntropyRng, EntropySource},
+    error, keypair, output_prefix,
+    public_key::VerifyOptions,
+    IntoBytes, KeyTag, KeyType, Network, Sign, Verify,
+};
+use ::p384::ecdsa::{
+    self,
+    signature::{Signer, Verifier},
+};
+use std::convert::TryFrom;
+use zeroize::Zeroize;
+
+/// Length of the binary form of a keypair.
+pub const KEYPAIR_LENGTH: usize = 49;
+
+/// Length of the binary form of a public key.
+pub const PUBLIC_KEY_LENGTH: usize = 50;
+
+/// The maximum length of a DER encoded P-384 ECDSA signature.
+pub const MAX_SIGNATURE_LENGTH: usize = 104;
+
+/// Length of a fixed size `r || s` signature.
+pub const RAW_SIGNATURE_LENGTH: usize = 96;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PublicKey {
+    /// The network this key is valid for
+    pub network: Network,
+    inner: ecdsa::VerifyingKey,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Signature(ecdsa::Signature);
+
+pub type Keypair = keypair::Keypair<ecdsa::SigningKey, PublicKey>;
+
+/// The P-384 crate has its own signature error type, which carries nothing
+/// worth keeping.
+fn signature_error<E>(_: E) -> error::Error {
+    signature::Error::new().into()
+}
+
+impl Keypair {
+    pub fn generate<E>(network: Network, entropy: &mut E) -> Keypair
+    where
+        E: EntropySource + ?Sized,
+    {
+        Self::from_signing_key(network, ecdsa::SigningKey::random(&mut EntropyRng(entropy)))
+    }
+
+    /// Generate a keypair using the operating system random number generator.
+    pub fn generate_default(network: Network) -> Keypair {
+        Self::generate(network, &mut rand_core::OsRng)
+    }
+
+    /// Construct a keypair from a 48 byte big endian secret scalar.
+    pub fn from_secret(network: Network, secret: &[u8]) -> error::Result<Keypair> {
+        if secret.len() != KEYPAIR_LENGTH - 1 {
+            return Err(error::invalid_length(KEYPAIR_LENGTH - 1, secret.len()));
+        }
+        let inner = ecdsa::SigningKey::from_slice(secret).map_err(signature_error)?;
+        Ok(Self::from_signing_key(network, inner))
+    }
+
+    fn from_signing_key(network: Network, inner: ecdsa::SigningKey) -> Keypair {
+        Keypair {
+            network,
+            public_key: PublicKey {
+                network,
+                inner: *inner.verifying_key(),
+            },
+            inner,
+        }
+    }
+
+    /// The same keypair tagged for the given network.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self.public_key.network = network;
+        self
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        self.public_key.tag()
+    }
+
+    export_secret! {
+        fn to_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
+            let mut result = [0u8; KEYPAIR_LENGTH];
+            self.try_bytes_into(&mut result)
+                .expect("keypair length");
+            result
+        }
+    }
+
+    /// Sign the given message, returning the fixed size `r || s` form of the
+    /// signature for protocols that need fixed size signatures.
+    pub fn sign_raw(&self, msg: &[u8]) -> error::Result<[u8; RAW_SIGNATURE_LENGTH]> {
+        Ok(self.try_sign_low_s(msg)?.to_raw())
+    }
+
+    fn try_sign_low_s(&self, msg: &[u8]) -> error::Result<Signature> {
+        let signature: ecdsa::Signature = self.inner.try_sign(msg).map_err(signature_error)?;
+        Ok(Signature(signature.normalize_s().unwrap_or(signature)))
+    }
+}
+
+impl Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        Ok(self.try_sign_low_s(msg)?.to_der())
+    }
+
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        let signature = self.try_sign_low_s(msg)?;
+        keypair::copy_signature(signature.0.to_der().as_bytes(), output)
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != KEYPAIR_LENGTH {
+            return Err(error::invalid_length(KEYPAIR_LENGTH, input.len()));
+        }
+        if KeyType::try_from(input[0])? != KeyType::P384 {
+            return Err(error::invalid_keytype(input[0]));
+        }
+        Self::from_secret(Network::try_from(input[0])?, &input[1..])
+    }
+}
+
+impl IntoBytes for Keypair {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let output = output_prefix(output, KEYPAIR_LENGTH)?;
+        output[0] = u8::from(self.key_tag());
+        let mut secret = self.inner.to_bytes();
+        output[1..].copy_from_slice(&secret);
+        secret[..].zeroize();
+        Ok(KEYPAIR_LENGTH)
+    }
+}
+
+/// Keypairs never include their secret in `Debug` output.
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .field("secret", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl Signature {
+    /// Parse a DER encoded signature.
+    pub fn from_der(input: &[u8]) -> error::Result<Self> {
+        Ok(Signature(
+            ecdsa::Signature::from_der(input).map_err(signature_error)?,
+        ))
+    }
+
+    /// The DER encoding of the signature, as produced by `Sign::sign`.
+    pub fn to_der(&self) -> Vec<u8> {
+        self.0.to_der().as_bytes().to_vec()
+    }
+
+    /// Parse a fixed size `r || s` signature with both values big endian.
+    pub fn from_raw(input: &[u8]) -> error::Result<Self> {
+        Ok(Signature(
+            ecdsa::Signature::from_slice(input).map_err(signature_error)?,
+        ))
+    }
+
+    /// The fixed size `r || s` form of the signature.
+    pub fn to_raw(&self) -> [u8; RAW_SIGNATURE_LENGTH] {
+        let mut result = [0u8; RAW_SIGNATURE_LENGTH];
+        result.copy_from_slice(&self.0.to_bytes());
+        result
+    }
+}
+
+/// Parse a DER signature, or a raw one if allowed, applying the encoding rules
+/// in the given options. A high s is rejected if a low s is required, and
+/// normalized otherwise.
+fn parse_signature(input: &[u8], options: &VerifyOptions) -> error::Result<ecdsa::Signature> {
+    let signature = match ecdsa::Signature::from_der(input) {
+        Ok(signature) => {
+            if options.strict_der && signature.to_der().as_bytes() != input {
+                return Err(signature::Error::new().into());
+            }
+            signature
+        }
+        Err(_) if options.allow_raw_ecdsa && input.len() == RAW_SIGNATURE_LENGTH => {
+            ecdsa::Signature::from_slice(input).map_err(signature_error)?
+        }
+        Err(err) => return Err(signature_error(err)),
+    };
+    match signature.normalize_s() {
+        Some(_) if options.require_low_s => Err(signature::Error::new().into()),
+        Some(normalized) => Ok(normalized),
+        None => Ok(signature),
+    }
+}
+
+impl PublicKey {
+    pub fn tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::P384,
+        }
+    }
+
+    /// Convert the key to its binary form.
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        let mut result = [0u8; PUBLIC_KEY_LENGTH];
+        result[0] = u8::from(self.tag());
+        result[1..].copy_from_slice(self.inner.to_encoded_point(true).as_bytes());
+        result
+    }
+
+    /// The SEC1 encoding of the public key, compressed or not.
+    pub fn to_sec1_bytes(&self, compress: bool) -> Vec<u8> {
+        self.inner.to_encoded_point(compress).as_bytes().to_vec()
+    }
+}
+
+impl Verify for PublicKey {
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        let signature = parse_signature(signature, options)?;
+        self.inner.verify(msg, &signature).map_err(signature_error)
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != PUBLIC_KEY_LENGTH {
+            return Err(error::invalid_length(PUBLIC_KEY_LENGTH, input.len()));
+        }
+        if KeyType::try_from(input[0])? != KeyType::P384 {
+            return Err(error::invalid_keytype(input[0]));
+        }
+        let inner = ecdsa::VerifyingKey::from_sec1_bytes(&input[1..])
+            .map_err(|_| error::invalid_point(error::PointError::NotOnCurve))?;
+        Ok(Self {
+            network: Network::try_from(input[0])?,
+            inner,
+        })
+    }
+}
+
+impl std::str::FromStr for PublicKey {
+    type Err = error::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let data = bs58::decode(s).with_check(Some(0)).into_vec()?;
+        Self::try_from(&data[1..])
+    }
+}
+
+impl std::fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let mut data = [0u8; PUBLIC_KEY_LENGTH + 1];
+        data[1..].copy_from_slice(&self.to_bytes());
+        f.write_str(&bs58::encode(data.as_ref()).with_check().into_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keypair, PublicKey, Signature, KEYPAIR_LENGTH, PUBLIC_KEY_LENGTH};
+    use crate::{KeyType, Network, Sign, Verify, VerifyOptions};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify_strict(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair.public_key.verify(b"hello", &signature).is_err());
+        let other = Keypair::generate(Network::MainNet, &mut OsRng);
+        assert!(other.public_key.verify(b"hello world", &signature).is_err());
+
+        let raw = keypair.sign_raw(b"hello world").expect("raw signature");
+        assert!(keypair.public_key.verify(b"hello world", &raw).is_err());
+        let options = VerifyOptions {
+            allow_raw_ecdsa: true,
+            ..VerifyOptions::DEFAULT
+        };
+        assert!(keypair
+            .public_key
+            .verify_with_options(b"hello world", &raw, &options)
+            .is_ok());
+        let der = Signature::from_raw(&raw).expect("signature").to_der();
+        assert!(keypair.public_key.verify(b"hello world", &der).is_ok());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let bytes = keypair.to_bytes();
+        assert_eq!(KEYPAIR_LENGTH, bytes.len());
+        assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+        assert_eq!(KeyType::P384, keypair.key_tag().key_type);
+
+        let public_key = keypair.public_key.to_bytes();
+        assert_eq!(PUBLIC_KEY_LENGTH, public_key.len());
+        assert_eq!(
+            keypair.public_key,
+            PublicKey::try_from(&public_key[..]).expect("public key")
+        );
+        assert_eq!(
+            keypair.public_key,
+            keypair
+                .public_key
+                .to_string()
+                .parse::<PublicKey>()
+                .expect("public key")
+        );
+        assert!(crate::PublicKey::try_from(&public_key[..]).is_err());
+        assert!(PublicKey::try_from(&public_key[..PUBLIC_KEY_LENGTH - 1]).is_err());
+        assert!(Keypair::try_from(&bytes[..KEYPAIR_LENGTH - 1]).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! A printable encoding for backing up key material on paper.
+//!
+//! Data is encoded in base32 (RFC 4648 alphabet, no padding) in numbered lines
+//! of up to 10 bytes, written as groups of four characters. Each line ends with
+//! a two character checksum over the line number and the line's bytes, and a
+//! final line carries a CRC-32 over all of the data. The 23 bytes `0x10..0x27`
+//! encode as:
+//!
+//! ```text
+//! 01: CAIR EEYU CULB OGAZ E4
+//! 02: DINR YHI6 D4QC CIRD EQ
+//! 03: EQSS M Z4
+//! CRC: XYBU2OI
+//! ```
+//!
+//! The parser is forgiving about what people get wrong when copying by hand:
+//! case, spacing and the commonly confused digits `0`, `1` and `8` (read as
+//! `O`, `I` and `B`) are all accepted. A transcription error is reported with
+//! the number of the line it is on.
+use crate::error;
+use std::convert::TryInto;
+
+/// Number of data bytes encoded per line.
+const LINE_BYTES: usize = 10;
+/// Number of characters per group within a line.
+const GROUP_CHARS: usize = 4;
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const CRC_PREFIX: &str = "CRC:";
+
+/// Encode the given data as a printable, line oriented backup.
+pub fn encode(data: &[u8]) -> String {
+    let mut result = String::new();
+    for (index, line) in data.chunks(LINE_BYTES).enumerate() {
+        let number = index + 1;
+        let encoded = base32_encode(line);
+        result.push_str(&format!("{:02}:", number));
+        for group in encoded.as_bytes().chunks(GROUP_CHARS) {
+            result.push(' ');
+            result.push_str(std::str::from_utf8(group).expect("base32"));
+        }
+        result.push(' ');
+        result.push_str(&line_checksum(number, line));
+        result.push('\n');
+    }
+    result.push_str(CRC_PREFIX);
+    result.push(' ');
+    result.push_str(&base32_encode(&crc32(data).to_be_bytes()));
+    result.push('\n');
+    result
+}
+
+/// Decode a backup produced by `encode`. Blank lines are ignored.
+pub fn decode(input: &str) -> error::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut expected_crc = None;
+    let mut number = 0;
+    for line in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if expected_crc.is_some() {
+            // Nothing is allowed after the CRC line
+            return Err(error::paper_line(number + 1));
+        }
+        if let Some(crc) = strip_prefix_ignore_case(line, CRC_PREFIX) {
+            let crc = base32_decode(&normalize(crc)).ok_or_else(error::paper_checksum)?;
+            let crc: [u8; 4] = crc[..].try_into().map_err(|_| error::paper_checksum())?;
+            expected_crc = Some(u32::from_be_bytes(crc));
+            continue;
+        }
+        number += 1;
+        let (label, rest) = line
+            .split_once(':')
+            .ok_or_else(|| error::paper_line(number))?;
+        if label.trim().parse::<usize>().ok() != Some(number) {
+            return Err(error::paper_line(number));
+        }
+        let chars = normalize(rest);
+        if chars.len() < 3 {
+            return Err(error::paper_line(number));
+        }
+        let (encoded, checksum) = chars.split_at(chars.len() - 2);
+        let bytes = base32_decode(encoded).ok_or_else(|| error::paper_line(number))?;
+        if bytes.is_empty() || bytes.len() > LINE_BYTES || line_checksum(number, &bytes) != checksum
+        {
+            return Err(error::paper_line(number));
+        }
+        data.extend_from_slice(&bytes);
+    }
+    match expected_crc {
+        Some(crc) if crc == crc32(&data) => Ok(data),
+        _ => Err(error::paper_checksum()),
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    match line.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&line[prefix.len()..]),
+        _ => None,
+    }
+}
+
+/// Uppercases and removes separators, mapping digits that are easily confused
+/// with letters of the alphabet.
+fn normalize(input: &str) -> String {
+    input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| match c.to_ascii_uppercase() {
+            '0' => 'O',
+            '1' => 'I',
+            '8' => 'B',
+            c => c,
+        })
+        .collect()
+}
+
+/// The line checksum is a CRC-8 over the line number and the line's data,
+/// rendered as two base32 characters.
+fn line_checksum(number: usize, data: &[u8]) -> String {
+    let mut crc = crc8(0, &[number as u8]);
+    crc = crc8(crc, data);
+    let value = (crc as u16) << 2;
+    [
+        ALPHABET[(value >> 5) as usize & 0x1f] as char,
+        ALPHABET[value as usize & 0x1f] as char,
+    ]
+    .iter()
+    .collect()
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut result = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(ALPHABET[(buffer >> bits) as usize & 0x1f] as char);
+        }
+    }
+    if bits > 0 {
+        result.push(ALPHABET[(buffer << (5 - bits)) as usize & 0x1f] as char);
+    }
+    result
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(input.len() * 5 / 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for c in input.bytes() {
+        let value = ALPHABET.iter().position(|a| *a == c)? as u16;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+        }
+    }
+    // Leftover bits are padding and must be zero
+    if buffer & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+    Some(result)
+}
+
+fn crc8(mut crc: u8, data: &[u8]) -> u8 {
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+    use crate::{ecc_compact, error, Network};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn roundtrip() {
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let encoded = encode(&keypair.to_bytes());
+        let decoded = decode(&encoded).expect("decoded");
+        assert_eq!(
+            keypair,
+            ecc_compact::Keypair::try_from(&decoded[..]).expect("keypair")
+        );
+        // Sloppy transcription is fine
+        let sloppy = encoded.to_lowercase().replace(' ', "").replace('o', "0");
+        assert_eq!(decoded, decode(&sloppy).expect("sloppy decode"));
+    }
+
+    #[test]
+    fn typo_line() {
+        let encoded = encode(&[0x5a; 33]);
+        let mut lines: Vec<String> = encoded.lines().map(str::to_string).collect();
+        // Change a data character on the second line
+        let typo = if lines[1].as_bytes()[4] == b'A' {
+            "B"
+        } else {
+            "A"
+        };
+        lines[1].replace_range(4..5, typo);
+        match decode(&lines.join("\n")) {
+            Err(error::Error::Decode(error::DecodeError::PaperLine(2))) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_line() {
+        let encoded = encode(&[0x5a; 33]);
+        let mut lines: Vec<&str> = encoded.lines().collect();
+        lines.remove(3);
+        assert!(decode(&lines.join("\n")).is_err());
+    }
+}
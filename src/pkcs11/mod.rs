@@ -0,0 +1,166 @@
+//! PKCS#11 backed keypairs. The secret key is an existing P-256 key on a
+//! PKCS#11 token, like an HSM, referenced by its label. The key never leaves
+//! the token and all signing happens on it.
+//!
+//! The public key is read from the public key object with the same label as
+//! the private key, which is how keys generated by common token tooling are
+//! stored. Keys are ecc_compact keys when their public key is compactable and
+//! secp256r1 keys otherwise.
+use crate::{ecc_compact, error, keypair, public_key, Network};
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    mechanism::Mechanism,
+    object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle},
+    session::{Session, UserType},
+    types::AuthPin,
+};
+use sha2::{Digest, Sha256};
+use std::{convert::TryFrom, path::Path, sync::Mutex};
+
+/// How to find the token holding the key.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Token<'a> {
+    /// The token with the given label
+    Label(&'a str),
+    /// The token in the slot with the given id
+    Slot(u64),
+}
+
+/// A logged in session on a token and the key to sign with.
+pub struct Hsm {
+    session: Mutex<Session>,
+    key: ObjectHandle,
+}
+
+pub type Keypair = keypair::Keypair<Hsm>;
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        let digest = Sha256::digest(msg);
+        let session = self.inner.session.lock().expect("pkcs11 lock");
+        // Plain ECDSA signs the given digest and returns the raw r and s values
+        let signature = session.sign(&Mechanism::Ecdsa, self.inner.key, &digest)?;
+        let signature = p256::ecdsa::Signature::try_from(&signature[..])
+            .map_err(|_| error::invalid_pkcs11_response())?;
+        // Tokens may return a high s
+        let signature = ecc_compact::normalize_s(signature)?;
+        Ok(signature.to_der().as_bytes().to_vec())
+    }
+}
+
+impl Keypair {
+    /// Load the PKCS#11 module at the given path, log in to the given token
+    /// with the user PIN and open the EC key pair with the given label.
+    pub fn open<P: AsRef<Path>>(
+        network: Network,
+        module: P,
+        token: Token,
+        pin: &str,
+        label: &str,
+    ) -> error::Result<Self> {
+        let pkcs11 = Pkcs11::new(module)?;
+        pkcs11.initialize(CInitializeArgs::OsThreads)?;
+        let slot = pkcs11
+            .get_slots_with_token()?
+            .into_iter()
+            .find(|slot| match token {
+                Token::Slot(id) => slot.id() == id,
+                Token::Label(label) => matches!(
+                    pkcs11.get_token_info(*slot),
+                    Ok(info) if info.label() == label
+                ),
+            })
+            .ok_or_else(|| error::pkcs11_not_found(token_name(token)))?;
+        let session = pkcs11.open_ro_session(slot)?;
+        session.login(UserType::User, Some(&AuthPin::new(pin.into())))?;
+        let key = find_key(&session, ObjectClass::PRIVATE_KEY, label)?;
+        let public = find_key(&session, ObjectClass::PUBLIC_KEY, label)?;
+        let public_key = read_public_key(network, &session, public)?;
+        Ok(Keypair {
+            network,
+            public_key,
+            inner: Hsm {
+                session: Mutex::new(session),
+                key,
+            },
+        })
+    }
+}
+
+fn token_name(token: Token) -> String {
+    match token {
+        Token::Label(label) => format!("token {}", label),
+        Token::Slot(id) => format!("slot {}", id),
+    }
+}
+
+/// Finds the single EC key object of the given class with the given label.
+fn find_key(session: &Session, class: ObjectClass, label: &str) -> error::Result<ObjectHandle> {
+    let objects = session.find_objects(&[
+        Attribute::Class(class),
+        Attribute::KeyType(KeyType::EC),
+        Attribute::Label(label.as_bytes().to_vec()),
+    ])?;
+    match objects[..] {
+        [object] => Ok(object),
+        [] => Err(error::pkcs11_not_found(format!("key {}", label))),
+        _ => Err(error::invalid_pkcs11_response()),
+    }
+}
+
+fn read_public_key(
+    network: Network,
+    session: &Session,
+    object: ObjectHandle,
+) -> error::Result<public_key::PublicKey> {
+    let attributes = session.get_attributes(object, &[AttributeType::EcPoint])?;
+    match &attributes[..] {
+        [Attribute::EcPoint(point)] => public_key_from_ec_point(network, point),
+        _ => Err(error::invalid_pkcs11_response()),
+    }
+}
+
+/// The EC point of a public key object is a DER octet string wrapping the
+/// uncompressed SEC1 point.
+fn public_key_from_ec_point(
+    network: Network,
+    ec_point: &[u8],
+) -> error::Result<public_key::PublicKey> {
+    match ec_point {
+        [0x04, len, point @ ..] if *len as usize == point.len() => {
+            public_key::PublicKey::from_p256_sec1_bytes(network, point)
+        }
+        _ => Err(error::invalid_pkcs11_response()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::public_key_from_ec_point;
+    use crate::{ecc_compact, error::Error, Network};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn ec_points() {
+        let keypair = ecc_compact::Keypair::generate(Network::TestNet, &mut OsRng);
+        let point = keypair.public_key.to_sec1_bytes(false).expect("point");
+        let mut ec_point = vec![0x04, point.len() as u8];
+        ec_point.extend_from_slice(&point);
+        assert_eq!(
+            keypair.public_key,
+            public_key_from_ec_point(Network::TestNet, &ec_point).expect("public key")
+        );
+
+        let truncated = &ec_point[..ec_point.len() - 1];
+        let mut overlong = ec_point.clone();
+        overlong.push(0);
+        let mut not_octets = ec_point.clone();
+        not_octets[0] = 0x03;
+        for ec_point in &[&[][..], &[0x04], truncated, &overlong[..], &not_octets[..]] {
+            assert!(matches!(
+                public_key_from_ec_point(Network::TestNet, ec_point),
+                Err(Error::InvalidPkcs11Response)
+            ));
+        }
+    }
+}
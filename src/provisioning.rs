@@ -0,0 +1,207 @@
+//! Signed provisioning bundles for IoT devices.
+//!
+//! A bundle ties a device key to its onboarding key and carries free form
+//! text metadata, like the hardware revision or production batch. It is
+//! signed by the maker and checked by the onboarding server against the maker
+//! keys it trusts.
+//!
+//! Bundles are encoded as deterministic CBOR (RFC 8949 section 4.2), a map
+//! with the unsigned integer keys:
+//!
+//! * 1: the binary form of the device key,
+//! * 2: the binary form of the onboarding key,
+//! * 3: the binary form of the maker key,
+//! * 4: the metadata as a map of text to text,
+//! * 5: the maker signature.
+//!
+//! The signature is over the same encoding without the signature entry. The
+//! decoder only accepts the deterministic encoding, so a bundle that decodes
+//! re-encodes to exactly the bytes that were signed.
+use crate::{cbor, error, keypair, PublicKey, Sign, Verify};
+use std::{collections::BTreeMap, convert::TryFrom};
+
+const KEY_DEVICE: u64 = 1;
+const KEY_ONBOARDING: u64 = 2;
+const KEY_MAKER: u64 = 3;
+const KEY_METADATA: u64 = 4;
+const KEY_SIGNATURE: u64 = 5;
+
+/// A signed provisioning bundle.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ProvisioningBundle {
+    /// The device key
+    pub device_key: PublicKey,
+    /// The onboarding key of the device
+    pub onboarding_key: PublicKey,
+    /// The maker key the bundle is signed with
+    pub maker_key: PublicKey,
+    /// Free form metadata
+    pub metadata: BTreeMap<String, String>,
+    /// The maker signature
+    pub signature: Vec<u8>,
+}
+
+/// Builds and signs a [`ProvisioningBundle`].
+#[derive(Debug, Clone)]
+pub struct ProvisioningBundleBuilder {
+    device_key: PublicKey,
+    onboarding_key: PublicKey,
+    metadata: BTreeMap<String, String>,
+}
+
+impl ProvisioningBundleBuilder {
+    /// Add a metadata entry, replacing any previous value for the key.
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Sign the bundle with the given maker keypair.
+    pub fn sign<C>(self, maker: &keypair::Keypair<C>) -> error::Result<ProvisioningBundle>
+    where
+        keypair::Keypair<C>: Sign,
+    {
+        let mut bundle = ProvisioningBundle {
+            device_key: self.device_key,
+            onboarding_key: self.onboarding_key,
+            maker_key: maker.public_key.clone(),
+            metadata: self.metadata,
+            signature: vec![],
+        };
+        bundle.signature = maker.sign(&bundle.encode(false))?;
+        Ok(bundle)
+    }
+}
+
+impl ProvisioningBundle {
+    /// Start building a bundle for the given device and onboarding keys.
+    pub fn builder(device_key: PublicKey, onboarding_key: PublicKey) -> ProvisioningBundleBuilder {
+        ProvisioningBundleBuilder {
+            device_key,
+            onboarding_key,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Verify the maker signature and that the maker key is one of the given
+    /// trusted keys.
+    pub fn verify(&self, trusted_makers: &[PublicKey]) -> error::Result {
+        if !trusted_makers.contains(&self.maker_key) {
+            return Err(signature::Error::new().into());
+        }
+        self.maker_key.verify(&self.encode(false), &self.signature)
+    }
+
+    /// The bytes the maker signature is computed over.
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        self.encode(false)
+    }
+
+    /// Convert the bundle to its CBOR encoding.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.encode(true)
+    }
+
+    fn encode(&self, with_signature: bool) -> Vec<u8> {
+        let mut result = vec![];
+        cbor::write_head(&mut result, cbor::MAJOR_MAP, 4 + u64::from(with_signature));
+        cbor::write_uint(&mut result, KEY_DEVICE);
+        cbor::write_bytes(&mut result, &self.device_key.to_bytes());
+        cbor::write_uint(&mut result, KEY_ONBOARDING);
+        cbor::write_bytes(&mut result, &self.onboarding_key.to_bytes());
+        cbor::write_uint(&mut result, KEY_MAKER);
+        cbor::write_bytes(&mut result, &self.maker_key.to_bytes());
+        cbor::write_uint(&mut result, KEY_METADATA);
+        cbor::write_text_map(
+            &mut result,
+            self.metadata
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        );
+        if with_signature {
+            cbor::write_uint(&mut result, KEY_SIGNATURE);
+            cbor::write_bytes(&mut result, &self.signature);
+        }
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for ProvisioningBundle {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        let mut reader = cbor::Reader::new(input);
+        if reader.map_len()? != 5 {
+            return Err(error::invalid_provisioning_bundle());
+        }
+        // Integer keys in deterministic order are simply ascending
+        expect_key(&mut reader, KEY_DEVICE)?;
+        let device_key = PublicKey::try_from(reader.bytes()?)?;
+        expect_key(&mut reader, KEY_ONBOARDING)?;
+        let onboarding_key = PublicKey::try_from(reader.bytes()?)?;
+        expect_key(&mut reader, KEY_MAKER)?;
+        let maker_key = PublicKey::try_from(reader.bytes()?)?;
+        expect_key(&mut reader, KEY_METADATA)?;
+        let metadata = reader.text_map()?.into_iter().collect();
+        expect_key(&mut reader, KEY_SIGNATURE)?;
+        let signature = reader.bytes()?.to_vec();
+        if !reader.is_empty() {
+            return Err(error::invalid_provisioning_bundle());
+        }
+        Ok(Self {
+            device_key,
+            onboarding_key,
+            maker_key,
+            metadata,
+            signature,
+        })
+    }
+}
+
+fn expect_key(reader: &mut cbor::Reader<'_>, key: u64) -> error::Result {
+    if reader.uint()? != key {
+        return Err(error::invalid_provisioning_bundle());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProvisioningBundle;
+    use crate::{ecc_compact, ed25519, Network};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    fn bundle(maker: &ed25519::Keypair) -> ProvisioningBundle {
+        let device = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let onboarding = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        ProvisioningBundle::builder(device.public_key, onboarding.public_key)
+            .metadata("hardware", "rev-c")
+            .metadata("batch", "2026-10")
+            .sign(maker)
+            .expect("bundle")
+    }
+
+    #[test]
+    fn roundtrip() {
+        let maker = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let bundle = bundle(&maker);
+        let encoded = bundle.to_vec();
+        let decoded = ProvisioningBundle::try_from(&encoded[..]).expect("decoded bundle");
+        assert_eq!(bundle, decoded);
+        assert_eq!(encoded, decoded.to_vec());
+        assert!(decoded.verify(&[maker.public_key.clone()]).is_ok());
+        assert!(ProvisioningBundle::try_from(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn untrusted() {
+        let maker = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let other = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let mut bundle = bundle(&maker);
+        assert!(bundle.verify(&[other.public_key]).is_err());
+        bundle
+            .metadata
+            .insert("hardware".to_string(), "rev-d".to_string());
+        assert!(bundle.verify(&[maker.public_key]).is_err());
+    }
+}
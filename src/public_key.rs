@@ -2,8 +2,20 @@
 //! implementations, the pulic key implementation needs to support all of them
 //! since a client will need to be able to parse and use a public key from any
 //! keypair.
-use crate::{ecc_compact, ed25519, error, IntoBytes, KeyTag, KeyType, Network};
-use std::convert::TryFrom;
+#[cfg(feature = "std")]
+use crate::secp256r1;
+#[cfg(feature = "sr25519")]
+use crate::sr25519;
+use crate::{
+    ecc_compact, ed25519,
+    error::{self, AddressError},
+    keypair::Prehash,
+    output_prefix, schnorr, secp256k1, IntoBytes, KeyTag, KeyType, Network,
+};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::convert::TryFrom;
+use subtle::{Choice, ConstantTimeEq};
 
 ///Verify a given message against a given signature slice. Public keys are
 ///expected to implemt this trait to verify signed messages.
@@ -11,22 +23,289 @@ pub trait Verify {
     /// Verify the given message against the givem signature. An error is
     /// returned if the signature can not be parsed or verified for the
     /// implementor
-    fn verify(&self, msg: &[u8], signature: &[u8]) -> error::Result;
+    fn verify(&self, msg: &[u8], signature: &[u8]) -> error::Result {
+        self.verify_with_options(msg, signature, &VerifyOptions::DEFAULT)
+    }
+
+    /// Verify the given message against the given signature using
+    /// [`VerifyOptions::STRICT`], rejecting malleable signatures.
+    fn verify_strict(&self, msg: &[u8], signature: &[u8]) -> error::Result {
+        self.verify_with_options(msg, signature, &VerifyOptions::STRICT)
+    }
+
+    /// Verify the given message against the given signature using the given
+    /// verification rules.
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result;
+
+    /// Verify a signature made with
+    /// [`Sign::sign_with_context`](crate::Sign::sign_with_context) for the
+    /// given domain and message.
+    #[cfg(feature = "alloc")]
+    fn verify_with_context(&self, domain: &[u8], msg: &[u8], signature: &[u8]) -> error::Result {
+        self.verify(&crate::context::message(domain, msg)?, signature)
+    }
+
+    /// Verify the given signature over a message that has been hashed into
+    /// the given digest. See [`Prehash`] for the digest each key type expects.
+    fn verify_digest(&self, digest: Prehash, signature: &[u8]) -> error::Result {
+        self.verify_digest_with_options(digest, signature, &VerifyOptions::DEFAULT)
+    }
+
+    /// Verify a signature over a hashed message using the given verification
+    /// rules. Implementors that can only verify full messages return
+    /// `Error::UnsupportedPrehash`.
+    fn verify_digest_with_options(
+        &self,
+        _digest: Prehash,
+        _signature: &[u8],
+        _options: &VerifyOptions,
+    ) -> error::Result {
+        Err(error::unsupported_prehash())
+    }
+
+    /// Verify the given signature over a message given as its finished
+    /// digest, for example one kept while streaming the message. This is
+    /// [`Verify::verify_digest`] with [`Prehash::from_digest`]: ECDSA keys
+    /// take the 32 byte SHA-256 digest and ed25519 keys the 64 byte SHA-512
+    /// digest of an Ed25519ph signature.
+    fn verify_prehashed(&self, digest: &[u8], signature: &[u8]) -> error::Result {
+        self.verify_digest(Prehash::from_digest(digest)?, signature)
+    }
+
+    /// Verify a signature over a finished digest using the given verification
+    /// rules, see [`Verify::verify_prehashed`].
+    fn verify_prehashed_with_options(
+        &self,
+        digest: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        self.verify_digest_with_options(Prehash::from_digest(digest)?, signature, options)
+    }
+
+    /// Box this verifier, so verifiers of different types, like public keys
+    /// and multisig keys, can be held together.
+    #[cfg(feature = "alloc")]
+    fn into_boxed_verifier(self) -> BoxedVerifier
+    where
+        Self: Sized + Send + Sync + 'static,
+    {
+        Box::new(self)
+    }
 }
 
-/// The public key byte length is 32 for all key types with an extra type byte
-/// prefixed.
+/// A verifier of any type. [`Verify`] is object safe, so verifiers can also
+/// be used as `&dyn Verify`.
+#[cfg(feature = "alloc")]
+pub type BoxedVerifier = Box<dyn Verify + Send + Sync>;
+
+macro_rules! forward_verify {
+    ($($pointer:ty),+) => {
+        $(impl<V: Verify + ?Sized> Verify for $pointer {
+            fn verify_with_options(
+                &self,
+                msg: &[u8],
+                signature: &[u8],
+                options: &VerifyOptions,
+            ) -> error::Result {
+                (**self).verify_with_options(msg, signature, options)
+            }
+
+            fn verify_digest_with_options(
+                &self,
+                digest: Prehash,
+                signature: &[u8],
+                options: &VerifyOptions,
+            ) -> error::Result {
+                (**self).verify_digest_with_options(digest, signature, options)
+            }
+        })+
+    };
+}
+
+forward_verify!(&V);
+#[cfg(feature = "alloc")]
+forward_verify!(alloc::sync::Arc<V>, Box<V>);
+
+/// Options controlling how strictly signatures are checked. Options that do
+/// not apply to a key type are ignored for it.
+///
+/// Different consumers need different rules: API ingress can be lenient,
+/// while anything that must agree with other nodes on whether a signature is
+/// valid should use [`VerifyOptions::CONSENSUS`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VerifyOptions {
+    /// Reject ECDSA signatures whose s value is in the upper half of the curve
+    /// order.
+    pub require_low_s: bool,
+    /// Reject ECDSA signatures whose encoding is not the canonical DER
+    /// encoding of the signature.
+    pub strict_der: bool,
+    /// Accept ECDSA signatures in the fixed size IEEE P1363 form, `r || s`
+    /// with both big endian, as emitted by many mobile SDKs and HSMs, in
+    /// addition to DER.
+    pub allow_raw_ecdsa: bool,
+    /// Reject ed25519 signatures with a non-canonical or small order R and
+    /// public keys of small order.
+    pub strict_ed25519: bool,
+    /// Check ed25519 signatures with the cofactored equation `[8][s]B =
+    /// [8]R + [8][k]A` rather than the cofactorless `[s]B = R + [k]A`.
+    pub cofactored: bool,
+}
+
+impl VerifyOptions {
+    /// The default, lenient, rules.
+    pub const DEFAULT: Self = Self {
+        require_low_s: false,
+        strict_der: false,
+        allow_raw_ecdsa: false,
+        strict_ed25519: false,
+        cofactored: false,
+    };
+
+    /// Rules matching the chain's Erlang implementation (libsodium for ed25519
+    /// and OpenSSL, which accepts high s values, for ECDSA) while rejecting
+    /// every encoding that implementations could disagree on.
+    pub const CONSENSUS: Self = Self {
+        require_low_s: false,
+        strict_der: true,
+        allow_raw_ecdsa: false,
+        strict_ed25519: true,
+        cofactored: false,
+    };
+
+    /// Malleability resistant rules: ECDSA signatures need a low s and
+    /// canonical DER, and ed25519 signatures canonical encodings, so a valid
+    /// signature can not be altered into another valid signature. Signatures
+    /// made by this crate always pass.
+    pub const STRICT: Self = Self {
+        require_low_s: true,
+        strict_der: true,
+        allow_raw_ecdsa: false,
+        strict_ed25519: true,
+        cofactored: false,
+    };
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The public key byte length is 32 for most key types with an extra type
+/// byte prefixed.
 pub const PUBLIC_KEY_LENGTH: usize = 33;
 
+/// The longest binary form of a public key, that of secp256k1 and secp256r1
+/// keys, which hold a 33 byte SEC1 compressed point.
+pub const MAX_PUBLIC_KEY_LENGTH: usize = 34;
+
+/// The length of the binary form of a public key with the given tag byte,
+/// for splitting concatenated keys.
+pub(crate) fn tagged_len(tag: u8) -> usize {
+    match KeyType::try_from(tag) {
+        Ok(KeyType::Secp256k1) | Ok(KeyType::Secp256r1) => MAX_PUBLIC_KEY_LENGTH,
+        _ => PUBLIC_KEY_LENGTH,
+    }
+}
+
+/// Splits the binary form of the public key at the start of the given input
+/// from the rest of it.
+pub(crate) fn split_tagged(input: &[u8]) -> error::Result<(&[u8], &[u8])> {
+    let len = tagged_len(*input.first().ok_or_else(|| error::invalid_length(1, 0))?);
+    if input.len() < len {
+        return Err(error::invalid_length(len, input.len()));
+    }
+    Ok(input.split_at(len))
+}
+
+/// The binary form of a public key as returned by [`PublicKey::to_bytes`],
+/// which dereferences to the tagged key bytes. Its length depends on the key
+/// type.
+#[derive(Clone, Copy)]
+pub struct PublicKeyBytes {
+    len: usize,
+    data: [u8; MAX_PUBLIC_KEY_LENGTH],
+}
+
+impl PublicKeyBytes {
+    /// Copy the given binary form of a key, which must be at most
+    /// [`PublicKey::MAX_LEN`] bytes long.
+    pub(crate) fn from_slice(bytes: &[u8]) -> Self {
+        let mut data = [0u8; MAX_PUBLIC_KEY_LENGTH];
+        data[..bytes.len()].copy_from_slice(bytes);
+        Self {
+            len: bytes.len(),
+            data,
+        }
+    }
+}
+
+impl core::ops::Deref for PublicKeyBytes {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl AsRef<[u8]> for PublicKeyBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl PartialEq for PublicKeyBytes {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for PublicKeyBytes {}
+
+impl PartialOrd for PublicKeyBytes {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PublicKeyBytes {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl core::hash::Hash for PublicKeyBytes {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl core::fmt::Debug for PublicKeyBytes {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
+        core::fmt::Debug::fmt(&**self, f)
+    }
+}
+
 /// A public key representing any of the supported public key types on a given
 /// network.
 ///
 /// Public keys can convert to and from their binary and base58 representation
-#[derive(Debug, PartialEq, Clone)]
+///
+/// Equality and ordering are defined on the tagged binary form returned by
+/// `to_bytes`. Keys of different types or networks are never equal, and keys
+/// sort by their tag byte first and then by their key bytes, which is also the
+/// order in which the chain sorts keys.
+#[derive(Debug, Clone)]
 pub struct PublicKey {
     /// The network this public key is valid for
     pub network: Network,
-    inner: PublicKeyRepr,
+    pub(crate) inner: PublicKeyRepr,
 }
 
 /// Holds the actual representation of all supported public key types.
@@ -34,24 +313,68 @@ pub struct PublicKey {
 pub(crate) enum PublicKeyRepr {
     EccCompact(ecc_compact::PublicKey),
     Ed25519(ed25519::PublicKey),
+    Secp256k1(secp256k1::PublicKey),
+    Schnorr(schnorr::PublicKey),
+    /// A P-256 key without a compact form, see [`secp256r1`]
+    #[cfg(feature = "std")]
+    Secp256r1(secp256r1::PublicKey),
+    /// A Substrate key, see [`sr25519`]
+    #[cfg(feature = "sr25519")]
+    Sr25519(sr25519::PublicKey),
+    /// A key of an experimental or vendor key type, kept as is
+    Other(u8, [u8; PUBLIC_KEY_LENGTH - 1]),
+}
+
+/// Public keys compare in constant time, so services matching keys do not
+/// leak how much of a key matched.
+impl ConstantTimeEq for PublicKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.to_bytes()[..].ct_eq(&other.to_bytes()[..])
+    }
+}
+
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
 }
 
 impl Eq for PublicKey {}
 
+impl PartialOrd for PublicKey {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PublicKey {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.to_bytes().cmp(&other.to_bytes())
+    }
+}
+
+/// Hashes the tagged binary form, consistent with `Eq` and `Ord`.
+impl core::hash::Hash for PublicKey {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.to_bytes().hash(state)
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl From<&PublicKey> for Vec<u8> {
     fn from(v: &PublicKey) -> Self {
-        let mut result = vec![0u8; PUBLIC_KEY_LENGTH];
-        v.bytes_into(&mut result);
-        result
+        v.to_bytes().to_vec()
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<PublicKey> for Vec<u8> {
     fn from(v: PublicKey) -> Self {
         Self::from(&v)
     }
 }
 
+#[cfg(feature = "alloc")]
 impl TryFrom<Vec<u8>> for PublicKey {
     type Error = error::Error;
     fn try_from(v: Vec<u8>) -> error::Result<Self> {
@@ -62,35 +385,124 @@ impl TryFrom<Vec<u8>> for PublicKey {
 impl TryFrom<&[u8]> for PublicKey {
     type Error = error::Error;
     fn try_from(bytes: &[u8]) -> error::Result<Self> {
+        // Unset protobuf fields are empty, which must not panic
+        let tag = bytes
+            .first()
+            .ok_or_else(|| error::invalid_length(PUBLIC_KEY_LENGTH, 0))?;
         Ok(Self {
-            network: Network::try_from(bytes[0])?,
+            network: Network::try_from(*tag)?,
             inner: PublicKeyRepr::try_from(bytes)?,
         })
     }
 }
 
+#[cfg(feature = "alloc")]
+impl TryFrom<&Vec<u8>> for PublicKey {
+    type Error = error::Error;
+    fn try_from(v: &Vec<u8>) -> error::Result<Self> {
+        Self::try_from(&v[..])
+    }
+}
+
+/// Conversions for protobuf `bytes` fields generated as [`Bytes`](prost::bytes::Bytes)
+/// rather than `Vec<u8>`, as some helium-proto services are.
+#[cfg(feature = "proto")]
+impl From<&PublicKey> for prost::bytes::Bytes {
+    fn from(v: &PublicKey) -> Self {
+        Self::from(v.to_vec())
+    }
+}
+
+#[cfg(feature = "proto")]
+impl From<PublicKey> for prost::bytes::Bytes {
+    fn from(v: PublicKey) -> Self {
+        Self::from(&v)
+    }
+}
+
+#[cfg(feature = "proto")]
+impl TryFrom<prost::bytes::Bytes> for PublicKey {
+    type Error = error::Error;
+    fn try_from(v: prost::bytes::Bytes) -> error::Result<Self> {
+        Self::try_from(&v[..])
+    }
+}
+
+#[cfg(feature = "proto")]
+impl TryFrom<&prost::bytes::Bytes> for PublicKey {
+    type Error = error::Error;
+    fn try_from(v: &prost::bytes::Bytes) -> error::Result<Self> {
+        Self::try_from(&v[..])
+    }
+}
+
 impl TryFrom<&[u8]> for PublicKeyRepr {
     type Error = error::Error;
     fn try_from(bytes: &[u8]) -> error::Result<Self> {
-        match KeyType::try_from(bytes[0])? {
+        let tag = *bytes
+            .first()
+            .ok_or_else(|| error::invalid_length(PUBLIC_KEY_LENGTH, 0))?;
+        match KeyType::try_from(tag)? {
             KeyType::EccCompact => Ok(Self::EccCompact(ecc_compact::PublicKey::try_from(bytes)?)),
             KeyType::Ed25519 => Ok(Self::Ed25519(ed25519::PublicKey::try_from(bytes)?)),
+            KeyType::Secp256k1 => Ok(Self::Secp256k1(secp256k1::PublicKey::try_from(bytes)?)),
+            KeyType::Schnorr => Ok(Self::Schnorr(schnorr::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "std")]
+            KeyType::Secp256r1 => Ok(Self::Secp256r1(secp256r1::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "sr25519")]
+            KeyType::Sr25519 => Ok(Self::Sr25519(sr25519::PublicKey::try_from(bytes)?)),
+            KeyType::Multisig | KeyType::P384 | KeyType::Rsa => Err(error::invalid_keytype(tag)),
+            #[cfg(not(feature = "std"))]
+            KeyType::Secp256r1 => Err(error::invalid_keytype(tag)),
+            #[cfg(not(feature = "sr25519"))]
+            KeyType::Sr25519 => Err(error::invalid_keytype(tag)),
+            KeyType::Other(key_type) => {
+                let mut key = [0u8; PUBLIC_KEY_LENGTH - 1];
+                key.copy_from_slice(
+                    bytes
+                        .get(1..PUBLIC_KEY_LENGTH)
+                        .ok_or_else(|| error::invalid_length(PUBLIC_KEY_LENGTH, bytes.len()))?,
+                );
+                #[cfg(feature = "std")]
+                crate::key_registry::check(key_type, &key)?;
+                Ok(Self::Other(key_type, key))
+            }
         }
     }
 }
 
 impl IntoBytes for PublicKey {
-    fn bytes_into(&self, output: &mut [u8]) {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let output = output_prefix(output, self.encoded_len())?;
         output[0] = u8::from(self.tag());
-        self.inner.bytes_into(&mut output[1..]);
+        Ok(1 + self.inner.try_bytes_into(&mut output[1..])?)
     }
 }
 
 impl IntoBytes for PublicKeyRepr {
-    fn bytes_into(&self, output: &mut [u8]) {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
         match self {
-            Self::EccCompact(key) => key.bytes_into(output),
-            Self::Ed25519(key) => key.bytes_into(output),
+            Self::EccCompact(key) => key.try_bytes_into(output),
+            Self::Ed25519(key) => key.try_bytes_into(output),
+            Self::Secp256k1(key) => key.try_bytes_into(output),
+            Self::Schnorr(key) => key.try_bytes_into(output),
+            #[cfg(feature = "std")]
+            Self::Secp256r1(key) => {
+                // Skip the tag of the standalone binary form
+                let bytes = key.to_bytes();
+                output_prefix(output, bytes.len() - 1)?.copy_from_slice(&bytes[1..]);
+                Ok(bytes.len() - 1)
+            }
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(key) => {
+                let bytes = key.to_bytes();
+                output_prefix(output, bytes.len() - 1)?.copy_from_slice(&bytes[1..]);
+                Ok(bytes.len() - 1)
+            }
+            Self::Other(_, key) => {
+                output_prefix(output, key.len())?.copy_from_slice(key);
+                Ok(key.len())
+            }
         }
     }
 }
@@ -107,39 +519,447 @@ impl From<ed25519::PublicKey> for PublicKeyRepr {
     }
 }
 
+impl From<secp256k1::PublicKey> for PublicKeyRepr {
+    fn from(v: secp256k1::PublicKey) -> Self {
+        Self::Secp256k1(v)
+    }
+}
+
+impl From<schnorr::PublicKey> for PublicKeyRepr {
+    fn from(v: schnorr::PublicKey) -> Self {
+        Self::Schnorr(v)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<secp256r1::PublicKey> for PublicKeyRepr {
+    fn from(v: secp256r1::PublicKey) -> Self {
+        Self::Secp256r1(v)
+    }
+}
+
+#[cfg(feature = "sr25519")]
+impl From<sr25519::PublicKey> for PublicKeyRepr {
+    fn from(v: sr25519::PublicKey) -> Self {
+        Self::Sr25519(v)
+    }
+}
+
 impl Verify for PublicKey {
-    fn verify(&self, msg: &[u8], signature: &[u8]) -> error::Result {
-        self.inner.verify(msg, signature)
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        self.inner.verify_with_options(msg, signature, options)
+    }
+
+    fn verify_digest_with_options(
+        &self,
+        digest: Prehash,
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        self.inner
+            .verify_digest_with_options(digest, signature, options)
     }
 }
 
 impl Verify for PublicKeyRepr {
-    fn verify(&self, msg: &[u8], signature: &[u8]) -> error::Result {
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        if let Some(found) = self.mismatched_signature_type(signature, options) {
+            return Err(error::signature_type_mismatch(self.key_type(), found));
+        }
+        match self {
+            Self::Ed25519(key) => key.verify_with_options(msg, signature, options),
+            Self::EccCompact(key) => key.verify_with_options(msg, signature, options),
+            Self::Secp256k1(key) => key.verify_with_options(msg, signature, options),
+            Self::Schnorr(key) => key.verify_with_options(msg, signature, options),
+            #[cfg(feature = "std")]
+            Self::Secp256r1(key) => key.verify_with_options(msg, signature, options),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(key) => key.verify_with_options(msg, signature, options),
+            #[cfg(feature = "std")]
+            Self::Other(key_type, key) => {
+                crate::key_registry::verify(*key_type, key, msg, signature, options)
+            }
+            #[cfg(not(feature = "std"))]
+            Self::Other(key_type, _) => Err(error::unsupported_keytype(KeyType::Other(*key_type))),
+        }
+    }
+
+    fn verify_digest_with_options(
+        &self,
+        digest: Prehash,
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        if let Some(found) = self.mismatched_signature_type(signature, options) {
+            return Err(error::signature_type_mismatch(self.key_type(), found));
+        }
+        match self {
+            Self::Ed25519(key) => key.verify_digest_with_options(digest, signature, options),
+            Self::EccCompact(key) => key.verify_digest_with_options(digest, signature, options),
+            Self::Secp256k1(key) => key.verify_digest_with_options(digest, signature, options),
+            Self::Schnorr(key) => key.verify_digest_with_options(digest, signature, options),
+            #[cfg(feature = "std")]
+            Self::Secp256r1(key) => key.verify_digest_with_options(digest, signature, options),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(key) => key.verify_digest_with_options(digest, signature, options),
+            #[cfg(feature = "std")]
+            Self::Other(key_type, key) => {
+                crate::key_registry::verify_digest(*key_type, key, digest, signature, options)
+            }
+            #[cfg(not(feature = "std"))]
+            Self::Other(key_type, _) => Err(error::unsupported_keytype(KeyType::Other(*key_type))),
+        }
+    }
+}
+
+/// Verify many `(public key, message, signature)` items at once, failing if
+/// any of the signatures is invalid. Verify the items individually to find
+/// out which one failed.
+///
+/// ed25519 signatures are combined into a single multiscalar multiplication,
+/// which is several times faster than verifying them one by one. The batch
+/// uses the cofactored verification equation, as with
+/// [`VerifyOptions::cofactored`], which can accept signatures with small
+/// order components that default verification rejects. ECDSA signatures can
+/// not be combined without the full R point, so they are verified
+/// individually.
+#[cfg(feature = "std")]
+pub fn verify_batch(items: &[(PublicKey, &[u8], &[u8])]) -> error::Result {
+    let mut batch = Vec::with_capacity(items.len());
+    for (public_key, msg, signature) in items {
+        match &public_key.inner {
+            PublicKeyRepr::Ed25519(key) => batch.push((key, *msg, *signature)),
+            _ => public_key.verify(msg, signature)?,
+        }
+    }
+    if batch.is_empty() {
+        return Ok(());
+    }
+    ed25519::verify_batch(&batch, &mut rand_core::OsRng)
+}
+
+/// Number of items each thread verifies at once with [`verify_batch`] in
+/// [`verify_batch_parallel`].
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK_SIZE: usize = 256;
+
+/// Verify many items like [`verify_batch`], spread over all cores with rayon.
+/// Items are verified in chunks, and no further chunks are started once one
+/// has failed.
+#[cfg(feature = "rayon")]
+pub fn verify_batch_parallel(items: &[(PublicKey, &[u8], &[u8])]) -> error::Result {
+    use rayon::prelude::*;
+    items
+        .par_chunks(PARALLEL_CHUNK_SIZE)
+        .try_for_each(verify_batch)
+}
+
+/// Verify every item individually, spread over all cores with rayon, and
+/// return the result for each item in order. Unlike [`verify_batch`] this uses
+/// default verification for ed25519 signatures as well.
+#[cfg(feature = "rayon")]
+pub fn verify_each_parallel(items: &[(PublicKey, &[u8], &[u8])]) -> Vec<error::Result> {
+    use rayon::prelude::*;
+    items
+        .par_iter()
+        .map(|(public_key, msg, signature)| public_key.verify(msg, signature))
+        .collect()
+}
+
+/// Verify a signature for the given message against each of the given keys
+/// and return the index of the first key it verifies for, as for a packet
+/// signed by one of a known set of gateways. Fails like a failed verification
+/// when none of the keys verifies it.
+///
+/// An ECDSA signature is parsed at most once per curve rather than once per
+/// key, and not at all without keys of that curve.
+pub fn verify_any(keys: &[PublicKey], msg: &[u8], signature: &[u8]) -> error::Result<usize> {
+    let mut ecc_compact = None;
+    let mut secp256k1 = None;
+    let options = &VerifyOptions::DEFAULT;
+    keys.iter()
+        .position(|key| match &key.inner {
+            PublicKeyRepr::EccCompact(key) => ecc_compact
+                .get_or_insert_with(|| ecc_compact::parse_signature(signature, options).ok())
+                .as_ref()
+                .map_or(false, |signature| key.verify_parsed(msg, signature).is_ok()),
+            PublicKeyRepr::Secp256k1(key) => secp256k1
+                .get_or_insert_with(|| secp256k1::parse_signature(signature, options).ok())
+                .as_ref()
+                .map_or(false, |signature| key.verify_parsed(msg, signature).is_ok()),
+            _ => key.verify(msg, signature).is_ok(),
+        })
+        .ok_or_else(|| signature::Error::new().into())
+}
+
+impl PublicKeyRepr {
+    fn key_type(&self) -> KeyType {
+        match self {
+            Self::EccCompact(..) => KeyType::EccCompact,
+            Self::Ed25519(..) => KeyType::Ed25519,
+            Self::Secp256k1(..) => KeyType::Secp256k1,
+            Self::Schnorr(..) => KeyType::Schnorr,
+            #[cfg(feature = "std")]
+            Self::Secp256r1(..) => KeyType::Secp256r1,
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(..) => KeyType::Sr25519,
+            Self::Other(key_type, _) => KeyType::Other(*key_type),
+        }
+    }
+
+    /// Returns the key type a signature was clearly made with if that is not
+    /// the type of this key. ed25519 and BIP340 signatures are always 64
+    /// bytes, while ECDSA signatures are DER sequences. A 64 byte DER sequence is
+    /// a valid ed25519 signature as well, so it is never reported. When raw
+    /// ECDSA signatures are allowed a 64 byte signature is ambiguous as well.
+    pub(crate) fn mismatched_signature_type(
+        &self,
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> Option<KeyType> {
+        let looks_der = signature.len() >= 8
+            && signature[0] == 0x30
+            && signature[1] as usize == signature.len() - 2;
         match self {
-            Self::Ed25519(key) => key.verify(msg, signature),
-            Self::EccCompact(key) => key.verify(msg, signature),
+            Self::Ed25519(..) if looks_der && signature.len() != 64 => Some(KeyType::EccCompact),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(..) if looks_der && signature.len() != 64 => Some(KeyType::EccCompact),
+            Self::Schnorr(..) if looks_der && signature.len() != 64 => Some(KeyType::Secp256k1),
+            Self::EccCompact(..) | Self::Secp256k1(..)
+                if !looks_der && signature.len() == 64 && !options.allow_raw_ecdsa =>
+            {
+                Some(KeyType::Ed25519)
+            }
+            #[cfg(feature = "std")]
+            Self::Secp256r1(..)
+                if !looks_der && signature.len() == 64 && !options.allow_raw_ecdsa =>
+            {
+                Some(KeyType::Ed25519)
+            }
+            _ => None,
         }
     }
 }
 
-impl std::str::FromStr for PublicKey {
+impl core::str::FromStr for PublicKey {
     type Err = error::Error;
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        let data = bs58::decode(s).with_check(Some(0)).into_vec()?;
-        Self::try_from(&data[1..])
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        // Decode into a stack buffer with room for the version byte and the
+        // checksum to avoid allocating
+        let mut data = [0u8; MAX_PUBLIC_KEY_LENGTH + 5];
+        let len = bs58::decode(s).with_check(Some(0)).into(&mut data)?;
+        Self::try_from(&data[1..len])
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn address_error(s: &str, err: bs58::decode::Error) -> AddressError {
+    match err {
+        bs58::decode::Error::InvalidCharacter { character, index } => {
+            AddressError::InvalidCharacter { character, index }
+        }
+        bs58::decode::Error::NonAsciiCharacter { index } => AddressError::InvalidCharacter {
+            character: s[index..].chars().next().unwrap_or_default(),
+            index,
+        },
+        bs58::decode::Error::InvalidVersion { ver, .. } => AddressError::Version(ver),
+        bs58::decode::Error::NoChecksum => AddressError::Length(0),
+        _ => AddressError::Checksum,
+    }
+}
+
+/// The problems with the length and tag of the binary form of an address, in
+/// the order `validate_str` reports them.
+#[cfg(feature = "alloc")]
+fn address_bytes_problems(bytes: &[u8]) -> impl Iterator<Item = AddressError> + '_ {
+    let tag = bytes.first().copied();
+    let length = match bytes.len() {
+        len if Some(len) == tag.map(tagged_len) => None,
+        len => Some(AddressError::Length(len)),
+    };
+    let network = tag.and_then(|tag| match Network::try_from(tag) {
+        Ok(_) => None,
+        Err(_) => Some(AddressError::UnknownNetwork(tag & 0xf0)),
+    });
+    let key_type = tag.and_then(|tag| match KeyType::try_from(tag) {
+        Ok(KeyType::Multisig)
+        | Ok(KeyType::P384)
+        | Ok(KeyType::Rsa)
+        | Ok(KeyType::Other(_))
+        | Err(_) => Some(AddressError::UnsupportedKeyType(tag & 0x0f)),
+        #[cfg(not(feature = "std"))]
+        Ok(KeyType::Secp256r1) => Some(AddressError::UnsupportedKeyType(tag & 0x0f)),
+        #[cfg(not(feature = "sr25519"))]
+        Ok(KeyType::Sr25519) => Some(AddressError::UnsupportedKeyType(tag & 0x0f)),
+        Ok(_) => None,
+    });
+    length.into_iter().chain(network).chain(key_type)
+}
+
+/// The encodings [`PublicKey::parse`] accepts.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ParseFormat {
+    /// The base58check address
+    B58,
+    /// Hex encoded binary form, optionally prefixed with `0x`
+    Hex,
+    /// The binary form
+    Bytes,
+}
+
+impl ParseFormat {
+    /// Detect the encoding of the given input by its length and characters.
+    /// Text is tried first: hex input has twice as many digits as the binary
+    /// form, optionally after a `0x` prefix, and input made only of base58
+    /// characters is taken to be an address. Otherwise the input is the
+    /// binary form when it is as long as its tag byte says, or one byte longer
+    /// when it includes the zero version byte of the address payload.
+    /// Anything else that is text is taken to be an address.
+    pub fn detect(input: &[u8]) -> Option<Self> {
+        let hex = input.strip_prefix(b"0x").unwrap_or(input);
+        if (2 * PUBLIC_KEY_LENGTH..=2 * (MAX_PUBLIC_KEY_LENGTH + 1)).contains(&hex.len())
+            && hex.len() % 2 == 0
+            && hex.iter().all(u8::is_ascii_hexdigit)
+        {
+            return Some(Self::Hex);
+        }
+        if !input.is_empty() && input.iter().all(|c| B58_ALPHABET.contains(c)) {
+            return Some(Self::B58);
+        }
+        if versioned_bytes(input).is_some() {
+            return Some(Self::Bytes);
+        }
+        core::str::from_utf8(input).ok().map(|_| Self::B58)
+    }
+}
+
+/// The characters of base58 encoded text.
+pub(crate) const B58_ALPHABET: &[u8] =
+    b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The binary form of a key in the given input, which may include the zero
+/// version byte of the address payload, or `None` if the input is not as long
+/// as its tag byte says.
+fn versioned_bytes(input: &[u8]) -> Option<&[u8]> {
+    match input {
+        [0, rest @ ..] if rest.first().map(|tag| tagged_len(*tag)) == Some(rest.len()) => {
+            Some(rest)
+        }
+        [tag, ..] if tagged_len(*tag) == input.len() => Some(input),
+        _ => None,
+    }
+}
+
+/// A public key parsed from an address by [`PublicKey::validate_str`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct ValidatedKey(PublicKey);
+
+#[cfg(feature = "alloc")]
+impl ValidatedKey {
+    /// The network of the address.
+    pub fn network(&self) -> Network {
+        self.0.network
+    }
+
+    /// The key type of the address.
+    pub fn key_type(&self) -> KeyType {
+        self.0.tag().key_type
+    }
+
+    /// Check that the address is for the given network.
+    pub fn require_network(self, expected: Network) -> Result<Self, AddressError> {
+        if self.0.network != expected {
+            return Err(AddressError::WrongNetwork {
+                expected,
+                found: self.0.network,
+            });
+        }
+        Ok(self)
+    }
+
+    /// The public key of the address.
+    pub fn into_public_key(self) -> PublicKey {
+        self.0
     }
 }
 
-impl std::fmt::Display for PublicKey {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        let mut data = [0u8; PUBLIC_KEY_LENGTH + 1];
-        self.bytes_into(&mut data[1..]);
-        let encoded = bs58::encode(data.as_ref()).with_check().into_string();
-        f.write_str(&encoded)
+/// Formats a public key is rendered in, see [`PublicKey::display`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DisplayFormat {
+    /// The full base58 form, as rendered by `{}`
+    B58,
+    /// The first and last four characters of the base58 form, as in
+    /// `13Wv…esKw`, as rendered by `{:#}`
+    Short,
+    /// The hex encoded binary form
+    Hex,
+    /// The base58 form prefixed with the key type, as in `ed25519:13Wv...`
+    Annotated,
+}
+
+/// Renders a public key in a given format, returned by
+/// [`PublicKey::display`].
+pub struct Displayed<'a> {
+    public_key: &'a PublicKey,
+    format: DisplayFormat,
+}
+
+/// Number of leading and trailing characters kept in the short format.
+const SHORT_CHARS: usize = 4;
+
+/// Room for the base58check form of the longest public key, which with the
+/// version byte and the checksum is 39 bytes and at most 54 characters.
+const MAX_B58_LENGTH: usize = 54;
+
+impl core::fmt::Display for Displayed<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
+        match self.format {
+            DisplayFormat::B58 => self.public_key.with_b58(|encoded| f.write_str(encoded)),
+            DisplayFormat::Short => self.public_key.with_b58(|encoded| {
+                // base58 is ascii so slicing at any byte is fine
+                f.write_str(&encoded[..SHORT_CHARS])?;
+                f.write_str("…")?;
+                f.write_str(&encoded[encoded.len() - SHORT_CHARS..])
+            }),
+            DisplayFormat::Hex => self
+                .public_key
+                .to_bytes()
+                .iter()
+                .try_for_each(|byte| write!(f, "{:02x}", byte)),
+            DisplayFormat::Annotated => self
+                .public_key
+                .with_b58(|encoded| write!(f, "{}:{}", self.public_key.tag().key_type, encoded)),
+        }
+    }
+}
+
+impl core::fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
+        let format = if f.alternate() {
+            DisplayFormat::Short
+        } else {
+            DisplayFormat::B58
+        };
+        core::fmt::Display::fmt(&self.display(format), f)
     }
 }
 
 impl PublicKey {
+    /// Length of the binary form of the longest public keys, those of
+    /// secp256k1 and secp256r1.
+    pub const MAX_LEN: usize = MAX_PUBLIC_KEY_LENGTH;
+
     pub(crate) fn for_network<C: Into<PublicKeyRepr>>(network: Network, public_key: C) -> Self {
         Self {
             network,
@@ -147,33 +967,630 @@ impl PublicKey {
         }
     }
 
+    /// Construct a public key for the given network from an ecc_compact key.
+    pub fn from_ecc_compact(network: Network, public_key: ecc_compact::PublicKey) -> Self {
+        Self::for_network(network, public_key)
+    }
+
+    /// Construct a public key for the given network from an ed25519 key.
+    pub fn from_ed25519(network: Network, public_key: ed25519::PublicKey) -> Self {
+        Self::for_network(network, public_key)
+    }
+
     /// Construct a public key from its binary form
     pub fn from_bytes(bytes: impl AsRef<[u8]>) -> error::Result<Self> {
         Self::try_from(bytes.as_ref())
     }
 
     /// Convert a public key to it's binary form
-    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH] {
-        let mut result = [0u8; PUBLIC_KEY_LENGTH];
-        self.bytes_into(&mut result);
-        result
+    pub fn to_bytes(&self) -> PublicKeyBytes {
+        let mut data = [0u8; MAX_PUBLIC_KEY_LENGTH];
+        let len = self.write_into(&mut data);
+        PublicKeyBytes { len, data }
+    }
+
+    /// Length of the binary form of this key, at most
+    /// [`PublicKey::MAX_LEN`].
+    pub fn encoded_len(&self) -> usize {
+        tagged_len(u8::from(self.tag()))
+    }
+
+    /// Write the binary form of the key into the start of the given buffer,
+    /// returning its length. Keys of different key types differ in length,
+    /// so the buffer has room for the longest.
+    pub fn write_into(&self, output: &mut [u8; PublicKey::MAX_LEN]) -> usize {
+        self.try_bytes_into(output).expect("public key length")
     }
 
     /// Convert a public to a Vec of it's binary form. A convenience function
     /// equivalent to calling `public_key.to_bytes().to_vec()`
+    #[cfg(feature = "alloc")]
     pub fn to_vec(&self) -> Vec<u8> {
         self.to_bytes().to_vec()
     }
 
+    /// Construct an ecc_compact public key for the given network from a SEC1
+    /// compressed or uncompressed point. Points without a compact form are
+    /// rejected with `DecodeError::NotCompact`.
+    pub fn from_sec1_bytes(network: Network, input: &[u8]) -> error::Result<Self> {
+        Ok(Self::for_network(
+            network,
+            ecc_compact::PublicKey::from_sec1_point(input)?,
+        ))
+    }
+
+    /// Construct a P-256 public key for the given network from a SEC1
+    /// compressed or uncompressed point: an ecc_compact key if the point is
+    /// compactable and a secp256r1 key otherwise. For keys that can not be
+    /// chosen, like those held by hardware or a KMS.
+    #[cfg(feature = "std")]
+    pub fn from_p256_sec1_bytes(network: Network, input: &[u8]) -> error::Result<Self> {
+        match ecc_compact::PublicKey::from_sec1_point(input) {
+            Err(error::Error::Decode(error::DecodeError::NotCompact)) => Ok(Self::for_network(
+                network,
+                secp256r1::PublicKey::from_sec1_point(network, input)?,
+            )),
+            result => Ok(Self::for_network(network, result?)),
+        }
+    }
+
+    /// The SEC1 compressed or uncompressed encoding of this key, for use with
+    /// standard ECDSA verifiers. Only ECDSA keys have a SEC1 encoding.
+    #[cfg(feature = "alloc")]
+    pub fn to_sec1_bytes(&self, compressed: bool) -> error::Result<Vec<u8>> {
+        match &self.inner {
+            PublicKeyRepr::EccCompact(key) => Ok(key.to_sec1_bytes(compressed)),
+            PublicKeyRepr::Secp256k1(key) if compressed => Ok(key.to_sec1_compressed().to_vec()),
+            PublicKeyRepr::Secp256k1(key) => Ok(key.to_sec1_uncompressed().to_vec()),
+            #[cfg(feature = "std")]
+            PublicKeyRepr::Secp256r1(key) => Ok(key.to_sec1_bytes(compressed)),
+            _ => Err(error::unsupported_keytype(self.tag().key_type)),
+        }
+    }
+
+    /// The Solana address of this key, the plain base58 encoding of the 32
+    /// byte ed25519 public key. Only ed25519 keys have a Solana address.
+    #[cfg(feature = "alloc")]
+    pub fn to_solana_address(&self) -> error::Result<String> {
+        match &self.inner {
+            PublicKeyRepr::Ed25519(_) => Ok(bs58::encode(&self.to_bytes()[1..]).into_string()),
+            _ => Err(error::unsupported_keytype(self.tag().key_type)),
+        }
+    }
+
+    /// Recover the secp256k1 public key for the given network from a
+    /// recoverable signature over the given message, as made by
+    /// `secp256k1::Keypair::sign_recoverable`.
+    pub fn recover(network: Network, msg: &[u8], signature: &[u8]) -> error::Result<Self> {
+        let public_key = secp256k1::PublicKey::recover(msg, signature)?;
+        Ok(Self::for_network(network, public_key))
+    }
+
+    /// Construct an ed25519 public key for the given network from a Solana
+    /// address. Weak points are rejected like when parsing the binary form.
+    #[cfg(feature = "alloc")]
+    pub fn from_solana_address(network: Network, address: &str) -> error::Result<Self> {
+        let bytes = bs58::decode(address).into_vec()?;
+        let public_key =
+            ed25519_dalek::PublicKey::from_bytes(&bytes).map_err(|_| error::invalid_solana())?;
+        Ok(Self::for_network(
+            network,
+            ed25519::PublicKey::try_from(public_key)?,
+        ))
+    }
+
+    /// Parse a public key from the given input in the given encoding.
+    pub fn parse(input: &[u8], format: ParseFormat) -> error::Result<Self> {
+        match format {
+            ParseFormat::B58 => core::str::from_utf8(input)
+                .map_err(|_| error::invalid_encoding())?
+                .trim()
+                .parse(),
+            ParseFormat::Hex => {
+                let hex = input.strip_prefix(b"0x").unwrap_or(input);
+                let mut data = [0u8; MAX_PUBLIC_KEY_LENGTH + 1];
+                if hex.len() % 2 != 0 || hex.len() / 2 > data.len() {
+                    return Err(error::invalid_encoding());
+                }
+                let data = &mut data[..hex.len() / 2];
+                for (byte, digits) in data.iter_mut().zip(hex.chunks(2)) {
+                    let digits =
+                        core::str::from_utf8(digits).map_err(|_| error::invalid_encoding())?;
+                    *byte =
+                        u8::from_str_radix(digits, 16).map_err(|_| error::invalid_encoding())?;
+                }
+                Self::parse(data, ParseFormat::Bytes)
+            }
+            ParseFormat::Bytes => {
+                Self::try_from(versioned_bytes(input).ok_or_else(error::invalid_encoding)?)
+            }
+        }
+    }
+
+    /// Parse a public key from the given input, detecting its encoding with
+    /// [`ParseFormat::detect`].
+    pub fn parse_detect(input: &[u8]) -> error::Result<Self> {
+        let format = ParseFormat::detect(input).ok_or_else(error::invalid_encoding)?;
+        Self::parse(input, format)
+    }
+
+    /// The checksummed short form of this key's address, see
+    /// [`crate::short_address`].
+    #[cfg(feature = "std")]
+    pub fn short_address(&self) -> crate::short_address::ShortAddress {
+        crate::short_address::ShortAddress::from(self)
+    }
+
+    /// Run all point validity checks for this key. Parsing already rejects
+    /// points off the curve as well as weak and non-canonical ed25519 points.
+    /// This additionally requires ed25519 points to be in the prime order
+    /// subgroup, which is appropriate for keys used in key agreement.
+    pub fn validate(&self) -> error::Result {
+        match &self.inner {
+            PublicKeyRepr::EccCompact(key) => key.check_point(),
+            PublicKeyRepr::Ed25519(key) => key.check_point(true),
+            PublicKeyRepr::Secp256k1(key) => key.check_point(),
+            PublicKeyRepr::Schnorr(key) => key.check_point(),
+            // Parsing already checks the point, and P-256 has no cofactor
+            #[cfg(feature = "std")]
+            PublicKeyRepr::Secp256r1(_) => Ok(()),
+            // Ristretto points are always in the prime order group
+            #[cfg(feature = "sr25519")]
+            PublicKeyRepr::Sr25519(_) => Ok(()),
+            PublicKeyRepr::Other(..) => Err(error::unsupported_keytype(self.tag().key_type)),
+        }
+    }
+
+    /// Parse an address, reporting exactly what is wrong with it if it can
+    /// not be used. Unlike `parse`, keys of key types that are not supported
+    /// here are rejected.
+    #[cfg(feature = "alloc")]
+    pub fn validate_str(s: &str) -> Result<ValidatedKey, AddressError> {
+        let data = bs58::decode(s)
+            .with_check(Some(0))
+            .into_vec()
+            .map_err(|err| address_error(s, err))?;
+        let bytes = &data[1..];
+        if let Some(problem) = address_bytes_problems(bytes).next() {
+            return Err(problem);
+        }
+        Self::try_from(bytes)
+            .map(ValidatedKey)
+            .map_err(|_| AddressError::InvalidKey)
+    }
+
+    /// Check an address like `validate_str`, but report every problem with it
+    /// rather than only the first, so support tooling can show them all at
+    /// once. The rest of an address with a bad checksum or version is still
+    /// checked as if it had the right one. An address that can be used has no
+    /// problems.
+    #[cfg(feature = "alloc")]
+    pub fn address_problems(s: &str) -> Vec<AddressError> {
+        let (mut problems, data) = match bs58::decode(s).with_check(Some(0)).into_vec() {
+            Ok(data) => (vec![], data),
+            Err(
+                err @ bs58::decode::Error::InvalidChecksum { .. }
+                | err @ bs58::decode::Error::InvalidVersion { .. },
+            ) => {
+                let mut data = bs58::decode(s).into_vec().unwrap_or_default();
+                data.truncate(data.len().saturating_sub(4));
+                (vec![address_error(s, err)], data)
+            }
+            Err(err) => return vec![address_error(s, err)],
+        };
+        let bytes = data.get(1..).unwrap_or_default();
+        problems.extend(address_bytes_problems(bytes));
+        if problems.is_empty() && Self::try_from(bytes).is_err() {
+            problems.push(AddressError::InvalidKey);
+        }
+        problems
+    }
+
+    /// A cheap plausibility check of the binary form of a key, for filtering
+    /// out garbage before full parsing. Checks the length, the tag and that
+    /// the coordinate is a valid field element, but not that it is on the
+    /// curve. A `true` result does not guarantee the key parses.
+    pub fn quick_check(bytes: &[u8]) -> bool {
+        match bytes.first() {
+            Some(tag) if bytes.len() == tagged_len(*tag) && Network::try_from(*tag).is_ok() => (),
+            _ => return false,
+        }
+        match KeyType::try_from(bytes[0]) {
+            Ok(KeyType::EccCompact) => ecc_compact::PublicKey::quick_check(&bytes[1..]),
+            Ok(KeyType::Ed25519) => ed25519::PublicKey::quick_check(&bytes[1..]),
+            Ok(KeyType::Secp256k1) => secp256k1::PublicKey::quick_check(&bytes[1..]),
+            Ok(KeyType::Schnorr) => schnorr::PublicKey::quick_check(&bytes[1..]),
+            #[cfg(feature = "std")]
+            Ok(KeyType::Secp256r1) => secp256r1::PublicKey::quick_check(&bytes[1..]),
+            #[cfg(feature = "sr25519")]
+            Ok(KeyType::Sr25519) => sr25519::PublicKey::quick_check(&bytes[1..]),
+            _ => false,
+        }
+    }
+
+    /// Render this key in the given format. `{}` renders the full base58
+    /// form and `{:#}` the short form, this gives access to the others.
+    pub fn display(&self, format: DisplayFormat) -> Displayed<'_> {
+        Displayed {
+            public_key: self,
+            format,
+        }
+    }
+
+    /// Calls `f` with the base58check form of this key, encoded into a stack
+    /// buffer so displaying a key does not allocate.
+    pub(crate) fn with_b58<R>(&self, f: impl FnOnce(&str) -> R) -> R {
+        let mut data = [0u8; MAX_PUBLIC_KEY_LENGTH + 1];
+        let len = self
+            .try_bytes_into(&mut data[1..])
+            .expect("public key length");
+        let mut encoded = [0u8; MAX_B58_LENGTH];
+        let encoded_len = bs58::encode(&data[..=len])
+            .with_check()
+            .into(&mut encoded[..])
+            .expect("b58 length");
+        f(core::str::from_utf8(&encoded[..encoded_len]).expect("b58 is ascii"))
+    }
+
     /// Get the tag for this key
     pub fn tag(&self) -> KeyTag {
-        let key_type = match self.inner {
-            PublicKeyRepr::EccCompact(..) => KeyType::EccCompact,
-            PublicKeyRepr::Ed25519(..) => KeyType::Ed25519,
-        };
         KeyTag {
             network: self.network,
-            key_type,
+            key_type: self.inner.key_type(),
+        }
+    }
+
+    /// The network this key is valid for.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// The same key tagged for the given network.
+    pub fn to_network(&self, network: Network) -> Self {
+        Self {
+            network,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        versioned_bytes, DisplayFormat, ParseFormat, PublicKey, Verify, PUBLIC_KEY_LENGTH,
+    };
+    use crate::{ecc_compact, ed25519, Error, KeyType, Network, Sign};
+    use core::convert::TryFrom;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn verify_batch() {
+        let keypairs = (0..16)
+            .map(|i| {
+                let key_type = match i % 4 {
+                    0 => KeyType::EccCompact,
+                    1 => KeyType::Secp256k1,
+                    _ => KeyType::Ed25519,
+                };
+                let tag = crate::KeyTag {
+                    network: Network::MainNet,
+                    key_type,
+                };
+                crate::Keypair::generate(tag, &mut OsRng).expect("keypair")
+            })
+            .collect::<Vec<_>>();
+        let mut signatures = keypairs
+            .iter()
+            .map(|keypair| keypair.sign(b"hello world").expect("signature"))
+            .collect::<Vec<_>>();
+        let verify = |signatures: &[Vec<u8>]| {
+            let items = keypairs
+                .iter()
+                .zip(signatures)
+                .map(|(keypair, signature)| {
+                    (
+                        keypair.public_key().clone(),
+                        &b"hello world"[..],
+                        &signature[..],
+                    )
+                })
+                .collect::<Vec<_>>();
+            super::verify_batch(&items)
+        };
+        assert!(verify(&signatures).is_ok());
+        assert!(verify(&[]).is_ok());
+        // A bad ed25519 signature fails the batch
+        signatures[6][10] ^= 0x01;
+        assert!(verify(&signatures).is_err());
+        signatures[6][10] ^= 0x01;
+        // And so does a bad ECDSA one
+        signatures[4] = signatures[0].clone();
+        assert!(verify(&signatures).is_err());
+    }
+
+    #[test]
+    fn verify_any() {
+        let keypairs = [KeyType::Ed25519, KeyType::EccCompact, KeyType::Secp256k1]
+            .iter()
+            .map(|key_type| {
+                let tag = crate::KeyTag {
+                    network: Network::MainNet,
+                    key_type: *key_type,
+                };
+                crate::Keypair::generate(tag, &mut OsRng).expect("keypair")
+            })
+            .collect::<Vec<_>>();
+        let keys = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key().clone())
+            .collect::<Vec<_>>();
+        for (index, keypair) in keypairs.iter().enumerate() {
+            let signature = keypair.sign(b"hello world").expect("signature");
+            assert_eq!(
+                index,
+                super::verify_any(&keys, b"hello world", &signature).expect("verifying key")
+            );
+            assert!(super::verify_any(&keys, b"hello", &signature).is_err());
+            assert!(super::verify_any(&keys[..index], b"hello world", &signature).is_err());
         }
     }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn verify_parallel() {
+        let keypairs = (0..600)
+            .map(|i| {
+                let key_type = match i % 2 {
+                    0 => KeyType::EccCompact,
+                    _ => KeyType::Ed25519,
+                };
+                let tag = crate::KeyTag {
+                    network: Network::MainNet,
+                    key_type,
+                };
+                crate::Keypair::generate(tag, &mut OsRng).expect("keypair")
+            })
+            .collect::<Vec<_>>();
+        let mut signatures = keypairs
+            .iter()
+            .map(|keypair| keypair.sign(b"hello world").expect("signature"))
+            .collect::<Vec<_>>();
+        signatures[555][10] ^= 0x01;
+        let items = keypairs
+            .iter()
+            .zip(&signatures)
+            .map(|(keypair, signature)| {
+                (
+                    keypair.public_key().clone(),
+                    &b"hello world"[..],
+                    &signature[..],
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(super::verify_batch_parallel(&items).is_err());
+        assert!(super::verify_batch_parallel(&items[..555]).is_ok());
+        let results = super::verify_each_parallel(&items);
+        assert_eq!(items.len(), results.len());
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(i != 555, result.is_ok());
+        }
+    }
+
+    #[test]
+    fn cross_type_ordering() {
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        let ed = ed25519::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        let mut ed_test = ed.clone();
+        ed_test.network = Network::TestNet;
+        assert_ne!(ecc, ed);
+        assert_ne!(ed, ed_test);
+        // Sorted by tag byte: mainnet ed25519 (0x01), mainnet ecc_compact
+        // (0x00) and testnet ed25519 (0x11)
+        let mut keys = vec![ed_test.clone(), ed.clone(), ecc.clone()];
+        keys.sort();
+        assert_eq!(vec![ecc.clone(), ed.clone(), ed_test], keys);
+        assert_eq!(ed, PublicKey::from_bytes(ed.to_vec()).expect("public key"));
+    }
+
+    #[test]
+    fn parse_formats() {
+        let key = ed25519::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        let bytes = key.to_vec();
+        let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let mut versioned = vec![0u8];
+        versioned.extend_from_slice(&bytes);
+        for (input, format) in [
+            (key.to_string().into_bytes(), ParseFormat::B58),
+            (hex.clone().into_bytes(), ParseFormat::Hex),
+            (
+                format!("0x{}", hex.to_uppercase()).into_bytes(),
+                ParseFormat::Hex,
+            ),
+            (bytes.clone(), ParseFormat::Bytes),
+            (versioned, ParseFormat::Bytes),
+        ] {
+            assert_eq!(Some(format), ParseFormat::detect(&input));
+            assert_eq!(key, PublicKey::parse(&input, format).expect("public key"));
+            assert_eq!(key, PublicKey::parse_detect(&input).expect("public key"));
+        }
+        assert!(PublicKey::parse(&bytes[1..], ParseFormat::Bytes).is_err());
+        assert!(PublicKey::parse(b"0xzz", ParseFormat::Hex).is_err());
+
+        // Text as long as the binary form its first character would tag is
+        // still text, and hex digits that are also base58 characters are hex
+        let text = [b'1'; PUBLIC_KEY_LENGTH];
+        assert!(versioned_bytes(&text).is_some());
+        assert_eq!(Some(ParseFormat::B58), ParseFormat::detect(&text));
+        assert_eq!(
+            Some(ParseFormat::Hex),
+            ParseFormat::detect(&[b'c'; 2 * PUBLIC_KEY_LENGTH])
+        );
+    }
+
+    #[test]
+    fn hash_set() {
+        use std::collections::HashSet;
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        let ed = ed25519::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        let keys: HashSet<PublicKey> = vec![ecc.clone(), ed.clone(), ecc.clone()]
+            .into_iter()
+            .collect();
+        assert_eq!(2, keys.len());
+        assert!(keys.contains(&PublicKey::from_bytes(ed.to_vec()).expect("public key")));
+    }
+
+    #[test]
+    fn constant_time_eq() {
+        use subtle::ConstantTimeEq;
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        let ed = ed25519::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        assert!(bool::from(ecc.ct_eq(&ecc.clone())));
+        assert!(!bool::from(ecc.ct_eq(&ed)));
+    }
+
+    #[test]
+    fn signature_type_mismatch() {
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let ed = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let ecc_signature = ecc.sign(b"hello world").expect("ecc signature");
+        let ed_signature = ed.sign(b"hello world").expect("ed25519 signature");
+        match ed.public_key.verify(b"hello world", &ecc_signature) {
+            Err(Error::SignatureTypeMismatch {
+                expected: KeyType::Ed25519,
+                found: KeyType::EccCompact,
+            }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        match ecc.public_key.verify(b"hello world", &ed_signature) {
+            Err(Error::SignatureTypeMismatch {
+                expected: KeyType::EccCompact,
+                found: KeyType::Ed25519,
+            }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validate_str() {
+        use crate::error::AddressError;
+        const B58: &str = "13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw";
+        let validated = PublicKey::validate_str(B58).expect("valid address");
+        assert_eq!(KeyType::Ed25519, validated.key_type());
+        assert_eq!(
+            Err(AddressError::WrongNetwork {
+                expected: Network::TestNet,
+                found: Network::MainNet
+            }),
+            validated.clone().require_network(Network::TestNet)
+        );
+        assert_eq!(
+            B58.parse::<PublicKey>().expect("public key"),
+            validated.into_public_key()
+        );
+        assert_eq!(
+            Err(AddressError::InvalidCharacter {
+                character: '0',
+                index: 3
+            }),
+            PublicKey::validate_str("13W0V82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw")
+        );
+        assert_eq!(
+            Err(AddressError::Checksum),
+            PublicKey::validate_str("13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKv")
+        );
+    }
+
+    #[test]
+    fn address_problems() {
+        use crate::error::AddressError;
+        const B58: &str = "13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw";
+        assert!(PublicKey::address_problems(B58).is_empty());
+        assert_eq!(
+            vec![AddressError::Checksum],
+            PublicKey::address_problems("13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKv")
+        );
+        // A bad checksum, a short key, an unassigned network and a key type
+        // without a PublicKey form
+        let mut data = bs58::decode(B58).into_vec().expect("data");
+        data.truncate(20);
+        data[1] = 0x32;
+        let address = bs58::encode(&data).into_string();
+        assert_eq!(
+            vec![
+                AddressError::Checksum,
+                AddressError::Length(15),
+                AddressError::UnknownNetwork(0x30),
+                AddressError::UnsupportedKeyType(0x02),
+            ],
+            PublicKey::address_problems(&address)
+        );
+    }
+
+    #[test]
+    fn quick_check() {
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        let ed = ed25519::Keypair::generate(Network::TestNet, &mut OsRng).public_key;
+        assert!(PublicKey::quick_check(&ecc.to_bytes()));
+        assert!(PublicKey::quick_check(&ed.to_bytes()));
+        assert!(!PublicKey::quick_check(&ed.to_bytes()[..32]));
+        let mut bytes = [0xffu8; PUBLIC_KEY_LENGTH];
+        bytes[0] = 0x00;
+        assert!(!PublicKey::quick_check(&bytes));
+        bytes[0] = 0x01;
+        bytes[32] = 0x7f;
+        assert!(!PublicKey::quick_check(&bytes));
+        // Only the network nibble of a valid key differs
+        bytes.copy_from_slice(&ed.to_bytes());
+        bytes[0] = 0x21;
+        assert!(PublicKey::quick_check(&bytes));
+        bytes[0] = 0x31;
+        assert!(!PublicKey::quick_check(&bytes));
+    }
+
+    #[test]
+    fn display_formats() {
+        const B58: &str = "13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw";
+        let public_key: PublicKey = B58.parse().expect("public key");
+        assert_eq!(B58, public_key.to_string());
+        assert_eq!("13Wv…esKw", format!("{:#}", public_key));
+        assert_eq!(
+            "014b5587796832132f039f4ca97f48c0592eda0e381a4d42aad290ad728d26ec66",
+            public_key.display(DisplayFormat::Hex).to_string()
+        );
+        assert_eq!(
+            format!("ed25519:{}", B58),
+            public_key.display(DisplayFormat::Annotated).to_string()
+        );
+    }
+
+    #[test]
+    fn other_key_type_passthrough() {
+        let mut bytes = [0x42u8; PUBLIC_KEY_LENGTH];
+        bytes[0] = 0x1c;
+        let public_key = PublicKey::from_bytes(&bytes[..]).expect("public key");
+        assert_eq!(KeyType::Other(0x0c), public_key.tag().key_type);
+        assert_eq!(bytes[..], public_key.to_bytes()[..]);
+        let encoded = public_key.to_string();
+        assert_eq!(public_key, encoded.parse().expect("b58 public key"));
+        assert!(public_key.verify(b"hello world", &[0u8; 64]).is_err());
+    }
+
+    #[test]
+    fn empty_bytes() {
+        assert!(PublicKey::try_from(&[0u8; 0][..]).is_err());
+        assert!(PublicKey::try_from(Vec::new()).is_err());
+    }
+
+    #[cfg(feature = "proto")]
+    #[test]
+    fn proto_bytes() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let bytes = prost::bytes::Bytes::from(&keypair.public_key);
+        assert_eq!(keypair.public_key.to_vec(), &bytes[..]);
+        assert_eq!(
+            keypair.public_key,
+            PublicKey::try_from(&bytes).expect("public key")
+        );
+        assert!(PublicKey::try_from(prost::bytes::Bytes::new()).is_err());
+    }
 }
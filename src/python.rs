@@ -0,0 +1,120 @@
+//! Python bindings built with [PyO3](https://pyo3.rs), for scripts that
+//! generate, parse and use keys without shelling out to a CLI.
+//!
+//! The extension module is named `helium_crypto` and is built from a `cdylib`
+//! with a tool like `maturin`:
+//!
+//! ```text
+//! >>> import helium_crypto
+//! >>> keypair = helium_crypto.Keypair.generate("mainnet/ed25519")
+//! >>> signature = keypair.sign(b"hello world")
+//! >>> keypair.public_key.verify(b"hello world", signature)
+//! True
+//! ```
+//!
+//! Errors are raised as `ValueError` with the message of the underlying
+//! error.
+use crate::{error, KeyTag, Keypair, PublicKey, Sign, Verify};
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
+use std::convert::TryFrom;
+
+impl From<error::Error> for PyErr {
+    fn from(err: error::Error) -> Self {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// A keypair of any of the supported key types.
+#[pyclass(name = "Keypair")]
+pub struct PyKeypair(Keypair);
+
+#[pymethods]
+impl PyKeypair {
+    /// Generate a keypair for the given key tag, like `mainnet/ecc_compact`.
+    #[staticmethod]
+    fn generate(tag: &str) -> PyResult<Self> {
+        let tag: KeyTag = tag.parse()?;
+        Ok(Self(Keypair::generate_default(tag)?))
+    }
+
+    /// Parse a keypair from its binary form.
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Ok(Self(Keypair::try_from(bytes)?))
+    }
+
+    /// The binary form of the keypair.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.0.to_vec())
+    }
+
+    #[getter]
+    fn public_key(&self) -> PyPublicKey {
+        PyPublicKey(self.0.public_key().clone())
+    }
+
+    /// Sign the given message.
+    fn sign<'py>(&self, py: Python<'py>, msg: &[u8]) -> PyResult<&'py PyBytes> {
+        Ok(PyBytes::new(py, &self.0.sign(msg)?))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Keypair({})", self.0)
+    }
+}
+
+/// A public key of any of the supported key types.
+#[pyclass(name = "PublicKey")]
+#[derive(Clone)]
+pub struct PyPublicKey(PublicKey);
+
+#[pymethods]
+impl PyPublicKey {
+    /// Parse a public key from its b58 address.
+    #[staticmethod]
+    fn from_b58(address: &str) -> PyResult<Self> {
+        Ok(Self(address.parse()?))
+    }
+
+    /// Parse a public key from its binary form.
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Ok(Self(PublicKey::try_from(bytes)?))
+    }
+
+    /// The binary form of the public key.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.0.to_vec())
+    }
+
+    /// The key tag of the public key, like `mainnet/ed25519`.
+    #[getter]
+    fn key_tag(&self) -> String {
+        self.0.tag().to_string()
+    }
+
+    /// Whether the given signature over the given message is valid for this
+    /// key.
+    fn verify(&self, msg: &[u8], signature: &[u8]) -> bool {
+        self.0.verify(msg, signature).is_ok()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("PublicKey({})", self.0)
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+#[pymodule]
+fn helium_crypto(_py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<PyKeypair>()?;
+    module.add_class::<PyPublicKey>()?;
+    Ok(())
+}
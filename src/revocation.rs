@@ -0,0 +1,333 @@
+//! Self-signed revocation statements.
+//!
+//! A revocation statement flags a key as no longer to be trusted as of a
+//! timestamp, with a reason and optionally the key that succeeds it. It is
+//! signed by the revoked key itself, in the [`context::REVOCATION`] domain,
+//! so anyone holding a compromised key can revoke it and anyone can check
+//! the revocation without further trust. Revocations should be created, and
+//! kept somewhere safe, while the key is still known to be good.
+//!
+//! The binary form of the statement starts with the four byte magic `HKRV`
+//! and a format version byte of 1, followed by the binary revoked key with a
+//! big endian `u16` length, the reason code byte, the timestamp in seconds
+//! since the unix epoch as a big endian `u64`, and the binary successor key
+//! with a big endian `u16` length, which is zero without a successor. A
+//! [`Revocation`] is serialized as the statement followed by the signature
+//! with a big endian `u16` length.
+//!
+//! The JSON form is an object with `key`, `reason`, `timestamp`, `successor`
+//! and `signature` fields, in that order. Keys are b58 addresses, the reason
+//! is its name, the successor is `null` when there is none, and the
+//! signature is base64 encoded.
+use crate::{context, error, Backend, PublicKey, Sign, Verify};
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt,
+    str::FromStr,
+};
+
+const MAGIC: &[u8; 4] = b"HKRV";
+const FORMAT_VERSION: u8 = 1;
+
+/// Why a key was revoked. The codes match the X.509 CRL reason codes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Reason {
+    Unspecified,
+    KeyCompromise,
+    Superseded,
+    CessationOfOperation,
+}
+
+impl From<Reason> for u8 {
+    fn from(v: Reason) -> Self {
+        match v {
+            Reason::Unspecified => 0,
+            Reason::KeyCompromise => 1,
+            Reason::Superseded => 4,
+            Reason::CessationOfOperation => 5,
+        }
+    }
+}
+
+impl TryFrom<u8> for Reason {
+    type Error = error::Error;
+    fn try_from(v: u8) -> error::Result<Self> {
+        match v {
+            0 => Ok(Self::Unspecified),
+            1 => Ok(Self::KeyCompromise),
+            4 => Ok(Self::Superseded),
+            5 => Ok(Self::CessationOfOperation),
+            _ => Err(error::invalid_revocation()),
+        }
+    }
+}
+
+impl Reason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "unspecified",
+            Self::KeyCompromise => "key_compromise",
+            Self::Superseded => "superseded",
+            Self::CessationOfOperation => "cessation_of_operation",
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Reason {
+    type Err = error::Error;
+    fn from_str(s: &str) -> error::Result<Self> {
+        [
+            Self::Unspecified,
+            Self::KeyCompromise,
+            Self::Superseded,
+            Self::CessationOfOperation,
+        ]
+        .iter()
+        .find(|reason| reason.as_str() == s)
+        .copied()
+        .ok_or_else(error::invalid_revocation)
+    }
+}
+
+/// A signed statement that `key` is revoked as of `timestamp`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Revocation {
+    /// The revoked key
+    pub key: PublicKey,
+    /// Why the key was revoked
+    pub reason: Reason,
+    /// When the key stops being trusted, in seconds since the unix epoch
+    pub timestamp: u64,
+    /// The key replacing the revoked key, if any
+    pub successor: Option<PublicKey>,
+    /// The signature of the revoked key over the statement
+    pub signature: Vec<u8>,
+}
+
+impl Revocation {
+    /// Sign a revocation of the key of the given keypair.
+    pub fn sign<K>(
+        keypair: &K,
+        reason: Reason,
+        timestamp: u64,
+        successor: Option<PublicKey>,
+    ) -> error::Result<Self>
+    where
+        K: Backend + ?Sized,
+    {
+        let mut result = Self {
+            key: keypair.public_key().clone(),
+            reason,
+            timestamp,
+            successor,
+            signature: vec![],
+        };
+        let statement = result.statement()?;
+        result.signature = keypair.sign_with_context(context::REVOCATION, &statement)?;
+        Ok(result)
+    }
+
+    /// The binary form of the statement the revoked key signs.
+    pub fn statement(&self) -> error::Result<Vec<u8>> {
+        if self.successor.as_ref() == Some(&self.key) {
+            return Err(error::invalid_revocation());
+        }
+        let mut result = MAGIC.to_vec();
+        result.push(FORMAT_VERSION);
+        write_bytes(&mut result, &self.key.to_vec())?;
+        result.push(self.reason.into());
+        result.extend_from_slice(&self.timestamp.to_be_bytes());
+        let successor = self.successor.as_ref().map(PublicKey::to_vec);
+        write_bytes(&mut result, successor.as_deref().unwrap_or_default())?;
+        Ok(result)
+    }
+
+    /// Check that the revoked key signed the statement.
+    pub fn verify(&self) -> error::Result {
+        self.key
+            .verify_with_context(context::REVOCATION, &self.statement()?, &self.signature)
+    }
+
+    /// Convert the revocation to its binary form.
+    pub fn to_vec(&self) -> error::Result<Vec<u8>> {
+        let mut result = self.statement()?;
+        write_bytes(&mut result, &self.signature)?;
+        Ok(result)
+    }
+
+    /// Convert the revocation to its JSON form.
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"key":"{}","reason":"{}","timestamp":{},"successor":{},"signature":"{}"}}"#,
+            self.key,
+            self.reason,
+            self.timestamp,
+            self.successor
+                .as_ref()
+                .map_or_else(|| "null".to_string(), |key| format!(r#""{}""#, key)),
+            base64::encode(&self.signature),
+        )
+    }
+
+    /// Parse the JSON form of a revocation. Unknown fields are rejected.
+    pub fn from_json(s: &str) -> error::Result<Self> {
+        let value: serde_json::Value =
+            serde_json::from_str(s).map_err(|_| error::invalid_revocation())?;
+        let object = value.as_object().ok_or_else(error::invalid_revocation)?;
+        if object.len() != 5 {
+            return Err(error::invalid_revocation());
+        }
+        let str_field = |name: &str| {
+            object
+                .get(name)
+                .and_then(serde_json::Value::as_str)
+                .ok_or_else(error::invalid_revocation)
+        };
+        let key = |s: &str| {
+            s.parse::<PublicKey>()
+                .map_err(|_| error::invalid_revocation())
+        };
+        let successor = match object.get("successor") {
+            Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::String(successor)) => Some(key(successor.as_str())?),
+            _ => return Err(error::invalid_revocation()),
+        };
+        Ok(Self {
+            key: key(str_field("key")?)?,
+            reason: str_field("reason")?.parse()?,
+            timestamp: object
+                .get("timestamp")
+                .and_then(serde_json::Value::as_u64)
+                .ok_or_else(error::invalid_revocation)?,
+            successor,
+            signature: base64::decode(str_field("signature")?)
+                .map_err(|_| error::invalid_revocation())?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for Revocation {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        let mut input = match input.strip_prefix(&MAGIC[..]) {
+            Some([FORMAT_VERSION, rest @ ..]) => rest,
+            _ => return Err(error::invalid_revocation()),
+        };
+        let key = read_key(&mut input)?;
+        let [reason] = read_array(&mut input)?;
+        let reason = Reason::try_from(reason)?;
+        let timestamp = u64::from_be_bytes(read_array(&mut input)?);
+        let successor = match read_bytes(&mut input)? {
+            [] => None,
+            bytes => Some(PublicKey::from_bytes(bytes).map_err(|_| error::invalid_revocation())?),
+        };
+        let signature = read_bytes(&mut input)?.to_vec();
+        if !input.is_empty() || successor.as_ref() == Some(&key) {
+            return Err(error::invalid_revocation());
+        }
+        Ok(Self {
+            key,
+            reason,
+            timestamp,
+            successor,
+            signature,
+        })
+    }
+}
+
+fn write_bytes(output: &mut Vec<u8>, value: &[u8]) -> error::Result {
+    let len = u16::try_from(value.len()).map_err(|_| error::invalid_revocation())?;
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(value);
+    Ok(())
+}
+
+fn read_array<const N: usize>(input: &mut &[u8]) -> error::Result<[u8; N]> {
+    if input.len() < N {
+        return Err(error::invalid_revocation());
+    }
+    let (bytes, rest) = input.split_at(N);
+    *input = rest;
+    Ok(bytes.try_into().expect("array length"))
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8]) -> error::Result<&'a [u8]> {
+    let len = u16::from_be_bytes(read_array(input)?) as usize;
+    if input.len() < len {
+        return Err(error::invalid_revocation());
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn read_key(input: &mut &[u8]) -> error::Result<PublicKey> {
+    PublicKey::from_bytes(read_bytes(input)?).map_err(|_| error::invalid_revocation())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reason, Revocation};
+    use crate::{ecc_compact, ed25519, Network};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sign_verify() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let successor = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        for successor in [None, Some(successor.public_key)] {
+            let revocation =
+                Revocation::sign(&keypair, Reason::KeyCompromise, 1_700_000_000, successor)
+                    .expect("revocation");
+            assert!(revocation.verify().is_ok());
+
+            let bytes = revocation.to_vec().expect("binary revocation");
+            assert_eq!(
+                revocation,
+                Revocation::try_from(&bytes[..]).expect("revocation")
+            );
+            assert!(Revocation::try_from(&bytes[..bytes.len() - 1]).is_err());
+            let json = revocation.to_json();
+            assert_eq!(
+                revocation,
+                Revocation::from_json(&json).expect("revocation")
+            );
+
+            let mut tampered = revocation;
+            tampered.reason = Reason::Superseded;
+            assert!(tampered.verify().is_err());
+        }
+        assert!(Revocation::sign(
+            &keypair,
+            Reason::Superseded,
+            1_700_000_000,
+            Some(keypair.public_key.clone())
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn json() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let revocation =
+            Revocation::sign(&keypair, Reason::Unspecified, 1, None).expect("revocation");
+        let json = revocation.to_json();
+        assert!(json.starts_with(&format!(
+            r#"{{"key":"{}","reason":"unspecified","timestamp":1,"successor":null,"signature":""#,
+            keypair.public_key
+        )));
+        assert!(Revocation::from_json(&json.replace("unspecified", "lost")).is_err());
+        assert!(Revocation::from_json(&json.replace("null", "null,\"extra\":1")).is_err());
+        assert_eq!(
+            Reason::CessationOfOperation,
+            "cessation_of_operation".parse().expect("reason")
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Key rotation statements.
+//!
+//! A rotation statement says that a new key replaces an old key as of a
+//! timestamp, and is signed by both keys: the old key authorizes its
+//! successor and the new key proves that whoever made the statement holds
+//! it. Both sign the statement in the [`context::ROTATION`] domain. A key
+//! rotated several times is followed from its first key through a chain of
+//! statements with [`verify_chain`]. [`Keypair::rekey`](crate::Keypair::rekey)
+//! and the `rekey` of the software keypairs return such a statement.
+//!
+//! The binary form of the statement starts with the four byte magic `HKRT`
+//! and a format version byte of 1, followed by the binary old and new keys,
+//! each with a big endian `u16` length, and the timestamp in seconds since
+//! the unix epoch as a big endian `u64`. A [`Rotation`] is serialized as the
+//! statement followed by the signatures of the old and the new key, each
+//! with a big endian `u16` length.
+use crate::{context, error, Backend, PublicKey, Sign, Verify};
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+
+const MAGIC: &[u8; 4] = b"HKRT";
+const FORMAT_VERSION: u8 = 1;
+
+/// A signed statement that `new_key` replaces `old_key` as of `timestamp`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Rotation {
+    /// The key being replaced
+    pub old_key: PublicKey,
+    /// The key replacing it
+    pub new_key: PublicKey,
+    /// When the new key takes over, in seconds since the unix epoch
+    pub timestamp: u64,
+    /// The signature of the old key over the statement
+    pub old_signature: Vec<u8>,
+    /// The signature of the new key over the statement
+    pub new_signature: Vec<u8>,
+}
+
+impl Rotation {
+    /// Sign a statement that the key of `new` replaces the key of `old` as of
+    /// the given timestamp.
+    pub fn sign<O, N>(old: &O, new: &N, timestamp: u64) -> error::Result<Self>
+    where
+        O: Backend + ?Sized,
+        N: Backend + ?Sized,
+    {
+        if old.public_key() == new.public_key() {
+            return Err(error::RotationError::SameKey.into());
+        }
+        let statement = statement(old.public_key(), new.public_key(), timestamp)?;
+        Ok(Self {
+            old_key: old.public_key().clone(),
+            new_key: new.public_key().clone(),
+            timestamp,
+            old_signature: old.sign_with_context(context::ROTATION, &statement)?,
+            new_signature: new.sign_with_context(context::ROTATION, &statement)?,
+        })
+    }
+
+    /// The binary form of the statement both keys sign.
+    pub fn statement(&self) -> error::Result<Vec<u8>> {
+        statement(&self.old_key, &self.new_key, self.timestamp)
+    }
+
+    /// Check that the keys differ and that both signed the statement.
+    pub fn verify(&self) -> error::Result {
+        if self.old_key == self.new_key {
+            return Err(error::RotationError::SameKey.into());
+        }
+        let statement = self.statement()?;
+        self.old_key
+            .verify_with_context(context::ROTATION, &statement, &self.old_signature)?;
+        self.new_key
+            .verify_with_context(context::ROTATION, &statement, &self.new_signature)
+    }
+
+    /// Convert the rotation to its binary form.
+    pub fn to_vec(&self) -> error::Result<Vec<u8>> {
+        let mut result = self.statement()?;
+        write_bytes(&mut result, &self.old_signature)?;
+        write_bytes(&mut result, &self.new_signature)?;
+        Ok(result)
+    }
+}
+
+impl TryFrom<&[u8]> for Rotation {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        let mut input = match input.strip_prefix(&MAGIC[..]) {
+            Some([FORMAT_VERSION, rest @ ..]) => rest,
+            _ => return Err(error::invalid_rotation()),
+        };
+        let old_key = read_key(&mut input)?;
+        let new_key = read_key(&mut input)?;
+        let timestamp = u64::from_be_bytes(read_array(&mut input)?);
+        let old_signature = read_bytes(&mut input)?.to_vec();
+        let new_signature = read_bytes(&mut input)?.to_vec();
+        if !input.is_empty() {
+            return Err(error::invalid_rotation());
+        }
+        Ok(Self {
+            old_key,
+            new_key,
+            timestamp,
+            old_signature,
+            new_signature,
+        })
+    }
+}
+
+/// Follow a chain of rotations from the given first key and return the
+/// current key. Every rotation has to verify, replace the key the previous
+/// rotation introduced, and be later than the previous rotation. An empty
+/// chain leaves the first key current.
+pub fn verify_chain(first: &PublicKey, rotations: &[Rotation]) -> error::Result<PublicKey> {
+    let mut current = first;
+    let mut last_timestamp = None;
+    for (index, rotation) in rotations.iter().enumerate() {
+        if &rotation.old_key != current {
+            return Err(error::RotationError::Chain(index).into());
+        }
+        if matches!(last_timestamp, Some(last) if rotation.timestamp <= last) {
+            return Err(error::RotationError::Timestamp(index).into());
+        }
+        rotation.verify()?;
+        current = &rotation.new_key;
+        last_timestamp = Some(rotation.timestamp);
+    }
+    Ok(current.clone())
+}
+
+fn statement(old_key: &PublicKey, new_key: &PublicKey, timestamp: u64) -> error::Result<Vec<u8>> {
+    let mut result = MAGIC.to_vec();
+    result.push(FORMAT_VERSION);
+    write_bytes(&mut result, &old_key.to_vec())?;
+    write_bytes(&mut result, &new_key.to_vec())?;
+    result.extend_from_slice(&timestamp.to_be_bytes());
+    Ok(result)
+}
+
+fn write_bytes(output: &mut Vec<u8>, value: &[u8]) -> error::Result {
+    let len = u16::try_from(value.len()).map_err(|_| error::invalid_rotation())?;
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(value);
+    Ok(())
+}
+
+fn read_array<const N: usize>(input: &mut &[u8]) -> error::Result<[u8; N]> {
+    if input.len() < N {
+        return Err(error::invalid_rotation());
+    }
+    let (bytes, rest) = input.split_at(N);
+    *input = rest;
+    Ok(bytes.try_into().expect("array length"))
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8]) -> error::Result<&'a [u8]> {
+    let len = u16::from_be_bytes(read_array(input)?) as usize;
+    if input.len() < len {
+        return Err(error::invalid_rotation());
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn read_key(input: &mut &[u8]) -> error::Result<PublicKey> {
+    PublicKey::from_bytes(read_bytes(input)?).map_err(|_| error::invalid_rotation())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_chain, Rotation};
+    use crate::{ecc_compact, ed25519, Network, Sign};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sign_verify() {
+        let old = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let new = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let rotation = Rotation::sign(&old, &new, 1_700_000_000).expect("rotation");
+        assert!(rotation.verify().is_ok());
+
+        let bytes = rotation.to_vec().expect("binary rotation");
+        assert_eq!(rotation, Rotation::try_from(&bytes[..]).expect("rotation"));
+        assert!(Rotation::try_from(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut tampered = rotation.clone();
+        tampered.timestamp += 1;
+        assert!(tampered.verify().is_err());
+        let mut tampered = rotation.clone();
+        tampered.new_signature = tampered.old_signature.clone();
+        assert!(tampered.verify().is_err());
+        // Plain signatures over the statement are not a rotation
+        let statement = rotation.statement().expect("statement");
+        let mut forged = rotation;
+        forged.old_signature = old.sign(&statement).expect("signature");
+        forged.new_signature = new.sign(&statement).expect("signature");
+        assert!(forged.verify().is_err());
+
+        assert!(Rotation::sign(&old, &old, 1_700_000_000).is_err());
+    }
+
+    #[test]
+    fn chain() {
+        let keys: Vec<ed25519::Keypair> = (0..3)
+            .map(|_| ed25519::Keypair::generate(Network::MainNet, &mut OsRng))
+            .collect();
+        let first = Rotation::sign(&keys[0], &keys[1], 100).expect("rotation");
+        let second = Rotation::sign(&keys[1], &keys[2], 200).expect("rotation");
+        let chain = [first.clone(), second.clone()];
+        assert_eq!(
+            keys[2].public_key,
+            verify_chain(&keys[0].public_key, &chain).expect("chain")
+        );
+        assert_eq!(
+            keys[0].public_key,
+            verify_chain(&keys[0].public_key, &[]).expect("chain")
+        );
+        assert!(verify_chain(&keys[1].public_key, &chain).is_err());
+        assert!(verify_chain(&keys[0].public_key, &[second.clone(), first.clone()]).is_err());
+
+        let late = Rotation::sign(&keys[1], &keys[2], 100).expect("rotation");
+        assert!(verify_chain(&keys[0].public_key, &[first, late]).is_err());
+    }
+}
@@ -0,0 +1,443 @@
+//! RSA keypairs, for enterprise HSM and smartcard deployments that can only
+//! host RSA keys.
+//!
+//! Only 2048 and 3072 bit keys are supported. Signatures are over the SHA-256
+//! digest of the message. `Sign` and `Verify` use PKCS#1 v1.5 padding, which
+//! every HSM supports and which is deterministic, while PSS padding is
+//! available through [`Keypair::sign_with_scheme`] and
+//! [`PublicKey::verify_with_scheme`].
+//!
+//! RSA keys are far larger than a [`crate::PublicKey`], so like P-384 keys
+//! these have their own [`PublicKey`] type. Every key type nibble is taken,
+//! so their binary forms start with a two byte extended tag instead of a key
+//! tag: [`NETTYPE_EXTENDED`] with [`KEYTYPE_EXTENDED_RSA`], followed by the
+//! network. Parsers that do not know extended tags reject the first byte as
+//! an unassigned network. The binary form of a public key is the tag followed
+//! by the PKCS#1 DER encoding of the public key, and its address is the
+//! b58check encoding of that form. Keypairs are the tag followed by the PKCS#1
+//! DER encoding of the private key.
+use crate::{
+    entropy::{EntropyRng, EntropySource},
+    error, keypair,
+    keypair::Prehash,
+    output_prefix,
+    public_key::VerifyOptions,
+    IntoBytes, KeyType, Network, Sign, Verify, KEYTYPE_EXTENDED_RSA, NETTYPE_EXTENDED,
+};
+use ::rsa::{
+    pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey, EncodeRsaPrivateKey, EncodeRsaPublicKey},
+    sha2::{Digest, Sha256},
+    traits::PublicKeyParts,
+    Pkcs1v15Sign, Pss, RsaPrivateKey, RsaPublicKey,
+};
+use std::convert::TryFrom;
+
+/// Length of the extended tag of the binary forms.
+pub const TAG_LENGTH: usize = 2;
+
+/// The supported modulus sizes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum KeySize {
+    Rsa2048,
+    Rsa3072,
+}
+
+impl KeySize {
+    /// The size of the modulus in bits.
+    pub fn bits(&self) -> usize {
+        match self {
+            Self::Rsa2048 => 2048,
+            Self::Rsa3072 => 3072,
+        }
+    }
+
+    fn from_modulus(key: &RsaPublicKey) -> error::Result<Self> {
+        match key.size() * 8 {
+            2048 => Ok(Self::Rsa2048),
+            3072 => Ok(Self::Rsa3072),
+            _ => Err(error::invalid_rsa_key()),
+        }
+    }
+}
+
+/// The padding of a signature.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Scheme {
+    /// RSASSA-PKCS1-v1_5 with SHA-256
+    Pkcs1v15,
+    /// RSASSA-PSS with SHA-256, MGF1 with SHA-256 and a 32 byte salt
+    Pss,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PublicKey {
+    /// The network this key is valid for
+    pub network: Network,
+    inner: RsaPublicKey,
+}
+
+pub type Keypair = keypair::Keypair<RsaPrivateKey, PublicKey>;
+
+/// RSA errors carry nothing worth keeping for a failed signature.
+fn signature_error<E>(_: E) -> error::Error {
+    signature::Error::new().into()
+}
+
+/// The extended tag of keys for the given network.
+fn tag(network: Network) -> [u8; TAG_LENGTH] {
+    [NETTYPE_EXTENDED | KEYTYPE_EXTENDED_RSA, u8::from(network)]
+}
+
+/// Splits the extended tag off the given binary form, returning the network
+/// it is tagged for and the DER encoded key.
+fn split_tag(input: &[u8]) -> error::Result<(Network, &[u8])> {
+    if input.len() < TAG_LENGTH {
+        return Err(error::invalid_length(TAG_LENGTH, input.len()));
+    }
+    let (tag, der) = input.split_at(TAG_LENGTH);
+    if tag[0] != NETTYPE_EXTENDED | KEYTYPE_EXTENDED_RSA {
+        return Err(error::invalid_keytype(tag[0]));
+    }
+    // The network byte carries no key type
+    if tag[1] & 0x0f != 0 {
+        return Err(error::invalid_network(tag[1]));
+    }
+    Ok((Network::try_from(tag[1])?, der))
+}
+
+impl Keypair {
+    /// Generate a keypair with a modulus of the given size. Generating a
+    /// 3072 bit key can take several seconds.
+    pub fn generate<E>(network: Network, size: KeySize, entropy: &mut E) -> error::Result<Keypair>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let inner = RsaPrivateKey::new(&mut EntropyRng(entropy), size.bits())
+            .map_err(|_| error::invalid_rsa_key())?;
+        Self::from_private_key(network, inner)
+    }
+
+    /// Generate a keypair using the operating system random number generator.
+    pub fn generate_default(network: Network, size: KeySize) -> error::Result<Keypair> {
+        Self::generate(network, size, &mut rand_core::OsRng)
+    }
+
+    /// Construct a keypair from a PKCS#1 DER encoded private key, as
+    /// exported by most HSMs.
+    pub fn from_pkcs1_der(network: Network, input: &[u8]) -> error::Result<Keypair> {
+        let inner = RsaPrivateKey::from_pkcs1_der(input).map_err(|_| error::invalid_rsa_key())?;
+        Self::from_private_key(network, inner)
+    }
+
+    /// Construct a keypair for the given network from a private key, which
+    /// has to have a supported modulus size.
+    pub fn from_private_key(network: Network, inner: RsaPrivateKey) -> error::Result<Keypair> {
+        let public_key = PublicKey::new(network, inner.to_public_key())?;
+        inner.validate().map_err(|_| error::invalid_rsa_key())?;
+        Ok(Keypair {
+            network,
+            public_key,
+            inner,
+        })
+    }
+
+    /// The same keypair tagged for the given network.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self.public_key.network = network;
+        self
+    }
+
+    /// The extended tag of the binary form.
+    pub fn tag(&self) -> [u8; TAG_LENGTH] {
+        self.public_key.tag()
+    }
+
+    pub fn key_size(&self) -> KeySize {
+        self.public_key.key_size()
+    }
+
+    export_secret! {
+        fn to_vec(&self) -> Vec<u8> {
+            let mut result = self.tag().to_vec();
+            let der = self.inner.to_pkcs1_der().expect("rsa private key");
+            result.extend_from_slice(der.as_bytes());
+            result
+        }
+    }
+
+    /// Sign the SHA-256 digest of the given message with the given padding.
+    /// The given entropy blinds the private key operation and provides the
+    /// PSS salt.
+    pub fn sign_with_scheme<E>(
+        &self,
+        scheme: Scheme,
+        msg: &[u8],
+        entropy: &mut E,
+    ) -> error::Result<Vec<u8>>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let digest = Sha256::digest(msg);
+        let mut rng = EntropyRng(entropy);
+        match scheme {
+            Scheme::Pkcs1v15 => {
+                self.inner
+                    .sign_with_rng(&mut rng, Pkcs1v15Sign::new::<Sha256>(), &digest)
+            }
+            Scheme::Pss => self
+                .inner
+                .sign_with_rng(&mut rng, Pss::new::<Sha256>(), &digest),
+        }
+        .map_err(signature_error)
+    }
+}
+
+impl Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        self.sign_with_scheme(Scheme::Pkcs1v15, msg, &mut rand_core::OsRng)
+    }
+
+    /// RSA signatures do not fit a [`keypair::SignatureBuf`], so this always
+    /// fails with `Error::UnsupportedKeyType`.
+    fn sign_buf(&self, _msg: &[u8]) -> error::Result<keypair::SignatureBuf> {
+        Err(error::unsupported_keytype(KeyType::Rsa))
+    }
+
+    /// RSA signatures do not fit [`keypair::MAX_SIGNATURE_LENGTH`], so this
+    /// always fails with `Error::UnsupportedKeyType`.
+    #[cfg(feature = "heapless")]
+    fn sign_heapless(
+        &self,
+        _msg: &[u8],
+    ) -> error::Result<heapless::Vec<u8, { keypair::MAX_SIGNATURE_LENGTH }>> {
+        Err(error::unsupported_keytype(KeyType::Rsa))
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        let (network, der) = split_tag(input)?;
+        Self::from_pkcs1_der(network, der)
+    }
+}
+
+impl IntoBytes for Keypair {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let der = self
+            .inner
+            .to_pkcs1_der()
+            .map_err(|_| error::invalid_rsa_key())?;
+        let output = output_prefix(output, TAG_LENGTH + der.as_bytes().len())?;
+        output[..TAG_LENGTH].copy_from_slice(&self.tag());
+        output[TAG_LENGTH..].copy_from_slice(der.as_bytes());
+        Ok(output.len())
+    }
+}
+
+/// Keypairs never include their secret in `Debug` output.
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("network", &self.network)
+            .field("public", &self.public_key)
+            .field("secret", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl PublicKey {
+    /// The public key for the given network, which has to have a supported
+    /// modulus size.
+    pub fn new(network: Network, inner: RsaPublicKey) -> error::Result<Self> {
+        KeySize::from_modulus(&inner)?;
+        Ok(Self { network, inner })
+    }
+
+    /// Construct a public key from a PKCS#1 DER encoded public key.
+    pub fn from_pkcs1_der(network: Network, input: &[u8]) -> error::Result<Self> {
+        let inner = RsaPublicKey::from_pkcs1_der(input).map_err(|_| error::invalid_rsa_key())?;
+        Self::new(network, inner)
+    }
+
+    /// The extended tag of the binary form.
+    pub fn tag(&self) -> [u8; TAG_LENGTH] {
+        tag(self.network)
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        KeyType::Rsa
+    }
+
+    pub fn key_size(&self) -> KeySize {
+        KeySize::from_modulus(&self.inner).expect("rsa key size")
+    }
+
+    /// Convert the key to its binary form.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = self.tag().to_vec();
+        result.extend_from_slice(&self.to_pkcs1_der());
+        result
+    }
+
+    /// The PKCS#1 DER encoding of the public key.
+    pub fn to_pkcs1_der(&self) -> Vec<u8> {
+        self.inner
+            .to_pkcs1_der()
+            .expect("rsa public key")
+            .as_bytes()
+            .to_vec()
+    }
+
+    /// Verify a signature with the given padding over the SHA-256 digest of
+    /// the given message.
+    pub fn verify_with_scheme(
+        &self,
+        scheme: Scheme,
+        msg: &[u8],
+        signature: &[u8],
+    ) -> error::Result {
+        self.verify_digest_with_scheme(scheme, &Sha256::digest(msg), signature)
+    }
+
+    fn verify_digest_with_scheme(
+        &self,
+        scheme: Scheme,
+        digest: &[u8],
+        signature: &[u8],
+    ) -> error::Result {
+        match scheme {
+            Scheme::Pkcs1v15 => self
+                .inner
+                .verify(Pkcs1v15Sign::new::<Sha256>(), digest, signature),
+            Scheme::Pss => self.inner.verify(Pss::new::<Sha256>(), digest, signature),
+        }
+        .map_err(signature_error)
+    }
+}
+
+impl Verify for PublicKey {
+    /// PKCS#1 v1.5 signatures have a single encoding, so the options do not
+    /// apply.
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        _options: &VerifyOptions,
+    ) -> error::Result {
+        self.verify_with_scheme(Scheme::Pkcs1v15, msg, signature)
+    }
+
+    fn verify_digest_with_options(
+        &self,
+        digest: Prehash,
+        signature: &[u8],
+        _options: &VerifyOptions,
+    ) -> error::Result {
+        match digest {
+            Prehash::Sha256(digest) => {
+                use sha2::Digest;
+                self.verify_digest_with_scheme(Scheme::Pkcs1v15, &digest.finalize(), signature)
+            }
+            Prehash::Sha256Digest(digest) => {
+                self.verify_digest_with_scheme(Scheme::Pkcs1v15, &digest, signature)
+            }
+            Prehash::Sha512(_) | Prehash::Sha512Digest(_) => Err(error::unsupported_prehash()),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        let (network, der) = split_tag(input)?;
+        Self::from_pkcs1_der(network, der)
+    }
+}
+
+impl std::str::FromStr for PublicKey {
+    type Err = error::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let data = bs58::decode(s).with_check(Some(0)).into_vec()?;
+        Self::try_from(&data[1..])
+    }
+}
+
+impl std::fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let mut data = vec![0u8];
+        data.extend_from_slice(&self.to_vec());
+        f.write_str(&bs58::encode(data).with_check().into_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeySize, Keypair, PublicKey, Scheme, TAG_LENGTH};
+    use crate::{Error, KeyTag, KeyType, Network, Sign, Verify};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair =
+            Keypair::generate(Network::MainNet, KeySize::Rsa2048, &mut OsRng).expect("keypair");
+        assert_eq!(KeySize::Rsa2048, keypair.key_size());
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert_eq!(256, signature.len());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair.public_key.verify(b"hello", &signature).is_err());
+        // RSA signatures don't fit a fixed size signature buffer
+        assert!(matches!(
+            keypair.sign_buf(b"hello world"),
+            Err(Error::UnsupportedKeyType(KeyType::Rsa))
+        ));
+
+        let signature = keypair
+            .sign_with_scheme(Scheme::Pss, b"hello world", &mut OsRng)
+            .expect("pss signature");
+        assert!(keypair
+            .public_key
+            .verify_with_scheme(Scheme::Pss, b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair =
+            Keypair::generate(Network::TestNet, KeySize::Rsa2048, &mut OsRng).expect("keypair");
+        let bytes = keypair.to_vec();
+        assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+        assert_eq!(KeyType::Rsa, keypair.public_key.key_type());
+        assert_eq!([0x70, 0x10], bytes[..TAG_LENGTH]);
+        // Extended tags are not key tags, and no key tag is an RSA tag
+        assert!(KeyTag::try_from(bytes[0]).is_err());
+        assert_eq!(
+            KeyType::Other(0x08),
+            KeyType::try_from(0x08).expect("key type")
+        );
+
+        let public_key = keypair.public_key.to_vec();
+        assert_eq!(
+            keypair.public_key,
+            PublicKey::try_from(&public_key[..]).expect("public key")
+        );
+        let address = keypair.public_key.to_string();
+        assert_eq!(keypair.public_key, address.parse().expect("address"));
+        assert!(PublicKey::try_from(&public_key[..public_key.len() - 1]).is_err());
+        let mut other = public_key.clone();
+        other[0] = 0x08;
+        assert!(PublicKey::try_from(&other[..]).is_err());
+        other[0] = 0x70;
+        other[1] = 0x11;
+        assert!(PublicKey::try_from(&other[..]).is_err());
+    }
+}
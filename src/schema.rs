@@ -0,0 +1,116 @@
+//! [JSON Schema](https://json-schema.org) definitions for the string forms of
+//! public types, as produced by their `Display` implementations, so API
+//! specifications generated with [schemars](https://docs.rs/schemars)
+//! document the actual format.
+use crate::{multisig::MultisigPublicKey, KeyTag, KeyType, Network, PublicKey};
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation},
+    JsonSchema,
+};
+
+/// The base58 alphabet as a regular expression character class.
+const B58_CLASS: &str = "[1-9A-HJ-NP-Za-km-z]";
+
+fn string_schema(description: &str, pattern: String) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        metadata: Some(Box::new(Metadata {
+            description: Some(description.to_string()),
+            ..Default::default()
+        })),
+        string: Some(Box::new(StringValidation {
+            pattern: Some(pattern),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+impl JsonSchema for PublicKey {
+    fn schema_name() -> String {
+        "PublicKey".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema(
+            "A base58check encoded Helium public key",
+            format!("^{}{{50,53}}$", B58_CLASS),
+        )
+    }
+}
+
+impl JsonSchema for MultisigPublicKey {
+    fn schema_name() -> String {
+        "MultisigPublicKey".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema(
+            "A base58check encoded Helium multisig public key",
+            format!("^{}{{54,58}}$", B58_CLASS),
+        )
+    }
+}
+
+impl JsonSchema for Network {
+    fn schema_name() -> String {
+        "Network".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema(
+            "A Helium network, or the hex value of a custom network",
+            "^(mainnet|testnet|devnet|0x[89a-f]0)$".to_string(),
+        )
+    }
+}
+
+impl JsonSchema for KeyType {
+    fn schema_name() -> String {
+        "KeyType".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema(
+            "A key type, or the hex value of an experimental or vendor key type",
+            "^(ed25519|ecc_compact|secp256k1|multisig|0x0[89a-f])$".to_string(),
+        )
+    }
+}
+
+impl JsonSchema for KeyTag {
+    fn schema_name() -> String {
+        "KeyTag".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema(
+            "A network and key type",
+            "^(mainnet|testnet|devnet|0x[89a-f]0)/(ed25519|ecc_compact|secp256k1|multisig|0x0[89a-f])$".to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{KeyTag, PublicKey};
+    use schemars::schema_for;
+
+    fn pattern<T: schemars::JsonSchema>() -> String {
+        match schema_for!(T).schema.string {
+            Some(string) => string.pattern.expect("pattern"),
+            None => panic!("not a string schema"),
+        }
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(
+            "^(mainnet|testnet|devnet|0x[89a-f]0)/(ed25519|ecc_compact|secp256k1|multisig|0x0[89a-f])$",
+            pattern::<KeyTag>()
+        );
+        assert!(pattern::<PublicKey>().starts_with("^[1-9A-HJ-NP-Za-km-z]"));
+    }
+}
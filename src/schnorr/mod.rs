@@ -0,0 +1,463 @@
+//! BIP340 Schnorr signatures over secp256k1, for interoperating with Taproot
+//! style protocols.
+//!
+//! Public keys are x-only, the 32 byte x coordinate of a point with an
+//! implied even y coordinate. Any secret works, since BIP340 signing negates
+//! the secret of a point with an odd y. Signatures are the fixed size
+//! `R.x || s` form.
+//!
+//! Signing through [`Sign`](crate::Sign) uses an all zero auxiliary random
+//! input, which BIP340 allows and which makes signatures deterministic. Use
+//! [`Keypair::sign_with_aux`] to mix fresh randomness into the nonce as a
+//! protection against side channel attacks.
+//!
+//! The [`musig`] module aggregates the keys of a group of signers into a
+//! single key that the group signs for together.
+#[cfg(feature = "pkcs8")]
+use crate::pkcs8;
+use crate::{
+    entropy::{EntropyRng, EntropySource},
+    error, keypair,
+    keypair::Prehash,
+    output_prefix,
+    public_key::{self, VerifyOptions},
+    secp256k1,
+    secret::Secret,
+    IntoBytes, KeyTag, KeyType, Network,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use k256::{elliptic_curve::sec1::ToEncodedPoint, FieldBytes, ProjectivePoint, Scalar};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+#[cfg(feature = "alloc")]
+pub mod musig;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PublicKey(secp256k1::PublicKey);
+
+pub type Keypair = keypair::Keypair<Secret<k256::SecretKey>>;
+
+#[cfg(any(test, feature = "test-utils"))]
+impl keypair::DebugSecret for Keypair {
+    fn secret_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes().to_vec()
+    }
+}
+
+pub const KEYPAIR_LENGTH: usize = 33;
+
+/// The length of a BIP340 signature.
+pub const SIGNATURE_LENGTH: usize = 64;
+
+/// The length of an x-only public key.
+pub const X_ONLY_LENGTH: usize = 32;
+
+const TAG_AUX: &[u8] = b"BIP0340/aux";
+const TAG_NONCE: &[u8] = b"BIP0340/nonce";
+const TAG_CHALLENGE: &[u8] = b"BIP0340/challenge";
+
+/// SEC1 prefix of a compressed point with an even y coordinate.
+const SEC1_EVEN: u8 = 0x02;
+
+/// The BIP340 tagged hash of the concatenation of the given parts.
+fn tagged_hash(tag: &[u8], parts: &[&[u8]]) -> FieldBytes {
+    let tag = Sha256::digest(tag);
+    let mut hasher = Sha256::new();
+    hasher.update(&tag);
+    hasher.update(&tag);
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize()
+}
+
+fn challenge(r: &[u8], public_key: &[u8], msg: &[u8]) -> Scalar {
+    Scalar::from_bytes_reduced(&tagged_hash(TAG_CHALLENGE, &[r, public_key, msg]))
+}
+
+/// The SEC1 compressed encoding of the given point, or `None` for the
+/// identity.
+fn compressed(point: &ProjectivePoint) -> Option<[u8; 33]> {
+    let encoded = point.to_affine().to_encoded_point(true);
+    let mut result = [0u8; 33];
+    if encoded.as_bytes().len() != result.len() {
+        return None;
+    }
+    result.copy_from_slice(encoded.as_bytes());
+    Some(result)
+}
+
+impl keypair::Sign for Keypair {
+    #[cfg(feature = "alloc")]
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        Ok(self.sign_with_aux(msg, &[0u8; 32])?.to_vec())
+    }
+
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        keypair::copy_signature(&self.sign_with_aux(msg, &[0u8; 32])?, output)
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != KEYPAIR_LENGTH {
+            return Err(error::invalid_length(KEYPAIR_LENGTH, input.len()));
+        }
+        let network = Network::try_from(input[0])?;
+        Self::from_secret(network, &input[1..])
+    }
+}
+
+impl IntoBytes for Keypair {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let output = output_prefix(output, KEYPAIR_LENGTH)?;
+        output[0] = u8::from(KeyTag {
+            network: self.network,
+            key_type: KeyType::Schnorr,
+        });
+        let mut secret = self.inner.to_bytes();
+        output[1..].copy_from_slice(&secret);
+        secret[..].zeroize();
+        Ok(KEYPAIR_LENGTH)
+    }
+}
+
+impl Keypair {
+    /// Length of the binary form of keypairs of this type.
+    pub const LEN: usize = KEYPAIR_LENGTH;
+
+    pub fn generate<E>(network: Network, entropy: &mut E) -> Keypair
+    where
+        E: EntropySource + ?Sized,
+    {
+        Self::from_secret_key(network, k256::SecretKey::random(&mut EntropyRng(entropy)))
+    }
+
+    /// Generate a keypair using the operating system random number generator.
+    #[cfg(feature = "std")]
+    pub fn generate_default(network: Network) -> Keypair {
+        Self::generate(network, &mut rand_core::OsRng)
+    }
+
+    /// Replace the secret of this keypair with a freshly generated one for the
+    /// same network, zeroizing the old secret. The returned statement that the
+    /// new key replaces the old one as of `timestamp` is signed by both keys,
+    /// see [`rotation`](crate::rotation).
+    #[cfg(feature = "alloc")]
+    pub fn rekey<E>(
+        &mut self,
+        entropy: &mut E,
+        timestamp: u64,
+    ) -> error::Result<crate::rotation::Rotation>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let new = Self::generate(self.network, entropy);
+        self.rotate(new, timestamp)
+    }
+
+    /// Construct a keypair from a 32 byte secret key.
+    pub(crate) fn from_secret(network: Network, secret: &[u8]) -> error::Result<Keypair> {
+        Ok(Self::from_secret_key(
+            network,
+            k256::SecretKey::from_bytes(secret)?,
+        ))
+    }
+
+    fn from_secret_key(network: Network, secret: k256::SecretKey) -> Keypair {
+        let public_key = PublicKey::from_secret(&secret);
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            inner: secret.into(),
+        }
+    }
+
+    export_secret! {
+        fn to_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
+            let mut result = [0u8; KEYPAIR_LENGTH];
+            self.try_bytes_into(&mut result).expect("keypair length");
+            result
+        }
+    }
+
+    /// Sign the given message with the given 32 bytes of auxiliary random
+    /// data mixed into the nonce.
+    pub fn sign_with_aux(
+        &self,
+        msg: &[u8],
+        aux: &[u8; 32],
+    ) -> error::Result<[u8; SIGNATURE_LENGTH]> {
+        let (mut secret, point) = self.even_secret();
+        let mut masked = secret.to_bytes();
+        for (byte, mask) in masked.iter_mut().zip(tagged_hash(TAG_AUX, &[aux]).iter()) {
+            *byte ^= mask;
+        }
+        let mut nonce =
+            Scalar::from_bytes_reduced(&tagged_hash(TAG_NONCE, &[&masked, &point[1..], msg]));
+        masked[..].zeroize();
+        let r = match compressed(&(ProjectivePoint::generator() * nonce)) {
+            Some(r) => r,
+            None => {
+                secret.zeroize();
+                return Err(signature::Error::new().into());
+            }
+        };
+        if r[0] != SEC1_EVEN {
+            nonce = -nonce;
+        }
+        let s = nonce + challenge(&r[1..], &point[1..], msg) * secret;
+        secret.zeroize();
+        nonce.zeroize();
+        let mut result = [0u8; SIGNATURE_LENGTH];
+        result[..32].copy_from_slice(&r[1..]);
+        result[32..].copy_from_slice(&s.to_bytes());
+        Ok(result)
+    }
+
+    /// The secret scalar for the even y form of the public point, and the
+    /// compressed encoding of that point.
+    fn even_secret(&self) -> (Scalar, [u8; 33]) {
+        let mut secret = *self.inner.secret_scalar().as_ref();
+        let mut point =
+            compressed(&(ProjectivePoint::generator() * secret)).expect("non zero secret");
+        if point[0] != SEC1_EVEN {
+            secret = -secret;
+            point[0] = SEC1_EVEN;
+        }
+        (secret, point)
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+impl Keypair {
+    export_secret! {
+        /// Encode the keypair as an unencrypted PKCS#8 document in DER form.
+        /// PKCS#8 only names the curve, so the document is a secp256k1 key.
+        fn to_pkcs8_der(&self) -> Vec<u8> {
+            let public_key = self.inner.public_key().to_encoded_point(true);
+            pkcs8::encode_ec(
+                KeyType::Secp256k1,
+                &self.inner.to_bytes(),
+                public_key.as_bytes(),
+            )
+        }
+    }
+
+    /// Decode a DER encoded PKCS#8 secp256k1 key as a BIP340 key for the given
+    /// network.
+    pub fn from_pkcs8_der(network: Network, input: &[u8]) -> error::Result<Keypair> {
+        let (key_type, mut secret) = pkcs8::decode(input)?;
+        let result = match key_type {
+            KeyType::Secp256k1 => Self::from_secret(network, &secret),
+            _ => Err(error::invalid_keytype(u8::from(KeyTag {
+                network,
+                key_type,
+            }))),
+        };
+        secret[..].zeroize();
+        result
+    }
+}
+
+impl public_key::Verify for PublicKey {
+    /// BIP340 signatures are not malleable, so the options do not apply.
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        _options: &VerifyOptions,
+    ) -> error::Result {
+        if signature.len() != SIGNATURE_LENGTH {
+            return Err(signature::Error::new().into());
+        }
+        let (r, s) = signature.split_at(32);
+        if !secp256k1::is_field_element(r) || s >= &secp256k1::ORDER[..] {
+            return Err(signature::Error::new().into());
+        }
+        let s = Scalar::from_bytes_reduced(FieldBytes::from_slice(s));
+        let e = challenge(r, &self.to_x_only(), msg);
+        let point = k256::PublicKey::from_sec1_bytes(&self.0.to_sec1_compressed())?;
+        let expected =
+            ProjectivePoint::generator() * s - ProjectivePoint::from(*point.as_affine()) * e;
+        match compressed(&expected) {
+            Some(expected) if expected[0] == SEC1_EVEN && expected[1..] == *r => Ok(()),
+            _ => Err(signature::Error::new().into()),
+        }
+    }
+
+    fn verify_digest_with_options(
+        &self,
+        _digest: Prehash,
+        _signature: &[u8],
+        _options: &VerifyOptions,
+    ) -> error::Result {
+        Err(error::unsupported_prehash())
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = error::Error;
+
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        let x = input.get(1..).ok_or_else(error::not_compact)?;
+        if !Self::quick_check(x) {
+            return Err(error::not_compact());
+        }
+        Self::from_x_only(x)
+    }
+}
+
+impl TryFrom<&public_key::PublicKey> for PublicKey {
+    type Error = error::Error;
+
+    /// Fails for keys of any other key type.
+    fn try_from(v: &public_key::PublicKey) -> error::Result<Self> {
+        match &v.inner {
+            public_key::PublicKeyRepr::Schnorr(key) => Ok(key.clone()),
+            _ => Err(error::unsupported_keytype(v.tag().key_type)),
+        }
+    }
+}
+
+impl PublicKey {
+    fn from_secret(secret: &k256::SecretKey) -> Self {
+        let mut point = [SEC1_EVEN; 33];
+        point[1..].copy_from_slice(&secret.public_key().to_encoded_point(true).as_bytes()[1..]);
+        PublicKey(secp256k1::PublicKey::from_sec1_point(&point).expect("point on curve"))
+    }
+
+    /// Construct a key from its 32 byte x-only form.
+    pub fn from_x_only(input: &[u8]) -> error::Result<Self> {
+        if input.len() != X_ONLY_LENGTH {
+            return Err(error::invalid_length(X_ONLY_LENGTH, input.len()));
+        }
+        let mut point = [SEC1_EVEN; 33];
+        point[1..].copy_from_slice(input);
+        Ok(PublicKey(secp256k1::PublicKey::from_sec1_point(&point)?))
+    }
+
+    /// The 32 byte x-only form of the key, as used in BIP340.
+    pub fn to_x_only(&self) -> [u8; X_ONLY_LENGTH] {
+        let mut result = [0u8; X_ONLY_LENGTH];
+        result.copy_from_slice(&self.0.to_sec1_compressed()[1..]);
+        result
+    }
+
+    /// The SEC1 uncompressed encoding of the point with an even y coordinate
+    /// for this key.
+    pub fn to_sec1_uncompressed(&self) -> [u8; 65] {
+        self.0.to_sec1_uncompressed()
+    }
+
+    /// Checks that the given untagged key bytes are a field element, without
+    /// decompressing the point.
+    pub(crate) fn quick_check(input: &[u8]) -> bool {
+        secp256k1::is_field_element(input)
+    }
+
+    pub(crate) fn check_point(&self) -> error::Result {
+        self.0.check_point()
+    }
+}
+
+impl IntoBytes for PublicKey {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        output_prefix(output, X_ONLY_LENGTH)?.copy_from_slice(&self.to_x_only());
+        Ok(X_ONLY_LENGTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keypair, PublicKey};
+    use crate::{Network, Sign, Verify};
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert_eq!(64, signature.len());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world!", &signature)
+            .is_err());
+        let randomized = keypair
+            .sign_with_aux(b"hello world", &[7u8; 32])
+            .expect("signature");
+        assert_ne!(&signature[..], &randomized[..]);
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &randomized)
+            .is_ok());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        for _ in 0..16 {
+            let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+            assert_eq!(
+                keypair,
+                Keypair::try_from(&keypair.to_bytes()[..]).expect("keypair")
+            );
+            assert_eq!(
+                keypair.public_key,
+                crate::PublicKey::from_bytes(keypair.public_key.to_bytes()).expect("public key")
+            );
+            let public_key = PublicKey::try_from(&keypair.public_key).expect("schnorr key");
+            assert_eq!(
+                public_key,
+                PublicKey::from_x_only(&public_key.to_x_only()).expect("x-only key")
+            );
+        }
+    }
+
+    #[test]
+    fn bip340_vectors() {
+        // Vectors 0 and 1 from the BIP340 test vectors
+        const VECTORS: [([u8; 32], [u8; 32], [u8; 32], [u8; 32], [u8; 64]); 2] = [
+            (
+                hex!("0000000000000000000000000000000000000000000000000000000000000003"),
+                hex!("f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9"),
+                hex!("0000000000000000000000000000000000000000000000000000000000000000"),
+                hex!("0000000000000000000000000000000000000000000000000000000000000000"),
+                hex!(
+                    "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca8215"
+                    "25f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0"
+                ),
+            ),
+            (
+                hex!("b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef"),
+                hex!("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659"),
+                hex!("0000000000000000000000000000000000000000000000000000000000000001"),
+                hex!("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89"),
+                hex!(
+                    "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de3341"
+                    "8906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a"
+                ),
+            ),
+        ];
+        for (secret, public_key, aux, msg, signature) in VECTORS.iter() {
+            let keypair = Keypair::from_secret(Network::MainNet, secret).expect("keypair");
+            let x_only = PublicKey::try_from(&keypair.public_key).expect("schnorr key");
+            assert_eq!(public_key, &x_only.to_x_only());
+            let signed = keypair.sign_with_aux(msg, aux).expect("signature");
+            assert_eq!(&signature[..], &signed[..]);
+            assert!(keypair.public_key.verify(msg, signature).is_ok());
+            let mut tampered = *signature;
+            tampered[63] ^= 1;
+            assert!(keypair.public_key.verify(msg, &tampered).is_err());
+        }
+    }
+}
@@ -0,0 +1,534 @@
+//! MuSig2 multi-signatures for BIP340 keys, following BIP327, so that a group
+//! of signers can co-sign for a single aggregated key with an ordinary BIP340
+//! signature.
+//!
+//! Every signer creates a [`KeyAggContext`] from the keys of all signers, in
+//! the same order, and signing takes two rounds:
+//!
+//! 1. Every signer calls [`KeyAggContext::generate_nonce`], keeps the
+//!    [`SecretNonce`] and sends the [`PublicNonce`] to the other signers.
+//! 2. With the public nonces of all signers combined into an
+//!    [`AggregateNonce`], every signer calls [`KeyAggContext::sign`] and
+//!    sends its [`PartialSignature`] on.
+//!
+//! Anyone with the aggregate nonce and all partial signatures, and no
+//! secrets, combines them into the final signature with
+//! [`KeyAggContext::aggregate`]. Partial signatures can be checked one by
+//! one with [`KeyAggContext::verify_partial`] to find a misbehaving signer.
+//!
+//! Signing consumes the secret nonce, which must never be used for a second
+//! signature. Key tweaking is not supported.
+use super::{challenge, compressed, tagged_hash, Keypair, PublicKey, SEC1_EVEN};
+use crate::{entropy::EntropySource, error, public_key, secp256k1, Network};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use k256::{FieldBytes, ProjectivePoint, Scalar};
+use zeroize::Zeroize;
+
+const TAG_KEYAGG_LIST: &[u8] = b"KeyAgg list";
+const TAG_KEYAGG_COEFFICIENT: &[u8] = b"KeyAgg coefficient";
+const TAG_AUX: &[u8] = b"MuSig/aux";
+const TAG_NONCE: &[u8] = b"MuSig/nonce";
+const TAG_NONCE_COEFFICIENT: &[u8] = b"MuSig/noncecoef";
+
+/// The length of a public or aggregate nonce.
+pub const NONCE_LENGTH: usize = 66;
+
+/// The length of a partial signature.
+pub const PARTIAL_SIGNATURE_LENGTH: usize = 32;
+
+/// Decode a SEC1 compressed point.
+fn decode_point(input: &[u8]) -> error::Result<ProjectivePoint> {
+    if !matches!(input.first(), Some(&tag) if tag == SEC1_EVEN || tag == SEC1_EVEN + 1) {
+        return Err(error::invalid_musig());
+    }
+    let point = k256::PublicKey::from_sec1_bytes(input).map_err(|_| error::invalid_musig())?;
+    Ok(ProjectivePoint::from(*point.as_affine()))
+}
+
+/// The keys of a group of signers and their aggregate.
+#[derive(Debug, Clone)]
+pub struct KeyAggContext {
+    keys: Vec<[u8; 33]>,
+    coefficients: Vec<Scalar>,
+    points: Vec<ProjectivePoint>,
+    aggregate: ProjectivePoint,
+}
+
+/// The values every signer derives from the aggregate nonce and the message.
+struct Session {
+    nonce_coefficient: Scalar,
+    r: [u8; 33],
+    challenge: Scalar,
+}
+
+impl KeyAggContext {
+    /// Aggregate the given keys. Every signer has to use the same keys in the
+    /// same order.
+    pub fn new(keys: &[PublicKey]) -> error::Result<Self> {
+        if keys.is_empty() {
+            return Err(error::invalid_musig());
+        }
+        let keys: Vec<[u8; 33]> = keys.iter().map(|key| key.0.to_sec1_compressed()).collect();
+        let list: Vec<&[u8]> = keys.iter().map(|key| &key[..]).collect();
+        let list_hash = tagged_hash(TAG_KEYAGG_LIST, &list);
+        // The second distinct key gets a coefficient of one
+        let second = keys.iter().find(|key| **key != keys[0]);
+        let mut coefficients = Vec::with_capacity(keys.len());
+        let mut points = Vec::with_capacity(keys.len());
+        let mut aggregate = ProjectivePoint::identity();
+        for key in &keys {
+            let coefficient = if Some(key) == second {
+                Scalar::one()
+            } else {
+                Scalar::from_bytes_reduced(&tagged_hash(
+                    TAG_KEYAGG_COEFFICIENT,
+                    &[&list_hash, &key[..]],
+                ))
+            };
+            let point = decode_point(key)?;
+            aggregate = aggregate + point * coefficient;
+            coefficients.push(coefficient);
+            points.push(point);
+        }
+        if compressed(&aggregate).is_none() {
+            return Err(error::invalid_musig());
+        }
+        Ok(KeyAggContext {
+            keys,
+            coefficients,
+            points,
+            aggregate,
+        })
+    }
+
+    /// The aggregated BIP340 key.
+    pub fn aggregate_key(&self) -> PublicKey {
+        PublicKey::from_x_only(&self.x_only()).expect("aggregate key")
+    }
+
+    /// The aggregated key as a public key for the given network.
+    pub fn public_key(&self, network: Network) -> public_key::PublicKey {
+        public_key::PublicKey::for_network(network, self.aggregate_key())
+    }
+
+    fn x_only(&self) -> [u8; 32] {
+        let mut result = [0u8; 32];
+        result.copy_from_slice(&compressed(&self.aggregate).expect("aggregate key")[1..]);
+        result
+    }
+
+    /// One if the aggregate point has an even y coordinate, minus one if not.
+    fn parity(&self) -> Scalar {
+        match compressed(&self.aggregate) {
+            Some(point) if point[0] == SEC1_EVEN => Scalar::one(),
+            _ => -Scalar::one(),
+        }
+    }
+
+    fn coefficient(&self, key: &[u8; 33]) -> Option<(Scalar, ProjectivePoint)> {
+        let index = self.keys.iter().position(|other| other == key)?;
+        Some((self.coefficients[index], self.points[index]))
+    }
+
+    fn session(&self, nonce: &AggregateNonce, msg: &[u8]) -> Session {
+        let aggregate = self.x_only();
+        let nonce_coefficient = Scalar::from_bytes_reduced(&tagged_hash(
+            TAG_NONCE_COEFFICIENT,
+            &[&nonce.to_bytes(), &aggregate, msg],
+        ));
+        let r = compressed(&(nonce.r1 + nonce.r2 * nonce_coefficient))
+            .unwrap_or_else(|| compressed(&ProjectivePoint::generator()).expect("generator"));
+        Session {
+            nonce_coefficient,
+            challenge: challenge(&r[1..], &aggregate, msg),
+            r,
+        }
+    }
+
+    /// Generate the nonces of the given signer for a signature over the
+    /// given message.
+    pub fn generate_nonce<E>(
+        &self,
+        keypair: &Keypair,
+        msg: &[u8],
+        entropy: &mut E,
+    ) -> error::Result<(SecretNonce, PublicNonce)>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let mut random = [0u8; 32];
+        entropy.fill_entropy(&mut random)?;
+        let (mut secret, public_key) = keypair.even_secret();
+        let mut masked = secret.to_bytes();
+        secret.zeroize();
+        for (byte, mask) in masked
+            .iter_mut()
+            .zip(tagged_hash(TAG_AUX, &[&random]).iter())
+        {
+            *byte ^= mask;
+        }
+        random.zeroize();
+        let aggregate = self.x_only();
+        let msg_length = (msg.len() as u64).to_be_bytes();
+        let nonce = |index: u8| {
+            Scalar::from_bytes_reduced(&tagged_hash(
+                TAG_NONCE,
+                &[
+                    &masked,
+                    &[public_key.len() as u8],
+                    &public_key,
+                    &[aggregate.len() as u8],
+                    &aggregate,
+                    &[1],
+                    &msg_length,
+                    msg,
+                    &[0u8; 4],
+                    &[index],
+                ],
+            ))
+        };
+        let secret_nonce = SecretNonce {
+            k1: nonce(0),
+            k2: nonce(1),
+            public_key,
+        };
+        masked[..].zeroize();
+        if secret_nonce.k1 == Scalar::zero() || secret_nonce.k2 == Scalar::zero() {
+            return Err(error::entropy_failure());
+        }
+        let public_nonce = PublicNonce {
+            r1: ProjectivePoint::generator() * secret_nonce.k1,
+            r2: ProjectivePoint::generator() * secret_nonce.k2,
+        };
+        Ok((secret_nonce, public_nonce))
+    }
+
+    /// Make the partial signature of the given signer over the message, with
+    /// the nonce it generated for it.
+    pub fn sign(
+        &self,
+        keypair: &Keypair,
+        nonce: SecretNonce,
+        aggregate_nonce: &AggregateNonce,
+        msg: &[u8],
+    ) -> error::Result<PartialSignature> {
+        let public_key = PublicKey::try_from(&keypair.public_key)?
+            .0
+            .to_sec1_compressed();
+        if public_key != nonce.public_key {
+            return Err(error::invalid_musig());
+        }
+        let (coefficient, _) = self
+            .coefficient(&public_key)
+            .ok_or_else(error::invalid_musig)?;
+        let session = self.session(aggregate_nonce, msg);
+        let (mut k1, mut k2) = (nonce.k1, nonce.k2);
+        if session.r[0] != SEC1_EVEN {
+            k1 = -k1;
+            k2 = -k2;
+        }
+        let (mut secret, _) = keypair.even_secret();
+        let s = k1
+            + session.nonce_coefficient * k2
+            + session.challenge * coefficient * self.parity() * secret;
+        secret.zeroize();
+        k1.zeroize();
+        k2.zeroize();
+        Ok(PartialSignature(s))
+    }
+
+    /// Verify the partial signature of the signer with the given key and
+    /// public nonce.
+    pub fn verify_partial(
+        &self,
+        partial: &PartialSignature,
+        public_nonce: &PublicNonce,
+        key: &PublicKey,
+        aggregate_nonce: &AggregateNonce,
+        msg: &[u8],
+    ) -> error::Result {
+        let (coefficient, point) = self
+            .coefficient(&key.0.to_sec1_compressed())
+            .ok_or_else(error::invalid_musig)?;
+        let session = self.session(aggregate_nonce, msg);
+        let mut expected = public_nonce.r1 + public_nonce.r2 * session.nonce_coefficient;
+        if session.r[0] != SEC1_EVEN {
+            expected = -expected;
+        }
+        expected = expected + point * (session.challenge * coefficient * self.parity());
+        if ProjectivePoint::generator() * partial.0 != expected {
+            return Err(signature::Error::new().into());
+        }
+        Ok(())
+    }
+
+    /// Combine the partial signatures of all signers into a BIP340 signature
+    /// for the aggregated key.
+    pub fn aggregate(
+        &self,
+        aggregate_nonce: &AggregateNonce,
+        msg: &[u8],
+        partials: &[PartialSignature],
+    ) -> error::Result<[u8; super::SIGNATURE_LENGTH]> {
+        if partials.is_empty() {
+            return Err(error::invalid_musig());
+        }
+        let session = self.session(aggregate_nonce, msg);
+        let s = partials
+            .iter()
+            .fold(Scalar::zero(), |acc, partial| acc + partial.0);
+        let mut result = [0u8; super::SIGNATURE_LENGTH];
+        result[..32].copy_from_slice(&session.r[1..]);
+        result[32..].copy_from_slice(&s.to_bytes());
+        Ok(result)
+    }
+}
+
+/// The secret nonces of a signer for one signature. They are consumed by
+/// signing and can not be copied, so they are used at most once.
+pub struct SecretNonce {
+    k1: Scalar,
+    k2: Scalar,
+    public_key: [u8; 33],
+}
+
+impl Drop for SecretNonce {
+    fn drop(&mut self) {
+        self.k1.zeroize();
+        self.k2.zeroize();
+    }
+}
+
+/// The public nonces of a signer for one signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicNonce {
+    r1: ProjectivePoint,
+    r2: ProjectivePoint,
+}
+
+impl PublicNonce {
+    /// The two SEC1 compressed nonce points.
+    pub fn to_bytes(&self) -> [u8; NONCE_LENGTH] {
+        let mut result = [0u8; NONCE_LENGTH];
+        result[..33].copy_from_slice(&compressed(&self.r1).expect("non zero nonce"));
+        result[33..].copy_from_slice(&compressed(&self.r2).expect("non zero nonce"));
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for PublicNonce {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != NONCE_LENGTH {
+            return Err(error::invalid_length(NONCE_LENGTH, input.len()));
+        }
+        Ok(PublicNonce {
+            r1: decode_point(&input[..33])?,
+            r2: decode_point(&input[33..])?,
+        })
+    }
+}
+
+/// The sum of the public nonces of all signers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateNonce {
+    r1: ProjectivePoint,
+    r2: ProjectivePoint,
+}
+
+impl AggregateNonce {
+    /// Combine the public nonces of all signers.
+    pub fn new(nonces: &[PublicNonce]) -> error::Result<Self> {
+        if nonces.is_empty() {
+            return Err(error::invalid_musig());
+        }
+        let (r1, r2) = nonces.iter().fold(
+            (ProjectivePoint::identity(), ProjectivePoint::identity()),
+            |(r1, r2), nonce| (r1 + nonce.r1, r2 + nonce.r2),
+        );
+        Ok(AggregateNonce { r1, r2 })
+    }
+
+    /// The two SEC1 compressed nonce points, with the point at infinity
+    /// encoded as 33 zero bytes.
+    pub fn to_bytes(&self) -> [u8; NONCE_LENGTH] {
+        let mut result = [0u8; NONCE_LENGTH];
+        if let Some(r1) = compressed(&self.r1) {
+            result[..33].copy_from_slice(&r1);
+        }
+        if let Some(r2) = compressed(&self.r2) {
+            result[33..].copy_from_slice(&r2);
+        }
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for AggregateNonce {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != NONCE_LENGTH {
+            return Err(error::invalid_length(NONCE_LENGTH, input.len()));
+        }
+        let decode = |input: &[u8]| {
+            if input.iter().all(|byte| *byte == 0) {
+                Ok(ProjectivePoint::identity())
+            } else {
+                decode_point(input)
+            }
+        };
+        Ok(AggregateNonce {
+            r1: decode(&input[..33])?,
+            r2: decode(&input[33..])?,
+        })
+    }
+}
+
+/// The partial signature of one signer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialSignature(Scalar);
+
+impl PartialSignature {
+    pub fn to_bytes(&self) -> [u8; PARTIAL_SIGNATURE_LENGTH] {
+        let mut result = [0u8; PARTIAL_SIGNATURE_LENGTH];
+        result.copy_from_slice(&self.0.to_bytes());
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for PartialSignature {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != PARTIAL_SIGNATURE_LENGTH {
+            return Err(error::invalid_length(PARTIAL_SIGNATURE_LENGTH, input.len()));
+        }
+        if input >= &secp256k1::ORDER[..] {
+            return Err(error::invalid_musig());
+        }
+        Ok(PartialSignature(Scalar::from_bytes_reduced(
+            FieldBytes::from_slice(input),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AggregateNonce, KeyAggContext, PartialSignature, PublicNonce};
+    use crate::{
+        schnorr::{Keypair, PublicKey},
+        Network, Verify,
+    };
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn key_aggregation() {
+        // Keys X1 and X3 from the BIP327 key aggregation vectors
+        let x1 = PublicKey::from_x_only(&hex!(
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9"
+        ))
+        .expect("x1");
+        let x3 = PublicKey::from_x_only(&hex!(
+            "3590a94e768f8e1815c2f24b4d80a8e3149316c3518ce7b7ad338368d038ca66"
+        ))
+        .expect("x3");
+        let context = KeyAggContext::new(&[x1.clone(), x1.clone(), x1.clone()]).expect("context");
+        assert_eq!(
+            hex!("b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935"),
+            context.aggregate_key().to_x_only()
+        );
+        let context = KeyAggContext::new(&[x1, x3]).expect("context");
+        assert_eq!(
+            hex!("85eb6101982e142dba553cae437d08a82880fe9a22889c997f8e415a61b7a2d5"),
+            context.aggregate_key().to_x_only()
+        );
+        assert!(KeyAggContext::new(&[]).is_err());
+    }
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypairs: Vec<Keypair> = (0..3)
+            .map(|_| Keypair::generate(Network::MainNet, &mut OsRng))
+            .collect();
+        let keys: Vec<PublicKey> = keypairs
+            .iter()
+            .map(|keypair| PublicKey::try_from(&keypair.public_key).expect("schnorr key"))
+            .collect();
+        let context = KeyAggContext::new(&keys).expect("context");
+        let msg = b"hello world";
+
+        let (secret_nonces, public_nonces): (Vec<_>, Vec<_>) = keypairs
+            .iter()
+            .map(|keypair| {
+                context
+                    .generate_nonce(keypair, msg, &mut OsRng)
+                    .expect("nonce")
+            })
+            .unzip();
+        let aggregate_nonce = AggregateNonce::new(&public_nonces).expect("aggregate nonce");
+        let partials: Vec<PartialSignature> = keypairs
+            .iter()
+            .zip(secret_nonces)
+            .map(|(keypair, nonce)| {
+                context
+                    .sign(keypair, nonce, &aggregate_nonce, msg)
+                    .expect("partial signature")
+            })
+            .collect();
+        for ((partial, nonce), key) in partials.iter().zip(&public_nonces).zip(&keys) {
+            assert!(context
+                .verify_partial(partial, nonce, key, &aggregate_nonce, msg)
+                .is_ok());
+        }
+        assert!(context
+            .verify_partial(
+                &partials[0],
+                &public_nonces[1],
+                &keys[1],
+                &aggregate_nonce,
+                msg
+            )
+            .is_err());
+
+        let signature = context
+            .aggregate(&aggregate_nonce, msg, &partials)
+            .expect("signature");
+        let public_key = context.public_key(Network::MainNet);
+        assert!(public_key.verify(msg, &signature).is_ok());
+        assert!(public_key.verify(b"hello world!", &signature).is_err());
+        let signature = context
+            .aggregate(&aggregate_nonce, msg, &partials[1..])
+            .expect("signature");
+        assert!(public_key.verify(msg, &signature).is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let key = PublicKey::try_from(&keypair.public_key).expect("schnorr key");
+        let context = KeyAggContext::new(&[key]).expect("context");
+        let (nonce, public_nonce) = context
+            .generate_nonce(&keypair, b"hello world", &mut OsRng)
+            .expect("nonce");
+        assert_eq!(
+            public_nonce,
+            PublicNonce::try_from(&public_nonce.to_bytes()[..]).expect("public nonce")
+        );
+        let aggregate_nonce = AggregateNonce::new(&[public_nonce]).expect("aggregate nonce");
+        assert_eq!(
+            aggregate_nonce,
+            AggregateNonce::try_from(&aggregate_nonce.to_bytes()[..]).expect("aggregate nonce")
+        );
+        assert!(AggregateNonce::try_from(&[0u8; 66][..]).is_ok());
+        assert!(PublicNonce::try_from(&[0u8; 66][..]).is_err());
+        let partial = context
+            .sign(&keypair, nonce, &aggregate_nonce, b"hello world")
+            .expect("partial signature");
+        assert_eq!(
+            partial,
+            PartialSignature::try_from(&partial.to_bytes()[..]).expect("partial signature")
+        );
+        assert!(PartialSignature::try_from(&[0xffu8; 32][..]).is_err());
+    }
+}
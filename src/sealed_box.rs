@@ -0,0 +1,171 @@
+//! Anonymous sealed boxes to ed25519 keys, compatible with libsodium's
+//! `crypto_box_seal`.
+//!
+//! The ed25519 recipient key is converted to its X25519 form and the message
+//! sealed with an ephemeral X25519 keypair. A sealed box is laid out as:
+//!
+//! ```text
+//! ephemeral public key (32) | poly1305 tag (16) | xsalsa20 encrypted message
+//! ```
+//!
+//! The nonce is the 24 byte BLAKE2b hash of the ephemeral and the recipient
+//! X25519 public keys, so boxes produced here can be opened with
+//! `crypto_box_seal_open` using the converted recipient keypair and the other
+//! way around.
+use crate::{ed25519, entropy::EntropySource, error, Keypair, PublicKey};
+use blake2::{
+    digest::{Update, VariableOutput},
+    VarBlake2b,
+};
+use crypto_box::{aead::AeadInPlace, SalsaBox};
+use std::convert::TryFrom;
+use zeroize::Zeroize;
+
+const KEY_LENGTH: usize = 32;
+const TAG_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+/// The number of bytes a sealed box is longer than its message.
+pub const OVERHEAD: usize = KEY_LENGTH + TAG_LENGTH;
+
+impl PublicKey {
+    /// Seal the given message to this ed25519 key. The ephemeral key is drawn
+    /// from the given entropy source.
+    pub fn seal<E>(&self, msg: &[u8], entropy: &mut E) -> error::Result<Vec<u8>>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let recipient =
+            crypto_box::PublicKey::from(ed25519::PublicKey::try_from(self)?.to_x25519());
+        let mut secret = [0u8; KEY_LENGTH];
+        entropy.fill_entropy(&mut secret)?;
+        let ephemeral = crypto_box::SecretKey::from(secret);
+        secret[..].zeroize();
+        let ephemeral_public = ephemeral.public_key();
+
+        let mut buffer = msg.to_vec();
+        let tag = SalsaBox::new(&recipient, &ephemeral)
+            .encrypt_in_place_detached(
+                &nonce(&ephemeral_public, &recipient).into(),
+                b"",
+                &mut buffer,
+            )
+            .map_err(|_| error::invalid_sealed_box())?;
+        let mut result = Vec::with_capacity(OVERHEAD + msg.len());
+        result.extend_from_slice(ephemeral_public.as_bytes());
+        result.extend_from_slice(&tag);
+        result.extend_from_slice(&buffer);
+        Ok(result)
+    }
+}
+
+impl ed25519::Keypair {
+    /// Open a sealed box produced by [`PublicKey::seal`], or by libsodium, for
+    /// this keypair. Fails with `DecodeError::SealedBox` if the box is
+    /// malformed, not sealed to this key or has been tampered with.
+    pub fn open_sealed(&self, sealed: &[u8]) -> error::Result<Vec<u8>> {
+        if sealed.len() < OVERHEAD {
+            return Err(error::invalid_sealed_box());
+        }
+        let (ephemeral, rest) = sealed.split_at(KEY_LENGTH);
+        let (tag, ciphertext) = rest.split_at(TAG_LENGTH);
+        let mut ephemeral_bytes = [0u8; KEY_LENGTH];
+        ephemeral_bytes.copy_from_slice(ephemeral);
+        let ephemeral = crypto_box::PublicKey::from(ephemeral_bytes);
+
+        let mut secret = self.to_x25519_secret();
+        let recipient = crypto_box::SecretKey::from(secret);
+        secret[..].zeroize();
+        let nonce = nonce(&ephemeral, &recipient.public_key());
+
+        let mut buffer = ciphertext.to_vec();
+        SalsaBox::new(&ephemeral, &recipient)
+            .decrypt_in_place_detached(&nonce.into(), b"", &mut buffer, tag.into())
+            .map_err(|_| error::invalid_sealed_box())?;
+        Ok(buffer)
+    }
+}
+
+impl Keypair {
+    /// Open a sealed box for this keypair. Only ed25519 keypairs can receive
+    /// sealed boxes.
+    pub fn open_sealed(&self, sealed: &[u8]) -> error::Result<Vec<u8>> {
+        match self {
+            Self::Ed25519(keypair) => keypair.open_sealed(sealed),
+            _ => Err(error::unsupported_keytype(self.key_tag().key_type)),
+        }
+    }
+}
+
+fn nonce(
+    ephemeral: &crypto_box::PublicKey,
+    recipient: &crypto_box::PublicKey,
+) -> [u8; NONCE_LENGTH] {
+    let mut hasher = VarBlake2b::new(NONCE_LENGTH).expect("blake2b output length");
+    hasher.update(ephemeral.as_bytes());
+    hasher.update(recipient.as_bytes());
+    let mut nonce = [0u8; NONCE_LENGTH];
+    hasher.finalize_variable(|hash| nonce.copy_from_slice(hash));
+    nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OVERHEAD;
+    use crate::{ecc_compact, ed25519, Network};
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    // RFC 8032 test vector 1 as a keypair
+    fn keypair() -> ed25519::Keypair {
+        let mut bytes = vec![0x01];
+        bytes.extend_from_slice(&hex!(
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"
+        ));
+        ed25519::Keypair::try_from(&bytes[..]).expect("keypair")
+    }
+
+    #[test]
+    fn x25519_conversion() {
+        let public_key = ed25519::PublicKey::try_from(&keypair().public_key).expect("ed25519");
+        assert_eq!(
+            hex!("d85e07ec22b0ad881537c2f44d662d1a143cf830c57aca4305d85c7a90f6b62e"),
+            public_key.to_x25519()
+        );
+    }
+
+    #[test]
+    fn open_vector() {
+        // Sealed with libsodium's algorithm using the ephemeral secret 00..1f
+        let sealed = hex!(
+            "8f40c5adb68f25624ae5b214ea767a6ec94d829d3d7b5e1ad1ba6f3e2138285f"
+            "a186e4b06335c4a7a6e929f01a94421a6f8fdd5bd230f6ba226224"
+        );
+        assert_eq!(
+            b"hello world".to_vec(),
+            keypair().open_sealed(&sealed).expect("open")
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let sealed = keypair
+            .public_key
+            .seal(b"hello world", &mut OsRng)
+            .expect("seal");
+        assert_eq!(OVERHEAD + 11, sealed.len());
+        assert_eq!(
+            b"hello world".to_vec(),
+            keypair.open_sealed(&sealed).expect("open")
+        );
+
+        let mut tampered = sealed;
+        tampered[OVERHEAD] ^= 1;
+        assert!(keypair.open_sealed(&tampered).is_err());
+
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        assert!(ecc.public_key.seal(b"hello world", &mut OsRng).is_err());
+    }
+}
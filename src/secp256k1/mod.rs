@@ -0,0 +1,702 @@
+//! secp256k1 keypairs with ECDSA over SHA-256 signatures.
+//!
+//! Public keys are stored as their 33 byte SEC1 compressed point, so keys
+//! from other wallets and bridges are representable whatever the parity of
+//! their y coordinate, and the binary form of a public key is one byte longer
+//! than that of the other key types. Signatures are DER encoded and always
+//! produced with a low s.
+#[cfg(feature = "pkcs8")]
+use crate::pkcs8;
+use crate::{
+    ecdh,
+    entropy::{EntropyRng, EntropySource},
+    error, keypair,
+    keypair::{FixedSha256, Prehash},
+    output_prefix,
+    public_key::{self, VerifyOptions},
+    secret::Secret,
+    IntoBytes, KeyTag, KeyType, Network,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use k256::ecdsa;
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PublicKey(ecdsa::VerifyingKey);
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Signature(ecdsa::Signature);
+
+pub type Keypair = keypair::Keypair<Secret<ecdsa::SigningKey>>;
+
+#[cfg(any(test, feature = "test-utils"))]
+impl keypair::DebugSecret for Keypair {
+    fn secret_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes().to_vec()
+    }
+}
+
+pub const KEYPAIR_LENGTH: usize = 33;
+
+/// Length of the binary form of a public key, the key tag followed by the
+/// SEC1 compressed point.
+pub const PUBLIC_KEY_LENGTH: usize = 34;
+
+/// The maximum length of a DER encoded secp256k1 ECDSA signature.
+pub const MAX_SIGNATURE_LENGTH: usize = 72;
+
+/// Length of a recoverable signature, `r || s` followed by the recovery id.
+pub const RECOVERABLE_SIGNATURE_LENGTH: usize = 65;
+
+/// The order of the secp256k1 curve, big endian.
+pub(crate) const ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+/// Half the order of the secp256k1 curve, the largest allowed low s value.
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// The secp256k1 field modulus, big endian.
+const FIELD_MODULUS: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe, 0xff, 0xff, 0xfc, 0x2f,
+];
+
+/// SEC1 prefix of a compressed point with an even y coordinate.
+const SEC1_EVEN: u8 = 0x02;
+
+/// SEC1 prefix of a compressed point with an odd y coordinate.
+const SEC1_ODD: u8 = 0x03;
+
+impl keypair::Sign for Keypair {
+    #[cfg(feature = "alloc")]
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        use signature::Signer;
+        let signature = self.try_sign(msg)?;
+        Ok(signature.0.to_der().as_bytes().to_vec())
+    }
+
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        use signature::Signer;
+        let signature = self.try_sign(msg)?;
+        keypair::copy_signature(signature.0.to_der().as_bytes(), output)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn sign_digest(&self, digest: Prehash) -> error::Result<Vec<u8>> {
+        use signature::DigestSigner;
+        match digest {
+            Prehash::Sha256(digest) => {
+                let signature: ecdsa::Signature = self.inner.try_sign_digest(digest)?;
+                Ok(normalize_s(signature)?.to_der().as_bytes().to_vec())
+            }
+            Prehash::Sha256Digest(_) | Prehash::Sha512(_) | Prehash::Sha512Digest(_) => {
+                Err(error::unsupported_prehash())
+            }
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != KEYPAIR_LENGTH {
+            return Err(error::invalid_length(KEYPAIR_LENGTH, input.len()));
+        }
+        let network = Network::try_from(input[0])?;
+        Self::from_secret(network, &input[1..])
+    }
+}
+
+impl IntoBytes for Keypair {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let output = output_prefix(output, KEYPAIR_LENGTH)?;
+        output[0] = u8::from(KeyTag {
+            network: self.network,
+            key_type: KeyType::Secp256k1,
+        });
+        let mut secret = self.inner.to_bytes();
+        output[1..].copy_from_slice(&secret);
+        secret[..].zeroize();
+        Ok(KEYPAIR_LENGTH)
+    }
+}
+
+impl Keypair {
+    /// Length of the binary form of keypairs of this type.
+    pub const LEN: usize = KEYPAIR_LENGTH;
+
+    pub fn generate<E>(network: Network, entropy: &mut E) -> Keypair
+    where
+        E: EntropySource + ?Sized,
+    {
+        Self::from_signing_key(network, ecdsa::SigningKey::random(&mut EntropyRng(entropy)))
+    }
+
+    /// Generate a keypair using the operating system random number generator.
+    #[cfg(feature = "std")]
+    pub fn generate_default(network: Network) -> Keypair {
+        Self::generate(network, &mut rand_core::OsRng)
+    }
+
+    /// Replace the secret of this keypair with a freshly generated one for the
+    /// same network, zeroizing the old secret. The returned statement that the
+    /// new key replaces the old one as of `timestamp` is signed by both keys,
+    /// see [`rotation`](crate::rotation).
+    #[cfg(feature = "alloc")]
+    pub fn rekey<E>(
+        &mut self,
+        entropy: &mut E,
+        timestamp: u64,
+    ) -> error::Result<crate::rotation::Rotation>
+    where
+        E: EntropySource + ?Sized,
+    {
+        let new = Self::generate(self.network, entropy);
+        self.rotate(new, timestamp)
+    }
+
+    /// Construct a keypair from a 32 byte secret key.
+    pub(crate) fn from_secret(network: Network, secret: &[u8]) -> error::Result<Keypair> {
+        Ok(Self::from_signing_key(
+            network,
+            ecdsa::SigningKey::from_bytes(secret)?,
+        ))
+    }
+
+    fn from_signing_key(network: Network, inner: ecdsa::SigningKey) -> Keypair {
+        let public_key = PublicKey::from(inner.verify_key());
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            inner: inner.into(),
+        }
+    }
+
+    export_secret! {
+        fn to_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
+            let mut result = [0u8; KEYPAIR_LENGTH];
+            self.try_bytes_into(&mut result).expect("keypair length");
+            result
+        }
+    }
+
+    /// Agree on a shared secret with the given secp256k1 peer key.
+    pub fn ecdh(&self, peer: &public_key::PublicKey) -> error::Result<ecdh::SharedSecret> {
+        let peer = PublicKey::try_from(peer)?;
+        let peer = k256::PublicKey::from_sec1_bytes(&peer.to_sec1_uncompressed())?;
+        let secret = k256::SecretKey::from_bytes(&self.inner.to_bytes())?;
+        let shared = k256::ecdh::diffie_hellman(secret.secret_scalar(), peer.as_affine());
+        Ok(ecdh::SharedSecret::from_slice(shared.as_bytes()))
+    }
+
+    /// Sign the given message, returning the fixed size `r || s` form of the
+    /// signature for protocols that need fixed size signatures.
+    pub fn sign_raw(&self, msg: &[u8]) -> error::Result<[u8; RAW_SIGNATURE_LENGTH]> {
+        use signature::Signer;
+        Ok(self.try_sign(msg)?.to_raw())
+    }
+
+    /// Sign the SHA-256 digest of the given message with a recoverable
+    /// signature, the fixed size `r || s` form followed by a recovery id of 0
+    /// or 1. The public key can be recovered from the signature with
+    /// [`PublicKey::recover`].
+    pub fn sign_recoverable(
+        &self,
+        msg: &[u8],
+    ) -> error::Result<[u8; RECOVERABLE_SIGNATURE_LENGTH]> {
+        use sha2::Digest;
+        use signature::DigestSigner;
+        let signature: ecdsa::recoverable::Signature =
+            self.inner.try_sign_digest(Sha256::new().chain(msg))?;
+        let mut result = [0u8; RECOVERABLE_SIGNATURE_LENGTH];
+        result.copy_from_slice(signature.as_ref());
+        // Negating s negates the R point used for recovery, so the recovery
+        // id flips along with it
+        if result[32..64] > HALF_ORDER[..] {
+            let mut s = [0u8; 32];
+            s.copy_from_slice(&result[32..64]);
+            result[32..64].copy_from_slice(&negate(&s));
+            result[64] ^= 1;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+impl Keypair {
+    export_secret! {
+        /// Encode the keypair as an unencrypted PKCS#8 document in DER form.
+        fn to_pkcs8_der(&self) -> Vec<u8> {
+            pkcs8::encode_ec(
+                KeyType::Secp256k1,
+                &self.inner.to_bytes(),
+                &self.inner.verify_key().to_bytes(),
+            )
+        }
+    }
+
+    /// Decode a DER encoded PKCS#8 secp256k1 key for the given network.
+    pub fn from_pkcs8_der(network: Network, input: &[u8]) -> error::Result<Keypair> {
+        let (key_type, mut secret) = pkcs8::decode(input)?;
+        let result = match key_type {
+            KeyType::Secp256k1 => Self::from_secret(network, &secret),
+            _ => Err(error::invalid_keytype(u8::from(KeyTag {
+                network,
+                key_type,
+            }))),
+        };
+        secret[..].zeroize();
+        result
+    }
+
+    export_secret! {
+        /// Encode the keypair as an unencrypted PKCS#8 PEM block.
+        fn to_pkcs8_pem(&self) -> String {
+            pkcs8::to_pem(&self.to_pkcs8_der())
+        }
+    }
+
+    /// Decode a PKCS#8 PEM block holding a secp256k1 key for the given
+    /// network.
+    pub fn from_pkcs8_pem(network: Network, input: &str) -> error::Result<Keypair> {
+        Self::from_pkcs8_der(network, &pkcs8::from_pem(input)?)
+    }
+}
+
+/// Computes n - v for a big endian value v in (0, n).
+fn negate(v: &[u8; 32]) -> [u8; 32] {
+    let mut result = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut diff = ORDER[i] as i16 - v[i] as i16 - borrow;
+        borrow = if diff < 0 {
+            diff += 256;
+            1
+        } else {
+            0
+        };
+        result[i] = diff as u8;
+    }
+    result
+}
+
+impl signature::Signature for Signature {
+    fn from_bytes(input: &[u8]) -> core::result::Result<Self, signature::Error> {
+        Ok(Signature(signature::Signature::from_bytes(input)?))
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl Signature {
+    /// Parse a DER encoded signature.
+    pub fn from_der(input: &[u8]) -> error::Result<Self> {
+        Ok(Signature(ecdsa::Signature::from_der(input)?))
+    }
+
+    /// The DER encoding of the signature, as produced by `Sign::sign`.
+    #[cfg(feature = "alloc")]
+    pub fn to_der(&self) -> Vec<u8> {
+        self.0.to_der().as_bytes().to_vec()
+    }
+
+    /// Write the DER encoding of the signature into the start of the given
+    /// output slice, returning its length.
+    pub(crate) fn der_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        keypair::copy_signature(self.0.to_der().as_bytes(), output)
+    }
+
+    /// Parse a fixed size `r || s` signature with both values big endian.
+    pub fn from_raw(input: &[u8]) -> error::Result<Self> {
+        Ok(Signature(signature::Signature::from_bytes(input)?))
+    }
+
+    /// The fixed size `r || s` form of the signature.
+    pub fn to_raw(&self) -> [u8; RAW_SIGNATURE_LENGTH] {
+        let mut result = [0u8; RAW_SIGNATURE_LENGTH];
+        result.copy_from_slice(self.0.as_ref());
+        result
+    }
+}
+
+impl signature::Signer<Signature> for Keypair {
+    fn try_sign(&self, msg: &[u8]) -> core::result::Result<Signature, signature::Error> {
+        let signature: ecdsa::Signature = signature::Signer::try_sign(&*self.inner, msg)?;
+        Ok(Signature(normalize_s(signature)?))
+    }
+}
+
+/// Always hand out the low s form of a signature.
+fn normalize_s(
+    signature: ecdsa::Signature,
+) -> core::result::Result<ecdsa::Signature, signature::Error> {
+    let mut raw = [0u8; 64];
+    raw.copy_from_slice(signature.as_ref());
+    if raw[32..] > HALF_ORDER[..] {
+        let mut s = [0u8; 32];
+        s.copy_from_slice(&raw[32..]);
+        raw[32..].copy_from_slice(&negate(&s));
+    }
+    signature::Signature::from_bytes(&raw)
+}
+
+impl public_key::Verify for PublicKey {
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        use signature::Verifier;
+        let signature = parse_signature(signature, options)?;
+        Ok(self.0.verify(msg, &signature)?)
+    }
+
+    fn verify_digest_with_options(
+        &self,
+        digest: Prehash,
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        match digest {
+            Prehash::Sha256(digest) => self.verify_sha256(digest, signature, options),
+            Prehash::Sha256Digest(digest) => {
+                use signature::DigestVerifier;
+                let signature = parse_signature(signature, options)?;
+                Ok(self
+                    .0
+                    .verify_digest(FixedSha256::new(&digest), &signature)?)
+            }
+            Prehash::Sha512(_) | Prehash::Sha512Digest(_) => Err(error::unsupported_prehash()),
+        }
+    }
+}
+
+/// Length of a fixed size `r || s` signature.
+pub const RAW_SIGNATURE_LENGTH: usize = 64;
+
+/// Parse a DER signature, or a raw one if allowed, applying the encoding rules
+/// in the given options. A high s is rejected if a low s is required, and
+/// normalized otherwise.
+pub(crate) fn parse_signature(
+    input: &[u8],
+    options: &VerifyOptions,
+) -> error::Result<ecdsa::Signature> {
+    let signature = match ecdsa::Signature::from_der(input) {
+        Ok(signature) => {
+            if options.strict_der && signature.to_der().as_bytes() != input {
+                return Err(signature::Error::new().into());
+            }
+            signature
+        }
+        Err(_) if options.allow_raw_ecdsa && input.len() == RAW_SIGNATURE_LENGTH => {
+            <ecdsa::Signature as signature::Signature>::from_bytes(input)?
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if signature.as_ref()[32..] <= HALF_ORDER[..] {
+        return Ok(signature);
+    }
+    if options.require_low_s {
+        return Err(signature::Error::new().into());
+    }
+    let mut raw = [0u8; RAW_SIGNATURE_LENGTH];
+    raw.copy_from_slice(signature.as_ref());
+    let mut s = [0u8; 32];
+    s.copy_from_slice(&raw[32..]);
+    raw[32..].copy_from_slice(&negate(&s));
+    Ok(<ecdsa::Signature as signature::Signature>::from_bytes(
+        &raw,
+    )?)
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = error::Error;
+
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != PUBLIC_KEY_LENGTH {
+            return Err(error::invalid_length(PUBLIC_KEY_LENGTH, input.len()));
+        }
+        // Only the compressed form is a valid binary form
+        if !Self::quick_check(&input[1..]) {
+            return Err(error::invalid_point(error::PointError::NotOnCurve));
+        }
+        Self::from_sec1_point(&input[1..])
+    }
+}
+
+impl From<ecdsa::VerifyingKey> for PublicKey {
+    fn from(v: ecdsa::VerifyingKey) -> Self {
+        PublicKey(v)
+    }
+}
+
+impl TryFrom<&public_key::PublicKey> for PublicKey {
+    type Error = error::Error;
+
+    /// Fails for keys of any other key type.
+    fn try_from(v: &public_key::PublicKey) -> error::Result<Self> {
+        match &v.inner {
+            public_key::PublicKeyRepr::Secp256k1(key) => Ok(key.clone()),
+            _ => Err(error::unsupported_keytype(v.tag().key_type)),
+        }
+    }
+}
+
+impl From<&PublicKey> for ecdsa::VerifyingKey {
+    fn from(v: &PublicKey) -> Self {
+        v.0
+    }
+}
+
+/// Checks that the given bytes are a big endian field element.
+pub(crate) fn is_field_element(input: &[u8]) -> bool {
+    input.len() == FIELD_MODULUS.len() && input < &FIELD_MODULUS[..]
+}
+
+impl PublicKey {
+    /// Checks that the given untagged key bytes are a SEC1 compressed point
+    /// prefix followed by a field element, without decompressing the point.
+    pub(crate) fn quick_check(input: &[u8]) -> bool {
+        match input.split_first() {
+            Some((&prefix, x)) => {
+                (prefix == SEC1_EVEN || prefix == SEC1_ODD) && is_field_element(x)
+            }
+            None => false,
+        }
+    }
+
+    /// Recover the public key from a recoverable signature over the given
+    /// message, as made by [`Keypair::sign_recoverable`].
+    pub fn recover(msg: &[u8], signature: &[u8]) -> error::Result<Self> {
+        use sha2::Digest;
+        let signature = ecdsa::recoverable::Signature::try_from(signature)?;
+        let key = signature.recover_verify_key_from_digest(Sha256::new().chain(msg))?;
+        Ok(Self::from(key))
+    }
+
+    /// Verify a fixed size `r || s` signature for the given message. A high s
+    /// is normalized, as for DER signatures.
+    pub fn verify_raw(&self, msg: &[u8], signature: &[u8]) -> error::Result {
+        use signature::Verifier;
+        let signature = normalize_s(Signature::from_raw(signature)?.0)?;
+        Ok(self.0.verify(msg, &signature)?)
+    }
+
+    /// secp256k1 has a cofactor of one and decompression only produces points
+    /// on the curve, so every constructed key is valid. The checks are
+    /// repeated here to not depend on that.
+    pub(crate) fn check_point(&self) -> error::Result {
+        let decoded = ecdsa::VerifyingKey::from_sec1_bytes(&self.0.to_bytes())
+            .map_err(|_| error::invalid_point(error::PointError::NotOnCurve))?;
+        if decoded != self.0 {
+            return Err(error::invalid_point(error::PointError::NonCanonical));
+        }
+        Ok(())
+    }
+
+    /// Verify an already parsed signature for the given message.
+    pub(crate) fn verify_parsed(&self, msg: &[u8], signature: &ecdsa::Signature) -> error::Result {
+        use signature::Verifier;
+        Ok(self.0.verify(msg, signature)?)
+    }
+
+    /// Verify the given DER signature against a SHA-256 digest of the signed
+    /// message that has been fed in incrementally.
+    pub(crate) fn verify_sha256(
+        &self,
+        digest: Sha256,
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        use signature::DigestVerifier;
+        let signature = parse_signature(signature, options)?;
+        Ok(self.0.verify_digest(digest, &signature)?)
+    }
+
+    /// The SEC1 compressed encoding of this key.
+    pub fn to_sec1_compressed(&self) -> [u8; 33] {
+        let mut result = [0u8; 33];
+        result.copy_from_slice(&self.0.to_bytes());
+        result
+    }
+
+    /// The SEC1 uncompressed encoding of this key, a `0x04` prefix followed by
+    /// the x and y coordinates.
+    pub fn to_sec1_uncompressed(&self) -> [u8; 65] {
+        use k256::elliptic_curve::sec1::ToEncodedPoint;
+        let point = k256::PublicKey::from_sec1_bytes(&self.0.to_bytes()).expect("valid point");
+        let mut result = [0u8; 65];
+        result.copy_from_slice(point.to_encoded_point(false).as_bytes());
+        result
+    }
+
+    /// Construct a key from a SEC1 compressed or uncompressed point.
+    pub fn from_sec1_point(input: &[u8]) -> error::Result<Self> {
+        let key = ecdsa::VerifyingKey::from_sec1_bytes(input)
+            .map_err(|_| error::invalid_point(error::PointError::NotOnCurve))?;
+        Ok(Self::from(key))
+    }
+}
+
+impl IntoBytes for PublicKey {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let bytes = self.0.to_bytes();
+        output_prefix(output, bytes.len())?.copy_from_slice(&bytes);
+        Ok(bytes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{negate, Keypair, PublicKey, Signature, HALF_ORDER, ORDER};
+    use crate::{Network, Sign, Verify};
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        let signature = k256::ecdsa::Signature::from_der(&signature).expect("der");
+        assert!(signature.as_ref()[32..] <= HALF_ORDER[..]);
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        for _ in 0..16 {
+            let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+            assert_eq!(
+                keypair,
+                Keypair::try_from(&keypair.to_bytes()[..]).expect("keypair")
+            );
+            assert_eq!(
+                keypair.public_key,
+                crate::PublicKey::from_bytes(keypair.public_key.to_bytes()).expect("public key")
+            );
+        }
+    }
+
+    #[test]
+    fn odd_y() {
+        // The secret n - 1 belongs to -G, which has an odd y coordinate
+        let mut bytes = [0x04u8; 33];
+        bytes[1..].copy_from_slice(&ORDER);
+        bytes[32] -= 1;
+        let keypair = Keypair::try_from(&bytes[..]).expect("keypair");
+        assert_eq!(
+            hex!("04" "03" "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+            keypair.public_key.to_bytes()[..]
+        );
+        let signature = keypair.sign(b"hello world").expect("signature");
+        let public_key: crate::PublicKey = keypair
+            .public_key
+            .to_string()
+            .parse()
+            .expect("b58 public key");
+        assert!(public_key.verify(b"hello world", &signature).is_ok());
+        assert_eq!(
+            keypair,
+            Keypair::try_from(&keypair.to_bytes()[..]).expect("keypair")
+        );
+        let signature = keypair.sign_recoverable(b"hello world").expect("signature");
+        assert_eq!(
+            keypair.public_key,
+            crate::PublicKey::recover(Network::MainNet, b"hello world", &signature)
+                .expect("recovered")
+        );
+    }
+
+    #[test]
+    fn b58_roundtrip() {
+        const B58: &str = "1bQdVnGyTnZAMSL9mFvjpGeM6kX3wpLXjSvWUNE2BfK1S2BkGFoU";
+        let decoded: crate::PublicKey = B58.parse().expect("b58 public key");
+        assert_eq!(crate::KeyType::Secp256k1, decoded.key_type());
+        assert_eq!(B58, decoded.to_string());
+    }
+
+    #[test]
+    fn verify() {
+        // Signed with an independent implementation
+        const MSG: &[u8] = b"hello world";
+        const SECRET: [u8; 32] =
+            hex!("f6553b317a7a3da1b23f6d608dca42cde4dd42a20c89196347579687e3a35b94");
+        const PUBKEY: &str = "1bQdVnGyTnZAMSL9mFvjpGeM6kX3wpLXjSvWUNE2BfK1S2BkGFoU";
+        const SIG: &[u8] =
+            &hex!("30440220206af21f2c817e9842992da764cac3c9aa7cacf730c798dc581cf6969afcce95022069507dd499beea82a9a1efcef4530806a2302d40cb526fd7212a305c524e5f92");
+
+        let public_key: crate::PublicKey = PUBKEY.parse().expect("b58 public key");
+        assert!(public_key.verify(MSG, SIG).is_ok());
+        let mut bytes = [0x04u8; 33];
+        bytes[1..].copy_from_slice(&SECRET);
+        let keypair = Keypair::try_from(&bytes[..]).expect("keypair");
+        assert_eq!(public_key, keypair.public_key);
+    }
+
+    #[test]
+    fn raw_signature_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let raw = keypair.sign_raw(b"hello world").expect("signature");
+        assert!(raw[32..] <= HALF_ORDER[..]);
+        let public_key = PublicKey::try_from(&keypair.public_key).expect("secp256k1 key");
+        assert!(public_key.verify_raw(b"hello world", &raw).is_ok());
+        assert!(public_key.verify_raw(b"hello world!", &raw).is_err());
+
+        let der = Signature::from_raw(&raw).expect("raw").to_der();
+        assert!(keypair.public_key.verify(b"hello world", &der).is_ok());
+        assert_eq!(raw, Signature::from_der(&der).expect("der").to_raw());
+    }
+
+    #[test]
+    fn recover() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key = PublicKey::try_from(&keypair.public_key).expect("secp256k1 key");
+        let signature = keypair.sign_recoverable(b"hello world").expect("signature");
+        assert!(signature[32..64] <= HALF_ORDER[..]);
+        assert_eq!(
+            keypair.public_key,
+            crate::PublicKey::recover(Network::MainNet, b"hello world", &signature)
+                .expect("recovered")
+        );
+        // A different message recovers a different key, or none at all
+        assert_ne!(
+            Some(public_key.clone()),
+            PublicKey::recover(b"hello world!", &signature).ok()
+        );
+        // Without the recovery id it is a regular signature
+        assert!(public_key
+            .verify_raw(b"hello world", &signature[..64])
+            .is_ok());
+    }
+
+    #[test]
+    fn negation() {
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        let mut expected = ORDER;
+        expected[31] -= 1;
+        assert_eq!(expected, negate(&one));
+        assert_eq!(one, negate(&expected));
+    }
+}
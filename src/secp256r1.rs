@@ -0,0 +1,368 @@
+//! NIST P-256 keypairs without the compactability requirement of
+//! [`crate::ecc_compact`], for keys generated by hardware like TPMs and
+//! smartcards that can not be made to produce compactable keys.
+//!
+//! Signatures are the same DER encoded ECDSA over SHA-256 signatures as
+//! ecc_compact signatures. The binary form of a public key is the key tag
+//! followed by the 33 byte SEC1 compressed point, and its address is the
+//! b58check encoding of that form, like secp256k1 keys. [`PublicKey`] converts
+//! into a [`crate::PublicKey`], which parses these keys as well. Keypairs are
+//! the key tag followed by the 32 byte secret scalar.
+use crate::{
+    ecc_compact::{self, normalize_s, parse_signature},
+    entropy::{EntropyRng, EntropySource},
+    error, keypair,
+    keypair::{FixedSha256, Prehash},
+    output_prefix,
+    public_key::VerifyOptions,
+    IntoBytes, KeyTag, KeyType, Network, Sign, Verify,
+};
+use p256::{ecdsa, elliptic_curve::sec1::ToEncodedPoint};
+use std::convert::TryFrom;
+use zeroize::Zeroize;
+
+/// Length of the binary form of a keypair.
+pub const KEYPAIR_LENGTH: usize = 33;
+
+/// Length of the binary form of a public key.
+pub const PUBLIC_KEY_LENGTH: usize = 34;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PublicKey {
+    /// The network this key is valid for
+    pub network: Network,
+    inner: p256::PublicKey,
+}
+
+pub type Keypair = keypair::Keypair<ecdsa::SigningKey, PublicKey>;
+
+impl Keypair {
+    pub fn generate<E>(network: Network, entropy: &mut E) -> Keypair
+    where
+        E: EntropySource + ?Sized,
+    {
+        Self::from_secret_key(network, p256::SecretKey::random(&mut EntropyRng(entropy)))
+    }
+
+    /// Generate a keypair using the operating system random number generator.
+    pub fn generate_default(network: Network) -> Keypair {
+        Self::generate(network, &mut rand_core::OsRng)
+    }
+
+    /// Construct a keypair from a 32 byte big endian secret scalar.
+    pub fn from_secret(network: Network, secret: &[u8]) -> error::Result<Keypair> {
+        if secret.len() != KEYPAIR_LENGTH - 1 {
+            return Err(error::invalid_length(KEYPAIR_LENGTH - 1, secret.len()));
+        }
+        Ok(Self::from_secret_key(
+            network,
+            p256::SecretKey::from_bytes(secret)?,
+        ))
+    }
+
+    /// Construct a keypair for the given network from any p256 secret key.
+    pub fn from_secret_key(network: Network, secret: p256::SecretKey) -> Keypair {
+        Keypair {
+            network,
+            public_key: PublicKey {
+                network,
+                inner: secret.public_key(),
+            },
+            inner: ecdsa::SigningKey::from(secret),
+        }
+    }
+
+    /// The same keypair tagged for the given network.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self.public_key.network = network;
+        self
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        self.public_key.tag()
+    }
+
+    export_secret! {
+        fn to_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
+            let mut result = [0u8; KEYPAIR_LENGTH];
+            self.try_bytes_into(&mut result)
+                .expect("keypair length");
+            result
+        }
+    }
+
+    fn try_sign_low_s(&self, msg: &[u8]) -> error::Result<ecdsa::Signature> {
+        use signature::Signer;
+        let signature: ecdsa::Signature = self.inner.try_sign(msg)?;
+        Ok(normalize_s(signature)?)
+    }
+}
+
+impl Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        Ok(self.try_sign_low_s(msg)?.to_der().as_bytes().to_vec())
+    }
+
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        let signature = self.try_sign_low_s(msg)?;
+        keypair::copy_signature(signature.to_der().as_bytes(), output)
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != KEYPAIR_LENGTH {
+            return Err(error::invalid_length(KEYPAIR_LENGTH, input.len()));
+        }
+        if KeyType::try_from(input[0])? != KeyType::Secp256r1 {
+            return Err(error::invalid_keytype(input[0]));
+        }
+        Self::from_secret(Network::try_from(input[0])?, &input[1..])
+    }
+}
+
+impl IntoBytes for Keypair {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let output = output_prefix(output, KEYPAIR_LENGTH)?;
+        output[0] = u8::from(self.key_tag());
+        let mut secret = self.inner.to_bytes();
+        output[1..].copy_from_slice(&secret);
+        secret[..].zeroize();
+        Ok(KEYPAIR_LENGTH)
+    }
+}
+
+/// Keypairs never include their secret in `Debug` output.
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .field("secret", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl PublicKey {
+    pub fn tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::Secp256r1,
+        }
+    }
+
+    /// Construct a public key for the given network from a SEC1 compressed
+    /// or uncompressed point. Unlike ecc_compact keys any point on the curve
+    /// is accepted.
+    pub fn from_sec1_point(network: Network, input: &[u8]) -> error::Result<Self> {
+        Ok(Self {
+            network,
+            inner: p256::PublicKey::from_sec1_bytes(input)
+                .map_err(|_| error::invalid_point(error::PointError::NotOnCurve))?,
+        })
+    }
+
+    /// Whether the given SEC1 compressed point has a valid prefix and
+    /// coordinate, see [`crate::PublicKey::quick_check`].
+    pub(crate) fn quick_check(input: &[u8]) -> bool {
+        match input.split_first() {
+            Some((&prefix, x)) => {
+                (prefix == 0x02 || prefix == 0x03) && ecc_compact::PublicKey::quick_check(x)
+            }
+            None => false,
+        }
+    }
+
+    /// Convert the key to its binary form.
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        let mut result = [0u8; PUBLIC_KEY_LENGTH];
+        result[0] = u8::from(self.tag());
+        result[1..].copy_from_slice(self.inner.to_encoded_point(true).as_bytes());
+        result
+    }
+
+    /// The SEC1 encoding of the public key, compressed or not.
+    pub fn to_sec1_bytes(&self, compress: bool) -> Vec<u8> {
+        self.inner.to_encoded_point(compress).as_bytes().to_vec()
+    }
+
+    /// The ecc_compact form of this key, which only exists for compactable
+    /// keys. Fails with `DecodeError::NotCompact` otherwise.
+    pub fn to_ecc_compact(&self) -> error::Result<crate::PublicKey> {
+        let key = ecc_compact::PublicKey::try_from(self.inner)?;
+        Ok(crate::PublicKey::for_network(self.network, key))
+    }
+}
+
+impl From<PublicKey> for crate::PublicKey {
+    fn from(v: PublicKey) -> Self {
+        Self::for_network(v.network, v)
+    }
+}
+
+impl From<&ecc_compact::Keypair> for Keypair {
+    /// Every ecc_compact keypair is also a valid keypair of this type, with
+    /// the same secret.
+    fn from(v: &ecc_compact::Keypair) -> Self {
+        Self::from_secret_key(v.network, v.secret_key())
+    }
+}
+
+impl Verify for PublicKey {
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        use signature::Verifier;
+        let signature = parse_signature(signature, options)?;
+        Ok(ecdsa::VerifyingKey::from(self.inner).verify(msg, &signature)?)
+    }
+
+    fn verify_digest_with_options(
+        &self,
+        digest: Prehash,
+        signature: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        use signature::DigestVerifier;
+        let key = ecdsa::VerifyingKey::from(self.inner);
+        match digest {
+            Prehash::Sha256(digest) => {
+                Ok(key.verify_digest(digest, &parse_signature(signature, options)?)?)
+            }
+            Prehash::Sha256Digest(digest) => Ok(key.verify_digest(
+                FixedSha256::new(&digest),
+                &parse_signature(signature, options)?,
+            )?),
+            Prehash::Sha512(_) | Prehash::Sha512Digest(_) => Err(error::unsupported_prehash()),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != PUBLIC_KEY_LENGTH {
+            return Err(error::invalid_length(PUBLIC_KEY_LENGTH, input.len()));
+        }
+        if KeyType::try_from(input[0])? != KeyType::Secp256r1 {
+            return Err(error::invalid_keytype(input[0]));
+        }
+        Self::from_sec1_point(Network::try_from(input[0])?, &input[1..])
+    }
+}
+
+impl std::str::FromStr for PublicKey {
+    type Err = error::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let data = bs58::decode(s).with_check(Some(0)).into_vec()?;
+        Self::try_from(&data[1..])
+    }
+}
+
+impl std::fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let mut data = [0u8; PUBLIC_KEY_LENGTH + 1];
+        data[1..].copy_from_slice(&self.to_bytes());
+        f.write_str(&bs58::encode(data.as_ref()).with_check().into_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keypair, PublicKey, KEYPAIR_LENGTH, PUBLIC_KEY_LENGTH};
+    use crate::{ecc_compact, KeyType, Network, Sign, Verify};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify_strict(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair.public_key.verify(b"hello", &signature).is_err());
+        let other = Keypair::generate(Network::MainNet, &mut OsRng);
+        assert!(other.public_key.verify(b"hello world", &signature).is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let bytes = keypair.to_bytes();
+        assert_eq!(KEYPAIR_LENGTH, bytes.len());
+        assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+        assert_eq!(KeyType::Secp256r1, keypair.key_tag().key_type);
+
+        let public_key = keypair.public_key.to_bytes();
+        assert_eq!(PUBLIC_KEY_LENGTH, public_key.len());
+        assert_eq!(
+            keypair.public_key,
+            PublicKey::try_from(&public_key[..]).expect("public key")
+        );
+        let address = keypair.public_key.to_string();
+        assert_eq!(keypair.public_key, address.parse().expect("address"));
+        assert!(PublicKey::try_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn ecc_compact_keys() {
+        // Both points for a given x are accepted, while only one of them is
+        // compactable
+        let compact = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let keypair = Keypair::from(&compact);
+        let signature = compact.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert_eq!(
+            compact.public_key,
+            keypair.public_key.to_ecc_compact().expect("compact key")
+        );
+
+        let mut sec1 = keypair.public_key.to_sec1_bytes(true);
+        sec1[0] ^= 0x01;
+        let negated = PublicKey::from_sec1_point(Network::MainNet, &sec1).expect("negated key");
+        assert!(negated.to_ecc_compact().is_err());
+    }
+
+    #[test]
+    fn crate_public_key() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        let public_key = crate::PublicKey::from(keypair.public_key.clone());
+        assert_eq!(KeyType::Secp256r1, public_key.tag().key_type);
+        assert_eq!(Network::TestNet, public_key.network);
+        assert_eq!(
+            &keypair.public_key.to_bytes()[..],
+            &public_key.to_bytes()[..]
+        );
+        assert_eq!(keypair.public_key.to_string(), public_key.to_string());
+        assert!(public_key.verify(b"hello world", &signature).is_ok());
+        assert!(crate::PublicKey::quick_check(&public_key.to_bytes()));
+        assert!(crate::PublicKey::validate_str(&public_key.to_string()).is_ok());
+        assert_eq!(
+            public_key,
+            crate::PublicKey::try_from(&public_key.to_bytes()[..]).expect("public key")
+        );
+
+        // P-256 points become secp256r1 keys only when they are not compactable
+        let sec1 = keypair.public_key.to_sec1_bytes(false);
+        let parsed =
+            crate::PublicKey::from_p256_sec1_bytes(Network::TestNet, &sec1).expect("p256 key");
+        let mut negated = keypair.public_key.to_sec1_bytes(true);
+        negated[0] ^= 0x01;
+        let negated =
+            crate::PublicKey::from_p256_sec1_bytes(Network::TestNet, &negated).expect("p256 key");
+        let key_types = [parsed.tag().key_type, negated.tag().key_type];
+        assert!(key_types.contains(&KeyType::EccCompact));
+        assert!(key_types.contains(&KeyType::Secp256r1));
+    }
+}
@@ -0,0 +1,165 @@
+//! Storage for the secret keys of software keypairs.
+//!
+//! A secret normally lives wherever its keypair does. With the `secure-mem`
+//! feature each secret gets pages of its own instead, locked with `mlock` so
+//! they are never written to swap and, on Linux, left out of core dumps with
+//! `madvise(MADV_DONTDUMP)`. The pages are zeroed, unlocked and unmapped when
+//! the keypair is dropped.
+//!
+//! Locked memory is limited by `RLIMIT_MEMLOCK`, and every secret takes at
+//! least a page of it. Creating a keypair panics when its pages can't be
+//! locked, rather than quietly keeping the secret in swappable memory.
+use core::ops::Deref;
+
+#[cfg(all(feature = "secure-mem", not(unix)))]
+compile_error!("the secure-mem feature is only supported on unix platforms");
+
+/// The secret held by a keypair.
+pub struct Secret<T>(Storage<T>);
+
+#[cfg(not(feature = "secure-mem"))]
+type Storage<T> = T;
+#[cfg(feature = "secure-mem")]
+type Storage<T> = locked::Locked<T>;
+
+impl<T> Secret<T> {
+    #[cfg(not(feature = "secure-mem"))]
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    #[cfg(feature = "secure-mem")]
+    pub fn new(value: T) -> Self {
+        Self(locked::Locked::new(value))
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(v: T) -> Self {
+        Self::new(v)
+    }
+}
+
+impl<T> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[cfg(feature = "secure-mem")]
+mod locked {
+    use core::{marker::PhantomData, mem, ops::Deref, ptr, ptr::NonNull, slice};
+    use zeroize::Zeroize;
+
+    /// A value alone on locked pages of its own.
+    pub struct Locked<T> {
+        ptr: NonNull<T>,
+        len: usize,
+        _value: PhantomData<T>,
+    }
+
+    // The value is owned like a Box owns its value
+    unsafe impl<T: Send> Send for Locked<T> {}
+    unsafe impl<T: Sync> Sync for Locked<T> {}
+
+    impl<T> Locked<T> {
+        pub fn new(value: T) -> Self {
+            let page = page_size();
+            assert!(mem::align_of::<T>() <= page, "secret alignment");
+            let len = (mem::size_of::<T>().max(1) + page - 1) / page * page;
+            // SAFETY: a new anonymous mapping, nothing else refers to it
+            let addr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANON,
+                    -1,
+                    0,
+                )
+            };
+            if addr == libc::MAP_FAILED {
+                panic!("mapping secret memory failed");
+            }
+            // SAFETY: addr and len are the mapping created above
+            if unsafe { lock(addr, len) }.is_err() {
+                // SAFETY: the mapping is not used after this
+                unsafe { libc::munmap(addr, len) };
+                panic!("locking secret memory failed, check RLIMIT_MEMLOCK");
+            }
+            let ptr = addr as *mut T;
+            // SAFETY: the mapping is page aligned, writable and at least as
+            // large as the value
+            unsafe { ptr.write(value) };
+            Self {
+                ptr: NonNull::new(ptr).expect("mapped secret"),
+                len,
+                _value: PhantomData,
+            }
+        }
+    }
+
+    impl<T> Deref for Locked<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            // SAFETY: the value was written in new and is only dropped in drop
+            unsafe { self.ptr.as_ref() }
+        }
+    }
+
+    impl<T> Drop for Locked<T> {
+        fn drop(&mut self) {
+            let addr = self.ptr.as_ptr() as *mut libc::c_void;
+            // SAFETY: the value is dropped exactly once, after which its
+            // mapping is only zeroed and released
+            unsafe {
+                ptr::drop_in_place(self.ptr.as_ptr());
+                slice::from_raw_parts_mut(addr as *mut u8, self.len).zeroize();
+                libc::munlock(addr, self.len);
+                libc::munmap(addr, self.len);
+            }
+        }
+    }
+
+    /// Lock the given pages and keep them out of core dumps where the
+    /// platform allows it.
+    unsafe fn lock(addr: *mut libc::c_void, len: usize) -> Result<(), ()> {
+        if libc::mlock(addr, len) != 0 {
+            return Err(());
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if libc::madvise(addr, len, libc::MADV_DONTDUMP) != 0 {
+            libc::munlock(addr, len);
+            return Err(());
+        }
+        Ok(())
+    }
+
+    fn page_size() -> usize {
+        // SAFETY: sysconf has no preconditions
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Secret;
+    use crate::{ecc_compact, Network, Sign, Verify};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn keypair() {
+        let secret = Secret::new([7u8; 32]);
+        assert_eq!([7u8; 32], *secret);
+
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+    }
+}
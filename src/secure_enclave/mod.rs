@@ -0,0 +1,102 @@
+//! Secure Enclave backed keypairs on macOS and iOS. The secret key is a P-256
+//! key generated inside the Secure Enclave and stored in the data protection
+//! keychain under a label. It can not be exported and all signing happens in
+//! the enclave.
+//!
+//! Since the public key needs to be representable as an ecc_compact public
+//! key, key generation is retried until the enclave produces a compactable
+//! key. Keys that are not compactable are removed from the keychain again.
+use crate::{ecc_compact, error, keypair, public_key, Network};
+use security_framework::{
+    item::{ItemClass, ItemSearchOptions, Location, Reference, SearchResult},
+    key::{Algorithm, GenerateKeyOptions, KeyType, SecKey, Token},
+};
+use sha2::{Digest, Sha256};
+
+pub type Keypair = keypair::Keypair<SecKey>;
+
+/// Number of key generation attempts before giving up on finding a
+/// compactable key.
+const MAX_GENERATE_ATTEMPTS: usize = 64;
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        // The enclave signs the digest and returns a DER encoded signature,
+        // which may have a high s
+        let digest = Sha256::digest(msg);
+        let signature = self
+            .inner
+            .create_signature(Algorithm::ECDSASignatureDigestX962SHA256, &digest)
+            .map_err(|err| error::secure_enclave(err.description().to_string()))?;
+        let signature = p256::ecdsa::Signature::from_der(&signature)?;
+        let signature = ecc_compact::normalize_s(signature)?;
+        Ok(signature.to_der().as_bytes().to_vec())
+    }
+}
+
+impl Keypair {
+    /// Open the Secure Enclave key stored in the keychain with the given
+    /// label.
+    pub fn open(network: Network, label: &str) -> error::Result<Self> {
+        let results = ItemSearchOptions::new()
+            .class(ItemClass::key())
+            .label(label)
+            .load_refs(true)
+            .search()
+            .map_err(|_| error::secure_enclave(format!("key {} not found", label)))?;
+        let key = results
+            .into_iter()
+            .find_map(|result| match result {
+                SearchResult::Ref(Reference::Key(key)) => Some(key),
+                _ => None,
+            })
+            .ok_or_else(|| error::secure_enclave(format!("key {} not found", label)))?;
+        let public_key = read_public_key(&key)?;
+        Ok(Self::from_parts(network, public_key, key))
+    }
+
+    /// Generate a new key in the Secure Enclave and store it in the keychain
+    /// with the given label.
+    pub fn generate(network: Network, label: &str) -> error::Result<Self> {
+        let mut options = GenerateKeyOptions::default();
+        options
+            .set_key_type(KeyType::ec())
+            .set_size_in_bits(256)
+            .set_token(Token::SecureEnclave)
+            .set_location(Location::DataProtectionKeychain)
+            .set_label(label);
+        for _ in 0..MAX_GENERATE_ATTEMPTS {
+            let key = SecKey::new(&options)
+                .map_err(|err| error::secure_enclave(err.description().to_string()))?;
+            match read_public_key(&key) {
+                Ok(public_key) => return Ok(Self::from_parts(network, public_key, key)),
+                Err(err) => {
+                    key.delete()
+                        .map_err(|_| error::secure_enclave("key delete failed".to_string()))?;
+                    if !matches!(err, error::Error::Decode(error::DecodeError::NotCompact)) {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        Err(error::not_compact())
+    }
+
+    fn from_parts(network: Network, public_key: ecc_compact::PublicKey, key: SecKey) -> Self {
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            inner: key,
+        }
+    }
+}
+
+/// The external representation of an EC public key is the uncompressed SEC1
+/// point.
+fn read_public_key(key: &SecKey) -> error::Result<ecc_compact::PublicKey> {
+    let point = key
+        .public_key()
+        .and_then(|public_key| public_key.external_representation())
+        .ok_or_else(|| error::secure_enclave("public key unavailable".to_string()))?;
+    ecc_compact::PublicKey::from_sec1_point(&point)
+}
@@ -0,0 +1,211 @@
+//! [serde](https://serde.rs) support for public keys, key tags and signatures.
+//!
+//! Human readable formats, like JSON, get the string forms: public keys and
+//! key tags as produced by their `Display` implementations and signatures in
+//! base64. Binary formats get the binary forms instead, the 33 byte public key,
+//! the key tag byte and the raw signature bytes.
+//!
+//! Keypairs are only serialized encrypted, as a
+//! [`keyfile::EncryptedKeypair`](crate::keyfile::EncryptedKeypair), which is
+//! available with the `keyfile` feature.
+use crate::{KeyTag, PublicKey, SignatureBytes};
+use serde_crate::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+impl Serialize for PublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_bytes(&self.to_vec())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PublicKeyVisitor;
+
+        impl<'de> de::Visitor<'de> for PublicKeyVisitor {
+            type Value = PublicKey;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a public key")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<PublicKey, E> {
+                PublicKey::from_str(v).map_err(de::Error::custom)
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<PublicKey, E> {
+                PublicKey::try_from(v).map_err(de::Error::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(PublicKeyVisitor)
+        } else {
+            deserializer.deserialize_bytes(PublicKeyVisitor)
+        }
+    }
+}
+
+impl Serialize for KeyTag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u8(u8::from(*self))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyTag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyTagVisitor;
+
+        impl<'de> de::Visitor<'de> for KeyTagVisitor {
+            type Value = KeyTag;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a key tag")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<KeyTag, E> {
+                KeyTag::from_str(v).map_err(de::Error::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<KeyTag, E> {
+                let v = u8::try_from(v).map_err(de::Error::custom)?;
+                KeyTag::try_from(v).map_err(de::Error::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(KeyTagVisitor)
+        } else {
+            deserializer.deserialize_u8(KeyTagVisitor)
+        }
+    }
+}
+
+impl Serialize for SignatureBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64::encode(self.as_bytes()))
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SignatureBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SignatureVisitor;
+
+        impl<'de> de::Visitor<'de> for SignatureVisitor {
+            type Value = SignatureBytes;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a signature")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<SignatureBytes, E> {
+                base64::decode(v)
+                    .map(SignatureBytes::new)
+                    .map_err(de::Error::custom)
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<SignatureBytes, E> {
+                Ok(SignatureBytes::new(v))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<SignatureBytes, E> {
+                Ok(SignatureBytes::new(v))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(SignatureVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(SignatureVisitor)
+        }
+    }
+}
+
+#[cfg(feature = "keyfile")]
+impl Serialize for crate::keyfile::EncryptedKeypair {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64::encode(self.as_bytes()))
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+#[cfg(feature = "keyfile")]
+impl<'de> Deserialize<'de> for crate::keyfile::EncryptedKeypair {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use crate::keyfile::EncryptedKeypair;
+        struct EncryptedKeypairVisitor;
+
+        impl<'de> de::Visitor<'de> for EncryptedKeypairVisitor {
+            type Value = EncryptedKeypair;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an encrypted keypair")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<EncryptedKeypair, E> {
+                let v = base64::decode(v).map_err(de::Error::custom)?;
+                EncryptedKeypair::try_from(v).map_err(de::Error::custom)
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<EncryptedKeypair, E> {
+                EncryptedKeypair::try_from(v.to_vec()).map_err(de::Error::custom)
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<EncryptedKeypair, E> {
+                EncryptedKeypair::try_from(v).map_err(de::Error::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(EncryptedKeypairVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(EncryptedKeypairVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ed25519, KeyTag, Keypair, Network, PublicKey, Sign, SignatureBytes};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn json_roundtrip() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let public_key = keypair.public_key();
+        let json = serde_json::to_string(public_key).expect("json");
+        assert_eq!(format!("\"{}\"", public_key), json);
+        assert_eq!(
+            public_key,
+            &serde_json::from_str::<PublicKey>(&json).expect("public key")
+        );
+
+        let json = serde_json::to_string(&keypair.key_tag()).expect("json");
+        assert_eq!("\"mainnet/ed25519\"", json);
+        assert_eq!(
+            keypair.key_tag(),
+            serde_json::from_str::<KeyTag>(&json).expect("key tag")
+        );
+
+        let signature = SignatureBytes::new(keypair.sign(b"hello world").expect("signature"));
+        let json = serde_json::to_string(&signature).expect("json");
+        let decoded = serde_json::from_str::<SignatureBytes>(&json).expect("signature");
+        assert_eq!(signature.as_bytes(), decoded.as_bytes());
+        assert!(serde_json::from_str::<PublicKey>("\"not a key\"").is_err());
+    }
+}
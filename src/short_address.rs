@@ -0,0 +1,147 @@
+//! A checksummed short form of an address for constrained displays, like
+//! hotspot LCDs and SMS flows, where the full base58 string does not fit.
+//!
+//! A short address is the first and last four characters of the base58
+//! address, followed by the first three bytes of the SHA-256 digest of the
+//! binary form of the key in hex, separated by dashes:
+//!
+//! ```text
+//! 13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw -> 13Wv-esKw-55dd75
+//! ```
+//!
+//! A short address can not be turned back into a key. It is used to confirm
+//! that a key known from elsewhere is the one shown, see
+//! [`ShortAddress::matches`].
+//!
+//! ## Collisions
+//!
+//! The leading characters of an address mostly encode the network and key
+//! type, so only the suffix and the checksum are counted as random. The last
+//! four characters are determined by the base58check checksum and carry
+//! about 23 bits, the digest bytes another 24, for at least 47 bits overall.
+//! The chance that a given short address matches some other random key is
+//! below 1 in 10^14. Among `n` keys the chance that any two share a short
+//! address is roughly `n^2 / 2^48`, about 0.4% for a million keys. Short
+//! addresses are therefore not suitable as unique identifiers.
+use crate::{error, public_key::B58_ALPHABET, PublicKey};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+
+/// Number of address characters kept at each end.
+const AFFIX_CHARS: usize = 4;
+/// Number of digest bytes in the checksum.
+const CHECKSUM_BYTES: usize = 3;
+/// Length of the string form, including separators.
+pub const SHORT_ADDRESS_LENGTH: usize = 2 * AFFIX_CHARS + 2 * CHECKSUM_BYTES + 2;
+
+/// The short address of a public key.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ShortAddress {
+    prefix: [u8; AFFIX_CHARS],
+    suffix: [u8; AFFIX_CHARS],
+    checksum: [u8; CHECKSUM_BYTES],
+}
+
+impl ShortAddress {
+    /// Whether the given key has this short address.
+    pub fn matches(&self, public_key: &PublicKey) -> bool {
+        Self::from(public_key) == *self
+    }
+}
+
+impl From<&PublicKey> for ShortAddress {
+    fn from(public_key: &PublicKey) -> Self {
+        let address = public_key.to_string();
+        let address = address.as_bytes();
+        let digest = Sha256::digest(&public_key.to_bytes());
+        Self {
+            prefix: address[..AFFIX_CHARS].try_into().expect("address prefix"),
+            suffix: address[address.len() - AFFIX_CHARS..]
+                .try_into()
+                .expect("address suffix"),
+            checksum: digest[..CHECKSUM_BYTES].try_into().expect("checksum"),
+        }
+    }
+}
+
+impl std::str::FromStr for ShortAddress {
+    type Err = error::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = s.trim().split('-');
+        let (prefix, suffix, checksum) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(prefix), Some(suffix), Some(checksum)) if parts.next().is_none() => {
+                (prefix, suffix, checksum)
+            }
+            _ => return Err(error::invalid_short_address()),
+        };
+        let affix = |part: &str| -> error::Result<[u8; AFFIX_CHARS]> {
+            match part.as_bytes().try_into() {
+                Ok(chars) if part.bytes().all(|c| B58_ALPHABET.contains(&c)) => Ok(chars),
+                _ => Err(error::invalid_short_address()),
+            }
+        };
+        if checksum.len() != 2 * CHECKSUM_BYTES {
+            return Err(error::invalid_short_address());
+        }
+        let mut digest = [0u8; CHECKSUM_BYTES];
+        for (i, byte) in digest.iter_mut().enumerate() {
+            *byte = checksum
+                .get(2 * i..2 * i + 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(error::invalid_short_address)?;
+        }
+        Ok(Self {
+            prefix: affix(prefix)?,
+            suffix: affix(suffix)?,
+            checksum: digest,
+        })
+    }
+}
+
+impl std::fmt::Display for ShortAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        // Both affixes only hold base58 characters
+        let prefix = std::str::from_utf8(&self.prefix).map_err(|_| std::fmt::Error)?;
+        let suffix = std::str::from_utf8(&self.suffix).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}-{}-", prefix, suffix)?;
+        for byte in &self.checksum {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShortAddress, SHORT_ADDRESS_LENGTH};
+    use crate::PublicKey;
+
+    const ADDRESS: &str = "13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw";
+    const OTHER: &str = "14HZVR4bdF9QMowYxWrumcFBNfWnhDdD5XXA5za1fWwUhHxxFS1";
+
+    #[test]
+    fn roundtrip() {
+        let public_key: PublicKey = ADDRESS.parse().expect("public key");
+        let short = ShortAddress::from(&public_key);
+        assert_eq!("13Wv-esKw-55dd75", short.to_string());
+        assert_eq!(SHORT_ADDRESS_LENGTH, short.to_string().len());
+        let parsed: ShortAddress = "13Wv-esKw-55DD75".parse().expect("short address");
+        assert_eq!(short, parsed);
+        assert!(parsed.matches(&public_key));
+        assert!(!parsed.matches(&OTHER.parse().expect("public key")));
+    }
+
+    #[test]
+    fn invalid() {
+        for s in &[
+            "13Wv-esKw",
+            "13Wv-esKw-55dd7",
+            "13Wv-esKw-55dd75-00",
+            "13W0-esKw-55dd75",
+            "13Wv-esK-55dd75",
+            "13Wv-esKw-55dd7g",
+        ] {
+            assert!(s.parse::<ShortAddress>().is_err(), "{}", s);
+        }
+    }
+}
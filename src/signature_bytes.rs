@@ -0,0 +1,241 @@
+//! Signatures that are parsed lazily.
+//!
+//! Signatures are often passed along several hops, like queues between
+//! services, before they are verified. [`SignatureBytes`] holds the raw bytes
+//! and only parses them when verifying, caching the result so verifying the
+//! same signature again, for example against several messages or keys, does
+//! not parse it again.
+//!
+//! Only ECDSA signatures need parsing, from DER or the raw form, into their
+//! fixed size form. ed25519 signatures are already fixed size and are always
+//! verified from their bytes.
+//!
+//! For JSON APIs and log lines signatures are rendered as strings, hex by
+//! `Display` and parsed back by `FromStr`, or in a chosen
+//! [`SignatureEncoding`]. Converting between the DER and raw forms of ECDSA
+//! signatures needs the key type, see [`SignatureBytes::to_typed`].
+use crate::{
+    ecc_compact, error,
+    public_key::{PublicKey, PublicKeyRepr, VerifyOptions},
+    KeyTag, Signature, Verify,
+};
+use p256::ecdsa;
+use std::{fmt, str::FromStr, sync::Mutex};
+
+/// String encodings of signatures.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SignatureEncoding {
+    /// Lowercase hex, as rendered by `Display`
+    Hex,
+    /// Standard base64 with padding
+    #[cfg(feature = "base64")]
+    Base64,
+}
+
+/// A parse result together with the options it was parsed with.
+type ParsedEcdsa = (VerifyOptions, Option<ecdsa::Signature>);
+
+pub struct SignatureBytes {
+    bytes: Vec<u8>,
+    ecdsa: Mutex<Option<ParsedEcdsa>>,
+}
+
+impl SignatureBytes {
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            bytes: bytes.into(),
+            ecdsa: Mutex::new(None),
+        }
+    }
+
+    /// The raw signature bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// The signature bytes in the given string encoding.
+    pub fn encode(&self, encoding: SignatureEncoding) -> String {
+        match encoding {
+            SignatureEncoding::Hex => self.to_string(),
+            #[cfg(feature = "base64")]
+            SignatureEncoding::Base64 => base64::encode(&self.bytes),
+        }
+    }
+
+    /// Parse signature bytes in the given string encoding. Hex may have a
+    /// `0x` prefix and be in either case.
+    pub fn decode(input: &str, encoding: SignatureEncoding) -> error::Result<Self> {
+        match encoding {
+            SignatureEncoding::Hex => {
+                let hex = input.strip_prefix("0x").unwrap_or(input);
+                if hex.len() % 2 != 0 || !hex.is_ascii() {
+                    return Err(error::invalid_encoding());
+                }
+                (0..hex.len())
+                    .step_by(2)
+                    .map(|pos| u8::from_str_radix(&hex[pos..pos + 2], 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .map(Self::new)
+                    .map_err(|_| error::invalid_encoding())
+            }
+            #[cfg(feature = "base64")]
+            SignatureEncoding::Base64 => base64::decode(input)
+                .map(Self::new)
+                .map_err(|_| error::invalid_encoding()),
+        }
+    }
+
+    /// Parse the signature for a key with the given tag, from either its DER
+    /// or its raw form, see [`Signature::from_bytes`]. The result converts to
+    /// either form.
+    pub fn to_typed(&self, tag: KeyTag) -> error::Result<Signature> {
+        Signature::from_bytes(tag, &self.bytes)
+    }
+
+    /// Verify the signature for the given message and public key using the
+    /// given verification rules, parsing the signature only if it has not
+    /// been parsed with the same rules before.
+    pub fn verify(
+        &self,
+        public_key: &PublicKey,
+        msg: &[u8],
+        options: &VerifyOptions,
+    ) -> error::Result {
+        match &public_key.inner {
+            PublicKeyRepr::EccCompact(key) => match self.parsed_ecdsa(options) {
+                Some(signature) => key.verify_parsed(msg, &signature),
+                // Take the regular path to get the detailed error
+                None => public_key.verify_with_options(msg, &self.bytes, options),
+            },
+            _ => public_key.verify_with_options(msg, &self.bytes, options),
+        }
+    }
+
+    fn parsed_ecdsa(&self, options: &VerifyOptions) -> Option<ecdsa::Signature> {
+        let mut cached = self.ecdsa.lock().expect("signature lock");
+        match &*cached {
+            Some((cached_options, signature)) if cached_options == options => signature.clone(),
+            _ => {
+                let signature = ecc_compact::parse_signature(&self.bytes, options).ok();
+                *cached = Some((*options, signature.clone()));
+                signature
+            }
+        }
+    }
+}
+
+impl Clone for SignatureBytes {
+    fn clone(&self) -> Self {
+        Self {
+            bytes: self.bytes.clone(),
+            ecdsa: Mutex::new(self.ecdsa.lock().expect("signature lock").clone()),
+        }
+    }
+}
+
+impl PartialEq for SignatureBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl std::fmt::Debug for SignatureBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_tuple("SignatureBytes").field(&self.bytes).finish()
+    }
+}
+
+impl fmt::Display for SignatureBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.bytes
+            .iter()
+            .try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl FromStr for SignatureBytes {
+    type Err = error::Error;
+    fn from_str(s: &str) -> error::Result<Self> {
+        Self::decode(s, SignatureEncoding::Hex)
+    }
+}
+
+impl AsRef<[u8]> for SignatureBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl From<Vec<u8>> for SignatureBytes {
+    fn from(v: Vec<u8>) -> Self {
+        Self::new(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SignatureBytes, SignatureEncoding};
+    use crate::{ecc_compact, ed25519, Network, Sign, VerifyOptions};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn verify_cached() {
+        let ecc = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let ed = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = SignatureBytes::new(ecc.sign(b"hello world").expect("signature"));
+        for _ in 0..2 {
+            assert!(signature
+                .verify(&ecc.public_key, b"hello world", &VerifyOptions::DEFAULT)
+                .is_ok());
+            assert!(signature
+                .verify(&ecc.public_key, b"hello there", &VerifyOptions::DEFAULT)
+                .is_err());
+        }
+        assert!(signature
+            .verify(&ecc.public_key, b"hello world", &VerifyOptions::CONSENSUS)
+            .is_ok());
+        assert!(signature
+            .verify(&ed.public_key, b"hello world", &VerifyOptions::DEFAULT)
+            .is_err());
+        let signature = SignatureBytes::from(ed.sign(b"hello world").expect("signature"));
+        assert!(signature
+            .verify(&ed.public_key, b"hello world", &VerifyOptions::DEFAULT)
+            .is_ok());
+    }
+
+    #[test]
+    fn string_encodings() {
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = SignatureBytes::new(keypair.sign(b"hello world").expect("signature"));
+        let hex = signature.to_string();
+        assert_eq!(hex, signature.encode(SignatureEncoding::Hex));
+        assert_eq!(signature, hex.parse().expect("hex signature"));
+        assert_eq!(
+            signature,
+            format!("0x{}", hex.to_uppercase())
+                .parse()
+                .expect("hex signature")
+        );
+        assert!("abc".parse::<SignatureBytes>().is_err());
+        #[cfg(feature = "base64")]
+        {
+            let encoded = signature.encode(SignatureEncoding::Base64);
+            assert_eq!(
+                signature,
+                SignatureBytes::decode(&encoded, SignatureEncoding::Base64).expect("signature")
+            );
+        }
+
+        let tag = keypair.public_key.tag();
+        let raw = signature.to_typed(tag).expect("typed signature").to_raw();
+        let from_raw = SignatureBytes::new(raw.to_vec());
+        assert_eq!(
+            signature.as_bytes(),
+            &from_raw.to_typed(tag).expect("typed signature").to_der()[..]
+        );
+    }
+}
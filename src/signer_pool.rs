@@ -0,0 +1,194 @@
+//! A pool of signers that distributes sign requests over worker threads. Each
+//! worker owns one signer, which can be a separate hardware session for
+//! backends that serialize signing per session, or a shared software keypair
+//! behind an `Arc`.
+//!
+//! Requests queue up in a bounded channel. Once the queue is full `sign` blocks
+//! until a worker frees up, while `try_sign` fails fast with
+//! `Error::PoolFull`.
+use crate::{error, Sign};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+struct Request {
+    msg: Vec<u8>,
+    reply: mpsc::SyncSender<error::Result<Vec<u8>>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    submitted: AtomicU64,
+    signed: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// A snapshot of the pool counters.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Metrics {
+    /// Number of requests accepted into the queue
+    pub submitted: u64,
+    /// Number of requests that produced a signature
+    pub signed: u64,
+    /// Number of requests where the signer returned an error
+    pub failed: u64,
+}
+
+impl Metrics {
+    /// Number of requests queued or in progress.
+    pub fn pending(&self) -> u64 {
+        // Counters are read independently, so a snapshot can briefly see more
+        // completed than submitted requests
+        self.submitted
+            .saturating_sub(self.signed)
+            .saturating_sub(self.failed)
+    }
+}
+
+pub struct SignerPool {
+    sender: Option<mpsc::SyncSender<Request>>,
+    workers: Vec<thread::JoinHandle<()>>,
+    counters: Arc<Counters>,
+}
+
+impl SignerPool {
+    /// Create a pool with a worker thread for each of the given signers and a
+    /// request queue holding up to `queue_depth` pending requests.
+    pub fn new<S, I>(signers: I, queue_depth: usize) -> Self
+    where
+        S: Sign + Send + 'static,
+        I: IntoIterator<Item = S>,
+    {
+        let (sender, receiver) = mpsc::sync_channel::<Request>(queue_depth);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let counters = Arc::new(Counters::default());
+        let workers = signers
+            .into_iter()
+            .map(|signer| {
+                let receiver = receiver.clone();
+                let counters = counters.clone();
+                thread::spawn(move || loop {
+                    // Release the receiver lock before signing so other
+                    // workers can pick up requests
+                    let request = match receiver.lock().expect("pool lock").recv() {
+                        Ok(request) => request,
+                        Err(_) => break,
+                    };
+                    let result = signer.sign(&request.msg);
+                    let counter = if result.is_ok() {
+                        &counters.signed
+                    } else {
+                        &counters.failed
+                    };
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    // The requester may have given up waiting
+                    let _ = request.reply.send(result);
+                })
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+            counters,
+        }
+    }
+
+    /// Sign the given message on the next available worker, blocking while the
+    /// request queue is full.
+    pub fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        let (request, reply) = Self::request(msg);
+        self.submit(|sender| sender.send(request).map_err(|_| error::pool_closed()))?;
+        reply.recv().map_err(|_| error::pool_closed())?
+    }
+
+    /// Sign the given message on the next available worker, failing with
+    /// `Error::PoolFull` instead of blocking if the request queue is full.
+    pub fn try_sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        let (request, reply) = Self::request(msg);
+        self.submit(|sender| {
+            sender.try_send(request).map_err(|err| match err {
+                mpsc::TrySendError::Full(_) => error::pool_full(),
+                mpsc::TrySendError::Disconnected(_) => error::pool_closed(),
+            })
+        })?;
+        reply.recv().map_err(|_| error::pool_closed())?
+    }
+
+    /// The number of workers in the pool.
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Returns a snapshot of the pool counters.
+    pub fn metrics(&self) -> Metrics {
+        Metrics {
+            submitted: self.counters.submitted.load(Ordering::Relaxed),
+            signed: self.counters.signed.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn request(msg: &[u8]) -> (Request, mpsc::Receiver<error::Result<Vec<u8>>>) {
+        let (reply, receiver) = mpsc::sync_channel(1);
+        (
+            Request {
+                msg: msg.to_vec(),
+                reply,
+            },
+            receiver,
+        )
+    }
+
+    /// Counts the request as submitted before handing it to the queue so a
+    /// fast worker can never make the pending count go negative.
+    fn submit<F>(&self, send: F) -> error::Result
+    where
+        F: FnOnce(&mpsc::SyncSender<Request>) -> error::Result,
+    {
+        let sender = self.sender.as_ref().ok_or_else(error::pool_closed)?;
+        self.counters.submitted.fetch_add(1, Ordering::Relaxed);
+        send(sender).map_err(|err| {
+            self.counters.submitted.fetch_sub(1, Ordering::Relaxed);
+            err
+        })
+    }
+}
+
+impl Drop for SignerPool {
+    fn drop(&mut self) {
+        // Closing the queue makes workers exit once it has drained
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignerPool;
+    use crate::{ed25519, Network, Verify};
+    use rand::rngs::OsRng;
+    use std::sync::Arc;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Arc::new(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let pool = SignerPool::new(std::iter::repeat(keypair.clone()).take(4), 8);
+        assert_eq!(4, pool.workers());
+        for _ in 0..16 {
+            let signature = pool.sign(b"hello world").expect("signature");
+            assert!(keypair
+                .public_key
+                .verify(b"hello world", &signature)
+                .is_ok());
+        }
+        let metrics = pool.metrics();
+        assert_eq!(16, metrics.signed);
+        assert_eq!(0, metrics.pending());
+    }
+}
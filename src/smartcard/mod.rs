@@ -0,0 +1,428 @@
+//! Smartcard backed keypairs. The secret key lives in a PIV slot of an ISO
+//! 7816 smartcard or JavaCard reached over PC/SC and all signing happens on the
+//! card.
+//!
+//! Only P-256 keys are supported. Keys are ecc_compact keys when their public
+//! key is compactable and secp256r1 keys otherwise. On-card key generation is
+//! retried until the card produces a compactable key.
+use crate::{ecc_compact, error, keypair, public_key, KeyType, Network};
+use des::{
+    cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, NewBlockCipher},
+    TdesEde3,
+};
+use sha2::{Digest, Sha256};
+use std::{ffi::CStr, sync::Mutex};
+
+#[cfg(feature = "yubikey")]
+pub mod yubikey;
+
+/// The PIV key slots that can hold a signing key.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Slot {
+    Authentication,
+    Signature,
+    KeyManagement,
+    CardAuthentication,
+}
+
+impl From<Slot> for u8 {
+    fn from(v: Slot) -> Self {
+        match v {
+            Slot::Authentication => 0x9a,
+            Slot::Signature => 0x9c,
+            Slot::KeyManagement => 0x9d,
+            Slot::CardAuthentication => 0x9e,
+        }
+    }
+}
+
+/// A connection to a card and the slot holding the key to sign with.
+pub struct Card {
+    card: Mutex<pcsc::Card>,
+    slot: Slot,
+}
+
+pub type Keypair = keypair::Keypair<Card>;
+
+/// The default PIV management key (3DES) that cards ship with.
+pub const DEFAULT_MANAGEMENT_KEY: [u8; 24] = [
+    0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+    0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+];
+
+/// Number of on-card key generation attempts before giving up on finding a
+/// compactable key.
+const MAX_GENERATE_ATTEMPTS: usize = 64;
+
+const PIV_AID: &[u8] = &[0xa0, 0x00, 0x00, 0x03, 0x08];
+const ALG_3DES: u8 = 0x03;
+const ALG_ECC_P256: u8 = 0x11;
+const KEY_MANAGEMENT_REF: u8 = 0x9b;
+const PIN_REF: u8 = 0x80;
+/// PIV PINs are at most 8 bytes.
+const MAX_PIN_LENGTH: usize = 8;
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        let digest = Sha256::digest(msg);
+        let mut data = vec![0x7c, 0x24, 0x82, 0x00, 0x81, 0x20];
+        data.extend_from_slice(&digest);
+        let card = self.inner.card.lock().expect("card lock");
+        let response = transmit(
+            &card,
+            &apdu(0x87, ALG_ECC_P256, u8::from(self.inner.slot), &data),
+        )?;
+        signature_from_response(&response)
+    }
+}
+
+impl Keypair {
+    /// Connect to the card in the given reader, verify the user PIN and read
+    /// the public key for the key in the given slot from the certificate stored
+    /// with it.
+    pub fn open(network: Network, reader: &CStr, slot: Slot, pin: &[u8]) -> error::Result<Self> {
+        let card = connect(reader)?;
+        verify_pin(&card, pin)?;
+        let certificate = transmit(&card, &get_certificate_apdu(slot))?;
+        let public_key = public_key_from_certificate(network, &certificate)?;
+        Ok(Keypair {
+            network,
+            public_key,
+            inner: Card {
+                card: Mutex::new(card),
+                slot,
+            },
+        })
+    }
+
+    /// Generate a new key in the given slot of the card in the given reader.
+    /// Key generation is an administrative operation and requires the card's
+    /// 3DES management key. Any existing key in the slot is overwritten.
+    ///
+    /// Note that no certificate is written for the generated key. Since `open`
+    /// reads the public key from the slot certificate, one needs to be issued
+    /// and stored with the card's management tooling before the key can be
+    /// opened again.
+    pub fn generate(
+        network: Network,
+        reader: &CStr,
+        slot: Slot,
+        pin: &[u8],
+        management_key: &[u8; 24],
+    ) -> error::Result<Self> {
+        Self::generate_with_policy(network, reader, slot, pin, management_key, &[])
+    }
+
+    /// Generate a key with the given extra TLV encoded entries in the key
+    /// generation control template, used for vendor specific key policies.
+    fn generate_with_policy(
+        network: Network,
+        reader: &CStr,
+        slot: Slot,
+        pin: &[u8],
+        management_key: &[u8; 24],
+        policy: &[u8],
+    ) -> error::Result<Self> {
+        let card = connect(reader)?;
+        authenticate(&card, management_key)?;
+        verify_pin(&card, pin)?;
+        let mut template = vec![0xac, (policy.len() + 3) as u8, 0x80, 0x01, ALG_ECC_P256];
+        template.extend_from_slice(policy);
+        let generate = apdu(0x47, 0x00, u8::from(slot), &template);
+        for _ in 0..MAX_GENERATE_ATTEMPTS {
+            let response = transmit(&card, &generate)?;
+            let point = point_from_generate_response(&response)?;
+            let public_key = public_key::PublicKey::from_p256_sec1_bytes(network, point)?;
+            if public_key.tag().key_type == KeyType::EccCompact {
+                return Ok(Keypair {
+                    network,
+                    public_key,
+                    inner: Card {
+                        card: Mutex::new(card),
+                        slot,
+                    },
+                });
+            }
+        }
+        Err(error::not_compact())
+    }
+}
+
+fn connect(reader: &CStr) -> error::Result<pcsc::Card> {
+    let ctx = pcsc::Context::establish(pcsc::Scope::User)?;
+    let card = ctx.connect(reader, pcsc::ShareMode::Shared, pcsc::Protocols::ANY)?;
+    transmit(&card, &apdu(0xa4, 0x04, 0x00, PIV_AID))?;
+    Ok(card)
+}
+
+fn verify_pin(card: &pcsc::Card, pin: &[u8]) -> error::Result {
+    transmit(card, &apdu(0x20, 0x00, PIN_REF, &pin_data(pin)?)).map(|_| ())
+}
+
+/// The PIN padded to 8 bytes with 0xff. Longer PINs are rejected rather than
+/// cut short, since a truncated PIN may still verify.
+fn pin_data(pin: &[u8]) -> error::Result<[u8; MAX_PIN_LENGTH]> {
+    if pin.len() > MAX_PIN_LENGTH {
+        return Err(error::invalid_length(MAX_PIN_LENGTH, pin.len()));
+    }
+    let mut data = [0xffu8; MAX_PIN_LENGTH];
+    data[..pin.len()].copy_from_slice(pin);
+    Ok(data)
+}
+
+/// Mutually authenticate with the card using the management key: decrypt the
+/// witness the card hands out and check that the card can encrypt a challenge
+/// of our own.
+fn authenticate(card: &pcsc::Card, management_key: &[u8; 24]) -> error::Result {
+    use rand_core::RngCore;
+    let response = transmit(
+        card,
+        &apdu(
+            0x87,
+            ALG_3DES,
+            KEY_MANAGEMENT_REF,
+            &[0x7c, 0x02, 0x80, 0x00],
+        ),
+    )?;
+    let witness = tlv(tlv(&response, 0x7c)?, 0x80)?;
+    if witness.len() != 8 {
+        return Err(error::invalid_smartcard_response());
+    }
+    let cipher = TdesEde3::new(GenericArray::from_slice(management_key));
+    let mut block = GenericArray::clone_from_slice(witness);
+    cipher.decrypt_block(&mut block);
+    let mut challenge = [0u8; 8];
+    rand_core::OsRng.fill_bytes(&mut challenge);
+    let mut data = vec![0x7c, 0x14, 0x80, 0x08];
+    data.extend_from_slice(&block);
+    data.extend_from_slice(&[0x81, 0x08]);
+    data.extend_from_slice(&challenge);
+    let response = transmit(card, &apdu(0x87, ALG_3DES, KEY_MANAGEMENT_REF, &data))?;
+    let mut expected = GenericArray::clone_from_slice(&challenge);
+    cipher.encrypt_block(&mut expected);
+    if tlv(tlv(&response, 0x7c)?, 0x82)? != expected.as_slice() {
+        return Err(error::invalid_smartcard_response());
+    }
+    Ok(())
+}
+
+fn get_certificate_apdu(slot: Slot) -> Vec<u8> {
+    let object: &[u8] = match slot {
+        Slot::Authentication => &[0x5f, 0xc1, 0x05],
+        Slot::Signature => &[0x5f, 0xc1, 0x0a],
+        Slot::KeyManagement => &[0x5f, 0xc1, 0x0b],
+        Slot::CardAuthentication => &[0x5f, 0xc1, 0x01],
+    };
+    let mut data = vec![0x5c, object.len() as u8];
+    data.extend_from_slice(object);
+    apdu(0xcb, 0x3f, 0xff, &data)
+}
+
+/// Finds the P-256 public key in a DER encoded certificate. The
+/// SubjectPublicKeyInfo for P-256 keys has a fixed encoding so it is located by
+/// its prefix rather than parsing the full certificate.
+fn public_key_from_certificate(
+    network: Network,
+    certificate: &[u8],
+) -> error::Result<public_key::PublicKey> {
+    let start = certificate
+        .windows(ecc_compact::P256_SPKI_PREFIX.len())
+        .position(|window| window == ecc_compact::P256_SPKI_PREFIX)
+        .map(|pos| pos + ecc_compact::P256_SPKI_PREFIX.len())
+        .ok_or_else(|| error::invalid_smartcard_response())?;
+    let point = certificate
+        .get(start..start + 65)
+        .ok_or_else(|| error::invalid_smartcard_response())?;
+    public_key::PublicKey::from_p256_sec1_bytes(network, point)
+}
+
+/// The normalized DER signature in a signing response, a dynamic
+/// authentication template wrapping the DER encoded signature:
+/// 7c <len> 82 <len> <der>
+fn signature_from_response(response: &[u8]) -> error::Result<Vec<u8>> {
+    let template = tlv(response, 0x7c)?;
+    let signature = p256::ecdsa::Signature::from_der(tlv(template, 0x82)?)
+        .map_err(|_| error::invalid_smartcard_response())?;
+    // Cards may return a high s
+    let signature = ecc_compact::normalize_s(signature)?;
+    Ok(signature.to_der().as_bytes().to_vec())
+}
+
+/// The uncompressed point in a key generation response, which is a 7f49
+/// template with the point in tag 86.
+fn point_from_generate_response(response: &[u8]) -> error::Result<&[u8]> {
+    let template = response
+        .strip_prefix(&[0x7f, 0x49])
+        .and_then(|template| template.get(1..))
+        .ok_or_else(|| error::invalid_smartcard_response())?;
+    tlv(template, 0x86)
+}
+
+/// Builds a short case 4 APDU.
+fn apdu(ins: u8, p1: u8, p2: u8, data: &[u8]) -> Vec<u8> {
+    let mut result = vec![0x00, ins, p1, p2, data.len() as u8];
+    result.extend_from_slice(data);
+    result.push(0x00);
+    result
+}
+
+/// Returns the value of the given single byte tag at the start of the given
+/// BER-TLV encoded data.
+fn tlv(data: &[u8], tag: u8) -> error::Result<&[u8]> {
+    let (value_start, len) = match data {
+        [t, 0x81, len, ..] if *t == tag => (3, *len as usize),
+        [t, 0x82, hi, lo, ..] if *t == tag => (4, u16::from_be_bytes([*hi, *lo]) as usize),
+        [t, len, ..] if *t == tag && *len < 0x80 => (2, *len as usize),
+        _ => return Err(error::invalid_smartcard_response()),
+    };
+    data.get(value_start..value_start + len)
+        .ok_or_else(|| error::invalid_smartcard_response())
+}
+
+/// Transmits the given APDU, following GET RESPONSE chaining, and returns the
+/// response data or an error carrying the status word.
+fn transmit(card: &pcsc::Card, apdu: &[u8]) -> error::Result<Vec<u8>> {
+    let mut buf = [0u8; pcsc::MAX_BUFFER_SIZE_EXTENDED];
+    let mut data = Vec::new();
+    let mut response = card.transmit(apdu, &mut buf)?;
+    loop {
+        match parse_response(response)? {
+            Response::Done(body) => {
+                data.extend_from_slice(body);
+                return Ok(data);
+            }
+            Response::More(body, remaining) => {
+                data.extend_from_slice(body);
+                response = card.transmit(&[0x00, 0xc0, 0x00, 0x00, remaining], &mut buf)?
+            }
+        }
+    }
+}
+
+/// A successful response APDU, split from its status word.
+#[derive(Debug, PartialEq)]
+enum Response<'a> {
+    /// The last part of the response data
+    Done(&'a [u8]),
+    /// Part of the response data, with the number of bytes still to fetch
+    /// with GET RESPONSE
+    More(&'a [u8], u8),
+}
+
+/// Splits a response APDU into its data and status word, returning an error
+/// carrying the status word for anything but success or more data.
+fn parse_response(response: &[u8]) -> error::Result<Response<'_>> {
+    let len = response.len();
+    if len < 2 {
+        return Err(error::invalid_smartcard_response());
+    }
+    let body = &response[..len - 2];
+    match (response[len - 2], response[len - 1]) {
+        (0x90, 0x00) => Ok(Response::Done(body)),
+        (0x61, remaining) => Ok(Response::More(body, remaining)),
+        (sw1, sw2) => Err(error::smartcard_status(u16::from_be_bytes([sw1, sw2]))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_response, pin_data, point_from_generate_response, signature_from_response, tlv,
+        Response,
+    };
+    use crate::{error::Error, Sign};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn tlv_lengths() {
+        assert_eq!(
+            &[1, 2][..],
+            tlv(&[0x86, 0x02, 1, 2, 3], 0x86).expect("short")
+        );
+        let mut long = vec![0x86, 0x81, 0x80];
+        long.extend_from_slice(&[7u8; 0x80]);
+        assert_eq!(&[7u8; 0x80][..], tlv(&long, 0x86).expect("one byte"));
+        let mut longer = vec![0x86, 0x82, 0x01, 0x00];
+        longer.extend_from_slice(&[7u8; 0x100]);
+        assert_eq!(&[7u8; 0x100][..], tlv(&longer, 0x86).expect("two byte"));
+    }
+
+    #[test]
+    fn tlv_rejects() {
+        for data in &[
+            &[][..],
+            &[0x86],
+            &[0x85, 0x01, 0x00],
+            &[0x86, 0x03, 1, 2],
+            &[0x86, 0x81],
+            &[0x86, 0x81, 0x02, 1],
+            &[0x86, 0x82, 0x00],
+            &[0x86, 0x83, 0x00, 0x00, 0x01],
+        ] {
+            assert!(matches!(
+                tlv(data, 0x86),
+                Err(Error::InvalidSmartcardResponse)
+            ));
+        }
+    }
+
+    #[test]
+    fn responses() {
+        assert_eq!(
+            Response::Done(&[1, 2]),
+            parse_response(&[1, 2, 0x90, 0x00]).expect("done")
+        );
+        assert_eq!(
+            Response::Done(&[]),
+            parse_response(&[0x90, 0x00]).expect("empty")
+        );
+        assert_eq!(
+            Response::More(&[1], 0x10),
+            parse_response(&[1, 0x61, 0x10]).expect("more")
+        );
+        assert!(matches!(
+            parse_response(&[0x6a, 0x82]),
+            Err(Error::SmartcardStatus(0x6a82))
+        ));
+        assert!(matches!(
+            parse_response(&[0x90]),
+            Err(Error::InvalidSmartcardResponse)
+        ));
+    }
+
+    #[test]
+    fn generate_responses() {
+        let mut response = vec![0x7f, 0x49, 0x43, 0x86, 0x41];
+        response.extend_from_slice(&[4u8; 65]);
+        assert_eq!(
+            &[4u8; 65][..],
+            point_from_generate_response(&response).expect("point")
+        );
+        for response in &[&[][..], &[0x7f, 0x49], &[0x7f, 0x49, 0x02], &[0x7c, 0x00]] {
+            assert!(matches!(
+                point_from_generate_response(response),
+                Err(Error::InvalidSmartcardResponse)
+            ));
+        }
+    }
+
+    #[test]
+    fn sign_responses() {
+        let keypair = crate::ecc_compact::Keypair::generate(crate::Network::MainNet, &mut OsRng);
+        let der = keypair.sign(b"hello").expect("signature");
+        let mut response = vec![0x7c, der.len() as u8 + 2, 0x82, der.len() as u8];
+        response.extend_from_slice(&der);
+        assert_eq!(der, signature_from_response(&response).expect("der"));
+        assert!(signature_from_response(&[0x7c, 0x02, 0x82, 0x00]).is_err());
+    }
+
+    #[test]
+    fn pins() {
+        assert_eq!(
+            [b'1', b'2', b'3', b'4', b'5', b'6', 0xff, 0xff],
+            pin_data(b"123456").expect("pin")
+        );
+        assert_eq!(*b"12345678", pin_data(b"12345678").expect("pin"));
+        assert!(matches!(pin_data(b"123456789"), Err(Error::Decode(_))));
+    }
+}
@@ -0,0 +1,56 @@
+//! YubiKey specific PIV key generation. A YubiKey is a PIV card, so keys on it
+//! are opened and used like any other smartcard key, but key generation can
+//! additionally require the user to touch the key before each signature.
+use super::{Keypair, Slot};
+use crate::{error, Network};
+use std::ffi::CStr;
+
+/// Whether signing with a key requires touching the YubiKey.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TouchPolicy {
+    /// Signing never requires touch
+    Never,
+    /// Every signature requires touch
+    Always,
+    /// A touch is valid for signatures in the following 15 seconds
+    Cached,
+}
+
+impl From<TouchPolicy> for u8 {
+    fn from(v: TouchPolicy) -> Self {
+        match v {
+            TouchPolicy::Never => 0x01,
+            TouchPolicy::Always => 0x02,
+            TouchPolicy::Cached => 0x03,
+        }
+    }
+}
+
+const TAG_TOUCH_POLICY: u8 = 0xab;
+
+/// Generate a new key with the given touch policy in the authentication (9a)
+/// or signature (9c) slot of the YubiKey in the given reader. Any existing key
+/// in the slot is overwritten.
+///
+/// Like `Keypair::generate` this needs the management key and does not write
+/// a certificate for the new key.
+pub fn generate(
+    network: Network,
+    reader: &CStr,
+    slot: Slot,
+    pin: &[u8],
+    management_key: &[u8; 24],
+    touch_policy: TouchPolicy,
+) -> error::Result<Keypair> {
+    if !matches!(slot, Slot::Authentication | Slot::Signature) {
+        return Err(error::invalid_smartcard_slot(slot));
+    }
+    Keypair::generate_with_policy(
+        network,
+        reader,
+        slot,
+        pin,
+        management_key,
+        &[TAG_TOUCH_POLICY, 0x01, u8::from(touch_policy)],
+    )
+}
@@ -0,0 +1,346 @@
+//! sr25519 keypairs with Schnorr signatures over the Ristretto group, as used
+//! by Substrate based chains, so Substrate identities can be bridged to
+//! Helium identities.
+//!
+//! sr25519 signatures are always made in a signing context, which keeps
+//! signatures for one protocol from verifying in another. `Sign` and
+//! `Verify` use the `substrate` context that Substrate uses for extrinsics
+//! and account signatures, other contexts are available through
+//! [`Keypair::sign_with_signing_context`] and
+//! [`PublicKey::verify_with_signing_context`].
+//!
+//! The binary form of a public key is the key tag followed by the 32 byte
+//! Ristretto point, and its address is the b58check encoding of that form.
+//! [`PublicKey`] converts into a [`crate::PublicKey`], which parses these keys
+//! as well. Keypairs are the key tag followed by the
+//! 32 byte mini secret key, which is expanded the way Substrate expands
+//! secret seeds, so a seed gives the same key here as in `subkey`.
+use crate::{
+    entropy::{EntropyRng, EntropySource},
+    error, keypair, output_prefix,
+    public_key::VerifyOptions,
+    IntoBytes, KeyTag, KeyType, Network, Sign, Verify,
+};
+use schnorrkel::{ExpansionMode, MiniSecretKey};
+use std::convert::TryFrom;
+
+/// Length of the binary form of a keypair.
+pub const KEYPAIR_LENGTH: usize = 33;
+
+/// Length of the binary form of a public key.
+pub const PUBLIC_KEY_LENGTH: usize = 33;
+
+/// Length of an sr25519 signature.
+pub const SIGNATURE_LENGTH: usize = 64;
+
+/// The signing context used by `Sign` and `Verify`.
+pub const SUBSTRATE_CONTEXT: &[u8] = b"substrate";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PublicKey {
+    /// The network this key is valid for
+    pub network: Network,
+    inner: schnorrkel::PublicKey,
+}
+
+/// The mini secret key is kept next to the keypair it expands to, since the
+/// expansion can not be undone to export the keypair.
+pub struct Secret {
+    mini: MiniSecretKey,
+    keypair: schnorrkel::Keypair,
+}
+
+pub type Keypair = keypair::Keypair<Secret, PublicKey>;
+
+/// Schnorrkel errors carry nothing worth keeping.
+fn signature_error<E>(_: E) -> error::Error {
+    signature::Error::new().into()
+}
+
+impl Keypair {
+    pub fn generate<E>(network: Network, entropy: &mut E) -> Keypair
+    where
+        E: EntropySource + ?Sized,
+    {
+        Self::from_mini_secret(
+            network,
+            MiniSecretKey::generate_with(&mut EntropyRng(entropy)),
+        )
+    }
+
+    /// Generate a keypair using the operating system random number generator.
+    pub fn generate_default(network: Network) -> Keypair {
+        Self::generate(network, &mut rand_core::OsRng)
+    }
+
+    /// Construct a keypair from a 32 byte mini secret key, like the secret
+    /// seed of a Substrate account.
+    pub fn from_secret(network: Network, secret: &[u8]) -> error::Result<Keypair> {
+        if secret.len() != KEYPAIR_LENGTH - 1 {
+            return Err(error::invalid_length(KEYPAIR_LENGTH - 1, secret.len()));
+        }
+        let mini = MiniSecretKey::from_bytes(secret).map_err(signature_error)?;
+        Ok(Self::from_mini_secret(network, mini))
+    }
+
+    fn from_mini_secret(network: Network, mini: MiniSecretKey) -> Keypair {
+        let keypair = mini.expand_to_keypair(ExpansionMode::Ed25519);
+        Keypair {
+            network,
+            public_key: PublicKey {
+                network,
+                inner: keypair.public,
+            },
+            inner: Secret { mini, keypair },
+        }
+    }
+
+    /// The same keypair tagged for the given network.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self.public_key.network = network;
+        self
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        self.public_key.tag()
+    }
+
+    export_secret! {
+        fn to_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
+            let mut result = [0u8; KEYPAIR_LENGTH];
+            self.try_bytes_into(&mut result)
+                .expect("keypair length");
+            result
+        }
+    }
+
+    /// Sign the given message in the given signing context.
+    pub fn sign_with_signing_context(&self, context: &[u8], msg: &[u8]) -> [u8; SIGNATURE_LENGTH] {
+        let context = schnorrkel::signing_context(context);
+        self.inner.keypair.sign(context.bytes(msg)).to_bytes()
+    }
+}
+
+impl Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        Ok(self
+            .sign_with_signing_context(SUBSTRATE_CONTEXT, msg)
+            .to_vec())
+    }
+
+    fn sign_into(&self, msg: &[u8], output: &mut [u8]) -> error::Result<usize> {
+        let signature = self.sign_with_signing_context(SUBSTRATE_CONTEXT, msg);
+        keypair::copy_signature(&signature, output)
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != KEYPAIR_LENGTH {
+            return Err(error::invalid_length(KEYPAIR_LENGTH, input.len()));
+        }
+        if KeyType::try_from(input[0])? != KeyType::Sr25519 {
+            return Err(error::invalid_keytype(input[0]));
+        }
+        Self::from_secret(Network::try_from(input[0])?, &input[1..])
+    }
+}
+
+impl IntoBytes for Keypair {
+    fn try_bytes_into(&self, output: &mut [u8]) -> error::Result<usize> {
+        let output = output_prefix(output, KEYPAIR_LENGTH)?;
+        output[0] = u8::from(self.key_tag());
+        output[1..].copy_from_slice(self.inner.mini.as_bytes());
+        Ok(KEYPAIR_LENGTH)
+    }
+}
+
+/// Keypairs never include their secret in `Debug` output.
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .field("secret", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl PublicKey {
+    pub fn tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::Sr25519,
+        }
+    }
+
+    /// Whether the given point encoding is a non-negative field element, as
+    /// every Ristretto encoding is: even and below 2^255. See
+    /// [`crate::PublicKey::quick_check`].
+    pub(crate) fn quick_check(input: &[u8]) -> bool {
+        input.len() == 32 && input[0] & 0x01 == 0 && input[31] & 0x80 == 0
+    }
+
+    /// Convert the key to its binary form.
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        let mut result = [0u8; PUBLIC_KEY_LENGTH];
+        result[0] = u8::from(self.tag());
+        result[1..].copy_from_slice(&self.inner.to_bytes());
+        result
+    }
+
+    /// Verify a signature for the given message in the given signing
+    /// context.
+    pub fn verify_with_signing_context(
+        &self,
+        context: &[u8],
+        msg: &[u8],
+        signature: &[u8],
+    ) -> error::Result {
+        let signature = schnorrkel::Signature::from_bytes(signature).map_err(signature_error)?;
+        let context = schnorrkel::signing_context(context);
+        self.inner
+            .verify(context.bytes(msg), &signature)
+            .map_err(signature_error)
+    }
+}
+
+impl From<PublicKey> for crate::PublicKey {
+    fn from(v: PublicKey) -> Self {
+        Self::for_network(v.network, v)
+    }
+}
+
+impl Verify for PublicKey {
+    /// sr25519 signatures have a single encoding, so the options do not
+    /// apply.
+    fn verify_with_options(
+        &self,
+        msg: &[u8],
+        signature: &[u8],
+        _options: &VerifyOptions,
+    ) -> error::Result {
+        self.verify_with_signing_context(SUBSTRATE_CONTEXT, msg, signature)
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != PUBLIC_KEY_LENGTH {
+            return Err(error::invalid_length(PUBLIC_KEY_LENGTH, input.len()));
+        }
+        if KeyType::try_from(input[0])? != KeyType::Sr25519 {
+            return Err(error::invalid_keytype(input[0]));
+        }
+        let inner = schnorrkel::PublicKey::from_bytes(&input[1..])
+            .map_err(|_| error::invalid_point(error::PointError::NotOnCurve))?;
+        Ok(Self {
+            network: Network::try_from(input[0])?,
+            inner,
+        })
+    }
+}
+
+impl std::str::FromStr for PublicKey {
+    type Err = error::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let data = bs58::decode(s).with_check(Some(0)).into_vec()?;
+        Self::try_from(&data[1..])
+    }
+}
+
+impl std::fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let mut data = [0u8; PUBLIC_KEY_LENGTH + 1];
+        data[1..].copy_from_slice(&self.to_bytes());
+        f.write_str(&bs58::encode(data.as_ref()).with_check().into_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keypair, PublicKey, KEYPAIR_LENGTH, SUBSTRATE_CONTEXT};
+    use crate::{KeyType, Network, Sign, Verify};
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair.public_key.verify(b"hello", &signature).is_err());
+        assert!(keypair
+            .public_key
+            .verify_with_signing_context(b"other", b"hello world", &signature)
+            .is_err());
+
+        let signature = keypair.sign_with_signing_context(b"other", b"hello world");
+        assert!(keypair
+            .public_key
+            .verify_with_signing_context(b"other", b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify_with_signing_context(SUBSTRATE_CONTEXT, b"hello world", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let bytes = keypair.to_bytes();
+        assert_eq!(KEYPAIR_LENGTH, bytes.len());
+        assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+        assert_eq!(KeyType::Sr25519, keypair.key_tag().key_type);
+
+        let public_key = keypair.public_key.to_bytes();
+        assert_eq!(
+            keypair.public_key,
+            PublicKey::try_from(&public_key[..]).expect("public key")
+        );
+        let address = keypair.public_key.to_string();
+        assert_eq!(keypair.public_key, address.parse().expect("address"));
+    }
+
+    #[test]
+    fn crate_public_key() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        let public_key = crate::PublicKey::from(keypair.public_key.clone());
+        assert_eq!(KeyType::Sr25519, public_key.tag().key_type);
+        assert_eq!(
+            &keypair.public_key.to_bytes()[..],
+            &public_key.to_bytes()[..]
+        );
+        assert_eq!(keypair.public_key.to_string(), public_key.to_string());
+        assert!(public_key.verify(b"hello world", &signature).is_ok());
+        assert!(crate::PublicKey::quick_check(&public_key.to_bytes()));
+        assert!(public_key.validate().is_ok());
+        assert_eq!(
+            public_key,
+            crate::PublicKey::validate_str(&public_key.to_string())
+                .expect("address")
+                .into_public_key()
+        );
+    }
+
+    #[test]
+    fn substrate_seed() {
+        // The well known development seed of Substrate, giving Alice's
+        // account key
+        let seed = hex!("e5be9a5092b81bca64be81d212e7f2f9eba183bb7a90954f7b76361f6edb5c0a");
+        let keypair = Keypair::from_secret(Network::MainNet, &seed).expect("keypair");
+        assert_eq!(
+            hex!("d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d"),
+            keypair.public_key.to_bytes()[1..]
+        );
+    }
+}
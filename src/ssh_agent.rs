@@ -0,0 +1,260 @@
+//! ssh-agent backed keypairs. The secret key is an ed25519 or ECDSA P-256 key
+//! held by an ssh-agent, like the OpenSSH agent or a hardware token that
+//! speaks the agent protocol, and all signing happens in the agent.
+//!
+//! ed25519 signatures made by the agent are plain ed25519 signatures over the
+//! message. ECDSA signatures are over the SHA-256 digest of the message, like
+//! ecc_compact signatures, and are converted to DER. As for
+//! [OpenSSH keys](crate::openssh), P-256 keys that are not compactable can't
+//! be used.
+use crate::{
+    error, keypair,
+    openssh::{mpint_scalar, public_blob, put_string, read_public, Reader},
+    Network, PublicKey,
+};
+use std::{
+    convert::TryFrom,
+    env,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// The environment variable with the path of the agent socket.
+pub const AUTH_SOCK: &str = "SSH_AUTH_SOCK";
+
+const FAILURE: u8 = 5;
+const REQUEST_IDENTITIES: u8 = 11;
+const IDENTITIES_ANSWER: u8 = 12;
+const SIGN_REQUEST: u8 = 13;
+const SIGN_RESPONSE: u8 = 14;
+// The limit OpenSSH puts on agent messages
+const MAX_MESSAGE_LENGTH: usize = 256 * 1024;
+
+/// A connection to an ssh-agent. Requests from different threads are
+/// serialized on the connection.
+pub struct Agent {
+    stream: Mutex<UnixStream>,
+}
+
+/// A key held by an agent.
+pub struct AgentKey {
+    agent: Arc<Agent>,
+    blob: Vec<u8>,
+}
+
+pub type Keypair = keypair::Keypair<AgentKey>;
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        let mut request = vec![SIGN_REQUEST];
+        put_string(&mut request, &self.inner.blob);
+        put_string(&mut request, msg);
+        request.extend_from_slice(&0u32.to_be_bytes());
+        let response = self.inner.agent.request(&request)?;
+        match response.split_first() {
+            Some((&SIGN_RESPONSE, signature)) => {
+                parse_signature(signature).map_err(|_| error::invalid_ssh_agent_response())
+            }
+            _ => Err(error::invalid_ssh_agent_response()),
+        }
+    }
+}
+
+impl Keypair {
+    /// The keypair for the given public key, which the agent has to hold.
+    pub fn open(agent: Arc<Agent>, public_key: &PublicKey) -> error::Result<Self> {
+        if !agent
+            .identities(public_key.network)?
+            .iter()
+            .any(|(key, _)| key == public_key)
+        {
+            return Err(error::ssh_agent_not_found(format!("key {}", public_key)));
+        }
+        Ok(Keypair {
+            network: public_key.network,
+            public_key: public_key.clone(),
+            inner: AgentKey {
+                agent,
+                blob: public_blob(public_key)?,
+            },
+        })
+    }
+}
+
+impl Agent {
+    /// Connect to the agent listening on the given socket.
+    pub fn connect<P: AsRef<Path>>(path: P) -> error::Result<Self> {
+        Ok(Self {
+            stream: Mutex::new(UnixStream::connect(path)?),
+        })
+    }
+
+    /// Connect to the agent of the current session, as given by
+    /// `SSH_AUTH_SOCK`.
+    pub fn from_env() -> error::Result<Self> {
+        let path =
+            env::var_os(AUTH_SOCK).ok_or_else(|| error::ssh_agent_not_found(AUTH_SOCK.into()))?;
+        Self::connect(path)
+    }
+
+    /// The keys held by the agent that can be used as keys for the given
+    /// network, with their comments. Keys of other types are skipped.
+    pub fn identities(&self, network: Network) -> error::Result<Vec<(PublicKey, String)>> {
+        let response = self.request(&[REQUEST_IDENTITIES])?;
+        match response.split_first() {
+            Some((&IDENTITIES_ANSWER, answer)) => {
+                parse_identities(network, answer).map_err(|_| error::invalid_ssh_agent_response())
+            }
+            _ => Err(error::invalid_ssh_agent_response()),
+        }
+    }
+
+    /// Sends a request and reads the response. A failure response becomes
+    /// `Error::SshAgentFailure`.
+    fn request(&self, request: &[u8]) -> error::Result<Vec<u8>> {
+        let mut stream = self.stream.lock().expect("ssh agent lock");
+        stream.write_all(&(request.len() as u32).to_be_bytes())?;
+        stream.write_all(request)?;
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len == 0 || len > MAX_MESSAGE_LENGTH {
+            return Err(error::invalid_ssh_agent_response());
+        }
+        let mut response = vec![0u8; len];
+        stream.read_exact(&mut response)?;
+        if response[0] == FAILURE {
+            return Err(error::ssh_agent_failure());
+        }
+        Ok(response)
+    }
+}
+
+fn parse_identities(network: Network, answer: &[u8]) -> error::Result<Vec<(PublicKey, String)>> {
+    let mut reader = Reader(answer);
+    let count = reader.u32()?;
+    let mut result = Vec::new();
+    for _ in 0..count {
+        let blob = reader.string()?;
+        let comment = reader.str()?;
+        let mut blob_reader = Reader(blob);
+        // Keys that are not ed25519 or compactable P-256 keys are skipped
+        if let Ok(public_key) = read_public(network, &mut blob_reader) {
+            if blob_reader.is_empty() {
+                result.push((public_key, comment.to_string()));
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn parse_signature(signature: &[u8]) -> error::Result<Vec<u8>> {
+    let mut reader = Reader(signature);
+    let mut blob = Reader(reader.string()?);
+    let signature = match blob.str()? {
+        "ssh-ed25519" => blob.string()?.to_vec(),
+        "ecdsa-sha2-nistp256" => {
+            let mut scalars = Reader(blob.string()?);
+            let mut raw = mpint_scalar(scalars.string()?)?.to_vec();
+            raw.extend_from_slice(&mpint_scalar(scalars.string()?)?);
+            let signature = p256::ecdsa::Signature::try_from(&raw[..])?;
+            signature.to_der().as_bytes().to_vec()
+        }
+        _ => return Err(error::invalid_ssh_agent_response()),
+    };
+    if !reader.is_empty() || !blob.is_empty() {
+        return Err(error::invalid_ssh_agent_response());
+    }
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{put_string, Agent, Keypair, IDENTITIES_ANSWER, SIGN_RESPONSE};
+    use crate::{ecc_compact, ed25519, openssh::public_blob, Network, Sign, Verify};
+    use rand::rngs::OsRng;
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixStream,
+        sync::Arc,
+        thread,
+    };
+
+    fn read(stream: &mut UnixStream) -> Vec<u8> {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len).expect("length");
+        let mut request = vec![0u8; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut request).expect("request");
+        request
+    }
+
+    fn write(stream: &mut UnixStream, response: &[u8]) {
+        stream
+            .write_all(&(response.len() as u32).to_be_bytes())
+            .expect("length");
+        stream.write_all(response).expect("response");
+    }
+
+    fn identities_answer(keypair: &ed25519::Keypair) -> Vec<u8> {
+        let mut response = vec![IDENTITIES_ANSWER];
+        response.extend_from_slice(&1u32.to_be_bytes());
+        put_string(
+            &mut response,
+            &public_blob(&keypair.public_key).expect("blob"),
+        );
+        put_string(&mut response, b"test key");
+        response
+    }
+
+    /// Answers an identities request and a sign request the way an agent
+    /// holding the given ed25519 keypair would.
+    fn serve(mut stream: UnixStream, keypair: ed25519::Keypair) {
+        assert_eq!(vec![11], read(&mut stream));
+        write(&mut stream, &identities_answer(&keypair));
+
+        let request = read(&mut stream);
+        assert_eq!(13, request[0]);
+        // The message follows the type, the 51 byte key blob and its length
+        let msg = &request[60..request.len() - 4];
+        let mut signature = vec![];
+        put_string(&mut signature, b"ssh-ed25519");
+        put_string(&mut signature, &keypair.sign(msg).expect("signature"));
+        let mut response = vec![SIGN_RESPONSE];
+        put_string(&mut response, &signature);
+        write(&mut stream, &response);
+    }
+
+    #[test]
+    fn sign() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key = keypair.public_key.clone();
+        let (client, stream) = UnixStream::pair().expect("socket pair");
+        let server = thread::spawn(move || serve(stream, keypair));
+
+        let agent = Arc::new(Agent {
+            stream: client.into(),
+        });
+        let keypair = Keypair::open(agent, &public_key).expect("agent keypair");
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(public_key.verify(b"hello world", &signature).is_ok());
+        server.join().expect("agent");
+    }
+
+    #[test]
+    fn missing_key() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let other = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let (client, mut stream) = UnixStream::pair().expect("socket pair");
+        let server = thread::spawn(move || {
+            read(&mut stream);
+            write(&mut stream, &identities_answer(&keypair));
+        });
+        let agent = Arc::new(Agent {
+            stream: client.into(),
+        });
+        assert!(Keypair::open(agent, &other.public_key).is_err());
+        server.join().expect("agent");
+    }
+}
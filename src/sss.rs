@@ -0,0 +1,373 @@
+//! Shamir secret sharing of keypairs.
+//!
+//! The binary form of a keypair, without its key tag, is split byte by byte
+//! over GF(2^8) with the AES reduction polynomial `x^8 + x^4 + x^3 + x + 1`.
+//! Any `threshold` of the generated shares reconstruct the keypair, fewer
+//! reveal nothing about it.
+//!
+//! A share is encoded as:
+//!
+//! ```text
+//! version (1) | key tag (1) | threshold (1) | index (1) | fingerprint (4) | data
+//! ```
+//!
+//! The index is the non-zero x coordinate the share was evaluated at and the
+//! fingerprint is the first four bytes of the SHA-256 of the binary public key.
+//! The fingerprint keeps shares of different keys from being combined and lets
+//! a reconstruction be checked against the key it was split from.
+//!
+//! This is a format of this crate, not SLIP-0039 or the format of any other
+//! Shamir tool, and shares can only be combined here. The field arithmetic is
+//! checked against FIPS 197 and the encoding is pinned by known answer
+//! vectors computed with an independent implementation.
+use crate::{entropy::EntropySource, error, KeyTag, Keypair, PublicKey};
+use sha2::{Digest, Sha256};
+use std::convert::{TryFrom, TryInto};
+use zeroize::Zeroize;
+
+const VERSION: u8 = 1;
+const FINGERPRINT_LENGTH: usize = 4;
+const HEADER_LENGTH: usize = 4 + FINGERPRINT_LENGTH;
+/// The maximum number of shares, one for each non-zero field element.
+pub const MAX_SHARES: u8 = 255;
+
+/// One share of a split keypair.
+#[derive(Clone)]
+pub struct Share {
+    /// The key tag of the split keypair
+    pub key_tag: KeyTag,
+    /// The number of shares needed to reconstruct the keypair
+    pub threshold: u8,
+    /// The index of this share, from 1
+    pub index: u8,
+    fingerprint: [u8; FINGERPRINT_LENGTH],
+    data: Vec<u8>,
+}
+
+impl Share {
+    /// Encode the share in its binary form.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(HEADER_LENGTH + self.data.len());
+        result.extend_from_slice(&[VERSION, u8::from(self.key_tag), self.threshold, self.index]);
+        result.extend_from_slice(&self.fingerprint);
+        result.extend_from_slice(&self.data);
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for Share {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() <= HEADER_LENGTH || input[0] != VERSION {
+            return Err(error::invalid_share());
+        }
+        let share = Share {
+            key_tag: KeyTag::try_from(input[1])?,
+            threshold: input[2],
+            index: input[3],
+            fingerprint: input[4..HEADER_LENGTH].try_into().expect("fingerprint"),
+            data: input[HEADER_LENGTH..].to_vec(),
+        };
+        if share.threshold == 0 || share.index == 0 {
+            return Err(error::invalid_share());
+        }
+        Ok(share)
+    }
+}
+
+/// Shares never include their data in `Debug` output.
+impl std::fmt::Debug for Share {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Share")
+            .field("key_tag", &self.key_tag)
+            .field("threshold", &self.threshold)
+            .field("index", &self.index)
+            .field("fingerprint", &self.fingerprint)
+            .field("data", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+/// Shares compare their secret data in constant time.
+impl PartialEq for Share {
+    fn eq(&self, other: &Self) -> bool {
+        use subtle::ConstantTimeEq;
+        self.key_tag == other.key_tag
+            && self.threshold == other.threshold
+            && self.index == other.index
+            && self.fingerprint == other.fingerprint
+            && bool::from(self.data.ct_eq(&other.data))
+    }
+}
+
+impl Zeroize for Share {
+    fn zeroize(&mut self) {
+        self.data.zeroize();
+    }
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+export_secret! {
+    /// Split the given keypair into `shares` shares, any `threshold` of which
+    /// reconstruct it. Fails with `Error::ShareThreshold` unless
+    /// `1 <= threshold <= shares`.
+    fn split<E>(
+        keypair: &Keypair,
+        threshold: u8,
+        shares: u8,
+        entropy: &mut E,
+    ) -> error::Result<Vec<Share>>
+    where
+        E: EntropySource + ?Sized,
+    {
+        if threshold == 0 || threshold > shares {
+            return Err(error::share_threshold(threshold, shares));
+        }
+        let key_tag = keypair.key_tag();
+        let fingerprint = fingerprint(keypair.public_key());
+        let mut secret = keypair.to_vec();
+        let mut result: Vec<Share> = (1..=shares)
+            .map(|index| Share {
+                key_tag,
+                threshold,
+                index,
+                fingerprint,
+                data: Vec::with_capacity(secret.len() - 1),
+            })
+            .collect();
+        // The polynomial for each byte has the secret byte as its constant term
+        // and random higher coefficients
+        let mut coefficients = vec![0u8; threshold as usize];
+        let mut fill = || -> error::Result {
+            for byte in &secret[1..] {
+                coefficients[0] = *byte;
+                entropy.fill_entropy(&mut coefficients[1..])?;
+                for share in result.iter_mut() {
+                    share.data.push(evaluate(&coefficients, share.index));
+                }
+            }
+            Ok(())
+        };
+        let filled = fill();
+        coefficients[..].zeroize();
+        secret[..].zeroize();
+        filled.map(|()| result)
+    }
+}
+
+/// Reconstruct a keypair from the given shares. Fails with
+/// `DecodeError::Share` if there are fewer shares than the threshold, the
+/// shares do not belong to the same split, or the reconstructed keypair does
+/// not match the fingerprint in the shares.
+pub fn combine(shares: &[Share]) -> error::Result<Keypair> {
+    let first = shares.first().ok_or_else(error::invalid_share)?;
+    let threshold = first.threshold as usize;
+    let shares = &shares[..threshold.min(shares.len())];
+    if shares.len() < threshold {
+        return Err(error::invalid_share());
+    }
+    for (pos, share) in shares.iter().enumerate() {
+        if share.key_tag != first.key_tag
+            || share.threshold != first.threshold
+            || share.fingerprint != first.fingerprint
+            || share.data.len() != first.data.len()
+            || shares[..pos].iter().any(|other| other.index == share.index)
+        {
+            return Err(error::invalid_share());
+        }
+    }
+    let mut secret = Vec::with_capacity(first.data.len() + 1);
+    secret.push(u8::from(first.key_tag));
+    for pos in 0..first.data.len() {
+        secret.push(interpolate(shares, pos));
+    }
+    let keypair = Keypair::try_from(&secret[..]);
+    secret[..].zeroize();
+    match keypair {
+        Ok(keypair) if fingerprint(keypair.public_key()) == first.fingerprint => Ok(keypair),
+        _ => Err(error::invalid_share()),
+    }
+}
+
+fn fingerprint(public_key: &PublicKey) -> [u8; FINGERPRINT_LENGTH] {
+    let digest = Sha256::digest(&public_key.to_vec());
+    digest[..FINGERPRINT_LENGTH]
+        .try_into()
+        .expect("fingerprint")
+}
+
+/// Evaluates the polynomial with the given coefficients, lowest degree first,
+/// at x using Horner's method.
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0, |acc, coefficient| gf_mul(acc, x) ^ coefficient)
+}
+
+/// Lagrange interpolation of the byte at the given position at x = 0.
+fn interpolate(shares: &[Share], pos: usize) -> u8 {
+    shares.iter().fold(0, |acc, share| {
+        let basis = shares
+            .iter()
+            .filter(|other| other.index != share.index)
+            .fold(1, |basis, other| {
+                // Subtraction in GF(2^8) is addition, which is xor
+                gf_mul(basis, gf_div(other.index, other.index ^ share.index))
+            });
+        acc ^ gf_mul(share.data[pos], basis)
+    })
+}
+
+/// Multiplies in a fixed eight rounds, with masks in place of branches on the
+/// operands, so the time taken does not depend on secret bytes.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut result = 0;
+    for _ in 0..8 {
+        result ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    result
+}
+
+/// Divides by a non-zero b, using b^254 as the inverse of b.
+fn gf_div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1;
+    for _ in 0..254 {
+        inverse = gf_mul(inverse, b);
+    }
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{combine, gf_div, gf_mul, split, Share};
+    use crate::{ecc_compact, ed25519, error, Keypair, Network};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn field() {
+        // The multiplication example from FIPS 197
+        assert_eq!(0xc1, gf_mul(0x57, 0x83));
+        assert_eq!(0, gf_mul(0, 0xff));
+        assert_eq!(0x9b, gf_mul(0x80, 0x03));
+        for b in 1..=255u8 {
+            assert_eq!(1, gf_mul(gf_div(1, b), b));
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let keypair =
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng));
+        let shares = split(&keypair, 3, 5, &mut OsRng).expect("split");
+        assert_eq!(5, shares.len());
+        // Any three shares will do, in any order
+        for picked in &[[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let picked: Vec<Share> = picked.iter().map(|i| shares[*i].clone()).collect();
+            assert_eq!(keypair, combine(&picked).expect("combine"));
+        }
+        assert!(combine(&shares[..2]).is_err());
+
+        let encoded: Vec<Vec<u8>> = shares.iter().map(Share::to_vec).collect();
+        let decoded: Vec<Share> = encoded
+            .iter()
+            .map(|bytes| Share::try_from(&bytes[..]).expect("share"))
+            .collect();
+        assert_eq!(shares, decoded);
+    }
+
+    #[test]
+    fn debug_redacted() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let shares = split(&keypair, 2, 3, &mut OsRng).expect("split");
+        let debug = format!("{:?}", shares[0]);
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains(&format!("{:?}", shares[0].data)));
+    }
+
+    #[test]
+    fn known_answer() {
+        use crate::entropy::EntropySource;
+        use hex_literal::hex;
+
+        /// Supplies the coefficients 0, 1, 2, ... in turn
+        struct Counter(u8);
+
+        impl EntropySource for Counter {
+            fn fill_entropy(&mut self, dest: &mut [u8]) -> error::Result {
+                for byte in dest.iter_mut() {
+                    *byte = self.0;
+                    self.0 = self.0.wrapping_add(1);
+                }
+                Ok(())
+            }
+        }
+
+        // The secret key of test 1 from RFC 8032
+        let keypair = Keypair::Ed25519(
+            ed25519::Keypair::from_secret(
+                Network::MainNet,
+                &hex!("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"),
+            )
+            .expect("keypair"),
+        );
+        let shares = split(&keypair, 2, 3, &mut Counter(0)).expect("split");
+        let expected = [
+            hex!(
+                "01010201bcd1d56b"
+                "9d60b39eebf85c67b28d40ff9ee122cb5458d77a6f277f0e6822b61800b3617f"
+                "f77bba22a6942c90fd62d4f8e54929153ed040c0ee931512973b2053cb3a6f25"
+            ),
+            hex!(
+                "01010202bcd1d56b"
+                "9d63b59be7f7566eaa965ee28af630da646be14f53184537400998352494435e"
+                "9718dc47cafb46f98519aa85913e5b646e831695b2cc4f4bdf706e1e8f7d2d64"
+            ),
+            hex!(
+                "01010203bcd1d56b"
+                "9d62b798e3f25069a29f54e986fb3ed5747af35c470d53205810822e38895d41"
+                "b739fe64eede60dead3080aebd13754b5eb224a686f9797ce7495425b340135b"
+            ),
+        ];
+        for (share, expected) in shares.iter().zip(expected.iter()) {
+            assert_eq!(&expected[..], &share.to_vec()[..]);
+        }
+        let picked = [
+            Share::try_from(&expected[2][..]).expect("share"),
+            Share::try_from(&expected[0][..]).expect("share"),
+        ];
+        assert_eq!(keypair, combine(&picked).expect("combine"));
+    }
+
+    #[test]
+    fn mixed_shares() {
+        let a = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let b = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let mut shares = split(&a, 2, 3, &mut OsRng).expect("split");
+        shares[1] = split(&b, 2, 3, &mut OsRng).expect("split").remove(1);
+        assert!(combine(&shares[..2]).is_err());
+
+        let mut duplicate = split(&a, 2, 3, &mut OsRng).expect("split");
+        duplicate[1] = duplicate[0].clone();
+        assert!(combine(&duplicate[..2]).is_err());
+    }
+
+    #[test]
+    fn invalid_threshold() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        assert!(matches!(
+            split(&keypair, 4, 3, &mut OsRng),
+            Err(error::Error::ShareThreshold(4, 3))
+        ));
+        assert!(split(&keypair, 0, 3, &mut OsRng).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+//! Incremental signing and verification of messages, like firmware images or
+//! chain snapshots, that are too large to load into memory in full.
+use crate::{
+    ecc_compact, ed25519, error,
+    public_key::{PublicKey, PublicKeyRepr, VerifyOptions},
+    secp256k1, secp256r1, Backend, Prehash, Verify,
+};
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// The default chunk size used to read messages when verifying.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Verifies a signature over a message that is fed in incrementally through
+/// `update`.
+pub struct IncrementalVerifier {
+    state: VerifierState,
+    signature: Vec<u8>,
+}
+
+enum VerifierState {
+    EccCompact(ecc_compact::PublicKey, Sha256),
+    Ed25519(ed25519::PublicKey, Sha512),
+    Secp256k1(secp256k1::PublicKey, Sha256),
+    Secp256r1(secp256r1::PublicKey, Sha256),
+}
+
+impl IncrementalVerifier {
+    /// Start verifying the given signature using the given public key.
+    pub fn new(public_key: &PublicKey, signature: &[u8]) -> error::Result<Self> {
+        let state = match &public_key.inner {
+            PublicKeyRepr::EccCompact(key) => VerifierState::EccCompact(key.clone(), Sha256::new()),
+            PublicKeyRepr::Ed25519(key) => {
+                VerifierState::Ed25519(key.clone(), key.verify_hasher(signature)?)
+            }
+            PublicKeyRepr::Secp256k1(key) => VerifierState::Secp256k1(key.clone(), Sha256::new()),
+            PublicKeyRepr::Secp256r1(key) => VerifierState::Secp256r1(key.clone(), Sha256::new()),
+            PublicKeyRepr::Schnorr(_) | PublicKeyRepr::Other(..) => {
+                return Err(error::unsupported_keytype(public_key.tag().key_type))
+            }
+            #[cfg(feature = "sr25519")]
+            PublicKeyRepr::Sr25519(_) => {
+                return Err(error::unsupported_keytype(public_key.tag().key_type))
+            }
+        };
+        Ok(Self {
+            state,
+            signature: signature.to_vec(),
+        })
+    }
+
+    /// Add the next part of the message.
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            VerifierState::EccCompact(_, hasher) => hasher.update(data),
+            VerifierState::Ed25519(_, hasher) => hasher.update(data),
+            VerifierState::Secp256k1(_, hasher) => hasher.update(data),
+            VerifierState::Secp256r1(_, hasher) => hasher.update(data),
+        }
+    }
+
+    /// Check the signature against all message data added so far.
+    pub fn finalize(self) -> error::Result {
+        match self.state {
+            VerifierState::EccCompact(key, hasher) => {
+                key.verify_sha256(hasher, &self.signature, &VerifyOptions::DEFAULT)
+            }
+            VerifierState::Ed25519(key, hasher) => {
+                key.verify_hashed(&self.signature, hasher, &VerifyOptions::DEFAULT)
+            }
+            VerifierState::Secp256k1(key, hasher) => {
+                key.verify_sha256(hasher, &self.signature, &VerifyOptions::DEFAULT)
+            }
+            VerifierState::Secp256r1(key, hasher) => key.verify_digest_with_options(
+                Prehash::Sha256(hasher),
+                &self.signature,
+                &VerifyOptions::DEFAULT,
+            ),
+        }
+    }
+}
+
+/// Signs everything written to it, hashing data as it comes in so the message
+/// is never held in memory. The signature is produced with
+/// [`Sign::sign_digest`](crate::Sign::sign_digest), which for ed25519 keys
+/// is an Ed25519ph signature that has to be checked with a [`VerifyingWriter`]
+/// or [`Verify::verify_digest`]. ECDSA signatures are regular signatures.
+pub struct SigningWriter<'a, B: Backend + ?Sized> {
+    signer: &'a B,
+    digest: Prehash,
+}
+
+impl<'a, B: Backend + ?Sized> SigningWriter<'a, B> {
+    /// Start signing with the given signer.
+    pub fn new(signer: &'a B) -> error::Result<Self> {
+        let digest = Prehash::new(signer.public_key().tag().key_type)?;
+        Ok(Self { signer, digest })
+    }
+
+    /// Sign all data written so far.
+    pub fn finalize(self) -> error::Result<Vec<u8>> {
+        self.signer.sign_digest(self.digest)
+    }
+}
+
+impl<'a, B: Backend + ?Sized> Write for SigningWriter<'a, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.digest.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Checks a signature made by a [`SigningWriter`] against everything written
+/// to it.
+pub struct VerifyingWriter {
+    public_key: PublicKey,
+    digest: Prehash,
+    signature: Vec<u8>,
+}
+
+impl VerifyingWriter {
+    /// Start verifying the given signature using the given public key.
+    pub fn new(public_key: &PublicKey, signature: &[u8]) -> error::Result<Self> {
+        Ok(Self {
+            public_key: public_key.clone(),
+            digest: Prehash::new(public_key.tag().key_type)?,
+            signature: signature.to_vec(),
+        })
+    }
+
+    /// Check the signature against all data written so far.
+    pub fn finalize(self) -> error::Result {
+        self.public_key.verify_digest(self.digest, &self.signature)
+    }
+}
+
+impl Write for VerifyingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.digest.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Verify the given signature over the full contents of the given reader,
+/// reading `chunk_size` bytes at a time. The progress callback is called with
+/// the total number of bytes read after each chunk.
+pub fn verify_reader<R, F>(
+    public_key: &PublicKey,
+    mut reader: R,
+    signature: &[u8],
+    chunk_size: usize,
+    mut progress: F,
+) -> error::Result
+where
+    R: Read,
+    F: FnMut(u64),
+{
+    let mut verifier = IncrementalVerifier::new(public_key, signature)?;
+    let mut buf = vec![0u8; chunk_size.max(1)];
+    let mut total = 0u64;
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        verifier.update(&buf[..read]);
+        total += read as u64;
+        progress(total);
+    }
+    verifier.finalize()
+}
+
+/// Verify the given signature over the contents of the file at the given
+/// path. See `verify_reader`.
+pub fn verify_file<P, F>(
+    public_key: &PublicKey,
+    path: P,
+    signature: &[u8],
+    chunk_size: usize,
+    progress: F,
+) -> error::Result
+where
+    P: AsRef<Path>,
+    F: FnMut(u64),
+{
+    let file = File::open(path)?;
+    verify_reader(public_key, file, signature, chunk_size, progress)
+}
+
+/// Sign the full contents of the given async reader without buffering it.
+///
+/// Only ecc_compact keypairs can sign streamed messages since ed25519 needs to
+/// hash the message twice to produce a signature.
+#[cfg(feature = "tokio")]
+pub async fn sign_async_reader<R>(
+    keypair: &ecc_compact::Keypair,
+    mut reader: R,
+) -> error::Result<Vec<u8>>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; DEFAULT_CHUNK_SIZE];
+    loop {
+        match reader.read(&mut buf).await? {
+            0 => break,
+            read => hasher.update(&buf[..read]),
+        }
+    }
+    keypair.sign_sha256(hasher)
+}
+
+/// Verify the given signature over the full contents of the given async
+/// reader without buffering it.
+#[cfg(feature = "tokio")]
+pub async fn verify_async_reader<R>(
+    public_key: &PublicKey,
+    mut reader: R,
+    signature: &[u8],
+) -> error::Result
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+    let mut verifier = IncrementalVerifier::new(public_key, signature)?;
+    let mut buf = vec![0u8; DEFAULT_CHUNK_SIZE];
+    loop {
+        match reader.read(&mut buf).await? {
+            0 => break,
+            read => verifier.update(&buf[..read]),
+        }
+    }
+    verifier.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_reader, SigningWriter, VerifyingWriter};
+    use crate::{ecc_compact, ed25519, Network, Sign};
+    use rand::{rngs::OsRng, RngCore};
+    use std::io::Cursor;
+
+    fn roundtrip<S: Sign>(keypair: &S, public_key: &crate::PublicKey) {
+        let mut msg = vec![0u8; 100_000];
+        OsRng.fill_bytes(&mut msg);
+        let signature = keypair.sign(&msg).expect("signature");
+        let mut read = 0;
+        assert!(
+            verify_reader(public_key, Cursor::new(&msg), &signature, 4096, |total| {
+                read = total
+            })
+            .is_ok()
+        );
+        assert_eq!(msg.len() as u64, read);
+        msg[50_000] ^= 0xff;
+        assert!(verify_reader(public_key, Cursor::new(&msg), &signature, 4096, |_| ()).is_err());
+    }
+
+    #[test]
+    fn ecc_compact_roundtrip() {
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        roundtrip(&keypair, &keypair.public_key);
+    }
+
+    #[test]
+    fn ed25519_roundtrip() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        roundtrip(&keypair, &keypair.public_key);
+    }
+
+    #[test]
+    fn writer_roundtrip() {
+        use crate::{KeyTag, KeyType, Keypair, Verify};
+        let mut msg = vec![0u8; 100_000];
+        OsRng.fill_bytes(&mut msg);
+        for key_type in &[KeyType::EccCompact, KeyType::Ed25519, KeyType::Secp256k1] {
+            let tag = KeyTag {
+                network: Network::MainNet,
+                key_type: *key_type,
+            };
+            let keypair = Keypair::generate(tag, &mut OsRng).expect("keypair");
+            let mut signer = SigningWriter::new(&keypair).expect("signer");
+            std::io::copy(&mut Cursor::new(&msg), &mut signer).expect("copy");
+            let signature = signer.finalize().expect("signature");
+
+            let mut verifier =
+                VerifyingWriter::new(keypair.public_key(), &signature).expect("verifier");
+            std::io::copy(&mut Cursor::new(&msg), &mut verifier).expect("copy");
+            assert!(verifier.finalize().is_ok());
+
+            let mut verifier =
+                VerifyingWriter::new(keypair.public_key(), &signature).expect("verifier");
+            std::io::copy(&mut Cursor::new(&msg[1..]), &mut verifier).expect("copy");
+            assert!(verifier.finalize().is_err());
+
+            if *key_type != KeyType::Ed25519 {
+                assert!(keypair.public_key().verify(&msg, &signature).is_ok());
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_reader_roundtrip() {
+        use super::{sign_async_reader, verify_async_reader};
+        use crate::Verify;
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let mut msg = vec![0u8; 200_000];
+        OsRng.fill_bytes(&mut msg);
+        let signature = sign_async_reader(&keypair, &msg[..])
+            .await
+            .expect("signature");
+        assert!(keypair.public_key.verify(&msg, &signature).is_ok());
+        assert!(
+            verify_async_reader(&keypair.public_key, &msg[..], &signature)
+                .await
+                .is_ok()
+        );
+    }
+}
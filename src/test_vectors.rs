@@ -0,0 +1,159 @@
+//! Known answer test vectors for every signing key type, for validating other
+//! implementations against this one and the erlang `libp2p_crypto` library.
+//!
+//! Each vector gives the secret seed of a key, the binary form of its keypair
+//! as written by both libraries, its b58 address and a signature over
+//! [`MESSAGE`]. ed25519 signatures and BIP340 signatures, made with an all
+//! zero auxiliary input, are deterministic and have to match exactly. ECDSA
+//! signatures are randomized, so implementations should check that the given
+//! signature verifies and that their own signatures over the message verify
+//! against the address. ECDSA signatures are DER encoded with a low s. The
+//! erlang library has no BIP340 keys, so that vector only applies to other
+//! implementations.
+//!
+//! The seeds are SHA-256 digests of `helium-crypto test vector <key type>`,
+//! with a counter byte appended for ECDSA keys until the public key has an
+//! even y coordinate, which makes it compactable under either convention.
+//!
+//! [`to_json`] renders the vectors with hex encoded binary fields for
+//! projects that can not link against this crate.
+use crate::{KeyTag, KeyType, Network};
+
+/// A known answer test vector.
+#[derive(Debug, Clone, Copy)]
+pub struct TestVector {
+    pub key_tag: KeyTag,
+    /// The secret key
+    pub seed: [u8; 32],
+    /// The binary form of the keypair
+    pub keypair: &'static [u8],
+    /// The b58 address of the public key
+    pub address: &'static str,
+    /// A signature over [`MESSAGE`]
+    pub signature: &'static [u8],
+}
+
+/// The message signed in every vector.
+pub const MESSAGE: &[u8] = b"hello world";
+
+pub const VECTORS: &[TestVector] = &[
+    TestVector {
+        key_tag: KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::Ed25519,
+        },
+        seed: hex_literal::hex!("3916f0035ae5a0695f75ccf66ff7091149902488b7539195c86f971954d2a0f2"),
+        keypair: &hex_literal::hex!(
+            "01"
+            "3916f0035ae5a0695f75ccf66ff7091149902488b7539195c86f971954d2a0f2"
+            "35461f397b152ac43ea83ebda722e843153fcac3cd79a5300569230df8db3b0f"
+        ),
+        address: "13MCzFQoVdWMuHiLpt7dg9GaujXXkrczp62r4nmpZ3FnWS5w1FL",
+        signature: &hex_literal::hex!(
+            "7b1030c5495b3ad0ca391355c5233f48f7f6ecbe1bf238eb8992467029f7f217"
+            "fc147deddf714cb8a7511ddb11ed713220cec1e5c3ae99e37c5a89dc048c1300"
+        ),
+    },
+    TestVector {
+        key_tag: KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccCompact,
+        },
+        seed: hex_literal::hex!("f5734ef074a98c18bbcaf628bae19bfd68faaec69ef8fce6f6029b9fecc6db6b"),
+        keypair: &hex_literal::hex!(
+            "00"
+            "f5734ef074a98c18bbcaf628bae19bfd68faaec69ef8fce6f6029b9fecc6db6b"
+        ),
+        address: "112cJ6kNd8dNBun3mhLAGftzdNErG12oRuf5Uf79Dhh92behsBbE",
+        signature: &hex_literal::hex!(
+            "304402203321671301b30a2c2e99ee22725ae7362053139edc617d7c3b5eace5"
+            "061c71b6022004575daad7f6ec34ec02e14ea9eefd3c4b39e66cf2b8c54e07df"
+            "fd250dd71fd3"
+        ),
+    },
+    TestVector {
+        key_tag: KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::Secp256k1,
+        },
+        seed: hex_literal::hex!("8162a523b187a7e1af8cc84fb156755093ee5c4aba73454b0cb3c8d81256a9db"),
+        keypair: &hex_literal::hex!(
+            "04"
+            "8162a523b187a7e1af8cc84fb156755093ee5c4aba73454b0cb3c8d81256a9db"
+        ),
+        address: "1bPZ1CgmsEvUjRgB6U3vU4Ckx2XXHFes1oGiD2wxdi24UmF5VA1a",
+        signature: &hex_literal::hex!(
+            "3045022100f0717703701b360024920b3978fa165ddc70870757dcd3da2dc0db"
+            "a65bbaf4a002203070ab5c05015cd7812af75b22b0a54ff36be74eb534336c93"
+            "827f8b190ed66d"
+        ),
+    },
+    TestVector {
+        key_tag: KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::Schnorr,
+        },
+        seed: hex_literal::hex!("3e36da4930e07b060ed723bf37296bcdcdff1351a73cc57d0ce79178129b48eb"),
+        keypair: &hex_literal::hex!(
+            "05"
+            "3e36da4930e07b060ed723bf37296bcdcdff1351a73cc57d0ce79178129b48eb"
+        ),
+        address: "1CbNbjXC47shPxVU21LyJMA8uDrzFiDX25t13TM1hQFvGhu5HQp",
+        signature: &hex_literal::hex!(
+            "f9ec92d60f9e1f4bebf37685a75de97ff88d449452cc48ae74c8ba3ca7562ec4"
+            "b0300776b0a1677e80a8ecc8db9b055b9d4d562d2621325a2c96989cd5c1d6a6"
+        ),
+    },
+];
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The vectors as a JSON array of objects with `key_tag`, `seed`, `keypair`,
+/// `address`, `message` and `signature` fields. Binary fields are hex
+/// encoded.
+pub fn to_json() -> String {
+    let entries: Vec<String> = VECTORS
+        .iter()
+        .map(|vector| {
+            format!(
+                r#"{{"key_tag":"{}","seed":"{}","keypair":"{}","address":"{}","message":"{}","signature":"{}"}}"#,
+                vector.key_tag,
+                hex(&vector.seed),
+                hex(vector.keypair),
+                vector.address,
+                hex(MESSAGE),
+                hex(vector.signature)
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_json, MESSAGE, VECTORS};
+    use crate::{Keypair, PublicKey, Sign, Verify};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn vectors() {
+        for vector in VECTORS {
+            let keypair = Keypair::try_from(vector.keypair).expect("keypair");
+            assert_eq!(vector.key_tag, keypair.key_tag());
+            assert_eq!(vector.keypair, &keypair.to_vec()[..]);
+            assert_eq!(vector.seed, vector.keypair[1..33]);
+            assert_eq!(vector.address, keypair.public_key().to_string());
+            let public_key: PublicKey = vector.address.parse().expect("public key");
+            assert!(public_key.verify(MESSAGE, vector.signature).is_ok());
+            let signature = keypair.sign(MESSAGE).expect("signature");
+            assert!(public_key.verify(MESSAGE, &signature).is_ok());
+            if matches!(keypair, Keypair::Ed25519(_) | Keypair::Schnorr(_)) {
+                assert_eq!(vector.signature, &signature[..]);
+            }
+        }
+        let json: serde_json::Value = serde_json::from_str(&to_json()).expect("json");
+        assert_eq!(VECTORS.len(), json.as_array().expect("array").len());
+    }
+}
@@ -0,0 +1,137 @@
+//! TLS client authentication with a Helium identity key through [rustls].
+//!
+//! [`SigningKey`] implements [`rustls::sign::SigningKey`] over any
+//! [`Backend`], including secure elements and remote signers, so a gateway
+//! can do mutual TLS to a service with the same key it uses on chain. Only
+//! key types with a TLS signature scheme are supported: ecc_compact keys sign
+//! with `ecdsa_secp256r1_sha256` and ed25519 keys with `ed25519`.
+//!
+//! The certificate for the key is not made here. Pair the signing key with
+//! the certificate chain issued for its public key in a
+//! [`rustls::sign::CertifiedKey`].
+//!
+//! [rustls]: https://docs.rs/rustls
+use crate::{error, Backend, KeyType, Sign};
+use rustls::{SignatureAlgorithm, SignatureScheme};
+use std::sync::Arc;
+
+/// A rustls signing key backed by a keypair.
+#[derive(Clone)]
+pub struct SigningKey {
+    backend: Arc<dyn Backend>,
+    scheme: SignatureScheme,
+}
+
+impl SigningKey {
+    /// Wrap the given keypair. Fails for key types that have no TLS signature
+    /// scheme.
+    pub fn new<B: Backend + 'static>(backend: B) -> error::Result<Self> {
+        Self::from_arc(Arc::new(backend))
+    }
+
+    /// Wrap a shared keypair.
+    pub fn from_arc(backend: Arc<dyn Backend>) -> error::Result<Self> {
+        let key_type = backend.public_key().tag().key_type;
+        let scheme = match key_type {
+            KeyType::EccCompact => SignatureScheme::ECDSA_NISTP256_SHA256,
+            KeyType::Ed25519 => SignatureScheme::ED25519,
+            _ => return Err(error::unsupported_keytype(key_type)),
+        };
+        Ok(Self { backend, scheme })
+    }
+
+    /// The signature scheme signatures are made with.
+    pub fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("public_key", self.backend.public_key())
+            .field("scheme", &self.scheme)
+            .finish()
+    }
+}
+
+impl rustls::sign::SigningKey for SigningKey {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn rustls::sign::Signer>> {
+        if offered.contains(&self.scheme) {
+            Some(Box::new(self.clone()))
+        } else {
+            None
+        }
+    }
+
+    fn algorithm(&self) -> SignatureAlgorithm {
+        match self.scheme {
+            SignatureScheme::ED25519 => SignatureAlgorithm::ED25519,
+            _ => SignatureAlgorithm::ECDSA,
+        }
+    }
+}
+
+/// The TLS signature schemes hash the message with SHA-256 for P-256 and not
+/// at all for ed25519, which is what the keypairs do when signing already.
+impl rustls::sign::Signer for SigningKey {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
+        self.backend
+            .sign(message)
+            .map_err(|err| rustls::Error::General(err.to_string()))
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SigningKey;
+    use crate::{KeyTag, KeyType, Keypair, Network, Verify};
+    use rand::rngs::OsRng;
+    use rustls::{sign::SigningKey as _, SignatureAlgorithm, SignatureScheme};
+
+    fn keypair(key_type: KeyType) -> Keypair {
+        Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type,
+            },
+            &mut OsRng,
+        )
+        .expect("keypair")
+    }
+
+    #[test]
+    fn sign() {
+        for (key_type, scheme, algorithm) in [
+            (
+                KeyType::EccCompact,
+                SignatureScheme::ECDSA_NISTP256_SHA256,
+                SignatureAlgorithm::ECDSA,
+            ),
+            (
+                KeyType::Ed25519,
+                SignatureScheme::ED25519,
+                SignatureAlgorithm::ED25519,
+            ),
+        ] {
+            let keypair = keypair(key_type);
+            let public_key = keypair.public_key().clone();
+            let key = SigningKey::new(keypair).expect("signing key");
+            assert_eq!(algorithm, key.algorithm());
+            assert!(key
+                .choose_scheme(&[SignatureScheme::RSA_PSS_SHA256])
+                .is_none());
+            let signer = key
+                .choose_scheme(&[SignatureScheme::RSA_PSS_SHA256, scheme])
+                .expect("signer");
+            assert_eq!(scheme, signer.scheme());
+            let signature = signer.sign(b"hello world").expect("signature");
+            assert!(public_key.verify(b"hello world", &signature).is_ok());
+        }
+        assert!(SigningKey::new(keypair(KeyType::Secp256k1)).is_err());
+    }
+}
@@ -0,0 +1,472 @@
+//! TPM 2.0 backed keypairs. The secret key is a P-256 signing key stored under
+//! a persistent handle in the owner hierarchy of a TPM, reached through a TSS2
+//! TCTI like `device:/dev/tpmrm0`. The key never leaves the TPM and all signing
+//! happens on it.
+//!
+//! Keys are ecc_compact keys when their public key is compactable and
+//! secp256r1 keys otherwise. Key creation is retried with a fresh template
+//! until the TPM produces a compactable key.
+//!
+//! Existing ecc_compact keypairs can be moved into a TPM with
+//! [`Keypair::import`]. Imported keys can't have the fixed TPM attribute, since
+//! the TPM did not generate them, so attestations of them don't prove that the
+//! key never left the TPM.
+#[cfg(feature = "attestation")]
+use crate::attestation::Attestation;
+use crate::{ecc_compact, error, keypair, public_key, KeyType, Network};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::{
+    convert::{TryFrom, TryInto},
+    str::FromStr,
+    sync::Mutex,
+};
+use tss_esapi::{
+    attributes::ObjectAttributesBuilder,
+    constants::tss::{TPM2_RH_NULL, TPM2_ST_HASHCHECK},
+    handles::{KeyHandle, ObjectHandle, PersistentTpmHandle, TpmHandle},
+    interface_types::{
+        algorithm::{HashingAlgorithm, PublicAlgorithm},
+        dynamic_handles::Persistent,
+        ecc::EccCurve,
+        key_bits::RsaKeyBits,
+        resource_handles::{Hierarchy, Provision},
+    },
+    structures::{
+        EccParameter, EccPoint, EccScheme, EncryptedSecret, HashScheme, HashcheckTicket,
+        KeyDerivationFunctionScheme, Private, Public, PublicBuilder, PublicEccParametersBuilder,
+        RsaExponent, Signature, SignatureScheme, SymmetricDefinitionObject,
+    },
+    tss2_esys::TPMT_TK_HASHCHECK,
+    Context, TctiNameConf,
+};
+use zeroize::Zeroize;
+
+/// The default TCTI, the kernel's resource manager.
+pub const DEFAULT_TCTI: &str = "device:/dev/tpmrm0";
+
+/// Number of key creation attempts before giving up on finding a compactable
+/// key.
+const MAX_CREATE_ATTEMPTS: usize = 64;
+
+/// Size of a P-256 coordinate or signature scalar.
+const SCALAR_LENGTH: usize = 32;
+
+/// The TPM algorithm identifier of ECC keys.
+const TPM_ALG_ECC: u16 = 0x0023;
+
+/// A TPM context and the handle of the key to sign with.
+pub struct Tpm {
+    context: Mutex<Context>,
+    handle: KeyHandle,
+}
+
+pub type Keypair = keypair::Keypair<Tpm>;
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> error::Result<Vec<u8>> {
+        let digest = Sha256::digest(msg).to_vec().try_into()?;
+        // The key is not restricted, so the TPM does not need a ticket proving
+        // it hashed the message itself
+        let validation = HashcheckTicket::try_from(TPMT_TK_HASHCHECK {
+            tag: TPM2_ST_HASHCHECK,
+            hierarchy: TPM2_RH_NULL,
+            digest: Default::default(),
+        })?;
+        let mut context = self.inner.context.lock().expect("tpm lock");
+        let signature = context.execute_with_nullauth_session(|ctx| {
+            ctx.sign(self.inner.handle, digest, SignatureScheme::Null, validation)
+        })?;
+        let signature = p256::ecdsa::Signature::try_from(&raw_signature(signature)?[..])?;
+        // The TPM may return a high s
+        let signature = ecc_compact::normalize_s(signature)?;
+        Ok(signature.to_der().as_bytes().to_vec())
+    }
+}
+
+impl Keypair {
+    /// Open the key stored under the given persistent handle, like
+    /// `0x81000001`, on the TPM reached through the given TCTI and read its
+    /// public key.
+    pub fn open(network: Network, tcti: &str, handle: u32) -> error::Result<Self> {
+        let mut context = connect(tcti)?;
+        let handle =
+            context.tr_from_tpm_public(TpmHandle::Persistent(PersistentTpmHandle::new(handle)?))?;
+        let handle = KeyHandle::from(handle);
+        let public_key = read_public_key(network, &mut context, handle)?;
+        Ok(Self::from_parts(public_key, context, handle))
+    }
+
+    /// Create a new P-256 signing key in the owner hierarchy of the TPM
+    /// reached through the given TCTI and persist it under the given handle.
+    /// The handle has to be free; an existing persistent key is not replaced.
+    pub fn create(network: Network, tcti: &str, handle: u32) -> error::Result<Self> {
+        let mut context = connect(tcti)?;
+        let persistent = PersistentTpmHandle::new(handle)?;
+        for _ in 0..MAX_CREATE_ATTEMPTS {
+            let template = signing_key_template()?;
+            let transient = context
+                .execute_with_nullauth_session(|ctx| {
+                    ctx.create_primary(Hierarchy::Owner, template, None, None, None, None)
+                })?
+                .key_handle;
+            match read_public_key(network, &mut context, transient) {
+                Ok(public_key) if public_key.tag().key_type == KeyType::EccCompact => {
+                    let stored = context.execute_with_nullauth_session(|ctx| {
+                        ctx.evict_control(
+                            Provision::Owner,
+                            ObjectHandle::from(transient),
+                            Persistent::Persistent(persistent),
+                        )
+                    });
+                    context.flush_context(ObjectHandle::from(transient))?;
+                    let handle = KeyHandle::from(stored?);
+                    return Ok(Self::from_parts(public_key, context, handle));
+                }
+                Ok(_) => context.flush_context(ObjectHandle::from(transient))?,
+                Err(err) => {
+                    context.flush_context(ObjectHandle::from(transient))?;
+                    return Err(err);
+                }
+            }
+        }
+        Err(error::not_compact())
+    }
+
+    /// Import the given keypair into the owner hierarchy of the TPM reached
+    /// through the given TCTI and persist it under the given handle, which has
+    /// to be free. The keypair keeps its network.
+    ///
+    /// The key is imported in the clear under a transient storage key derived
+    /// from the owner hierarchy seed, so the secret key passes through the
+    /// TCTI unencrypted. Only import over a local TCTI.
+    pub fn import(keypair: &ecc_compact::Keypair, tcti: &str, handle: u32) -> error::Result<Self> {
+        let mut context = connect(tcti)?;
+        let persistent = PersistentTpmHandle::new(handle)?;
+        let public = imported_key_template(&keypair.public_key)?;
+        let mut sensitive = sensitive_ecc(&keypair.inner.to_bytes());
+        let duplicate = Private::try_from(sensitive.clone());
+        sensitive.zeroize();
+        let duplicate = duplicate?;
+        let parent = context
+            .execute_with_nullauth_session(|ctx| {
+                ctx.create_primary(
+                    Hierarchy::Owner,
+                    tss_esapi::utils::create_restricted_decryption_rsa_public(
+                        SymmetricDefinitionObject::AES_128_CFB,
+                        RsaKeyBits::Rsa2048,
+                        RsaExponent::default(),
+                    )?,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+            })?
+            .key_handle;
+        let stored = context.execute_with_nullauth_session(|ctx| {
+            let private = ctx.import(
+                ObjectHandle::from(parent),
+                None,
+                public.clone(),
+                duplicate,
+                EncryptedSecret::default(),
+                SymmetricDefinitionObject::Null,
+            )?;
+            let transient = ctx.load(parent, private, public)?;
+            let stored = ctx.evict_control(
+                Provision::Owner,
+                ObjectHandle::from(transient),
+                Persistent::Persistent(persistent),
+            );
+            ctx.flush_context(ObjectHandle::from(transient))?;
+            stored
+        });
+        context.flush_context(ObjectHandle::from(parent))?;
+        let key_handle = KeyHandle::from(stored?);
+        let public_key = read_public_key(keypair.network, &mut context, key_handle)?;
+        if keypair.public_key != public_key {
+            return Err(error::device_key_mismatch(&format!(
+                "tpm handle {:#x}",
+                handle
+            )));
+        }
+        Ok(Self::from_parts(public_key, context, key_handle))
+    }
+
+    /// Certify the key with the attestation key stored under the given
+    /// persistent handle, over the given challenge. The attestation key has to
+    /// be a P-256 signing key in the same TPM.
+    #[cfg(feature = "attestation")]
+    pub fn attest(&self, attestation_handle: u32, challenge: &[u8]) -> error::Result<Attestation> {
+        use tss_esapi::{
+            interface_types::session_handles::AuthSession, structures::Data, traits::Marshall,
+        };
+        let mut context = self.inner.context.lock().expect("tpm lock");
+        let signer = context.tr_from_tpm_public(TpmHandle::Persistent(
+            PersistentTpmHandle::new(attestation_handle)?,
+        ))?;
+        let qualifying_data = Data::try_from(Sha256::digest(challenge).to_vec())?;
+        // Both the certified key and the attestation key need authorization
+        let (attest, signature) = context.execute_with_sessions(
+            (
+                Some(AuthSession::Password),
+                Some(AuthSession::Password),
+                None,
+            ),
+            |ctx| {
+                ctx.certify(
+                    ObjectHandle::from(self.inner.handle),
+                    KeyHandle::from(signer),
+                    qualifying_data,
+                    SignatureScheme::Null,
+                )
+            },
+        )?;
+        let (public, _, _) = context.read_public(self.inner.handle)?;
+        Ok(Attestation::Tpm {
+            public: public.marshall()?,
+            attest: attest.marshall()?,
+            signature: raw_signature(signature)?,
+        })
+    }
+
+    /// The public key of the attestation key stored under the given
+    /// persistent handle, for verifiers to enroll.
+    #[cfg(feature = "attestation")]
+    pub fn attestation_key(&self, attestation_handle: u32) -> error::Result<p256::PublicKey> {
+        let mut context = self.inner.context.lock().expect("tpm lock");
+        let handle = context.tr_from_tpm_public(TpmHandle::Persistent(
+            PersistentTpmHandle::new(attestation_handle)?,
+        ))?;
+        let point = read_point(&mut context, KeyHandle::from(handle))?;
+        p256::PublicKey::from_sec1_bytes(&point).map_err(error::Error::from)
+    }
+
+    fn from_parts(public_key: public_key::PublicKey, context: Context, handle: KeyHandle) -> Self {
+        Keypair {
+            network: public_key.network,
+            public_key,
+            inner: Tpm {
+                context: Mutex::new(context),
+                handle,
+            },
+        }
+    }
+}
+
+fn connect(tcti: &str) -> error::Result<Context> {
+    Ok(Context::new(TctiNameConf::from_str(tcti)?)?)
+}
+
+/// The template for a non-exportable, unrestricted ECDSA P-256 signing key.
+/// Primary keys are derived from the hierarchy seed and the template, so the
+/// unique field is filled with random data to get a different key from each
+/// attempt.
+fn signing_key_template() -> error::Result<Public> {
+    let attributes = ObjectAttributesBuilder::new()
+        .with_fixed_tpm(true)
+        .with_fixed_parent(true)
+        .with_sensitive_data_origin(true)
+        .with_user_with_auth(true)
+        .with_sign_encrypt(true)
+        .build()?;
+    let parameters = PublicEccParametersBuilder::new()
+        .with_ecc_scheme(EccScheme::EcDsa(HashScheme::new(HashingAlgorithm::Sha256)))
+        .with_curve(EccCurve::NistP256)
+        .with_is_signing_key(true)
+        .with_is_decryption_key(false)
+        .with_restricted(false)
+        .with_key_derivation_function_scheme(KeyDerivationFunctionScheme::Null)
+        .build()?;
+    let mut unique = [0u8; 2 * SCALAR_LENGTH];
+    OsRng.fill_bytes(&mut unique);
+    let (x, y) = unique.split_at(SCALAR_LENGTH);
+    Ok(PublicBuilder::new()
+        .with_public_algorithm(PublicAlgorithm::Ecc)
+        .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+        .with_object_attributes(attributes)
+        .with_ecc_parameters(parameters)
+        .with_ecc_unique_identifier(EccPoint::new(
+            EccParameter::try_from(x)?,
+            EccParameter::try_from(y)?,
+        ))
+        .build()?)
+}
+
+/// The template for an imported ECDSA P-256 signing key with the given
+/// public key. Keys that were not generated by the TPM can't be fixed to it or
+/// their parent.
+fn imported_key_template(public_key: &public_key::PublicKey) -> error::Result<Public> {
+    let attributes = ObjectAttributesBuilder::new()
+        .with_user_with_auth(true)
+        .with_sign_encrypt(true)
+        .build()?;
+    let parameters = PublicEccParametersBuilder::new()
+        .with_ecc_scheme(EccScheme::EcDsa(HashScheme::new(HashingAlgorithm::Sha256)))
+        .with_curve(EccCurve::NistP256)
+        .with_is_signing_key(true)
+        .with_is_decryption_key(false)
+        .with_restricted(false)
+        .with_key_derivation_function_scheme(KeyDerivationFunctionScheme::Null)
+        .build()?;
+    let point = public_key.to_sec1_bytes(false)?;
+    let (x, y) = point[1..].split_at(SCALAR_LENGTH);
+    Ok(PublicBuilder::new()
+        .with_public_algorithm(PublicAlgorithm::Ecc)
+        .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+        .with_object_attributes(attributes)
+        .with_ecc_parameters(parameters)
+        .with_ecc_unique_identifier(EccPoint::new(
+            EccParameter::try_from(x)?,
+            EccParameter::try_from(y)?,
+        ))
+        .build()?)
+}
+
+/// The marshalled sensitive area of an ECC key with the given secret scalar,
+/// with an empty auth value and seed. Without inner or outer wrapping this is
+/// what TPM2_Import takes as the duplicate.
+fn sensitive_ecc(secret: &[u8]) -> Vec<u8> {
+    let mut area = TPM_ALG_ECC.to_be_bytes().to_vec();
+    // Empty auth value and seed
+    area.extend_from_slice(&[0, 0, 0, 0]);
+    area.extend_from_slice(&(secret.len() as u16).to_be_bytes());
+    area.extend_from_slice(secret);
+    let mut result = (area.len() as u16).to_be_bytes().to_vec();
+    result.extend_from_slice(&area);
+    area.zeroize();
+    result
+}
+
+fn read_public_key(
+    network: Network,
+    context: &mut Context,
+    handle: KeyHandle,
+) -> error::Result<public_key::PublicKey> {
+    public_key::PublicKey::from_p256_sec1_bytes(network, &read_point(context, handle)?)
+}
+
+/// The uncompressed SEC1 form of the public key of an ECC key.
+fn read_point(
+    context: &mut Context,
+    handle: KeyHandle,
+) -> error::Result<[u8; ecc_compact::SEC1_UNCOMPRESSED_LENGTH]> {
+    let (public, _, _) = context.read_public(handle)?;
+    public_point(public)
+}
+
+/// The uncompressed SEC1 form of the public key in the public area of an ECC
+/// key.
+fn public_point(public: Public) -> error::Result<[u8; ecc_compact::SEC1_UNCOMPRESSED_LENGTH]> {
+    let point = match public {
+        Public::Ecc { unique, .. } => unique,
+        _ => return Err(error::invalid_tpm_response()),
+    };
+    let mut sec1 = [0u8; ecc_compact::SEC1_UNCOMPRESSED_LENGTH];
+    sec1[0] = 0x04;
+    let (x, y) = sec1[1..].split_at_mut(SCALAR_LENGTH);
+    left_pad(point.x().value(), x)?;
+    left_pad(point.y().value(), y)?;
+    Ok(sec1)
+}
+
+/// The raw r and s values of an ECDSA signature made by the TPM.
+fn raw_signature(signature: Signature) -> error::Result<[u8; 2 * SCALAR_LENGTH]> {
+    let signature = match signature {
+        Signature::EcDsa(signature) => signature,
+        _ => return Err(error::invalid_tpm_response()),
+    };
+    let mut result = [0u8; 2 * SCALAR_LENGTH];
+    let (r, s) = result.split_at_mut(SCALAR_LENGTH);
+    left_pad(signature.signature_r().value(), r)?;
+    left_pad(signature.signature_s().value(), s)?;
+    Ok(result)
+}
+
+/// The TPM strips leading zeros from coordinates and scalars.
+fn left_pad(value: &[u8], output: &mut [u8]) -> error::Result {
+    if value.len() > output.len() {
+        return Err(error::invalid_tpm_response());
+    }
+    let start = output.len() - value.len();
+    output[start..].copy_from_slice(value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        imported_key_template, left_pad, public_point, raw_signature, sensitive_ecc, SCALAR_LENGTH,
+    };
+    use crate::{ecc_compact, error::Error, Network};
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+    use tss_esapi::{
+        interface_types::algorithm::HashingAlgorithm,
+        structures::{EccParameter, EccSignature, Signature},
+    };
+
+    fn ecc_signature(r: &[u8], s: &[u8]) -> EccSignature {
+        EccSignature::create(
+            HashingAlgorithm::Sha256,
+            EccParameter::try_from(r).expect("r"),
+            EccParameter::try_from(s).expect("s"),
+        )
+        .expect("signature")
+    }
+
+    #[test]
+    fn left_pads() {
+        let mut output = [0xffu8; 4];
+        left_pad(&[1, 2], &mut output).expect("padded");
+        assert_eq!([0, 0, 1, 2], output);
+        left_pad(&[1, 2, 3, 4], &mut output).expect("full");
+        assert_eq!([1, 2, 3, 4], output);
+        left_pad(&[], &mut output).expect("empty");
+        assert_eq!([0, 0, 0, 0], output);
+        assert!(matches!(
+            left_pad(&[1, 2, 3, 4, 5], &mut output),
+            Err(Error::InvalidTpmResponse)
+        ));
+    }
+
+    #[test]
+    fn raw_signatures() {
+        // The TPM strips the leading zeros of r
+        let raw = raw_signature(Signature::EcDsa(ecc_signature(&[7; 31], &[9; 32])))
+            .expect("raw signature");
+        let (r, s) = raw.split_at(SCALAR_LENGTH);
+        assert_eq!(0, r[0]);
+        assert_eq!(&[7; 31][..], &r[1..]);
+        assert_eq!(&[9; 32][..], s);
+        assert!(matches!(
+            raw_signature(Signature::EcSchnorr(ecc_signature(&[7; 32], &[9; 32]))),
+            Err(Error::InvalidTpmResponse)
+        ));
+    }
+
+    #[test]
+    fn public_points() {
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let template = imported_key_template(&keypair.public_key).expect("template");
+        let point = public_point(template).expect("point");
+        assert_eq!(
+            keypair.public_key,
+            crate::PublicKey::from_p256_sec1_bytes(Network::MainNet, &point).expect("key")
+        );
+    }
+
+    #[test]
+    fn sensitive_area() {
+        let secret = [5u8; SCALAR_LENGTH];
+        let area = sensitive_ecc(&secret);
+        // The size of the area, the algorithm, the empty auth value and seed
+        // and the sized secret
+        assert_eq!(
+            &[0x00, 0x28, 0x00, 0x23, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20][..],
+            &area[..10]
+        );
+        assert_eq!(&secret[..], &area[10..]);
+    }
+}
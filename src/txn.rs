@@ -0,0 +1,170 @@
+//! Signing and verification of Helium blockchain transactions from
+//! [helium-proto].
+//!
+//! Every party to a transaction signs the same payload: the protobuf encoding
+//! of the transaction with all of its signature fields cleared. Each party's
+//! signature then goes into the signature field for its [`Role`], next to the
+//! field holding its binary public key. [`sign`] checks that the keypair is
+//! the one named for the role before filling in the signature, so a
+//! transaction is never signed by the wrong key or into the wrong field.
+//!
+//! Optional signers, like the payer of an add gateway transaction, only sign
+//! when their key field is set.
+//!
+//! [helium-proto]: https://github.com/helium/proto
+use crate::{error, Keypair, PublicKey, Sign, Verify};
+use helium_proto::{
+    BlockchainTxnAddGatewayV1, BlockchainTxnAssertLocationV1, BlockchainTxnAssertLocationV2,
+    BlockchainTxnPaymentV1, BlockchainTxnPaymentV2, BlockchainTxnStakeValidatorV1,
+    BlockchainTxnTokenBurnV1, BlockchainTxnTransferHotspotV1, BlockchainTxnTransferHotspotV2,
+};
+use prost::Message;
+use std::convert::TryFrom;
+
+/// The parties that sign transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Owner,
+    Gateway,
+    Payer,
+    Seller,
+    Buyer,
+}
+
+/// A transaction with signature fields.
+pub trait Signable: Message + Clone {
+    /// The public key and signature of the signer with the given role, if
+    /// the transaction has that signer.
+    fn signer(&self, role: Role) -> Option<(&[u8], &[u8])>;
+
+    /// The signature field for the given role.
+    fn signature_mut(&mut self, role: Role) -> Option<&mut Vec<u8>>;
+
+    /// Clear every signature field.
+    fn clear_signatures(&mut self);
+}
+
+macro_rules! signable {
+    ($txn:ty { $($role:ident => $key:ident, $signature:ident),+ $(,)? }) => {
+        impl Signable for $txn {
+            fn signer(&self, role: Role) -> Option<(&[u8], &[u8])> {
+                match role {
+                    $(Role::$role if !self.$key.is_empty() => {
+                        Some((self.$key.as_slice(), self.$signature.as_slice()))
+                    })+
+                    _ => None,
+                }
+            }
+
+            fn signature_mut(&mut self, role: Role) -> Option<&mut Vec<u8>> {
+                match role {
+                    $(Role::$role if !self.$key.is_empty() => Some(&mut self.$signature),)+
+                    _ => None,
+                }
+            }
+
+            fn clear_signatures(&mut self) {
+                $(self.$signature.clear();)+
+            }
+        }
+    };
+}
+
+signable!(BlockchainTxnPaymentV1 { Payer => payer, signature });
+signable!(BlockchainTxnPaymentV2 { Payer => payer, signature });
+signable!(BlockchainTxnTokenBurnV1 { Payer => payer, signature });
+signable!(BlockchainTxnAddGatewayV1 {
+    Owner => owner, owner_signature,
+    Gateway => gateway, gateway_signature,
+    Payer => payer, payer_signature,
+});
+signable!(BlockchainTxnAssertLocationV1 {
+    Owner => owner, owner_signature,
+    Gateway => gateway, gateway_signature,
+    Payer => payer, payer_signature,
+});
+signable!(BlockchainTxnAssertLocationV2 {
+    Owner => owner, owner_signature,
+    Payer => payer, payer_signature,
+});
+signable!(BlockchainTxnTransferHotspotV1 {
+    Seller => seller, seller_signature,
+    Buyer => buyer, buyer_signature,
+});
+signable!(BlockchainTxnTransferHotspotV2 { Owner => owner, owner_signature });
+signable!(BlockchainTxnStakeValidatorV1 { Owner => owner, owner_signature });
+
+/// The payload every signer of the given transaction signs.
+pub fn signing_payload<T: Signable>(txn: &T) -> Vec<u8> {
+    let mut txn = txn.clone();
+    txn.clear_signatures();
+    txn.encode_to_vec()
+}
+
+/// Sign the given transaction as the given role. The keypair has to be the
+/// one named in the transaction for the role.
+pub fn sign<T: Signable>(txn: &mut T, role: Role, keypair: &Keypair) -> error::Result {
+    let (key, _) = txn.signer(role).ok_or_else(error::txn_signer)?;
+    if key != &keypair.public_key().to_vec()[..] {
+        return Err(error::txn_signer());
+    }
+    let signature = keypair.sign(&signing_payload(txn))?;
+    *txn.signature_mut(role).ok_or_else(error::txn_signer)? = signature;
+    Ok(())
+}
+
+/// Verify the signature of the given role against the key named for it in
+/// the transaction.
+pub fn verify<T: Signable>(txn: &T, role: Role) -> error::Result {
+    let (key, signature) = txn.signer(role).ok_or_else(error::txn_signer)?;
+    PublicKey::try_from(key)?.verify(&signing_payload(txn), signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, signing_payload, verify, Role};
+    use crate::{ecc_compact, ed25519, Keypair, Network};
+    use helium_proto::{BlockchainTxnAddGatewayV1, BlockchainTxnPaymentV1};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn payment() {
+        let payer = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let payee = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let mut txn = BlockchainTxnPaymentV1 {
+            payer: payer.public_key().to_vec(),
+            payee: payee.public_key().to_vec(),
+            amount: 10,
+            nonce: 1,
+            ..Default::default()
+        };
+        let payload = signing_payload(&txn);
+        assert!(sign(&mut txn, Role::Payer, &payee).is_err());
+        sign(&mut txn, Role::Payer, &payer).expect("signed");
+        assert!(!txn.signature.is_empty());
+        assert_eq!(payload, signing_payload(&txn));
+        assert!(verify(&txn, Role::Payer).is_ok());
+        assert!(verify(&txn, Role::Owner).is_err());
+
+        txn.amount = 11;
+        assert!(verify(&txn, Role::Payer).is_err());
+    }
+
+    #[test]
+    fn add_gateway() {
+        let owner = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let gateway =
+            Keypair::EccCompact(ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng));
+        let mut txn = BlockchainTxnAddGatewayV1 {
+            owner: owner.public_key().to_vec(),
+            gateway: gateway.public_key().to_vec(),
+            ..Default::default()
+        };
+        sign(&mut txn, Role::Gateway, &gateway).expect("gateway signed");
+        sign(&mut txn, Role::Owner, &owner).expect("owner signed");
+        // Without a payer the owner pays and there is no payer signature
+        assert!(sign(&mut txn, Role::Payer, &owner).is_err());
+        assert!(verify(&txn, Role::Gateway).is_ok());
+        assert!(verify(&txn, Role::Owner).is_ok());
+    }
+}
@@ -0,0 +1,181 @@
+//! Signatures of any of the supported key types.
+//!
+//! Signatures are otherwise passed around as bytes in the form
+//! [`Sign::sign`](crate::Sign::sign) produces them. [`Signature`] holds the
+//! parsed signature for a key type instead, so it can only be verified
+//! against keys of that type and converts between the DER and raw `r || s`
+//! forms of ECDSA signatures.
+//!
+//! ed25519 and BIP340 Schnorr signatures only have a fixed size form, which
+//! is both their DER and raw form here.
+use crate::{
+    ecc_compact, ed25519, error, keypair, schnorr, secp256k1, KeyTag, KeyType, PublicKey, Verify,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Length of the raw form of signatures of every supported key type.
+pub const RAW_SIGNATURE_LENGTH: usize = 64;
+
+/// A signature of any of the supported key types.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Signature {
+    EccCompact(ecc_compact::Signature),
+    Ed25519(ed25519::Signature),
+    Secp256k1(secp256k1::Signature),
+    Schnorr([u8; schnorr::SIGNATURE_LENGTH]),
+}
+
+impl Signature {
+    /// Parse a signature for a key with the given tag from either its DER or
+    /// its raw form. ECDSA signatures are parsed as DER first, and as raw
+    /// only if that fails.
+    pub fn from_bytes(tag: KeyTag, input: &[u8]) -> error::Result<Self> {
+        match tag.key_type {
+            KeyType::EccCompact => ecc_compact::Signature::from_der(input)
+                .or_else(|err| match input.len() {
+                    RAW_SIGNATURE_LENGTH => ecc_compact::Signature::from_raw(input),
+                    _ => Err(err),
+                })
+                .map(Self::EccCompact),
+            KeyType::Secp256k1 => secp256k1::Signature::from_der(input)
+                .or_else(|err| match input.len() {
+                    RAW_SIGNATURE_LENGTH => secp256k1::Signature::from_raw(input),
+                    _ => Err(err),
+                })
+                .map(Self::Secp256k1),
+            _ => Self::from_raw(tag, input),
+        }
+    }
+
+    /// Parse a DER signature for a key with the given tag.
+    pub fn from_der(tag: KeyTag, input: &[u8]) -> error::Result<Self> {
+        match tag.key_type {
+            KeyType::EccCompact => ecc_compact::Signature::from_der(input).map(Self::EccCompact),
+            KeyType::Secp256k1 => secp256k1::Signature::from_der(input).map(Self::Secp256k1),
+            _ => Self::from_raw(tag, input),
+        }
+    }
+
+    /// Parse a raw signature for a key with the given tag.
+    pub fn from_raw(tag: KeyTag, input: &[u8]) -> error::Result<Self> {
+        match tag.key_type {
+            KeyType::EccCompact => ecc_compact::Signature::from_raw(input).map(Self::EccCompact),
+            KeyType::Secp256k1 => secp256k1::Signature::from_raw(input).map(Self::Secp256k1),
+            KeyType::Ed25519 => ed25519::Signature::try_from(input).map(Self::Ed25519),
+            KeyType::Schnorr => {
+                let signature = <[u8; schnorr::SIGNATURE_LENGTH]>::try_from(input)
+                    .map_err(|_| error::invalid_length(schnorr::SIGNATURE_LENGTH, input.len()))?;
+                Ok(Self::Schnorr(signature))
+            }
+            key_type => Err(error::unsupported_keytype(key_type)),
+        }
+    }
+
+    /// The key type of the key this signature is for.
+    pub fn key_type(&self) -> KeyType {
+        match self {
+            Self::EccCompact(_) => KeyType::EccCompact,
+            Self::Ed25519(_) => KeyType::Ed25519,
+            Self::Secp256k1(_) => KeyType::Secp256k1,
+            Self::Schnorr(_) => KeyType::Schnorr,
+        }
+    }
+
+    /// The DER form of the signature, which is the form
+    /// [`Sign::sign`](crate::Sign::sign) produces.
+    #[cfg(feature = "alloc")]
+    pub fn to_der(&self) -> Vec<u8> {
+        match self {
+            Self::EccCompact(signature) => signature.to_der(),
+            Self::Secp256k1(signature) => signature.to_der(),
+            Self::Ed25519(signature) => signature.as_ref().to_vec(),
+            Self::Schnorr(signature) => signature.to_vec(),
+        }
+    }
+
+    /// The raw form of the signature.
+    pub fn to_raw(&self) -> [u8; RAW_SIGNATURE_LENGTH] {
+        match self {
+            Self::EccCompact(signature) => signature.to_raw(),
+            Self::Secp256k1(signature) => signature.to_raw(),
+            Self::Ed25519(signature) => {
+                let mut result = [0u8; RAW_SIGNATURE_LENGTH];
+                result.copy_from_slice(signature.as_ref());
+                result
+            }
+            Self::Schnorr(signature) => *signature,
+        }
+    }
+
+    /// Verify the signature for the given message against the given public
+    /// key, which has to have the key type of the signature.
+    pub fn verify(&self, public_key: &PublicKey, msg: &[u8]) -> error::Result {
+        let key_type = public_key.tag().key_type;
+        if key_type != self.key_type() {
+            return Err(error::signature_type_mismatch(key_type, self.key_type()));
+        }
+        let mut der = [0u8; crate::MAX_SIGNATURE_LENGTH];
+        let len = match self {
+            Self::EccCompact(signature) => signature.der_into(&mut der)?,
+            Self::Secp256k1(signature) => signature.der_into(&mut der)?,
+            Self::Ed25519(signature) => keypair::copy_signature(signature.as_ref(), &mut der)?,
+            Self::Schnorr(signature) => keypair::copy_signature(signature, &mut der)?,
+        };
+        public_key.verify(msg, &der[..len])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Signature;
+    use crate::{KeyTag, KeyType, Keypair, Network, Sign};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn roundtrip() {
+        for key_type in &[
+            KeyType::EccCompact,
+            KeyType::Ed25519,
+            KeyType::Secp256k1,
+            KeyType::Schnorr,
+        ] {
+            let tag = KeyTag {
+                network: Network::MainNet,
+                key_type: *key_type,
+            };
+            let keypair = Keypair::generate(tag, &mut OsRng).expect("keypair");
+            let bytes = keypair.sign(b"hello world").expect("signature");
+            let signature = Signature::from_bytes(tag, &bytes).expect("parsed signature");
+            assert_eq!(*key_type, signature.key_type());
+            assert_eq!(bytes, signature.to_der());
+            assert_eq!(
+                signature,
+                Signature::from_bytes(tag, &signature.to_raw()).expect("raw signature")
+            );
+            assert_eq!(
+                signature,
+                Signature::from_der(tag, &signature.to_der()).expect("der signature")
+            );
+            assert!(signature
+                .verify(keypair.public_key(), b"hello world")
+                .is_ok());
+            assert!(signature.verify(keypair.public_key(), b"hello").is_err());
+        }
+
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng).expect("keypair");
+        let other = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        )
+        .expect("keypair");
+        let signature =
+            Signature::from_bytes(KeyTag::default(), &keypair.sign(b"hello").expect("bytes"))
+                .expect("signature");
+        assert!(signature.verify(other.public_key(), b"hello").is_err());
+    }
+}
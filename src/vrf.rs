@@ -0,0 +1,288 @@
+//! Verifiable random functions over ed25519 keys, for proof of coverage and
+//! leader election schemes that need randomness tied to a hotspot identity.
+//!
+//! This implements ECVRF-EDWARDS25519-SHA512-TAI from RFC 9381. Proving over
+//! an input gives a 64 byte [`Output`] that looks random to anyone without
+//! the secret key, and a [`Proof`] with which anyone holding the public key
+//! can check that the output is the only one the key has for that input.
+//! Only ed25519 keys are supported.
+use crate::{ed25519, error, public_key::PublicKeyRepr, Keypair, PublicKey};
+use core::convert::{TryFrom, TryInto};
+use curve25519_dalek::{
+    constants::ED25519_BASEPOINT_TABLE,
+    edwards::{CompressedEdwardsY, EdwardsPoint},
+    scalar::Scalar,
+};
+use sha2::{Digest, Sha512};
+use zeroize::Zeroize;
+
+/// The RFC 9381 suite string of ECVRF-EDWARDS25519-SHA512-TAI.
+const SUITE: u8 = 0x03;
+const CHALLENGE_LENGTH: usize = 16;
+
+/// The length of a proof.
+pub const PROOF_LENGTH: usize = 80;
+
+/// The length of an output.
+pub const OUTPUT_LENGTH: usize = 64;
+
+/// A proof that an output belongs to a key and input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proof {
+    gamma: EdwardsPoint,
+    c: Scalar,
+    s: Scalar,
+}
+
+/// The pseudorandom output for a key and input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Output([u8; OUTPUT_LENGTH]);
+
+impl Output {
+    pub fn as_bytes(&self) -> &[u8; OUTPUT_LENGTH] {
+        &self.0
+    }
+}
+
+impl Proof {
+    /// The output the proof is for.
+    pub fn to_output(&self) -> Output {
+        let hash = Sha512::new()
+            .chain(&[SUITE, 0x03])
+            .chain(self.gamma.mul_by_cofactor().compress().as_bytes())
+            .chain(&[0x00])
+            .finalize();
+        let mut result = [0u8; OUTPUT_LENGTH];
+        result.copy_from_slice(&hash);
+        Output(result)
+    }
+
+    /// The 80 byte form of the proof, the gamma point followed by the
+    /// truncated challenge and the response.
+    pub fn to_bytes(&self) -> [u8; PROOF_LENGTH] {
+        let mut result = [0u8; PROOF_LENGTH];
+        result[..32].copy_from_slice(self.gamma.compress().as_bytes());
+        result[32..48].copy_from_slice(&self.c.as_bytes()[..CHALLENGE_LENGTH]);
+        result[48..].copy_from_slice(self.s.as_bytes());
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for Proof {
+    type Error = error::Error;
+
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() != PROOF_LENGTH {
+            return Err(error::invalid_length(PROOF_LENGTH, input.len()));
+        }
+        let gamma_bytes: [u8; 32] = input[..32].try_into().expect("gamma");
+        let gamma = CompressedEdwardsY(gamma_bytes)
+            .decompress()
+            .filter(|gamma| gamma.compress().as_bytes() == &gamma_bytes)
+            .ok_or_else(error::invalid_vrf_proof)?;
+        let mut c = [0u8; 32];
+        c[..CHALLENGE_LENGTH].copy_from_slice(&input[32..48]);
+        let s = Scalar::from_canonical_bytes(input[48..].try_into().expect("response"))
+            .ok_or_else(error::invalid_vrf_proof)?;
+        Ok(Proof {
+            gamma,
+            c: Scalar::from_bytes_mod_order(c),
+            s,
+        })
+    }
+}
+
+/// Hash the public key and input to a point of the prime order subgroup
+/// with the try and increment method.
+fn encode_to_curve(public_key: &[u8], alpha: &[u8]) -> error::Result<EdwardsPoint> {
+    for counter in 0..=255u8 {
+        let hash = Sha512::new()
+            .chain(&[SUITE, 0x01])
+            .chain(public_key)
+            .chain(alpha)
+            .chain(&[counter, 0x00])
+            .finalize();
+        let mut candidate = [0u8; 32];
+        candidate.copy_from_slice(&hash[..32]);
+        if let Some(point) = CompressedEdwardsY(candidate).decompress() {
+            return Ok(point.mul_by_cofactor());
+        }
+    }
+    Err(signature::Error::new().into())
+}
+
+fn challenge(points: &[&EdwardsPoint]) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.update(&[SUITE, 0x02]);
+    for point in points {
+        hasher.update(point.compress().as_bytes());
+    }
+    hasher.update(&[0x00]);
+    let hash = hasher.finalize();
+    let mut c = [0u8; 32];
+    c[..CHALLENGE_LENGTH].copy_from_slice(&hash[..CHALLENGE_LENGTH]);
+    Scalar::from_bytes_mod_order(c)
+}
+
+impl ed25519::Keypair {
+    /// Prove over the given input, returning the proof and the output.
+    pub fn vrf_prove(&self, alpha: &[u8]) -> error::Result<(Proof, Output)> {
+        let mut hash = Sha512::digest(self.inner.secret.as_bytes());
+        let mut secret = [0u8; 32];
+        secret.copy_from_slice(&hash[..32]);
+        secret[0] &= 248;
+        secret[31] &= 127;
+        secret[31] |= 64;
+        let mut x = Scalar::from_bytes_mod_order(secret);
+        secret.zeroize();
+
+        let public_key = self.inner.public.as_bytes();
+        let h = encode_to_curve(public_key, alpha)?;
+        let gamma = h * x;
+        let mut k = Scalar::from_hash(
+            Sha512::new()
+                .chain(&hash[32..])
+                .chain(h.compress().as_bytes()),
+        );
+        hash[..].zeroize();
+        let c = challenge(&[
+            &(&x * &ED25519_BASEPOINT_TABLE),
+            &h,
+            &gamma,
+            &(&k * &ED25519_BASEPOINT_TABLE),
+            &(h * k),
+        ]);
+        let s = k + c * x;
+        k.zeroize();
+        x.zeroize();
+        let proof = Proof { gamma, c, s };
+        let output = proof.to_output();
+        Ok((proof, output))
+    }
+}
+
+impl Keypair {
+    /// Prove over the given input, returning the proof and the output. Only
+    /// ed25519 keypairs are supported.
+    pub fn vrf_prove(&self, alpha: &[u8]) -> error::Result<(Proof, Output)> {
+        match self {
+            Self::Ed25519(keypair) => keypair.vrf_prove(alpha),
+            _ => Err(error::unsupported_keytype(self.key_tag().key_type)),
+        }
+    }
+}
+
+impl PublicKey {
+    /// Verify a proof over the given input, returning the output it proves.
+    /// Only ed25519 keys are supported, and keys of small order are rejected.
+    pub fn vrf_verify(&self, alpha: &[u8], proof: &Proof) -> error::Result<Output> {
+        let public_key = match &self.inner {
+            PublicKeyRepr::Ed25519(public_key) => public_key.as_ref(),
+            _ => return Err(error::unsupported_keytype(self.tag().key_type)),
+        };
+        let y = CompressedEdwardsY::from_slice(public_key)
+            .decompress()
+            .ok_or_else(signature::Error::new)?;
+        if y.is_small_order() {
+            return Err(signature::Error::new().into());
+        }
+        let h = encode_to_curve(public_key, alpha)?;
+        let u = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-proof.c, &y, &proof.s);
+        let v = h * proof.s - proof.gamma * proof.c;
+        if challenge(&[&y, &h, &proof.gamma, &u, &v]) != proof.c {
+            return Err(signature::Error::new().into());
+        }
+        Ok(proof.to_output())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Proof;
+    use crate::{ed25519, secp256k1, Keypair, Network};
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn rfc9381_vectors() {
+        // Examples 16 to 18 of RFC 9381
+        let vectors: [(&[u8], &[u8], &[u8], &[u8]); 3] = [
+            (
+                &hex!("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"),
+                &[],
+                &hex!(
+                    "8657106690b5526245a92b003bb079ccd1a92130477671f6fc01ad16f26f723f"
+                    "26f8a57ccaed74ee1b190bed1f479d9727d2d0f9b005a6e456a35d4fb0daab12"
+                    "68a1b0db10836d9826a528ca76567805"
+                ),
+                &hex!(
+                    "90cf1df3b703cce59e2a35b925d411164068269d7b2d29f3301c03dd757876ff"
+                    "66b71dda49d2de59d03450451af026798e8f81cd2e333de5cdf4f3e140fdd8ae"
+                ),
+            ),
+            (
+                &hex!("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb"),
+                &hex!("72"),
+                &hex!(
+                    "f3141cd382dc42909d19ec5110469e4feae18300e94f304590abdced48aed593"
+                    "3bf0864a62558b3ed7f2fea45c92a465301b3bbf5e3e54ddf2d935be3b67926d"
+                    "a3ef39226bbc355bdc9850112c8f4b02"
+                ),
+                &hex!(
+                    "eb4440665d3891d668e7e0fcaf587f1b4bd7fbfe99d0eb2211ccec90496310eb"
+                    "5e33821bc613efb94db5e5b54c70a848a0bef4553a41befc57663b56373a5031"
+                ),
+            ),
+            (
+                &hex!("c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7"),
+                &hex!("af82"),
+                &hex!(
+                    "9bc0f79119cc5604bf02d23b4caede71393cedfbb191434dd016d30177ccbf80"
+                    "96bb474e53895c362d8628ee9f9ea3c0e52c7a5c691b6c18c9979866568add7a"
+                    "2d41b00b05081ed0f58ee5e31b3a970e"
+                ),
+                &hex!(
+                    "645427e5d00c62a23fb703732fa5d892940935942101e456ecca7bb217c61c45"
+                    "2118fec1219202a0edcf038bb6373241578be7217ba85a2687f7a0310b2df19f"
+                ),
+            ),
+        ];
+        for (secret, alpha, pi, beta) in vectors.iter() {
+            let keypair = ed25519::Keypair::from_secret(Network::MainNet, secret).expect("keypair");
+            let (proof, output) = keypair.vrf_prove(alpha).expect("proof");
+            assert_eq!(*pi, &proof.to_bytes()[..]);
+            assert_eq!(*beta, &output.as_bytes()[..]);
+            let decoded = Proof::try_from(*pi).expect("proof");
+            assert_eq!(
+                output,
+                keypair
+                    .public_key
+                    .vrf_verify(alpha, &decoded)
+                    .expect("output")
+            );
+        }
+    }
+
+    #[test]
+    fn verify_rejects() {
+        let keypair = Keypair::Ed25519(ed25519::Keypair::generate(Network::MainNet, &mut OsRng));
+        let (proof, _) = keypair.vrf_prove(b"round 1").expect("proof");
+        let public_key = keypair.public_key();
+        assert!(public_key.vrf_verify(b"round 1", &proof).is_ok());
+        assert!(public_key.vrf_verify(b"round 2", &proof).is_err());
+        let other = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        assert!(other.public_key.vrf_verify(b"round 1", &proof).is_err());
+
+        let mut tampered = proof.to_bytes();
+        tampered[40] ^= 1;
+        let tampered = Proof::try_from(&tampered[..]).expect("proof");
+        assert!(public_key.vrf_verify(b"round 1", &tampered).is_err());
+        assert!(Proof::try_from(&proof.to_bytes()[1..]).is_err());
+
+        let keypair =
+            Keypair::Secp256k1(secp256k1::Keypair::generate(Network::MainNet, &mut OsRng));
+        assert!(keypair.vrf_prove(b"round 1").is_err());
+        assert!(keypair.public_key().vrf_verify(b"round 1", &proof).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! A multi-account wallet over the [`hd`](crate::hd) derivation subsystem.
+//!
+//! A wallet derives any number of ed25519 accounts from a single backed up
+//! seed and keeps track of which accounts have been issued. The public part of
+//! the wallet, the network and the issued accounts with their public keys, can
+//! be serialized on its own so services can hand out and look up addresses
+//! without having the seed at hand.
+use crate::{
+    ed25519, error,
+    hd::{DerivationPath, ExtendedSecret},
+    public_key, KeyTag, KeyType, Network, Sign,
+};
+use std::convert::TryFrom;
+
+/// Minimum seed length in bytes, as for BIP32.
+pub const MIN_SEED_LENGTH: usize = 16;
+/// Maximum seed length in bytes, as for BIP32.
+pub const MAX_SEED_LENGTH: usize = 64;
+
+const STATE_VERSION: u8 = 1;
+const ACCOUNT_LENGTH: usize = 4 + public_key::PUBLIC_KEY_LENGTH;
+
+/// An issued account.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Account {
+    /// The account index in the derivation path
+    pub index: u32,
+    /// The public key for the account
+    pub public_key: public_key::PublicKey,
+}
+
+/// The public state of a wallet.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WalletState {
+    /// The network accounts are issued for
+    pub network: Network,
+    /// The issued accounts, in order of issue
+    pub accounts: Vec<Account>,
+}
+
+#[derive(Debug)]
+pub struct Wallet {
+    master: ExtendedSecret,
+    state: WalletState,
+}
+
+impl Wallet {
+    /// Create a wallet with no issued accounts for the given seed.
+    pub fn from_seed(network: Network, seed: &[u8]) -> error::Result<Self> {
+        Self::with_state(
+            seed,
+            WalletState {
+                network,
+                accounts: vec![],
+            },
+        )
+    }
+
+    /// Restore a wallet from its seed and previously saved public state. The
+    /// public keys in the state are checked against the ones derived from the
+    /// seed to catch a state that belongs to a different seed.
+    pub fn with_state(seed: &[u8], state: WalletState) -> error::Result<Self> {
+        if seed.len() < MIN_SEED_LENGTH || seed.len() > MAX_SEED_LENGTH {
+            return Err(error::invalid_seed_length(seed.len()));
+        }
+        let wallet = Self {
+            master: ExtendedSecret::from_seed(seed),
+            state,
+        };
+        for account in &wallet.state.accounts {
+            if wallet.keypair(account.index)?.public_key != account.public_key {
+                return Err(error::wallet_mismatch(account.index));
+            }
+        }
+        Ok(wallet)
+    }
+
+    /// The public state of this wallet.
+    pub fn state(&self) -> &WalletState {
+        &self.state
+    }
+
+    /// The network this wallet issues accounts for.
+    pub fn network(&self) -> Network {
+        self.state.network
+    }
+
+    /// Issue the next unused account.
+    pub fn issue_account(&mut self) -> error::Result<&Account> {
+        let index = self
+            .state
+            .accounts
+            .iter()
+            .map(|account| account.index + 1)
+            .max()
+            .unwrap_or(0);
+        self.issue(index)
+    }
+
+    /// Issue the account with the given index if it has not been issued
+    /// already, and return it.
+    pub fn issue(&mut self, index: u32) -> error::Result<&Account> {
+        let position = match self.position(index) {
+            Some(position) => position,
+            None => {
+                let public_key = self.keypair(index)?.public_key;
+                self.state.accounts.push(Account { index, public_key });
+                self.state.accounts.len() - 1
+            }
+        };
+        Ok(&self.state.accounts[position])
+    }
+
+    /// Look up an issued account by its public key.
+    pub fn find(&self, public_key: &public_key::PublicKey) -> Option<&Account> {
+        self.state
+            .accounts
+            .iter()
+            .find(|account| &account.public_key == public_key)
+    }
+
+    /// Derive the keypair for the account with the given index. The account
+    /// does not need to have been issued.
+    pub fn keypair(&self, index: u32) -> error::Result<ed25519::Keypair> {
+        let path = DerivationPath::account(index)?;
+        Ok(self.master.derive(&path).to_keypair(self.state.network))
+    }
+
+    /// Sign the given message with the account with the given index.
+    pub fn sign(&self, index: u32, msg: &[u8]) -> error::Result<Vec<u8>> {
+        self.keypair(index)?.sign(msg)
+    }
+
+    fn position(&self, index: u32) -> Option<usize> {
+        self.state
+            .accounts
+            .iter()
+            .position(|account| account.index == index)
+    }
+}
+
+impl WalletState {
+    /// Serialize the public wallet state. The format is a version byte, the
+    /// network tag byte, a big endian u32 account count and then for each
+    /// account its big endian u32 index followed by its binary public key.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(6 + self.accounts.len() * ACCOUNT_LENGTH);
+        result.push(STATE_VERSION);
+        result.push(u8::from(self.network));
+        result.extend_from_slice(&(self.accounts.len() as u32).to_be_bytes());
+        for account in &self.accounts {
+            result.extend_from_slice(&account.index.to_be_bytes());
+            result.extend_from_slice(&account.public_key.to_bytes());
+        }
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for WalletState {
+    type Error = error::Error;
+    fn try_from(input: &[u8]) -> error::Result<Self> {
+        if input.len() < 6 || input[0] != STATE_VERSION {
+            return Err(error::invalid_wallet_state());
+        }
+        let network = Network::try_from(input[1])?;
+        let mut count = [0u8; 4];
+        count.copy_from_slice(&input[2..6]);
+        let count = u32::from_be_bytes(count) as usize;
+        let accounts = &input[6..];
+        // The count is read from the input, so the length it implies can
+        // overflow on 32 bit targets
+        if count.checked_mul(ACCOUNT_LENGTH) != Some(accounts.len()) {
+            return Err(error::invalid_wallet_state());
+        }
+        let accounts = accounts
+            .chunks(ACCOUNT_LENGTH)
+            .map(|account| {
+                let mut index = [0u8; 4];
+                index.copy_from_slice(&account[..4]);
+                let public_key = public_key::PublicKey::try_from(&account[4..])?;
+                if public_key.tag()
+                    != (KeyTag {
+                        network,
+                        key_type: KeyType::Ed25519,
+                    })
+                {
+                    return Err(error::invalid_wallet_state());
+                }
+                Ok(Account {
+                    index: u32::from_be_bytes(index),
+                    public_key,
+                })
+            })
+            .collect::<error::Result<Vec<Account>>>()?;
+        Ok(Self { network, accounts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Wallet, WalletState};
+    use crate::{Network, Verify};
+    use std::convert::TryFrom;
+
+    const SEED: &[u8] = b"not a very secret wallet seed";
+
+    #[test]
+    fn issue_and_restore() {
+        let mut wallet = Wallet::from_seed(Network::TestNet, SEED).expect("wallet");
+        let first = wallet.issue_account().expect("account").clone();
+        let second = wallet.issue_account().expect("account").clone();
+        assert_eq!((0, 1), (first.index, second.index));
+        assert_ne!(first.public_key, second.public_key);
+        // Issuing an existing index returns the same account
+        assert_eq!(&first, wallet.issue(0).expect("account"));
+
+        let signature = wallet.sign(1, b"hello world").expect("signature");
+        assert!(second.public_key.verify(b"hello world", &signature).is_ok());
+
+        let state = WalletState::try_from(&wallet.state().to_vec()[..]).expect("state");
+        assert_eq!(wallet.state(), &state);
+        let restored = Wallet::with_state(SEED, state.clone()).expect("restored");
+        assert_eq!(Some(&second), restored.find(&second.public_key));
+        assert!(Wallet::with_state(b"some other wallet seed", state).is_err());
+    }
+
+    #[test]
+    fn reject_bad_count() {
+        let mut bytes = Wallet::from_seed(Network::TestNet, SEED)
+            .expect("wallet")
+            .state()
+            .to_vec();
+        bytes[2..6].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(WalletState::try_from(&bytes[..]).is_err());
+    }
+}
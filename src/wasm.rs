@@ -0,0 +1,65 @@
+//! JavaScript bindings for web wallets, built with `wasm-bindgen` for the
+//! `wasm32-unknown-unknown` target.
+//!
+//! Keys are generated from the browser's `crypto.getRandomValues` through
+//! `getrandom`. Keypairs and public keys cross the boundary as their binary
+//! and b58 forms, and errors are raised as JavaScript `Error`s:
+//!
+//! ```js
+//! import { WasmKeypair, verify } from "helium-crypto";
+//!
+//! const keypair = WasmKeypair.generate("mainnet/ed25519");
+//! const signature = keypair.sign(message);
+//! verify(keypair.publicKey(), message, signature);
+//! ```
+use crate::{error, KeyTag, Keypair, PublicKey, Sign, Verify};
+use std::convert::TryFrom;
+use wasm_bindgen::prelude::*;
+
+fn js_error(err: error::Error) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// A keypair of any of the supported key types.
+#[wasm_bindgen]
+pub struct WasmKeypair(Keypair);
+
+#[wasm_bindgen]
+impl WasmKeypair {
+    /// Generate a keypair for the given key tag, like `mainnet/ecc_compact`.
+    pub fn generate(tag: &str) -> Result<WasmKeypair, JsError> {
+        let tag: KeyTag = tag.parse().map_err(js_error)?;
+        Keypair::generate_default(tag).map(Self).map_err(js_error)
+    }
+
+    /// Parse a keypair from its binary form.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmKeypair, JsError> {
+        Keypair::try_from(bytes).map(Self).map_err(js_error)
+    }
+
+    /// The binary form of the keypair.
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    /// The b58 address of the public key.
+    #[wasm_bindgen(js_name = publicKey)]
+    pub fn public_key(&self) -> String {
+        self.0.public_key().to_string()
+    }
+
+    /// Sign the given message.
+    pub fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, JsError> {
+        self.0.sign(msg).map_err(js_error)
+    }
+}
+
+/// Verify a signature over the given message by the public key with the given
+/// b58 address.
+#[wasm_bindgen]
+pub fn verify(public_key: &str, msg: &[u8], signature: &[u8]) -> Result<(), JsError> {
+    let public_key: PublicKey = public_key.parse().map_err(js_error)?;
+    public_key.verify(msg, signature).map_err(js_error)
+}